@@ -0,0 +1,123 @@
+// Shipment scanning benchmarks
+//
+// Run with `cargo bench`.
+//
+// - `datum_decoding`: `TrackingDatum::from_cbor` over 10k generated datums.
+// - `fetch_shipments`: one scan against a mock Blockfrost holding 500 and 5k UTxOs.
+// - `data_fetcher_run`: a full `DataFetcher::run` over 1k in-transit shipments
+//   (status lookups only, nothing is resolved or submitted).
+// - `status_fetch_concurrency`: 200 Shippo lookups issued sequentially versus
+//   through `buffer_unordered`. The buffered variant should win by roughly the
+//   buffer size while the provider answers in constant time; use it to sanity
+//   check the concurrency defaults before changing them.
+
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use futures::StreamExt;
+use std::hint::black_box;
+use std::sync::Arc;
+use tokio::runtime::Runtime;
+
+use shipping_oracle::blockchain::CardanoClient;
+use shipping_oracle::fetcher::DataFetcher;
+use shipping_oracle::models::TrackingDatum;
+use shipping_oracle::shipment::ShipmentClient;
+use shipping_oracle::testing::{self, MockProviders};
+
+const DATUM_CORPUS_SIZE: usize = 10_000;
+const HISTORY_SIZES: [usize; 2] = [500, 5_000];
+const RUN_SHIPMENTS: usize = 1_000;
+const STATUS_LOOKUPS: usize = 200;
+const BUFFER_SIZES: [usize; 2] = [4, 16];
+
+fn datum_decoding(c: &mut Criterion) {
+    let corpus = testing::datum_corpus(DATUM_CORPUS_SIZE);
+
+    let mut group = c.benchmark_group("datum_decoding");
+    group.throughput(Throughput::Elements(corpus.len() as u64));
+    group.bench_function("from_cbor", |b| {
+        b.iter(|| {
+            for datum in &corpus {
+                let _ = black_box(TrackingDatum::from_cbor(black_box(datum)));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn fetch_shipments(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+
+    let mut group = c.benchmark_group("fetch_shipments");
+    group.sample_size(20);
+    for size in HISTORY_SIZES {
+        let providers = rt.block_on(MockProviders::start(size, "TRANSIT"));
+        let client = CardanoClient::new(providers.config()).unwrap();
+
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &client, |b, client| {
+            b.to_async(&rt).iter(|| async { client.fetch_shipments().await.unwrap() })
+        });
+    }
+    group.finish();
+}
+
+fn data_fetcher_run(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let providers = rt.block_on(MockProviders::start(RUN_SHIPMENTS, "TRANSIT"));
+
+    let fetcher = DataFetcher::new(
+        Arc::new(CardanoClient::new(providers.config()).unwrap()),
+        Arc::new(ShipmentClient::new(providers.config()).unwrap()),
+    );
+
+    let mut group = c.benchmark_group("data_fetcher_run");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(RUN_SHIPMENTS as u64));
+    group.bench_function(BenchmarkId::from_parameter(RUN_SHIPMENTS), |b| {
+        b.to_async(&rt).iter(|| async { fetcher.run().await.unwrap() })
+    });
+    group.finish();
+}
+
+fn status_fetch_concurrency(c: &mut Criterion) {
+    let rt = Runtime::new().unwrap();
+    let providers = rt.block_on(MockProviders::start(0, "TRANSIT"));
+    let client = ShipmentClient::new(providers.config()).unwrap();
+    let tracking_numbers: Vec<String> = (0..STATUS_LOOKUPS).map(testing::tracking_number).collect();
+
+    let mut group = c.benchmark_group("status_fetch_concurrency");
+    group.sample_size(20);
+    group.throughput(Throughput::Elements(STATUS_LOOKUPS as u64));
+
+    group.bench_function("sequential", |b| {
+        b.to_async(&rt).iter(|| async {
+            for tracking_number in &tracking_numbers {
+                client.fetch_shipment_status("usps", tracking_number).await.unwrap();
+            }
+        })
+    });
+
+    for buffer in BUFFER_SIZES {
+        group.bench_with_input(BenchmarkId::new("buffered", buffer), &buffer, |b, &buffer| {
+            b.to_async(&rt).iter(|| async {
+                futures::stream::iter(&tracking_numbers)
+                    .map(|tracking_number| client.fetch_shipment_status("usps", tracking_number))
+                    .buffer_unordered(buffer)
+                    .for_each(|status| async move {
+                        status.unwrap();
+                    })
+                    .await
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    datum_decoding,
+    fetch_shipments,
+    data_fetcher_run,
+    status_fetch_concurrency
+);
+criterion_main!(benches);
@@ -0,0 +1,26 @@
+use std::path::Path;
+
+#[allow(dead_code)]
+#[path = "src/protocol.rs"]
+mod protocol;
+
+/// Protocol the bindings in `src/tx3.rs` are generated from (`trix codegen` in `tx3/`)
+const PROTOCOL: &str = "../tx3/main.tx3";
+const BINDINGS: &str = "src/tx3.rs";
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", PROTOCOL);
+    println!("cargo:rerun-if-changed={}", BINDINGS);
+
+    // Packaged without the protocol directory; nothing to compare against
+    if !Path::new(PROTOCOL).exists() {
+        println!("cargo:warning={} not found, skipping the tx3 bindings check", PROTOCOL);
+        return;
+    }
+
+    let protocol = std::fs::read_to_string(PROTOCOL).expect("protocol file is readable");
+    let bindings = std::fs::read_to_string(BINDINGS).expect("bindings file is readable");
+    if let Err(e) = protocol::check_bindings(&protocol, &bindings) {
+        panic!("{}\nRegenerate {} by running `trix codegen` in tx3/", e, BINDINGS);
+    }
+}
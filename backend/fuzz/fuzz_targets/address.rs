@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pallas::ledger::addresses::Address;
+
+fuzz_target!(|data: &[u8]| {
+    // Outbox addresses are decoded from raw datum bytes
+    if let Ok(address) = Address::from_bytes(data) {
+        let _ = address.to_bech32();
+    }
+
+    if let Ok(text) = std::str::from_utf8(data) {
+        let _ = Address::from_bech32(text);
+    }
+});
@@ -0,0 +1,14 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use shipping_oracle::models::TrackingDatum;
+
+fuzz_target!(|data: &[u8]| {
+    // Well-formed hex reaches the CBOR decoder and field extraction
+    let _ = TrackingDatum::from_cbor(&hex::encode(data));
+
+    // Arbitrary text exercises the hex and length guards
+    if let Ok(text) = std::str::from_utf8(data) {
+        let _ = TrackingDatum::from_cbor(text);
+    }
+});
@@ -0,0 +1,561 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use hyper::header::{AUTHORIZATION, CONTENT_TYPE, HeaderValue, WWW_AUTHENTICATE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Serialize;
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::Arc;
+
+use crate::debug_events::{DebugEvent, EventBuffer};
+use crate::decisions::DEFAULT_PAGE_SIZE;
+use crate::fetcher::{LastRun, RunStats};
+use crate::heartbeat::PingStatus;
+use crate::models::TrackingUTxO;
+use crate::redact::Secret;
+use crate::run_report::RunReport;
+use crate::scheduler::{Pipeline, PreviousRunActive, trigger_fetch_job};
+use crate::state::ShipmentState;
+
+/// HTTP API for operators, next to the scheduler (`ADMIN_ADDR`)
+///
+/// Every request must carry `Authorization: Bearer <ADMIN_TOKEN>`, where each
+/// pipeline answers to the `ADMIN_TOKEN` of its own configuration. Routes:
+/// - `GET /shipments`: tracking UTxOs of the latest scan with their carrier status and backoff
+/// - `GET /merchants/{address}/shipments`: the same, for the merchant with that outbox address
+/// - `POST /run`: run the pipeline now, `409` while a run is in flight
+/// - `GET /runs`: the last `RECENT_RUNS` runs, aborted ones included, newest first
+/// - `GET /runs/latest`: report of the latest completed run
+/// - `GET /status`: scheduler state, latest runs, heartbeat, Shippo quota and fees in one document
+/// - `GET /debug/events`: the latest oracle events and logged warnings and errors, newest first
+///
+/// The lists are paged with `?offset=` and `?limit=`. Only `POST /run` calls a
+/// provider; the other routes answer from memory and the state database. When the token opens several
+/// pipelines, `?tenant=<name>` picks one. `?format=json` is the only format.
+pub struct AdminServer {
+    listener: TcpListener,
+    state: AdminState,
+}
+
+struct AdminState {
+    /// Every pipeline served, with the token opening it
+    pipelines: Vec<(Arc<Pipeline>, Secret<String>)>,
+    /// Events served by `GET /debug/events`; without them the route is a `404`
+    debug_events: Option<EventBuffer>,
+}
+
+/// Default `?limit=` of `GET /runs`
+const DEFAULT_RUNS_LIMIT: usize = 20;
+/// Default `?limit=` of `GET /debug/events`
+const DEFAULT_DEBUG_EVENTS_LIMIT: usize = 200;
+
+/// One page of `GET /shipments`, in scan order
+#[derive(Debug, Clone, Serialize)]
+pub struct ShipmentPage {
+    /// When the latest run scanned the oracle address; `None` before any run
+    pub scanned_at: Option<DateTime<Utc>>,
+    /// Shipments of the scan across every page
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub shipments: Vec<OpenShipment>,
+}
+
+/// An open tracking UTxO in `GET /shipments`, serialized as its `TrackingUTxO` plus what the oracle knows of it
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenShipment {
+    #[serde(flatten)]
+    pub shipment: TrackingUTxO,
+    /// What the latest run saw for the shipment; `None` when no run looked at it yet
+    pub last_status: Option<LastStatus>,
+    /// Failed attempts in a row; always `0` without a state store
+    pub failure_count: u32,
+    /// Before this time the shipment is not retried
+    pub next_attempt_at: Option<DateTime<Utc>>,
+    /// Whether the shipment failed too often to be retried
+    pub dead: bool,
+}
+
+/// Carrier status of a shipment as of the latest run
+#[derive(Debug, Clone, Serialize)]
+pub struct LastStatus {
+    pub run_id: String,
+    pub checked_at: DateTime<Utc>,
+    pub fetched_status: Option<String>,
+    pub status_details: Option<String>,
+    pub derived_status: Option<String>,
+}
+
+/// One page of `GET /runs`, newest first
+#[derive(Debug, Clone, Serialize)]
+pub struct RunPage {
+    /// Runs kept across every page
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub runs: Vec<RecentRun>,
+}
+
+/// A finished run in `GET /runs`; `GET /runs/latest` has the shipments of the latest completed one
+#[derive(Debug, Clone, Serialize)]
+pub struct RecentRun {
+    pub run_id: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    /// The run completed with no failed shipment
+    pub succeeded: bool,
+    /// Run counters; `None` when the run aborted
+    pub stats: Option<RunStats>,
+    /// Why the run aborted
+    pub error: Option<String>,
+}
+
+impl From<LastRun> for RecentRun {
+    fn from(run: LastRun) -> Self {
+        let (stats, error) = match run.result {
+            Ok(stats) => (Some(stats), None),
+            Err(e) => (None, Some(e)),
+        };
+        Self {
+            run_id: run.run_id,
+            started_at: run.started_at,
+            finished_at: run.finished_at,
+            succeeded: stats.is_some_and(|stats| stats.failed == 0),
+            stats,
+            error,
+        }
+    }
+}
+
+impl From<RunReport> for RecentRun {
+    fn from(report: RunReport) -> Self {
+        Self {
+            run_id: report.run_id,
+            started_at: report.started_at,
+            finished_at: report.finished_at,
+            succeeded: report.stats.failed == 0,
+            stats: Some(report.stats),
+            error: None,
+        }
+    }
+}
+
+/// `GET /status` of a pipeline
+///
+/// Every field is read from memory or the state database, so the route can be
+/// polled as often as needed. Fields are only ever added to this document.
+#[derive(Debug, Clone, Serialize)]
+pub struct PipelineStatus {
+    pub tenant: Option<String>,
+    pub scheduler: SchedulerStatus,
+    /// Latest finished run, aborted or not
+    pub last_run: Option<RecentRun>,
+    /// Latest run that completed, the one `GET /runs/latest` reports
+    pub last_completed_run: Option<RecentRun>,
+    /// Close transactions submitted and not seen confirmed yet
+    pub pending_submissions: usize,
+    /// Outcome of the latest heartbeat ping; `None` without `HEARTBEAT_URL` or before the first run
+    pub heartbeat: Option<PingStatus>,
+    /// Shippo tracking calls of the billing period; `None` without a state database
+    pub shippo_quota: Option<QuotaStatus>,
+    /// Lovelace paid for every journalled closure; `None` without a state database
+    pub fees: Option<FeeStatus>,
+}
+
+/// One page of `GET /debug/events`, newest first
+#[derive(Debug, Clone, Serialize)]
+pub struct DebugEventPage {
+    /// Events the token may see across every page
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub events: Vec<DebugEvent>,
+}
+
+/// Where the pipeline stands in its schedule
+#[derive(Debug, Clone, Serialize)]
+pub struct SchedulerStatus {
+    pub cron_schedule: String,
+    /// A run is in flight
+    pub running: bool,
+    /// Next time `CRON_SCHEDULE` fires
+    pub next_run_at: Option<DateTime<Utc>>,
+    /// Whether closures may be submitted now; `None` without `SUBMIT_WINDOW`
+    pub submit_window_open: Option<bool>,
+}
+
+/// Shippo tracking calls of the current billing period against `SHIPPO_MONTHLY_BUDGET`
+#[derive(Debug, Clone, Serialize)]
+pub struct QuotaStatus {
+    /// Billing month, `YYYY-MM`
+    pub period: String,
+    pub calls: u64,
+    pub budget: Option<u64>,
+    /// The budget is reached, so only shipments near a final status are polled
+    pub spent: bool,
+}
+
+/// Fees of the closures journalled in the state database, as `fees` reports them
+#[derive(Debug, Clone, Serialize)]
+pub struct FeeStatus {
+    pub closures: usize,
+    pub total_lovelace: u64,
+    /// Closures journalled before fees were recorded; not part of the total
+    pub unknown_fee: usize,
+}
+
+/// `?offset=` and `?limit=` of a listing
+#[derive(Debug, Clone, Copy)]
+struct PageQuery {
+    offset: usize,
+    limit: usize,
+}
+
+impl PageQuery {
+    /// The page the request asks for, `limit` defaulting to `default_limit`; `Err` is the message of a `400`
+    fn from_request(request: &Request<Body>, default_limit: usize) -> Result<Self, String> {
+        let number = |name: &str, default: usize| match query_param(request, name) {
+            Some(value) => value.parse().map_err(|_| format!("?{}= must be a non-negative integer, not {:?}", name, value)),
+            None => Ok(default),
+        };
+        Ok(Self { offset: number("offset", 0)?, limit: number("limit", default_limit)? })
+    }
+
+    fn select<T>(&self, items: impl IntoIterator<Item = T>) -> Vec<T> {
+        items.into_iter().skip(self.offset).take(self.limit).collect()
+    }
+}
+
+impl AdminServer {
+    /// Serve `pipelines`, each to requests carrying its token
+    pub fn bind(addr: SocketAddr, pipelines: Vec<(Arc<Pipeline>, Secret<String>)>) -> Result<Self> {
+        let listener = TcpListener::bind(addr).with_context(|| format!("Failed to bind the admin server to {}", addr))?;
+        listener.set_nonblocking(true).context("Failed to configure the admin listener")?;
+
+        Ok(Self { listener, state: AdminState { pipelines, debug_events: None } })
+    }
+
+    /// Serve the events of `buffer` at `GET /debug/events`
+    pub fn with_debug_events(mut self, buffer: EventBuffer) -> Self {
+        self.state.debug_events = Some(buffer);
+        self
+    }
+
+    /// Address the server listens on, e.g. to learn the port picked for `127.0.0.1:0`
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener.local_addr().context("Failed to read the admin server address")
+    }
+
+    /// Serve requests until `shutdown` resolves, then finish the ones in flight
+    pub async fn serve_until(self, shutdown: impl Future<Output = ()>) -> Result<()> {
+        let state = Arc::new(self.state);
+        let make_service = make_service_fn(move |_connection| {
+            let state = state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let state = state.clone();
+                    async move { Ok::<_, Infallible>(respond(&state, request).await) }
+                }))
+            }
+        });
+
+        Server::from_tcp(self.listener)
+            .context("Failed to start the admin server")?
+            .serve(make_service)
+            .with_graceful_shutdown(shutdown)
+            .await
+            .context("Admin server failed")
+    }
+}
+
+/// What a request asks for
+enum Route {
+    /// `GET /shipments`, or `GET /merchants/{address}/shipments` for the shipments of one outbox address
+    Shipments(Option<String>),
+    Run,
+    Runs,
+    LatestRun,
+    Status,
+    DebugEvents,
+}
+
+impl Route {
+    fn of(request: &Request<Body>) -> Option<Self> {
+        let path = request.uri().path();
+        let route = match (request.method(), path) {
+            (&Method::GET, "/shipments") => Route::Shipments(None),
+            (&Method::POST, "/run") => Route::Run,
+            (&Method::GET, "/runs") => Route::Runs,
+            (&Method::GET, "/runs/latest") => Route::LatestRun,
+            (&Method::GET, "/status") => Route::Status,
+            (&Method::GET, "/debug/events") => Route::DebugEvents,
+            (&Method::GET, _) => {
+                let outbox = path.strip_prefix("/merchants/")?.strip_suffix("/shipments")?;
+                if outbox.is_empty() || outbox.contains('/') {
+                    return None;
+                }
+                Route::Shipments(Some(percent_decode(outbox)?))
+            }
+            _ => return None,
+        };
+
+        Some(route)
+    }
+}
+
+async fn respond(state: &AdminState, request: Request<Body>) -> Response<Body> {
+    // Tenants the token opens; the others are answered as if they did not exist
+    let granted: Vec<&Arc<Pipeline>> = state
+        .pipelines
+        .iter()
+        .filter(|(_, token)| authorized(token, &request))
+        .map(|(pipeline, _)| pipeline)
+        .collect();
+    if granted.is_empty() {
+        let mut response = status_response(StatusCode::UNAUTHORIZED, "Unauthorized");
+        response.headers_mut().insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        return response;
+    }
+
+    let Some(route) = Route::of(&request) else {
+        return status_response(StatusCode::NOT_FOUND, "Not found");
+    };
+    if let Some(format) = query_param(&request, "format")
+        && format != "json"
+    {
+        return status_response(StatusCode::BAD_REQUEST, &format!("Unsupported format {:?}, only json is served", format));
+    }
+
+    let tenant = query_param(&request, "tenant");
+    if let Route::DebugEvents = route {
+        return debug_events(state, &granted, tenant.as_deref(), &request);
+    }
+    let pipeline = match (tenant.as_deref(), granted.as_slice()) {
+        (None, [pipeline]) => *pipeline,
+        (None, _) => return status_response(StatusCode::BAD_REQUEST, "?tenant= is required with several tenants"),
+        (Some(name), granted) => {
+            match granted.iter().find(|pipeline| pipeline.data_fetcher().tenant() == Some(name)) {
+                Some(pipeline) => *pipeline,
+                None => return status_response(StatusCode::NOT_FOUND, &format!("No tenant named {}", name)),
+            }
+        }
+    };
+
+    match route {
+        Route::Shipments(outbox) => {
+            let page = match PageQuery::from_request(&request, DEFAULT_PAGE_SIZE) {
+                Ok(page) => page,
+                Err(message) => return status_response(StatusCode::BAD_REQUEST, &message),
+            };
+            match open_shipments(pipeline, page, outbox.as_deref()).await {
+                Ok(shipments) => json_response(StatusCode::OK, &shipments),
+                Err(e) => status_response(StatusCode::INTERNAL_SERVER_ERROR, &format!("{:#}", e)),
+            }
+        }
+        Route::Run => match trigger_fetch_job(pipeline).await {
+            Ok(report) => json_response(StatusCode::OK, &report),
+            Err(e) if e.is::<PreviousRunActive>() => status_response(StatusCode::CONFLICT, &e.to_string()),
+            Err(e) => status_response(StatusCode::INTERNAL_SERVER_ERROR, &format!("{:#}", e)),
+        },
+        Route::Runs => match PageQuery::from_request(&request, DEFAULT_RUNS_LIMIT) {
+            Ok(page) => json_response(StatusCode::OK, &recent_runs(pipeline, page)),
+            Err(message) => status_response(StatusCode::BAD_REQUEST, &message),
+        },
+        Route::Status => match pipeline_status(pipeline).await {
+            Ok(status) => json_response(StatusCode::OK, &status),
+            Err(e) => status_response(StatusCode::INTERNAL_SERVER_ERROR, &format!("{:#}", e)),
+        },
+        Route::LatestRun => match pipeline.data_fetcher().last_report() {
+            Some(report) => json_response(StatusCode::OK, &report),
+            None => status_response(StatusCode::NOT_FOUND, "No run has completed yet"),
+        },
+    }
+}
+
+/// Page of the tracking UTxOs the latest run scanned, those into `outbox` if given, with what the runs and the state store know of each
+async fn open_shipments(pipeline: &Pipeline, page: PageQuery, outbox: Option<&str>) -> Result<ShipmentPage> {
+    let fetcher = pipeline.data_fetcher();
+    let (scanned_at, mut shipments) = match fetcher.last_scan() {
+        Some(scan) => (Some(scan.scanned_at), scan.shipments),
+        None => (None, Vec::new()),
+    };
+    if let Some(outbox) = outbox {
+        shipments.retain(|shipment| shipment.datum.outbox_address.to_string() == outbox);
+    }
+    let total = shipments.len();
+    let last_run = fetcher.last_run();
+
+    let last_status = |utxo_ref: &str| {
+        let run = last_run.as_ref()?;
+        let report = run.shipments.iter().find(|shipment| shipment.utxo_ref == utxo_ref)?;
+        Some(LastStatus {
+            run_id: run.run_id.clone(),
+            checked_at: run.finished_at,
+            fetched_status: report.fetched_status.clone(),
+            status_details: report.status_details.clone(),
+            derived_status: report.derived_status.clone(),
+        })
+    };
+
+    let mut open = Vec::new();
+    for shipment in page.select(shipments) {
+        let utxo_ref = shipment.utxo_ref.to_string();
+        let state = match fetcher.state() {
+            Some(state) => state.shipment(&utxo_ref).await?,
+            None => None,
+        }
+        .unwrap_or_else(|| ShipmentState::new(&utxo_ref));
+        open.push(OpenShipment {
+            last_status: last_status(&utxo_ref),
+            failure_count: state.failure_count,
+            next_attempt_at: state.next_attempt_at.and_then(|at| DateTime::from_timestamp(at as i64, 0)),
+            dead: state.dead,
+            shipment,
+        });
+    }
+
+    Ok(ShipmentPage { scanned_at, total, offset: page.offset, limit: page.limit, shipments: open })
+}
+
+/// Page of the runs the fetcher kept, newest first
+fn recent_runs(pipeline: &Pipeline, page: PageQuery) -> RunPage {
+    let runs = pipeline.data_fetcher().recent_runs();
+    RunPage {
+        total: runs.len(),
+        offset: page.offset,
+        limit: page.limit,
+        runs: page.select(runs.into_iter().map(RecentRun::from)),
+    }
+}
+
+/// `GET /debug/events`: the buffered events of the tenants the token opens, or of `tenant` only
+///
+/// Events of no tenant come from the whole process, so only a token opening
+/// every served pipeline sees them.
+fn debug_events(state: &AdminState, granted: &[&Arc<Pipeline>], tenant: Option<&str>, request: &Request<Body>) -> Response<Body> {
+    let Some(buffer) = &state.debug_events else {
+        return status_response(StatusCode::NOT_FOUND, "Debug events are not recorded");
+    };
+    let page = match PageQuery::from_request(request, DEFAULT_DEBUG_EVENTS_LIMIT) {
+        Ok(page) => page,
+        Err(message) => return status_response(StatusCode::BAD_REQUEST, &message),
+    };
+
+    let tenants: Vec<Option<&str>> = granted.iter().map(|pipeline| pipeline.data_fetcher().tenant()).collect();
+    if let Some(name) = tenant
+        && !tenants.contains(&Some(name))
+    {
+        return status_response(StatusCode::NOT_FOUND, &format!("No tenant named {}", name));
+    }
+    let process_wide = tenant.is_none() && granted.len() == state.pipelines.len();
+    let visible = |event: &DebugEvent| match (event.tenant.as_deref(), tenant) {
+        (Some(of), Some(name)) => of == name,
+        (Some(of), None) => tenants.contains(&Some(of)),
+        (None, _) => process_wide,
+    };
+
+    let events: Vec<DebugEvent> = buffer.recent().into_iter().filter(visible).collect();
+    json_response(
+        StatusCode::OK,
+        &DebugEventPage { total: events.len(), offset: page.offset, limit: page.limit, events: page.select(events) },
+    )
+}
+
+/// What `pipeline` knows of itself, without calling any provider
+async fn pipeline_status(pipeline: &Pipeline) -> Result<PipelineStatus> {
+    let fetcher = pipeline.data_fetcher();
+    let shippo_quota = fetcher.shippo_usage().await?.map(|usage| QuotaStatus {
+        spent: usage.budget.is_some_and(|budget| usage.calls >= budget),
+        period: usage.period,
+        calls: usage.calls,
+        budget: usage.budget,
+    });
+    let fees = fetcher.fees().await?.map(|report| FeeStatus {
+        closures: report.closures,
+        total_lovelace: report.total_lovelace,
+        unknown_fee: report.unknown_fee,
+    });
+
+    Ok(PipelineStatus {
+        tenant: fetcher.tenant().map(str::to_string),
+        scheduler: SchedulerStatus {
+            cron_schedule: pipeline.cron_schedule().to_string(),
+            running: pipeline.is_running(),
+            next_run_at: pipeline.next_run_at(),
+            submit_window_open: fetcher.submit_window_open(),
+        },
+        last_run: fetcher.last_run().map(RecentRun::from),
+        last_completed_run: fetcher.last_report().map(RecentRun::from),
+        pending_submissions: fetcher.pending_submissions().len(),
+        heartbeat: pipeline.heartbeat_status(),
+        shippo_quota,
+        fees,
+    })
+}
+
+/// Value of the `name` query parameter, percent-decoded
+fn query_param(request: &Request<Body>, name: &str) -> Option<String> {
+    request.uri().query()?.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        (percent_decode(key).as_deref() == Some(name)).then(|| percent_decode(value)).flatten()
+    })
+}
+
+/// `application/x-www-form-urlencoded` decoding; `None` when the result is not UTF-8
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let escaped = bytes
+            .get(pos + 1..pos + 3)
+            .filter(|hex| bytes[pos] == b'%' && hex.iter().all(u8::is_ascii_hexdigit))
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match (escaped, bytes[pos]) {
+            (Some(byte), _) => {
+                decoded.push(byte);
+                pos += 3;
+                continue;
+            }
+            (None, b'+') => decoded.push(b' '),
+            (None, byte) => decoded.push(byte),
+        }
+        pos += 1;
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+/// Whether the request carries `Authorization: Bearer <token>`, compared in constant time
+fn authorized(token: &Secret<String>, request: &Request<Body>) -> bool {
+    let Some(given) = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+    else {
+        return false;
+    };
+
+    let (given, expected) = (given.trim().as_bytes(), token.expose().as_bytes());
+    given.len() == expected.len() && given.iter().zip(expected).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+fn json_response(status: StatusCode, value: &impl Serialize) -> Response<Body> {
+    match serde_json::to_string(value) {
+        Ok(body) => Response::builder()
+            .status(status)
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(body))
+            .unwrap_or_else(|_| status_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to build the response")),
+        Err(e) => status_response(StatusCode::INTERNAL_SERVER_ERROR, &format!("Failed to serialize the response: {}", e)),
+    }
+}
+
+fn status_response(status: StatusCode, message: &str) -> Response<Body> {
+    let mut response = Response::new(Body::from(message.to_string()));
+    *response.status_mut() = status;
+    response
+}
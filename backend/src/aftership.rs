@@ -0,0 +1,179 @@
+use anyhow::{Context, Result, anyhow, bail};
+use reqwest::{Client, Url};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use tracing::{Instrument, debug};
+
+use crate::config::Config;
+use crate::logging;
+use crate::metrics::Metrics;
+use crate::models::TrackingStatus;
+use crate::redact::{redact, register_config_secrets};
+use crate::tracking_provider::{ProviderKind, TrackingProvider};
+
+/// Shippo carrier tokens whose AfterShip slug is not the token with `_` turned into `-`
+const SLUGS: &[(&str, &str)] = &[
+    ("dhl_express", "dhl"),
+    ("dhl_ecommerce", "dhl-global-mail"),
+    ("deutsche_post", "deutsch-post"),
+    ("parcelforce", "parcel-force"),
+];
+
+/// AfterShip `GET /v4/trackings/{slug}/{tracking_number}` response (partial)
+#[derive(Debug, Deserialize)]
+struct TrackingResponse {
+    data: TrackingData,
+}
+
+#[derive(Debug, Deserialize)]
+struct TrackingData {
+    tracking: Tracking,
+}
+
+#[derive(Debug, Deserialize)]
+struct Tracking {
+    tag: String,
+    #[serde(default)]
+    subtag_message: Option<String>,
+    /// Oldest first
+    #[serde(default)]
+    checkpoints: Vec<Checkpoint>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Checkpoint {
+    #[serde(default)]
+    checkpoint_time: Option<String>,
+}
+
+/// AfterShip tag in Shippo's vocabulary, so `StatusMapping` reads both alike
+///
+/// `Expired` only means AfterShip stopped tracking, so it is not treated as final.
+pub fn shippo_status(tag: &str) -> &'static str {
+    match tag {
+        "InfoReceived" | "Pending" => "PRE_TRANSIT",
+        "InTransit" | "OutForDelivery" | "AvailableForPickup" | "AttemptFail" => "TRANSIT",
+        "Delivered" => "DELIVERED",
+        "Exception" => "FAILURE",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Parse `AFTERSHIP_SLUGS`: comma-separated `carrier=slug` pairs, e.g. `lasership=lasership-api`
+pub fn parse_slugs(value: &str) -> Result<BTreeMap<String, String>> {
+    let mut slugs = BTreeMap::new();
+    for pair in value.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+        let Some((carrier, slug)) = pair.split_once('=') else {
+            bail!("expected carrier=slug, got '{}'", pair);
+        };
+        let (carrier, slug) = (carrier.trim().to_lowercase(), slug.trim());
+        if carrier.is_empty() || slug.is_empty() {
+            bail!("expected carrier=slug, got '{}'", pair);
+        }
+        slugs.insert(carrier, slug.to_string());
+    }
+
+    Ok(slugs)
+}
+
+/// AfterShip slug of a datum `carrier`: `overrides` first, then the built-in table,
+/// else the lowercase carrier with `_` turned into `-`
+pub fn slug(carrier: &str, overrides: &BTreeMap<String, String>) -> String {
+    let carrier = carrier.trim().to_lowercase();
+    if let Some(slug) = overrides.get(&carrier) {
+        return slug.clone();
+    }
+
+    match SLUGS.iter().find(|(token, _)| *token == carrier) {
+        Some((_, slug)) => slug.to_string(),
+        None => carrier.replace('_', "-"),
+    }
+}
+
+/// Tracks shipments through AfterShip's `GET /v4/trackings/{slug}/{tracking_number}` with `AFTERSHIP_API_KEY`
+pub struct AfterShipProvider {
+    config: Config,
+    http_client: Client,
+    metrics: Metrics,
+}
+
+impl AfterShipProvider {
+    pub fn new(config: Config) -> Result<Self> {
+        if config.aftership_api_key.is_none() {
+            bail!("AfterShip tracking needs AFTERSHIP_API_KEY");
+        }
+        register_config_secrets(&config);
+
+        let http_client = Client::builder()
+            .timeout(std::time::Duration::from_secs(30))
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        Ok(Self { config, http_client, metrics: Metrics::new() })
+    }
+
+    /// Count fetched statuses and AfterShip errors in `metrics`
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    async fn request_status(&self, carrier: &str, tracking_number: &str) -> Result<TrackingStatus> {
+        let api_key = self.config.aftership_api_key.as_ref().map(|key| key.expose().as_str()).unwrap_or_default();
+        let slug = slug(carrier, &self.config.aftership_slugs);
+        let mut url = Url::parse(&self.config.aftership_url)
+            .with_context(|| format!("Invalid AFTERSHIP_URL '{}'", self.config.aftership_url))?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow!("AFTERSHIP_URL '{}' cannot have a path", self.config.aftership_url))?
+            .pop_if_empty()
+            .extend(["v4", "trackings", &slug, tracking_number]);
+
+        let span = logging::http_span("aftership", &reqwest::Method::GET, url.as_str());
+        let response = self.http_client
+            .get(url)
+            .header("aftership-api-key", api_key)
+            .send()
+            .instrument(span)
+            .await
+            .map_err(|e| anyhow!("Failed to send request to AfterShip: {}", redact(&e.to_string())))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            bail!("AfterShip query failed for {} {} (status {}): {}", slug, tracking_number, status, redact(&body));
+        }
+
+        let tracking = response
+            .json::<TrackingResponse>()
+            .await
+            .context("Failed to parse AfterShip tracking response")?
+            .data
+            .tracking;
+
+        debug!(tag = %tracking.tag, "AfterShip answered");
+        Ok(TrackingStatus {
+            status: shippo_status(&tracking.tag).into(),
+            status_details: tracking.subtag_message.unwrap_or_else(|| tracking.tag.clone()),
+            status_date: tracking.checkpoints.last().and_then(|checkpoint| checkpoint.checkpoint_time.clone()),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl TrackingProvider for AfterShipProvider {
+    async fn fetch_status(&self, carrier: &str, tracking_number: &str) -> Result<TrackingStatus> {
+        debug!(carrier, tracking_number, "Fetching tracking status from AfterShip");
+
+        let result = self.request_status(carrier, tracking_number).await;
+        match &result {
+            Ok(_) => self.metrics.status_fetched(carrier),
+            Err(_) => self.metrics.request_error("aftership"),
+        }
+
+        result
+    }
+
+    fn provider(&self, _carrier: &str) -> ProviderKind {
+        ProviderKind::AfterShip
+    }
+}
@@ -0,0 +1,20 @@
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// Delay before retry `attempt` (counting from 0): `base * 2^attempt` plus up to half as much again
+pub fn exponential(base: Duration, attempt: u32) -> Duration {
+    let delay = base.saturating_mul(2u32.saturating_pow(attempt));
+    let jitter_ms = (delay.as_millis() as u64 / 2).saturating_add(1);
+    delay + Duration::from_millis(RandomState::new().build_hasher().finish() % jitter_ms)
+}
+
+/// How long a `Retry-After` header asks to wait, when it is given in seconds
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+}
@@ -1,118 +1,370 @@
-use anyhow::{Context, Result, anyhow};
-use ed25519_dalek::{Signer, SigningKey};
-use pallas::codec::{
-    minicbor,
-    utils::{Bytes, NonEmptySet, KeepRaw},
-};
+use anyhow::{Context, Result, anyhow, bail};
+use chrono::{DateTime, Utc};
 use pallas::ledger::{
-    addresses::Address,
-    primitives::{PlutusData, conway::VKeyWitness},
-    traverse::MultiEraTx,
+    addresses::{Address, Network},
+    primitives::{BigInt, PlutusData},
 };
-use reqwest::Client as HttpClient;
+use reqwest::{Client as HttpClient, RequestBuilder, Response, StatusCode};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use serde::Deserialize;
-use std::collections::HashMap;
+use serde::de::DeserializeOwned;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tracing::{Instrument, debug, warn};
 use tx3_sdk::trp::{ClientOptions, TxEnvelope};
 
-use crate::config::Config;
-use crate::models::{TrackingUTxO, TrackingDatum};
-use crate::submitter::{BlockfrostSubmitter, TxSubmitter};
-use crate::tx3::{Client as Tx3Client, CloseShipmentParams};
+use crate::backoff;
+use crate::config::{Config, Network as CardanoNetwork};
+use crate::datum_codec::{self, CodecRegistry, DatumCodec, DatumRejected, DecodeError, PositionalCodec};
+use crate::decisions::{Confirmation, Decision, DecisionSource};
+use crate::indexer::{self, ChainIndexer};
+use crate::logging;
+use crate::metrics::Metrics;
+use crate::models::{DerivedStatus, ShipmentDatum, TrackingUTxO, TrackingDatum, TrackingNumber, UtxoRef};
+use crate::proxy;
+use crate::reconcile::{ReconcileEntry, ReconcileReport, ReconcileStatus};
+use crate::state::Submission;
+use crate::redact::{redact, register_config_secrets};
+use crate::signing::{self, SignedTx, TxSigner, envelope_fee, sign_envelope_with};
+use crate::submitter::{self, SubmitError, TxSubmitter, forbidden_hint};
+use crate::tx3::{Client as Tx3Client, CloseShipmentParams, TrackShipmentParams};
+use crate::validation::{CloseExpectation, validate_close_tx};
 
 #[derive(Debug, Deserialize)]
 struct BlockfrostUTxO {
     tx_hash: String,
-    output_index: u32,
     inline_datum: Option<String>,
 }
 
-impl TrackingDatum {
-    pub fn from_cbor(datum_bytes: &str) -> Option<TrackingDatum> {
-        let datum = minicbor::decode::<PlutusData>(
-            &hex::decode(datum_bytes)
-                .unwrap_or_default()
-        );
+/// A close-shipment transaction accepted by the submitter
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClosedShipment {
+    pub tx_hash: String,
+    /// Fee paid by the transaction, in lovelace
+    pub fee: u64,
+}
 
-        let mut carrier: Option<String> = None;
-        let mut tracking_number: Option<String> = None;
-        let mut outbox_address: Option<Address> = None;
+/// A close-shipment transaction resolved, validated and signed, not submitted yet
+#[derive(Debug, Clone)]
+pub struct PreparedClose {
+    pub params: CloseShipmentParams,
+    pub signed: SignedTx,
+    /// Fee paid by the transaction, in lovelace
+    pub fee: u64,
+}
 
-        if datum.is_ok() {
-            if let PlutusData::Constr(constr) = datum.unwrap() {
-                let carrier_field = constr.fields.get(0);
-                if let Some(PlutusData::BoundedBytes(carrier_bytes)) = carrier_field {
-                    carrier = Some(String::from_utf8(carrier_bytes.to_vec()).unwrap_or_default());
-                }
+/// A resolved transaction was refused because its fee is above `MAX_FEE_LOVELACE`
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Transaction fee exceeds MAX_FEE_LOVELACE (fee {fee} lovelace, max {max_fee})")]
+pub struct FeeExceeded {
+    pub fee: u64,
+    pub max_fee: u64,
+}
 
-                let tracking_number_field = constr.fields.get(1);
-                if let Some(PlutusData::BoundedBytes(tracking_number_bytes)) = tracking_number_field {
-                    tracking_number = Some(String::from_utf8(tracking_number_bytes.to_vec()).unwrap_or_default());
-                }
+/// The tracking UTxO was spent by another transaction before the oracle closed it
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Tracking UTxO {utxo_ref} was already spent by {spent_by}")]
+pub struct Raced {
+    pub utxo_ref: String,
+    pub spent_by: String,
+}
 
-                let outbox_address_field = constr.fields.get(2);
-                if let Some(PlutusData::BoundedBytes(outbox_address_bytes)) = outbox_address_field {
-                    let address = Address::from_bytes(outbox_address_bytes);
-                    if address.is_ok() {
-                        outbox_address = Some(address.unwrap());
-                    }
-                }
+/// Blockfrost `/txs/{hash}` response (partial)
+#[derive(Debug, Deserialize)]
+struct BlockfrostTx {
+    block_height: u64,
+    /// Unix time of the block
+    block_time: Option<i64>,
+}
+
+/// Blockfrost `/genesis` response (partial)
+#[derive(Debug, Deserialize)]
+struct BlockfrostGenesis {
+    network_magic: u64,
+}
+
+/// Blockfrost `/txs/{hash}/utxos` response (partial)
+#[derive(Debug, Deserialize)]
+struct BlockfrostTxUtxos {
+    outputs: Vec<BlockfrostTxOutput>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockfrostTxOutput {
+    address: String,
+    #[serde(default)]
+    output_index: u32,
+    inline_datum: Option<String>,
+    reference_script_hash: Option<String>,
+    consumed_by_tx: Option<String>,
+}
+
+/// State of the `VALIDATOR_SCRIPT_REF` UTxO on-chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ValidatorScriptCheck {
+    /// Unspent, holding a reference script that matches `VALIDATOR_SCRIPT_HASH` when set
+    Present { script_hash: String },
+    /// No such transaction output
+    Missing,
+    /// The output exists but carries no reference script
+    NoReferenceScript,
+    /// The reference script is not the one `VALIDATOR_SCRIPT_HASH` names
+    HashMismatch { expected: String, actual: String },
+    /// The output was spent; `moved_to` is the address now holding the same script, if it was re-locked
+    Spent { spent_by: String, moved_to: Option<String> },
+}
+
+impl ValidatorScriptCheck {
+    pub fn is_present(&self) -> bool {
+        matches!(self, ValidatorScriptCheck::Present { .. })
+    }
+}
+
+impl fmt::Display for ValidatorScriptCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidatorScriptCheck::Present { script_hash } => write!(f, "reference script {} present", script_hash),
+            ValidatorScriptCheck::Missing => write!(f, "VALIDATOR_SCRIPT_REF does not exist on-chain"),
+            ValidatorScriptCheck::NoReferenceScript => write!(f, "VALIDATOR_SCRIPT_REF holds no reference script"),
+            ValidatorScriptCheck::HashMismatch { expected, actual } => write!(
+                f,
+                "VALIDATOR_SCRIPT_REF holds script {}, VALIDATOR_SCRIPT_HASH expects {}",
+                actual, expected
+            ),
+            ValidatorScriptCheck::Spent { spent_by, moved_to: Some(address) } => write!(
+                f,
+                "VALIDATOR_SCRIPT_REF was spent by {}; the script now sits at {}",
+                spent_by, address
+            ),
+            ValidatorScriptCheck::Spent { spent_by, moved_to: None } => {
+                write!(f, "VALIDATOR_SCRIPT_REF was spent by {}", spent_by)
             }
         }
+    }
+}
 
-        if !tracking_number.is_some() || !carrier.is_some() || !outbox_address.is_some() {
-            return None
+/// Whether Blockfrost serves the network `CARDANO_NETWORK` declares
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkCheck {
+    Matches,
+    Mismatch { expected: CardanoNetwork, network_magic: u64 },
+}
+
+impl fmt::Display for NetworkCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkCheck::Matches => write!(f, "Blockfrost serves the declared network"),
+            NetworkCheck::Mismatch { expected, network_magic } => write!(
+                f,
+                "BLOCKFROST_URL serves {} (network magic {}) but CARDANO_NETWORK is {}",
+                CardanoNetwork::from_magic(*network_magic).map_or("an unknown network", |network| network.as_str()),
+                network_magic,
+                expected.as_str()
+            ),
         }
+    }
+}
+
+impl TrackingDatum {
+    /// Decode a hex inline datum in the positional layout (see `datum_codec`)
+    ///
+    /// The error says why the datum was refused: bad hex, not a constructor,
+    /// a missing or mistyped field, invalid UTF-8 or an unparseable address.
+    pub fn from_cbor(datum_bytes: &str) -> Result<TrackingDatum, DecodeError> {
+        datum_codec::datum_bytes(datum_bytes).and_then(|bytes| PositionalCodec.decode(&bytes))
+    }
+
+    /// Encode as the positional inline datum `from_cbor` reads
+    pub fn to_cbor(&self) -> Vec<u8> {
+        PositionalCodec::encode(self)
+    }
+
+    /// `to_cbor` as hex, the form Blockfrost returns inline datums in
+    pub fn to_cbor_hex(&self) -> String {
+        hex::encode(self.to_cbor())
+    }
+}
+
+impl ShipmentDatum {
+    pub fn from_cbor(datum_bytes: &str) -> Option<ShipmentDatum> {
+        let bytes = datum_codec::datum_bytes(datum_bytes).ok()?;
+        let PlutusData::Constr(constr) = datum_codec::plutus_data(&bytes).ok()? else {
+            return None;
+        };
 
-        Some(TrackingDatum {
-            carrier: carrier.unwrap(),
-            tracking_number: tracking_number.unwrap(),
-            outbox_address: outbox_address.unwrap(),
+        let [
+            PlutusData::BoundedBytes(carrier),
+            PlutusData::BoundedBytes(tracking_number),
+            PlutusData::BoundedBytes(status),
+            PlutusData::BigInt(BigInt::Int(timestamp)),
+            PlutusData::BoundedBytes(oracle_pkh),
+        ] = constr.fields.as_slice()
+        else {
+            return None;
+        };
+
+        Some(ShipmentDatum {
+            carrier: non_empty_utf8(carrier)?,
+            tracking_number: TrackingNumber::from_datum_bytes(tracking_number)?,
+            status: non_empty_utf8(status)?,
+            timestamp: u64::try_from(i128::from(*timestamp)).ok()?,
+            oracle_pkh: hex::encode(oracle_pkh.as_slice()),
         })
     }
 }
 
+/// HTTP client for Blockfrost, through `HTTP_PROXY_BLOCKFROST` when set, sending
+/// `BLOCKFROST_PROJECT_ID` as the `project_id` header on every request
+pub fn blockfrost_http_client(config: &Config) -> Result<HttpClient> {
+    let mut builder = proxy::apply(
+        HttpClient::builder(),
+        config.http_proxy_blockfrost.as_ref().map(|proxy| proxy.expose().as_str()),
+        config.no_proxy.as_deref(),
+    )?;
+
+    if let Some(project_id) = &config.blockfrost_project_id {
+        let mut value = HeaderValue::from_str(project_id.expose().trim()).context("BLOCKFROST_PROJECT_ID is not a valid header value")?;
+        value.set_sensitive(true);
+        builder = builder.default_headers(HeaderMap::from_iter([(HeaderName::from_static("project_id"), value)]));
+    }
+
+    builder.build().context("Failed to create HTTP client")
+}
+
+/// Send a Blockfrost GET, retrying 429 and 5xx answers up to `BLOCKFROST_MAX_RETRIES` times
+///
+/// Each retry waits as long as `Retry-After` says, or else backs off
+/// exponentially up to `BLOCKFROST_MAX_BACKOFF_MS`. The last answer is
+/// returned whatever its status, for the caller to report.
+pub async fn blockfrost_send(config: &Config, request: RequestBuilder) -> Result<Response> {
+    let base_backoff = Duration::from_millis(config.blockfrost_base_backoff_ms);
+    let max_backoff = Duration::from_millis(config.blockfrost_max_backoff_ms);
+    let mut attempt = 0;
+    loop {
+        let (client, attempt_request) = request.try_clone().context("Blockfrost request cannot be retried")?.build_split();
+        let attempt_request = attempt_request.map_err(|e| anyhow!("Invalid Blockfrost request: {}", redact(&e.to_string())))?;
+        let span = logging::http_span("blockfrost", attempt_request.method(), attempt_request.url().as_str());
+        let response = client
+            .execute(attempt_request)
+            .instrument(span)
+            .await
+            .map_err(|e| anyhow!("Blockfrost query failed: {}", redact(&e.to_string())))?;
+
+        let status = response.status();
+        let transient = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+        if !transient || attempt >= config.blockfrost_max_retries {
+            return Ok(response);
+        }
+
+        let backoff = backoff::retry_after(response.headers())
+            .unwrap_or_else(|| backoff::exponential(base_backoff, attempt).min(max_backoff));
+        attempt += 1;
+        warn!(
+            %status,
+            attempt,
+            max_retries = config.blockfrost_max_retries,
+            backoff_ms = backoff.as_millis() as u64,
+            "⚠️  Blockfrost answered with a transient error, retrying",
+        );
+        tokio::time::sleep(backoff).await;
+    }
+}
+
+fn non_empty_utf8(bytes: &[u8]) -> Option<String> {
+    let value = String::from_utf8(bytes.to_vec()).ok()?;
+    (!value.is_empty()).then_some(value)
+}
+
 pub struct CardanoClient {
     config: Config,
     http_client: HttpClient,
     tx3_client: Tx3Client,
     submitter: Box<dyn TxSubmitter>,
+    indexer: Box<dyn ChainIndexer>,
+    signer: Box<dyn TxSigner>,
+    /// Script hash found by the first successful `check_validator_script`
+    validator_script_hash: OnceLock<String>,
+    datum_codecs: CodecRegistry,
+    metrics: Metrics,
 }
 
-impl CardanoClient {
-    pub fn new(config: Config) -> Result<Self> {
-        let http_client = HttpClient::new();
+/// UTxOs per page of Blockfrost list endpoints (their maximum)
+pub const BLOCKFROST_PAGE_SIZE: usize = 100;
+
+/// Tracking UTxOs found at the oracle address by `CardanoClient::scan_shipments`
+#[derive(Debug)]
+pub struct ShipmentScan {
+    pub shipments: Vec<TrackingUTxO>,
+    /// Inline datums no registered codec could decode
+    pub undecodable: Vec<UndecodableDatum>,
+    /// The indexer stopped early (Blockfrost after `BLOCKFROST_MAX_PAGES` pages) and more UTxOs may remain
+    pub truncated: bool,
+}
 
-        let mut headers = None;
-        if let Some(trp_api_key) = &config.trp_api_key {
-            headers = Some(HashMap::from([("dmtr-api-key".to_string(), trp_api_key.clone())]));
-        }
+#[derive(Debug)]
+pub struct UndecodableDatum {
+    pub utxo_ref: String,
+    pub rejection: DatumRejected,
+}
 
-        let tx3_client = Tx3Client::new(
-            ClientOptions {
-                endpoint: config.trp_url.clone(),
-                headers,
-            }
+/// `ORACLE_ADDRESS` and `ORACLE_PAYMENT_ADDRESS` must be bech32 addresses on the same network
+fn check_oracle_addresses(config: &Config) -> Result<()> {
+    let parse = |name: &str, value: &str| {
+        Address::from_bech32(value).map_err(|e| anyhow!("{} is not a bech32 address ({}): {}", name, value, e))
+    };
+    let oracle = parse("ORACLE_ADDRESS", &config.oracle_address)?;
+    let payment = parse("ORACLE_PAYMENT_ADDRESS", &config.oracle_payment_address)?;
+
+    if oracle.network() != payment.network() {
+        bail!(
+            "ORACLE_ADDRESS is a {} address but ORACLE_PAYMENT_ADDRESS is a {} address",
+            network_name(oracle.network()),
+            network_name(payment.network())
         );
+    }
 
-        let submitter = Box::new(BlockfrostSubmitter::new(
-            config.blockfrost_url.clone(),
-            http_client.clone(),
-        ));
+    Ok(())
+}
 
-        Ok(Self {
-            config,
-            http_client,
-            tx3_client,
-            submitter,
-        })
+fn network_name(network: Option<Network>) -> &'static str {
+    match network {
+        Some(Network::Mainnet) => "mainnet",
+        Some(Network::Testnet) => "testnet",
+        _ => "unknown network",
+    }
+}
+
+impl CardanoClient {
+    pub fn new(config: Config) -> Result<Self> {
+        let submitter = submitter::from_config(&config)?;
+        Self::with_submitter(config, submitter)
     }
 
     pub fn with_submitter(config: Config, submitter: Box<dyn TxSubmitter>) -> Result<Self> {
-        let http_client = HttpClient::new();
+        register_config_secrets(&config);
+        let signer = signing::from_config(&config)?;
+        Self::from_parts(config, submitter, signer)
+    }
+
+    /// Sign close transactions with `signer` instead of the `ORACLE_SIGNER` one
+    pub fn with_signer(config: Config, signer: Box<dyn TxSigner>) -> Result<Self> {
+        register_config_secrets(&config);
+        let submitter = submitter::from_config(&config)?;
+        Self::from_parts(config, submitter, signer)
+    }
+
+    fn from_parts(config: Config, submitter: Box<dyn TxSubmitter>, signer: Box<dyn TxSigner>) -> Result<Self> {
+        check_oracle_addresses(&config)?;
+
+        let http_client = blockfrost_http_client(&config)?;
+        let indexer = indexer::from_config(&config)?;
+        let datum_codecs = CodecRegistry::from_names(&config.datum_codecs)?;
 
         let mut headers = None;
         if let Some(trp_api_key) = &config.trp_api_key {
-            headers = Some(HashMap::from([("dmtr-api-key".to_string(), trp_api_key.clone())]));
+            headers = Some(HashMap::from([("dmtr-api-key".to_string(), trp_api_key.expose().clone())]));
         }
 
         let tx3_client = Tx3Client::new(
@@ -127,55 +379,82 @@ impl CardanoClient {
             http_client,
             tx3_client,
             submitter,
+            indexer,
+            signer,
+            validator_script_hash: OnceLock::new(),
+            datum_codecs,
+            metrics: Metrics::new(),
         })
     }
 
-    pub async fn fetch_shipments(&self) -> Result<Vec<TrackingUTxO>> {
-        let url = format!(
-            "{}/addresses/{}/utxos",
-            self.config.blockfrost_url,
-            self.config.oracle_address,
-        );
+    /// Discover tracking UTxOs with `indexer` instead of the `CHAIN_INDEXER` one
+    pub fn with_indexer(mut self, indexer: Box<dyn ChainIndexer>) -> Self {
+        self.indexer = indexer;
+        self
+    }
 
-        let response = self.http_client
-            .get(&url)
-            .send()
-            .await?;
-        
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            return Err(anyhow!(
-                "Blockfrost query failed (status {}): {}",
-                status,
-                body
-            ));
-        }
+    /// Decode tracking datums with `registry` instead of the `DATUM_CODECS` ones
+    pub fn with_datum_codecs(mut self, registry: CodecRegistry) -> Self {
+        self.datum_codecs = registry;
+        self
+    }
+
+    /// Count failed Blockfrost, indexer, TRP and submit API requests in `metrics`
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub async fn fetch_shipments(&self) -> Result<Vec<TrackingUTxO>> {
+        Ok(self.scan_shipments().await?.shipments)
+    }
 
-        let utxos: Vec<BlockfrostUTxO> = response.json().await
-            .context("Failed to parse Blockfrost UTxOs response")?;
-
-        let mut tracking_utxos = Vec::new();
-        for utxo in utxos {
-            if let Some(inline_datum) = utxo.inline_datum {
-                let tracking_datum = TrackingDatum::from_cbor(&inline_datum);
-                if let Some(tracking_datum) = tracking_datum {
-                    tracking_utxos.push(TrackingUTxO {
-                        tx_hash: utxo.tx_hash.clone(),
-                        tx_index: utxo.output_index,
-                        datum: tracking_datum,
-                    });
+    /// Tracking UTxOs at the oracle address, along with the inline datums that failed to decode
+    ///
+    /// The UTxOs are listed by the `CHAIN_INDEXER`; only Blockfrost stops early,
+    /// after `BLOCKFROST_MAX_PAGES` pages, in which case the scan is marked truncated.
+    pub async fn scan_shipments(&self) -> Result<ShipmentScan> {
+        let listed = self
+            .indexer
+            .unspent_at(&self.config.oracle_address)
+            .await
+            .inspect_err(|_| self.metrics.request_error(&self.config.chain_indexer.to_string()))?;
+
+        let mut scan = ShipmentScan { shipments: Vec::new(), undecodable: Vec::new(), truncated: listed.truncated };
+        for utxo in listed.utxos {
+            let Some(inline_datum) = utxo.inline_datum else {
+                continue;
+            };
+
+            let utxo_ref = match UtxoRef::from_hex(&utxo.tx_hash, utxo.output_index) {
+                Ok(utxo_ref) => utxo_ref,
+                Err(e) => {
+                    warn!(tx_hash = %utxo.tx_hash, error = %e, "⚠️  Skipping a UTxO with a malformed reference");
+                    continue;
                 }
+            };
+            match self.datum_codecs.decode_hex(&inline_datum) {
+                Ok(datum) => scan.shipments.push(TrackingUTxO { utxo_ref, datum }),
+                Err(rejection) => scan.undecodable.push(UndecodableDatum {
+                    utxo_ref: utxo_ref.to_string(),
+                    rejection,
+                }),
             }
         }
 
-        Ok(tracking_utxos)
+        debug!(
+            shipments = scan.shipments.len(),
+            undecodable = scan.undecodable.len(),
+            truncated = scan.truncated,
+            "Scanned the oracle address",
+        );
+        Ok(scan)
     }
 
     pub async fn submit_shipment(
         &self,
         tracking: &TrackingUTxO,
-        status: &str,
+        status: DerivedStatus,
     ) -> Result<String> {
         self.submit_shipment_at(tracking, status, chrono::Utc::now().timestamp() as u64).await
     }
@@ -183,7 +462,7 @@ impl CardanoClient {
     pub async fn prepare_close_shipment(
         &self,
         tracking: &TrackingUTxO,
-        status: &str,
+        status: DerivedStatus,
     ) -> Result<(CloseShipmentParams, TxEnvelope)> {
         self.prepare_close_shipment_at(tracking, status, chrono::Utc::now().timestamp() as u64).await
     }
@@ -191,21 +470,50 @@ impl CardanoClient {
     pub async fn prepare_close_shipment_at(
         &self,
         tracking: &TrackingUTxO,
-        status: &str,
+        status: DerivedStatus,
         timestamp: u64,
     ) -> Result<(CloseShipmentParams, TxEnvelope)> {
         let params = CloseShipmentParams {
             oracle: self.config.oracle_address.clone(),
-            oracle_pkh: self.config.oracle_pkh.clone(),
+            oracle_pkh: self.signer_pkh(),
             outbox: tracking.datum.outbox_address.to_string(),
-            p_status: hex::encode(status.to_string()),
+            p_status: status.hex(),
             p_timestamp: format!("{}", timestamp),
-            p_utxo_ref: format!("{}#{}", tracking.tx_hash, tracking.tx_index),
+            p_utxo_ref: tracking.utxo_ref.to_string(),
             payment: self.config.oracle_payment_address.clone(),
-            validator_script_ref: self.config.validator_script_ref.clone(),
+            validator_script_ref: self.config.validator_script_ref.to_string(),
+        };
+
+        let envelope = self
+            .tx3_client
+            .close_shipment_tx(params.clone())
+            .await
+            .inspect_err(|_| self.metrics.request_error("trp"))?;
+
+        Ok((params, envelope))
+    }
+
+    /// Resolve a transaction that locks a new tracking UTxO at the oracle
+    /// address, funded by `customer`
+    pub async fn prepare_track_shipment(
+        &self,
+        customer: &str,
+        datum: &TrackingDatum,
+    ) -> Result<(TrackShipmentParams, TxEnvelope)> {
+        let params = TrackShipmentParams {
+            customer: customer.to_string(),
+            oracle: self.config.oracle_address.clone(),
+            outbox: datum.outbox_address.to_string(),
+            p_carrier: hex::encode(&datum.carrier),
+            p_tracking_number: hex::encode(datum.tracking_number.as_bytes()),
+            validator_script_ref: self.config.validator_script_ref.to_string(),
         };
 
-        let envelope = self.tx3_client.close_shipment_tx(params.clone()).await?;
+        let envelope = self
+            .tx3_client
+            .track_shipment_tx(params.clone())
+            .await
+            .inspect_err(|_| self.metrics.request_error("trp"))?;
 
         Ok((params, envelope))
     }
@@ -213,48 +521,404 @@ impl CardanoClient {
     pub async fn submit_shipment_at(
         &self,
         tracking: &TrackingUTxO,
-        status: &str,
+        status: DerivedStatus,
+        timestamp: u64,
+    ) -> Result<String> {
+        Ok(self.close_shipment_at(tracking, status, timestamp).await?.tx_hash)
+    }
+
+    /// Resolve, validate, sign and submit a close-shipment transaction, reporting its fee
+    ///
+    /// Fails like `sign_close_shipment_at` before anything is submitted.
+    pub async fn close_shipment_at(
+        &self,
+        tracking: &TrackingUTxO,
+        status: DerivedStatus,
         timestamp: u64,
+    ) -> Result<ClosedShipment> {
+        let prepared = self.sign_close_shipment_at(tracking, status, timestamp).await?;
+        self.submit_prepared(prepared).await
+    }
+
+    /// The unspent tracking UTxO `utxo_ref` at the oracle address, with its inline datum decoded
+    ///
+    /// The datum is decoded by the `DATUM_CODECS`; with `force`, every built-in
+    /// codec is tried instead. A spent UTxO downcasts to `Raced`.
+    pub async fn tracking_utxo(&self, utxo_ref: &UtxoRef, force: bool) -> Result<TrackingUTxO> {
+        let output = self
+            .blockfrost_get::<BlockfrostTxUtxos>(&format!("/txs/{}/utxos", utxo_ref.tx_hash_hex()))
+            .await?
+            .and_then(|tx| tx.outputs.into_iter().find(|output| output.output_index == utxo_ref.index))
+            .with_context(|| format!("Tracking UTxO {} not found on-chain", utxo_ref))?;
+
+        if let Some(spent_by) = output.consumed_by_tx {
+            return Err(Raced { utxo_ref: utxo_ref.to_string(), spent_by }.into());
+        }
+        if output.address != self.config.oracle_address {
+            bail!("UTxO {} is held at {}, not at the oracle address", utxo_ref, output.address);
+        }
+        let inline_datum = output.inline_datum.with_context(|| format!("UTxO {} has no inline datum", utxo_ref))?;
+
+        let decoded = match self.datum_codecs.decode_hex(&inline_datum) {
+            Err(_) if force => CodecRegistry::from_names(&datum_codec::CODEC_NAMES)?
+                .decode_hex(&inline_datum)
+                .map_err(|every| anyhow!("No datum codec decodes the datum of {}: {}", utxo_ref, every)),
+            decoded => decoded.map_err(|rejection| {
+                anyhow!("DATUM_CODECS cannot decode the datum of {} ({}); force tries every codec", utxo_ref, rejection)
+            }),
+        };
+
+        Ok(TrackingUTxO { utxo_ref: *utxo_ref, datum: decoded? })
+    }
+
+    /// Close the tracking UTxO `utxo_ref` with `status` whatever its carrier says, for operator intervention
+    ///
+    /// The UTxO is looked up on-chain with `tracking_utxo` and must be unspent,
+    /// at the oracle address and decoded by the `DATUM_CODECS`. The close is
+    /// stamped with `timestamp`, or the current time, and goes through the same
+    /// validation, fee check, signing and submission as scheduled closes.
+    /// Returns the close transaction hash.
+    ///
+    /// Only the chain is involved: nothing is journalled, tracked as pending or
+    /// notified, and it refuses to submit under `DRY_RUN`. Operators close through
+    /// `Oracle::close_shipment`, which does all of that.
+    pub async fn close_utxo(&self, utxo_ref: &UtxoRef, status: DerivedStatus, timestamp: Option<u64>) -> Result<String> {
+        self.close_tracking_utxo(utxo_ref, status, timestamp, false).await
+    }
+
+    /// `close_utxo` for a tracking UTxO whose datum only another built-in codec decodes
+    pub async fn force_close_utxo(&self, utxo_ref: &UtxoRef, status: DerivedStatus, timestamp: Option<u64>) -> Result<String> {
+        self.close_tracking_utxo(utxo_ref, status, timestamp, true).await
+    }
+
+    async fn close_tracking_utxo(
+        &self,
+        utxo_ref: &UtxoRef,
+        status: DerivedStatus,
+        timestamp: Option<u64>,
+        force: bool,
     ) -> Result<String> {
-        let (_params, envelope) = self
+        if self.config.dry_run {
+            bail!("DRY_RUN is set, not submitting a close of {}", utxo_ref);
+        }
+        let tracking = self.tracking_utxo(utxo_ref, force).await?;
+        let timestamp = timestamp.unwrap_or_else(|| chrono::Utc::now().timestamp() as u64);
+
+        self.submit_shipment_at(&tracking, status, timestamp).await
+    }
+
+    /// Resolve, validate and sign a close-shipment transaction without submitting it
+    ///
+    /// A transaction that does not spend the tracking UTxO into the expected
+    /// shipment datum is never signed; the error downcasts to `TxValidationFailed`.
+    ///
+    /// With `RECHECK_BEFORE_SIGN`, the tracking UTxO is first looked up again; if
+    /// it was spent since the scan, nothing is resolved or signed and the error
+    /// downcasts to `Raced`.
+    pub async fn sign_close_shipment_at(
+        &self,
+        tracking: &TrackingUTxO,
+        status: DerivedStatus,
+        timestamp: u64,
+    ) -> Result<PreparedClose> {
+        if self.config.recheck_before_sign
+            && let Some(spent_by) = self.spent_by(&tracking.utxo_ref.tx_hash_hex(), tracking.utxo_ref.index).await?
+        {
+            return Err(Raced { utxo_ref: tracking.utxo_ref.to_string(), spent_by }.into());
+        }
+
+        let (params, envelope) = self
             .prepare_close_shipment_at(tracking, status, timestamp)
             .await?;
 
-        let cbor = self.sign_cbor(&envelope)?;
-        let tx_hash = self.submitter.submit(cbor).await?;
+        let expectation = CloseExpectation {
+            utxo_ref: params.p_utxo_ref.clone(),
+            outbox_address: tracking.datum.outbox_address.clone(),
+            payment_address: Address::from_bech32(&self.config.oracle_payment_address)
+                .context("ORACLE_PAYMENT_ADDRESS must be a bech32 address")?,
+            datum: ShipmentDatum {
+                carrier: tracking.datum.carrier.clone(),
+                tracking_number: tracking.datum.tracking_number.clone(),
+                status: status.to_string(),
+                timestamp,
+                oracle_pkh: params.oracle_pkh.clone(),
+            },
+        };
+        validate_close_tx(&envelope, &expectation)?;
+        debug!(tx_hash = %envelope.hash, "Resolved close transaction passed validation");
+
+        let (signed, fee) = self.sign_within_fee(&envelope).await?;
+        debug!(tx_hash = %signed.hash, fee, "Signed close transaction");
 
-        Ok(tx_hash)
+        Ok(PreparedClose { params, signed, fee })
     }
 
-    fn sign_cbor(&self, envelope: &TxEnvelope) -> Result<Vec<u8>> {
-        let tx_hash_bytes = hex::decode(&envelope.hash).expect("tx_hash must be hex");
-        let private_key_bytes = hex::decode(&self.config.oracle_sk).expect("private_key must be hex");
-        let signing_key = SigningKey::from_bytes(
-            private_key_bytes
-                .as_slice()
-                .try_into()
-                .expect("private_key must be 32 bytes"),
-        );
+    /// Submit a close signed by `sign_close_shipment_at`
+    ///
+    /// A submission the submit API did not accept downcasts to `SubmitError`.
+    pub async fn submit_prepared(&self, prepared: PreparedClose) -> Result<ClosedShipment> {
+        let tx_hash = self.submit_signed(prepared.signed.cbor).await?;
+
+        Ok(ClosedShipment { tx_hash, fee: prepared.fee })
+    }
+
+    /// Sign and submit a resolved envelope unless its fee exceeds `MAX_FEE_LOVELACE`
+    ///
+    /// A refused envelope is never signed; the error downcasts to `FeeExceeded`.
+    /// A submission the submit API did not accept downcasts to `SubmitError`.
+    pub async fn submit_envelope(&self, envelope: &TxEnvelope) -> Result<ClosedShipment> {
+        let (signed, fee) = self.sign_within_fee(envelope).await?;
+        let tx_hash = self.submit_signed(signed.cbor).await?;
+
+        Ok(ClosedShipment { tx_hash, fee })
+    }
+
+    /// Submit through the `SUBMITTER`, counting submissions that got no verdict as request errors
+    async fn submit_signed(&self, signed_tx: Vec<u8>) -> Result<String, SubmitError> {
+        self.submitter.submit(signed_tx).await.inspect_err(|e| {
+            if e.is_transient() {
+                self.metrics.request_error(&self.config.submitter.to_string());
+            }
+        })
+    }
+
+    /// Sign `envelope` and read its fee, refusing fees above `MAX_FEE_LOVELACE` before signing
+    async fn sign_within_fee(&self, envelope: &TxEnvelope) -> Result<(SignedTx, u64)> {
+        let fee = envelope_fee(envelope)?;
+        if fee > self.config.max_fee_lovelace {
+            return Err(FeeExceeded { fee, max_fee: self.config.max_fee_lovelace }.into());
+        }
+
+        let signed = sign_envelope_with(envelope, self.signer.as_ref()).await?;
+
+        Ok((signed, fee))
+    }
+
+    /// The latest decision for `tracking_number` still held at `outbox`, read from the shipment datums there
+    ///
+    /// Fallback for when the state database has no record, e.g. after a fresh
+    /// deploy. Outputs the merchant has already spent are not found.
+    pub async fn find_decision_onchain(&self, tracking_number: &str, outbox: &str) -> Result<Option<Decision>> {
+        let Some(utxos) = self.blockfrost_get::<Vec<BlockfrostUTxO>>(&format!("/addresses/{}/utxos", outbox)).await? else {
+            return Ok(None);
+        };
+
+        let decision = utxos
+            .into_iter()
+            .filter_map(|utxo| {
+                let datum = ShipmentDatum::from_cbor(utxo.inline_datum.as_deref()?)?;
+                (datum.tracking_number.to_string() == tracking_number).then_some((utxo, datum))
+            })
+            .max_by_key(|(_, datum)| datum.timestamp)
+            .map(|(utxo, datum)| Decision {
+                utxo_ref: None,
+                carrier: Some(datum.carrier),
+                tracking_number: Some(datum.tracking_number.to_string()),
+                outbox_address: Some(outbox.to_string()),
+                status: datum.status,
+                timestamp: datum.timestamp,
+                tx_hash: utxo.tx_hash,
+                confirmation: Confirmation::Confirmed { block: None },
+                source: DecisionSource::Chain,
+            });
+
+        Ok(decision)
+    }
+
+    /// Height of the block holding `tx_hash`, or `None` while it is not on-chain
+    pub async fn tx_block_height(&self, tx_hash: &str) -> Result<Option<u64>> {
+        let tx = self.blockfrost_get::<BlockfrostTx>(&format!("/txs/{}", tx_hash)).await?;
+        Ok(tx.map(|tx| tx.block_height))
+    }
+
+    /// Time of the block holding `tx_hash`, or `None` while it is not on-chain
+    pub async fn tx_block_time(&self, tx_hash: &str) -> Result<Option<DateTime<Utc>>> {
+        let tx = self.blockfrost_get::<BlockfrostTx>(&format!("/txs/{}", tx_hash)).await?;
+        Ok(tx.and_then(|tx| tx.block_time).and_then(|time| DateTime::from_timestamp(time, 0)))
+    }
+
+    /// Check every journalled submission against the chain
+    ///
+    /// Each transaction must exist on-chain and pay a shipment datum matching the
+    /// journalled status and signer to one of `outbox_addresses`. Entries also
+    /// record whether their tracking UTxO is still unspent at the oracle address.
+    pub async fn reconcile(&self, outbox_addresses: &[String], journal: &[Submission]) -> Result<ReconcileReport> {
+        let unspent: HashSet<String> = self
+            .fetch_shipments()
+            .await?
+            .iter()
+            .map(|utxo| utxo.utxo_ref.to_string())
+            .collect();
+
+        let mut entries = Vec::with_capacity(journal.len());
+        for submission in journal {
+            let (status, detail) = self.reconcile_submission(outbox_addresses, submission).await?;
+            entries.push(ReconcileEntry {
+                submission: submission.clone(),
+                status,
+                detail,
+                tracking_unspent: unspent.contains(&submission.utxo_ref),
+            });
+        }
+
+        Ok(ReconcileReport::new(entries))
+    }
+
+    async fn reconcile_submission(
+        &self,
+        outbox_addresses: &[String],
+        submission: &Submission,
+    ) -> Result<(ReconcileStatus, Option<String>)> {
+        let tx_hash = &submission.tx_hash;
+        if self.blockfrost_get::<serde_json::Value>(&format!("/txs/{}", tx_hash)).await?.is_none() {
+            return Ok((ReconcileStatus::MissingOnChain, None));
+        }
+
+        let Some(datum) = self.shipment_datum_in(tx_hash, outbox_addresses).await? else {
+            return Ok((ReconcileStatus::DatumMismatch, Some("no shipment datum at an outbox address".to_string())));
+        };
+
+        if datum.status != submission.status {
+            return Ok((
+                ReconcileStatus::DatumMismatch,
+                Some(format!("status {} on-chain, {} in journal", datum.status, submission.status)),
+            ));
+        }
+
+        if let Some(signer_pkh) = &submission.signer_pkh
+            && datum.oracle_pkh != *signer_pkh
+        {
+            return Ok((
+                ReconcileStatus::DatumMismatch,
+                Some(format!("signed by {} on-chain, {} in journal", datum.oracle_pkh, signer_pkh)),
+            ));
+        }
+
+        Ok((ReconcileStatus::Confirmed, None))
+    }
+
+    /// The shipment datum `tx_hash` pays to one of `outbox_addresses`, if any
+    pub async fn shipment_datum_in(&self, tx_hash: &str, outbox_addresses: &[String]) -> Result<Option<ShipmentDatum>> {
+        let Some(tx) = self.blockfrost_get::<BlockfrostTxUtxos>(&format!("/txs/{}/utxos", tx_hash)).await? else {
+            return Ok(None);
+        };
+
+        Ok(tx
+            .outputs
+            .iter()
+            .filter(|output| outbox_addresses.contains(&output.address))
+            .find_map(|output| output.inline_datum.as_deref().and_then(ShipmentDatum::from_cbor)))
+    }
+
+    /// The transaction that spent output `index` of `tx_hash`, or `None` while it is unspent
+    pub async fn spent_by(&self, tx_hash: &str, index: u32) -> Result<Option<String>> {
+        let output = self
+            .blockfrost_get::<BlockfrostTxUtxos>(&format!("/txs/{}/utxos", tx_hash))
+            .await
+            .context("Failed to re-check the tracking UTxO")?
+            .and_then(|tx| tx.outputs.into_iter().find(|output| output.output_index == index))
+            .with_context(|| format!("Tracking UTxO {}#{} not found on-chain", tx_hash, index))?;
+
+        Ok(output.consumed_by_tx)
+    }
+
+    /// Compare the network magic of Blockfrost's `/genesis` with `expected`
+    pub async fn check_network(&self, expected: CardanoNetwork) -> Result<NetworkCheck> {
+        let genesis = self
+            .blockfrost_get::<BlockfrostGenesis>("/genesis")
+            .await?
+            .context("Blockfrost has no /genesis endpoint")?;
+
+        if genesis.network_magic == expected.network_magic() {
+            return Ok(NetworkCheck::Matches);
+        }
+        Ok(NetworkCheck::Mismatch { expected, network_magic: genesis.network_magic })
+    }
+
+    /// Look up the `VALIDATOR_SCRIPT_REF` UTxO and its reference script
+    ///
+    /// A `Present` result is cached for the lifetime of the client; anything
+    /// else is checked again on the next call.
+    pub async fn check_validator_script(&self) -> Result<ValidatorScriptCheck> {
+        if let Some(script_hash) = self.validator_script_hash.get() {
+            return Ok(ValidatorScriptCheck::Present { script_hash: script_hash.clone() });
+        }
 
-        let signature = signing_key.sign(&tx_hash_bytes);
-        let public_key = signing_key.verifying_key().to_bytes();
+        let script_ref = self.config.validator_script_ref;
+        let (tx_hash, index) = (script_ref.tx_hash_hex(), script_ref.index);
 
-        let witness = VKeyWitness {
-            vkey: Bytes::from(public_key.to_vec()),
-            signature: Bytes::from(signature.to_bytes().to_vec()),
+        let output = self
+            .blockfrost_get::<BlockfrostTxUtxos>(&format!("/txs/{}/utxos", tx_hash))
+            .await?
+            .and_then(|tx| tx.outputs.into_iter().find(|output| output.output_index == index));
+        let Some(output) = output else {
+            return Ok(ValidatorScriptCheck::Missing);
         };
 
-        let bytes = hex::decode(&envelope.tx)?;
-        let tx = MultiEraTx::decode(&bytes)?;
-        let mut tx = tx.as_conway().ok_or(anyhow!("Unsupported tx era"))?.to_owned();
+        let Some(script_hash) = output.reference_script_hash else {
+            return Ok(ValidatorScriptCheck::NoReferenceScript);
+        };
+
+        if let Some(spent_by) = output.consumed_by_tx {
+            let moved_to = self
+                .blockfrost_get::<BlockfrostTxUtxos>(&format!("/txs/{}/utxos", spent_by))
+                .await?
+                .and_then(|tx| {
+                    tx.outputs
+                        .into_iter()
+                        .find(|output| output.reference_script_hash.as_ref() == Some(&script_hash))
+                })
+                .map(|output| output.address);
+
+            return Ok(ValidatorScriptCheck::Spent { spent_by, moved_to });
+        }
+
+        if let Some(expected) = &self.config.validator_script_hash
+            && *expected != script_hash
+        {
+            return Ok(ValidatorScriptCheck::HashMismatch { expected: expected.clone(), actual: script_hash });
+        }
+
+        let script_hash = self.validator_script_hash.get_or_init(|| script_hash);
+        Ok(ValidatorScriptCheck::Present { script_hash: script_hash.clone() })
+    }
+
+    /// GET a Blockfrost resource; `None` when it does not exist
+    async fn blockfrost_get<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>> {
+        self.request_blockfrost(path).await.inspect_err(|_| self.metrics.request_error("blockfrost"))
+    }
+
+    async fn request_blockfrost<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>> {
+        let url = format!("{}{}", self.config.blockfrost_url, path);
 
-        let mut witness_set = tx.transaction_witness_set.unwrap();
-        witness_set.vkeywitness = NonEmptySet::from_vec(vec![witness]);
-        tx.transaction_witness_set = KeepRaw::from(witness_set);
+        let response = blockfrost_send(&self.config, self.http_client.get(&url)).await?;
 
-        Ok(pallas::codec::minicbor::to_vec(&tx)?)
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow!(
+                "Blockfrost query failed (status {}): {}{}",
+                status,
+                redact(&body),
+                forbidden_hint(status, self.config.blockfrost_project_id.is_some())
+            ));
+        }
+
+        let value = response.json().await
+            .with_context(|| format!("Failed to parse Blockfrost {} response", path))?;
+
+        Ok(Some(value))
     }
-    
+
+    /// Key hash of the oracle key that signs close transactions for the configured validator
+    pub fn signer_pkh(&self) -> String {
+        self.signer.pkh()
+    }
+
     #[cfg(test)]
     pub fn submitter(&self) -> &dyn TxSubmitter {
         self.submitter.as_ref()
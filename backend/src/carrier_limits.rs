@@ -0,0 +1,145 @@
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, Semaphore};
+use tokio::time::Instant;
+
+use crate::backoff;
+use crate::config::Config;
+use crate::shipment::ShipmentError;
+
+/// Delay before the first retry of a carrier request failing without a `retry_after`, doubled after each
+pub const CARRIER_RETRY_BASE_BACKOFF: Duration = Duration::from_millis(200);
+
+/// One carrier's entry of `CARRIER_LIMITS`; unset fields take the global values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CarrierLimit {
+    /// Tracking requests in flight at once (default: `FETCH_CONCURRENCY`)
+    pub max_concurrency: Option<usize>,
+    /// Milliseconds between the starts of two tracking requests (default: 0)
+    pub min_interval_ms: Option<u64>,
+    /// Retries of a rate limited or failed request, on top of the provider's own (default: 0)
+    pub max_retries: Option<u32>,
+}
+
+/// Parse `CARRIER_LIMITS`: a JSON object of carrier limits, e.g. `{"ups": {"max_concurrency": 2, "min_interval_ms": 500}}`
+pub fn parse_carrier_limits(value: &str) -> Result<BTreeMap<String, CarrierLimit>> {
+    let parsed: BTreeMap<String, CarrierLimit> =
+        serde_json::from_str(value).context("expected a JSON object of carrier limits")?;
+
+    let mut limits = BTreeMap::new();
+    for (carrier, limit) in parsed {
+        let carrier = carrier.trim().to_lowercase();
+        if carrier.is_empty() {
+            bail!("carrier names cannot be empty");
+        }
+        if limit.max_concurrency == Some(0) {
+            bail!("max_concurrency of {} must be at least 1", carrier);
+        }
+        limits.insert(carrier, limit);
+    }
+
+    Ok(limits)
+}
+
+/// Concurrency cap and pacer of one carrier's tracking requests
+#[derive(Debug)]
+struct Lane {
+    permits: Semaphore,
+    min_interval: Duration,
+    max_retries: u32,
+    /// Earliest start of the next request
+    next_start: Mutex<Instant>,
+}
+
+impl Lane {
+    fn new(limit: &CarrierLimit, default_concurrency: usize) -> Self {
+        Self {
+            permits: Semaphore::new(limit.max_concurrency.unwrap_or(default_concurrency).max(1)),
+            min_interval: Duration::from_millis(limit.min_interval_ms.unwrap_or(0)),
+            max_retries: limit.max_retries.unwrap_or(0),
+            next_start: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Wait for this request's turn, `min_interval` after the previous one started
+    async fn pace(&self) {
+        if self.min_interval.is_zero() {
+            return;
+        }
+
+        let start = {
+            let mut next_start = self.next_start.lock().await;
+            let start = (*next_start).max(Instant::now());
+            *next_start = start + self.min_interval;
+            start
+        };
+        tokio::time::sleep_until(start).await;
+    }
+}
+
+/// Per-carrier limits applied to tracking requests; clones share the limits
+///
+/// Carriers without an entry are only bound by the global limits: the
+/// fetcher's `FETCH_CONCURRENCY` and each provider's own rate limits.
+#[derive(Debug, Clone, Default)]
+pub struct CarrierLimits {
+    lanes: Arc<BTreeMap<String, Lane>>,
+}
+
+impl CarrierLimits {
+    /// Limits of `limits` carriers (lowercase), `default_concurrency` requests at once unless they set theirs
+    pub fn new(limits: &BTreeMap<String, CarrierLimit>, default_concurrency: usize) -> Self {
+        let lanes = limits
+            .iter()
+            .map(|(carrier, limit)| (carrier.clone(), Lane::new(limit, default_concurrency)))
+            .collect();
+        Self { lanes: Arc::new(lanes) }
+    }
+
+    /// Limits of `CARRIER_LIMITS`, defaulting to `FETCH_CONCURRENCY`
+    pub fn from_config(config: &Config) -> Self {
+        Self::new(&config.carrier_limits, config.fetch_concurrency)
+    }
+
+    /// Run `request` within the limits of `carrier`, retrying its transient failures
+    ///
+    /// Each attempt takes a slot of the carrier and waits for its pace; rate
+    /// limited attempts are retried after their `retry_after`, network failures
+    /// after an exponential backoff, up to the carrier's `max_retries`.
+    pub async fn run<T, F, Fut>(&self, carrier: &str, mut request: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let Some(lane) = self.lanes.get(&carrier.to_lowercase()) else {
+            return request().await;
+        };
+
+        let mut attempt = 0;
+        loop {
+            let result = {
+                let _permit = lane.permits.acquire().await.context("Carrier limits were closed")?;
+                lane.pace().await;
+                request().await
+            };
+
+            let wait = match result.as_ref().err().and_then(|e| e.downcast_ref::<ShipmentError>()) {
+                Some(ShipmentError::RateLimited { retry_after, .. }) => {
+                    retry_after.unwrap_or_else(|| backoff::exponential(CARRIER_RETRY_BASE_BACKOFF, attempt))
+                }
+                Some(ShipmentError::Network(_)) => backoff::exponential(CARRIER_RETRY_BASE_BACKOFF, attempt),
+                _ => return result,
+            };
+            if attempt >= lane.max_retries {
+                return result;
+            }
+            attempt += 1;
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::config::Config;
+use crate::tracking_provider::ProviderKind;
+
+/// Carriers the oracle asks tracking providers about
+///
+/// Anyone can lock a tracking UTxO with any carrier, so refused carriers are
+/// skipped before any tracking call. Carriers compare trimmed and lowercase,
+/// without their `<provider>:` prefix.
+#[derive(Debug, Clone, Default)]
+pub struct CarrierPolicy {
+    allowlist: Option<BTreeSet<String>>,
+    denylist: Option<BTreeSet<String>>,
+}
+
+/// Why a shipment's carrier is refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CarrierViolation {
+    Denylisted,
+    NotAllowlisted,
+}
+
+impl fmt::Display for CarrierViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CarrierViolation::Denylisted => f.write_str("carrier is denylisted"),
+            CarrierViolation::NotAllowlisted => f.write_str("carrier is not allowlisted"),
+        }
+    }
+}
+
+/// Parse a comma-separated carrier list, trimmed and lowercase
+pub fn parse_carriers(value: &str) -> BTreeSet<String> {
+    value.split(',').map(normalize).filter(|carrier| !carrier.is_empty()).collect()
+}
+
+/// Policy for the `CARRIER_ALLOWLIST` and `CARRIER_DENYLIST` of `config`; `None` without either
+pub fn from_config(config: &Config) -> Option<CarrierPolicy> {
+    if config.carrier_allowlist.is_none() && config.carrier_denylist.is_none() {
+        return None;
+    }
+
+    Some(CarrierPolicy::new(config.carrier_allowlist.clone(), config.carrier_denylist.clone()))
+}
+
+impl CarrierPolicy {
+    pub fn new(allowlist: Option<BTreeSet<String>>, denylist: Option<BTreeSet<String>>) -> Self {
+        Self { allowlist, denylist }
+    }
+
+    /// Whether `carrier` may be tracked; the denylist wins over the allowlist
+    pub fn check(&self, carrier: &str) -> Result<(), CarrierViolation> {
+        let carrier = match carrier.split_once(':') {
+            Some((prefix, name)) if prefix.parse::<ProviderKind>().is_ok() => normalize(name),
+            _ => normalize(carrier),
+        };
+        if self.denylist.as_ref().is_some_and(|list| list.contains(&carrier)) {
+            return Err(CarrierViolation::Denylisted);
+        }
+        if self.allowlist.as_ref().is_some_and(|list| !list.contains(&carrier)) {
+            return Err(CarrierViolation::NotAllowlisted);
+        }
+
+        Ok(())
+    }
+}
+
+fn normalize(carrier: &str) -> String {
+    carrier.trim().to_lowercase()
+}
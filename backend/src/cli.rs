@@ -0,0 +1,297 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use clap::{Args, Parser, Subcommand};
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
+
+use crate::config::Config;
+use crate::metrics::Metrics;
+use crate::models::{DerivedStatus, TrackingUTxO, UtxoRef};
+use crate::oracle::Oracle;
+use crate::scheduler::RunMode;
+use crate::shipment::ShipmentClient;
+use crate::tracking_provider::{self, TrackingProvider};
+
+/// Closes Cardano tracking UTxOs once the carrier reports a final status
+#[derive(Debug, Parser)]
+#[command(name = "shipping-oracle", version)]
+pub struct Cli {
+    #[command(flatten)]
+    pub overrides: ConfigOverrides,
+    /// Same as the `once` subcommand
+    #[arg(long, hide = true)]
+    pub once: bool,
+    /// Defaults to `run`
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    /// `RunMode::Once` for `once` or `--once`, else the configured `RUN_MODE`
+    pub fn run_mode(&self, configured: RunMode) -> RunMode {
+        if self.once || matches!(self.command, Some(Command::Once)) {
+            return RunMode::Once;
+        }
+        configured
+    }
+}
+
+/// Flags that take precedence over the environment variable of the same name
+#[derive(Debug, Default, Args)]
+pub struct ConfigOverrides {
+    /// `BLOCKFROST_URL`
+    #[arg(long, global = true, value_name = "URL")]
+    pub blockfrost_url: Option<String>,
+    /// `TRP_URL`
+    #[arg(long, global = true, value_name = "URL")]
+    pub trp_url: Option<String>,
+    /// `SHIPPO_URL`
+    #[arg(long, global = true, value_name = "URL")]
+    pub shippo_url: Option<String>,
+    /// `ORACLE_ADDRESS`
+    #[arg(long, global = true, value_name = "BECH32")]
+    pub oracle_address: Option<String>,
+    /// `CARDANO_NETWORK`
+    #[arg(long, global = true, value_name = "NETWORK")]
+    pub cardano_network: Option<String>,
+    /// `STATE_DB_PATH`, in place of any `DATABASE_URL`
+    #[arg(long, global = true, value_name = "PATH")]
+    pub state_db_path: Option<String>,
+    /// `CRON_SCHEDULE`
+    #[arg(long, global = true, value_name = "CRON")]
+    pub cron_schedule: Option<String>,
+    /// `DRY_RUN=true`
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+}
+
+impl ConfigOverrides {
+    /// Environment variables the flags set
+    pub fn vars(&self) -> Vec<(&'static str, String)> {
+        let values = [
+            ("BLOCKFROST_URL", &self.blockfrost_url),
+            ("TRP_URL", &self.trp_url),
+            ("SHIPPO_URL", &self.shippo_url),
+            ("ORACLE_ADDRESS", &self.oracle_address),
+            ("CARDANO_NETWORK", &self.cardano_network),
+            ("STATE_DB_PATH", &self.state_db_path),
+            ("CRON_SCHEDULE", &self.cron_schedule),
+        ];
+        let mut vars: Vec<(&'static str, String)> =
+            values.into_iter().filter_map(|(key, value)| Some((key, value.clone()?))).collect();
+        if self.dry_run {
+            vars.push(("DRY_RUN", "true".to_string()));
+        }
+        vars
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vars().is_empty()
+    }
+
+    /// Load a config from `var`, with every flag given taking precedence
+    pub fn load(&self, var: impl Fn(&str) -> Option<String>) -> Result<Config> {
+        let vars = self.vars();
+        Config::from_vars(|key| {
+            if let Some((_, value)) = vars.iter().find(|(name, _)| *name == key) {
+                return Some(value.clone());
+            }
+            // A database given on the command line replaces the environment's either way
+            if key == "DATABASE_URL" && self.state_db_path.is_some() {
+                return None;
+            }
+            var(key)
+        })
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run every pipeline on CRON_SCHEDULE, or once under RUN_MODE=once
+    Run,
+    /// Run every pipeline a single time and exit
+    Once,
+    /// Print the tracking UTxOs at the oracle address
+    List {
+        #[arg(long)]
+        json: bool,
+        #[arg(long)]
+        tenant: Option<String>,
+    },
+    /// Ask the carrier for the status of a shipment
+    Status {
+        carrier: String,
+        tracking_number: String,
+        #[arg(long)]
+        tenant: Option<String>,
+    },
+    /// Close one tracking UTxO with the given on-chain status, whatever the carrier says
+    Close(CloseArgs),
+    /// Print the privacy-mode datum hash of a tracking number and record it in the state database
+    RegisterTracking { carrier: String, tracking_number: String, salt: String },
+    /// Classify every journalled closure against the chain
+    Reconcile {
+        #[arg(long)]
+        json: bool,
+        /// Queue closures that never landed for resubmission
+        #[arg(long)]
+        requeue: bool,
+        #[arg(long)]
+        tenant: Option<String>,
+        #[arg(required = true)]
+        outbox_addresses: Vec<String>,
+    },
+    /// Total the fees paid for journalled closures, per outbox address
+    Fees {
+        #[arg(long)]
+        json: bool,
+        /// Only closures from this day on
+        #[arg(long, value_name = "YYYY-MM-DD", value_parser = parse_date)]
+        since: Option<NaiveDate>,
+        #[arg(long)]
+        tenant: Option<String>,
+    },
+    /// Count the Shippo tracking calls of a billing period, per carrier and outbox address
+    ShippoUsage {
+        #[arg(long)]
+        json: bool,
+        /// Billing period (default: the current one)
+        #[arg(long, value_name = "YYYY-MM")]
+        period: Option<String>,
+        #[arg(long)]
+        tenant: Option<String>,
+    },
+    /// Print what the oracle decided for matching shipments as JSON
+    Decisions {
+        #[arg(long, required_unless_present = "outbox")]
+        tracking_number: Option<String>,
+        #[arg(long)]
+        outbox: Option<String>,
+        #[arg(long)]
+        offset: Option<usize>,
+        #[arg(long)]
+        limit: Option<usize>,
+        #[arg(long)]
+        tenant: Option<String>,
+    },
+    /// Provision a `selftest` shipment, close it as delivered and check the result on-chain
+    SelfTest {
+        /// Close this tracking UTxO instead of provisioning one
+        #[arg(long, value_name = "TxHash#TxIx")]
+        utxo: Option<String>,
+        #[arg(long)]
+        tenant: Option<String>,
+    },
+    /// Seed the state database with open shipments from `utxo_ref,carrier,tracking_number,outbox` rows
+    ImportShipments {
+        /// Overwrite shipments the state database already tracks
+        #[arg(long)]
+        force: bool,
+        #[arg(long)]
+        json: bool,
+        #[arg(long)]
+        tenant: Option<String>,
+        csv: PathBuf,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct CloseArgs {
+    /// `TxHash#TxIx` of the tracking UTxO
+    pub utxo_ref: UtxoRef,
+    /// DELIVERED or NOT_DELIVERED
+    pub status: DerivedStatus,
+    /// Unix time to write to the shipment datum (default: now)
+    #[arg(long, value_name = "UNIX")]
+    pub timestamp: Option<u64>,
+    /// Try every datum codec when DATUM_CODECS cannot decode the tracking datum
+    #[arg(long)]
+    pub force: bool,
+    /// Close without asking for confirmation
+    #[arg(long, short)]
+    pub yes: bool,
+    #[arg(long)]
+    pub tenant: Option<String>,
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| anyhow::anyhow!("expected a YYYY-MM-DD date, got '{}'", value))
+}
+
+/// `list`: tracking UTxOs as a table, or as JSON in the report shape
+pub async fn list(oracle: &Oracle, json: bool, out: &mut impl Write) -> Result<()> {
+    let shipments = oracle.scan().await?;
+    if json {
+        writeln!(out, "{}", serde_json::to_string_pretty(&shipments)?)?;
+        return Ok(());
+    }
+
+    writeln!(out, "{:<68} {:<10} {:<26} {:<12} OUTBOX", "UTXO", "CARRIER", "TRACKING NUMBER", "DEADLINE")?;
+    for TrackingUTxO { utxo_ref, datum } in &shipments {
+        let deadline = datum.deadline.map_or_else(|| "-".to_string(), |deadline| deadline.to_string());
+        let outbox = datum.outbox_address.to_bech32().unwrap_or_else(|_| datum.outbox_address.to_hex());
+        writeln!(
+            out,
+            "{:<68} {:<10} {:<26} {:<12} {}",
+            utxo_ref.to_string(),
+            datum.carrier,
+            datum.tracking_number.to_string(),
+            deadline,
+            outbox
+        )?;
+    }
+    writeln!(out, "{} tracking UTxO(s)", shipments.len())?;
+
+    Ok(())
+}
+
+/// `status`: the carrier's latest status and the on-chain status it would close the shipment with
+pub async fn status(config: &Config, carrier: &str, tracking_number: &str, out: &mut impl Write) -> Result<()> {
+    let metrics = Metrics::new();
+    let providers = tracking_provider::from_config(config, ShipmentClient::new(config.clone())?, &metrics)?;
+    let status = providers.fetch_status(carrier, tracking_number).await?;
+
+    writeln!(out, "{} {}: {}", carrier, tracking_number, status.status)?;
+    if !status.status_details.is_empty() {
+        writeln!(out, "Details: {}", status.status_details)?;
+    }
+    if let Some(date) = &status.status_date {
+        writeln!(out, "Date: {}", date)?;
+    }
+    match config.status_mapping.map(&status) {
+        Some(on_chain) => writeln!(out, "Closes as {}", on_chain)?,
+        None => writeln!(out, "Not final; the shipment stays open")?,
+    }
+
+    Ok(())
+}
+
+/// `close`: show the tracking datum, confirm unless `--yes`, then close with `Oracle::close_shipment`
+///
+/// Under `DRY_RUN` (or `--dry-run`) the close is signed and printed, not submitted.
+pub async fn close(oracle: &Oracle, args: &CloseArgs, input: &mut impl BufRead, out: &mut impl Write) -> Result<()> {
+    let shipment = oracle.chain().tracking_utxo(&args.utxo_ref, args.force).await?;
+
+    writeln!(out, "Tracking UTxO {}", args.utxo_ref)?;
+    writeln!(out, "{}", serde_json::to_string_pretty(&shipment.datum)?)?;
+
+    if !args.yes {
+        write!(out, "Close as {}? [y/N] ", args.status)?;
+        out.flush()?;
+        let mut answer = String::new();
+        input.read_line(&mut answer)?;
+        if !matches!(answer.trim(), "y" | "Y" | "yes") {
+            writeln!(out, "Not closed")?;
+            return Ok(());
+        }
+    }
+
+    let closed = oracle.close_shipment(&shipment, args.status, args.timestamp).await?;
+    if closed.is_dry_run() {
+        writeln!(out, "Dry run: signed the close as {} in {}, not submitted", args.status, closed.tx_hash())?;
+    } else {
+        writeln!(out, "Closed as {} in {}", args.status, closed.tx_hash())?;
+    }
+
+    Ok(())
+}
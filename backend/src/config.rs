@@ -1,74 +1,613 @@
-use anyhow::{Context, Result, bail};
+use anyhow::{Context, Result, anyhow, bail};
+use chrono_tz::Tz;
+use pallas::ledger::addresses::{Address, Network as AddressNetwork};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::env;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::aftership;
+use crate::carrier_limits::{CarrierLimit, parse_carrier_limits};
+use crate::carrier_policy::parse_carriers;
+use crate::datum_codec::CodecRegistry;
+use crate::indexer::{IndexerKind, ScanMode};
+use crate::metrics::OTHER_OUTBOX;
+use crate::models::UtxoRef;
+use crate::notifier::{NotifyMode, WebhookTemplate};
+use crate::redact::Secret;
+use crate::tracking_provider::{ProviderKind, parse_carrier_providers};
+use crate::scheduler::{OverlapPolicy, RunMode};
+use crate::shippo_budget::DEFAULT_DEGRADED_TRANSIT_HOURS;
+use crate::signing::SignerKind;
+use crate::stale_status::{DEFAULT_STALE_STATUSES, parse_statuses};
+use crate::status_mapping::StatusMapping;
+use crate::submit_window::SubmitWindow;
+use crate::submitter::{DEFAULT_SUBMIT_BASE_BACKOFF_MS, DEFAULT_SUBMIT_MAX_RETRIES, SubmitterKind};
+use crate::timestamp_source::TimestampSource;
+
+/// Default `BLOCKFROST_MAX_PAGES`: 10,000 UTxOs at Blockfrost's 100 per page
+pub const DEFAULT_BLOCKFROST_MAX_PAGES: u32 = 100;
+
+/// Default `SHIPPO_MAX_RETRIES`
+pub const DEFAULT_SHIPPO_MAX_RETRIES: u32 = 3;
+
+/// Default `BLOCKFROST_MAX_RETRIES`
+pub const DEFAULT_BLOCKFROST_MAX_RETRIES: u32 = 3;
+
+/// Default `BLOCKFROST_BASE_BACKOFF_MS`
+pub const DEFAULT_BLOCKFROST_BASE_BACKOFF_MS: u64 = 500;
+
+/// Default `BLOCKFROST_MAX_BACKOFF_MS`
+pub const DEFAULT_BLOCKFROST_MAX_BACKOFF_MS: u64 = 30_000;
+
+/// Default `FETCH_CONCURRENCY`
+pub const DEFAULT_FETCH_CONCURRENCY: usize = 8;
+
+/// Default `CLOSE_CONCURRENCY`
+pub const DEFAULT_CLOSE_CONCURRENCY: usize = 4;
+
+/// Default `CLOSE_QUEUE_DEPTH`
+pub const DEFAULT_CLOSE_QUEUE_DEPTH: usize = 64;
+
+/// Default `PENDING_TX_TTL_MINUTES`: a close transaction normally confirms within minutes
+pub const DEFAULT_PENDING_TX_TTL_MINUTES: u64 = 30;
+
+/// Default `ROLLBACK_DEPTH`: Cardano rollbacks rarely go deeper than a few blocks
+pub const DEFAULT_ROLLBACK_DEPTH: u64 = 20;
+
+/// Default `SHUTDOWN_GRACE_SECONDS`: under Kubernetes' default 30 s termination grace period
+pub const DEFAULT_SHUTDOWN_GRACE_SECONDS: u64 = 25;
+
+/// Default `MAX_FEE_LOVELACE`: close transactions normally cost ~0.2 ADA
+pub const DEFAULT_MAX_FEE_LOVELACE: u64 = 2_000_000;
+
+/// Default `METRICS_MAX_OUTBOXES`: enough merchants for billing, few enough series for Prometheus
+pub const DEFAULT_METRICS_MAX_OUTBOXES: usize = 100;
+
+/// Cardano network the oracle operates on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Network {
+    Mainnet,
+    Preprod,
+    Preview,
+}
+
+impl Network {
+    /// Lowercase network name, as accepted by `CARDANO_NETWORK`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "mainnet",
+            Network::Preprod => "preprod",
+            Network::Preview => "preview",
+        }
+    }
+
+    /// Protocol magic Blockfrost's `/genesis` reports for this network
+    pub fn network_magic(&self) -> u64 {
+        match self {
+            Network::Mainnet => 764_824_073,
+            Network::Preprod => 1,
+            Network::Preview => 2,
+        }
+    }
+
+    /// Network whose protocol magic is `magic`
+    pub fn from_magic(magic: u64) -> Option<Self> {
+        [Network::Mainnet, Network::Preprod, Network::Preview].into_iter().find(|network| network.network_magic() == magic)
+    }
+
+    /// Whether addresses on this network are `addr` rather than `addr_test`
+    pub fn is_mainnet(&self) -> bool {
+        *self == Network::Mainnet
+    }
+
+    /// Base URL of the Cardanoscan explorer for this network
+    pub fn explorer_url(&self) -> &'static str {
+        match self {
+            Network::Mainnet => "https://cardanoscan.io",
+            Network::Preprod => "https://preprod.cardanoscan.io",
+            Network::Preview => "https://preview.cardanoscan.io",
+        }
+    }
+
+    /// Explorer page for a transaction on this network
+    pub fn transaction_url(&self, tx_hash: &str) -> String {
+        format!("{}/transaction/{}", self.explorer_url(), tx_hash)
+    }
+}
+
+impl FromStr for Network {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "mainnet" => Ok(Network::Mainnet),
+            "preprod" => Ok(Network::Preprod),
+            "preview" => Ok(Network::Preview),
+            other => Err(anyhow!("unknown network '{}' (expected mainnet, preprod or preview)", other)),
+        }
+    }
+}
 
 /// Application configuration loaded from environment variables
 #[derive(Debug, Clone)]
 pub struct Config {
     pub cron_schedule: String,
-    pub shippo_api_key: String,
-    pub validator_script_ref: String,
-    pub oracle_sk: String,
-    pub oracle_pkh: String,
+    /// Timezone of `cron_schedule` and `submit_window`
+    pub cron_timezone: Tz,
+    /// Close transactions are only submitted inside this window
+    pub submit_window: Option<SubmitWindow>,
+    pub shippo_api_key: Secret<String>,
+    pub shippo_url: String,
+    /// Register carrier/tracking pairs Shippo does not know (404) instead of skipping them
+    pub shippo_auto_register: bool,
+    /// Most Shippo requests per second, unlimited if unset
+    pub shippo_max_rps: Option<u32>,
+    /// Most Shippo requests per minute, unlimited if unset
+    pub shippo_max_per_minute: Option<u32>,
+    /// Retries of a Shippo request answered with 429
+    pub shippo_max_retries: u32,
+    /// EasyPost API key; EasyPost tracking is disabled without it
+    pub easypost_api_key: Option<Secret<String>>,
+    pub easypost_url: String,
+    /// AfterShip API key; AfterShip tracking is disabled without it
+    pub aftership_api_key: Option<Secret<String>>,
+    pub aftership_url: String,
+    /// AfterShip slug of each (lowercase) carrier, over the built-in table
+    pub aftership_slugs: BTreeMap<String, String>,
+    /// Tracking provider of each (lowercase) carrier not prefixed with one; Shippo otherwise
+    pub carrier_providers: BTreeMap<String, ProviderKind>,
+    /// Concurrency, pacing and retries of each (lowercase) carrier's tracking requests
+    pub carrier_limits: BTreeMap<String, CarrierLimit>,
+    /// Carriers (lowercase) shipments may be tracked with; any carrier if unset
+    pub carrier_allowlist: Option<BTreeSet<String>>,
+    /// Carriers (lowercase) shipments are never tracked with
+    pub carrier_denylist: Option<BTreeSet<String>>,
+    /// Tracking provider of carriers neither prefixed nor in `carrier_providers`
+    pub tracking_provider: ProviderKind,
+    /// Tracking number statuses served by the mock provider before its suffix rule
+    pub mock_tracking_fixtures: Option<String>,
+    /// Seconds a non-final carrier status is reused before the provider is asked again; 0 disables the cache
+    pub tracking_cache_ttl_secs: u64,
+    /// Carrier statuses that close a shipment, and the on-chain status each one closes it with
+    pub status_mapping: StatusMapping,
+    /// Days a shipment may stay in one of `stale_statuses` before it is closed as NOT_DELIVERED (`None` never)
+    pub stale_status_timeout_days: Option<u32>,
+    /// Carrier statuses that go stale after `stale_status_timeout_days`
+    pub stale_statuses: BTreeSet<String>,
+    pub validator_script_ref: UtxoRef,
+    /// Where close transactions are signed
+    pub oracle_signer: SignerKind,
+    /// Empty with `ORACLE_SIGNER=kms` or when the key comes from `oracle_sk_file` or `oracle_mnemonic`
+    pub oracle_sks: Vec<Secret<String>>,
+    /// cardano-cli `payment.skey` file holding the oracle key
+    pub oracle_sk_file: Option<String>,
+    /// BIP-39 phrase the oracle key is derived from, at `1852'/1815'/0'/0/0`
+    pub oracle_mnemonic: Option<Secret<String>>,
+    pub oracle_pkh: Option<String>,
+    pub oracle_validator_keys: HashMap<String, String>,
+    /// AWS KMS key id or ARN, required with `ORACLE_SIGNER=kms`
+    pub kms_key_id: Option<String>,
     pub oracle_address: String,
     pub oracle_payment_address: String,
     pub blockfrost_url: String,
+    /// Sent as the `project_id` header on every Blockfrost request
+    pub blockfrost_project_id: Option<Secret<String>>,
+    /// Most pages of oracle address UTxOs read per scan
+    pub blockfrost_max_pages: u32,
+    /// Retries of a Blockfrost query answered with 429 or 5xx
+    pub blockfrost_max_retries: u32,
+    /// Delay before the first Blockfrost retry without `Retry-After`, doubled on each one after
+    pub blockfrost_base_backoff_ms: u64,
+    /// Longest delay between Blockfrost retries without `Retry-After`
+    pub blockfrost_max_backoff_ms: u64,
+    /// Where tracking UTxOs are discovered
+    pub chain_indexer: IndexerKind,
+    /// Kupo endpoint, required with `CHAIN_INDEXER=kupo`
+    pub kupo_url: Option<String>,
+    /// Whether every run lists the whole oracle address or only what changed since the last one
+    pub scan_mode: ScanMode,
+    /// Blocks re-read by every incremental scan in case they were rolled back
+    pub rollback_depth: u64,
+    /// Where signed close transactions are submitted
+    pub submitter: SubmitterKind,
+    /// Ogmios JSON-RPC endpoint, required with `SUBMITTER=ogmios`
+    pub ogmios_url: Option<String>,
+    /// Retries of a submission that failed on a network error, 5xx or 429
+    pub submit_max_retries: u32,
+    /// Delay before the first submission retry, doubled on each one after
+    pub submit_base_backoff_ms: u64,
     pub trp_url: String,
-    pub trp_api_key: Option<String>,
+    pub trp_api_key: Option<Secret<String>>,
+    pub notify_webhook_url: Option<String>,
+    pub notify_webhook_secret: Option<Secret<String>>,
+    pub notify_slack_webhook: Option<Secret<String>>,
+    pub notify_discord_webhook: Option<Secret<String>>,
+    /// Webhook body template, checked by `WebhookTemplate::parse`
+    pub notify_webhook_template: Option<String>,
+    /// Whether notifiers send a message per event or one per run
+    pub notify_mode: NotifyMode,
+    pub cardano_network: Option<Network>,
+    pub heartbeat_url: Option<String>,
+    /// Where the Prometheus `/metrics` server listens; disabled when `None`
+    pub metrics_addr: Option<SocketAddr>,
+    /// `outbox` metric label of each outbox address, over the address itself
+    pub outbox_aliases: BTreeMap<String, String>,
+    /// Distinct outbox addresses labelled in metrics before the others are labelled `other`
+    pub metrics_max_outboxes: usize,
+    /// Where the admin HTTP API listens; disabled when `None`
+    pub admin_addr: Option<SocketAddr>,
+    /// Bearer token every admin API request must carry
+    pub admin_token: Option<Secret<String>>,
+    pub sentry_dsn: Option<Secret<String>>,
+    pub state_db_path: Option<String>,
+    pub tracking_lookup_path: Option<String>,
+    /// Only close shipments into the outbox addresses listed in this file
+    pub outbox_allowlist_file: Option<String>,
+    /// Never close shipments into the outbox addresses listed in this file
+    pub outbox_denylist_file: Option<String>,
+    /// Close transactions with a higher fee are refused before signing
+    pub max_fee_lovelace: u64,
+    /// Shippo tracking calls per calendar month before runs poll only long-running transits
+    pub shippo_monthly_budget: Option<u64>,
+    /// Once the budget is spent, shipments in transit for longer than this are still polled
+    pub shippo_degraded_transit_hours: u64,
+    /// Expected hash of the reference script at `validator_script_ref`
+    pub validator_script_hash: Option<String>,
+    /// Refuse to start when the validator script cannot be verified on-chain
+    pub strict_startup: bool,
+    /// Check the tracking UTxO is still unspent right before closing it
+    pub recheck_before_sign: bool,
+    /// How long a shipment with a submitted close transaction is left alone while its UTxO stays unspent
+    pub pending_tx_ttl_minutes: u64,
+    /// Shipments a run checks at the same time
+    pub fetch_concurrency: usize,
+    /// Closes a run builds, signs and submits at the same time
+    pub close_concurrency: usize,
+    /// Closes waiting to be made before a run stops checking shipments
+    pub close_queue_depth: usize,
+    /// Run a self-test transaction before scheduling and refuse to start if it fails
+    pub self_test_on_start: bool,
+    /// Sign closes without submitting them
+    pub dry_run: bool,
+    /// How long runs in flight may take to finish after SIGTERM or Ctrl-C
+    pub shutdown_grace_seconds: u64,
+    /// What a run triggered while the previous one is still going does
+    pub overlap_policy: OverlapPolicy,
+    /// Schedule runs, or run once and exit
+    pub run_mode: RunMode,
+    /// Where the `p_timestamp` of a close comes from
+    pub timestamp_source: TimestampSource,
+    /// Datum codecs tried in order when decoding tracking datums (see `datum_codec`)
+    pub datum_codecs: Vec<String>,
+    /// Proxy for Shippo requests; credentials may be given as URL userinfo
+    pub http_proxy_shippo: Option<Secret<String>>,
+    /// Proxy for Blockfrost queries and transaction submission
+    pub http_proxy_blockfrost: Option<Secret<String>>,
+    /// Comma-separated hosts reached directly even when a proxy is configured
+    pub no_proxy: Option<String>,
+    /// Tenant name when loaded from a `TENANTS` file; labels logs, events and state
+    pub tenant: Option<String>,
 }
 
 impl Config {
     /// Load configuration from environment variables
     /// 
     /// # Environment Variables
+    /// - `RUN_MODE`: Optional - `scheduled` or `once` to run every pipeline once and exit (default: scheduled)
     /// - `CRON_SCHEDULE`: Optional - Cron expression (default: "0 */5 * * * *")
-    /// - `SHIPPO_API_KEY`: Required - Your Shippo API key
-    /// - `VALIDATOR_SCRIPT_REF`: Required - Reference script UTXO (TxHash#TxIx)
-    /// - `ORACLE_SK`: Required - Oracle signing key (hex-encoded)
-    /// - `ORACLE_PKH`: Required - Oracle public key (hex-encoded)
+    /// - `CRON_TIMEZONE`: Optional - IANA timezone of the cron schedule and submit window (default: UTC)
+    /// - `SUBMIT_WINDOW`: Optional - Local `HH:MM-HH:MM` window in which closures are submitted
+    /// - `SHIPPO_API_KEY`: Required unless no carrier is tracked through Shippo - Your Shippo API key
+    /// - `SHIPPO_URL`: Optional - Shippo API base URL (default: "https://api.goshippo.com")
+    /// - `SHIPPO_AUTO_REGISTER`: Optional - Register tracking numbers Shippo does not know yet (default: false)
+    /// - `SHIPPO_MAX_RPS`: Optional - Most Shippo requests per second (default: unlimited)
+    /// - `SHIPPO_MAX_PER_MINUTE`: Optional - Most Shippo requests per minute (default: unlimited)
+    /// - `SHIPPO_MAX_RETRIES`: Optional - Retries of a Shippo request answered with 429 (default: 3)
+    /// - `EASYPOST_API_KEY`: Optional - EasyPost API key, enables EasyPost tracking
+    /// - `EASYPOST_URL`: Optional - EasyPost API base URL (default: "https://api.easypost.com/v2")
+    /// - `AFTERSHIP_API_KEY`: Optional - AfterShip API key, enables AfterShip tracking
+    /// - `AFTERSHIP_URL`: Optional - AfterShip API base URL (default: "https://api.aftership.com")
+    /// - `AFTERSHIP_SLUGS`: Optional - Comma-separated `carrier=slug` pairs overriding the AfterShip slug of carriers
+    /// - `CARRIER_PROVIDERS`: Optional - Comma-separated `carrier=provider` pairs, e.g. `fedex=easypost` (default: all Shippo)
+    /// - `CARRIER_LIMITS`: Optional - JSON object of per-carrier `max_concurrency`, `min_interval_ms` and `max_retries`, e.g. `{"ups":{"max_concurrency":2,"min_interval_ms":500}}` (default: `FETCH_CONCURRENCY`, no interval, no extra retries)
+    /// - `CARRIER_ALLOWLIST`: Optional - Comma-separated carriers shipments may be tracked with (default: any)
+    /// - `CARRIER_DENYLIST`: Optional - Comma-separated carriers shipments are never tracked with
+    /// - `TRACKING_PROVIDER`: Optional - `shippo`, `easypost`, `aftership` or `mock`, provider of carriers not in `CARRIER_PROVIDERS` (default: shippo)
+    /// - `MOCK_TRACKING_FIXTURES`: Optional - JSON file of tracking number statuses for the mock provider
+    /// - `STATUS_MAP`: Optional - Comma-separated `carrier_status:on_chain_status` pairs closing shipments (default: "DELIVERED:DELIVERED,RETURNED:NOT_DELIVERED,FAILURE:NOT_DELIVERED")
+    /// - `STALE_STATUS_TIMEOUT_DAYS`: Optional - Days a shipment may stay in a `STALE_STATUSES` status before it is closed as NOT_DELIVERED (default: never)
+    /// - `STALE_STATUSES`: Optional - Comma-separated carrier statuses that go stale (default: "PRE_TRANSIT,UNKNOWN")
+    /// - `TRACKING_CACHE_TTL_SECS`: Optional - Seconds a non-final carrier status is reused before asking again (default: 0, off)
+    /// - `VALIDATOR_SCRIPT_REF`: Required - Reference script UTXO (TxHash#TxIx with a 64 hex character hash)
+    /// - `ORACLE_SIGNER`: Optional - `local` or `kms`, where close transactions are signed (default: local)
+    /// - `ORACLE_SKS`: Required unless another key source is set or `ORACLE_SIGNER` is kms - Comma-separated oracle signing keys (hex or key file paths)
+    /// - `ORACLE_SK`: Optional - Single oracle signing key (hex-encoded), used when `ORACLE_SKS` is not set
+    /// - `ORACLE_SK_FILE`: Optional - cardano-cli `PaymentSigningKeyShelley_ed25519` key file, instead of `ORACLE_SKS`
+    /// - `ORACLE_MNEMONIC`: Optional - BIP-39 phrase to derive the key from at `1852'/1815'/0'/0/0`, instead of `ORACLE_SKS`
+    /// - `ORACLE_PKH`: Optional - Key hash the configured validator expects (default: first key; required with kms)
+    /// - `ORACLE_VALIDATOR_KEYS`: Optional - Comma-separated `TxHash#TxIx=pkh` pairs selecting the key per validator
+    /// - `KMS_KEY_ID`: Optional - AWS KMS Ed25519 key id or ARN (required with `ORACLE_SIGNER=kms`)
     /// - `ORACLE_ADDRESS`: Required - Cardano oracle address
-    /// - `ORACLE_PAYMENT_ADDRESS`: Required - Oracle payment address
+    /// - `ORACLE_PAYMENT_ADDRESS`: Required - Oracle payment address, on the same network as `ORACLE_ADDRESS`
     /// - `BLOCKFROST_URL`: Required - Blockfrost API URL
+    /// - `BLOCKFROST_PROJECT_ID`: Optional - Blockfrost project ID, sent as the `project_id` header
+    /// - `BLOCKFROST_MAX_PAGES`: Optional - Most pages of 100 UTxOs read per oracle address scan (default: 100)
+    /// - `BLOCKFROST_MAX_RETRIES`: Optional - Retries of a Blockfrost query answered with 429 or 5xx (default: 3)
+    /// - `BLOCKFROST_BASE_BACKOFF_MS`: Optional - Delay before the first Blockfrost retry, doubled after each (default: 500)
+    /// - `BLOCKFROST_MAX_BACKOFF_MS`: Optional - Longest delay between Blockfrost retries (default: 30000)
+    /// - `CHAIN_INDEXER`: Optional - `blockfrost` or `kupo`, where tracking UTxOs are discovered (default: blockfrost)
+    /// - `KUPO_URL`: Optional - Kupo endpoint (required with `CHAIN_INDEXER=kupo`)
+    /// - `SCAN_MODE`: Optional - `full` or `incremental`, how the oracle address is scanned (default: full)
+    /// - `ROLLBACK_DEPTH`: Optional - Blocks re-read by incremental scans to catch rollbacks (default: 20)
+    /// - `SUBMITTER`: Optional - `blockfrost` or `ogmios`, where close transactions are submitted (default: blockfrost)
+    /// - `OGMIOS_URL`: Optional - Ogmios JSON-RPC endpoint (required with `SUBMITTER=ogmios`)
+    /// - `SUBMIT_MAX_RETRIES`: Optional - Retries of a submission that failed transiently (default: 3)
+    /// - `SUBMIT_BASE_BACKOFF_MS`: Optional - Delay before the first submission retry, doubled after each (default: 500)
     /// - `TRP_URL`: Required - TRP API URL
-    /// - `TRP_API_KEY`: Optional - TRP API key
+    /// - `TRP_API_KEY`: Optional - TRP API key sent as `dmtr-api-key`; unset or empty for a TRP without auth
+    /// - `NOTIFY_WEBHOOK_URL`: Optional - Webhook receiving shipment closure events
+    /// - `NOTIFY_WEBHOOK_SECRET`: Optional - Shared secret used to sign webhook payloads
+    /// - `NOTIFY_SLACK_WEBHOOK`: Optional - Slack incoming-webhook URL
+    /// - `NOTIFY_DISCORD_WEBHOOK`: Optional - Discord webhook URL
+    /// - `NOTIFY_WEBHOOK_TEMPLATE`: Optional - Webhook body with `{{name}}` placeholders (default: the event JSON)
+    /// - `NOTIFY_MODE`: Optional - `per_shipment` or `per_run`, how many messages a run sends (default: per_shipment)
+    /// - `CARDANO_NETWORK`: Optional - mainnet, preprod or preview; addresses and Blockfrost must match it (also used for explorer links)
+    /// - `HEARTBEAT_URL`: Optional - Monitoring URL pinged after every run
+    /// - `METRICS_ADDR`: Optional - `host:port` the Prometheus `/metrics` server listens on
+    /// - `OUTBOX_ALIASES`: Optional - Comma-separated `address=alias` pairs naming merchants in the `outbox` metric label
+    /// - `METRICS_MAX_OUTBOXES`: Optional - Outbox addresses labelled in metrics before the rest are `other` (default: 100)
+    /// - `ADMIN_ADDR`: Optional - `host:port` the admin HTTP API listens on (requires `ADMIN_TOKEN`)
+    /// - `ADMIN_TOKEN`: Optional - Bearer token for the admin HTTP API
+    /// - `SENTRY_DSN`: Optional - Sentry DSN (only used with the `sentry` feature)
+    /// - `STATE_DB_PATH`: Optional - SQLite file persisting shipment state across runs
+    /// - `DATABASE_URL`: Optional - `sqlite://<path>`, an alternative to `STATE_DB_PATH`
+    /// - `TRACKING_LOOKUP_PATH`: Optional - JSON file mapping privacy-mode tracking hashes to tracking numbers
+    /// - `OUTBOX_ALLOWLIST_FILE`: Optional - File of outbox addresses shipments may be closed into
+    /// - `OUTBOX_DENYLIST_FILE`: Optional - File of outbox addresses shipments are never closed into
+    /// - `MAX_FEE_LOVELACE`: Optional - Fee ceiling for close transactions (default: 2000000)
+    /// - `VALIDATOR_SCRIPT_HASH`: Optional - Script hash expected at `VALIDATOR_SCRIPT_REF`
+    /// - `STRICT_STARTUP`: Optional - Exit when the validator script check fails (default: false)
+    /// - `RECHECK_BEFORE_SIGN`: Optional - Re-check the tracking UTxO is unspent before each close (default: false)
+    /// - `PENDING_TX_TTL_MINUTES`: Optional - Minutes a submitted close is awaited before the shipment is polled again (default: 30)
+    /// - `FETCH_CONCURRENCY`: Optional - Shipments a run checks at the same time (default: 8)
+    /// - `CLOSE_CONCURRENCY`: Optional - Closes a run builds, signs and submits at the same time (default: 4)
+    /// - `CLOSE_QUEUE_DEPTH`: Optional - Closes waiting to be made before a run stops checking shipments (default: 64)
+    /// - `SHIPPO_MONTHLY_BUDGET`: Optional - Shippo tracking calls per month before polling is limited (needs `STATE_DB_PATH`)
+    /// - `SHIPPO_DEGRADED_TRANSIT_HOURS`: Optional - Transit age still polled once the budget is spent (default: 72)
+    /// - `SELF_TEST_ON_START`: Optional - Run a self-test before scheduling and exit if it fails (default: false)
+    /// - `DRY_RUN`: Optional - Sign the closes of scheduled runs without submitting them (default: false)
+    /// - `SHUTDOWN_GRACE_SECONDS`: Optional - Seconds runs in flight get to finish on SIGTERM or Ctrl-C (default: 25)
+    /// - `OVERLAP_POLICY`: Optional - `skip` or `queue` a run triggered while the previous one is going (default: skip)
+    /// - `TIMESTAMP_SOURCE`: Optional - `carrier` status date or `oracle` clock for the close timestamp (default: carrier)
+    /// - `DATUM_CODECS`: Optional - Comma-separated datum codecs tried in order (default: positional)
+    /// - `HTTP_PROXY_SHIPPO`: Optional - Proxy URL for Shippo requests
+    /// - `HTTP_PROXY_BLOCKFROST`: Optional - Proxy URL for Blockfrost queries and submission
+    /// - `NO_PROXY`: Optional - Comma-separated hosts that bypass the configured proxies
     pub fn from_env() -> Result<Self> {
+        Self::from_vars(|key| env::var(key).ok())
+    }
+
+    /// Load configuration from any key lookup using the environment variable names
+    pub fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
         // Parse cron schedule (optional, has default)
-        let cron_schedule = env::var("CRON_SCHEDULE")
-            .unwrap_or_else(|_| "0 */5 * * * *".to_string());
+        let cron_schedule = var("CRON_SCHEDULE")
+            .unwrap_or_else(|| "0 */5 * * * *".to_string());
 
-        // Parse API key (required)
-        let shippo_api_key = env::var("SHIPPO_API_KEY")
-            .context("SHIPPO_API_KEY not set")?;
-        
-        if shippo_api_key.trim().is_empty() {
-            bail!("SHIPPO_API_KEY cannot be empty");
+        // Parse cron timezone (optional, defaults to UTC)
+        let cron_timezone = match var("CRON_TIMEZONE") {
+            Some(timezone) => timezone
+                .trim()
+                .parse::<Tz>()
+                .map_err(|e| anyhow!("Invalid CRON_TIMEZONE: {}", e))?,
+            None => Tz::UTC,
+        };
+
+        // Parse submission window (optional)
+        let submit_window = var("SUBMIT_WINDOW")
+            .filter(|window| !window.trim().is_empty())
+            .map(|window| SubmitWindow::parse(&window, cron_timezone))
+            .transpose()
+            .context("Invalid SUBMIT_WINDOW")?;
+
+        // Parse Shippo base URL (optional, has default)
+        let shippo_url = var("SHIPPO_URL")
+            .unwrap_or_else(|| "https://api.goshippo.com".to_string())
+            .trim_end_matches('/')
+            .to_string();
+
+        // Parse Shippo auto-registration flag (optional, defaults to false)
+        let shippo_auto_register = match var("SHIPPO_AUTO_REGISTER") {
+            Some(value) => parse_bool(&value).context("SHIPPO_AUTO_REGISTER must be true or false")?,
+            None => false,
+        };
+
+        // Parse Shippo rate limits and retries (optional)
+        let shippo_max_rps = parse_rate_limit(var("SHIPPO_MAX_RPS"))
+            .context("SHIPPO_MAX_RPS must be a whole number of requests")?;
+        let shippo_max_per_minute = parse_rate_limit(var("SHIPPO_MAX_PER_MINUTE"))
+            .context("SHIPPO_MAX_PER_MINUTE must be a whole number of requests")?;
+        let shippo_max_retries = match var("SHIPPO_MAX_RETRIES") {
+            Some(value) => value
+                .trim()
+                .parse::<u32>()
+                .context("SHIPPO_MAX_RETRIES must be a whole number")?,
+            None => DEFAULT_SHIPPO_MAX_RETRIES,
+        };
+
+        // Parse EasyPost credentials and carrier routing (optional)
+        let easypost_api_key = var("EASYPOST_API_KEY").filter(|key| !key.trim().is_empty());
+        let easypost_url = var("EASYPOST_URL")
+            .unwrap_or_else(|| "https://api.easypost.com/v2".to_string())
+            .trim_end_matches('/')
+            .to_string();
+
+        // Parse AfterShip credentials and carrier slugs (optional)
+        let aftership_api_key = var("AFTERSHIP_API_KEY").filter(|key| !key.trim().is_empty());
+        let aftership_url = var("AFTERSHIP_URL")
+            .unwrap_or_else(|| "https://api.aftership.com".to_string())
+            .trim_end_matches('/')
+            .to_string();
+        let aftership_slugs = match var("AFTERSHIP_SLUGS") {
+            Some(value) => aftership::parse_slugs(&value).context("Invalid AFTERSHIP_SLUGS")?,
+            None => BTreeMap::new(),
+        };
+
+        let carrier_providers = match var("CARRIER_PROVIDERS") {
+            Some(value) => parse_carrier_providers(&value).context("Invalid CARRIER_PROVIDERS")?,
+            None => BTreeMap::new(),
+        };
+
+        let carrier_limits = match var("CARRIER_LIMITS") {
+            Some(value) => parse_carrier_limits(&value).context("Invalid CARRIER_LIMITS")?,
+            None => BTreeMap::new(),
+        };
+
+        // Parse carrier allowlist and denylist (optional, empty means unset)
+        let carrier_allowlist = var("CARRIER_ALLOWLIST").map(|value| parse_carriers(&value)).filter(|list| !list.is_empty());
+        let carrier_denylist = var("CARRIER_DENYLIST").map(|value| parse_carriers(&value)).filter(|list| !list.is_empty());
+
+        let tracking_provider: ProviderKind = match var("TRACKING_PROVIDER") {
+            Some(value) => value.parse().context("Invalid TRACKING_PROVIDER")?,
+            None => ProviderKind::Shippo,
+        };
+
+        let missing_key = |kind: ProviderKind| match kind {
+            ProviderKind::EasyPost if easypost_api_key.is_none() => Some("EASYPOST_API_KEY"),
+            ProviderKind::AfterShip if aftership_api_key.is_none() => Some("AFTERSHIP_API_KEY"),
+            _ => None,
+        };
+        if let Some(key) = missing_key(tracking_provider) {
+            bail!("TRACKING_PROVIDER={} needs {}", tracking_provider, key);
+        }
+        for (carrier, kind) in &carrier_providers {
+            if let Some(key) = missing_key(*kind) {
+                bail!("CARRIER_PROVIDERS routes {} to {}, which needs {}", carrier, kind, key);
+            }
+        }
+
+        let mock_tracking_fixtures = var("MOCK_TRACKING_FIXTURES").filter(|path| !path.trim().is_empty());
+
+        // Parse the carrier status to on-chain status mapping (optional, has default)
+        let status_mapping = match var("STATUS_MAP") {
+            Some(value) => value.parse().context("Invalid STATUS_MAP")?,
+            None => StatusMapping::default(),
+        };
+
+        // Parse the stale status timeout (optional, defaults to never)
+        let stale_status_timeout_days = match var("STALE_STATUS_TIMEOUT_DAYS") {
+            Some(value) => {
+                let days = value.trim().parse::<u32>().context("STALE_STATUS_TIMEOUT_DAYS must be a whole number of days")?;
+                if days == 0 {
+                    bail!("STALE_STATUS_TIMEOUT_DAYS must be at least 1");
+                }
+                Some(days)
+            }
+            None => None,
+        };
+
+        let stale_statuses = match var("STALE_STATUSES") {
+            Some(value) => parse_statuses(&value),
+            None => DEFAULT_STALE_STATUSES.iter().map(|status| status.to_string()).collect(),
+        };
+        if stale_status_timeout_days.is_some() && stale_statuses.is_empty() {
+            bail!("STALE_STATUSES must list at least one carrier status when STALE_STATUS_TIMEOUT_DAYS is set");
         }
 
+        // Parse tracking cache TTL (optional, defaults to no cache)
+        let tracking_cache_ttl_secs = match var("TRACKING_CACHE_TTL_SECS") {
+            Some(value) => value
+                .trim()
+                .parse::<u64>()
+                .context("TRACKING_CACHE_TTL_SECS must be a whole number of seconds")?,
+            None => 0,
+        };
+
+        // Parse Shippo API key (required unless no carrier is tracked through Shippo)
+        let uses_shippo = tracking_provider == ProviderKind::Shippo
+            || carrier_providers.values().any(|kind| *kind == ProviderKind::Shippo);
+        let shippo_api_key = match var("SHIPPO_API_KEY") {
+            Some(key) if !key.trim().is_empty() => key,
+            Some(_) if uses_shippo => bail!("SHIPPO_API_KEY cannot be empty"),
+            None if uses_shippo => bail!("SHIPPO_API_KEY not set"),
+            _ => String::new(),
+        };
+
         // Parse validator script reference (required)
-        let validator_script_ref = env::var("VALIDATOR_SCRIPT_REF")
-            .context("VALIDATOR_SCRIPT_REF not set")?;
-        
-        if validator_script_ref.trim().is_empty() {
-            bail!("VALIDATOR_SCRIPT_REF cannot be empty");
+        let validator_script_ref = var("VALIDATOR_SCRIPT_REF")
+            .context("VALIDATOR_SCRIPT_REF not set")?
+            .trim()
+            .parse()
+            .context("Invalid VALIDATOR_SCRIPT_REF")?;
+
+        // Parse oracle signer (optional, defaults to local keys)
+        let oracle_signer = match var("ORACLE_SIGNER") {
+            Some(value) => value.parse().context("Invalid ORACLE_SIGNER")?,
+            None => SignerKind::Local,
+        };
+
+        // Parse oracle signing keys (exactly one source with local signing)
+        let oracle_sks_var = var("ORACLE_SKS").or_else(|| var("ORACLE_SK"));
+        let oracle_sk_file = var("ORACLE_SK_FILE")
+            .map(|path| path.trim().to_string())
+            .filter(|path| !path.is_empty());
+        let oracle_mnemonic = var("ORACLE_MNEMONIC")
+            .map(|phrase| phrase.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|phrase| !phrase.is_empty());
+
+        if oracle_signer == SignerKind::Local {
+            let sources = [oracle_sks_var.is_some(), oracle_sk_file.is_some(), oracle_mnemonic.is_some()];
+            match sources.into_iter().filter(|set| *set).count() {
+                0 => bail!("No oracle signing key: set ORACLE_SKS (or ORACLE_SK), ORACLE_SK_FILE or ORACLE_MNEMONIC"),
+                1 => {}
+                _ => bail!("Set only one of ORACLE_SKS (or ORACLE_SK), ORACLE_SK_FILE and ORACLE_MNEMONIC"),
+            }
         }
 
-        // Parse oracle signing key (required)
-        let oracle_sk = env::var("ORACLE_SK")
-            .context("ORACLE_SK not set")?;
-        
-        if oracle_sk.trim().is_empty() {
-            bail!("ORACLE_SK cannot be empty");
+        let oracle_sks: Vec<String> = oracle_sks_var
+            .map(|keys| {
+                keys.split(',')
+                    .map(|key| key.trim().to_string())
+                    .filter(|key| !key.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if oracle_signer == SignerKind::Local && oracle_sk_file.is_none() && oracle_mnemonic.is_none() && oracle_sks.is_empty() {
+            bail!("ORACLE_SKS cannot be empty");
         }
 
-        // Parse oracle public key (required)
-        let oracle_pkh = env::var("ORACLE_PKH")
-            .context("ORACLE_PKH not set")?;
-        
-        if oracle_pkh.trim().is_empty() {
+        // Parse oracle public key hash (optional, defaults to the first key)
+        let oracle_pkh = var("ORACLE_PKH");
+
+        if let Some(ref pkh) = oracle_pkh
+            && pkh.trim().is_empty()
+        {
             bail!("ORACLE_PKH cannot be empty");
         }
 
+        let kms_key_id = var("KMS_KEY_ID")
+            .map(|key_id| key_id.trim().to_string())
+            .filter(|key_id| !key_id.is_empty());
+
+        if oracle_signer == SignerKind::Kms {
+            if kms_key_id.is_none() {
+                bail!("ORACLE_SIGNER=kms needs KMS_KEY_ID");
+            }
+            if oracle_pkh.is_none() {
+                bail!("ORACLE_SIGNER=kms needs ORACLE_PKH, the hash of the KMS key");
+            }
+        }
+
+        // Parse per-validator key selection (optional)
+        let oracle_validator_keys = match var("ORACLE_VALIDATOR_KEYS") {
+            Some(pairs) => parse_validator_keys(&pairs).context("Invalid ORACLE_VALIDATOR_KEYS")?,
+            None => HashMap::new(),
+        };
+
         // Parse oracle address (required)
-        let oracle_address = env::var("ORACLE_ADDRESS")
+        let oracle_address = var("ORACLE_ADDRESS")
             .context("ORACLE_ADDRESS not set")?;
         
         if oracle_address.trim().is_empty() {
@@ -76,7 +615,7 @@ impl Config {
         }
 
         // Parse oracle payment address (required)
-        let oracle_payment_address = env::var("ORACLE_PAYMENT_ADDRESS")
+        let oracle_payment_address = var("ORACLE_PAYMENT_ADDRESS")
             .context("ORACLE_PAYMENT_ADDRESS not set")?;
 
         if oracle_payment_address.trim().is_empty() {
@@ -84,41 +623,665 @@ impl Config {
         }
 
         // Parse Blockfrost URL (required)
-        let blockfrost_url = env::var("BLOCKFROST_URL")
+        let blockfrost_url = var("BLOCKFROST_URL")
             .context("BLOCKFROST_URL not set (required when OUTPUT_MODE is cardano)")?;
         
         if blockfrost_url.trim().is_empty() {
             bail!("BLOCKFROST_URL cannot be empty");
         }
 
+        // Parse Blockfrost project ID (optional; proxies may add it instead)
+        let blockfrost_project_id = var("BLOCKFROST_PROJECT_ID");
+
+        if let Some(ref project_id) = blockfrost_project_id {
+            if project_id.trim().is_empty() {
+                bail!("BLOCKFROST_PROJECT_ID cannot be empty");
+            }
+        }
+
+        // Parse Blockfrost page limit (optional, has default)
+        let blockfrost_max_pages = match var("BLOCKFROST_MAX_PAGES") {
+            Some(value) => value
+                .trim()
+                .parse::<u32>()
+                .context("BLOCKFROST_MAX_PAGES must be a whole number of pages")?,
+            None => DEFAULT_BLOCKFROST_MAX_PAGES,
+        };
+
+        if blockfrost_max_pages == 0 {
+            bail!("BLOCKFROST_MAX_PAGES must be at least 1");
+        }
+
+        // Parse Blockfrost retry policy (optional, has defaults)
+        let blockfrost_max_retries = match var("BLOCKFROST_MAX_RETRIES") {
+            Some(value) => value
+                .trim()
+                .parse::<u32>()
+                .context("BLOCKFROST_MAX_RETRIES must be a whole number")?,
+            None => DEFAULT_BLOCKFROST_MAX_RETRIES,
+        };
+
+        let blockfrost_base_backoff_ms = match var("BLOCKFROST_BASE_BACKOFF_MS") {
+            Some(value) => value
+                .trim()
+                .parse::<u64>()
+                .context("BLOCKFROST_BASE_BACKOFF_MS must be a whole number of milliseconds")?,
+            None => DEFAULT_BLOCKFROST_BASE_BACKOFF_MS,
+        };
+
+        let blockfrost_max_backoff_ms = match var("BLOCKFROST_MAX_BACKOFF_MS") {
+            Some(value) => value
+                .trim()
+                .parse::<u64>()
+                .context("BLOCKFROST_MAX_BACKOFF_MS must be a whole number of milliseconds")?,
+            None => DEFAULT_BLOCKFROST_MAX_BACKOFF_MS,
+        };
+
+        // Parse chain indexer (optional, defaults to Blockfrost)
+        let chain_indexer = match var("CHAIN_INDEXER") {
+            Some(value) => value.parse().context("Invalid CHAIN_INDEXER")?,
+            None => IndexerKind::Blockfrost,
+        };
+
+        let kupo_url = var("KUPO_URL")
+            .map(|url| url.trim().trim_end_matches('/').to_string())
+            .filter(|url| !url.is_empty());
+
+        if chain_indexer == IndexerKind::Kupo && kupo_url.is_none() {
+            bail!("CHAIN_INDEXER=kupo needs KUPO_URL");
+        }
+
+        // Parse transaction submitter (optional, defaults to Blockfrost)
+        let submitter = match var("SUBMITTER") {
+            Some(value) => value.parse().context("Invalid SUBMITTER")?,
+            None => SubmitterKind::Blockfrost,
+        };
+
+        let ogmios_url = var("OGMIOS_URL")
+            .map(|url| url.trim().trim_end_matches('/').to_string())
+            .filter(|url| !url.is_empty());
+
+        if submitter == SubmitterKind::Ogmios && ogmios_url.is_none() {
+            bail!("SUBMITTER=ogmios needs OGMIOS_URL");
+        }
+
+        let submit_max_retries = match var("SUBMIT_MAX_RETRIES") {
+            Some(value) => value
+                .trim()
+                .parse::<u32>()
+                .context("SUBMIT_MAX_RETRIES must be a whole number")?,
+            None => DEFAULT_SUBMIT_MAX_RETRIES,
+        };
+
+        let submit_base_backoff_ms = match var("SUBMIT_BASE_BACKOFF_MS") {
+            Some(value) => value
+                .trim()
+                .parse::<u64>()
+                .context("SUBMIT_BASE_BACKOFF_MS must be a whole number of milliseconds")?,
+            None => DEFAULT_SUBMIT_BASE_BACKOFF_MS,
+        };
+
         // Parse TRP URL (required)
-        let trp_url = env::var("TRP_URL")
+        let trp_url = var("TRP_URL")
             .context("TRP_URL not set")?;
         
         if trp_url.trim().is_empty() {
             bail!("TRP_URL cannot be empty");
         }
 
-        // Parse TRP API key (optional)
-        let trp_api_key = env::var("TRP_API_KEY").ok();
-        
-        if let Some(ref key) = trp_api_key {
-            if key.trim().is_empty() {
-                bail!("TRP_API_KEY cannot be empty");
+        // Parse TRP API key (optional, empty means a TRP without auth)
+        let trp_api_key = var("TRP_API_KEY")
+            .map(|key| key.trim().to_string())
+            .filter(|key| !key.is_empty());
+
+        // Parse notification webhook URL (optional)
+        let notify_webhook_url = var("NOTIFY_WEBHOOK_URL");
+
+        if let Some(ref url) = notify_webhook_url
+            && url.trim().is_empty()
+        {
+            bail!("NOTIFY_WEBHOOK_URL cannot be empty");
+        }
+
+        // Parse notification webhook secret (optional)
+        let notify_webhook_secret = var("NOTIFY_WEBHOOK_SECRET");
+
+        if let Some(ref secret) = notify_webhook_secret
+            && secret.trim().is_empty()
+        {
+            bail!("NOTIFY_WEBHOOK_SECRET cannot be empty");
+        }
+
+        // Parse Slack webhook URL (optional)
+        let notify_slack_webhook = var("NOTIFY_SLACK_WEBHOOK");
+
+        if let Some(ref url) = notify_slack_webhook
+            && url.trim().is_empty()
+        {
+            bail!("NOTIFY_SLACK_WEBHOOK cannot be empty");
+        }
+
+        // Parse Discord webhook URL (optional)
+        let notify_discord_webhook = var("NOTIFY_DISCORD_WEBHOOK");
+
+        if let Some(ref url) = notify_discord_webhook
+            && url.trim().is_empty()
+        {
+            bail!("NOTIFY_DISCORD_WEBHOOK cannot be empty");
+        }
+
+        // Parse webhook template (optional, defaults to the event JSON)
+        let notify_webhook_template = var("NOTIFY_WEBHOOK_TEMPLATE").filter(|template| !template.trim().is_empty());
+        if let Some(template) = &notify_webhook_template {
+            WebhookTemplate::parse(template).context("Invalid NOTIFY_WEBHOOK_TEMPLATE")?;
+        }
+
+        // Parse notification mode (optional, defaults to a message per shipment)
+        let notify_mode = match var("NOTIFY_MODE") {
+            Some(value) => value.parse().context("Invalid NOTIFY_MODE")?,
+            None => NotifyMode::PerShipment,
+        };
+
+        // Parse Cardano network (optional)
+        let cardano_network = var("CARDANO_NETWORK")
+            .map(|network| network.parse::<Network>())
+            .transpose()
+            .context("Invalid CARDANO_NETWORK")?;
+
+        // Parse heartbeat URL (optional)
+        let heartbeat_url = var("HEARTBEAT_URL");
+
+        if let Some(ref url) = heartbeat_url
+            && url.trim().is_empty()
+        {
+            bail!("HEARTBEAT_URL cannot be empty");
+        }
+
+        // Parse metrics server address (optional)
+        let metrics_addr = var("METRICS_ADDR")
+            .map(|addr| addr.trim().parse::<SocketAddr>())
+            .transpose()
+            .context("Invalid METRICS_ADDR")?;
+
+        // Parse merchant aliases and the outbox label cap (optional)
+        let outbox_aliases = match var("OUTBOX_ALIASES") {
+            Some(value) => parse_outbox_aliases(&value).context("Invalid OUTBOX_ALIASES")?,
+            None => BTreeMap::new(),
+        };
+        let metrics_max_outboxes = match var("METRICS_MAX_OUTBOXES") {
+            Some(value) => value
+                .trim()
+                .parse::<usize>()
+                .context("METRICS_MAX_OUTBOXES must be a whole number")?,
+            None => DEFAULT_METRICS_MAX_OUTBOXES,
+        };
+
+        // Parse admin API address and token (optional, the address needs a token)
+        let admin_addr = var("ADMIN_ADDR")
+            .map(|addr| addr.trim().parse::<SocketAddr>())
+            .transpose()
+            .context("Invalid ADMIN_ADDR")?;
+        let admin_token = var("ADMIN_TOKEN")
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty());
+
+        if admin_addr.is_some() && admin_token.is_none() {
+            bail!("ADMIN_TOKEN is required when ADMIN_ADDR is set");
+        }
+
+        // Parse Sentry DSN (optional)
+        let sentry_dsn = var("SENTRY_DSN");
+
+        if let Some(ref dsn) = sentry_dsn
+            && dsn.trim().is_empty()
+        {
+            bail!("SENTRY_DSN cannot be empty");
+        }
+
+        // Parse state database path (optional), given directly or as a sqlite:// URL
+        let state_db_path = var("STATE_DB_PATH");
+
+        if let Some(ref path) = state_db_path
+            && path.trim().is_empty()
+        {
+            bail!("STATE_DB_PATH cannot be empty");
+        }
+
+        let state_db_path = match (state_db_path, var("DATABASE_URL")) {
+            (Some(_), Some(_)) => bail!("Set either STATE_DB_PATH or DATABASE_URL, not both"),
+            (None, Some(url)) => Some(crate::state::sqlite_path(&url).context("Invalid DATABASE_URL")?),
+            (path, None) => path,
+        };
+
+        // Parse tracking lookup file path (optional)
+        let tracking_lookup_path = var("TRACKING_LOOKUP_PATH");
+
+        if let Some(ref path) = tracking_lookup_path
+            && path.trim().is_empty()
+        {
+            bail!("TRACKING_LOOKUP_PATH cannot be empty");
+        }
+
+        // Parse outbox address lists (optional)
+        let outbox_allowlist_file = var("OUTBOX_ALLOWLIST_FILE");
+
+        if let Some(ref path) = outbox_allowlist_file
+            && path.trim().is_empty()
+        {
+            bail!("OUTBOX_ALLOWLIST_FILE cannot be empty");
+        }
+
+        let outbox_denylist_file = var("OUTBOX_DENYLIST_FILE");
+
+        if let Some(ref path) = outbox_denylist_file
+            && path.trim().is_empty()
+        {
+            bail!("OUTBOX_DENYLIST_FILE cannot be empty");
+        }
+
+        // Parse fee ceiling (optional, has default)
+        let max_fee_lovelace = match var("MAX_FEE_LOVELACE") {
+            Some(value) => value
+                .trim()
+                .replace('_', "")
+                .parse::<u64>()
+                .context("MAX_FEE_LOVELACE must be a whole number of lovelace")?,
+            None => DEFAULT_MAX_FEE_LOVELACE,
+        };
+
+        // Parse Shippo budget (optional); counting across restarts needs the state database
+        let shippo_monthly_budget = match var("SHIPPO_MONTHLY_BUDGET") {
+            Some(value) => Some(
+                value
+                    .trim()
+                    .replace('_', "")
+                    .parse::<u64>()
+                    .context("SHIPPO_MONTHLY_BUDGET must be a whole number of tracking calls")?,
+            ),
+            None => None,
+        };
+
+        if shippo_monthly_budget.is_some() && state_db_path.is_none() {
+            bail!("SHIPPO_MONTHLY_BUDGET needs STATE_DB_PATH to count tracking calls");
+        }
+
+        let scan_mode = match var("SCAN_MODE") {
+            Some(value) => value.parse().context("Invalid SCAN_MODE")?,
+            None => ScanMode::default(),
+        };
+
+        if scan_mode == ScanMode::Incremental {
+            if state_db_path.is_none() {
+                bail!("SCAN_MODE=incremental needs STATE_DB_PATH to keep the scan position");
+            }
+            if chain_indexer != IndexerKind::Blockfrost {
+                bail!("SCAN_MODE=incremental needs CHAIN_INDEXER=blockfrost");
             }
         }
 
-        Ok(Config {
+        let rollback_depth = match var("ROLLBACK_DEPTH") {
+            Some(value) => value
+                .trim()
+                .parse::<u64>()
+                .context("ROLLBACK_DEPTH must be a whole number of blocks")?,
+            None => DEFAULT_ROLLBACK_DEPTH,
+        };
+
+        let shippo_degraded_transit_hours = match var("SHIPPO_DEGRADED_TRANSIT_HOURS") {
+            Some(value) => value
+                .trim()
+                .parse::<u64>()
+                .context("SHIPPO_DEGRADED_TRANSIT_HOURS must be a whole number of hours")?,
+            None => DEFAULT_DEGRADED_TRANSIT_HOURS,
+        };
+
+        // Parse expected validator script hash (optional)
+        let validator_script_hash = var("VALIDATOR_SCRIPT_HASH").map(|hash| hash.trim().to_lowercase());
+
+        // Parse strict startup flag (optional, defaults to false)
+        let strict_startup = match var("STRICT_STARTUP") {
+            Some(value) => parse_bool(&value).context("STRICT_STARTUP must be true or false")?,
+            None => false,
+        };
+
+        // Parse just-in-time liveness check flag (optional, defaults to false)
+        let recheck_before_sign = match var("RECHECK_BEFORE_SIGN") {
+            Some(value) => parse_bool(&value).context("RECHECK_BEFORE_SIGN must be true or false")?,
+            None => false,
+        };
+
+        // Parse pending close transaction TTL (optional, defaults to 30 minutes)
+        let pending_tx_ttl_minutes = match var("PENDING_TX_TTL_MINUTES") {
+            Some(value) => value
+                .trim()
+                .parse::<u64>()
+                .context("PENDING_TX_TTL_MINUTES must be a whole number of minutes")?,
+            None => DEFAULT_PENDING_TX_TTL_MINUTES,
+        };
+
+        // Parse shipment concurrency (optional, defaults to 8)
+        let fetch_concurrency = match var("FETCH_CONCURRENCY") {
+            Some(value) => value
+                .trim()
+                .parse::<usize>()
+                .context("FETCH_CONCURRENCY must be a whole number")?,
+            None => DEFAULT_FETCH_CONCURRENCY,
+        };
+
+        if fetch_concurrency == 0 {
+            bail!("FETCH_CONCURRENCY must be at least 1");
+        }
+
+        // Parse close concurrency and queue depth (optional, default to 4 and 64)
+        let close_concurrency = match var("CLOSE_CONCURRENCY") {
+            Some(value) => value
+                .trim()
+                .parse::<usize>()
+                .context("CLOSE_CONCURRENCY must be a whole number")?,
+            None => DEFAULT_CLOSE_CONCURRENCY,
+        };
+
+        if close_concurrency == 0 {
+            bail!("CLOSE_CONCURRENCY must be at least 1");
+        }
+
+        let close_queue_depth = match var("CLOSE_QUEUE_DEPTH") {
+            Some(value) => value
+                .trim()
+                .parse::<usize>()
+                .context("CLOSE_QUEUE_DEPTH must be a whole number")?,
+            None => DEFAULT_CLOSE_QUEUE_DEPTH,
+        };
+
+        if close_queue_depth == 0 {
+            bail!("CLOSE_QUEUE_DEPTH must be at least 1");
+        }
+
+        // Parse self-test flag (optional, defaults to false)
+        let self_test_on_start = match var("SELF_TEST_ON_START") {
+            Some(value) => parse_bool(&value).context("SELF_TEST_ON_START must be true or false")?,
+            None => false,
+        };
+
+        // Parse dry-run flag (optional, defaults to false)
+        let dry_run = match var("DRY_RUN") {
+            Some(value) => parse_bool(&value).context("DRY_RUN must be true or false")?,
+            None => false,
+        };
+
+        // Parse shutdown grace period (optional, defaults to 25 seconds)
+        let shutdown_grace_seconds = match var("SHUTDOWN_GRACE_SECONDS") {
+            Some(value) => value
+                .trim()
+                .parse::<u64>()
+                .context("SHUTDOWN_GRACE_SECONDS must be a whole number of seconds")?,
+            None => DEFAULT_SHUTDOWN_GRACE_SECONDS,
+        };
+
+        // Parse overlapping run policy (optional, defaults to skipping)
+        let overlap_policy = match var("OVERLAP_POLICY") {
+            Some(value) => value.parse().context("Invalid OVERLAP_POLICY")?,
+            None => OverlapPolicy::Skip,
+        };
+
+        // Parse run mode (optional, defaults to scheduled runs)
+        let run_mode = match var("RUN_MODE") {
+            Some(value) => value.parse().context("Invalid RUN_MODE")?,
+            None => RunMode::Scheduled,
+        };
+
+        // Parse timestamp source (optional, defaults to the carrier status date)
+        let timestamp_source = match var("TIMESTAMP_SOURCE") {
+            Some(value) => value.parse().context("Invalid TIMESTAMP_SOURCE")?,
+            None => TimestampSource::Carrier,
+        };
+
+        // Parse datum codecs (optional, defaults to the positional layout)
+        let datum_codecs = match var("DATUM_CODECS") {
+            Some(value) => value.split(',').map(|name| name.trim().to_string()).collect(),
+            None => vec!["positional".to_string()],
+        };
+        CodecRegistry::from_names(&datum_codecs).context("Invalid DATUM_CODECS")?;
+
+        // Parse per-client proxies (optional)
+        let http_proxy_shippo = parse_proxy(var("HTTP_PROXY_SHIPPO")).context("Invalid HTTP_PROXY_SHIPPO")?;
+        let http_proxy_blockfrost =
+            parse_proxy(var("HTTP_PROXY_BLOCKFROST")).context("Invalid HTTP_PROXY_BLOCKFROST")?;
+        let no_proxy = var("NO_PROXY").filter(|hosts| !hosts.trim().is_empty());
+
+        let config = Config {
             cron_schedule,
-            shippo_api_key,
+            cron_timezone,
+            submit_window,
+            shippo_api_key: Secret::new(shippo_api_key),
+            shippo_url,
+            shippo_auto_register,
+            shippo_max_rps,
+            shippo_max_per_minute,
+            shippo_max_retries,
+            easypost_api_key: easypost_api_key.map(Secret::new),
+            easypost_url,
+            aftership_api_key: aftership_api_key.map(Secret::new),
+            aftership_url,
+            aftership_slugs,
+            carrier_providers,
+            carrier_limits,
+            carrier_allowlist,
+            carrier_denylist,
+            tracking_provider,
+            mock_tracking_fixtures,
+            tracking_cache_ttl_secs,
+            status_mapping,
+            stale_status_timeout_days,
+            stale_statuses,
             validator_script_ref,
-            oracle_sk,
+            oracle_signer,
+            oracle_sks: oracle_sks.into_iter().map(Secret::new).collect(),
+            oracle_sk_file,
+            oracle_mnemonic: oracle_mnemonic.map(Secret::new),
             oracle_pkh,
+            oracle_validator_keys,
+            kms_key_id,
             oracle_address,
             oracle_payment_address,
             blockfrost_url,
+            blockfrost_project_id: blockfrost_project_id.map(Secret::new),
+            blockfrost_max_pages,
+            blockfrost_max_retries,
+            blockfrost_base_backoff_ms,
+            blockfrost_max_backoff_ms,
+            chain_indexer,
+            kupo_url,
+            scan_mode,
+            rollback_depth,
+            submitter,
+            ogmios_url,
+            submit_max_retries,
+            submit_base_backoff_ms,
             trp_url,
-            trp_api_key,
-        })
+            trp_api_key: trp_api_key.map(Secret::new),
+            notify_webhook_url,
+            notify_webhook_secret: notify_webhook_secret.map(Secret::new),
+            notify_slack_webhook: notify_slack_webhook.map(Secret::new),
+            notify_discord_webhook: notify_discord_webhook.map(Secret::new),
+            notify_webhook_template,
+            notify_mode,
+            cardano_network,
+            heartbeat_url,
+            metrics_addr,
+            outbox_aliases,
+            metrics_max_outboxes,
+            admin_addr,
+            admin_token: admin_token.map(Secret::new),
+            sentry_dsn: sentry_dsn.map(Secret::new),
+            state_db_path,
+            tracking_lookup_path,
+            outbox_allowlist_file,
+            outbox_denylist_file,
+            max_fee_lovelace,
+            shippo_monthly_budget,
+            shippo_degraded_transit_hours,
+            validator_script_hash,
+            strict_startup,
+            recheck_before_sign,
+            pending_tx_ttl_minutes,
+            fetch_concurrency,
+            close_concurrency,
+            close_queue_depth,
+            self_test_on_start,
+            dry_run,
+            shutdown_grace_seconds,
+            overlap_policy,
+            run_mode,
+            timestamp_source,
+            datum_codecs,
+            http_proxy_shippo: http_proxy_shippo.map(Secret::new),
+            http_proxy_blockfrost: http_proxy_blockfrost.map(Secret::new),
+            no_proxy,
+            tenant: None,
+        };
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Check addresses, hashes, keys and URLs parse, naming the variable that does not
+    pub fn validate(&self) -> Result<()> {
+        for (name, address) in [("ORACLE_ADDRESS", &self.oracle_address), ("ORACLE_PAYMENT_ADDRESS", &self.oracle_payment_address)] {
+            let parsed = Address::from_bech32(address.trim())
+                .map_err(|e| anyhow!("{} is not a bech32 address ({}): {}", name, address, e))?;
+
+            // A preprod address with a mainnet Blockfrost URL, or the other way round, must not start
+            if let (Some(network), Some(address_network)) = (self.cardano_network, parsed.network()) {
+                let mainnet = address_network == AddressNetwork::Mainnet;
+                if mainnet != network.is_mainnet() {
+                    bail!(
+                        "{} is a {} address ({}) but CARDANO_NETWORK is {}",
+                        name,
+                        if mainnet { "mainnet" } else { "testnet" },
+                        address,
+                        network.as_str()
+                    );
+                }
+            }
+        }
+
+        if let Some(hash) = &self.validator_script_hash
+            && !is_hex_bytes(hash, 28)
+        {
+            bail!("VALIDATOR_SCRIPT_HASH must be a hex-encoded 28-byte script hash");
+        }
+        if let Some(pkh) = &self.oracle_pkh
+            && !is_hex_bytes(pkh, 28)
+        {
+            bail!("ORACLE_PKH must be a hex-encoded 28-byte key hash, got '{}'", pkh.trim());
+        }
+        for (validator, pkh) in &self.oracle_validator_keys {
+            if !is_hex_bytes(pkh, 28) {
+                bail!("ORACLE_VALIDATOR_KEYS pkh of {} must be a hex-encoded 28-byte key hash, got '{}'", validator, pkh);
+            }
+        }
+        // Keys are never echoed back, only their position; key files are read by the keyring
+        for (i, key) in self.oracle_sks.iter().enumerate() {
+            if !is_hex_bytes(key.expose(), 32) && !Path::new(key.expose()).is_file() {
+                bail!("ORACLE_SKS key {} must be a hex-encoded 32-byte signing key or a key file", i + 1);
+            }
+        }
+
+        for (name, url) in [("BLOCKFROST_URL", &self.blockfrost_url), ("TRP_URL", &self.trp_url)] {
+            reqwest::Url::parse(url.trim()).map_err(|e| anyhow!("{} is not a URL ({}): {}", name, url, e))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether `value` is hex for exactly `len` bytes
+fn is_hex_bytes(value: &str, len: usize) -> bool {
+    matches!(hex::decode(value.trim()), Ok(bytes) if bytes.len() == len)
+}
+
+/// An optional proxy URL; empty means no proxy
+fn parse_proxy(value: Option<String>) -> Result<Option<String>> {
+    let Some(value) = value.map(|value| value.trim().to_string()).filter(|value| !value.is_empty()) else {
+        return Ok(None);
+    };
+
+    let url = reqwest::Url::parse(&value).map_err(|e| anyhow!("expected a proxy URL such as http://proxy:3128 ({})", e))?;
+    if url.host_str().is_none() {
+        bail!("proxy URL has no host");
     }
+
+    Ok(Some(value))
+}
+
+/// An optional request rate; empty means unlimited
+fn parse_rate_limit(value: Option<String>) -> Result<Option<u32>> {
+    let Some(value) = value.map(|value| value.trim().to_string()).filter(|value| !value.is_empty()) else {
+        return Ok(None);
+    };
+
+    match value.parse::<u32>()? {
+        0 => bail!("expected at least 1, got 0"),
+        limit => Ok(Some(limit)),
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "1" | "yes" => Ok(true),
+        "false" | "0" | "no" | "" => Ok(false),
+        other => bail!("expected a boolean, got '{}'", other),
+    }
+}
+
+/// Parse `address=alias` pairs separated by commas
+///
+/// Addresses must be bech32, and `other` is kept for the addresses over `METRICS_MAX_OUTBOXES`.
+fn parse_outbox_aliases(pairs: &str) -> Result<BTreeMap<String, String>> {
+    let mut aliases = BTreeMap::new();
+
+    for pair in pairs.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+        let Some((address, alias)) = pair.split_once('=') else {
+            bail!("expected address=alias, got '{}'", pair);
+        };
+
+        let (address, alias) = (address.trim(), alias.trim());
+        if address.is_empty() || alias.is_empty() {
+            bail!("expected address=alias, got '{}'", pair);
+        }
+        Address::from_bech32(address).map_err(|e| anyhow!("{} is not a bech32 address: {}", address, e))?;
+        if alias == OTHER_OUTBOX {
+            bail!("{} cannot be aliased '{}', the label of unlisted outboxes over the cap", address, OTHER_OUTBOX);
+        }
+
+        if aliases.insert(address.to_string(), alias.to_string()).is_some() {
+            bail!("outbox {} is listed more than once", address);
+        }
+    }
+
+    Ok(aliases)
+}
+
+/// Parse `TxHash#TxIx=pkh` pairs separated by commas
+fn parse_validator_keys(pairs: &str) -> Result<HashMap<String, String>> {
+    let mut validator_keys = HashMap::new();
+
+    for pair in pairs.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+        let Some((validator, pkh)) = pair.split_once('=') else {
+            bail!("expected TxHash#TxIx=pkh, got '{}'", pair);
+        };
+
+        let (validator, pkh) = (validator.trim(), pkh.trim());
+        if validator.is_empty() || pkh.is_empty() {
+            bail!("expected TxHash#TxIx=pkh, got '{}'", pair);
+        }
+        let validator = validator.parse::<UtxoRef>()?.to_string();
+
+        if validator_keys.insert(validator.clone(), pkh.to_lowercase()).is_some() {
+            bail!("validator {} is listed more than once", validator);
+        }
+    }
+
+    Ok(validator_keys)
 }
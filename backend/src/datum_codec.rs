@@ -0,0 +1,388 @@
+use anyhow::{Result, bail};
+use pallas::codec::minicbor;
+use pallas::ledger::addresses::Address;
+use pallas::ledger::primitives::{BigInt, PlutusData};
+use std::fmt;
+
+use crate::models::{TrackingDatum, TrackingNumber};
+
+/// Upper bound on the decoded size of a tracking datum (real datums are ~110 bytes)
+pub const MAX_DATUM_BYTES: usize = 1024;
+
+/// Upper bound on CBOR nesting, checked before the recursive PlutusData
+/// decoder runs so hostile on-chain data cannot overflow the stack
+pub const MAX_DATUM_DEPTH: usize = 16;
+
+/// Codec names accepted by `DATUM_CODECS`
+pub const CODEC_NAMES: [&str; 2] = ["positional", "map"];
+
+/// Why a codec could not decode a datum
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum DecodeError {
+    #[error("datum is not valid hex")]
+    InvalidHex,
+    #[error("datum is larger than {MAX_DATUM_BYTES} bytes")]
+    TooLarge,
+    #[error("datum is not well-formed CBOR or nests deeper than {MAX_DATUM_DEPTH}")]
+    Malformed,
+    #[error("datum is not PlutusData: {0}")]
+    NotPlutusData(String),
+    #[error("expected {0}")]
+    UnexpectedShape(&'static str),
+    #[error("field {field}: {reason}")]
+    Field {
+        field: &'static str,
+        reason: &'static str,
+    },
+}
+
+/// Turns inline datum bytes into a tracking datum
+///
+/// Register custom codecs with `CodecRegistry::new` to accept datum layouts
+/// other than the built-in ones.
+pub trait DatumCodec: Send + Sync {
+    /// Name shown in scan diagnostics
+    fn name(&self) -> &str;
+
+    fn decode(&self, bytes: &[u8]) -> Result<TrackingDatum, DecodeError>;
+}
+
+/// `Constr0 [carrier, tracking_number, outbox_address, deadline?]`, the layout of the on-chain validator
+///
+/// Datums written before the deadline field existed have only the first three fields.
+pub struct PositionalCodec;
+
+impl DatumCodec for PositionalCodec {
+    fn name(&self) -> &str {
+        "positional"
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<TrackingDatum, DecodeError> {
+        let PlutusData::Constr(constr) = plutus_data(bytes)? else {
+            return Err(DecodeError::UnexpectedShape("a constructor"));
+        };
+
+        let field = |index: usize, name: &'static str| match constr.fields.get(index) {
+            Some(PlutusData::BoundedBytes(bytes)) => Ok(bytes.as_slice()),
+            Some(_) => Err(DecodeError::Field { field: name, reason: "not a byte string" }),
+            None => Err(DecodeError::Field { field: name, reason: "missing" }),
+        };
+
+        tracking_datum(
+            field(0, "carrier")?,
+            field(1, "tracking_number")?,
+            field(2, "outbox_address")?,
+            deadline(constr.fields.get(3))?,
+        )
+    }
+}
+
+impl PositionalCodec {
+    /// Encode `datum` in this layout, the inverse of `decode`
+    ///
+    /// The deadline field is only written when the datum has one.
+    pub fn encode(datum: &TrackingDatum) -> Vec<u8> {
+        let fields = if datum.deadline.is_some() { 4 } else { 3 };
+
+        // Tag 121 (constructor 0) wrapping a definite array
+        let mut cbor = vec![0xd8, 0x79];
+        push_head(&mut cbor, 4, fields);
+        push_bytes(&mut cbor, datum.carrier.as_bytes());
+        push_bytes(&mut cbor, datum.tracking_number.as_bytes());
+        push_bytes(&mut cbor, &datum.outbox_address.to_vec());
+        if let Some(deadline) = datum.deadline {
+            push_head(&mut cbor, 0, deadline);
+        }
+
+        cbor
+    }
+}
+
+/// Map keyed by the byte strings `carrier`, `tracking_number` and `outbox_address`, plus an optional `deadline`
+pub struct MapCodec;
+
+impl DatumCodec for MapCodec {
+    fn name(&self) -> &str {
+        "map"
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<TrackingDatum, DecodeError> {
+        let PlutusData::Map(entries) = plutus_data(bytes)? else {
+            return Err(DecodeError::UnexpectedShape("a map"));
+        };
+
+        let value = |name: &'static str| {
+            entries.iter().find_map(|(key, value)| match key {
+                PlutusData::BoundedBytes(key) if key.as_slice() == name.as_bytes() => Some(value),
+                _ => None,
+            })
+        };
+        let field = |name: &'static str| match value(name) {
+            Some(PlutusData::BoundedBytes(bytes)) => Ok(bytes.as_slice()),
+            Some(_) => Err(DecodeError::Field { field: name, reason: "not a byte string" }),
+            None => Err(DecodeError::Field { field: name, reason: "missing" }),
+        };
+
+        tracking_datum(
+            field("carrier")?,
+            field("tracking_number")?,
+            field("outbox_address")?,
+            deadline(value("deadline"))?,
+        )
+    }
+}
+
+/// Codecs tried in order against every inline datum; the first to decode it wins
+pub struct CodecRegistry {
+    codecs: Vec<Box<dyn DatumCodec>>,
+}
+
+/// A datum every registered codec refused, with each codec's reason in registry order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatumRejected {
+    pub attempts: Vec<(String, DecodeError)>,
+}
+
+impl fmt::Display for DatumRejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let attempts: Vec<String> = self
+            .attempts
+            .iter()
+            .map(|(codec, error)| format!("{}: {}", codec, error))
+            .collect();
+
+        f.write_str(&attempts.join("; "))
+    }
+}
+
+impl std::error::Error for DatumRejected {}
+
+impl Default for CodecRegistry {
+    fn default() -> Self {
+        Self::new(vec![Box::new(PositionalCodec)])
+    }
+}
+
+impl CodecRegistry {
+    pub fn new(codecs: Vec<Box<dyn DatumCodec>>) -> Self {
+        Self { codecs }
+    }
+
+    /// Built-in codecs for `names`, in the given order (see `CODEC_NAMES`)
+    pub fn from_names<S: AsRef<str>>(names: &[S]) -> Result<Self> {
+        let mut codecs: Vec<Box<dyn DatumCodec>> = Vec::new();
+        for name in names {
+            match name.as_ref() {
+                "positional" => codecs.push(Box::new(PositionalCodec)),
+                "map" => codecs.push(Box::new(MapCodec)),
+                other => bail!("unknown datum codec '{}' (expected one of {})", other, CODEC_NAMES.join(", ")),
+            }
+        }
+        if codecs.is_empty() {
+            bail!("at least one datum codec is required");
+        }
+
+        Ok(Self::new(codecs))
+    }
+
+    /// Names of the registered codecs, in order
+    pub fn names(&self) -> Vec<&str> {
+        self.codecs.iter().map(|codec| codec.name()).collect()
+    }
+
+    pub fn decode(&self, bytes: &[u8]) -> Result<TrackingDatum, DatumRejected> {
+        let mut attempts = Vec::new();
+        for codec in &self.codecs {
+            match codec.decode(bytes) {
+                Ok(datum) => return Ok(datum),
+                Err(e) => attempts.push((codec.name().to_string(), e)),
+            }
+        }
+
+        Err(DatumRejected { attempts })
+    }
+
+    /// Decode a hex inline datum as returned by Blockfrost
+    pub fn decode_hex(&self, datum: &str) -> Result<TrackingDatum, DatumRejected> {
+        match datum_bytes(datum) {
+            Ok(bytes) => self.decode(&bytes),
+            Err(e) => Err(DatumRejected {
+                attempts: self.codecs.iter().map(|codec| (codec.name().to_string(), e.clone())).collect(),
+            }),
+        }
+    }
+}
+
+/// Bytes of a hex inline datum, refusing oversized ones before decoding
+pub fn datum_bytes(datum: &str) -> Result<Vec<u8>, DecodeError> {
+    if datum.len() > MAX_DATUM_BYTES * 2 {
+        return Err(DecodeError::TooLarge);
+    }
+
+    hex::decode(datum).map_err(|_| DecodeError::InvalidHex)
+}
+
+/// Decode `bytes` as PlutusData within `MAX_DATUM_BYTES` and `MAX_DATUM_DEPTH`
+pub fn plutus_data(bytes: &[u8]) -> Result<PlutusData, DecodeError> {
+    if bytes.len() > MAX_DATUM_BYTES {
+        return Err(DecodeError::TooLarge);
+    }
+    if !cbor_depth_within(bytes, MAX_DATUM_DEPTH) {
+        return Err(DecodeError::Malformed);
+    }
+
+    minicbor::decode::<PlutusData>(bytes).map_err(|e| DecodeError::NotPlutusData(e.to_string()))
+}
+
+/// The optional deadline field, a non-negative integer
+fn deadline(value: Option<&PlutusData>) -> Result<Option<u64>, DecodeError> {
+    match value {
+        None => Ok(None),
+        Some(PlutusData::BigInt(BigInt::Int(deadline))) => u64::try_from(i128::from(*deadline))
+            .map(Some)
+            .map_err(|_| DecodeError::Field { field: "deadline", reason: "not a Unix timestamp" }),
+        Some(_) => Err(DecodeError::Field { field: "deadline", reason: "not an integer" }),
+    }
+}
+
+fn tracking_datum(
+    carrier: &[u8],
+    tracking_number: &[u8],
+    outbox_address: &[u8],
+    deadline: Option<u64>,
+) -> Result<TrackingDatum, DecodeError> {
+    let carrier = std::str::from_utf8(carrier)
+        .ok()
+        .filter(|carrier| !carrier.is_empty())
+        .ok_or(DecodeError::Field { field: "carrier", reason: "not a non-empty UTF-8 string" })?;
+    let tracking_number = TrackingNumber::from_datum_bytes(tracking_number).ok_or(DecodeError::Field {
+        field: "tracking_number",
+        reason: "neither a non-empty UTF-8 string nor a 32-byte hash",
+    })?;
+    let outbox_address = Address::from_bytes(outbox_address)
+        .map_err(|_| DecodeError::Field { field: "outbox_address", reason: "not a valid address" })?;
+
+    Ok(TrackingDatum {
+        carrier: carrier.to_string(),
+        tracking_number,
+        outbox_address,
+        deadline,
+    })
+}
+
+/// Append the shortest CBOR head of `major` type carrying `argument`
+fn push_head(out: &mut Vec<u8>, major: u8, argument: u64) {
+    let major = major << 5;
+    match argument {
+        0..=23 => out.push(major | argument as u8),
+        24..=0xff => out.extend([major | 24, argument as u8]),
+        0x100..=0xffff => {
+            out.push(major | 25);
+            out.extend((argument as u16).to_be_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(major | 26);
+            out.extend((argument as u32).to_be_bytes());
+        }
+        _ => {
+            out.push(major | 27);
+            out.extend(argument.to_be_bytes());
+        }
+    }
+}
+
+/// Append a PlutusData byte string; the ledger wants longer ones in 64-byte chunks
+fn push_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    if bytes.len() <= 64 {
+        push_head(out, 2, bytes.len() as u64);
+        out.extend_from_slice(bytes);
+        return;
+    }
+
+    out.push(0x5f);
+    for chunk in bytes.chunks(64) {
+        push_head(out, 2, chunk.len() as u64);
+        out.extend_from_slice(chunk);
+    }
+    out.push(0xff);
+}
+
+/// Walk CBOR item headers iteratively and check that containers (arrays,
+/// maps, tags, indefinite strings) never nest deeper than `max_depth`.
+/// Malformed or truncated input is rejected.
+fn cbor_depth_within(bytes: &[u8], max_depth: usize) -> bool {
+    // Items still expected by each open container; `None` for indefinite length
+    let mut open: Vec<Option<u64>> = Vec::new();
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let initial = bytes[pos];
+        pos += 1;
+
+        if initial == 0xff {
+            if open.pop() != Some(None) {
+                return false;
+            }
+            close_item(&mut open);
+            continue;
+        }
+
+        let major = initial >> 5;
+        let argument = match initial & 0x1f {
+            info @ 0..=23 => Some(info as u64),
+            info @ 24..=27 => {
+                let width = 1usize << (info - 24);
+                let Some(chunk) = bytes.get(pos..pos + width) else {
+                    return false;
+                };
+                pos += width;
+                Some(chunk.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64))
+            }
+            31 if matches!(major, 2..=5) => None,
+            _ => return false,
+        };
+
+        let expected = match (major, argument) {
+            (2 | 3, Some(len)) => {
+                let Some(end) = usize::try_from(len).ok().and_then(|len| pos.checked_add(len)) else {
+                    return false;
+                };
+                pos = end;
+                close_item(&mut open);
+                continue;
+            }
+            (4, items) => items,
+            (5, Some(pairs)) => match pairs.checked_mul(2) {
+                Some(items) => Some(items),
+                None => return false,
+            },
+            (6, _) => Some(1),
+            (2 | 3 | 5, None) => None,
+            _ => {
+                close_item(&mut open);
+                continue;
+            }
+        };
+
+        if expected == Some(0) {
+            close_item(&mut open);
+        } else {
+            open.push(expected);
+            if open.len() > max_depth {
+                return false;
+            }
+        }
+    }
+
+    pos == bytes.len() && open.is_empty()
+}
+
+fn close_item(open: &mut Vec<Option<u64>>) {
+    while let Some(Some(remaining)) = open.last_mut() {
+        *remaining -= 1;
+        if *remaining > 0 {
+            return;
+        }
+        open.pop();
+    }
+}
@@ -0,0 +1,213 @@
+use anyhow::{Context as _, Result};
+use chrono::{DateTime, Utc};
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::Layer;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::notifier::OracleEvent;
+use crate::redact::redact;
+
+/// Events kept when `DEBUG_EVENTS_CAPACITY` is not set
+pub const DEFAULT_DEBUG_EVENTS_CAPACITY: usize = 1000;
+
+/// Buffer installed by `logging::init`, read by the admin API
+static INSTALLED: OnceCell<EventBuffer> = OnceCell::new();
+
+/// Something that happened in the process, as served by `GET /debug/events`
+#[derive(Debug, Clone, Serialize)]
+pub struct DebugEvent {
+    pub at: DateTime<Utc>,
+    /// Tenant the event belongs to; `None` for process-wide events
+    pub tenant: Option<String>,
+    #[serde(flatten)]
+    pub record: DebugRecord,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum DebugRecord {
+    /// An `OracleEvent`, as the notifiers see it
+    Oracle { event: Value },
+    /// A warning or error logged through `tracing`
+    Log {
+        level: String,
+        target: String,
+        message: String,
+        /// Fields of the event, `message` aside
+        fields: BTreeMap<String, String>,
+        /// Fields of the spans the event was logged in, e.g. `run_id` and `utxo_ref`
+        context: BTreeMap<String, String>,
+    },
+}
+
+/// The most recent `DebugEvent`s, the oldest evicted once `capacity` are kept
+///
+/// Clones share the same events. Every value is redacted before it is stored.
+#[derive(Debug, Clone)]
+pub struct EventBuffer {
+    capacity: usize,
+    events: Arc<Mutex<VecDeque<DebugEvent>>>,
+}
+
+impl EventBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self { capacity, events: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))) }
+    }
+
+    /// Buffer sized by `DEBUG_EVENTS_CAPACITY`; `None` when it is `0`
+    ///
+    /// Read from the process environment, so with `TENANTS` one buffer holds every tenant's events.
+    pub fn from_env() -> Result<Option<Self>> {
+        let capacity = match std::env::var("DEBUG_EVENTS_CAPACITY") {
+            Ok(value) if !value.trim().is_empty() => value.trim().parse().context("Invalid DEBUG_EVENTS_CAPACITY")?,
+            _ => DEFAULT_DEBUG_EVENTS_CAPACITY,
+        };
+
+        Ok((capacity > 0).then(|| Self::new(capacity)))
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn push(&self, event: DebugEvent) {
+        let mut events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        while events.len() >= self.capacity {
+            events.pop_front();
+        }
+        events.push_back(event);
+    }
+
+    /// Keep `event`, serialized as the webhook notifier sends it
+    pub fn record_oracle_event(&self, event: &OracleEvent) {
+        let json = redact(&serde_json::to_string(event).unwrap_or_default());
+        let event_json = serde_json::from_str(&json).unwrap_or(Value::String(json));
+        self.push(DebugEvent {
+            at: Utc::now(),
+            tenant: event.tenant().map(str::to_string),
+            record: DebugRecord::Oracle { event: event_json },
+        });
+    }
+
+    /// Every event kept, newest first
+    pub fn recent(&self) -> Vec<DebugEvent> {
+        let events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        events.iter().rev().cloned().collect()
+    }
+
+    /// Layer keeping the warnings and errors logged from now on
+    pub fn layer(&self) -> EventBufferLayer {
+        EventBufferLayer { buffer: self.clone() }
+    }
+
+    /// Make this buffer the one `installed` returns; only the first call has an effect
+    pub fn install(&self) {
+        let _ = INSTALLED.set(self.clone());
+    }
+}
+
+/// Buffer of the process-wide subscriber; `None` before `logging::init` or with `DEBUG_EVENTS_CAPACITY=0`
+pub fn installed() -> Option<EventBuffer> {
+    INSTALLED.get().cloned()
+}
+
+/// Keep every event `events` receives until its oracle is dropped
+///
+/// Events missed because the buffer fell behind the channel are skipped.
+pub async fn record_oracle_events(buffer: EventBuffer, mut events: broadcast::Receiver<OracleEvent>) {
+    loop {
+        match events.recv().await {
+            Ok(event) => buffer.record_oracle_event(&event),
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+}
+
+/// `tracing` layer writing warnings and errors into an `EventBuffer`, with the fields of their spans
+pub struct EventBufferLayer {
+    buffer: EventBuffer,
+}
+
+/// Redacted fields of a span, kept in its extensions
+struct SpanFields(BTreeMap<String, String>);
+
+impl<S> Layer<S> for EventBufferLayer
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = BTreeMap::new();
+        attrs.record(&mut FieldVisitor(&mut fields));
+        span.extensions_mut().insert(SpanFields(fields));
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        if let Some(fields) = span.extensions_mut().get_mut::<SpanFields>() {
+            values.record(&mut FieldVisitor(&mut fields.0));
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if *metadata.level() > Level::WARN {
+            return;
+        }
+
+        let mut fields = BTreeMap::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        let message = fields.remove("message").unwrap_or_default();
+
+        // Outer spans first, so the innermost value of a field wins
+        let mut context = BTreeMap::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                if let Some(span_fields) = span.extensions().get::<SpanFields>() {
+                    context.extend(span_fields.0.clone());
+                }
+            }
+        }
+
+        let tenant = fields.get("tenant").or_else(|| context.get("tenant")).cloned();
+        self.buffer.push(DebugEvent {
+            at: Utc::now(),
+            tenant,
+            record: DebugRecord::Log {
+                level: metadata.level().to_string(),
+                target: metadata.target().to_string(),
+                message,
+                fields,
+                context,
+            },
+        });
+    }
+}
+
+/// Records each field as its redacted text
+struct FieldVisitor<'a>(&'a mut BTreeMap<String, String>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), redact(value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0.insert(field.name().to_string(), redact(&format!("{:?}", value)));
+    }
+}
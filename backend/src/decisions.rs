@@ -0,0 +1,142 @@
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+
+use crate::lifecycle::ShipmentLifecycle;
+use crate::state::StateStore;
+
+/// Decisions returned per page unless the query asks for fewer
+pub const DEFAULT_PAGE_SIZE: usize = 50;
+
+/// What the oracle said about one shipment: the status it closed the tracking UTxO with
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Decision {
+    /// The tracking UTxO that was closed; unknown when found on-chain
+    pub utxo_ref: Option<String>,
+    pub carrier: Option<String>,
+    /// As written in the datum (the hash hex for privacy-mode shipments)
+    pub tracking_number: Option<String>,
+    pub outbox_address: Option<String>,
+    pub status: String,
+    /// Unix timestamp written in the shipment datum
+    pub timestamp: u64,
+    pub tx_hash: String,
+    pub confirmation: Confirmation,
+    pub source: DecisionSource,
+}
+
+/// Whether the close transaction carrying a decision is on-chain
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum Confirmation {
+    /// Accepted by the submit API, not seen on-chain yet
+    Pending,
+    /// On-chain, in block `block` (height) when known
+    Confirmed { block: Option<u64> },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DecisionSource {
+    /// The state database's submission journal
+    State,
+    /// A shipment datum at the outbox address
+    Chain,
+}
+
+/// Decisions matching a tracking number, an outbox address or both
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecisionQuery {
+    pub tracking_number: Option<String>,
+    pub outbox: Option<String>,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+impl Default for DecisionQuery {
+    fn default() -> Self {
+        Self {
+            tracking_number: None,
+            outbox: None,
+            offset: 0,
+            limit: DEFAULT_PAGE_SIZE,
+        }
+    }
+}
+
+impl DecisionQuery {
+    fn matches(&self, tracking_number: Option<&str>, outbox: Option<&str>) -> bool {
+        self.tracking_number.as_deref().is_none_or(|wanted| tracking_number == Some(wanted))
+            && self.outbox.as_deref().is_none_or(|wanted| outbox == Some(wanted))
+    }
+}
+
+/// One page of decisions, oldest first
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DecisionPage {
+    /// Matching decisions across every page
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+    pub decisions: Vec<Decision>,
+}
+
+impl DecisionPage {
+    /// The page of `decisions` selected by `query`
+    pub fn new(decisions: Vec<Decision>, query: &DecisionQuery) -> Self {
+        Self {
+            total: decisions.len(),
+            offset: query.offset,
+            limit: query.limit,
+            decisions: decisions.into_iter().skip(query.offset).take(query.limit).collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize decisions")
+    }
+}
+
+/// Decisions journalled in `state` matching `query`
+///
+/// As in the fee report, only the submission a shipment records as closing it
+/// counts; resubmissions that never confirmed are not decisions. Submissions
+/// journalled before tracking numbers were recorded only match outbox queries.
+pub async fn find_decisions(state: &dyn StateStore, query: &DecisionQuery) -> Result<DecisionPage> {
+    if query.tracking_number.is_none() && query.outbox.is_none() {
+        bail!("Query decisions by tracking number, outbox address or both");
+    }
+
+    let mut decisions = Vec::new();
+    for submission in state.journal().await? {
+        if !query.matches(submission.tracking_number.as_deref(), submission.outbox_address.as_deref()) {
+            continue;
+        }
+
+        let closing_tx = state
+            .shipment(&submission.utxo_ref)
+            .await?
+            .and_then(|shipment| shipment.closed_tx_hash);
+        if closing_tx.as_deref() != Some(submission.tx_hash.as_str()) {
+            continue;
+        }
+
+        let confirmation = match state.lifecycle(&submission.utxo_ref).await?.pop().map(|t| t.state) {
+            Some(ShipmentLifecycle::Confirmed { block }) => Confirmation::Confirmed { block: Some(block) },
+            _ => Confirmation::Pending,
+        };
+
+        decisions.push(Decision {
+            utxo_ref: Some(submission.utxo_ref),
+            carrier: submission.carrier,
+            tracking_number: submission.tracking_number,
+            outbox_address: submission.outbox_address,
+            status: submission.status,
+            timestamp: submission.submitted_at,
+            tx_hash: submission.tx_hash,
+            confirmation,
+            source: DecisionSource::State,
+        });
+    }
+
+    Ok(DecisionPage::new(decisions, query))
+}
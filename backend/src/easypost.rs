@@ -0,0 +1,142 @@
+use anyhow::{Context, Result, anyhow, bail};
+use reqwest::Client;
+use serde::Deserialize;
+use tracing::{Instrument, debug};
+
+use crate::config::Config;
+use crate::logging;
+use crate::metrics::Metrics;
+use crate::models::TrackingStatus;
+use crate::redact::{redact, register_config_secrets};
+use crate::tracking_provider::{ProviderKind, TrackingProvider};
+
+/// EasyPost `GET /trackers` response (partial)
+#[derive(Debug, Deserialize)]
+struct TrackerList {
+    trackers: Vec<Tracker>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Tracker {
+    #[serde(default)]
+    carrier: Option<String>,
+    status: String,
+    #[serde(default)]
+    status_detail: Option<String>,
+    /// Oldest first
+    #[serde(default)]
+    tracking_details: Vec<TrackingDetail>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TrackingDetail {
+    #[serde(default)]
+    message: Option<String>,
+    #[serde(default)]
+    datetime: Option<String>,
+}
+
+/// EasyPost tracker status in Shippo's vocabulary, so `StatusMapping` reads both alike
+pub fn shippo_status(status: &str) -> &'static str {
+    match status {
+        "pre_transit" => "PRE_TRANSIT",
+        "in_transit" | "out_for_delivery" | "available_for_pickup" => "TRANSIT",
+        "delivered" => "DELIVERED",
+        "return_to_sender" => "RETURNED",
+        "failure" => "FAILURE",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Tracks shipments through EasyPost's `GET /trackers` with `EASYPOST_API_KEY`
+///
+/// Trackers are not created here: the merchant creates them when buying the
+/// label. When several match the tracking code, the one of the datum's carrier wins.
+pub struct EasyPostProvider {
+    config: Config,
+    http_client: Client,
+    metrics: Metrics,
+}
+
+impl EasyPostProvider {
+    pub fn new(config: Config) -> Result<Self> {
+        if config.easypost_api_key.is_none() {
+            bail!("EasyPost tracking needs EASYPOST_API_KEY");
+        }
+        register_config_secrets(&config);
+
+        let http_client = Client::builder()
+            .timeout(std::time::Duration::from_secs(30))
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        Ok(Self { config, http_client, metrics: Metrics::new() })
+    }
+
+    /// Count fetched statuses and EasyPost errors in `metrics`
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    async fn request_status(&self, carrier: &str, tracking_number: &str) -> Result<TrackingStatus> {
+        let api_key = self.config.easypost_api_key.as_ref().map(|key| key.expose().as_str()).unwrap_or_default();
+        let url = format!("{}/trackers", self.config.easypost_url);
+        let response = self.http_client
+            .get(&url)
+            .query(&[("tracking_code", tracking_number)])
+            .basic_auth(api_key, None::<&str>)
+            .send()
+            .instrument(logging::http_span("easypost", &reqwest::Method::GET, &url))
+            .await
+            .map_err(|e| anyhow!("Failed to send request to EasyPost: {}", redact(&e.to_string())))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            bail!("EasyPost query failed (status {}): {}", status, redact(&body));
+        }
+
+        let list: TrackerList = response.json().await.context("Failed to parse EasyPost trackers response")?;
+        let position = list
+            .trackers
+            .iter()
+            .position(|tracker| tracker.carrier.as_deref().is_some_and(|name| name.eq_ignore_ascii_case(carrier)))
+            .unwrap_or(0);
+        let tracker = list
+            .trackers
+            .into_iter()
+            .nth(position)
+            .with_context(|| format!("EasyPost has no tracker for {} {}", carrier, tracking_number))?;
+
+        debug!(status = %tracker.status, "EasyPost answered");
+        let latest = tracker.tracking_details.last();
+        Ok(TrackingStatus {
+            status: shippo_status(&tracker.status).into(),
+            status_details: latest
+                .and_then(|detail| detail.message.clone())
+                .or(tracker.status_detail.clone())
+                .unwrap_or_else(|| tracker.status.clone()),
+            status_date: latest.and_then(|detail| detail.datetime.clone()),
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl TrackingProvider for EasyPostProvider {
+    async fn fetch_status(&self, carrier: &str, tracking_number: &str) -> Result<TrackingStatus> {
+        debug!(carrier, tracking_number, "Fetching tracking status from EasyPost");
+
+        let result = self.request_status(carrier, tracking_number).await;
+        match &result {
+            Ok(_) => self.metrics.status_fetched(carrier),
+            Err(_) => self.metrics.request_error("easypost"),
+        }
+
+        result
+    }
+
+    fn provider(&self, _carrier: &str) -> ProviderKind {
+        ProviderKind::EasyPost
+    }
+}
@@ -0,0 +1,105 @@
+use anyhow::Result;
+use sentry::protocol::{Breadcrumb, Level};
+
+use crate::config::Config;
+use crate::notifier::{Notifier, OracleEvent};
+
+/// Initialize the Sentry client and install the panic hook
+///
+/// Returns `None` when `SENTRY_DSN` is not configured. The returned guard
+/// must be kept alive for the lifetime of the process so pending events are
+/// flushed on shutdown.
+pub fn init(config: &Config) -> Option<sentry::ClientInitGuard> {
+    let dsn = config.sentry_dsn.as_ref()?;
+
+    let guard = sentry::init((
+        dsn.expose().as_str(),
+        sentry::ClientOptions {
+            release: sentry::release_name!(),
+            attach_stacktrace: true,
+            ..Default::default()
+        },
+    ));
+
+    sentry::configure_scope(|scope| {
+        scope.set_tag("version", env!("CARGO_PKG_VERSION"));
+        if let Some(network) = config.cardano_network {
+            scope.set_tag("network", network.as_str());
+        }
+    });
+
+    Some(guard)
+}
+
+/// Forwards shipment failures to Sentry and records closures as breadcrumbs
+pub struct SentryNotifier;
+
+#[async_trait::async_trait]
+impl Notifier for SentryNotifier {
+    async fn notify(&self, event: &OracleEvent) -> Result<()> {
+        match event {
+            OracleEvent::ShipmentClosed { utxo_ref, status, tx_hash, .. } => {
+                sentry::add_breadcrumb(Breadcrumb {
+                    category: Some("shipment".to_string()),
+                    message: Some(format!("Closed {} as {} in {}", utxo_ref, status, tx_hash)),
+                    level: Level::Info,
+                    ..Default::default()
+                });
+            }
+            OracleEvent::ShipmentSkipped { utxo_ref, reason, .. } => {
+                sentry::add_breadcrumb(Breadcrumb {
+                    category: Some("shipment".to_string()),
+                    message: Some(format!("Skipped {}: {}", utxo_ref, reason)),
+                    level: Level::Warning,
+                    ..Default::default()
+                });
+            }
+            OracleEvent::ShipmentFailed { utxo_ref, carrier, status, error, tenant, run_id, .. } => {
+                let class = error_class(error);
+
+                sentry::with_scope(
+                    |scope| {
+                        scope.set_tag("utxo_ref", utxo_ref);
+                        scope.set_tag("carrier", carrier);
+                        scope.set_tag("status", status);
+                        scope.set_tag("stage", "submit");
+                        if let Some(tenant) = tenant {
+                            scope.set_tag("tenant", tenant);
+                        }
+                        if let Some(run_id) = run_id {
+                            scope.set_tag("run_id", run_id);
+                        }
+                        scope.set_fingerprint(Some(&["shipment-failed", class.as_str()]));
+                    },
+                    || sentry::capture_message(&format!("Shipment close failed: {}", error), Level::Error),
+                );
+            }
+            OracleEvent::BudgetExceeded { period, calls, budget, .. } => {
+                sentry::add_breadcrumb(Breadcrumb {
+                    category: Some("shippo".to_string()),
+                    message: Some(format!("Shippo budget exceeded: {} of {} calls in {}", calls, budget, period)),
+                    level: Level::Warning,
+                    ..Default::default()
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reduce an error message to its class so events group regardless of
+/// response bodies, hashes or status details in the message
+///
+/// `"Blockfrost transaction submission failed (status 400): {...}"` becomes
+/// `"Blockfrost transaction submission failed"`.
+pub fn error_class(error: &str) -> String {
+    let end = error.find([':', '(']).unwrap_or(error.len());
+    let class = error[..end].trim();
+
+    if class.is_empty() {
+        "unknown".to_string()
+    } else {
+        class.to_string()
+    }
+}
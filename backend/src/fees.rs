@@ -0,0 +1,113 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::state::StateStore;
+
+/// Outbox key for submissions journalled before the outbox address was recorded
+const UNKNOWN_OUTBOX: &str = "unknown";
+
+/// Lovelace spent by the oracle wallet on close-shipment transactions
+///
+/// Built from the submission journal. A tracking UTxO can only be spent once, so
+/// of several submissions for the same shipment only the one the state store
+/// records as closing it is counted; resubmissions that never confirmed are not.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FeeReport {
+    /// Unix timestamp of the earliest submission considered, if limited
+    pub since: Option<u64>,
+    pub closures: usize,
+    pub total_lovelace: u64,
+    /// Closures journalled before fees were recorded; not part of the totals
+    pub unknown_fee: usize,
+    pub by_outbox: BTreeMap<String, OutboxFees>,
+}
+
+/// Fees for closures paying one outbox address
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct OutboxFees {
+    pub closures: usize,
+    pub lovelace: u64,
+}
+
+impl FeeReport {
+    /// Sum the fees of closures submitted at or after `since` (every closure when `None`)
+    pub async fn from_state(state: &dyn StateStore, since: Option<u64>) -> Result<Self> {
+        let mut report = FeeReport {
+            since,
+            ..Default::default()
+        };
+
+        for submission in state.journal().await? {
+            if since.is_some_and(|since| submission.submitted_at < since) {
+                continue;
+            }
+
+            let closing_tx = state
+                .shipment(&submission.utxo_ref)
+                .await?
+                .and_then(|shipment| shipment.closed_tx_hash);
+            if closing_tx.as_deref() != Some(submission.tx_hash.as_str()) {
+                continue;
+            }
+
+            let Some(fee) = submission.fee else {
+                report.unknown_fee += 1;
+                continue;
+            };
+
+            report.closures += 1;
+            report.total_lovelace += fee;
+
+            let outbox = submission.outbox_address.unwrap_or_else(|| UNKNOWN_OUTBOX.to_string());
+            let outbox_fees = report.by_outbox.entry(outbox).or_default();
+            outbox_fees.closures += 1;
+            outbox_fees.lovelace += fee;
+        }
+
+        Ok(report)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize fee report")
+    }
+
+    /// Markdown summary with one table row per outbox address
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# Oracle Fees\n\n");
+
+        if let Some(since) = self.since.and_then(|since| chrono::DateTime::from_timestamp(since as i64, 0)) {
+            out.push_str(&format!("- Since: {}\n", since.format("%Y-%m-%d %H:%M:%S UTC")));
+        }
+        out.push_str(&format!("- Closures: {}\n", self.closures));
+        out.push_str(&format!("- Total: {} lovelace ({} ADA)\n", self.total_lovelace, format_ada(self.total_lovelace)));
+        if self.unknown_fee > 0 {
+            out.push_str(&format!("- Closures without a recorded fee: {}\n", self.unknown_fee));
+        }
+        out.push('\n');
+
+        if self.by_outbox.is_empty() {
+            out.push_str("No closures in this period.\n");
+            return out;
+        }
+
+        out.push_str("| Outbox | Closures | Lovelace | ADA |\n");
+        out.push_str("|---|---|---|---|\n");
+        for (outbox, fees) in &self.by_outbox {
+            out.push_str(&format!(
+                "| `{}` | {} | {} | {} |\n",
+                outbox,
+                fees.closures,
+                fees.lovelace,
+                format_ada(fees.lovelace),
+            ));
+        }
+
+        out
+    }
+}
+
+fn format_ada(lovelace: u64) -> String {
+    format!("{}.{:06}", lovelace / 1_000_000, lovelace % 1_000_000)
+}
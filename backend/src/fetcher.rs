@@ -1,62 +1,1768 @@
-use crate::blockchain::CardanoClient;
-use crate::shipment::{ShipmentClient, get_status};
-use std::sync::Arc;
+use crate::blockchain::{CardanoClient, ClosedShipment, FeeExceeded, Raced};
+use crate::carrier_policy::{self, CarrierPolicy};
+use crate::clock::{Clock, SystemClock};
+use crate::config::{
+    Config, DEFAULT_CLOSE_CONCURRENCY, DEFAULT_CLOSE_QUEUE_DEPTH, DEFAULT_FETCH_CONCURRENCY, DEFAULT_PENDING_TX_TTL_MINUTES,
+};
+use crate::fees::FeeReport;
+use crate::indexer;
+use crate::lifecycle::{LifecycleTransition, ShipmentLifecycle, check_transition};
+use crate::metrics::Metrics;
+use crate::models::{CarrierStatus, DerivedStatus, TrackingDatum, TrackingNumber, TrackingStatus, TrackingUTxO};
+use crate::notifier::{self, Notifier, OracleEvent};
+use crate::outbox_policy::{self, OutboxPolicy};
+use crate::privacy::TrackingLookup;
+use crate::run_id;
+use crate::run_report::{self, CloseReason, RunReport, ShipmentAction, ShipmentReport};
+use crate::self_test::SELF_TEST_CARRIER;
+use crate::shipment::{ShipmentClient, ShipmentError};
+use crate::shippo_budget::{self, ShippoBudget, UsageReport};
+use crate::stale_status::{self, StaleStatusPolicy};
+use crate::state::{self, ShipmentState, StateStore, Submission, SubmissionAttempt};
+use crate::status_mapping::{TIMEOUT_STATUS, StatusMapping};
+use crate::submit_window::SubmitWindow;
+use crate::submitter::SubmitError;
+use crate::timestamp_source::{CloseTimestamp, TimestampSource};
+use crate::tracking_cache::TrackingCache;
+use crate::tracking_provider::{self, ProviderKind, TrackingProvider};
+use crate::validation::TxValidationFailed;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use futures::StreamExt;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::ops::AddAssign;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use tracing::{Instrument, Span, debug, error, info, info_span, warn};
+
+/// Wait before retrying shipments Shippo rate limited without a `Retry-After`
+const DEFAULT_RATE_LIMIT_WAIT: Duration = Duration::from_secs(5);
+/// Longest wait for a rate limit within a run; longer ones are cut short
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+/// Finished runs kept for `DataFetcher::recent_runs`, the oldest dropped first
+pub const RECENT_RUNS: usize = 100;
+
+/// Shipment counters for a single `DataFetcher::run`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct RunStats {
+    pub shipments: usize,
+    pub submitted: usize,
+    pub failed: usize,
+    /// Privacy-mode shipments whose tracking hash is not registered
+    pub skipped: usize,
+    /// Closures refused because the fee exceeded `MAX_FEE_LOVELACE` (also counted in `failed`)
+    pub fee_exceeded: usize,
+    /// Closures refused because the resolved transaction failed validation (also counted in `failed`)
+    pub tx_validation_failed: usize,
+    /// Total fee of the submitted closures, in lovelace
+    pub fees_lovelace: u64,
+    /// Lifecycle transitions refused as illegal (logged, not recorded)
+    pub illegal_transitions: usize,
+    /// Final statuses left unsubmitted because the submit window was closed
+    pub deferred_window: usize,
+    /// Shipments whose outbox address the outbox policy refuses (`skipped_policy`)
+    pub skipped_policy: usize,
+    /// Closures skipped because the tracking UTxO was spent since the scan, found by
+    /// `RECHECK_BEFORE_SIGN` or rejected by the submit API as already spent
+    pub raced: usize,
+    /// Inline datums at the oracle address that no `DATUM_CODECS` codec could decode
+    pub undecodable: usize,
+    /// Shipments not polled because the Shippo budget is spent (`SHIPPO_MONTHLY_BUDGET`)
+    pub skipped_budget: usize,
+    /// Shipments Shippo does not know while `SHIPPO_AUTO_REGISTER` is off
+    pub skipped_unregistered: usize,
+    /// Shipments whose carrier `CARRIER_ALLOWLIST` or `CARRIER_DENYLIST` refuses
+    pub skipped_carrier: usize,
+    /// Shipments left alone because their close transaction is still pending (`PENDING_TX_TTL_MINUTES`)
+    pub pending: usize,
+    /// Closes signed but not submitted because of `DRY_RUN` (not counted in `submitted`)
+    pub dry_run: usize,
+    /// Carrier statuses reused from the tracking cache instead of fetched (`TRACKING_CACHE_TTL_SECS`)
+    pub cached: usize,
+    /// Carrier statuses reused from another tracking UTxO with the same carrier and tracking number
+    pub deduplicated: usize,
+}
+
+impl AddAssign for RunStats {
+    /// Add the counters of one shipment, processed alongside others, to the run's
+    fn add_assign(&mut self, other: Self) {
+        // Destructured so that a new counter cannot be forgotten here
+        let RunStats {
+            shipments,
+            submitted,
+            failed,
+            skipped,
+            fee_exceeded,
+            tx_validation_failed,
+            fees_lovelace,
+            illegal_transitions,
+            deferred_window,
+            skipped_policy,
+            raced,
+            undecodable,
+            skipped_budget,
+            skipped_unregistered,
+            skipped_carrier,
+            pending,
+            dry_run,
+            cached,
+            deduplicated,
+        } = other;
+
+        self.shipments += shipments;
+        self.submitted += submitted;
+        self.failed += failed;
+        self.skipped += skipped;
+        self.fee_exceeded += fee_exceeded;
+        self.tx_validation_failed += tx_validation_failed;
+        self.fees_lovelace += fees_lovelace;
+        self.illegal_transitions += illegal_transitions;
+        self.deferred_window += deferred_window;
+        self.skipped_policy += skipped_policy;
+        self.raced += raced;
+        self.undecodable += undecodable;
+        self.skipped_budget += skipped_budget;
+        self.skipped_unregistered += skipped_unregistered;
+        self.skipped_carrier += skipped_carrier;
+        self.pending += pending;
+        self.dry_run += dry_run;
+        self.cached += cached;
+        self.deduplicated += deduplicated;
+    }
+}
+
+/// A close transaction accepted for submission whose tracking UTxO is still unspent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingSubmission {
+    pub utxo_ref: String,
+    pub tx_hash: String,
+    pub submitted_at: DateTime<Utc>,
+}
     
 pub struct DataFetcher {
     blockchain: Arc<CardanoClient>,
-    shipment: Arc<ShipmentClient>,
+    shipment: Arc<dyn TrackingProvider>,
+    notifier: Option<Arc<dyn Notifier>>,
+    state: Option<Arc<dyn StateStore>>,
+    tracking_lookup: Option<Arc<TrackingLookup>>,
+    tenant: Option<String>,
+    submit_window: Option<SubmitWindow>,
+    timestamp_source: TimestampSource,
+    shippo_budget: Option<ShippoBudget>,
+    billing_timezone: Tz,
+    clock: Arc<dyn Clock>,
+    outbox_policy: Option<Arc<OutboxPolicy>>,
+    carrier_policy: Option<CarrierPolicy>,
+    status_mapping: StatusMapping,
+    stale_policy: Option<StaleStatusPolicy>,
+    /// Block times of the transactions that created tracking UTxOs, by UTxO ref
+    created_at: Mutex<HashMap<String, DateTime<Utc>>>,
+    /// Shipments already reported as skipped by the outbox policy
+    policy_skipped: Mutex<HashSet<String>>,
+    /// Shipments already logged as not registered with Shippo
+    unregistered_logged: Mutex<HashSet<String>>,
+    /// Submitted closes awaiting confirmation, by UTxO ref
+    pending: Mutex<HashMap<String, PendingSubmission>>,
+    pending_ttl: chrono::Duration,
+    tracking_cache: Option<TrackingCache>,
+    /// Shipments checked at the same time (`FETCH_CONCURRENCY`)
+    concurrency: usize,
+    /// Closes built, signed and submitted at the same time (`CLOSE_CONCURRENCY`)
+    close_concurrency: usize,
+    /// Closes waiting between the checking and closing stages (`CLOSE_QUEUE_DEPTH`)
+    close_queue_depth: usize,
+    /// Latest finished runs, oldest first, at most `RECENT_RUNS`
+    recent_runs: Mutex<VecDeque<LastRun>>,
+    /// Tracking UTxOs seen by the latest address scan
+    last_scan: Mutex<Option<LastScan>>,
+    /// Report of the latest run that completed
+    last_report: Mutex<Option<RunReport>>,
+    illegal_transitions: AtomicUsize,
+    dry_run: bool,
+    metrics: Metrics,
+}
+
+/// When a `DataFetcher::run` started and finished and how it went
+#[derive(Debug, Clone)]
+pub struct LastRun {
+    /// ID carried by the run's logs and notifications
+    pub run_id: String,
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub finished_at: chrono::DateTime<chrono::Utc>,
+    /// Run counters, or the error that aborted the run
+    pub result: Result<RunStats, String>,
+    /// What the run did with each shipment
+    pub shipments: Vec<ShipmentReport>,
+}
+
+/// Tracking UTxOs found at the oracle address by the latest run
+#[derive(Debug, Clone)]
+pub struct LastScan {
+    pub scanned_at: DateTime<Utc>,
+    pub shipments: Vec<TrackingUTxO>,
+}
+
+/// A close made outside a run, see `DataFetcher::close_shipment`
+#[derive(Debug, Clone)]
+pub struct ManualClose {
+    /// `Submitted`, or `DryRun` when `DRY_RUN` only let the close be signed
+    pub action: ShipmentAction,
+    /// Fee paid by the transaction, in lovelace
+    pub fee: u64,
+}
+
+impl ManualClose {
+    /// Hash of the submitted or, under `DRY_RUN`, signed close transaction
+    pub fn tx_hash(&self) -> &str {
+        self.action.tx_hash().unwrap_or_default()
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        matches!(self.action, ShipmentAction::DryRun { .. })
+    }
+}
+
+/// What a run learnt about a shipment's carrier status, for its `ShipmentReport`
+#[derive(Default)]
+struct ObservedStatus {
+    fetched: Option<String>,
+    details: Option<String>,
+    derived: Option<String>,
+    close_reason: Option<CloseReason>,
+    /// Whether a rate-limited status fetch is retried later in the run rather than failed
+    retry_rate_limited: bool,
+    /// Shippo error the run acts on: `Unauthorized` aborts it, `RateLimited` retries the shipment
+    shippo_error: Option<ShipmentError>,
+    /// Status fetched for an earlier UTxO with the same carrier and tracking number, used instead of fetching
+    shared: Option<TrackingStatus>,
+    /// Status fetched (or reused) for this shipment, for later UTxOs with the same carrier and tracking number
+    status: Option<TrackingStatus>,
+}
+
+impl ObservedStatus {
+    /// Report of `shipment`, checked from `started_at` (`started`) and dealt with by `action`
+    fn report(self, shipment: &TrackingUTxO, action: ShipmentAction, started_at: DateTime<Utc>, started: Instant) -> ShipmentReport {
+        ShipmentReport {
+            utxo_ref: shipment.utxo_ref.to_string(),
+            carrier: shipment.datum.carrier.clone(),
+            tracking_number: shipment.datum.tracking_number.to_string(),
+            outbox_address: shipment.datum.outbox_address.to_string(),
+            fetched_status: self.fetched,
+            status_details: self.details,
+            derived_status: self.derived,
+            close_reason: self.close_reason,
+            action,
+            started_at,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        }
+    }
+}
+
+/// What checking a shipment left for the closing stage
+enum Checked {
+    /// Nothing, the shipment was dealt with
+    Done(ShipmentAction),
+    /// The carrier status is final: close the shipment with `status`
+    Close { status: DerivedStatus, timestamp: CloseTimestamp },
+}
+
+impl From<ShipmentAction> for Checked {
+    fn from(action: ShipmentAction) -> Self {
+        Checked::Done(action)
+    }
+}
+
+/// A shipment queued between the checking and closing stages of a run
+struct CloseJob {
+    /// Position of the shipment in the scan
+    index: usize,
+    shipment: TrackingUTxO,
+    status: DerivedStatus,
+    timestamp: CloseTimestamp,
+    /// Counters of the check, the close's are added to
+    stats: RunStats,
+    observed: ObservedStatus,
+    now: DateTime<Utc>,
+    started: Instant,
+    /// The shipment's span, so that the close is logged under it
+    span: Span,
+}
+
+/// Builds the fetcher for `config` with its notifiers, state database and tracking lookup
+///
+/// A tenant's state lives under its own namespace, so tenants may share a database.
+pub fn from_config(config: &Config) -> anyhow::Result<DataFetcher> {
+    let metrics = Metrics::new()
+        .for_tenant(config.tenant.as_deref())
+        .with_outbox_labels(config.outbox_aliases.clone(), config.metrics_max_outboxes);
+    let state = state::from_config(config)?;
+    let blockchain = indexer::apply_scan_mode(CardanoClient::new(config.clone())?, config, state.as_ref())?;
+    let blockchain = Arc::new(blockchain.with_metrics(metrics.clone()));
+    let shippo = ShipmentClient::new(config.clone())?.with_metrics(metrics.clone());
+    let shipment = Arc::new(tracking_provider::from_config(config, shippo, &metrics)?);
+
+    Ok(from_parts(config, blockchain, shipment, notifier::from_config(config, &metrics)?, state)?.with_metrics(metrics))
+}
+
+/// Builds the fetcher for `config` around already constructed clients, notifier and state store
+pub fn from_parts(
+    config: &Config,
+    blockchain: Arc<CardanoClient>,
+    shipment: Arc<dyn TrackingProvider>,
+    notifier: Option<Arc<dyn Notifier>>,
+    state: Option<Arc<dyn StateStore>>,
+) -> anyhow::Result<DataFetcher> {
+    let mut data_fetcher = match notifier {
+        Some(notifier) => DataFetcher::with_notifier(blockchain, shipment, notifier),
+        None => DataFetcher::new(blockchain, shipment),
+    };
+
+    let mut tracking_lookup = match &config.tracking_lookup_path {
+        Some(path) => Some(TrackingLookup::from_file(path)?),
+        None => None,
+    };
+
+    if let Some(state) = state {
+        tracking_lookup = Some(tracking_lookup.unwrap_or_default().with_state(state.clone()));
+        data_fetcher = data_fetcher.with_state(state);
+    }
+
+    if let Some(tracking_lookup) = tracking_lookup {
+        data_fetcher = data_fetcher.with_tracking_lookup(Arc::new(tracking_lookup));
+    }
+
+    if let Some(tenant) = &config.tenant {
+        data_fetcher = data_fetcher.with_tenant(tenant);
+    }
+
+    if let Some(window) = config.submit_window {
+        data_fetcher = data_fetcher.with_submit_window(window);
+    }
+
+    data_fetcher = data_fetcher
+        .with_timestamp_source(config.timestamp_source)
+        .with_billing_timezone(config.cron_timezone)
+        .with_pending_ttl(chrono::Duration::minutes(config.pending_tx_ttl_minutes as i64))
+        .with_concurrency(config.fetch_concurrency)
+        .with_close_concurrency(config.close_concurrency)
+        .with_close_queue_depth(config.close_queue_depth)
+        .with_status_mapping(config.status_mapping.clone())
+        .with_dry_run(config.dry_run);
+
+    if let Some(budget) = shippo_budget::from_config(config) {
+        data_fetcher = data_fetcher.with_shippo_budget(budget);
+    }
+
+    if config.tracking_cache_ttl_secs > 0 {
+        let ttl = chrono::Duration::seconds(config.tracking_cache_ttl_secs as i64);
+        data_fetcher = data_fetcher.with_tracking_cache(TrackingCache::new(ttl).with_mapping(config.status_mapping.clone()));
+    }
+
+    if let Some(policy) = outbox_policy::from_config(config)? {
+        data_fetcher = data_fetcher.with_outbox_policy(Arc::new(policy));
+    }
+
+    if let Some(policy) = carrier_policy::from_config(config) {
+        data_fetcher = data_fetcher.with_carrier_policy(policy);
+    }
+
+    if let Some(policy) = stale_status::from_config(config) {
+        data_fetcher = data_fetcher.with_stale_policy(policy);
+    }
+
+    Ok(data_fetcher)
 }
 
 impl DataFetcher {
-    pub fn new(blockchain: Arc<CardanoClient>, shipment: Arc<ShipmentClient>) -> Self {
-        Self { blockchain, shipment }
+    pub fn new(blockchain: Arc<CardanoClient>, shipment: Arc<dyn TrackingProvider>) -> Self {
+        Self {
+            blockchain,
+            shipment,
+            notifier: None,
+            state: None,
+            tracking_lookup: None,
+            tenant: None,
+            submit_window: None,
+            timestamp_source: TimestampSource::default(),
+            shippo_budget: None,
+            billing_timezone: Tz::UTC,
+            clock: Arc::new(SystemClock),
+            outbox_policy: None,
+            carrier_policy: None,
+            status_mapping: StatusMapping::default(),
+            stale_policy: None,
+            created_at: Mutex::new(HashMap::new()),
+            policy_skipped: Mutex::new(HashSet::new()),
+            unregistered_logged: Mutex::new(HashSet::new()),
+            pending: Mutex::new(HashMap::new()),
+            pending_ttl: chrono::Duration::minutes(DEFAULT_PENDING_TX_TTL_MINUTES as i64),
+            tracking_cache: None,
+            concurrency: DEFAULT_FETCH_CONCURRENCY,
+            close_concurrency: DEFAULT_CLOSE_CONCURRENCY,
+            close_queue_depth: DEFAULT_CLOSE_QUEUE_DEPTH,
+            recent_runs: Mutex::new(VecDeque::with_capacity(RECENT_RUNS)),
+            last_scan: Mutex::new(None),
+            last_report: Mutex::new(None),
+            illegal_transitions: AtomicUsize::new(0),
+            dry_run: false,
+            metrics: Metrics::new(),
+        }
     }
 
-    pub async fn run(&self) -> anyhow::Result<()> {
-        let shipments = self.blockchain.fetch_shipments().await?;
+    pub fn with_notifier(
+        blockchain: Arc<CardanoClient>,
+        shipment: Arc<dyn TrackingProvider>,
+        notifier: Arc<dyn Notifier>,
+    ) -> Self {
+        Self { notifier: Some(notifier), ..Self::new(blockchain, shipment) }
+    }
 
-        for shipment in shipments {
-            let shipment_response = self.shipment
-                .fetch_shipment_status(
-                    &shipment.datum.carrier,
-                    &shipment.datum.tracking_number,
-                )
-                .await;
+    /// Persist shipment outcomes and submissions to `state`
+    pub fn with_state(mut self, state: Arc<dyn StateStore>) -> Self {
+        self.state = Some(state);
+        self
+    }
 
-            if shipment_response.is_err() {
-                println!("❌ Failed to fetch shipment status for {}/{}: {}", shipment.datum.carrier, shipment.datum.tracking_number, shipment_response.err().unwrap());
-                continue;
+    /// Resolve privacy-mode tracking hashes through `tracking_lookup`
+    pub fn with_tracking_lookup(mut self, tracking_lookup: Arc<TrackingLookup>) -> Self {
+        self.tracking_lookup = Some(tracking_lookup);
+        self
+    }
+
+    /// Label logs and events with the tenant running this fetcher
+    pub fn with_tenant(mut self, tenant: &str) -> Self {
+        self.tenant = Some(tenant.to_string());
+        self
+    }
+
+    /// Only submit closures while `window` is open
+    pub fn with_submit_window(mut self, window: SubmitWindow) -> Self {
+        self.submit_window = Some(window);
+        self
+    }
+
+    /// Take the `p_timestamp` of closes made by `run` from `source`
+    pub fn with_timestamp_source(mut self, source: TimestampSource) -> Self {
+        self.timestamp_source = source;
+        self
+    }
+
+    /// Count Shippo tracking calls per calendar month in `timezone` (default: UTC)
+    pub fn with_billing_timezone(mut self, timezone: Tz) -> Self {
+        self.billing_timezone = timezone;
+        self
+    }
+
+    /// Limit polling once the month's tracking calls reach `budget`; needs a state store
+    pub fn with_shippo_budget(mut self, budget: ShippoBudget) -> Self {
+        self.shippo_budget = Some(budget);
+        self
+    }
+
+    /// Read the time for the submit window and close timestamps from `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Leave shipments with a submitted close alone for `ttl` while their UTxO stays unspent
+    pub fn with_pending_ttl(mut self, ttl: chrono::Duration) -> Self {
+        self.pending_ttl = ttl;
+        self
+    }
+
+    /// Check up to `concurrency` shipments at the same time (at least one)
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Build, sign and submit up to `concurrency` closes at the same time (at least one)
+    pub fn with_close_concurrency(mut self, concurrency: usize) -> Self {
+        self.close_concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Let up to `depth` closes wait for the closing stage before checking waits too (at least one)
+    pub fn with_close_queue_depth(mut self, depth: usize) -> Self {
+        self.close_queue_depth = depth.max(1);
+        self
+    }
+
+    /// Reuse non-final carrier statuses from `cache` instead of fetching them every run
+    pub fn with_tracking_cache(mut self, cache: TrackingCache) -> Self {
+        self.tracking_cache = Some(cache);
+        self
+    }
+
+    /// Skip shipments whose outbox address `policy` refuses
+    pub fn with_outbox_policy(mut self, policy: Arc<OutboxPolicy>) -> Self {
+        self.outbox_policy = Some(policy);
+        self
+    }
+
+    /// Close shipments whose carrier status `mapping` maps, with the on-chain status it maps to
+    pub fn with_status_mapping(mut self, mapping: StatusMapping) -> Self {
+        self.status_mapping = mapping;
+        self
+    }
+
+    /// Skip shipments whose carrier `policy` refuses, before any tracking call
+    pub fn with_carrier_policy(mut self, policy: CarrierPolicy) -> Self {
+        self.carrier_policy = Some(policy);
+        self
+    }
+
+    /// Close shipments stuck in a stale carrier status for longer than `policy` allows
+    pub fn with_stale_policy(mut self, policy: StaleStatusPolicy) -> Self {
+        self.stale_policy = Some(policy);
+        self
+    }
+
+    /// Sign the closes of `run` without submitting them
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Record run outcomes in `metrics`
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    pub fn outbox_policy(&self) -> Option<&Arc<OutboxPolicy>> {
+        self.outbox_policy.as_ref()
+    }
+
+    pub fn tenant(&self) -> Option<&str> {
+        self.tenant.as_deref()
+    }
+
+    /// Whether closures may be submitted now; `None` without a submit window
+    pub fn submit_window_open(&self) -> Option<bool> {
+        self.submit_window.map(|window| window.is_open(self.clock.now()))
+    }
+
+    pub fn blockchain(&self) -> &CardanoClient {
+        &self.blockchain
+    }
+
+    pub fn state(&self) -> Option<&Arc<dyn StateStore>> {
+        self.state.as_ref()
+    }
+
+    pub fn last_run(&self) -> Option<LastRun> {
+        self.recent_runs.lock().unwrap_or_else(|e| e.into_inner()).back().cloned()
+    }
+
+    /// The last `RECENT_RUNS` finished runs, aborted ones included, newest first
+    pub fn recent_runs(&self) -> Vec<LastRun> {
+        self.recent_runs.lock().unwrap_or_else(|e| e.into_inner()).iter().rev().cloned().collect()
+    }
+
+    /// Tracking UTxOs the latest address scan found; `None` before any run scanned
+    pub fn last_scan(&self) -> Option<LastScan> {
+        self.last_scan.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Report of the latest run that completed; aborted runs only update `last_run`
+    pub fn last_report(&self) -> Option<RunReport> {
+        self.last_report.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Shippo tracking calls of the current billing period, from the state store; `None` without one
+    pub async fn shippo_usage(&self) -> anyhow::Result<Option<UsageReport>> {
+        let Some(state) = &self.state else {
+            return Ok(None);
+        };
+
+        let period = shippo_budget::period(self.clock.now(), self.billing_timezone);
+        let budget = self.shippo_budget.map(|budget| budget.monthly_calls);
+        UsageReport::from_state(state.as_ref(), &period, budget).await.map(Some)
+    }
+
+    /// Fees paid for every journalled closure, from the state store; `None` without one
+    pub async fn fees(&self) -> anyhow::Result<Option<FeeReport>> {
+        let Some(state) = &self.state else {
+            return Ok(None);
+        };
+
+        FeeReport::from_state(state.as_ref(), None).await.map(Some)
+    }
+
+    /// Close transactions submitted but not seen confirmed yet, by UTxO ref
+    pub fn pending_submissions(&self) -> Vec<PendingSubmission> {
+        let mut pending: Vec<PendingSubmission> =
+            self.pending.lock().unwrap_or_else(|e| e.into_inner()).values().cloned().collect();
+        pending.sort_by(|a, b| a.utxo_ref.cmp(&b.utxo_ref));
+        pending
+    }
+
+    /// Lifecycle transitions refused since the fetcher was built
+    pub fn illegal_transitions(&self) -> usize {
+        self.illegal_transitions.load(Ordering::Relaxed)
+    }
+
+    /// Check every tracking UTxO once and close those whose carrier status is final
+    ///
+    /// Every log line and event of the run carries its ID (see `run_id`). The
+    /// report lists what was done with each shipment, failures included.
+    pub async fn run(&self) -> anyhow::Result<RunReport> {
+        let run_id = run_id::generate();
+        let started_at = Utc::now();
+        let span = info_span!("run", %run_id, tenant = self.tenant.as_deref());
+        let mut shipments = Vec::new();
+        let result = run_id::scope(
+            run_id.clone(),
+            async {
+                info!("🏁 Starting run");
+                self.run_shipments(&mut shipments).await
+            }
+            .instrument(span),
+        )
+        .await;
+        let finished_at = Utc::now();
+        if let Ok(stats) = &result
+            && stats.failed == 0
+        {
+            self.metrics.run_succeeded(finished_at.timestamp());
+        }
+
+        let mut recent_runs = self.recent_runs.lock().unwrap_or_else(|e| e.into_inner());
+        if recent_runs.len() >= RECENT_RUNS {
+            recent_runs.pop_front();
+        }
+        recent_runs.push_back(LastRun {
+            run_id: run_id.clone(),
+            started_at,
+            finished_at,
+            result: result.as_ref().map(|stats| *stats).map_err(|e| format!("{:#}", e)),
+            shipments: shipments.clone(),
+        });
+        drop(recent_runs);
+
+        let report = RunReport {
+            run_id,
+            tenant: self.tenant.clone(),
+            started_at,
+            finished_at,
+            stats: result?,
+            outboxes: run_report::outbox_subtotals(&shipments),
+            shipments,
+        };
+        *self.last_report.lock().unwrap_or_else(|e| e.into_inner()) = Some(report.clone());
+
+        Ok(report)
+    }
+
+    async fn run_shipments(&self, reports: &mut Vec<ShipmentReport>) -> anyhow::Result<RunStats> {
+        let illegal_before = self.illegal_transitions();
+        let scan = self.blockchain.scan_shipments().await?;
+        for undecodable in &scan.undecodable {
+            warn!(utxo_ref = %undecodable.utxo_ref, rejection = %undecodable.rejection, "⚠️  Skipping undecodable datum");
+        }
+        if scan.truncated {
+            warn!("⚠️  Oracle address scan stopped after BLOCKFROST_MAX_PAGES pages; later UTxOs are not seen this run");
+        }
+        let truncated = scan.truncated;
+        let shipments = scan.shipments;
+        *self.last_scan.lock().unwrap_or_else(|e| e.into_inner()) =
+            Some(LastScan { scanned_at: Utc::now(), shipments: shipments.clone() });
+        self.metrics.shipments_discovered(shipments.len());
+        let mut stats = RunStats {
+            shipments: shipments.len(),
+            undecodable: scan.undecodable.len(),
+            ..RunStats::default()
+        };
+        let unspent: HashSet<String> = shipments
+            .iter()
+            .map(|shipment| shipment.utxo_ref.to_string())
+            .collect();
+        if let Some(policy) = &self.outbox_policy
+            && let Err(e) = policy.refresh()
+        {
+            warn!(error = %format!("{:#}", e), "⚠️  Failed to reload outbox lists, keeping the previous ones");
+        }
+
+        // Shipments are checked by one stage and closed by another, connected by a queue of at most
+        // `close_queue_depth` closes: once it is full, checking waits for closes to catch up
+        let window_closed = Mutex::new(None);
+        // Set once Shippo refuses the API key: no shipment is checked or closed after that, those in flight finish
+        let aborted = AtomicBool::new(false);
+        let (closes, queued) = mpsc::channel(self.close_queue_depth);
+        let ((checked_stats, mut processed, refused), (closed_stats, closed)) = futures::join!(
+            self.check_shipments(shipments, &window_closed, &aborted, closes),
+            self.close_queued(queued, &aborted),
+        );
+        self.metrics.set_close_queue_depth(0);
+        if let Some(message) = refused {
+            self.flush_notifications().await;
+            anyhow::bail!("Shippo refused the API key, aborting the run: {}", message);
+        }
+        stats += checked_stats;
+        stats += closed_stats;
+        processed.extend(closed);
+
+        // Reports follow the scan order, whichever shipment finished first
+        processed.sort_by_key(|(index, _)| *index);
+        reports.extend(processed.into_iter().map(|(_, report)| report));
+
+        // A truncated scan does not list every unspent UTxO, so none can be told apart from a spent one
+        if !truncated {
+            self.settle_lifecycles(&unspent).await;
+            self.pending
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .retain(|utxo_ref, _| unspent.contains(utxo_ref));
+            self.created_at
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .retain(|utxo_ref, _| unspent.contains(utxo_ref));
+        }
+        self.metrics.set_pending_closes(self.pending.lock().unwrap_or_else(|e| e.into_inner()).len());
+        self.flush_notifications().await;
+        stats.illegal_transitions = self.illegal_transitions() - illegal_before;
+
+        Ok(stats)
+    }
+
+    /// Checking stage: fetch every shipment's status, queueing on `closes` those to close
+    ///
+    /// Up to `concurrency` shipments are checked at once; shipments Shippo rate
+    /// limits are checked again once, after the others. A refused API key sets
+    /// `aborted`, stopping both stages, and is returned with what was checked.
+    async fn check_shipments(
+        &self,
+        shipments: Vec<TrackingUTxO>,
+        window_closed: &Mutex<Option<DateTime<Utc>>>,
+        aborted: &AtomicBool,
+        closes: mpsc::Sender<CloseJob>,
+    ) -> (RunStats, Vec<(usize, ShipmentReport)>, Option<String>) {
+        let mut stats = RunStats::default();
+        let mut queue: Vec<(usize, TrackingUTxO)> = shipments.into_iter().enumerate().collect();
+        let mut processed = Vec::with_capacity(queue.len());
+        let mut retrying = false;
+        loop {
+            let mut rate_limited = Vec::new();
+            let mut wait = Duration::ZERO;
+            let retry_rate_limited = !retrying;
+            let closes = &closes;
+            let mut results = futures::stream::iter(group_duplicates(queue))
+                .map(|group| async move {
+                    if let [(_, first), _, ..] = group.as_slice() {
+                        info!(
+                            carrier = %first.datum.carrier,
+                            tracking_number = %first.datum.tracking_number,
+                            utxos = group.len(),
+                            "🔁 Tracking UTxOs share a carrier and tracking number, fetching the status once",
+                        );
+                    }
+
+                    let mut shared = None;
+                    let mut outcomes = Vec::with_capacity(group.len());
+                    for (index, shipment) in group {
+                        if aborted.load(Ordering::Relaxed) {
+                            break;
+                        }
+                        let now = self.clock.now();
+                        let started = Instant::now();
+                        let mut stats = RunStats::default();
+                        let mut observed = ObservedStatus {
+                            retry_rate_limited,
+                            shared: shared.clone(),
+                            ..ObservedStatus::default()
+                        };
+                        let span = info_span!(
+                            "shipment",
+                            carrier = %shipment.datum.carrier,
+                            tracking_number = %shipment.datum.tracking_number,
+                            utxo_ref = %shipment.utxo_ref,
+                        );
+                        let checked = self
+                            .run_shipment(&shipment, now, &mut stats, window_closed, &mut observed)
+                            .instrument(span.clone())
+                            .await;
+                        if shared.is_none() {
+                            shared = observed.status.take();
+                        }
+
+                        let action = match checked {
+                            Checked::Done(action) => action,
+                            Checked::Close { status, timestamp } => {
+                                let job = CloseJob { index, shipment, status, timestamp, stats, observed, now, started, span };
+                                // The closing stage only stops once every sender is dropped
+                                let _ = closes.send(job).await;
+                                self.metrics.set_close_queue_depth(closes.max_capacity() - closes.capacity());
+                                continue;
+                            }
+                        };
+                        let shippo_error = observed.shippo_error.take();
+                        let unauthorized = matches!(shippo_error, Some(ShipmentError::Unauthorized(_)));
+                        let report = observed.report(&shipment, action, now, started);
+                        outcomes.push((index, shipment, stats, shippo_error, report));
+                        if unauthorized {
+                            aborted.store(true, Ordering::Relaxed);
+                            break;
+                        }
+                    }
+                    outcomes
+                })
+                .buffer_unordered(self.concurrency);
+
+            let mut refused = None;
+            while let Some(outcomes) = results.next().await {
+                for (index, shipment, shipment_stats, shippo_error, report) in outcomes {
+                    match shippo_error {
+                        Some(ShipmentError::Unauthorized(message)) => {
+                            refused.get_or_insert(message);
+                        }
+                        // Counted by the retry instead
+                        Some(ShipmentError::RateLimited { retry_after, .. }) => {
+                            wait = wait.max(retry_after.unwrap_or(DEFAULT_RATE_LIMIT_WAIT));
+                            rate_limited.push((index, shipment));
+                        }
+                        _ => {
+                            stats += shipment_stats;
+                            processed.push((index, report));
+                        }
+                    }
+                }
+            }
+
+            if refused.is_some() || rate_limited.is_empty() {
+                return (stats, processed, refused);
             }
+            let wait = wait.min(MAX_RATE_LIMIT_WAIT);
+            warn!(shipments = rate_limited.len(), wait_seconds = wait.as_secs(), "⏳ Rate limited by Shippo, retrying");
+            tokio::time::sleep(wait).await;
+            queue = rate_limited;
+            retrying = true;
+        }
+    }
+
+    /// Closing stage: build, sign and submit the closes `queued` by the checking stage
+    ///
+    /// Up to `close_concurrency` closes are made at once, and a failed close only
+    /// fails its shipment. The queue is drained even once `aborted`, so that no
+    /// close is cancelled between its submission and its journal entry; closes
+    /// still queued by then are dropped unsubmitted, for the next run to check again.
+    async fn close_queued(
+        &self,
+        queued: mpsc::Receiver<CloseJob>,
+        aborted: &AtomicBool,
+    ) -> (RunStats, Vec<(usize, ShipmentReport)>) {
+        let jobs = futures::stream::unfold(queued, |mut queued| async move {
+            let job = queued.recv().await?;
+            self.metrics.set_close_queue_depth(queued.len());
+            Some((job, queued))
+        });
+        let mut results = jobs
+            .map(|job| async move {
+                let CloseJob { index, shipment, status, timestamp, mut stats, observed, now, started, span } = job;
+                if aborted.load(Ordering::Relaxed) {
+                    return None;
+                }
+                let action = self.close_checked(&shipment, status, &timestamp, &mut stats).instrument(span).await;
+                Some((index, stats, observed.report(&shipment, action, now, started)))
+            })
+            .buffer_unordered(self.close_concurrency);
+
+        let mut stats = RunStats::default();
+        let mut processed = Vec::new();
+        while let Some(outcome) = results.next().await {
+            if let Some((index, shipment_stats, report)) = outcome {
+                stats += shipment_stats;
+                processed.push((index, report));
+            }
+        }
+
+        (stats, processed)
+    }
+
+    /// Check one tracking UTxO, returning the close to make if its carrier status is final
+    async fn run_shipment(
+        &self,
+        shipment: &TrackingUTxO,
+        now: DateTime<Utc>,
+        stats: &mut RunStats,
+        window_closed: &Mutex<Option<DateTime<Utc>>>,
+        observed: &mut ObservedStatus,
+    ) -> Checked {
+        let utxo_ref = shipment.utxo_ref.to_string();
+        self.discover(&utxo_ref).await;
 
-            let tracking_status = shipment_response.unwrap();
+        // Left behind by a self-test that did not get to close it
+        if shipment.datum.carrier == SELF_TEST_CARRIER {
+            info!("🧪 Skipping self-test shipment");
+            return skipped("self-test shipment").into();
+        }
+
+        if let Some(pending) = self.pending_submission(&utxo_ref, now).await {
+            info!(tx_hash = %pending.tx_hash, "⏳ Close pending, skipping");
+            stats.pending += 1;
+            return skipped(format!("close pending in {}", pending.tx_hash)).into();
+        }
 
-            println!("🔗 UTxO: {}#{}", shipment.tx_hash, shipment.tx_index);
-            println!("🚚 Carrier: {}", shipment.datum.carrier);
-            println!("📦 Tracking: {}", shipment.datum.tracking_number);
-            println!("📍 Status: {} - {}", tracking_status.status, tracking_status.status_details);
+        if let Some(policy) = &self.outbox_policy
+            && let Err(violation) = policy.check(&shipment.datum.outbox_address)
+        {
+            warn!(outbox_address = %shipment.datum.outbox_address, "🚫 Skipping: {}", violation);
+            self.record_skipped_policy(shipment, &utxo_ref, &violation.to_string()).await;
+            stats.skipped_policy += 1;
+            return skipped(violation.to_string()).into();
+        }
+
+        if let Some(policy) = &self.carrier_policy
+            && let Err(violation) = policy.check(&shipment.datum.carrier)
+        {
+            info!("🚫 Skipping: {}", violation);
+            stats.skipped_carrier += 1;
+            return skipped(violation.to_string()).into();
+        }
 
-            let status = get_status(&tracking_status);
+        let Some(tracking_number) = self.resolve_tracking_number(&shipment.datum.tracking_number).await else {
+            info!("ℹ️  No tracking number registered for the hash, skipping");
+            stats.skipped += 1;
+            return skipped("no tracking number registered for the hash").into();
+        };
+
+        let known = match observed.shared.take() {
+            Some(tracking_status) => {
+                info!("🔁 Reusing the carrier status fetched for a tracking UTxO with the same tracking number");
+                stats.deduplicated += 1;
+                Some(tracking_status)
+            }
+            None => {
+                let cached = self
+                    .tracking_cache
+                    .as_ref()
+                    .and_then(|cache| cache.get(&shipment.datum.carrier, &tracking_number, now));
+                if cached.is_some() {
+                    debug!("Carrier status from the tracking cache");
+                    stats.cached += 1;
+                }
+                cached
+            }
+        };
+        let billed = known.is_none() && self.shipment.provider(&shipment.datum.carrier) == ProviderKind::Shippo;
+        if billed && !self.within_budget(&utxo_ref, now).await {
+            info!("💸 Shippo budget spent, not polling");
+            stats.skipped_budget += 1;
+            return skipped("Shippo budget spent").into();
+        }
 
-            if status.is_some() {
-                let submit_result = self.blockchain
-                    .submit_shipment(
-                        &shipment,
-                        &status.unwrap(),
+        let shipment_response = match known {
+            Some(tracking_status) => Ok(tracking_status),
+            None => {
+                let response = self.shipment
+                    .fetch_status(
+                        &shipment.datum.carrier,
+                        &tracking_number,
                     )
                     .await;
+                if billed {
+                    self.record_tracking_call(shipment, now).await;
+                }
+                if let (Some(cache), Ok(tracking_status)) = (&self.tracking_cache, &response) {
+                    cache.insert(&shipment.datum.carrier, &tracking_number, tracking_status, now);
+                }
+                response
+            }
+        };
+        if let Ok(tracking_status) = &shipment_response {
+            observed.status = Some(tracking_status.clone());
+        }
 
-                if submit_result.is_err() {
-                    println!("❌ Failed to submit transaction: {}", submit_result.err().unwrap());
-                } else{
-                    println!("✅ Submitted transaction: {}", submit_result.unwrap());
+        match shipment_response.as_ref().err().and_then(|e| e.downcast_ref::<ShipmentError>()) {
+            Some(not_registered @ ShipmentError::NotFound { .. }) => {
+                let first = self.unregistered_logged.lock().unwrap_or_else(|e| e.into_inner()).insert(utxo_ref.clone());
+                if first {
+                    info!("ℹ️  {}, skipping until it is registered (or set SHIPPO_AUTO_REGISTER=true)", not_registered);
+                } else {
+                    debug!("{}, skipping", not_registered);
                 }
-            } else {
-                println!("ℹ️  Status is not final, skipping update");
+                stats.skipped_unregistered += 1;
+                return skipped(not_registered.to_string()).into();
+            }
+            Some(unauthorized @ ShipmentError::Unauthorized(_)) => {
+                error!(error = %unauthorized, "🔑 Shippo refused the API key");
+                observed.shippo_error = Some(unauthorized.clone());
+                return ShipmentAction::Failed { error: format!("Failed to fetch shipment status: {}", unauthorized) }.into();
+            }
+            Some(rate_limited @ ShipmentError::RateLimited { .. }) if observed.retry_rate_limited => {
+                info!("⏳ Rate limited by Shippo, retrying later in the run");
+                observed.shippo_error = Some(rate_limited.clone());
+                return skipped("rate limited by Shippo").into();
             }
+            _ => {}
+        }
+
+        let tracking_status = match shipment_response {
+            Ok(tracking_status) => tracking_status,
+            Err(e) => {
+                error!(error = %format!("{:#}", e), "❌ Failed to fetch shipment status");
+                stats.failed += 1;
+                self.metrics.close_failed(&shipment.datum.outbox_address.to_string());
+                self.record_failure(&utxo_ref, None).await;
+                return ShipmentAction::Failed { error: format!("Failed to fetch shipment status: {:#}", e) }.into();
+            }
+        };
 
-            println!("================================");
+        info!(status = %tracking_status.status, details = %tracking_status.status_details, "📍 Carrier status");
+        observed.fetched = Some(tracking_status.status.to_string());
+        observed.details = Some(tracking_status.status_details.clone());
+
+        let mut status = self.status_mapping.map(&tracking_status);
+        if status.is_none()
+            && let Some(deadline) = passed_deadline(&shipment.datum, now)
+        {
+            info!(deadline, "⌛ Deadline passed, closing as {}", TIMEOUT_STATUS);
+            status = Some(TIMEOUT_STATUS);
+            observed.close_reason = Some(CloseReason::Deadline);
+        } else if status.is_none()
+            && let Some(created_at) = self.stale_since(shipment, &utxo_ref, &tracking_status.status, now).await
+        {
+            info!(created_at = %created_at.to_rfc3339(), "🕸️  Stuck in {}, closing as {}", tracking_status.status, TIMEOUT_STATUS);
+            status = Some(TIMEOUT_STATUS);
+            observed.close_reason = Some(CloseReason::StaleTimeout);
         }
+        observed.derived = status.map(|status| status.to_string());
+
+        if let Some(status) = status
+            && let Some(open_at) = self.window_deferral(window_closed, now)
+        {
+            info!(open_at = %open_at.to_rfc3339(), "⏸️  Outside the submit window, deferring");
+            self.record_deferred(&utxo_ref, status, open_at).await;
+            stats.deferred_window += 1;
+            skipped(format!("outside the submit window until {}", open_at.to_rfc3339())).into()
+        } else if let Some(status) = status {
+            let timestamp = self.timestamp_source.resolve(tracking_status.status_date.as_deref(), now);
+            if let Some(reason) = &timestamp.fallback {
+                warn!(%reason, "⚠️  Using the oracle clock as timestamp");
+            } else if timestamp.source == TimestampSource::Carrier {
+                info!(timestamp = timestamp.timestamp, oracle_timestamp = timestamp.oracle_timestamp, "🕒 Timestamp from the carrier");
+            }
 
-        Ok(())
+            Checked::Close { status, timestamp }
+        } else {
+            info!("ℹ️  Status is not final, skipping update");
+            self.advance(&utxo_ref, ShipmentLifecycle::AwaitingCarrier).await;
+            self.record_carrier_status(&utxo_ref, tracking_status.status.as_str(), now).await;
+            skipped("status is not final").into()
+        }
+    }
+
+    /// Close a shipment the checking stage found final, counting the outcome in `stats`
+    async fn close_checked(
+        &self,
+        shipment: &TrackingUTxO,
+        status: DerivedStatus,
+        timestamp: &CloseTimestamp,
+        stats: &mut RunStats,
+    ) -> ShipmentAction {
+        let result = if self.dry_run {
+            self.sign_close(shipment, status, timestamp).await
+        } else {
+            self.close_shipment_with(shipment, status, timestamp)
+                .await
+                .map(|closed| (closed.fee, ShipmentAction::Submitted { tx_hash: closed.tx_hash }))
+        };
+
+        match result {
+            Ok((fee, action)) => {
+                let tx_hash = action.tx_hash().unwrap_or_default();
+                if self.dry_run {
+                    info!(%status, tx_hash, fee, "🧪 Dry run, not submitting transaction");
+                    stats.dry_run += 1;
+                } else {
+                    info!(%status, tx_hash, fee, "✅ Submitted transaction");
+                    stats.submitted += 1;
+                    self.metrics.close_submitted(&shipment.datum.outbox_address.to_string());
+                    stats.fees_lovelace += fee;
+                }
+                action
+            }
+            Err(e) if e.downcast_ref::<Raced>().is_some() => {
+                info!(error = %format!("{:#}", e), "🏁 Raced, not signing");
+                stats.raced += 1;
+                skipped(e.to_string())
+            }
+            Err(e) if is_already_spent(&e) => {
+                info!(error = %format!("{:#}", e), "ℹ️  Tracking UTxO already spent, nothing left to close");
+                stats.raced += 1;
+                skipped(format!("tracking UTxO already spent: {}", e))
+            }
+            Err(e) => {
+                if e.downcast_ref::<FeeExceeded>().is_some() {
+                    error!(error = %format!("{:#}", e), "⛔ Refusing to sign");
+                    stats.fee_exceeded += 1;
+                } else if let Some(invalid) = e.downcast_ref::<TxValidationFailed>() {
+                    let violations: Vec<String> = invalid.violations.iter().map(ToString::to_string).collect();
+                    error!(
+                        violations = %violations.join("; "),
+                        "⛔ Refusing to sign, resolved transaction is not the requested close"
+                    );
+                    stats.tx_validation_failed += 1;
+                } else {
+                    error!(error = %format!("{:#}", e), "❌ Failed to submit transaction");
+                }
+                stats.failed += 1;
+                self.metrics.close_failed(&shipment.datum.outbox_address.to_string());
+                ShipmentAction::Failed { error: format!("{:#}", e) }
+            }
+        }
     }
+
+    /// Close a tracking UTxO with `status` whatever its carrier says, stamped `timestamp` (default: now)
+    ///
+    /// The close takes the same path as a run's: the outbox policy applies, a
+    /// close still pending is not repeated, and the attempt is journalled,
+    /// tracked as pending and notified. Under `DRY_RUN` it is only signed.
+    /// Batched chat notifications are only delivered by `flush_notifications`.
+    pub async fn close_shipment(
+        &self,
+        shipment: &TrackingUTxO,
+        status: DerivedStatus,
+        timestamp: Option<u64>,
+    ) -> anyhow::Result<ManualClose> {
+        let utxo_ref = shipment.utxo_ref.to_string();
+        let now = self.clock.now();
+        if let Some(policy) = &self.outbox_policy
+            && let Err(violation) = policy.check(&shipment.datum.outbox_address)
+        {
+            anyhow::bail!("Refusing to close {}: {}", utxo_ref, violation);
+        }
+        if let Some(pending) = self.pending_submission(&utxo_ref, now).await {
+            anyhow::bail!("A close of {} is already pending in {}", utxo_ref, pending.tx_hash);
+        }
+
+        let oracle = CloseTimestamp::oracle(now);
+        let close_timestamp = match timestamp {
+            Some(timestamp) => CloseTimestamp { timestamp, source: TimestampSource::Operator, ..oracle },
+            None => oracle,
+        };
+        if self.dry_run {
+            let (fee, action) = self.sign_close(shipment, status, &close_timestamp).await?;
+            info!(%utxo_ref, %status, tx_hash = action.tx_hash().unwrap_or_default(), fee, "🧪 Dry run, not submitting transaction");
+            return Ok(ManualClose { action, fee });
+        }
+
+        let closed = self.close_shipment_with(shipment, status, &close_timestamp).await?;
+        info!(%utxo_ref, %status, tx_hash = %closed.tx_hash, fee = closed.fee, "✅ Submitted transaction");
+        Ok(ManualClose { action: ShipmentAction::Submitted { tx_hash: closed.tx_hash }, fee: closed.fee })
+    }
+
+    async fn close_shipment_with(
+        &self,
+        shipment: &TrackingUTxO,
+        status: DerivedStatus,
+        close_timestamp: &CloseTimestamp,
+    ) -> anyhow::Result<ClosedShipment> {
+        let utxo_ref = shipment.utxo_ref.to_string();
+        let timestamp = close_timestamp.timestamp;
+        self.discover(&utxo_ref).await;
+        self.advance(&utxo_ref, ShipmentLifecycle::FinalStatusKnown).await;
+        let result = self.blockchain.close_shipment_at(shipment, status, timestamp).await;
+        self.record_attempt(&utxo_ref, status, close_timestamp.oracle_timestamp, &result).await;
+
+        let event = match &result {
+            Ok(closed) => {
+                self.record_closed(shipment, &utxo_ref, status, closed, timestamp).await;
+                self.advance(&utxo_ref, ShipmentLifecycle::Submitted { tx_hash: closed.tx_hash.clone() }).await;
+                self.pending.lock().unwrap_or_else(|e| e.into_inner()).insert(
+                    utxo_ref.clone(),
+                    PendingSubmission {
+                        utxo_ref: utxo_ref.clone(),
+                        tx_hash: closed.tx_hash.clone(),
+                        submitted_at: DateTime::from_timestamp(close_timestamp.oracle_timestamp as i64, 0)
+                            .unwrap_or_else(|| self.clock.now()),
+                    },
+                );
+                OracleEvent::ShipmentClosed {
+                    utxo_ref,
+                    carrier: shipment.datum.carrier.clone(),
+                    tracking_number: shipment.datum.tracking_number.to_string(),
+                    status: status.to_string(),
+                    timestamp,
+                    timestamp_source: close_timestamp.source,
+                    oracle_timestamp: close_timestamp.oracle_timestamp,
+                    tx_hash: closed.tx_hash.clone(),
+                    tenant: self.tenant.clone(),
+                    run_id: run_id::current(),
+                }
+            }
+            Err(e) if e.downcast_ref::<Raced>().is_some() || is_already_spent(e) => OracleEvent::ShipmentSkipped {
+                utxo_ref,
+                carrier: shipment.datum.carrier.clone(),
+                tracking_number: shipment.datum.tracking_number.to_string(),
+                outbox_address: shipment.datum.outbox_address.to_string(),
+                reason: e.to_string(),
+                tenant: self.tenant.clone(),
+                run_id: run_id::current(),
+            },
+            Err(e) => {
+                self.record_failure(&utxo_ref, Some(status)).await;
+                OracleEvent::ShipmentFailed {
+                    utxo_ref,
+                    carrier: shipment.datum.carrier.clone(),
+                    tracking_number: shipment.datum.tracking_number.to_string(),
+                    status: status.to_string(),
+                    timestamp,
+                    error: e.to_string(),
+                    tenant: self.tenant.clone(),
+                    run_id: run_id::current(),
+                }
+            }
+        };
+
+        self.notify(&event).await;
+        result
+    }
+
+    /// Resolve, validate and sign a close of `shipment` for `DRY_RUN`, logging what would be submitted
+    ///
+    /// Nothing is submitted, recorded or notified. Returns the fee with the action.
+    async fn sign_close(
+        &self,
+        shipment: &TrackingUTxO,
+        status: DerivedStatus,
+        close_timestamp: &CloseTimestamp,
+    ) -> anyhow::Result<(u64, ShipmentAction)> {
+        let prepared = self
+            .blockchain
+            .sign_close_shipment_at(shipment, status, close_timestamp.timestamp)
+            .await?;
+
+        let cbor_hex = prepared.signed.to_hex();
+        match serde_json::to_string(&prepared.params) {
+            Ok(params) => info!(%params, "🧪 Params"),
+            Err(e) => warn!(error = %e, "⚠️  Failed to serialize the close parameters"),
+        }
+        info!(cbor = %cbor_hex, "🧪 CBOR");
+
+        Ok((
+            prepared.fee,
+            ShipmentAction::DryRun {
+                tx_hash: prepared.signed.hash,
+                params: Box::new(prepared.params),
+                cbor_hex,
+            },
+        ))
+    }
+
+    /// Deliver notifications batched by chat notifiers
+    pub async fn flush_notifications(&self) {
+        if let Some(notifier) = &self.notifier
+            && let Err(e) = notifier.flush().await
+        {
+            warn!(error = %format!("{:#}", e), "⚠️  Failed to deliver notifications");
+        }
+    }
+
+    /// When the submit window opens next, if it is closed now
+    ///
+    /// Once the window is found closed, the rest of the run is deferred with it,
+    /// so a run crossing the end of the window stops submitting.
+    fn window_deferral(&self, closed: &Mutex<Option<DateTime<Utc>>>, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let window = self.submit_window.as_ref()?;
+        let mut closed = closed.lock().unwrap_or_else(|e| e.into_inner());
+        if closed.is_none() {
+            if window.is_open(now) {
+                return None;
+            }
+            *closed = Some(window.next_open(now));
+        }
+
+        *closed
+    }
+
+    /// When `shipment` was created, if it outlived the stale policy in carrier status `status`
+    ///
+    /// Shipments whose datum has a deadline are left to it.
+    async fn stale_since(
+        &self,
+        shipment: &TrackingUTxO,
+        utxo_ref: &str,
+        status: &CarrierStatus,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        let policy = self.stale_policy.as_ref()?;
+        if shipment.datum.deadline.is_some() || !policy.applies_to(status) {
+            return None;
+        }
+
+        let cached = self.created_at.lock().unwrap_or_else(|e| e.into_inner()).get(utxo_ref).copied();
+        let created_at = match cached {
+            Some(created_at) => created_at,
+            None => match self.blockchain.tx_block_time(&shipment.utxo_ref.tx_hash_hex()).await {
+                Ok(Some(created_at)) => {
+                    self.created_at.lock().unwrap_or_else(|e| e.into_inner()).insert(utxo_ref.to_string(), created_at);
+                    created_at
+                }
+                Ok(None) => return None,
+                Err(e) => {
+                    warn!(error = %format!("{:#}", e), "⚠️  Could not fetch the tracking UTxO's block time, not checking for a stale status");
+                    return None;
+                }
+            },
+        };
+
+        policy.expired(created_at, now).then_some(created_at)
+    }
+
+    /// Tracking number to query the provider with; `None` for unregistered hashes
+    async fn resolve_tracking_number(&self, tracking_number: &TrackingNumber) -> Option<String> {
+        let hash = match tracking_number {
+            TrackingNumber::Plain(value) => return Some(value.clone()),
+            TrackingNumber::Hashed(hash) => hash,
+        };
+
+        let tracking_lookup = self.tracking_lookup.as_ref()?;
+        match tracking_lookup.resolve(hash).await {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                warn!(hash = %hex::encode(hash), error = %format!("{:#}", e), "⚠️  Failed to look up tracking hash");
+                None
+            }
+        }
+    }
+
+    async fn notify(&self, event: &OracleEvent) {
+        let Some(notifier) = &self.notifier else {
+            return;
+        };
+
+        if let Err(e) = notifier.notify(event).await {
+            warn!(error = %format!("{:#}", e), "⚠️  Failed to deliver notification");
+        }
+    }
+
+    async fn record_closed(
+        &self,
+        tracking: &TrackingUTxO,
+        utxo_ref: &str,
+        status: DerivedStatus,
+        closed: &ClosedShipment,
+        timestamp: u64,
+    ) {
+        let Some(state) = &self.state else {
+            return;
+        };
+
+        let result = async {
+            state
+                .record_submission(&Submission {
+                    utxo_ref: utxo_ref.to_string(),
+                    tx_hash: closed.tx_hash.clone(),
+                    status: status.to_string(),
+                    submitted_at: timestamp,
+                    signer_pkh: Some(self.blockchain.signer_pkh()),
+                    fee: Some(closed.fee),
+                    outbox_address: Some(tracking.datum.outbox_address.to_string()),
+                    carrier: Some(tracking.datum.carrier.clone()),
+                    tracking_number: Some(tracking.datum.tracking_number.to_string()),
+                })
+                .await?;
+
+            let mut shipment = state.shipment(utxo_ref).await?.unwrap_or_else(|| ShipmentState::new(utxo_ref));
+            shipment.status = Some(status.to_string());
+            shipment.closed_tx_hash = Some(closed.tx_hash.clone());
+            state.save_shipment(&shipment).await
+        }
+        .await;
+
+        if let Err(e) = result {
+            warn!(utxo_ref, error = %format!("{:#}", e), "⚠️  Failed to persist state");
+        }
+    }
+
+    /// Journal a close attempt with the submitter's answer, accepted or not
+    async fn record_attempt(
+        &self,
+        utxo_ref: &str,
+        status: DerivedStatus,
+        attempted_at: u64,
+        result: &anyhow::Result<ClosedShipment>,
+    ) {
+        let Some(state) = &self.state else {
+            return;
+        };
+
+        let (tx_hash, response) = match result {
+            Ok(closed) => (Some(closed.tx_hash.clone()), closed.tx_hash.clone()),
+            Err(e) => (None, format!("{:#}", e)),
+        };
+        let attempt = SubmissionAttempt {
+            utxo_ref: utxo_ref.to_string(),
+            status: status.to_string(),
+            attempted_at,
+            tx_hash,
+            response,
+        };
+
+        if let Err(e) = state.record_attempt(&attempt).await {
+            warn!(utxo_ref, error = %format!("{:#}", e), "⚠️  Failed to persist state");
+        }
+    }
+
+    async fn record_failure(&self, utxo_ref: &str, status: Option<DerivedStatus>) {
+        let Some(state) = &self.state else {
+            return;
+        };
+
+        let result = async {
+            let mut shipment = state.shipment(utxo_ref).await?.unwrap_or_else(|| ShipmentState::new(utxo_ref));
+            if let Some(status) = status {
+                shipment.status = Some(status.to_string());
+            }
+            shipment.failure_count += 1;
+            state.save_shipment(&shipment).await
+        }
+        .await;
+
+        if let Err(e) = result {
+            warn!(utxo_ref, error = %format!("{:#}", e), "⚠️  Failed to persist state");
+        }
+    }
+
+    /// Notify once per shipment that the outbox policy refused it (`skipped_policy`)
+    ///
+    /// Refused shipments stay unspent and are skipped again every run; only the first skip is notified.
+    async fn record_skipped_policy(&self, shipment: &TrackingUTxO, utxo_ref: &str, reason: &str) {
+        let first = self
+            .policy_skipped
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(utxo_ref.to_string());
+        if !first {
+            return;
+        }
+
+        self.notify(&OracleEvent::ShipmentSkipped {
+            utxo_ref: utxo_ref.to_string(),
+            carrier: shipment.datum.carrier.clone(),
+            tracking_number: shipment.datum.tracking_number.to_string(),
+            outbox_address: shipment.datum.outbox_address.to_string(),
+            reason: reason.to_string(),
+            tenant: self.tenant.clone(),
+            run_id: run_id::current(),
+        })
+        .await;
+    }
+
+    /// The submitted close `utxo_ref` is waiting on, unless `pending_ttl` has passed since
+    ///
+    /// Falls back on the state store, whose lifecycle journal remembers a
+    /// submission across restarts as long as the closure was not requeued.
+    async fn pending_submission(&self, utxo_ref: &str, now: DateTime<Utc>) -> Option<PendingSubmission> {
+        let expired = |pending: &PendingSubmission| now - pending.submitted_at >= self.pending_ttl;
+
+        {
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            if let Some(submission) = pending.get(utxo_ref) {
+                if !expired(submission) {
+                    return Some(submission.clone());
+                }
+                info!(utxo_ref, minutes = self.pending_ttl.num_minutes(), "⌛ Close still unconfirmed, polling again");
+                pending.remove(utxo_ref);
+                return None;
+            }
+        }
+
+        let state = self.state.as_ref()?;
+        let result = async {
+            let Some(transition) = state.lifecycle(utxo_ref).await?.pop() else {
+                return anyhow::Ok(None);
+            };
+            let ShipmentLifecycle::Submitted { tx_hash } = transition.state else {
+                return Ok(None);
+            };
+            let closed = state.shipment(utxo_ref).await?.and_then(|shipment| shipment.closed_tx_hash);
+            if closed.as_ref() != Some(&tx_hash) {
+                return Ok(None);
+            }
+
+            Ok(DateTime::from_timestamp(transition.at as i64, 0).map(|submitted_at| PendingSubmission {
+                utxo_ref: utxo_ref.to_string(),
+                tx_hash,
+                submitted_at,
+            }))
+        }
+        .await;
+
+        match result {
+            Ok(Some(submission)) if !expired(&submission) => {
+                self.pending
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(utxo_ref.to_string(), submission.clone());
+                Some(submission)
+            }
+            Ok(_) => None,
+            Err(e) => {
+                warn!(utxo_ref, error = %format!("{:#}", e), "⚠️  Failed to read the submission");
+                None
+            }
+        }
+    }
+
+    /// Whether the shipment at `utxo_ref` may be polled under the Shippo budget
+    ///
+    /// The first time a billing period's budget is found spent, a
+    /// `budget_exceeded` event is sent. The budget is not enforced when it
+    /// cannot be checked.
+    async fn within_budget(&self, utxo_ref: &str, now: DateTime<Utc>) -> bool {
+        let (Some(budget), Some(state)) = (&self.shippo_budget, &self.state) else {
+            return true;
+        };
+
+        let period = shippo_budget::period(now, self.billing_timezone);
+        let result = async {
+            let calls: u64 = state.tracking_calls(&period).await?.iter().map(|calls| calls.calls).sum();
+            if !budget.is_spent(calls) {
+                return Ok(true);
+            }
+
+            if shippo_budget::mark_alerted(state.as_ref(), &period).await? {
+                warn!(calls, budget = budget.monthly_calls, %period, "💸 Shippo budget exceeded");
+                self.notify(&OracleEvent::BudgetExceeded {
+                    period: period.clone(),
+                    calls,
+                    budget: budget.monthly_calls,
+                    tenant: self.tenant.clone(),
+                    run_id: run_id::current(),
+                })
+                .await;
+            }
+
+            anyhow::Ok(budget.is_near_final(state.shipment(utxo_ref).await?.as_ref(), now))
+        }
+        .await;
+
+        result.unwrap_or_else(|e| {
+            warn!(error = %format!("{:#}", e), "⚠️  Failed to check the Shippo budget, polling anyway");
+            true
+        })
+    }
+
+    /// Count a tracking API call made at `now` for `shipment`
+    async fn record_tracking_call(&self, shipment: &TrackingUTxO, now: DateTime<Utc>) {
+        let Some(state) = &self.state else {
+            return;
+        };
+
+        let period = shippo_budget::period(now, self.billing_timezone);
+        let outbox_address = shipment.datum.outbox_address.to_string();
+        if let Err(e) = state.record_tracking_call(&period, &shipment.datum.carrier, &outbox_address).await {
+            warn!(error = %format!("{:#}", e), "⚠️  Failed to count the tracking call");
+        }
+    }
+
+    /// Remember a non-final carrier status seen at `now` and since when the carrier has reported it
+    async fn record_carrier_status(&self, utxo_ref: &str, carrier_status: &str, now: DateTime<Utc>) {
+        let Some(state) = &self.state else {
+            return;
+        };
+
+        let result = async {
+            let mut shipment = state.shipment(utxo_ref).await?.unwrap_or_else(|| ShipmentState::new(utxo_ref));
+            if shipment.carrier_status.as_deref() == Some(carrier_status) {
+                return Ok(());
+            }
+
+            shipment.carrier_status = Some(carrier_status.to_string());
+            shipment.carrier_status_since = Some(now.timestamp() as u64);
+            state.save_shipment(&shipment).await
+        }
+        .await;
+
+        if let Err(e) = result {
+            warn!(utxo_ref, error = %format!("{:#}", e), "⚠️  Failed to persist state");
+        }
+    }
+
+    /// Record a final status held back by the submit window (`deferred_window`)
+    async fn record_deferred(&self, utxo_ref: &str, status: DerivedStatus, open_at: DateTime<Utc>) {
+        self.advance(utxo_ref, ShipmentLifecycle::FinalStatusKnown).await;
+        let Some(state) = &self.state else {
+            return;
+        };
+
+        let result = async {
+            let mut shipment = state.shipment(utxo_ref).await?.unwrap_or_else(|| ShipmentState::new(utxo_ref));
+            shipment.status = Some(status.to_string());
+            shipment.next_attempt_at = Some(open_at.timestamp() as u64);
+            state.save_shipment(&shipment).await
+        }
+        .await;
+
+        if let Err(e) = result {
+            warn!(utxo_ref, error = %format!("{:#}", e), "⚠️  Failed to persist state");
+        }
+    }
+
+    /// Start the lifecycle of a shipment seen for the first time
+    async fn discover(&self, utxo_ref: &str) {
+        let Some(state) = &self.state else {
+            return;
+        };
+
+        match state.lifecycle(utxo_ref).await {
+            Ok(history) if history.is_empty() => self.advance(utxo_ref, ShipmentLifecycle::Discovered).await,
+            Ok(_) => {}
+            Err(e) => warn!(utxo_ref, error = %format!("{:#}", e), "⚠️  Failed to read lifecycle"),
+        }
+    }
+
+    /// Move a shipment to `to` and journal the transition
+    ///
+    /// Staying in the current state is a no-op. Illegal transitions are logged
+    /// and counted but never recorded, and do not stop the run.
+    async fn advance(&self, utxo_ref: &str, to: ShipmentLifecycle) {
+        let Some(state) = &self.state else {
+            return;
+        };
+
+        let result = async {
+            let current = state.lifecycle(utxo_ref).await?.pop().map(|transition| transition.state);
+            if current.as_ref() == Some(&to) {
+                return Ok(());
+            }
+
+            if let Err(e) = check_transition(utxo_ref, current.as_ref(), &to) {
+                error!(utxo_ref, "❌ {}", e);
+                self.illegal_transitions.fetch_add(1, Ordering::Relaxed);
+                return Ok(());
+            }
+
+            state
+                .record_transition(&LifecycleTransition {
+                    utxo_ref: utxo_ref.to_string(),
+                    state: to,
+                    at: chrono::Utc::now().timestamp() as u64,
+                })
+                .await
+        }
+        .await;
+
+        if let Err(e) = result {
+            warn!(utxo_ref, error = %format!("{:#}", e), "⚠️  Failed to persist lifecycle");
+        }
+    }
+
+    /// Confirm or expire open shipments whose tracking UTxO is no longer in `unspent`
+    ///
+    /// A submitted shipment is confirmed once one of its journalled close
+    /// transactions is found on-chain, and stays submitted until then. Any other
+    /// open shipment was spent by someone else and expires.
+    async fn settle_lifecycles(&self, unspent: &HashSet<String>) {
+        let Some(state) = &self.state else {
+            return;
+        };
+
+        let lifecycles = match state.lifecycles().await {
+            Ok(lifecycles) => lifecycles,
+            Err(e) => {
+                warn!(error = %format!("{:#}", e), "⚠️  Failed to read shipment lifecycles");
+                return;
+            }
+        };
+
+        for transition in lifecycles {
+            let utxo_ref = &transition.utxo_ref;
+            if transition.state.is_terminal() || unspent.contains(utxo_ref) {
+                continue;
+            }
+
+            let ShipmentLifecycle::Submitted { tx_hash } = &transition.state else {
+                info!(utxo_ref, "⌛ Tracking UTxO left the oracle address, expiring");
+                self.advance(utxo_ref, ShipmentLifecycle::Expired).await;
+                continue;
+            };
+
+            match self.confirmation_block(state.as_ref(), utxo_ref, tx_hash).await {
+                Ok(Some((block, fee))) => {
+                    info!(utxo_ref, %block, "🧱 Close confirmed");
+                    self.advance(utxo_ref, ShipmentLifecycle::Confirmed { block }).await;
+                    if let Some(fee) = fee {
+                        self.metrics.fee_paid(fee);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => warn!(utxo_ref, error = %format!("{:#}", e), "⚠️  Failed to look up the close"),
+            }
+        }
+    }
+
+    /// Block holding any journalled close of `utxo_ref`, newest submission first, with that close's fee
+    ///
+    /// Only the close found on-chain is returned, so the fees of resubmissions
+    /// that never landed are not counted.
+    async fn confirmation_block(
+        &self,
+        state: &dyn StateStore,
+        utxo_ref: &str,
+        tx_hash: &str,
+    ) -> anyhow::Result<Option<(u64, Option<u64>)>> {
+        let mut closes: Vec<(String, Option<u64>)> = state
+            .submissions(utxo_ref)
+            .await?
+            .into_iter()
+            .rev()
+            .map(|submission| (submission.tx_hash, submission.fee))
+            .collect();
+        if !closes.iter().any(|(hash, _)| hash == tx_hash) {
+            closes.insert(0, (tx_hash.to_string(), None));
+        }
+
+        for (tx_hash, fee) in closes {
+            if let Some(block) = self.blockchain.tx_block_height(&tx_hash).await? {
+                return Ok(Some((block, fee)));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+/// Shipments grouped by carrier and tracking number, the groups and their UTxOs in scan order
+fn group_duplicates(shipments: Vec<(usize, TrackingUTxO)>) -> Vec<Vec<(usize, TrackingUTxO)>> {
+    let mut groups: Vec<Vec<(usize, TrackingUTxO)>> = Vec::new();
+    let mut positions: HashMap<(String, String), usize> = HashMap::new();
+    for (index, shipment) in shipments {
+        let key = (shipment.datum.carrier.clone(), shipment.datum.tracking_number.to_string());
+        match positions.get(&key) {
+            Some(&position) => groups[position].push((index, shipment)),
+            None => {
+                positions.insert(key, groups.len());
+                groups.push(vec![(index, shipment)]);
+            }
+        }
+    }
+    groups
+}
+
+/// A run report action for a shipment left alone because of `reason`
+fn skipped(reason: impl Into<String>) -> ShipmentAction {
+    ShipmentAction::Skipped { reason: reason.into() }
+}
+
+/// The deadline of `datum`, if it passed by `now`
+fn passed_deadline(datum: &TrackingDatum, now: DateTime<Utc>) -> Option<u64> {
+    datum
+        .deadline
+        .filter(|deadline| u64::try_from(now.timestamp()).is_ok_and(|now| now > *deadline))
+}
+
+/// Whether the submit API refused a close because the tracking UTxO is spent already
+fn is_already_spent(error: &anyhow::Error) -> bool {
+    matches!(error.downcast_ref::<SubmitError>(), Some(SubmitError::AlreadySpent(_)))
 }
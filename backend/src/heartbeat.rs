@@ -0,0 +1,92 @@
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Utc};
+use reqwest::Client as HttpClient;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+use tracing::warn;
+
+const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Outcome of the most recent heartbeat ping
+#[derive(Debug, Clone, Serialize)]
+pub struct PingStatus {
+    pub at: DateTime<Utc>,
+    pub run_succeeded: bool,
+    pub delivered: bool,
+    pub error: Option<String>,
+}
+
+/// Dead-man switch pinged after every run (healthchecks.io style)
+///
+/// Successful runs GET the base URL; failed runs GET `<url>/fail`.
+pub struct Heartbeat {
+    url: String,
+    http_client: HttpClient,
+    last_status: Mutex<Option<PingStatus>>,
+}
+
+impl Heartbeat {
+    pub fn new(url: String) -> Result<Self> {
+        let http_client = HttpClient::builder()
+            .timeout(PING_TIMEOUT)
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        Ok(Self {
+            url: url.trim_end_matches('/').to_string(),
+            http_client,
+            last_status: Mutex::new(None),
+        })
+    }
+
+    /// Reports a run that scanned successfully with no failed shipments
+    pub async fn ping_success(&self) {
+        let result = self.ping(&self.url).await;
+        self.record(true, result);
+    }
+
+    /// Reports a failed run, with the number of failed shipments when the scan itself succeeded
+    pub async fn ping_failure(&self, failures: Option<usize>) {
+        let url = match failures {
+            Some(failures) => format!("{}/fail?failures={}", self.url, failures),
+            None => format!("{}/fail", self.url),
+        };
+
+        let result = self.ping(&url).await;
+        self.record(false, result);
+    }
+
+    pub fn last_status(&self) -> Option<PingStatus> {
+        self.last_status.lock().ok().and_then(|status| status.clone())
+    }
+
+    async fn ping(&self, url: &str) -> Result<()> {
+        let response = self.http_client
+            .get(url)
+            .send()
+            .await
+            .context("Failed to send heartbeat ping")?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!("Heartbeat ping rejected (status {})", response.status()));
+        }
+
+        Ok(())
+    }
+
+    fn record(&self, run_succeeded: bool, result: Result<()>) {
+        if let Err(ref e) = result {
+            warn!(error = %format!("{:#}", e), "⚠️  Heartbeat ping failed");
+        }
+
+        if let Ok(mut last_status) = self.last_status.lock() {
+            *last_status = Some(PingStatus {
+                at: Utc::now(),
+                run_succeeded,
+                delivered: result.is_ok(),
+                error: result.err().map(|e| e.to_string()),
+            });
+        }
+    }
+}
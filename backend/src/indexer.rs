@@ -0,0 +1,508 @@
+use anyhow::{Context, Result, anyhow};
+use reqwest::Client as HttpClient;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use tracing::{Instrument, debug};
+
+use crate::blockchain::{BLOCKFROST_PAGE_SIZE, CardanoClient, blockfrost_http_client, blockfrost_send};
+use crate::config::Config;
+use crate::logging;
+use crate::redact::redact;
+use crate::state::StateStore;
+use crate::submitter::forbidden_hint;
+
+/// An unspent output and its inline datum, as listed by a `ChainIndexer`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexedUTxO {
+    pub tx_hash: String,
+    pub output_index: u32,
+    /// Hex CBOR of the inline datum; `None` for outputs without one
+    pub inline_datum: Option<String>,
+}
+
+/// Unspent outputs at an address
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddressUTxOs {
+    pub utxos: Vec<IndexedUTxO>,
+    /// The indexer stopped listing before the last output, so more may remain
+    pub truncated: bool,
+}
+
+/// Lists the unspent outputs tracking UTxOs are discovered from
+#[async_trait::async_trait]
+pub trait ChainIndexer: Send + Sync {
+    async fn unspent_at(&self, address: &str) -> Result<AddressUTxOs>;
+}
+
+/// Where tracking UTxOs are discovered (`CHAIN_INDEXER`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum IndexerKind {
+    /// Blockfrost's `/addresses/{address}/utxos`, with the `BLOCKFROST_URL` credentials
+    #[default]
+    Blockfrost,
+    /// A Kupo instance at `KUPO_URL`
+    Kupo,
+}
+
+impl FromStr for IndexerKind {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "blockfrost" => Ok(IndexerKind::Blockfrost),
+            "kupo" => Ok(IndexerKind::Kupo),
+            other => Err(anyhow!("expected blockfrost or kupo, got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for IndexerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            IndexerKind::Blockfrost => "blockfrost",
+            IndexerKind::Kupo => "kupo",
+        })
+    }
+}
+
+/// How much of the oracle address every run reads (`SCAN_MODE`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScanMode {
+    /// List every unspent output at the address
+    #[default]
+    Full,
+    /// Only read the transactions since the last scan, see `IncrementalIndexer`
+    Incremental,
+}
+
+impl FromStr for ScanMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "full" => Ok(ScanMode::Full),
+            "incremental" => Ok(ScanMode::Incremental),
+            other => Err(anyhow!("expected full or incremental, got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for ScanMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ScanMode::Full => "full",
+            ScanMode::Incremental => "incremental",
+        })
+    }
+}
+
+/// Builds the indexer selected by `CHAIN_INDEXER`
+pub fn from_config(config: &Config) -> Result<Box<dyn ChainIndexer>> {
+    match config.chain_indexer {
+        IndexerKind::Blockfrost => Ok(Box::new(BlockfrostIndexer::new(config.clone(), blockfrost_http_client(config)?))),
+        IndexerKind::Kupo => {
+            let kupo_url = config.kupo_url.clone().context("CHAIN_INDEXER=kupo needs KUPO_URL")?;
+            Ok(Box::new(KupoIndexer::new(kupo_url, HttpClient::new())))
+        }
+    }
+}
+
+/// `chain` scanning its oracle address with an `IncrementalIndexer` under `SCAN_MODE=incremental`
+pub fn apply_scan_mode(chain: CardanoClient, config: &Config, state: Option<&Arc<dyn StateStore>>) -> Result<CardanoClient> {
+    if config.scan_mode != ScanMode::Incremental {
+        return Ok(chain);
+    }
+
+    let state = state.cloned().context("SCAN_MODE=incremental needs a state database")?;
+    let indexer = IncrementalIndexer::new(config.clone(), blockfrost_http_client(config)?, state);
+    Ok(chain.with_indexer(Box::new(indexer)))
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockfrostUTxO {
+    tx_hash: String,
+    output_index: u32,
+    inline_datum: Option<String>,
+}
+
+/// Pages through Blockfrost's address UTxOs
+///
+/// Pages of `BLOCKFROST_PAGE_SIZE` UTxOs are read until a short page, or
+/// until `BLOCKFROST_MAX_PAGES` pages were read, in which case the listing is
+/// marked truncated. Rate-limited and failing pages are retried as configured
+/// by `BLOCKFROST_MAX_RETRIES`.
+pub struct BlockfrostIndexer {
+    config: Config,
+    http_client: HttpClient,
+}
+
+impl BlockfrostIndexer {
+    pub fn new(config: Config, http_client: HttpClient) -> Self {
+        Self { config, http_client }
+    }
+}
+
+#[async_trait::async_trait]
+impl ChainIndexer for BlockfrostIndexer {
+    async fn unspent_at(&self, address: &str) -> Result<AddressUTxOs> {
+        let url = format!("{}/addresses/{}/utxos", self.config.blockfrost_url, address);
+
+        let mut listed = AddressUTxOs::default();
+        for page in 1..=self.config.blockfrost_max_pages {
+            let request = self.http_client
+                .get(&url)
+                .query(&[("count", BLOCKFROST_PAGE_SIZE), ("page", page as usize)]);
+            let response = blockfrost_send(&self.config, request).await?;
+
+            if !response.status().is_success() {
+                let status = response.status();
+                let body = response.text().await.unwrap_or_default();
+                return Err(anyhow!(
+                    "Blockfrost query failed (status {}): {}{}",
+                    status,
+                    redact(&body),
+                    forbidden_hint(status, self.config.blockfrost_project_id.is_some())
+                ));
+            }
+
+            let page_utxos: Vec<BlockfrostUTxO> = response.json().await
+                .with_context(|| format!("Failed to parse Blockfrost UTxOs response (page {})", page))?;
+
+            let last = page_utxos.len() < BLOCKFROST_PAGE_SIZE;
+            listed.utxos.extend(page_utxos.into_iter().map(|utxo| IndexedUTxO {
+                tx_hash: utxo.tx_hash,
+                output_index: utxo.output_index,
+                inline_datum: utxo.inline_datum,
+            }));
+            if last {
+                break;
+            }
+            listed.truncated = page == self.config.blockfrost_max_pages;
+        }
+
+        Ok(listed)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct KupoMatch {
+    transaction_id: String,
+    output_index: u32,
+    datum_hash: Option<String>,
+    /// `inline` or `hash`, when the output has a datum
+    datum_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KupoDatum {
+    datum: String,
+}
+
+/// Lists unspent matches of a Kupo instance and fetches their inline datums
+///
+/// Kupo must index the oracle address (e.g. `--match <ORACLE_ADDRESS>`). Inline
+/// datums are read from `/datums/{hash}`; outputs with a datum hash only are
+/// listed without a datum, like Blockfrost lists them.
+pub struct KupoIndexer {
+    kupo_url: String,
+    http_client: HttpClient,
+}
+
+impl KupoIndexer {
+    pub fn new(kupo_url: String, http_client: HttpClient) -> Self {
+        Self { kupo_url, http_client }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T> {
+        let url = format!("{}{}", self.kupo_url, path);
+        let response = self.http_client
+            .get(&url)
+            .send()
+            .instrument(logging::http_span("kupo", &reqwest::Method::GET, &url))
+            .await
+            .map_err(|e| anyhow!("Kupo query failed: {}", redact(&e.to_string())))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Kupo query failed (status {}): {}", status, redact(&body)));
+        }
+
+        response.json().await.with_context(|| format!("Failed to parse Kupo {} response", path))
+    }
+}
+
+#[async_trait::async_trait]
+impl ChainIndexer for KupoIndexer {
+    async fn unspent_at(&self, address: &str) -> Result<AddressUTxOs> {
+        let matches: Vec<KupoMatch> = self.get(&format!("/matches/{}?unspent", address)).await?;
+
+        let mut listed = AddressUTxOs::default();
+        for output in matches {
+            let inline_datum = match (output.datum_type.as_deref(), &output.datum_hash) {
+                (Some("inline"), Some(datum_hash)) => self
+                    .get::<Option<KupoDatum>>(&format!("/datums/{}", datum_hash))
+                    .await?
+                    .map(|datum| datum.datum),
+                _ => None,
+            };
+
+            listed.utxos.push(IndexedUTxO {
+                tx_hash: output.transaction_id,
+                output_index: output.output_index,
+                inline_datum,
+            });
+        }
+
+        Ok(listed)
+    }
+}
+
+/// State cursor holding the `IncrementalIndexer` snapshot
+pub const SCAN_CURSOR: &str = "incremental_scan";
+
+/// What an incremental scan knows about the oracle address
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ScanSnapshot {
+    /// Chain tip when the last scan started; later blocks have not been read
+    height: u64,
+    /// Outputs at the address by `tx_hash#index`; spent ones are kept while their spend may be rolled back
+    utxos: BTreeMap<String, ScannedUTxO>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ScannedUTxO {
+    tx_hash: String,
+    output_index: u32,
+    inline_datum: Option<String>,
+    /// Block of the creating transaction; 0 for outputs listed when the scan started
+    created_at: u64,
+    spent_at: Option<u64>,
+}
+
+impl ScanSnapshot {
+    /// Undo whatever blocks from `height` onwards did, to read them again
+    fn rewind(&mut self, height: u64) {
+        self.utxos.retain(|_, utxo| utxo.created_at < height);
+        for utxo in self.utxos.values_mut() {
+            if utxo.spent_at.is_some_and(|spent_at| spent_at >= height) {
+                utxo.spent_at = None;
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockfrostBlock {
+    height: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockfrostAddressTx {
+    tx_hash: String,
+    block_height: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockfrostTxUtxos {
+    inputs: Vec<BlockfrostTxInput>,
+    outputs: Vec<BlockfrostTxOutput>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockfrostTxInput {
+    address: String,
+    tx_hash: String,
+    output_index: u32,
+    #[serde(default)]
+    reference: bool,
+    #[serde(default)]
+    collateral: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockfrostTxOutput {
+    address: String,
+    output_index: u32,
+    inline_datum: Option<String>,
+    #[serde(default)]
+    collateral: bool,
+}
+
+/// Keeps the unspent outputs of the oracle address in the state store and reads only what changed
+///
+/// The first scan lists the address like `BlockfrostIndexer` and remembers the chain tip. Later
+/// scans read the address transactions from `ROLLBACK_DEPTH` blocks below the remembered tip:
+/// outputs they create are added, outputs they spend are dropped. Those blocks are undone first,
+/// so a rolled-back transaction leaves nothing behind. Up to `BLOCKFROST_MAX_PAGES` pages of
+/// transactions are read per scan; the rest wait for the next one.
+pub struct IncrementalIndexer {
+    config: Config,
+    http_client: HttpClient,
+    state: Arc<dyn StateStore>,
+}
+
+impl IncrementalIndexer {
+    pub fn new(config: Config, http_client: HttpClient, state: Arc<dyn StateStore>) -> Self {
+        Self { config, http_client, state }
+    }
+
+    async fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<Option<T>> {
+        let request = self.http_client.get(format!("{}{}", self.config.blockfrost_url, path)).query(query);
+        let response = blockfrost_send(&self.config, request).await?;
+
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(anyhow!(
+                "Blockfrost query failed (status {}): {}{}",
+                status,
+                redact(&body),
+                forbidden_hint(status, self.config.blockfrost_project_id.is_some())
+            ));
+        }
+
+        let value = response.json().await
+            .with_context(|| format!("Failed to parse Blockfrost {} response", path))?;
+
+        Ok(Some(value))
+    }
+
+    async fn tip(&self) -> Result<u64> {
+        let tip: BlockfrostBlock = self.get("/blocks/latest", &[]).await?.context("Blockfrost has no latest block")?;
+        Ok(tip.height)
+    }
+
+    /// List the address in full and start the snapshot from there, unless the listing was truncated
+    async fn bootstrap(&self, address: &str) -> Result<AddressUTxOs> {
+        let height = self.tip().await?;
+        let listed = BlockfrostIndexer::new(self.config.clone(), self.http_client.clone())
+            .unspent_at(address)
+            .await?;
+
+        if !listed.truncated {
+            let utxos = listed
+                .utxos
+                .iter()
+                .map(|utxo| {
+                    let scanned = ScannedUTxO {
+                        tx_hash: utxo.tx_hash.clone(),
+                        output_index: utxo.output_index,
+                        inline_datum: utxo.inline_datum.clone(),
+                        created_at: 0,
+                        spent_at: None,
+                    };
+                    (format!("{}#{}", utxo.tx_hash, utxo.output_index), scanned)
+                })
+                .collect();
+            self.save(&ScanSnapshot { height, utxos }).await?;
+        }
+
+        Ok(listed)
+    }
+
+    /// Apply the outputs `tx` creates at and spends from `address`
+    async fn apply(&self, snapshot: &mut ScanSnapshot, address: &str, tx: &BlockfrostAddressTx) -> Result<()> {
+        // Gone since it was listed, i.e. rolled back; the next scan reads its block again
+        let Some(utxos) = self.get::<BlockfrostTxUtxos>(&format!("/txs/{}/utxos", tx.tx_hash), &[]).await? else {
+            return Ok(());
+        };
+
+        for input in utxos.inputs {
+            if input.address != address || input.reference || input.collateral {
+                continue;
+            }
+            if let Some(utxo) = snapshot.utxos.get_mut(&format!("{}#{}", input.tx_hash, input.output_index)) {
+                utxo.spent_at = Some(tx.block_height);
+            }
+        }
+
+        for output in utxos.outputs {
+            if output.address != address || output.collateral {
+                continue;
+            }
+            let scanned = ScannedUTxO {
+                tx_hash: tx.tx_hash.clone(),
+                output_index: output.output_index,
+                inline_datum: output.inline_datum,
+                created_at: tx.block_height,
+                spent_at: None,
+            };
+            snapshot.utxos.insert(format!("{}#{}", tx.tx_hash, output.output_index), scanned);
+        }
+
+        Ok(())
+    }
+
+    async fn load(&self) -> Result<Option<ScanSnapshot>> {
+        let Some(value) = self.state.cursor(SCAN_CURSOR).await? else {
+            return Ok(None);
+        };
+
+        serde_json::from_str(&value).map(Some).context("Invalid incremental scan snapshot")
+    }
+
+    async fn save(&self, snapshot: &ScanSnapshot) -> Result<()> {
+        self.state.set_cursor(SCAN_CURSOR, &serde_json::to_string(snapshot)?).await
+    }
+}
+
+#[async_trait::async_trait]
+impl ChainIndexer for IncrementalIndexer {
+    async fn unspent_at(&self, address: &str) -> Result<AddressUTxOs> {
+        let Some(mut snapshot) = self.load().await? else {
+            return self.bootstrap(address).await;
+        };
+
+        let tip = self.tip().await?;
+        let from = (snapshot.height + 1).saturating_sub(self.config.rollback_depth);
+        snapshot.rewind(from);
+
+        let path = format!("/addresses/{}/transactions", address);
+        let (mut read, mut truncated, mut last_height) = (0, false, 0);
+        for page in 1..=self.config.blockfrost_max_pages {
+            let query = [
+                ("order", "asc".to_string()),
+                ("from", from.to_string()),
+                ("count", BLOCKFROST_PAGE_SIZE.to_string()),
+                ("page", page.to_string()),
+            ];
+            let txs: Vec<BlockfrostAddressTx> = self.get(&path, &query).await?.unwrap_or_default();
+
+            let last = txs.len() < BLOCKFROST_PAGE_SIZE;
+            for tx in &txs {
+                self.apply(&mut snapshot, address, tx).await?;
+                last_height = tx.block_height;
+            }
+            read += txs.len();
+            if last {
+                break;
+            }
+            truncated = page == self.config.blockfrost_max_pages;
+        }
+
+        // A truncated scan may have stopped within a block, so the next one reads that block again
+        snapshot.height = if truncated { last_height.saturating_sub(1).max(from.saturating_sub(1)) } else { tip.max(last_height) };
+        let next_from = (snapshot.height + 1).saturating_sub(self.config.rollback_depth);
+        snapshot.utxos.retain(|_, utxo| utxo.spent_at.is_none_or(|spent_at| spent_at >= next_from));
+        self.save(&snapshot).await?;
+
+        debug!(from, transactions = read, height = snapshot.height, truncated, "Scanned the oracle address incrementally");
+        let utxos = snapshot
+            .utxos
+            .into_values()
+            .filter(|utxo| utxo.spent_at.is_none())
+            .map(|utxo| IndexedUTxO { tx_hash: utxo.tx_hash, output_index: utxo.output_index, inline_datum: utxo.inline_datum })
+            .collect();
+
+        Ok(AddressUTxOs { utxos, truncated })
+    }
+}
@@ -0,0 +1,106 @@
+use anyhow::{Context, Result, anyhow, bail};
+use aws_sdk_kms::Client as KmsClient;
+use aws_sdk_kms::error::DisplayErrorContext;
+use aws_sdk_kms::primitives::Blob;
+use aws_sdk_kms::types::{MessageType, SigningAlgorithmSpec};
+use pallas::crypto::hash::Hasher;
+use tokio::sync::OnceCell;
+
+use crate::config::Config;
+use crate::signing::{SignatureBytes, TxSigner, VerifyingKeyBytes};
+
+/// DER `SubjectPublicKeyInfo` header of an Ed25519 key, followed by the 32 key bytes
+const ED25519_SPKI_PREFIX: [u8; 12] = [0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+/// Signs with an `ECC_NIST_EDWARDS25519` key held in AWS KMS
+///
+/// Credentials and region come from the usual AWS environment. The public key
+/// is read from KMS on the first signature and must hash to `ORACLE_PKH`, so a
+/// wrong `KMS_KEY_ID` fails before anything is submitted.
+pub struct KmsSigner {
+    key_id: String,
+    pkh: String,
+    client: OnceCell<KmsClient>,
+    verifying_key: OnceCell<VerifyingKeyBytes>,
+}
+
+impl KmsSigner {
+    pub fn new(key_id: String, pkh: String) -> Self {
+        Self {
+            key_id,
+            pkh: pkh.trim().to_lowercase(),
+            client: OnceCell::new(),
+            verifying_key: OnceCell::new(),
+        }
+    }
+
+    /// Sign with `KMS_KEY_ID`, expected to hash to `ORACLE_PKH`
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let key_id = config.kms_key_id.clone().context("ORACLE_SIGNER=kms needs KMS_KEY_ID")?;
+        let pkh = config.oracle_pkh.clone().context("ORACLE_SIGNER=kms needs ORACLE_PKH")?;
+
+        Ok(Self::new(key_id, pkh))
+    }
+
+    async fn client(&self) -> &KmsClient {
+        self.client
+            .get_or_init(|| async {
+                KmsClient::new(&aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await)
+            })
+            .await
+    }
+
+    async fn verifying_key(&self) -> Result<VerifyingKeyBytes> {
+        let verifying_key = self.verifying_key.get_or_try_init(|| async {
+            let output = self.client().await
+                .get_public_key()
+                .key_id(&self.key_id)
+                .send()
+                .await
+                .map_err(|e| anyhow!("KMS GetPublicKey failed for {}: {}", self.key_id, DisplayErrorContext(&e)))?;
+
+            let der = output.public_key().map(Blob::as_ref).unwrap_or_default();
+            let verifying_key: VerifyingKeyBytes = der
+                .strip_prefix(ED25519_SPKI_PREFIX.as_slice())
+                .and_then(|key| key.try_into().ok())
+                .with_context(|| format!("KMS key {} is not an Ed25519 key", self.key_id))?;
+
+            let pkh = hex::encode(Hasher::<224>::hash(&verifying_key));
+            if pkh != self.pkh {
+                bail!("KMS key {} hashes to {}, not ORACLE_PKH {}", self.key_id, pkh, self.pkh);
+            }
+
+            Ok(verifying_key)
+        });
+
+        verifying_key.await.copied()
+    }
+}
+
+#[async_trait::async_trait]
+impl TxSigner for KmsSigner {
+    fn pkh(&self) -> String {
+        self.pkh.clone()
+    }
+
+    async fn sign(&self, tx_hash: &[u8]) -> Result<(VerifyingKeyBytes, SignatureBytes)> {
+        let verifying_key = self.verifying_key().await?;
+
+        let output = self.client().await
+            .sign()
+            .key_id(&self.key_id)
+            .message(Blob::new(tx_hash.to_vec()))
+            .message_type(MessageType::Raw)
+            .signing_algorithm(SigningAlgorithmSpec::Ed25519Sha512)
+            .send()
+            .await
+            .map_err(|e| anyhow!("KMS Sign failed for {}: {}", self.key_id, DisplayErrorContext(&e)))?;
+
+        let signature = output.signature().map(Blob::as_ref).unwrap_or_default();
+        let signature: SignatureBytes = signature
+            .try_into()
+            .map_err(|_| anyhow!("KMS returned a {}-byte signature, expected 64", signature.len()))?;
+
+        Ok((verifying_key, signature))
+    }
+}
@@ -1,8 +1,62 @@
+pub mod admin;
+pub mod aftership;
+pub mod backoff;
 pub mod blockchain;
+pub mod carrier_limits;
+pub mod carrier_policy;
+pub mod cli;
+pub mod clock;
 pub mod config;
+pub mod datum_codec;
+pub mod debug_events;
+pub mod decisions;
+pub mod easypost;
+#[cfg(feature = "sentry")]
+pub mod error_reporting;
+pub mod fees;
 pub mod fetcher;
+pub mod heartbeat;
+pub mod indexer;
+#[cfg(feature = "kms")]
+pub mod kms;
+pub mod lifecycle;
+pub mod logging;
+pub mod metrics;
+pub mod mnemonic;
+pub mod mock_provider;
 pub mod models;
+pub mod notifier;
+pub mod oracle;
+pub mod outbox_policy;
+pub mod privacy;
+pub mod protocol;
+pub mod proxy;
+pub mod rate_limit;
+pub mod reconcile;
+#[cfg(feature = "replay")]
+pub mod replay;
+pub mod redact;
+pub mod reporting;
+pub mod run_id;
+pub mod run_report;
 pub mod scheduler;
+pub mod self_test;
 pub mod shipment;
+pub mod shipment_import;
+pub mod shippo_budget;
+pub mod signing;
+pub mod stale_status;
+pub mod state;
+pub mod status_mapping;
+pub mod submit_window;
 pub mod submitter;
+pub mod tenant;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+#[cfg(feature = "test-utils")]
+pub mod testing;
+pub mod timestamp_source;
+pub mod tracking_cache;
+pub mod tracking_provider;
 pub mod tx3;
+pub mod validation;
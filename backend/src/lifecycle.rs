@@ -0,0 +1,142 @@
+use anyhow::{Result, bail};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Where a tracking UTxO is in its life from first scan to closure
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ShipmentLifecycle {
+    /// Seen at the oracle address for the first time
+    Discovered,
+    /// The carrier reports a status that is not final yet
+    AwaitingCarrier,
+    /// The carrier reports a final status; the shipment is ready to close
+    FinalStatusKnown,
+    /// A close transaction was accepted by the submit API
+    Submitted { tx_hash: String },
+    /// The close transaction landed in block `block` (height)
+    Confirmed { block: u64 },
+    /// The tracking UTxO left the oracle address without a close by this oracle
+    Expired,
+    /// Given up on; the oracle will not close it
+    DeadLettered { reason: String },
+}
+
+impl ShipmentLifecycle {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ShipmentLifecycle::Discovered => "discovered",
+            ShipmentLifecycle::AwaitingCarrier => "awaiting_carrier",
+            ShipmentLifecycle::FinalStatusKnown => "final_status_known",
+            ShipmentLifecycle::Submitted { .. } => "submitted",
+            ShipmentLifecycle::Confirmed { .. } => "confirmed",
+            ShipmentLifecycle::Expired => "expired",
+            ShipmentLifecycle::DeadLettered { .. } => "dead_lettered",
+        }
+    }
+
+    /// The tx hash, block height or reason carried by the state, if any
+    pub fn detail(&self) -> Option<String> {
+        match self {
+            ShipmentLifecycle::Submitted { tx_hash } => Some(tx_hash.clone()),
+            ShipmentLifecycle::Confirmed { block } => Some(block.to_string()),
+            ShipmentLifecycle::DeadLettered { reason } => Some(reason.clone()),
+            _ => None,
+        }
+    }
+
+    /// Inverse of `name` and `detail`, for reading the state back from storage
+    pub fn from_parts(name: &str, detail: Option<&str>) -> Result<Self> {
+        let detail = || match detail {
+            Some(detail) => Ok(detail.to_string()),
+            None => bail!("Lifecycle state {} needs a detail", name),
+        };
+
+        Ok(match name {
+            "discovered" => ShipmentLifecycle::Discovered,
+            "awaiting_carrier" => ShipmentLifecycle::AwaitingCarrier,
+            "final_status_known" => ShipmentLifecycle::FinalStatusKnown,
+            "submitted" => ShipmentLifecycle::Submitted { tx_hash: detail()? },
+            "confirmed" => ShipmentLifecycle::Confirmed { block: detail()?.parse()? },
+            "expired" => ShipmentLifecycle::Expired,
+            "dead_lettered" => ShipmentLifecycle::DeadLettered { reason: detail()? },
+            other => bail!("Unknown lifecycle state {}", other),
+        })
+    }
+
+    /// Confirmed, expired and dead-lettered shipments never change again
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            ShipmentLifecycle::Confirmed { .. } | ShipmentLifecycle::Expired | ShipmentLifecycle::DeadLettered { .. }
+        )
+    }
+
+    /// Whether a shipment in this state may move to `next`
+    ///
+    /// A submitted shipment may be submitted again (with another transaction) or
+    /// go back to `FinalStatusKnown` when its close has to be resubmitted.
+    pub fn can_transition_to(&self, next: &ShipmentLifecycle) -> bool {
+        use ShipmentLifecycle::*;
+
+        match (self, next) {
+            (_, DeadLettered { .. }) => !self.is_terminal(),
+            (Discovered, AwaitingCarrier | FinalStatusKnown | Expired) => true,
+            (AwaitingCarrier, FinalStatusKnown | Expired) => true,
+            (FinalStatusKnown, Submitted { .. } | Expired) => true,
+            (Submitted { .. }, Submitted { .. } | FinalStatusKnown | Confirmed { .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for ShipmentLifecycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.detail() {
+            Some(detail) => write!(f, "{} ({})", self.name(), detail),
+            None => f.write_str(self.name()),
+        }
+    }
+}
+
+/// A shipment entering `state` at `at` (Unix timestamp)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LifecycleTransition {
+    pub utxo_ref: String,
+    #[serde(flatten)]
+    pub state: ShipmentLifecycle,
+    pub at: u64,
+}
+
+/// A transition refused by `check_transition`
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Illegal lifecycle transition for {utxo_ref}: {} -> {to}", .from.as_ref().map_or("none".to_string(), |from| from.to_string()))]
+pub struct IllegalTransition {
+    pub utxo_ref: String,
+    pub from: Option<ShipmentLifecycle>,
+    pub to: ShipmentLifecycle,
+}
+
+/// Check that a shipment currently in `from` (`None` before its first transition) may enter `to`
+///
+/// Every shipment starts out `Discovered`.
+pub fn check_transition(
+    utxo_ref: &str,
+    from: Option<&ShipmentLifecycle>,
+    to: &ShipmentLifecycle,
+) -> Result<(), IllegalTransition> {
+    let legal = match from {
+        Some(from) => from.can_transition_to(to),
+        None => *to == ShipmentLifecycle::Discovered,
+    };
+
+    if legal {
+        Ok(())
+    } else {
+        Err(IllegalTransition {
+            utxo_ref: utxo_ref.to_string(),
+            from: from.cloned(),
+            to: to.clone(),
+        })
+    }
+}
@@ -0,0 +1,104 @@
+use anyhow::{Context, Result, anyhow};
+use reqwest::{Method, Url};
+use std::fmt;
+use std::str::FromStr;
+use tracing::{Span, info_span};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Layer};
+
+use crate::debug_events::EventBuffer;
+use crate::redact::redact;
+
+/// Filter applied when `RUST_LOG` is not set
+pub const DEFAULT_LOG_FILTER: &str = "info";
+
+/// How log events are written (`LOG_FORMAT`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines, prefixed with the spans they belong to
+    #[default]
+    Text,
+    /// One JSON object per event, with the fields of its spans
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            other => Err(anyhow!("expected text or json, got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for LogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogFormat::Text => "text",
+            LogFormat::Json => "json",
+        })
+    }
+}
+
+/// Install the process-wide subscriber from `LOG_FORMAT` and `RUST_LOG`
+///
+/// Both are read from the process environment, so with `TENANTS` they apply to every tenant.
+pub fn init_from_env() -> Result<()> {
+    let format = match std::env::var("LOG_FORMAT") {
+        Ok(value) if !value.trim().is_empty() => value.parse().context("Invalid LOG_FORMAT")?,
+        _ => LogFormat::Text,
+    };
+
+    init(format)
+}
+
+/// Install the process-wide subscriber writing `format` to stdout, filtered by `RUST_LOG`
+///
+/// Warnings and errors are also kept for `GET /debug/events` unless
+/// `DEBUG_EVENTS_CAPACITY` is `0` (see `debug_events`). Built with the `otel`
+/// feature, spans are also exported over OTLP when `OTEL_EXPORTER_OTLP_ENDPOINT`
+/// is set (see `telemetry`).
+pub fn init(format: LogFormat) -> Result<()> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_LOG_FILTER));
+    let output = match format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer().json().with_current_span(true).with_span_list(true).boxed(),
+    };
+    let events = EventBuffer::from_env()?;
+    let subscriber = tracing_subscriber::registry().with(filter).with(output).with(events.as_ref().map(EventBuffer::layer));
+
+    #[cfg(feature = "otel")]
+    let subscriber = subscriber.with(crate::telemetry::layer_from_env()?);
+
+    subscriber.try_init().map_err(|e| anyhow!("Failed to install the log subscriber: {}", e))?;
+    if let Some(events) = events {
+        events.install();
+    }
+
+    Ok(())
+}
+
+/// Flush what the subscriber still holds, i.e. spans awaiting OTLP export; call before the process exits
+pub fn shutdown() {
+    #[cfg(feature = "otel")]
+    crate::telemetry::shutdown();
+}
+
+/// Span of a request to `service`, nested in the run or shipment span it is made for
+///
+/// Only the host and the redacted path of `url` are recorded, never its query.
+pub fn http_span(service: &'static str, method: &Method, url: &str) -> Span {
+    let url = Url::parse(url).ok();
+    info_span!(
+        "http.request",
+        otel.kind = "client",
+        peer.service = service,
+        http.request.method = %method,
+        server.address = url.as_ref().and_then(Url::host_str).unwrap_or_default(),
+        url.path = %url.as_ref().map(|url| redact(url.path())).unwrap_or_default(),
+    )
+}
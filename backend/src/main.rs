@@ -1,34 +1,460 @@
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
+use chrono::NaiveDate;
+use clap::Parser;
+use std::path::Path;
 use std::sync::Arc;
+use std::time::Duration;
 use shipping_oracle::{
-    scheduler,
+    admin::AdminServer,
+    scheduler::{self, Pipeline, RunMode},
+    cli::{self, Cli, Command, ConfigOverrides},
     config::Config,
-    fetcher::DataFetcher,
-    shipment::ShipmentClient,
-    blockchain::CardanoClient,
+    debug_events,
+    decisions::DecisionQuery,
+    fees::FeeReport,
+    logging,
+    metrics::{Metrics, MetricsServer},
+    oracle::Oracle,
+    privacy::tracking_hash,
+    proxy,
+    redact,
+    blockchain::{NetworkCheck, ValidatorScriptCheck},
+    self_test::SelfTest,
+    shipment_import,
+    shippo_budget::{self, UsageReport},
+    state::{self, SqliteStore, StateStore},
+    tenant,
 };
+#[cfg(feature = "replay")]
+use shipping_oracle::replay::ReplaySession;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     dotenvy::dotenv().ok();
+    if let Err(e) = logging::init_from_env() {
+        eprintln!("Configuration error: {:#}", e);
+        std::process::exit(1);
+    }
 
-    let config = match Config::from_env() {
-        Ok(cfg) => cfg,
+    let args = Cli::parse();
+    let overrides = &args.overrides;
+    match &args.command {
+        Some(Command::Run | Command::Once) | None => {}
+        Some(Command::List { json, tenant }) => {
+            let oracle = Oracle::from_config(select_config(overrides, tenant.as_deref())?)?;
+            return cli::list(&oracle, *json, &mut std::io::stdout()).await;
+        }
+        Some(Command::Status { carrier, tracking_number, tenant }) => {
+            let config = select_config(overrides, tenant.as_deref())?;
+            return cli::status(&config, carrier, tracking_number, &mut std::io::stdout()).await;
+        }
+        Some(Command::Close(close)) => {
+            let oracle = Oracle::from_config(select_config(overrides, close.tenant.as_deref())?)?;
+            let mut input = std::io::stdin().lock();
+            return cli::close(&oracle, close, &mut input, &mut std::io::stdout()).await;
+        }
+        Some(Command::RegisterTracking { carrier, tracking_number, salt }) => {
+            return register_tracking(overrides, carrier, tracking_number, salt).await;
+        }
+        Some(Command::Reconcile { json, requeue, tenant, outbox_addresses }) => {
+            let oracle = Oracle::from_config(select_config(overrides, tenant.as_deref())?)?;
+            return reconcile(&oracle, outbox_addresses, *json, *requeue).await;
+        }
+        Some(Command::Fees { json, since, tenant }) => {
+            let oracle = Oracle::from_config(select_config(overrides, tenant.as_deref())?)?;
+            return fees(&oracle, *json, *since).await;
+        }
+        Some(Command::ShippoUsage { json, period, tenant }) => {
+            let oracle = Oracle::from_config(select_config(overrides, tenant.as_deref())?)?;
+            return shippo_usage(&oracle, *json, period.as_deref()).await;
+        }
+        Some(Command::Decisions { tracking_number, outbox, offset, limit, tenant }) => {
+            let mut query = DecisionQuery { tracking_number: tracking_number.clone(), outbox: outbox.clone(), ..Default::default() };
+            query.offset = offset.unwrap_or(query.offset);
+            query.limit = limit.unwrap_or(query.limit);
+            let oracle = Oracle::from_config(select_config(overrides, tenant.as_deref())?)?;
+            println!("{}", oracle.decisions(&query).await?.to_json()?);
+            return Ok(());
+        }
+        Some(Command::SelfTest { utxo, tenant }) => {
+            let oracle = Oracle::from_config(select_config(overrides, tenant.as_deref())?)?;
+            return self_test(&oracle, utxo.as_deref()).await;
+        }
+        Some(Command::ImportShipments { force, json, tenant, csv }) => {
+            let oracle = Oracle::from_config(select_config(overrides, tenant.as_deref())?)?;
+            return import_shipments(&oracle, csv, *force, *json).await;
+        }
+    }
+
+    let configs = match load_configs(overrides) {
+        Ok(configs) => configs,
         Err(e) => {
-            eprintln!("Configuration error: {}", e);
+            eprintln!("Configuration error: {:#}", e);
             std::process::exit(1);
         }
     };
-    
-    let data_handler = Arc::new(DataFetcher::new(
-        Arc::new(CardanoClient::new(config.clone())?),
-        Arc::new(ShipmentClient::new(config.clone())?),
-    ));
 
-    println!("Cron schedule: {}", config.cron_schedule);
+    // Sentry is process-wide; the first config carrying a DSN initializes it
+    #[cfg(feature = "sentry")]
+    let _sentry = configs
+        .iter()
+        .find(|config| config.sentry_dsn.is_some())
+        .and_then(shipping_oracle::error_reporting::init);
+
+    #[cfg(not(feature = "sentry"))]
+    if configs.iter().any(|config| config.sentry_dsn.is_some()) {
+        eprintln!("⚠️  SENTRY_DSN is set but the binary was built without the `sentry` feature");
+    }
+
+    // One process runs every tenant the same way
+    if configs.iter().any(|config| config.run_mode != configs[0].run_mode) {
+        eprintln!("Configuration error: every tenant must use the same RUN_MODE");
+        std::process::exit(1);
+    }
+    let run_mode = args.run_mode(configs[0].run_mode);
+
+    if configs.len() > 1 || configs[0].tenant.is_some() {
+        println!("Tenants: {}", configs.iter().filter_map(|config| config.tenant.as_deref()).collect::<Vec<_>>().join(", "));
+    }
+
+    #[cfg(feature = "replay")]
+    let (configs, _recordings) = record_if_asked(configs).await?;
+
+    // One registry for every tenant, told apart by their `tenant` label
+    let metrics = Metrics::new();
+    let mut pipelines = Vec::with_capacity(configs.len());
+    for config in &configs {
+        let label = config.tenant.as_ref().map(|tenant| format!("[{}] ", tenant)).unwrap_or_default();
+        if let Some(path) = &config.state_db_path {
+            println!("{}State database: {}", label, path);
+        }
+        println!("{}Cron schedule: {} ({})", label, config.cron_schedule, config.cron_timezone);
+        if let Some(window) = &config.submit_window {
+            println!("{}Submit window: {}", label, window);
+        }
+        println!("{}Shippo proxy: {}", label, proxy::describe(config.http_proxy_shippo.as_ref().map(|proxy| proxy.expose().as_str())));
+        println!("{}Blockfrost proxy: {}", label, proxy::describe(config.http_proxy_blockfrost.as_ref().map(|proxy| proxy.expose().as_str())));
+        println!("{}Datum codecs: {}", label, config.datum_codecs.join(", "));
+        if config.dry_run {
+            println!("{}Dry run: closes are signed, not submitted", label);
+        }
+        if let Some(budget) = config.shippo_monthly_budget {
+            println!(
+                "{}Shippo budget: {} tracking calls a month, then transits older than {}h only",
+                label, budget, config.shippo_degraded_transit_hours
+            );
+        }
+
+        let oracle = Oracle::builder().config(config.clone()).metrics(metrics.clone()).build()?;
+        if let Some(policy) = oracle.data_fetcher().outbox_policy() {
+            println!("{}Outbox policy: {}", label, policy);
+        }
+        check_network(&oracle, &label).await;
+        check_validator_script(&oracle, &label).await;
+        if config.self_test_on_start {
+            let report = SelfTest::new(&oracle).run().await;
+            println!("{}", report);
+            if !report.passed() {
+                std::process::exit(1);
+            }
+        }
+        if let Some(events) = debug_events::installed() {
+            tokio::spawn(debug_events::record_oracle_events(events, oracle.subscribe()));
+        }
+        pipelines.push(Pipeline::for_oracle(&oracle)?);
+    }
+    println!("Run mode: {}", run_mode);
     println!("================================");
-    
-    scheduler::create_and_run_scheduler(config, data_handler).await?;
-    
+
+    if run_mode == RunMode::Once {
+        let summary = scheduler::run_once(pipelines).await;
+        println!("{}", summary);
+        redact::forget_secrets();
+        logging::shutdown();
+        std::process::exit(summary.exit_code());
+    }
+
+    // The metrics server shares the runtime and stops once the scheduler has shut down
+    let metrics_server = match configs.iter().find_map(|config| config.metrics_addr) {
+        Some(addr) => {
+            let server = MetricsServer::bind(addr, metrics)?;
+            println!("Metrics: http://{}/metrics", server.local_addr()?);
+            let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+            let handle = tokio::spawn(server.serve_until(async {
+                let _ = stopped.await;
+            }));
+            Some((stop, handle))
+        }
+        None => None,
+    };
+
+    // The admin API triggers runs of the same pipelines, so they never overlap with scheduled ones
+    let pipelines: Vec<Arc<Pipeline>> = pipelines.into_iter().map(Arc::new).collect();
+    let admin_server = match configs.iter().find_map(|config| config.admin_addr) {
+        Some(addr) => {
+            // Each tenant answers to its own token; tenants without one are not served
+            let mut served = Vec::new();
+            for (config, pipeline) in configs.iter().zip(&pipelines) {
+                match &config.admin_token {
+                    Some(token) => served.push((pipeline.clone(), token.clone())),
+                    None => println!("[{}] No ADMIN_TOKEN, not served by the admin API", config.tenant.as_deref().unwrap_or_default()),
+                }
+            }
+            let mut server = AdminServer::bind(addr, served)?;
+            if let Some(events) = debug_events::installed() {
+                server = server.with_debug_events(events);
+            }
+            println!("Admin API: http://{}", server.local_addr()?);
+            let (stop, stopped) = tokio::sync::oneshot::channel::<()>();
+            let handle = tokio::spawn(server.serve_until(async {
+                let _ = stopped.await;
+            }));
+            Some((stop, handle))
+        }
+        None => None,
+    };
+
+    // The admin API stops as soon as shutdown starts, so no run is triggered while the scheduler waits out the ones in flight
+    let (admin_stop, admin_handle) = admin_server.unzip();
+    let shutdown = async move {
+        scheduler::shutdown_signal().await;
+        if let Some(stop) = admin_stop {
+            let _ = stop.send(());
+        }
+    };
+    let shutdown_grace = configs.iter().map(|config| config.shutdown_grace_seconds).max().unwrap_or_default();
+    scheduler::run_shared_scheduler_until(pipelines, Duration::from_secs(shutdown_grace), shutdown).await?;
+
+    if let Some((stop, server)) = metrics_server {
+        let _ = stop.send(());
+        server.await??;
+    }
+    // Runs it triggered were waited for with the scheduler's; one outliving the grace period is abandoned
+    if let Some(server) = admin_handle {
+        if server.is_finished() {
+            server.await??;
+        } else {
+            server.abort();
+        }
+    }
+
+    redact::forget_secrets();
+    logging::shutdown();
+    Ok(())
+}
+
+/// Exit when Blockfrost serves another network than `CARDANO_NETWORK`
+///
+/// A failed lookup only warns, or exits under `STRICT_STARTUP`.
+async fn check_network(oracle: &Oracle, label: &str) {
+    let config = oracle.config();
+    let Some(network) = config.cardano_network else {
+        return;
+    };
+
+    match oracle.chain().check_network(network).await {
+        Ok(NetworkCheck::Matches) => println!("{}Network: {}", label, network.as_str()),
+        Ok(mismatch) => {
+            eprintln!("{}❌ {}", label, mismatch);
+            std::process::exit(1);
+        }
+        Err(e) if config.strict_startup => {
+            eprintln!("{}❌ Failed to check the Blockfrost network: {:#}", label, e);
+            std::process::exit(1);
+        }
+        Err(e) => eprintln!("{}⚠️  Failed to check the Blockfrost network: {:#}", label, e),
+    }
+}
+
+/// Warn, or exit under `STRICT_STARTUP`, when the validator reference script is not usable
+async fn check_validator_script(oracle: &Oracle, label: &str) {
+    let config = oracle.config();
+    let problem = match oracle.chain().check_validator_script().await {
+        Ok(ValidatorScriptCheck::Present { script_hash }) => {
+            println!("{}Validator script: {} ({})", label, config.validator_script_ref, script_hash);
+            return;
+        }
+        Ok(check) => check.to_string(),
+        Err(e) => format!("Failed to check VALIDATOR_SCRIPT_REF: {:#}", e),
+    };
+
+    if config.strict_startup {
+        eprintln!("{}❌ {}", label, problem);
+        std::process::exit(1);
+    }
+
+    eprintln!("{}⚠️  {}; close transactions will fail until this is fixed", label, problem);
+}
+
+/// One config per tenant when `TENANTS` points at a tenants file, otherwise the environment
+/// config with the command-line overrides applied
+fn load_configs(overrides: &ConfigOverrides) -> Result<Vec<Config>> {
+    match std::env::var("TENANTS") {
+        Ok(path) if !path.trim().is_empty() => {
+            if !overrides.is_empty() {
+                bail!("Configuration flags cannot override a TENANTS file; edit its sections instead");
+            }
+            let tenants = tenant::load(path.trim())?;
+            Ok(tenants.into_iter().map(|tenant| tenant.config).collect())
+        }
+        _ => Ok(vec![overrides.load(|key| std::env::var(key).ok())?]),
+    }
+}
+
+/// With `RECORD_DIR` set, send every tenant's Blockfrost, Shippo and TRP requests through a
+/// `ReplaySession` recording them there, one subdirectory per tenant
+#[cfg(feature = "replay")]
+async fn record_if_asked(configs: Vec<Config>) -> Result<(Vec<Config>, Vec<ReplaySession>)> {
+    let Some(dir) = std::env::var("RECORD_DIR").ok().filter(|dir| !dir.trim().is_empty()) else {
+        return Ok((configs, Vec::new()));
+    };
+
+    let mut recorded = Vec::with_capacity(configs.len());
+    let mut sessions = Vec::with_capacity(configs.len());
+    for config in configs {
+        let dir = Path::new(dir.trim()).join(config.tenant.as_deref().unwrap_or_default());
+        let (session, config) = ReplaySession::record(&config, &dir).await?;
+        println!("Recording Blockfrost, Shippo and TRP exchanges into {}", dir.display());
+        recorded.push(config);
+        sessions.push(session);
+    }
+    Ok((recorded, sessions))
+}
+
+/// `register-tracking`: print the privacy-mode datum hash for a merchant and record it in
+/// the state database when configured
+async fn register_tracking(overrides: &ConfigOverrides, carrier: &str, tracking_number: &str, salt: &str) -> Result<()> {
+    let hash = tracking_hash(carrier, tracking_number, salt);
+    println!("{}", hex::encode(hash));
+
+    let path = match (overrides.state_db_path.clone(), std::env::var("STATE_DB_PATH"), std::env::var("DATABASE_URL")) {
+        (Some(path), _, _) => Some(path),
+        (_, Ok(path), _) if !path.trim().is_empty() => Some(path),
+        (_, _, Ok(url)) => Some(state::sqlite_path(&url).context("Invalid DATABASE_URL")?),
+        _ => None,
+    };
+    match path {
+        Some(path) => {
+            SqliteStore::open(&path)?
+                .register_tracking(&hash, carrier, tracking_number)
+                .await?;
+            eprintln!("Registered in {}", path);
+        }
+        None => eprintln!("STATE_DB_PATH not set; add the hash to TRACKING_LOOKUP_PATH to resolve it"),
+    }
+
+    Ok(())
+}
+
+/// `reconcile`: classify every journalled closure against the chain and print the report
+/// as markdown or JSON
+async fn reconcile(oracle: &Oracle, outbox_addresses: &[String], json: bool, requeue: bool) -> Result<()> {
+    let Some(state) = oracle.state() else {
+        bail!("STATE_DB_PATH or DATABASE_URL must point at the state database holding the submission journal");
+    };
+
+    let journal = state.journal().await?;
+    let report = oracle.chain().reconcile(outbox_addresses, &journal).await?;
+
+    if json {
+        println!("{}", report.to_json()?);
+    } else {
+        print!("{}", report.to_markdown(oracle.config().cardano_network));
+    }
+
+    if requeue {
+        for utxo_ref in report.requeue(state.as_ref()).await? {
+            eprintln!("Queued {} for resubmission", utxo_ref);
+        }
+    }
+
+    Ok(())
+}
+
+/// `fees`: total the fees paid for journalled closures, broken down per outbox address
+async fn fees(oracle: &Oracle, json: bool, since: Option<NaiveDate>) -> Result<()> {
+    let Some(state) = oracle.state() else {
+        bail!("STATE_DB_PATH or DATABASE_URL must point at the state database holding the submission journal");
+    };
+
+    let since = since.map(|date| date.and_time(chrono::NaiveTime::MIN).and_utc().timestamp() as u64);
+    let report = FeeReport::from_state(state.as_ref(), since).await?;
+    if json {
+        println!("{}", report.to_json()?);
+    } else {
+        print!("{}", report.to_markdown());
+    }
+
     Ok(())
 }
+
+/// `shippo-usage`: count the Shippo tracking calls of a billing period (default: the current
+/// one), per carrier and per outbox address
+async fn shippo_usage(oracle: &Oracle, json: bool, period: Option<&str>) -> Result<()> {
+    let period = period.map(shippo_budget::parse_period).transpose().context("Invalid --period")?;
+    let Some(state) = oracle.state() else {
+        bail!("STATE_DB_PATH or DATABASE_URL must point at the state database holding the tracking call counts");
+    };
+
+    let config = oracle.config();
+    let period = period.unwrap_or_else(|| shippo_budget::period(chrono::Utc::now(), config.cron_timezone));
+    let report = UsageReport::from_state(state.as_ref(), &period, config.shippo_monthly_budget).await?;
+    if json {
+        println!("{}", report.to_json()?);
+    } else {
+        print!("{}", report.to_markdown());
+    }
+
+    Ok(())
+}
+
+/// `self-test`: provision a `selftest` shipment (or use the given one), close it as delivered
+/// and check the result on-chain
+async fn self_test(oracle: &Oracle, utxo: Option<&str>) -> Result<()> {
+    let mut self_test = SelfTest::new(oracle);
+    if let Some(utxo) = utxo {
+        self_test = self_test.with_tracking_utxo(utxo);
+    }
+
+    let report = self_test.run().await;
+    println!("{}", report);
+    if !report.passed() {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// `import-shipments`: seed the state database with open shipments listed as
+/// `utxo_ref,carrier,tracking_number,outbox` rows, checked against the chain
+async fn import_shipments(oracle: &Oracle, csv_path: &Path, force: bool, json: bool) -> Result<()> {
+    let csv = std::fs::read_to_string(csv_path).with_context(|| format!("Failed to read {}", csv_path.display()))?;
+
+    let Some(state) = oracle.state() else {
+        bail!("STATE_DB_PATH or DATABASE_URL must point at the state database to import shipments into");
+    };
+
+    let open = oracle.scan().await?;
+    let now = chrono::Utc::now().timestamp() as u64;
+    let report = shipment_import::import_shipments(&csv, &open, state.as_ref(), force, now).await?;
+    if json {
+        println!("{}", report.to_json()?);
+    } else {
+        print!("{}", report.to_markdown());
+    }
+
+    Ok(())
+}
+
+/// The named tenant's config, or the only config when no tenant is given
+fn select_config(overrides: &ConfigOverrides, tenant: Option<&str>) -> Result<Config> {
+    let mut configs = load_configs(overrides)?;
+    match tenant {
+        Some(name) => configs
+            .into_iter()
+            .find(|config| config.tenant.as_deref() == Some(name))
+            .with_context(|| format!("No tenant named {}", name)),
+        None if configs.len() == 1 => Ok(configs.remove(0)),
+        None => bail!("--tenant is required when TENANTS lists several tenants"),
+    }
+}
@@ -0,0 +1,305 @@
+use anyhow::{Context, Result};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use prometheus::{HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder};
+use std::collections::{BTreeMap, HashSet};
+use std::convert::Infallible;
+use std::future::Future;
+use std::net::{SocketAddr, TcpListener};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+
+use crate::config::DEFAULT_METRICS_MAX_OUTBOXES;
+
+/// `outbox` label of the outbox addresses seen after `METRICS_MAX_OUTBOXES` others
+pub const OTHER_OUTBOX: &str = "other";
+
+/// Prometheus counters and gauges of the oracle pipelines
+///
+/// Clones share the same registry; `for_tenant` gives a clone labelling
+/// everything it records with a tenant, so one registry serves every pipeline.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    /// `tenant` label value, empty outside multi-tenant deployments
+    tenant: String,
+    outboxes: Arc<OutboxLabels>,
+    shipments_discovered: IntCounterVec,
+    statuses_fetched: IntCounterVec,
+    closes_submitted: IntCounterVec,
+    closes_failed: IntCounterVec,
+    fees_paid: IntCounterVec,
+    request_errors: IntCounterVec,
+    tracking_request_seconds: HistogramVec,
+    tracking_errors: IntCounterVec,
+    notification_delivery_failures: IntCounterVec,
+    last_successful_run: IntGaugeVec,
+    pending_closes: IntGaugeVec,
+    close_queue_depth: IntGaugeVec,
+}
+
+/// `outbox` label values of one tenant's metrics
+///
+/// An address is labelled with its `OUTBOX_ALIASES` alias, else with itself
+/// until `max` distinct addresses are; the next ones share `OTHER_OUTBOX`.
+struct OutboxLabels {
+    aliases: BTreeMap<String, String>,
+    max: usize,
+    /// Unaliased addresses labelled with themselves
+    labelled: Mutex<HashSet<String>>,
+    /// Set once the cap was reached, so the warning is logged once
+    capped: AtomicBool,
+}
+
+impl OutboxLabels {
+    fn new(aliases: BTreeMap<String, String>, max: usize) -> Self {
+        Self { aliases, max, labelled: Mutex::new(HashSet::new()), capped: AtomicBool::new(false) }
+    }
+
+    fn label(&self, address: &str) -> String {
+        if let Some(alias) = self.aliases.get(address) {
+            return alias.clone();
+        }
+
+        let mut labelled = self.labelled.lock().unwrap_or_else(|e| e.into_inner());
+        if labelled.contains(address) || labelled.len() < self.max {
+            labelled.insert(address.to_string());
+            return address.to_string();
+        }
+        if !self.capped.swap(true, Ordering::Relaxed) {
+            warn!(
+                max_outboxes = self.max,
+                "⚠️  More outbox addresses than METRICS_MAX_OUTBOXES, labelling the others '{}' (name merchants in OUTBOX_ALIASES)",
+                OTHER_OUTBOX,
+            );
+        }
+        OTHER_OUTBOX.to_string()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    /// Metrics registered in a registry of their own
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let counter = |name: &str, help: &str, labels: &[&str]| {
+            let counter = IntCounterVec::new(Opts::new(name, help), labels).expect("valid metric");
+            registry.register(Box::new(counter.clone())).expect("metric names are unique");
+            counter
+        };
+        let gauge = |name: &str, help: &str| {
+            let gauge = IntGaugeVec::new(Opts::new(name, help), &["tenant"]).expect("valid metric");
+            registry.register(Box::new(gauge.clone())).expect("metric names are unique");
+            gauge
+        };
+
+        Self {
+            shipments_discovered: counter(
+                "oracle_shipments_discovered_total",
+                "Tracking UTxOs found at the oracle address, counted once per run",
+                &["tenant"],
+            ),
+            statuses_fetched: counter(
+                "oracle_statuses_fetched_total",
+                "Carrier statuses fetched from Shippo",
+                &["tenant", "carrier"],
+            ),
+            closes_submitted: counter(
+                "oracle_closes_submitted_total",
+                "Close transactions submitted, per merchant outbox address",
+                &["tenant", "outbox"],
+            ),
+            closes_failed: counter(
+                "oracle_closes_failed_total",
+                "Shipments whose status could not be fetched or whose close could not be made, per merchant outbox address",
+                &["tenant", "outbox"],
+            ),
+            fees_paid: counter(
+                "oracle_fees_paid_lovelace_total",
+                "Fees of the close transactions seen confirmed, in lovelace",
+                &["tenant"],
+            ),
+            request_errors: counter(
+                "oracle_request_errors_total",
+                "Failed requests to upstream services",
+                &["tenant", "service"],
+            ),
+            tracking_request_seconds: {
+                let opts = HistogramOpts::new(
+                    "oracle_tracking_request_seconds",
+                    "Duration of tracking provider requests, per carrier, waits for CARRIER_LIMITS aside",
+                );
+                let histogram = HistogramVec::new(opts, &["tenant", "carrier"]).expect("valid metric");
+                registry.register(Box::new(histogram.clone())).expect("metric names are unique");
+                histogram
+            },
+            tracking_errors: counter(
+                "oracle_tracking_errors_total",
+                "Failed tracking provider requests, per carrier",
+                &["tenant", "carrier"],
+            ),
+            notification_delivery_failures: counter(
+                "oracle_notification_delivery_failures_total",
+                "Notifications that could not be delivered after all retries, per notifier",
+                &["tenant", "notifier"],
+            ),
+            last_successful_run: gauge(
+                "oracle_last_successful_run_timestamp_seconds",
+                "Unix time the latest run without a failed shipment finished",
+            ),
+            pending_closes: gauge(
+                "oracle_pending_closes",
+                "Submitted close transactions whose tracking UTxO is still unspent",
+            ),
+            close_queue_depth: gauge(
+                "oracle_close_queue_depth",
+                "Shipments with a final status waiting in a run's queue to be closed",
+            ),
+            registry,
+            tenant: String::new(),
+            outboxes: Arc::new(OutboxLabels::new(BTreeMap::new(), DEFAULT_METRICS_MAX_OUTBOXES)),
+        }
+    }
+
+    /// A clone recording into the same registry under the `tenant` label
+    pub fn for_tenant(&self, tenant: Option<&str>) -> Self {
+        Self { tenant: tenant.unwrap_or_default().to_string(), ..self.clone() }
+    }
+
+    /// A clone labelling outbox addresses with `aliases`, and at most `max_outboxes` others with themselves
+    pub fn with_outbox_labels(&self, aliases: BTreeMap<String, String>, max_outboxes: usize) -> Self {
+        Self { outboxes: Arc::new(OutboxLabels::new(aliases, max_outboxes)), ..self.clone() }
+    }
+
+    /// `outbox` label value `outbox_address` is recorded under
+    pub fn outbox_label(&self, outbox_address: &str) -> String {
+        self.outboxes.label(outbox_address)
+    }
+
+    pub fn shipments_discovered(&self, count: usize) {
+        self.shipments_discovered.with_label_values(&[&self.tenant]).inc_by(count as u64);
+    }
+
+    pub fn status_fetched(&self, carrier: &str) {
+        self.statuses_fetched.with_label_values(&[&self.tenant, carrier]).inc();
+    }
+
+    pub fn close_submitted(&self, outbox_address: &str) {
+        self.closes_submitted.with_label_values(&[&self.tenant, &self.outbox_label(outbox_address)]).inc();
+    }
+
+    pub fn close_failed(&self, outbox_address: &str) {
+        self.closes_failed.with_label_values(&[&self.tenant, &self.outbox_label(outbox_address)]).inc();
+    }
+
+    /// A close transaction paying `fee` lovelace was seen confirmed
+    pub fn fee_paid(&self, fee: u64) {
+        self.fees_paid.with_label_values(&[&self.tenant]).inc_by(fee);
+    }
+
+    /// A request to `service` (`blockfrost`, `kupo`, `ogmios`, `shippo` or `trp`) failed
+    pub fn request_error(&self, service: &str) {
+        self.request_errors.with_label_values(&[&self.tenant, service]).inc();
+    }
+
+    /// A tracking request about `carrier` took `elapsed`, and `failed` or not
+    pub fn tracking_request(&self, carrier: &str, elapsed: Duration, failed: bool) {
+        let carrier = carrier.to_lowercase();
+        self.tracking_request_seconds.with_label_values(&[&self.tenant, &carrier]).observe(elapsed.as_secs_f64());
+        if failed {
+            self.tracking_errors.with_label_values(&[&self.tenant, &carrier]).inc();
+        }
+    }
+
+    /// A notification could not be delivered by `notifier` (`webhook`) after all retries
+    pub fn notification_delivery_failed(&self, notifier: &str) {
+        self.notification_delivery_failures.with_label_values(&[&self.tenant, notifier]).inc();
+    }
+
+    pub fn run_succeeded(&self, finished_at: i64) {
+        self.last_successful_run.with_label_values(&[&self.tenant]).set(finished_at);
+    }
+
+    pub fn set_pending_closes(&self, count: usize) {
+        self.pending_closes.with_label_values(&[&self.tenant]).set(count as i64);
+    }
+
+    pub fn set_close_queue_depth(&self, depth: usize) {
+        self.close_queue_depth.with_label_values(&[&self.tenant]).set(depth as i64);
+    }
+
+    /// Every metric of the registry in the Prometheus text format
+    pub fn render(&self) -> Result<String> {
+        TextEncoder::new()
+            .encode_to_string(&self.registry.gather())
+            .context("Failed to encode metrics")
+    }
+}
+
+/// HTTP server answering `GET /metrics` with the rendered `Metrics` (`METRICS_ADDR`)
+pub struct MetricsServer {
+    listener: TcpListener,
+    metrics: Metrics,
+}
+
+impl MetricsServer {
+    pub fn bind(addr: SocketAddr, metrics: Metrics) -> Result<Self> {
+        let listener = TcpListener::bind(addr).with_context(|| format!("Failed to bind the metrics server to {}", addr))?;
+        listener.set_nonblocking(true).context("Failed to configure the metrics listener")?;
+
+        Ok(Self { listener, metrics })
+    }
+
+    /// Address the server listens on, e.g. to learn the port picked for `127.0.0.1:0`
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.listener.local_addr().context("Failed to read the metrics server address")
+    }
+
+    /// Serve scrapes until `shutdown` resolves, then finish the ones in flight
+    pub async fn serve_until(self, shutdown: impl Future<Output = ()>) -> Result<()> {
+        let metrics = self.metrics;
+        let make_service = make_service_fn(move |_connection| {
+            let metrics = metrics.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request| {
+                    let response = respond(&metrics, &request);
+                    async move { Ok::<_, Infallible>(response) }
+                }))
+            }
+        });
+
+        Server::from_tcp(self.listener)
+            .context("Failed to start the metrics server")?
+            .serve(make_service)
+            .with_graceful_shutdown(shutdown)
+            .await
+            .context("Metrics server failed")
+    }
+}
+
+fn respond(metrics: &Metrics, request: &Request<Body>) -> Response<Body> {
+    if request.method() != Method::GET || request.uri().path() != "/metrics" {
+        return status_response(StatusCode::NOT_FOUND, "Not found");
+    }
+
+    match metrics.render() {
+        Ok(body) => Response::builder()
+            .header("Content-Type", prometheus::TEXT_FORMAT)
+            .body(Body::from(body))
+            .unwrap_or_else(|_| status_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to build the response")),
+        Err(e) => status_response(StatusCode::INTERNAL_SERVER_ERROR, &format!("{:#}", e)),
+    }
+}
+
+fn status_response(status: StatusCode, message: &str) -> Response<Body> {
+    let mut response = Response::new(Body::from(message.to_string()));
+    *response.status_mut() = status;
+    response
+}
@@ -0,0 +1,112 @@
+use anyhow::{Result, anyhow};
+use bip39::Mnemonic;
+use ed25519_dalek::VerifyingKey;
+use ed25519_dalek::hazmat::ExpandedSecretKey;
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+use zeroize::Zeroizing;
+
+use crate::signing::SigningKeyMaterial;
+
+/// First hardened BIP-32 index
+const HARDENED: u32 = 0x8000_0000;
+
+/// CIP-1852 path of the first payment key of the first account: `1852'/1815'/0'/0/0`
+pub const PAYMENT_KEY_PATH: [u32; 5] = [HARDENED + 1852, HARDENED + 1815, HARDENED, 0, 0];
+
+/// A BIP32-Ed25519 extended private key and its chain code
+struct ExtendedKey {
+    /// Secret scalar bytes (kL) followed by the signing nonce prefix (kR)
+    key: Zeroizing<[u8; 64]>,
+    chain_code: Zeroizing<[u8; 32]>,
+}
+
+impl ExtendedKey {
+    /// Icarus master key (CIP-3) of a mnemonic with an empty passphrase
+    fn from_mnemonic(phrase: &str) -> Result<Self> {
+        let mnemonic = Mnemonic::parse(phrase.trim()).map_err(|e| anyhow!("Invalid mnemonic: {}", e))?;
+        let entropy = Zeroizing::new(mnemonic.to_entropy());
+
+        let mut seed = Zeroizing::new([0u8; 96]);
+        pbkdf2::pbkdf2_hmac::<Sha512>(b"", &entropy, 4096, seed.as_mut_slice());
+        seed[0] &= 0b1111_1000;
+        seed[31] &= 0b0001_1111;
+        seed[31] |= 0b0100_0000;
+
+        let mut key = Zeroizing::new([0u8; 64]);
+        let mut chain_code = Zeroizing::new([0u8; 32]);
+        key.copy_from_slice(&seed[..64]);
+        chain_code.copy_from_slice(&seed[64..]);
+
+        Ok(Self { key, chain_code })
+    }
+
+    fn expanded(&self) -> ExpandedSecretKey {
+        let mut scalar = Zeroizing::new([0u8; 32]);
+        scalar.copy_from_slice(&self.key[..32]);
+        let mut hash_prefix = [0u8; 32];
+        hash_prefix.copy_from_slice(&self.key[32..]);
+
+        // Derived scalars are not clamped again, so they are reduced as they are
+        ExpandedSecretKey {
+            scalar: curve25519_dalek::Scalar::from_bytes_mod_order(*scalar),
+            hash_prefix,
+        }
+    }
+
+    /// Child key at `index` (BIP32-Ed25519, V2 derivation as used by Cardano wallets)
+    fn derive(&self, index: u32) -> Self {
+        let mut key_mac = Hmac::<Sha512>::new_from_slice(self.chain_code.as_slice()).expect("HMAC accepts any key length");
+        let mut code_mac = key_mac.clone();
+        if index >= HARDENED {
+            key_mac.update(&[0x00]);
+            key_mac.update(self.key.as_slice());
+            code_mac.update(&[0x01]);
+            code_mac.update(self.key.as_slice());
+        } else {
+            let public_key = VerifyingKey::from(&self.expanded()).to_bytes();
+            key_mac.update(&[0x02]);
+            key_mac.update(&public_key);
+            code_mac.update(&[0x03]);
+            code_mac.update(&public_key);
+        }
+        key_mac.update(&index.to_le_bytes());
+        code_mac.update(&index.to_le_bytes());
+        let mut z = Zeroizing::new([0u8; 64]);
+        z.copy_from_slice(&key_mac.finalize().into_bytes());
+        let i = code_mac.finalize().into_bytes();
+
+        let mut key = Zeroizing::new([0u8; 64]);
+        // kL = 8 * zL[0..28] + parent kL
+        let mut carry = 0u16;
+        for byte in 0..32 {
+            let z_times_8 = if byte < 28 { (z[byte] as u16) << 3 } else { 0 };
+            let z_shifted = z_times_8 | if byte > 0 && byte <= 28 { (z[byte - 1] >> 5) as u16 } else { 0 };
+            let sum = (z_shifted & 0xff) + self.key[byte] as u16 + carry;
+            key[byte] = sum as u8;
+            carry = sum >> 8;
+        }
+        // kR = zR + parent kR (mod 2^256)
+        let mut carry = 0u16;
+        for byte in 0..32 {
+            let sum = z[32 + byte] as u16 + self.key[32 + byte] as u16 + carry;
+            key[32 + byte] = sum as u8;
+            carry = sum >> 8;
+        }
+
+        let mut chain_code = Zeroizing::new([0u8; 32]);
+        chain_code.copy_from_slice(&i[32..]);
+
+        Self { key, chain_code }
+    }
+}
+
+/// The payment key a CIP-1852 wallet derives first from `phrase`, at `PAYMENT_KEY_PATH`
+pub fn payment_key(phrase: &str) -> Result<SigningKeyMaterial> {
+    let mut key = ExtendedKey::from_mnemonic(phrase)?;
+    for index in PAYMENT_KEY_PATH {
+        key = key.derive(index);
+    }
+
+    Ok(SigningKeyMaterial::from_expanded(key.expanded()))
+}
@@ -0,0 +1,97 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+use tracing::debug;
+
+use crate::config::Config;
+use crate::models::TrackingStatus;
+use crate::tracking_provider::{ProviderKind, TrackingProvider};
+
+/// Statuses a tracking number suffix can ask for
+const STATUSES: &[&str] = &["PRE_TRANSIT", "TRANSIT", "DELIVERED", "RETURNED", "FAILURE", "UNKNOWN"];
+
+/// `MOCK_TRACKING_FIXTURES` entry: a bare status or a full tracking status
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+enum Fixture {
+    Status(String),
+    Full {
+        status: String,
+        #[serde(default)]
+        status_details: Option<String>,
+        #[serde(default)]
+        status_date: Option<String>,
+    },
+}
+
+/// Status of a tracking number from its `-<STATUS>` suffix (e.g. `DEMO1-DELIVERED`), `TRANSIT` without one
+pub fn suffix_status(tracking_number: &str) -> &'static str {
+    tracking_number
+        .rsplit_once('-')
+        .and_then(|(_, suffix)| STATUSES.iter().find(|status| status.eq_ignore_ascii_case(suffix.trim())))
+        .copied()
+        .unwrap_or("TRANSIT")
+}
+
+/// Tracking provider answering without any API, for local runs, demos and CI (`TRACKING_PROVIDER=mock`)
+///
+/// Tracking numbers listed in the fixtures get their status from there; any other
+/// number gets the status of its suffix.
+#[derive(Debug, Default)]
+pub struct MockProvider {
+    fixtures: HashMap<String, Fixture>,
+}
+
+impl MockProvider {
+    /// Suffix rule only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Read a JSON object of tracking numbers to a status, or to `{status, status_details, status_date}`
+    pub fn from_fixtures(path: &str) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read MOCK_TRACKING_FIXTURES {}", path))?;
+        let fixtures = serde_json::from_str(&json)
+            .with_context(|| format!("Invalid MOCK_TRACKING_FIXTURES {}", path))?;
+
+        Ok(Self { fixtures })
+    }
+
+    pub fn from_config(config: &Config) -> Result<Self> {
+        match &config.mock_tracking_fixtures {
+            Some(path) => Self::from_fixtures(path),
+            None => Ok(Self::new()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl TrackingProvider for MockProvider {
+    async fn fetch_status(&self, carrier: &str, tracking_number: &str) -> Result<TrackingStatus> {
+        debug!(carrier, tracking_number, "Serving mock tracking status");
+
+        let fixture = self
+            .fixtures
+            .get(tracking_number)
+            .cloned()
+            .unwrap_or_else(|| Fixture::Status(suffix_status(tracking_number).to_string()));
+
+        Ok(match fixture {
+            Fixture::Status(status) => TrackingStatus {
+                status_details: format!("Mock {} status", status),
+                status: status.into(),
+                status_date: None,
+            },
+            Fixture::Full { status, status_details, status_date } => TrackingStatus {
+                status_details: status_details.unwrap_or_else(|| format!("Mock {} status", status)),
+                status: status.into(),
+                status_date,
+            },
+        })
+    }
+
+    fn provider(&self, _carrier: &str) -> ProviderKind {
+        ProviderKind::Mock
+    }
+}
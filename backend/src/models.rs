@@ -1,5 +1,8 @@
 use pallas::ledger::addresses::Address;
-use serde::Deserialize;
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
 
 /// Shippo API tracking response (partial, only fields we need)
 #[derive(Debug, Deserialize)]
@@ -9,25 +12,340 @@ pub struct TrackingResponse {
     pub tracking_status: TrackingStatus,
 }
 
-/// Shippo API tracking status (partial, only fields we need)
+/// Shippo `POST /tracks/` response (partial); a fresh registration may have no status yet
 #[derive(Debug, Deserialize)]
+pub struct TrackRegistration {
+    #[serde(default)]
+    pub tracking_status: Option<TrackingStatus>,
+}
+
+/// Shippo API tracking status (partial, only fields we need)
+#[derive(Debug, Clone, Deserialize)]
 pub struct TrackingStatus {
-    pub status: String,           // e.g., "DELIVERED", "TRANSIT", "PRE_TRANSIT"
+    pub status: CarrierStatus,
     pub status_details: String,   // Descriptive message
+    /// When the carrier recorded the status (RFC 3339)
+    #[serde(default)]
+    pub status_date: Option<String>,
+}
+
+/// Carrier status in Shippo's vocabulary, which the other providers are translated into
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+pub enum CarrierStatus {
+    Delivered,
+    Returned,
+    Failure,
+    Transit,
+    PreTransit,
+    /// `UNKNOWN`, or any status Shippo adds later, as sent
+    Unknown(String),
+}
+
+impl CarrierStatus {
+    pub fn as_str(&self) -> &str {
+        match self {
+            CarrierStatus::Delivered => "DELIVERED",
+            CarrierStatus::Returned => "RETURNED",
+            CarrierStatus::Failure => "FAILURE",
+            CarrierStatus::Transit => "TRANSIT",
+            CarrierStatus::PreTransit => "PRE_TRANSIT",
+            CarrierStatus::Unknown(status) => status,
+        }
+    }
+}
+
+impl From<&str> for CarrierStatus {
+    fn from(status: &str) -> Self {
+        match status {
+            "DELIVERED" => CarrierStatus::Delivered,
+            "RETURNED" => CarrierStatus::Returned,
+            "FAILURE" => CarrierStatus::Failure,
+            "TRANSIT" => CarrierStatus::Transit,
+            "PRE_TRANSIT" => CarrierStatus::PreTransit,
+            other => CarrierStatus::Unknown(other.to_string()),
+        }
+    }
+}
+
+impl From<String> for CarrierStatus {
+    fn from(status: String) -> Self {
+        CarrierStatus::from(status.as_str())
+    }
+}
+
+impl fmt::Display for CarrierStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Status a shipment is closed with, as written to the shipment datum
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DerivedStatus {
+    Delivered,
+    NotDelivered,
+}
+
+impl DerivedStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DerivedStatus::Delivered => "DELIVERED",
+            DerivedStatus::NotDelivered => "NOT_DELIVERED",
+        }
+    }
+
+    /// Bytes of the datum's `status` field
+    pub fn as_onchain_bytes(&self) -> &'static [u8] {
+        self.as_str().as_bytes()
+    }
+
+    /// Hex-encoded `as_onchain_bytes`, as the close transaction's `p_status` takes them
+    pub fn hex(&self) -> String {
+        hex::encode(self.as_onchain_bytes())
+    }
+}
+
+impl FromStr for DerivedStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        match value.trim() {
+            "DELIVERED" => Ok(DerivedStatus::Delivered),
+            "NOT_DELIVERED" => Ok(DerivedStatus::NotDelivered),
+            other => bail!("expected DELIVERED or NOT_DELIVERED, got '{}'", other),
+        }
+    }
+}
+
+impl fmt::Display for DerivedStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 /// Represents a tracking UTxO
-#[derive(Debug, Clone)]
+///
+/// Serializes as `{"tx_hash", "tx_index", "datum"}`, the shape the reports show.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "TrackingUTxOJson", into = "TrackingUTxOJson")]
 pub struct TrackingUTxO {
-    pub tx_hash: String,
-    pub tx_index: u32,
+    pub utxo_ref: UtxoRef,
     pub datum: TrackingDatum,
 }
 
+#[derive(Serialize, Deserialize)]
+struct TrackingUTxOJson {
+    tx_hash: String,
+    tx_index: u32,
+    datum: TrackingDatum,
+}
+
+impl TryFrom<TrackingUTxOJson> for TrackingUTxO {
+    type Error = anyhow::Error;
+
+    fn try_from(json: TrackingUTxOJson) -> anyhow::Result<Self> {
+        Ok(Self { utxo_ref: UtxoRef::from_hex(&json.tx_hash, json.tx_index)?, datum: json.datum })
+    }
+}
+
+impl From<TrackingUTxO> for TrackingUTxOJson {
+    fn from(utxo: TrackingUTxO) -> Self {
+        Self { tx_hash: utxo.utxo_ref.tx_hash_hex(), tx_index: utxo.utxo_ref.index, datum: utxo.datum }
+    }
+}
+
+/// Reference to a transaction output, written `TxHash#TxIx`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct UtxoRef {
+    pub tx_hash: [u8; 32],
+    pub index: u32,
+}
+
+impl UtxoRef {
+    pub fn new(tx_hash: [u8; 32], index: u32) -> Self {
+        Self { tx_hash, index }
+    }
+
+    /// Reference to output `index` of the transaction with the hex hash `tx_hash`
+    pub fn from_hex(tx_hash: &str, index: u32) -> anyhow::Result<Self> {
+        let bytes = hex::decode(tx_hash).ok().filter(|bytes| bytes.len() == 32);
+        let Some(bytes) = bytes else {
+            bail!("tx hash must be 64 hex characters, got '{}'", tx_hash);
+        };
+        let mut hash = [0; 32];
+        hash.copy_from_slice(&bytes);
+
+        Ok(Self::new(hash, index))
+    }
+
+    /// Transaction hash as lowercase hex, as Blockfrost takes it
+    pub fn tx_hash_hex(&self) -> String {
+        hex::encode(self.tx_hash)
+    }
+}
+
+impl FromStr for UtxoRef {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> anyhow::Result<Self> {
+        let [tx_hash, index] = value.split('#').collect::<Vec<_>>()[..] else {
+            bail!("expected TxHash#TxIx, got '{}'", value);
+        };
+        let index = index
+            .parse()
+            .with_context(|| format!("output index must be a number, got '{}'", index))?;
+
+        Self::from_hex(tx_hash, index)
+    }
+}
+
+impl TryFrom<String> for UtxoRef {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> anyhow::Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<UtxoRef> for String {
+    fn from(utxo_ref: UtxoRef) -> Self {
+        utxo_ref.to_string()
+    }
+}
+
+impl fmt::Display for UtxoRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}#{}", self.tx_hash_hex(), self.index)
+    }
+}
+
 /// On-chain tracking datum structure
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TrackingDatum {
     pub carrier: String,
-    pub tracking_number: String,
+    pub tracking_number: TrackingNumber,
     pub outbox_address: Address,
+    /// Unix timestamp after which the shipment is closed as NOT_DELIVERED unless the carrier says otherwise
+    pub deadline: Option<u64>,
+}
+
+/// `TrackingDatum` as JSON, with the outbox address in bech32
+#[derive(Serialize, Deserialize)]
+struct TrackingDatumJson {
+    carrier: String,
+    tracking_number: TrackingNumber,
+    outbox_address: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    deadline: Option<u64>,
+}
+
+impl Serialize for TrackingDatum {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let outbox_address = self.outbox_address.to_bech32().map_err(|e| {
+            serde::ser::Error::custom(format!("outbox address {} has no bech32 form: {}", self.outbox_address.to_hex(), e))
+        })?;
+        TrackingDatumJson {
+            carrier: self.carrier.clone(),
+            tracking_number: self.tracking_number.clone(),
+            outbox_address,
+            deadline: self.deadline,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for TrackingDatum {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let json = TrackingDatumJson::deserialize(deserializer)?;
+        let outbox_address = Address::from_bech32(&json.outbox_address).map_err(|e| {
+            serde::de::Error::custom(format!("outbox_address '{}' is not a bech32 address: {}", json.outbox_address, e))
+        })?;
+        Ok(Self { carrier: json.carrier, tracking_number: json.tracking_number, outbox_address, deadline: json.deadline })
+    }
+}
+
+/// On-chain shipment datum written to the outbox by a close-shipment transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShipmentDatum {
+    pub carrier: String,
+    pub tracking_number: TrackingNumber,
+    pub status: String,
+    pub timestamp: u64,
+    /// Hex key hash of the oracle key that signed the closure
+    pub oracle_pkh: String,
+}
+
+/// Tracking number field of a tracking datum
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrackingNumber {
+    Plain(String),
+    /// Privacy mode: blake2b-256 of `carrier || tracking_number || salt`
+    Hashed([u8; 32]),
+}
+
+impl TrackingNumber {
+    /// Interpret the raw datum field
+    ///
+    /// A 32-byte field that is not printable ASCII is a hash; real tracking
+    /// numbers are short printable strings, so the two never collide in practice.
+    pub fn from_datum_bytes(bytes: &[u8]) -> Option<Self> {
+        if let Ok(hash) = <[u8; 32]>::try_from(bytes)
+            && !hash.iter().all(|byte| (0x20..=0x7e).contains(byte))
+        {
+            return Some(TrackingNumber::Hashed(hash));
+        }
+
+        let value = std::str::from_utf8(bytes).ok()?;
+        (!value.is_empty()).then(|| TrackingNumber::Plain(value.to_string()))
+    }
+
+    /// Raw bytes as stored in the datum
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            TrackingNumber::Plain(value) => value.as_bytes(),
+            TrackingNumber::Hashed(hash) => hash,
+        }
+    }
+
+    pub fn as_plain(&self) -> Option<&str> {
+        match self {
+            TrackingNumber::Plain(value) => Some(value),
+            TrackingNumber::Hashed(_) => None,
+        }
+    }
+}
+
+/// Plain tracking numbers as-is, hashes as hex
+impl fmt::Display for TrackingNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrackingNumber::Plain(value) => f.write_str(value),
+            TrackingNumber::Hashed(hash) => f.write_str(&hex::encode(hash)),
+        }
+    }
+}
+
+/// Serializes as its `Display` form
+impl Serialize for TrackingNumber {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// 64 hex characters read back as a privacy-mode hash, anything else as a plain tracking number
+impl<'de> Deserialize<'de> for TrackingNumber {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        if value.is_empty() {
+            return Err(serde::de::Error::custom("tracking number cannot be empty"));
+        }
+
+        let hash = hex::decode(&value).ok().and_then(|bytes| <[u8; 32]>::try_from(bytes).ok());
+        Ok(match hash {
+            Some(hash) => TrackingNumber::Hashed(hash),
+            None => TrackingNumber::Plain(value),
+        })
+    }
 }
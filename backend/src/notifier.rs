@@ -0,0 +1,965 @@
+use anyhow::{Context, Result, anyhow};
+use hmac::{Hmac, Mac};
+use reqwest::Client as HttpClient;
+use serde::Serialize;
+use serde_json::{Value, json};
+use sha2::Sha256;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::warn;
+
+use crate::config::{Config, Network};
+use crate::metrics::Metrics;
+use crate::redact::Secret;
+use crate::timestamp_source::TimestampSource;
+
+/// Header carrying the hex-encoded HMAC-SHA256 of the request body
+pub const SIGNATURE_HEADER: &str = "X-Oracle-Signature";
+
+/// Chat notifiers collapse a run into one summary message above this many events
+pub const SUMMARY_THRESHOLD: usize = 5;
+
+const MAX_DELIVERY_RETRIES: u32 = 3;
+const DEFAULT_RETRY_BACKOFF: Duration = Duration::from_millis(500);
+const SUMMARY_MAX_ENTRIES: usize = 10;
+
+const BUDGET_EXCEEDED_NOTE: &str =
+    "Only shipments in transit for a long time are polled until the month rolls over.";
+
+/// Placeholders a `WebhookTemplate` may use
+pub const TEMPLATE_PLACEHOLDERS: [&str; 14] = [
+    "event", "title", "summary", "carrier", "tracking_number", "status", "tx_hash", "error", "reason", "utxo_ref",
+    "tenant", "run_id", "closed", "failed",
+];
+
+/// How notifiers group the events of a run (`NOTIFY_MODE`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NotifyMode {
+    /// A message per event; chat notifiers still collapse runs of more than `SUMMARY_THRESHOLD` events
+    #[default]
+    PerShipment,
+    /// A single message per run holding every event, sent once the run is over
+    PerRun,
+}
+
+impl FromStr for NotifyMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "per_shipment" => Ok(NotifyMode::PerShipment),
+            "per_run" => Ok(NotifyMode::PerRun),
+            other => Err(anyhow!("expected per_shipment or per_run, got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for NotifyMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            NotifyMode::PerShipment => "per_shipment",
+            NotifyMode::PerRun => "per_run",
+        })
+    }
+}
+
+/// Shipment outcome emitted by `DataFetcher` after each close attempt or skip, or a spent Shippo budget
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum OracleEvent {
+    ShipmentClosed {
+        utxo_ref: String,
+        carrier: String,
+        tracking_number: String,
+        status: String,
+        /// Unix timestamp written in the shipment datum
+        timestamp: u64,
+        /// `TIMESTAMP_SOURCE` that produced `timestamp`
+        timestamp_source: TimestampSource,
+        /// When the oracle closed the shipment; equals `timestamp` for oracle timestamps
+        oracle_timestamp: u64,
+        tx_hash: String,
+        /// Tenant that produced the event in a multi-tenant deployment
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tenant: Option<String>,
+        /// ID of the `DataFetcher::run` that produced the event
+        #[serde(skip_serializing_if = "Option::is_none")]
+        run_id: Option<String>,
+    },
+    ShipmentFailed {
+        utxo_ref: String,
+        carrier: String,
+        tracking_number: String,
+        status: String,
+        timestamp: u64,
+        error: String,
+        /// Tenant that produced the event in a multi-tenant deployment
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tenant: Option<String>,
+        /// ID of the `DataFetcher::run` that produced the event
+        #[serde(skip_serializing_if = "Option::is_none")]
+        run_id: Option<String>,
+    },
+    /// Not closed, without an error: the outbox policy refuses the datum's outbox
+    /// address, or the tracking UTxO was spent before the oracle signed
+    ShipmentSkipped {
+        utxo_ref: String,
+        carrier: String,
+        tracking_number: String,
+        outbox_address: String,
+        reason: String,
+        /// Tenant that produced the event in a multi-tenant deployment
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tenant: Option<String>,
+        /// ID of the `DataFetcher::run` that produced the event
+        #[serde(skip_serializing_if = "Option::is_none")]
+        run_id: Option<String>,
+    },
+    /// The month's tracking calls reached `SHIPPO_MONTHLY_BUDGET`; sent once per billing period
+    BudgetExceeded {
+        /// Billing month, `YYYY-MM`
+        period: String,
+        calls: u64,
+        budget: u64,
+        /// Tenant that produced the event in a multi-tenant deployment
+        #[serde(skip_serializing_if = "Option::is_none")]
+        tenant: Option<String>,
+        /// ID of the `DataFetcher::run` that produced the event
+        #[serde(skip_serializing_if = "Option::is_none")]
+        run_id: Option<String>,
+    },
+}
+
+impl OracleEvent {
+    /// Carrier of the shipment; `None` for events about no shipment in particular
+    pub fn carrier(&self) -> Option<&str> {
+        match self {
+            OracleEvent::ShipmentClosed { carrier, .. }
+            | OracleEvent::ShipmentFailed { carrier, .. }
+            | OracleEvent::ShipmentSkipped { carrier, .. } => Some(carrier),
+            OracleEvent::BudgetExceeded { .. } => None,
+        }
+    }
+
+    /// Tracking number of the shipment; `None` for events about no shipment in particular
+    pub fn tracking_number(&self) -> Option<&str> {
+        match self {
+            OracleEvent::ShipmentClosed { tracking_number, .. }
+            | OracleEvent::ShipmentFailed { tracking_number, .. }
+            | OracleEvent::ShipmentSkipped { tracking_number, .. } => Some(tracking_number),
+            OracleEvent::BudgetExceeded { .. } => None,
+        }
+    }
+
+    pub fn tenant(&self) -> Option<&str> {
+        match self {
+            OracleEvent::ShipmentClosed { tenant, .. }
+            | OracleEvent::ShipmentFailed { tenant, .. }
+            | OracleEvent::ShipmentSkipped { tenant, .. }
+            | OracleEvent::BudgetExceeded { tenant, .. } => tenant.as_deref(),
+        }
+    }
+
+    pub fn run_id(&self) -> Option<&str> {
+        match self {
+            OracleEvent::ShipmentClosed { run_id, .. }
+            | OracleEvent::ShipmentFailed { run_id, .. }
+            | OracleEvent::ShipmentSkipped { run_id, .. }
+            | OracleEvent::BudgetExceeded { run_id, .. } => run_id.as_deref(),
+        }
+    }
+
+    pub fn is_failure(&self) -> bool {
+        matches!(self, OracleEvent::ShipmentFailed { .. })
+    }
+
+    /// The `event` tag of the serialized event
+    pub fn name(&self) -> &'static str {
+        match self {
+            OracleEvent::ShipmentClosed { .. } => "shipment_closed",
+            OracleEvent::ShipmentFailed { .. } => "shipment_failed",
+            OracleEvent::ShipmentSkipped { .. } => "shipment_skipped",
+            OracleEvent::BudgetExceeded { .. } => "budget_exceeded",
+        }
+    }
+}
+
+#[async_trait::async_trait]
+pub trait Notifier: Send + Sync {
+    async fn notify(&self, event: &OracleEvent) -> Result<()>;
+
+    /// Called by `DataFetcher` once every event of a run has been notified
+    async fn flush(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Builds the notifiers enabled in the configuration, if any
+pub fn from_config(config: &Config, metrics: &Metrics) -> Result<Option<Arc<dyn Notifier>>> {
+    let mut notifiers: Vec<Arc<dyn Notifier>> = Vec::new();
+
+    if let Some(url) = &config.notify_webhook_url {
+        let mut webhook = WebhookNotifier::new(url.clone(), config.notify_webhook_secret.as_ref().map(|secret| secret.expose().clone()))?
+            .with_mode(config.notify_mode)
+            .with_metrics(metrics.clone());
+        if let Some(template) = &config.notify_webhook_template {
+            webhook = webhook.with_template(WebhookTemplate::parse(template).context("Invalid NOTIFY_WEBHOOK_TEMPLATE")?);
+        }
+        notifiers.push(Arc::new(webhook));
+    }
+
+    if let Some(url) = &config.notify_slack_webhook {
+        notifiers.push(Arc::new(SlackNotifier::new(url.expose().clone(), config.cardano_network)?.with_mode(config.notify_mode)));
+    }
+
+    if let Some(url) = &config.notify_discord_webhook {
+        notifiers.push(Arc::new(DiscordNotifier::new(url.expose().clone(), config.cardano_network)?.with_mode(config.notify_mode)));
+    }
+
+    #[cfg(feature = "sentry")]
+    if config.sentry_dsn.is_some() {
+        notifiers.push(Arc::new(crate::error_reporting::SentryNotifier));
+    }
+
+    Ok(match notifiers.len() {
+        0 => None,
+        1 => notifiers.pop(),
+        _ => Some(Arc::new(CompositeNotifier::new(notifiers))),
+    })
+}
+
+/// Fans every event out to several notifiers
+pub struct CompositeNotifier {
+    notifiers: Vec<Arc<dyn Notifier>>,
+}
+
+impl CompositeNotifier {
+    pub fn new(notifiers: Vec<Arc<dyn Notifier>>) -> Self {
+        Self { notifiers }
+    }
+}
+
+#[async_trait::async_trait]
+impl Notifier for CompositeNotifier {
+    async fn notify(&self, event: &OracleEvent) -> Result<()> {
+        let results = futures::future::join_all(
+            self.notifiers.iter().map(|notifier| notifier.notify(event))
+        ).await;
+
+        collect_errors(results)
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let results = futures::future::join_all(
+            self.notifiers.iter().map(|notifier| notifier.flush())
+        ).await;
+
+        collect_errors(results)
+    }
+}
+
+fn collect_errors(results: Vec<Result<()>>) -> Result<()> {
+    let errors: Vec<String> = results
+        .into_iter()
+        .filter_map(|result| result.err())
+        .map(|e| e.to_string())
+        .collect();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(errors.join("; ")))
+    }
+}
+
+/// Publishes every event on an in-process broadcast channel
+///
+/// Events are dropped when nobody is subscribed; slow subscribers miss the
+/// oldest events once `capacity` are queued.
+pub struct BroadcastNotifier {
+    sender: tokio::sync::broadcast::Sender<OracleEvent>,
+}
+
+impl BroadcastNotifier {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = tokio::sync::broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    pub fn subscribe(&self) -> tokio::sync::broadcast::Receiver<OracleEvent> {
+        self.sender.subscribe()
+    }
+}
+
+#[async_trait::async_trait]
+impl Notifier for BroadcastNotifier {
+    async fn notify(&self, event: &OracleEvent) -> Result<()> {
+        // Sending only fails when there are no subscribers
+        let _ = self.sender.send(event.clone());
+        Ok(())
+    }
+}
+
+/// Posts every event as JSON to a merchant-facing webhook URL
+///
+/// Under `NotifyMode::PerRun` the events of a run are buffered and posted as one
+/// `run_summary` payload (see `run_payload`) when the run is flushed.
+pub struct WebhookNotifier {
+    url: Secret<String>,
+    secret: Option<Secret<String>>,
+    http_client: HttpClient,
+    retry_backoff: Duration,
+    metrics: Metrics,
+    mode: NotifyMode,
+    template: Option<WebhookTemplate>,
+    pending: Mutex<Vec<OracleEvent>>,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String, secret: Option<String>) -> Result<Self> {
+        let http_client = HttpClient::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        Ok(Self {
+            url: Secret::new(url),
+            secret: secret.map(Secret::new),
+            http_client,
+            retry_backoff: DEFAULT_RETRY_BACKOFF,
+            metrics: Metrics::new(),
+            mode: NotifyMode::PerShipment,
+            template: None,
+            pending: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Post each event as it happens, or the whole run at once
+    pub fn with_mode(mut self, mode: NotifyMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Post bodies rendered from `template` instead of the event JSON
+    pub fn with_template(mut self, template: WebhookTemplate) -> Self {
+        self.template = Some(template);
+        self
+    }
+
+    /// Overrides the base delay between delivery retries (doubled on each attempt)
+    pub fn with_retry_backoff(mut self, retry_backoff: Duration) -> Self {
+        self.retry_backoff = retry_backoff;
+        self
+    }
+
+    /// Count the events that could not be delivered after all retries in `metrics`
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    async fn deliver(&self, body: &[u8]) -> Result<()> {
+        let mut request = self.http_client
+            .post(self.url.expose())
+            .header("Content-Type", "application/json")
+            .body(body.to_vec());
+
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, sign_payload(secret.expose(), body)?);
+        }
+
+        let response = request
+            .send()
+            .await
+            .context("Failed to send webhook notification")?;
+
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "Webhook notification rejected (status {})",
+                response.status()
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Deliver `body`, retrying with exponential backoff
+    async fn post(&self, body: &[u8]) -> Result<()> {
+        let mut attempt = 0;
+        loop {
+            match self.deliver(body).await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt < MAX_DELIVERY_RETRIES => {
+                    tokio::time::sleep(self.retry_backoff * 2u32.pow(attempt)).await;
+                    attempt += 1;
+                    warn!(attempt, max_retries = MAX_DELIVERY_RETRIES, error = %format!("{:#}", e), "⚠️  Retrying webhook notification");
+                }
+                Err(e) => {
+                    self.metrics.notification_delivery_failed("webhook");
+                    return Err(e);
+                }
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Notifier for WebhookNotifier {
+    async fn notify(&self, event: &OracleEvent) -> Result<()> {
+        if self.mode == NotifyMode::PerRun {
+            return push_pending(&self.pending, event);
+        }
+
+        let body = match &self.template {
+            Some(template) => template.render_event(event).into_bytes(),
+            None => serde_json::to_vec(event).context("Failed to serialize notification payload")?,
+        };
+
+        self.post(&body).await
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let events = take_pending(&self.pending)?;
+        if events.is_empty() {
+            return Ok(());
+        }
+
+        let body = match &self.template {
+            Some(template) => template.render_run(&events).into_bytes(),
+            None => serde_json::to_vec(&run_payload(&events)).context("Failed to serialize notification payload")?,
+        };
+
+        self.post(&body).await
+    }
+}
+
+/// Webhook payload of a whole run under `NotifyMode::PerRun`
+pub fn run_payload(events: &[OracleEvent]) -> Value {
+    let (closed, failed) = count_outcomes(events);
+    let mut payload = json!({
+        "event": "run_summary",
+        "closed": closed,
+        "failed": failed,
+        "events": events,
+    });
+    if let Some(tenant) = events.first().and_then(OracleEvent::tenant) {
+        payload["tenant"] = json!(tenant);
+    }
+    if let Some(run_id) = events.first().and_then(OracleEvent::run_id) {
+        payload["run_id"] = json!(run_id);
+    }
+
+    payload
+}
+
+/// Webhook body with `{{name}}` placeholders, from `NOTIFY_WEBHOOK_TEMPLATE`
+///
+/// Values are JSON-escaped without quotes, so placeholders belong inside string
+/// literals, e.g. `{"text": "{{title}}: {{summary}}"}`. A placeholder the event
+/// has no value for renders empty.
+#[derive(Debug, Clone)]
+pub struct WebhookTemplate {
+    template: String,
+}
+
+impl WebhookTemplate {
+    /// Checks every placeholder is one of `TEMPLATE_PLACEHOLDERS` and the template renders valid JSON
+    pub fn parse(template: &str) -> Result<Self> {
+        let mut unknown = Vec::new();
+        substitute(template, |name| {
+            if !TEMPLATE_PLACEHOLDERS.contains(&name) {
+                unknown.push(format!("{{{{{}}}}}", name));
+            }
+            String::new()
+        });
+        if !unknown.is_empty() {
+            return Err(anyhow!(
+                "unknown placeholder {}, expected one of {}",
+                unknown.join(", "),
+                TEMPLATE_PLACEHOLDERS.join(", ")
+            ));
+        }
+
+        let template = Self { template: template.to_string() };
+        let sample = OracleEvent::ShipmentFailed {
+            utxo_ref: "tx#0".to_string(),
+            carrier: "usps".to_string(),
+            tracking_number: "\"quoted\"\nnumber".to_string(),
+            status: "DELIVERED".to_string(),
+            timestamp: 0,
+            error: "error".to_string(),
+            tenant: None,
+            run_id: None,
+        };
+        for rendered in [template.render_event(&sample), template.render_run(&[sample])] {
+            serde_json::from_str::<Value>(&rendered).context("template does not render valid JSON")?;
+        }
+
+        Ok(template)
+    }
+
+    pub fn render_event(&self, event: &OracleEvent) -> String {
+        let mut values = vec![
+            ("event", event.name().to_string()),
+            ("title", event_title(event).to_string()),
+            ("summary", event_summary_line(event)),
+            ("carrier", event.carrier().unwrap_or_default().to_string()),
+            ("tracking_number", event.tracking_number().unwrap_or_default().to_string()),
+        ];
+        match event {
+            OracleEvent::ShipmentClosed { utxo_ref, status, tx_hash, .. } => {
+                values.extend([("utxo_ref", utxo_ref.clone()), ("status", status.clone()), ("tx_hash", tx_hash.clone())]);
+            }
+            OracleEvent::ShipmentFailed { utxo_ref, status, error, .. } => {
+                values.extend([("utxo_ref", utxo_ref.clone()), ("status", status.clone()), ("error", error.clone())]);
+            }
+            OracleEvent::ShipmentSkipped { utxo_ref, reason, .. } => {
+                values.extend([("utxo_ref", utxo_ref.clone()), ("reason", reason.clone())]);
+            }
+            OracleEvent::BudgetExceeded { .. } => {}
+        }
+        values.extend([
+            ("tenant", event.tenant().unwrap_or_default().to_string()),
+            ("run_id", event.run_id().unwrap_or_default().to_string()),
+        ]);
+
+        self.render(&values)
+    }
+
+    /// Render the events of a run; `summary` lists them one per line
+    pub fn render_run(&self, events: &[OracleEvent]) -> String {
+        let (closed, failed) = count_outcomes(events);
+        let mut lines = vec![format!("Oracle run: {} closed, {} failed", closed, failed)];
+        lines.extend(events.iter().take(SUMMARY_MAX_ENTRIES).map(|event| format!("• {}", event_summary_line(event))));
+        if events.len() > SUMMARY_MAX_ENTRIES {
+            lines.push(format!("…and {} more", events.len() - SUMMARY_MAX_ENTRIES));
+        }
+
+        let first = events.first();
+        self.render(&[
+            ("event", "run_summary".to_string()),
+            ("title", summary_title(events)),
+            ("summary", lines.join("\n")),
+            ("closed", closed.to_string()),
+            ("failed", failed.to_string()),
+            ("tenant", first.and_then(OracleEvent::tenant).unwrap_or_default().to_string()),
+            ("run_id", first.and_then(OracleEvent::run_id).unwrap_or_default().to_string()),
+        ])
+    }
+
+    fn render(&self, values: &[(&str, String)]) -> String {
+        substitute(&self.template, |name| {
+            let value = values.iter().find(|(key, _)| *key == name).map(|(_, value)| value.as_str()).unwrap_or_default();
+            let quoted = serde_json::Value::String(value.to_string()).to_string();
+            quoted[1..quoted.len() - 1].to_string()
+        })
+    }
+}
+
+/// `template` with every `{{name}}` replaced by `value(name)`
+fn substitute(template: &str, mut value: impl FnMut(&str) -> String) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(length) = rest[start..].find("}}") else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        rendered.push_str(&value(rest[start + 2..start + length].trim()));
+        rest = &rest[start + length + 2..];
+    }
+    rendered.push_str(rest);
+
+    rendered
+}
+
+/// Hex-encoded HMAC-SHA256 of `body` keyed with the shared webhook secret
+pub fn sign_payload(secret: &str, body: &[u8]) -> Result<String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .map_err(|e| anyhow!("Invalid webhook secret: {}", e))?;
+    mac.update(body);
+
+    Ok(hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Posts shipment outcomes to a Slack incoming webhook using Block Kit
+pub struct SlackNotifier {
+    webhook_url: Secret<String>,
+    network: Option<Network>,
+    http_client: HttpClient,
+    pending: Mutex<Vec<OracleEvent>>,
+    mode: NotifyMode,
+}
+
+impl SlackNotifier {
+    pub fn new(webhook_url: String, network: Option<Network>) -> Result<Self> {
+        Ok(Self {
+            webhook_url: Secret::new(webhook_url),
+            network,
+            http_client: chat_http_client()?,
+            pending: Mutex::new(Vec::new()),
+            mode: NotifyMode::PerShipment,
+        })
+    }
+
+    /// Under `NotifyMode::PerRun`, summarize every run in one message whatever its size
+    pub fn with_mode(mut self, mode: NotifyMode) -> Self {
+        self.mode = mode;
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl Notifier for SlackNotifier {
+    async fn notify(&self, event: &OracleEvent) -> Result<()> {
+        push_pending(&self.pending, event)
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let events = take_pending(&self.pending)?;
+        for payload in batch_payloads(&events, self.network, self.mode, slack_payload, slack_summary_payload) {
+            post_json(&self.http_client, self.webhook_url.expose(), &payload).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Posts shipment outcomes to a Discord webhook as embeds
+pub struct DiscordNotifier {
+    webhook_url: Secret<String>,
+    network: Option<Network>,
+    http_client: HttpClient,
+    pending: Mutex<Vec<OracleEvent>>,
+    mode: NotifyMode,
+}
+
+impl DiscordNotifier {
+    pub fn new(webhook_url: String, network: Option<Network>) -> Result<Self> {
+        Ok(Self {
+            webhook_url: Secret::new(webhook_url),
+            network,
+            http_client: chat_http_client()?,
+            pending: Mutex::new(Vec::new()),
+            mode: NotifyMode::PerShipment,
+        })
+    }
+
+    /// Under `NotifyMode::PerRun`, summarize every run in one message whatever its size
+    pub fn with_mode(mut self, mode: NotifyMode) -> Self {
+        self.mode = mode;
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl Notifier for DiscordNotifier {
+    async fn notify(&self, event: &OracleEvent) -> Result<()> {
+        push_pending(&self.pending, event)
+    }
+
+    async fn flush(&self) -> Result<()> {
+        let events = take_pending(&self.pending)?;
+        for payload in batch_payloads(&events, self.network, self.mode, discord_payload, discord_summary_payload) {
+            post_json(&self.http_client, self.webhook_url.expose(), &payload).await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Slack Block Kit message for a single shipment event
+pub fn slack_payload(event: &OracleEvent, network: Option<Network>) -> Value {
+    let mut blocks = vec![
+        json!({
+            "type": "header",
+            "text": { "type": "plain_text", "text": event_title(event) },
+        }),
+        json!({
+            "type": "section",
+            "fields": event_fields(event)
+                .into_iter()
+                .map(|(name, value)| json!({ "type": "mrkdwn", "text": format!("*{}*\n{}", name, value) }))
+                .collect::<Vec<_>>(),
+        }),
+    ];
+
+    match event {
+        OracleEvent::ShipmentClosed { tx_hash, .. } => {
+            if let Some(link) = transaction_link(network, tx_hash) {
+                blocks.push(json!({
+                    "type": "section",
+                    "text": { "type": "mrkdwn", "text": format!("<{}|View transaction>", link) },
+                }));
+            }
+        }
+        OracleEvent::ShipmentFailed { error, .. } => {
+            blocks.push(json!({
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": format!("*Error*\n```{}```", error) },
+            }));
+        }
+        OracleEvent::ShipmentSkipped { reason, .. } => {
+            blocks.push(json!({
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": format!("*Reason*\n{}", reason) },
+            }));
+        }
+        OracleEvent::BudgetExceeded { .. } => {
+            blocks.push(json!({
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": BUDGET_EXCEEDED_NOTE },
+            }));
+        }
+    }
+
+    json!({
+        "text": event_summary_line(event),
+        "blocks": blocks,
+    })
+}
+
+/// Slack message summarizing every event of a run
+pub fn slack_summary_payload(events: &[OracleEvent], _network: Option<Network>) -> Value {
+    let (closed, failed) = count_outcomes(events);
+    let mut lines: Vec<String> = events
+        .iter()
+        .take(SUMMARY_MAX_ENTRIES)
+        .map(|event| format!("• {}", event_summary_line(event)))
+        .collect();
+    if events.len() > SUMMARY_MAX_ENTRIES {
+        lines.push(format!("…and {} more", events.len() - SUMMARY_MAX_ENTRIES));
+    }
+
+    json!({
+        "text": format!("Oracle run: {} closed, {} failed", closed, failed),
+        "blocks": [
+            {
+                "type": "header",
+                "text": { "type": "plain_text", "text": summary_title(events) },
+            },
+            {
+                "type": "section",
+                "fields": [
+                    { "type": "mrkdwn", "text": format!("*Closed*\n{}", closed) },
+                    { "type": "mrkdwn", "text": format!("*Failed*\n{}", failed) },
+                ],
+            },
+            {
+                "type": "section",
+                "text": { "type": "mrkdwn", "text": lines.join("\n") },
+            },
+        ],
+    })
+}
+
+/// Discord embed for a single shipment event
+pub fn discord_payload(event: &OracleEvent, network: Option<Network>) -> Value {
+    let mut fields: Vec<Value> = event_fields(event)
+        .into_iter()
+        .map(|(name, value)| json!({ "name": name, "value": value, "inline": true }))
+        .collect();
+
+    let mut embed = json!({
+        "title": event_title(event),
+        "color": event_color(event),
+    });
+
+    match event {
+        OracleEvent::ShipmentClosed { tx_hash, .. } => {
+            if let Some(link) = transaction_link(network, tx_hash) {
+                embed["url"] = json!(link);
+            }
+        }
+        OracleEvent::ShipmentFailed { error, .. } => {
+            fields.push(json!({ "name": "Error", "value": error, "inline": false }));
+        }
+        OracleEvent::ShipmentSkipped { reason, .. } => {
+            fields.push(json!({ "name": "Reason", "value": reason, "inline": false }));
+        }
+        OracleEvent::BudgetExceeded { .. } => {
+            embed["description"] = json!(BUDGET_EXCEEDED_NOTE);
+        }
+    }
+    embed["fields"] = json!(fields);
+
+    json!({ "embeds": [embed] })
+}
+
+/// Discord embed summarizing every event of a run
+pub fn discord_summary_payload(events: &[OracleEvent], _network: Option<Network>) -> Value {
+    let (closed, failed) = count_outcomes(events);
+    let mut lines: Vec<String> = events
+        .iter()
+        .take(SUMMARY_MAX_ENTRIES)
+        .map(|event| format!("• {}", event_summary_line(event)))
+        .collect();
+    if events.len() > SUMMARY_MAX_ENTRIES {
+        lines.push(format!("…and {} more", events.len() - SUMMARY_MAX_ENTRIES));
+    }
+
+    json!({
+        "embeds": [{
+            "title": summary_title(events),
+            "color": if failed > 0 { 0xe01e5a } else { 0x2eb67d },
+            "description": lines.join("\n"),
+            "fields": [
+                { "name": "Closed", "value": closed.to_string(), "inline": true },
+                { "name": "Failed", "value": failed.to_string(), "inline": true },
+            ],
+        }],
+    })
+}
+
+fn event_title(event: &OracleEvent) -> &'static str {
+    match event {
+        OracleEvent::ShipmentClosed { .. } => "✅ Shipment closed",
+        OracleEvent::ShipmentFailed { .. } => "❌ Shipment close failed",
+        OracleEvent::ShipmentSkipped { .. } => "🚫 Shipment skipped",
+        OracleEvent::BudgetExceeded { .. } => "💸 Shippo budget exceeded",
+    }
+}
+
+/// Discord embed color: green when closed, red when failed, amber when skipped or over budget
+fn event_color(event: &OracleEvent) -> u32 {
+    match event {
+        OracleEvent::ShipmentClosed { .. } => 0x2eb67d,
+        OracleEvent::ShipmentFailed { .. } => 0xe01e5a,
+        OracleEvent::ShipmentSkipped { .. } | OracleEvent::BudgetExceeded { .. } => 0xecb22e,
+    }
+}
+
+fn event_fields(event: &OracleEvent) -> Vec<(&'static str, String)> {
+    let (utxo_ref, detail) = match event {
+        OracleEvent::ShipmentClosed { utxo_ref, status, .. }
+        | OracleEvent::ShipmentFailed { utxo_ref, status, .. } => (utxo_ref, ("Status", status.clone())),
+        OracleEvent::ShipmentSkipped { utxo_ref, outbox_address, .. } => {
+            (utxo_ref, ("Outbox", format!("`{}`", outbox_address)))
+        }
+        OracleEvent::BudgetExceeded { period, calls, budget, .. } => {
+            return with_run_fields(
+                event,
+                vec![("Period", period.clone()), ("Calls", format!("{} of {}", calls, budget))],
+            );
+        }
+    };
+
+    let fields = vec![
+        ("Carrier", event.carrier().unwrap_or_default().to_string()),
+        ("Tracking number", event.tracking_number().unwrap_or_default().to_string()),
+        detail,
+        ("UTxO", format!("`{}`", utxo_ref)),
+    ];
+
+    with_run_fields(event, fields)
+}
+
+/// `fields` led by the event's tenant and followed by its run, when known
+fn with_run_fields(event: &OracleEvent, mut fields: Vec<(&'static str, String)>) -> Vec<(&'static str, String)> {
+    if let Some(tenant) = event.tenant() {
+        fields.insert(0, ("Tenant", tenant.to_string()));
+    }
+    if let Some(run_id) = event.run_id() {
+        fields.push(("Run", format!("`{}`", run_id)));
+    }
+
+    fields
+}
+
+/// "Oracle run summary", suffixed with the tenant and ID of the run when known
+fn summary_title(events: &[OracleEvent]) -> String {
+    let first = events.first();
+    let details: Vec<&str> = [first.and_then(OracleEvent::tenant), first.and_then(OracleEvent::run_id)]
+        .into_iter()
+        .flatten()
+        .collect();
+
+    if details.is_empty() {
+        "Oracle run summary".to_string()
+    } else {
+        format!("Oracle run summary ({})", details.join(", "))
+    }
+}
+
+fn event_summary_line(event: &OracleEvent) -> String {
+    let shipment = format!("{} {}", event.carrier().unwrap_or_default(), event.tracking_number().unwrap_or_default());
+    match event {
+        OracleEvent::ShipmentClosed { status, .. } => format!("{} closed as {}", shipment, status),
+        OracleEvent::ShipmentFailed { error, .. } => format!("{} failed: {}", shipment, error),
+        OracleEvent::ShipmentSkipped { reason, .. } => format!("{} skipped: {}", shipment, reason),
+        OracleEvent::BudgetExceeded { period, calls, budget, .. } => {
+            format!("Shippo budget exceeded: {} of {} tracking calls in {}", calls, budget, period)
+        }
+    }
+}
+
+/// Closed and failed events; skipped shipments are neither
+fn count_outcomes(events: &[OracleEvent]) -> (usize, usize) {
+    let closed = events.iter().filter(|event| matches!(event, OracleEvent::ShipmentClosed { .. })).count();
+    let failed = events.iter().filter(|event| event.is_failure()).count();
+    (closed, failed)
+}
+
+fn transaction_link(network: Option<Network>, tx_hash: &str) -> Option<String> {
+    network.map(|network| network.transaction_url(tx_hash))
+}
+
+/// Renders the buffered events of a run, collapsing large batches, or any batch under `NotifyMode::PerRun`, into one summary
+fn batch_payloads(
+    events: &[OracleEvent],
+    network: Option<Network>,
+    mode: NotifyMode,
+    single: fn(&OracleEvent, Option<Network>) -> Value,
+    summary: fn(&[OracleEvent], Option<Network>) -> Value,
+) -> Vec<Value> {
+    if events.is_empty() {
+        return Vec::new();
+    }
+    if mode == NotifyMode::PerRun || events.len() > SUMMARY_THRESHOLD {
+        vec![summary(events, network)]
+    } else {
+        events.iter().map(|event| single(event, network)).collect()
+    }
+}
+
+fn push_pending(pending: &Mutex<Vec<OracleEvent>>, event: &OracleEvent) -> Result<()> {
+    pending
+        .lock()
+        .map_err(|_| anyhow!("notification buffer lock poisoned"))?
+        .push(event.clone());
+    Ok(())
+}
+
+fn take_pending(pending: &Mutex<Vec<OracleEvent>>) -> Result<Vec<OracleEvent>> {
+    let mut pending = pending
+        .lock()
+        .map_err(|_| anyhow!("notification buffer lock poisoned"))?;
+    Ok(std::mem::take(&mut *pending))
+}
+
+fn chat_http_client() -> Result<HttpClient> {
+    HttpClient::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .context("Failed to create HTTP client")
+}
+
+async fn post_json(http_client: &HttpClient, url: &str, payload: &Value) -> Result<()> {
+    let response = http_client
+        .post(url)
+        .json(payload)
+        .send()
+        .await
+        .context("Failed to send chat notification")?;
+
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "Chat notification rejected (status {})",
+            response.status()
+        ));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,341 @@
+use anyhow::{Context, Result, bail};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+use crate::blockchain::{CardanoClient, ValidatorScriptCheck};
+use crate::clock::Clock;
+use crate::config::Config;
+use crate::decisions::{self, DecisionPage, DecisionQuery};
+use crate::fetcher::{self, DataFetcher, LastRun, ManualClose};
+use crate::indexer;
+use crate::metrics::Metrics;
+use crate::models::{DerivedStatus, TrackingUTxO};
+use crate::notifier::{self, BroadcastNotifier, CompositeNotifier, Notifier, OracleEvent};
+use crate::run_report::RunReport;
+use crate::shipment::ShipmentClient;
+use crate::state::{self, StateStore};
+use crate::submitter::TxSubmitter;
+use crate::tracking_provider;
+
+/// Events buffered per subscriber before the oldest are dropped
+const EVENT_CAPACITY: usize = 256;
+
+/// One configured oracle pipeline, usable from another service
+///
+/// Every part is built from the [`Config`] unless overridden on the builder.
+///
+/// ```no_run
+/// use shipping_oracle::config::Config;
+/// use shipping_oracle::oracle::Oracle;
+///
+/// # async fn example() -> anyhow::Result<()> {
+/// let oracle = Oracle::builder().config(Config::from_env()?).build()?;
+///
+/// let mut events = oracle.subscribe();
+/// let report = oracle.run_once().await?;
+/// println!("{} shipments, {} closed", report.stats.shipments, report.stats.submitted);
+///
+/// while let Ok(event) = events.try_recv() {
+///     println!("{:?}", event);
+/// }
+/// # Ok(())
+/// # }
+/// ```
+pub struct Oracle {
+    config: Config,
+    data_fetcher: Arc<DataFetcher>,
+    events: Arc<BroadcastNotifier>,
+}
+
+/// Overrides for the parts `Oracle` would otherwise build from its config
+///
+/// ```no_run
+/// use shipping_oracle::config::Config;
+/// use shipping_oracle::oracle::Oracle;
+/// use shipping_oracle::submitter::{SubmitError, TxSubmitter};
+///
+/// struct MySubmitter;
+///
+/// #[async_trait::async_trait]
+/// impl TxSubmitter for MySubmitter {
+///     async fn submit(&self, signed_tx: Vec<u8>) -> Result<String, SubmitError> {
+///         Err(SubmitError::Other(format!("submit {} bytes through my own node", signed_tx.len())))
+///     }
+/// }
+///
+/// # async fn example(config: Config) -> anyhow::Result<()> {
+/// let oracle = Oracle::builder().config(config).submitter(MySubmitter).build()?;
+/// let utxo_ref = "a7a264ac1bef1f0da6312d16fb8f68b18d1a0d5010d8cb6a61790bf02a759301#0";
+/// let closed = oracle.close(utxo_ref, "DELIVERED").await?;
+/// println!("closed in {} for {} lovelace", closed.tx_hash(), closed.fee);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Default)]
+pub struct OracleBuilder {
+    config: Option<Config>,
+    chain: Option<CardanoClient>,
+    tracking: Option<ShipmentClient>,
+    submitter: Option<Box<dyn TxSubmitter>>,
+    notifier: Option<Arc<dyn Notifier>>,
+    state: Option<Arc<dyn StateStore>>,
+    clock: Option<Arc<dyn Clock>>,
+    metrics: Option<Metrics>,
+}
+
+impl OracleBuilder {
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Chain client used to scan tracking UTxOs and build, sign and submit closes
+    pub fn chain(mut self, chain: CardanoClient) -> Self {
+        self.chain = Some(chain);
+        self
+    }
+
+    /// Shippo tracking client; other providers are still built from the config
+    pub fn tracking(mut self, tracking: ShipmentClient) -> Self {
+        self.tracking = Some(tracking);
+        self
+    }
+
+    /// Submit signed transactions through `submitter` instead of Blockfrost
+    pub fn submitter(mut self, submitter: impl TxSubmitter + 'static) -> Self {
+        self.submitter = Some(Box::new(submitter));
+        self
+    }
+
+    /// Replace the notifiers enabled in the config
+    pub fn notifier(mut self, notifier: impl Notifier + 'static) -> Self {
+        self.notifier = Some(Arc::new(notifier));
+        self
+    }
+
+    /// Replace the state database configured by `STATE_DB_PATH`
+    pub fn state(mut self, state: Arc<dyn StateStore>) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    /// Read the time for `SUBMIT_WINDOW` from `clock` instead of the system clock
+    pub fn clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Some(Arc::new(clock));
+        self
+    }
+
+    /// Record into `metrics`, e.g. a registry shared by several oracles, instead of a registry of its own
+    pub fn metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn build(self) -> Result<Oracle> {
+        let config = self.config.context("Oracle::builder() needs a config")?;
+        let metrics = self
+            .metrics
+            .unwrap_or_default()
+            .for_tenant(config.tenant.as_deref())
+            .with_outbox_labels(config.outbox_aliases.clone(), config.metrics_max_outboxes);
+
+        let state = match self.state {
+            Some(state) => Some(state),
+            None => state::from_config(&config)?,
+        };
+
+        let chain = match (self.chain, self.submitter) {
+            (Some(_), Some(_)) => bail!("Set either a chain client or a submitter, not both"),
+            (Some(chain), None) => chain,
+            (None, Some(submitter)) => indexer::apply_scan_mode(CardanoClient::with_submitter(config.clone(), submitter)?, &config, state.as_ref())?,
+            (None, None) => indexer::apply_scan_mode(CardanoClient::new(config.clone())?, &config, state.as_ref())?,
+        }
+        .with_metrics(metrics.clone());
+
+        let shippo = match self.tracking {
+            Some(tracking) => tracking,
+            None => ShipmentClient::new(config.clone())?,
+        }
+        .with_metrics(metrics.clone());
+        let tracking = tracking_provider::from_config(&config, shippo, &metrics)?;
+
+        let notifier = match self.notifier {
+            Some(notifier) => Some(notifier),
+            None => notifier::from_config(&config, &metrics)?,
+        };
+
+        let events = Arc::new(BroadcastNotifier::new(EVENT_CAPACITY));
+        let notifier: Arc<dyn Notifier> = match notifier {
+            Some(notifier) => Arc::new(CompositeNotifier::new(vec![events.clone(), notifier])),
+            None => events.clone(),
+        };
+
+        let mut data_fetcher =
+            fetcher::from_parts(&config, Arc::new(chain), Arc::new(tracking), Some(notifier), state)?;
+        if let Some(clock) = self.clock {
+            data_fetcher = data_fetcher.with_clock(clock);
+        }
+        data_fetcher = data_fetcher.with_metrics(metrics);
+
+        Ok(Oracle {
+            config,
+            data_fetcher: Arc::new(data_fetcher),
+            events,
+        })
+    }
+}
+
+/// Liveness of an oracle pipeline
+#[derive(Debug, Clone)]
+pub struct Health {
+    pub tenant: Option<String>,
+    /// Latest `run_once` or scheduled run, if any has finished
+    pub last_run: Option<LastRun>,
+    /// `None` when the chain provider could not be asked
+    pub validator_script: Option<ValidatorScriptCheck>,
+    /// Shipments per current lifecycle state; empty without a state store
+    pub lifecycle: BTreeMap<&'static str, usize>,
+    /// Whether closures may be submitted now; `None` without `SUBMIT_WINDOW`
+    pub submit_window_open: Option<bool>,
+}
+
+impl Health {
+    /// The validator script is usable and the latest run, if any, did not abort
+    pub fn is_healthy(&self) -> bool {
+        self.validator_script.as_ref().is_some_and(ValidatorScriptCheck::is_present)
+            && self.last_run.as_ref().is_none_or(|run| run.result.is_ok())
+    }
+}
+
+impl Oracle {
+    pub fn builder() -> OracleBuilder {
+        OracleBuilder::default()
+    }
+
+    /// Oracle with every part built from `config`
+    pub fn from_config(config: Config) -> Result<Self> {
+        Self::builder().config(config).build()
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn tenant(&self) -> Option<&str> {
+        self.config.tenant.as_deref()
+    }
+
+    pub fn chain(&self) -> &CardanoClient {
+        self.data_fetcher.blockchain()
+    }
+
+    pub fn state(&self) -> Option<&Arc<dyn StateStore>> {
+        self.data_fetcher.state()
+    }
+
+    /// Counters and gauges of this oracle's runs, see `MetricsServer`
+    pub fn metrics(&self) -> &Metrics {
+        self.data_fetcher.metrics()
+    }
+
+    /// The fetcher behind this oracle, for registering it with the scheduler
+    pub fn data_fetcher(&self) -> &Arc<DataFetcher> {
+        &self.data_fetcher
+    }
+
+    /// Check every tracking UTxO once and close those whose carrier status is final
+    pub async fn run_once(&self) -> Result<RunReport> {
+        self.data_fetcher.run().await
+    }
+
+    /// Tracking UTxOs currently at the oracle address
+    pub async fn scan(&self) -> Result<Vec<TrackingUTxO>> {
+        self.chain().fetch_shipments().await
+    }
+
+    /// Close the tracking UTxO `utxo_ref` (`TxHash#TxIx`) with `status`, regardless of its carrier status
+    ///
+    /// See `close_shipment`.
+    pub async fn close(&self, utxo_ref: &str, status: &str) -> Result<ManualClose> {
+        let statuses = self.config.status_mapping.on_chain_statuses();
+        let Some(status) = statuses.iter().copied().find(|allowed| allowed.as_str() == status) else {
+            let names: Vec<&str> = statuses.iter().map(DerivedStatus::as_str).collect();
+            bail!("Status must be one of {}, got {}", names.join(", "), status);
+        };
+
+        let shipment = self
+            .scan()
+            .await?
+            .into_iter()
+            .find(|shipment| shipment.utxo_ref.to_string() == utxo_ref)
+            .with_context(|| format!("No tracking UTxO {} at the oracle address", utxo_ref))?;
+
+        self.close_shipment(&shipment, status, None).await
+    }
+
+    /// Close `shipment` with `status` stamped `timestamp` (default: now), regardless of its carrier status
+    ///
+    /// The close is checked, journalled and notified like a run's, and only
+    /// signed under `DRY_RUN`; see `DataFetcher::close_shipment`.
+    pub async fn close_shipment(
+        &self,
+        shipment: &TrackingUTxO,
+        status: DerivedStatus,
+        timestamp: Option<u64>,
+    ) -> Result<ManualClose> {
+        let result = self.data_fetcher.close_shipment(shipment, status, timestamp).await;
+        self.data_fetcher.flush_notifications().await;
+
+        result
+    }
+
+    /// Decisions matching `query`, from the state database
+    ///
+    /// When nothing is journalled (or there is no state database) and the query
+    /// names both a tracking number and an outbox address, the outbox's shipment
+    /// datums on-chain are searched instead.
+    pub async fn decisions(&self, query: &DecisionQuery) -> Result<DecisionPage> {
+        if let Some(state) = self.state() {
+            let page = decisions::find_decisions(state.as_ref(), query).await?;
+            if page.total > 0 {
+                return Ok(page);
+            }
+        }
+
+        let (Some(tracking_number), Some(outbox)) = (&query.tracking_number, &query.outbox) else {
+            if self.state().is_none() {
+                bail!("Without a state database, decisions can only be looked up by tracking number and outbox address");
+            }
+            return Ok(DecisionPage::new(Vec::new(), query));
+        };
+
+        let decision = self.chain().find_decision_onchain(tracking_number, outbox).await?;
+        Ok(DecisionPage::new(decision.into_iter().collect(), query))
+    }
+
+    /// Receive every shipment event from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<OracleEvent> {
+        self.events.subscribe()
+    }
+
+    /// Latest run outcome, the on-chain state of the validator script and shipment lifecycle counts
+    pub async fn health(&self) -> Health {
+        let mut lifecycle = BTreeMap::new();
+        if let Some(state) = self.state()
+            && let Ok(lifecycles) = state.lifecycles().await
+        {
+            for transition in lifecycles {
+                *lifecycle.entry(transition.state.name()).or_default() += 1;
+            }
+        }
+
+        Health {
+            tenant: self.config.tenant.clone(),
+            last_run: self.data_fetcher.last_run(),
+            validator_script: self.chain().check_validator_script().await.ok(),
+            lifecycle,
+            submit_window_open: self.data_fetcher.submit_window_open(),
+        }
+    }
+}
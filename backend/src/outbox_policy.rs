@@ -0,0 +1,149 @@
+use anyhow::{Context, Result};
+use pallas::ledger::addresses::Address;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::config::Config;
+
+/// Outbox addresses the oracle may close shipments into
+///
+/// Lists are files with one bech32 address per line; blank lines and `#`
+/// comments are ignored. `refresh` picks up edits without a restart.
+pub struct OutboxPolicy {
+    allowlist: Option<AddressList>,
+    denylist: Option<AddressList>,
+}
+
+/// Why a shipment's outbox address is refused
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PolicyViolation {
+    Denylisted,
+    NotAllowlisted,
+}
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyViolation::Denylisted => f.write_str("outbox address is denylisted"),
+            PolicyViolation::NotAllowlisted => f.write_str("outbox address is not allowlisted"),
+        }
+    }
+}
+
+/// Policy for the `OUTBOX_ALLOWLIST_FILE` and `OUTBOX_DENYLIST_FILE` of `config`; `None` without either
+pub fn from_config(config: &Config) -> Result<Option<OutboxPolicy>> {
+    if config.outbox_allowlist_file.is_none() && config.outbox_denylist_file.is_none() {
+        return Ok(None);
+    }
+
+    OutboxPolicy::new(config.outbox_allowlist_file.as_deref(), config.outbox_denylist_file.as_deref()).map(Some)
+}
+
+impl OutboxPolicy {
+    pub fn new(allowlist: Option<&str>, denylist: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            allowlist: allowlist.map(AddressList::load).transpose()?,
+            denylist: denylist.map(AddressList::load).transpose()?,
+        })
+    }
+
+    /// Reload the lists whose file changed since they were last read
+    ///
+    /// A list that fails to reload keeps its previous addresses.
+    pub fn refresh(&self) -> Result<()> {
+        for list in [&self.allowlist, &self.denylist].into_iter().flatten() {
+            list.refresh()?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether shipments may be closed into `address`; the denylist wins over the allowlist
+    pub fn check(&self, address: &Address) -> Result<(), PolicyViolation> {
+        let bytes = address.to_vec();
+        if self.denylist.as_ref().is_some_and(|list| list.contains(&bytes)) {
+            return Err(PolicyViolation::Denylisted);
+        }
+        if self.allowlist.as_ref().is_some_and(|list| !list.contains(&bytes)) {
+            return Err(PolicyViolation::NotAllowlisted);
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for OutboxPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lists: Vec<String> = [("allowlist", &self.allowlist), ("denylist", &self.denylist)]
+            .into_iter()
+            .filter_map(|(name, list)| list.as_ref().map(|list| format!("{} {}", name, list)))
+            .collect();
+
+        f.write_str(&lists.join(", "))
+    }
+}
+
+/// An address list file and the addresses it held when last read
+struct AddressList {
+    path: PathBuf,
+    loaded: Mutex<Loaded>,
+}
+
+struct Loaded {
+    contents: String,
+    addresses: HashSet<Vec<u8>>,
+}
+
+impl AddressList {
+    fn load(path: &str) -> Result<Self> {
+        let path = PathBuf::from(path);
+        let contents = read(&path)?;
+        let addresses = parse(&path, &contents)?;
+
+        Ok(Self { path, loaded: Mutex::new(Loaded { contents, addresses }) })
+    }
+
+    fn refresh(&self) -> Result<()> {
+        let contents = read(&self.path)?;
+        let mut loaded = self.loaded.lock().unwrap_or_else(|e| e.into_inner());
+        if contents != loaded.contents {
+            loaded.addresses = parse(&self.path, &contents)?;
+            loaded.contents = contents;
+        }
+
+        Ok(())
+    }
+
+    fn contains(&self, address: &[u8]) -> bool {
+        self.loaded.lock().unwrap_or_else(|e| e.into_inner()).addresses.contains(address)
+    }
+}
+
+impl fmt::Display for AddressList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let count = self.loaded.lock().unwrap_or_else(|e| e.into_inner()).addresses.len();
+        write!(f, "{} ({} addresses)", self.path.display(), count)
+    }
+}
+
+fn read(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).with_context(|| format!("Failed to read outbox list {}", path.display()))
+}
+
+fn parse(path: &Path, contents: &str) -> Result<HashSet<Vec<u8>>> {
+    let mut addresses = HashSet::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let address = Address::from_bech32(line)
+            .with_context(|| format!("{}:{}: invalid address {}", path.display(), number + 1, line))?;
+        addresses.insert(address.to_vec());
+    }
+
+    Ok(addresses)
+}
@@ -0,0 +1,74 @@
+use anyhow::{Context, Result, anyhow};
+use pallas::crypto::hash::Hasher;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::state::StateStore;
+
+/// Datum value for a tracking number in privacy mode: blake2b-256 of `carrier || tracking_number || salt`
+pub fn tracking_hash(carrier: &str, tracking_number: &str, salt: &str) -> [u8; 32] {
+    let preimage = [carrier.as_bytes(), tracking_number.as_bytes(), salt.as_bytes()].concat();
+    *Hasher::<256>::hash(&preimage)
+}
+
+/// Resolves hashed tracking numbers back to the real ones
+///
+/// Entries come from a local lookup file (a JSON object of hex hash to
+/// tracking number) and, when configured, the state database's
+/// `tracking_lookup` table filled by `register-tracking`.
+#[derive(Default)]
+pub struct TrackingLookup {
+    entries: HashMap<[u8; 32], String>,
+    state: Option<Arc<dyn StateStore>>,
+}
+
+impl TrackingLookup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load a `{ "<hash hex>": "<tracking number>" }` lookup file
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read tracking lookup file {}", path.display()))?;
+        let raw: HashMap<String, String> = serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid tracking lookup file {}", path.display()))?;
+
+        let mut lookup = Self::new();
+        for (hash, tracking_number) in raw {
+            lookup.insert(parse_hash(&hash)?, tracking_number);
+        }
+
+        Ok(lookup)
+    }
+
+    /// Also consult the `tracking_lookup` table of `state`
+    pub fn with_state(mut self, state: Arc<dyn StateStore>) -> Self {
+        self.state = Some(state);
+        self
+    }
+
+    pub fn insert(&mut self, hash: [u8; 32], tracking_number: String) {
+        self.entries.insert(hash, tracking_number);
+    }
+
+    /// The tracking number registered for `hash`, if any
+    pub async fn resolve(&self, hash: &[u8; 32]) -> Result<Option<String>> {
+        if let Some(tracking_number) = self.entries.get(hash) {
+            return Ok(Some(tracking_number.clone()));
+        }
+
+        match &self.state {
+            Some(state) => state.tracking_number(hash).await,
+            None => Ok(None),
+        }
+    }
+}
+
+fn parse_hash(value: &str) -> Result<[u8; 32]> {
+    let bytes = hex::decode(value.trim()).with_context(|| format!("Tracking hash {} is not hex", value))?;
+    <[u8; 32]>::try_from(bytes.as_slice())
+        .map_err(|_| anyhow!("Tracking hash {} must be 32 bytes, got {}", value, bytes.len()))
+}
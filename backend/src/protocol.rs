@@ -0,0 +1,199 @@
+use anyhow::{Result, bail};
+use std::collections::{BTreeSet, HashMap};
+
+/// A transaction of a tx3 protocol file and the arguments TRP needs to resolve it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxDecl {
+    pub name: String,
+    /// Declared parameters plus the parties and env fields the body uses, lowercased and sorted
+    pub args: Vec<String>,
+}
+
+impl TxDecl {
+    /// Name of the generated params struct (`close_shipment` -> `CloseShipmentParams`)
+    pub fn params_struct(&self) -> String {
+        let mut name: String = self
+            .name
+            .split('_')
+            .map(|word| {
+                let mut chars = word.chars();
+                chars.next().map(|first| first.to_uppercase().chain(chars).collect::<String>()).unwrap_or_default()
+            })
+            .collect();
+        name.push_str("Params");
+        name
+    }
+}
+
+/// Transactions declared in tx3 source, in file order
+///
+/// Only the declarations are read; bodies are scanned for the parties and
+/// env fields they reference, which the generated bindings take as arguments.
+pub fn parse(source: &str) -> Result<Vec<TxDecl>> {
+    let tokens = tokenize(source);
+    let mut parties = Vec::new();
+    let mut env = Vec::new();
+    let mut txs = Vec::new();
+
+    let mut pos = 0;
+    while pos < tokens.len() {
+        match tokens[pos] {
+            "party" => {
+                let Some(name) = tokens.get(pos + 1) else {
+                    bail!("party declaration without a name");
+                };
+                parties.push(*name);
+                pos += 2;
+            }
+            "env" => {
+                let (fields, end) = block(&tokens, pos + 1)?;
+                env.extend(declared_names(fields));
+                pos = end;
+            }
+            "tx" => {
+                let Some(name) = tokens.get(pos + 1) else {
+                    bail!("tx declaration without a name");
+                };
+                if tokens.get(pos + 2) != Some(&"(") {
+                    bail!("tx {}: expected a parameter list", name);
+                }
+                let Some(close) = tokens[pos + 2..].iter().position(|token| *token == ")") else {
+                    bail!("tx {}: unterminated parameter list", name);
+                };
+                let params = &tokens[pos + 3..pos + 2 + close];
+                let (body, end) = block(&tokens, pos + 3 + close)?;
+
+                let mut args: BTreeSet<String> = declared_names(params).map(str::to_string).collect();
+                for party in &parties {
+                    if body.contains(party) {
+                        args.insert(party.to_lowercase());
+                    }
+                }
+                for field in &env {
+                    if body.contains(field) {
+                        args.insert(field.to_string());
+                    }
+                }
+
+                txs.push(TxDecl { name: name.to_string(), args: args.into_iter().collect() });
+                pos = end;
+            }
+            _ => pos += 1,
+        }
+    }
+
+    Ok(txs)
+}
+
+/// Fields of every `pub struct *Params` in generated bindings, by struct name
+pub fn binding_params(bindings: &str) -> HashMap<String, Vec<String>> {
+    let tokens = tokenize(bindings);
+    let mut structs = HashMap::new();
+
+    for (pos, window) in tokens.windows(3).enumerate() {
+        if let ["pub", "struct", name] = window
+            && name.ends_with("Params")
+            && let Ok((fields, _)) = block(&tokens, pos + 3)
+        {
+            let mut fields: Vec<String> = declared_names(fields).map(str::to_string).collect();
+            fields.sort();
+            structs.insert(name.to_string(), fields);
+        }
+    }
+
+    structs
+}
+
+/// Check that `bindings` has a params struct with exactly the arguments of every tx in `protocol`
+pub fn check_bindings(protocol: &str, bindings: &str) -> Result<()> {
+    let structs = binding_params(bindings);
+    let mut problems = Vec::new();
+
+    for tx in parse(protocol)? {
+        let name = tx.params_struct();
+        let Some(fields) = structs.get(&name) else {
+            problems.push(format!("tx {}: no {} in the bindings", tx.name, name));
+            continue;
+        };
+
+        let missing: Vec<&str> = tx.args.iter().filter(|arg| !fields.contains(arg)).map(String::as_str).collect();
+        let stale: Vec<&str> = fields.iter().filter(|field| !tx.args.contains(field)).map(String::as_str).collect();
+        if !missing.is_empty() {
+            problems.push(format!("tx {}: {} is missing {}", tx.name, name, missing.join(", ")));
+        }
+        if !stale.is_empty() {
+            problems.push(format!("tx {}: {} has fields the protocol no longer takes: {}", tx.name, name, stale.join(", ")));
+        }
+    }
+
+    if !problems.is_empty() {
+        bail!("tx3 bindings are out of date:\n  {}", problems.join("\n  "));
+    }
+
+    Ok(())
+}
+
+/// Identifiers and single punctuation characters; comments, numbers and strings are dropped
+fn tokenize(source: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let bytes = source.as_bytes();
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let byte = bytes[pos];
+        if byte.is_ascii_alphabetic() || byte == b'_' {
+            let start = pos;
+            while pos < bytes.len() && (bytes[pos].is_ascii_alphanumeric() || bytes[pos] == b'_') {
+                pos += 1;
+            }
+            tokens.push(&source[start..pos]);
+        } else if byte.is_ascii_digit() {
+            while pos < bytes.len() && (bytes[pos].is_ascii_alphanumeric() || bytes[pos] == b'_') {
+                pos += 1;
+            }
+        } else if source[pos..].starts_with("//") {
+            pos = source[pos..].find('\n').map_or(bytes.len(), |end| pos + end);
+        } else if byte == b'"' {
+            pos = source[pos + 1..].find('"').map_or(bytes.len(), |end| pos + end + 2);
+        } else if byte.is_ascii_punctuation() {
+            tokens.push(&source[pos..pos + 1]);
+            pos += 1;
+        } else {
+            pos += source[pos..].chars().next().map_or(1, char::len_utf8);
+        }
+    }
+
+    tokens
+}
+
+/// Tokens inside the `{ ... }` block opening at `start`, and the position after it
+fn block<'a>(tokens: &'a [&'a str], start: usize) -> Result<(&'a [&'a str], usize)> {
+    if tokens.get(start) != Some(&"{") {
+        bail!("expected a block");
+    }
+
+    let mut depth = 0;
+    for (pos, token) in tokens.iter().enumerate().skip(start) {
+        match *token {
+            "{" => depth += 1,
+            "}" => {
+                depth -= 1;
+                if depth == 0 {
+                    return Ok((&tokens[start + 1..pos], pos + 1));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    bail!("unterminated block")
+}
+
+/// Names declared as `name: Type` (or `pub name: Type`) in `tokens`
+fn declared_names<'a>(tokens: &'a [&'a str]) -> impl Iterator<Item = &'a str> {
+    tokens
+        .windows(2)
+        .enumerate()
+        .filter(|(pos, window)| window[1] == ":" && (*pos == 0 || matches!(tokens[pos - 1], "," | "{" | "pub")))
+        .map(|(_, window)| window[0])
+}
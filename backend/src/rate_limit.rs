@@ -0,0 +1,77 @@
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Requests `capacity` at once, refilled evenly over `period`
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    per_second: f64,
+    /// Goes negative while requests wait for tokens already promised to them
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl Bucket {
+    fn new(capacity: u32, period: Duration) -> Self {
+        let capacity = f64::from(capacity);
+        Self { capacity, per_second: capacity / period.as_secs_f64(), tokens: capacity, refilled_at: Instant::now() }
+    }
+
+    /// Take a token, returning how long to wait until it is actually there
+    fn take(&mut self, now: Instant) -> Duration {
+        let elapsed = now.duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.capacity);
+        self.refilled_at = now;
+        self.tokens -= 1.0;
+
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.per_second)
+        }
+    }
+}
+
+/// Token buckets spacing out requests to an API; clones share the buckets
+///
+/// Requests are let through in the order they ask, each waiting until every
+/// bucket has a token for it. Without any limit requests never wait.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    buckets: Arc<Mutex<Vec<Bucket>>>,
+}
+
+impl RateLimiter {
+    /// At most `count` requests per `period` for each limit given; a `count` of 0 is no limit
+    pub fn new(limits: impl IntoIterator<Item = (u32, Duration)>) -> Self {
+        let buckets = limits
+            .into_iter()
+            .filter(|(count, period)| *count > 0 && !period.is_zero())
+            .map(|(count, period)| Bucket::new(count, period))
+            .collect();
+        Self { buckets: Arc::new(Mutex::new(buckets)) }
+    }
+
+    /// `max_per_second` and `max_per_minute` requests, whichever are set
+    pub fn per_second_and_minute(max_per_second: Option<u32>, max_per_minute: Option<u32>) -> Self {
+        Self::new(
+            [(max_per_second, Duration::from_secs(1)), (max_per_minute, Duration::from_secs(60))]
+                .into_iter()
+                .filter_map(|(count, period)| Some((count?, period))),
+        )
+    }
+
+    /// Wait for this request's turn
+    pub async fn acquire(&self) {
+        let wait = {
+            let mut buckets = self.buckets.lock().await;
+            let now = Instant::now();
+            buckets.iter_mut().map(|bucket| bucket.take(now)).max().unwrap_or(Duration::ZERO)
+        };
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
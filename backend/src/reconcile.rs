@@ -0,0 +1,133 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::config::Network;
+use crate::state::{ShipmentState, StateStore, Submission};
+
+/// How a journalled submission compares with the chain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReconcileStatus {
+    /// On-chain with the expected shipment datum at the outbox
+    Confirmed,
+    /// Accepted by the submit API but never landed
+    MissingOnChain,
+    /// On-chain, but the outbox datum is absent or disagrees with the journal
+    DatumMismatch,
+}
+
+impl ReconcileStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ReconcileStatus::Confirmed => "confirmed",
+            ReconcileStatus::MissingOnChain => "missing_on_chain",
+            ReconcileStatus::DatumMismatch => "datum_mismatch",
+        }
+    }
+}
+
+/// One journalled submission and its classification
+#[derive(Debug, Clone, Serialize)]
+pub struct ReconcileEntry {
+    #[serde(flatten)]
+    pub submission: Submission,
+    #[serde(rename = "reconcile_status")]
+    pub status: ReconcileStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// The tracking UTxO is still at the oracle address, so the closure can be retried
+    pub tracking_unspent: bool,
+}
+
+/// Classification of the whole submission journal
+#[derive(Debug, Clone, Serialize)]
+pub struct ReconcileReport {
+    pub entries: Vec<ReconcileEntry>,
+    pub confirmed: usize,
+    pub missing_on_chain: usize,
+    pub datum_mismatch: usize,
+}
+
+impl ReconcileReport {
+    pub fn new(entries: Vec<ReconcileEntry>) -> Self {
+        let count = |status| entries.iter().filter(|entry| entry.status == status).count();
+
+        Self {
+            confirmed: count(ReconcileStatus::Confirmed),
+            missing_on_chain: count(ReconcileStatus::MissingOnChain),
+            datum_mismatch: count(ReconcileStatus::DatumMismatch),
+            entries,
+        }
+    }
+
+    /// Missing closures whose tracking UTxO can still be closed
+    pub fn resubmittable(&self) -> impl Iterator<Item = &ReconcileEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.status == ReconcileStatus::MissingOnChain && entry.tracking_unspent)
+    }
+
+    /// Clear the recorded closure of every resubmittable entry so the shipment is
+    /// tracked as open again; the next run closes its tracking UTxO anew
+    ///
+    /// Returns the UTxO refs that were queued.
+    pub async fn requeue(&self, state: &dyn StateStore) -> Result<Vec<String>> {
+        let mut queued = Vec::new();
+
+        for entry in self.resubmittable() {
+            let utxo_ref = &entry.submission.utxo_ref;
+            let mut shipment = state.shipment(utxo_ref).await?.unwrap_or_else(|| ShipmentState::new(utxo_ref));
+            shipment.closed_tx_hash = None;
+            shipment.next_attempt_at = None;
+            shipment.dead = false;
+            state.save_shipment(&shipment).await?;
+
+            if !queued.contains(utxo_ref) {
+                queued.push(utxo_ref.clone());
+            }
+        }
+
+        Ok(queued)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize reconcile report")
+    }
+
+    /// Markdown summary with one table row per submission
+    pub fn to_markdown(&self, network: Option<Network>) -> String {
+        let mut out = String::new();
+        out.push_str("# Reconcile Report\n\n");
+        out.push_str(&format!("- Confirmed: {}\n", self.confirmed));
+        out.push_str(&format!("- Missing on-chain: {}\n", self.missing_on_chain));
+        out.push_str(&format!("- Datum mismatch: {}\n\n", self.datum_mismatch));
+
+        if self.entries.is_empty() {
+            out.push_str("The submission journal is empty.\n");
+            return out;
+        }
+
+        out.push_str("| Result | Tracking UTxO | Close tx | Status | Signer | Tracking unspent | Detail |\n");
+        out.push_str("|---|---|---|---|---|---|---|\n");
+        for entry in &self.entries {
+            let submission = &entry.submission;
+            let tx = match network {
+                Some(network) => format!("[{}]({})", submission.tx_hash, network.transaction_url(&submission.tx_hash)),
+                None => format!("`{}`", submission.tx_hash),
+            };
+
+            out.push_str(&format!(
+                "| {} | `{}` | {} | {} | {} | {} | {} |\n",
+                entry.status.as_str(),
+                submission.utxo_ref,
+                tx,
+                submission.status,
+                submission.signer_pkh.as_deref().map(|pkh| format!("`{}`", pkh)).unwrap_or_default(),
+                if entry.tracking_unspent { "yes" } else { "no" },
+                entry.detail.as_deref().unwrap_or_default().replace('|', "\\|"),
+            ));
+        }
+
+        out
+    }
+}
@@ -0,0 +1,258 @@
+use once_cell::sync::Lazy;
+use std::fmt;
+use std::sync::RwLock;
+use zeroize::Zeroize;
+
+use crate::config::Config;
+
+const MASK: &str = "[REDACTED]";
+
+/// Secrets shorter than this are not masked by value to avoid mangling unrelated text
+const MIN_SECRET_LEN: usize = 4;
+
+/// Keys whose value is masked when followed by `:` or `=` (header, query or JSON style)
+const SECRET_KEYS: &[&str] = &[
+    "project_id",
+    "dmtr-api-key",
+    "api_key",
+    "api-key",
+    "apikey",
+    "authorization",
+    "token",
+    "secret",
+];
+
+/// Authorization schemes whose following word is the credential
+const AUTH_SCHEMES: &[&str] = &["shippotoken", "bearer", "basic"];
+
+/// A configured secret, masked in `Debug` and `Display` output and wiped from memory on drop
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret<T: Zeroize>(T);
+
+impl<T: Zeroize> Secret<T> {
+    pub fn new(value: T) -> Self {
+        Self(value)
+    }
+
+    /// The raw value, for the call sites that send it upstream
+    pub fn expose(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Zeroize> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+
+impl<T: Zeroize> fmt::Display for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl From<String> for Secret<String> {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for Secret<String> {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+/// Registered values, kept as `Secret`s so every copy is wiped when it is dropped
+static SECRETS: Lazy<RwLock<Vec<Secret<String>>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Register a configured secret value so `redact` masks it wherever it appears
+pub fn register_secret(secret: &str) {
+    let secret = secret.trim();
+    if secret.len() < MIN_SECRET_LEN {
+        return;
+    }
+
+    let mut secrets = SECRETS.write().unwrap_or_else(|e| e.into_inner());
+    if !secrets.iter().any(|s| s.expose() == secret) {
+        secrets.push(Secret::from(secret));
+        // Mask longer secrets first so a secret containing another is fully hidden
+        secrets.sort_by_key(|s| std::cmp::Reverse(s.expose().len()));
+    }
+}
+
+/// Wipe every registered value; `redact` then only masks credential patterns
+pub fn forget_secrets() {
+    SECRETS.write().unwrap_or_else(|e| e.into_inner()).clear();
+}
+
+/// Register every secret value carried by the configuration
+pub fn register_config_secrets(config: &Config) {
+    register_secret(config.shippo_api_key.expose());
+    for oracle_sk in &config.oracle_sks {
+        // Key files are registered once read; their paths are not secret
+        if !std::path::Path::new(oracle_sk.expose()).is_file() {
+            register_secret(oracle_sk.expose());
+        }
+    }
+
+    if let Some(phrase) = &config.oracle_mnemonic {
+        register_secret(phrase.expose());
+    }
+
+    if let Some(project_id) = &config.blockfrost_project_id {
+        register_secret(project_id.expose());
+    }
+
+    if let Some(easypost_api_key) = &config.easypost_api_key {
+        register_secret(easypost_api_key.expose());
+    }
+
+    if let Some(aftership_api_key) = &config.aftership_api_key {
+        register_secret(aftership_api_key.expose());
+    }
+
+    if let Some(trp_api_key) = &config.trp_api_key {
+        register_secret(trp_api_key.expose());
+    }
+
+    if let Some(webhook_secret) = &config.notify_webhook_secret {
+        register_secret(webhook_secret.expose());
+    }
+
+    if let Some(admin_token) = &config.admin_token {
+        register_secret(admin_token.expose());
+    }
+
+    // Chat webhook URLs carry their credential in the path
+    for url in [&config.notify_slack_webhook, &config.notify_discord_webhook, &config.sentry_dsn].into_iter().flatten() {
+        register_secret(url.expose());
+    }
+
+    for proxy in [&config.http_proxy_shippo, &config.http_proxy_blockfrost].into_iter().flatten() {
+        if let Some(password) = crate::proxy::password(proxy.expose()) {
+            register_secret(password.expose());
+        }
+    }
+}
+
+/// Mask registered secrets and common credential patterns in an upstream
+/// response body, URL or error message before it is logged
+pub fn redact(input: &str) -> String {
+    let mut output = input.to_string();
+
+    {
+        let secrets = SECRETS.read().unwrap_or_else(|e| e.into_inner());
+        for secret in secrets.iter() {
+            if output.contains(secret.expose().as_str()) {
+                output = output.replace(secret.expose().as_str(), MASK);
+            }
+        }
+    }
+
+    redact_patterns(&output)
+}
+
+fn redact_patterns(input: &str) -> String {
+    // ASCII lowercasing keeps byte offsets aligned with `input`
+    let lower = input.to_ascii_lowercase();
+    let bytes = input.as_bytes();
+
+    let mut output = String::with_capacity(input.len());
+    let mut copied = 0;
+    let mut pos = 0;
+
+    while pos < bytes.len() {
+        let Some((key_end, is_scheme)) = match_key(&lower, pos) else {
+            pos += 1;
+            continue;
+        };
+
+        let (sep_end, has_assignment) = skip_separators(bytes, key_end);
+        if sep_end == key_end || (!is_scheme && !has_assignment) {
+            pos = key_end;
+            continue;
+        }
+
+        let mut value_start = sep_end;
+        if !is_scheme {
+            // `Authorization: ShippoToken <token>` masks the token, not the scheme
+            if let Some(scheme_end) = match_scheme(&lower, value_start)
+                && let (after, _) = skip_separators(bytes, scheme_end)
+                && after > scheme_end
+            {
+                value_start = after;
+            }
+        }
+
+        let value_end = skip_value(bytes, value_start);
+        if value_end == value_start {
+            pos = key_end;
+            continue;
+        }
+
+        output.push_str(&input[copied..value_start]);
+        output.push_str(MASK);
+        copied = value_end;
+        pos = value_end;
+    }
+
+    output.push_str(&input[copied..]);
+    output
+}
+
+fn match_key(lower: &str, pos: usize) -> Option<(usize, bool)> {
+    let bytes = lower.as_bytes();
+    if pos > 0 && bytes[pos - 1].is_ascii_alphanumeric() {
+        return None;
+    }
+
+    let rest = &bytes[pos..];
+    let keys = SECRET_KEYS.iter().map(|k| (*k, false));
+    let schemes = AUTH_SCHEMES.iter().map(|k| (*k, true));
+
+    keys.chain(schemes).find_map(|(key, is_scheme)| {
+        let end = pos + key.len();
+        let boundary = bytes.get(end).is_none_or(|b| !b.is_ascii_alphanumeric());
+        (rest.starts_with(key.as_bytes()) && boundary).then_some((end, is_scheme))
+    })
+}
+
+fn match_scheme(lower: &str, pos: usize) -> Option<usize> {
+    let rest = lower.as_bytes().get(pos..)?;
+    AUTH_SCHEMES
+        .iter()
+        .find(|scheme| rest.starts_with(scheme.as_bytes()))
+        .map(|scheme| pos + scheme.len())
+}
+
+fn skip_separators(bytes: &[u8], mut pos: usize) -> (usize, bool) {
+    let mut has_assignment = false;
+    while let Some(&b) = bytes.get(pos) {
+        match b {
+            b':' | b'=' => has_assignment = true,
+            b' ' | b'\t' | b'"' | b'\'' => {}
+            _ => break,
+        }
+        pos += 1;
+    }
+    (pos, has_assignment)
+}
+
+fn skip_value(bytes: &[u8], mut pos: usize) -> usize {
+    while let Some(&b) = bytes.get(pos) {
+        if b.is_ascii_alphanumeric() || matches!(b, b'_' | b'-' | b'.' | b'+' | b'/' | b'=' | b'%') {
+            pos += 1;
+        } else {
+            break;
+        }
+    }
+    pos
+}
@@ -0,0 +1,340 @@
+use anyhow::{Context, Result, bail};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::header::{CONTENT_LENGTH, CONTENT_TYPE, HOST};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+
+use crate::config::Config;
+
+/// Upstream services a recording covers, each in a subdirectory of its own
+pub const SERVICES: [&str; 3] = ["blockfrost", "shippo", "trp"];
+
+/// One request to an upstream service and the response it got
+///
+/// Request headers are never recorded, so API keys and project IDs stay out of
+/// the recordings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Exchange {
+    pub method: String,
+    /// Path and query string
+    pub path: String,
+    /// `body_sha256` of the request body, which replays are matched on
+    pub body_sha256: String,
+    /// `normalized_body` of the request, kept to explain a mismatch
+    pub request_body: Option<Value>,
+    pub status: u16,
+    pub content_type: Option<String>,
+    /// JSON responses as JSON, anything else as a string
+    pub response_body: Value,
+}
+
+impl Exchange {
+    fn key(&self) -> (String, String, String) {
+        (self.method.clone(), self.path.clone(), self.body_sha256.clone())
+    }
+}
+
+/// Request body as matched: JSON without its JSON-RPC `id`, which differs on
+/// every call, other bodies as a string, and `None` when empty
+pub fn normalized_body(body: &[u8]) -> Option<Value> {
+    if body.is_empty() {
+        return None;
+    }
+
+    match serde_json::from_slice::<Value>(body) {
+        Ok(Value::Object(mut map)) if map.contains_key("jsonrpc") => {
+            map.remove("id");
+            Some(Value::Object(map))
+        }
+        Ok(value) => Some(value),
+        Err(_) => Some(Value::String(String::from_utf8_lossy(body).into_owned())),
+    }
+}
+
+/// Hex SHA-256 of the `normalized_body`, serialized with sorted keys and no
+/// whitespace; an empty body hashes as the empty string
+pub fn body_sha256(body: &[u8]) -> String {
+    let mut canonical = String::new();
+    if let Some(value) = normalized_body(body) {
+        write_canonical(&value, &mut canonical);
+    }
+    hex::encode(Sha256::digest(canonical.as_bytes()))
+}
+
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            out.push('{');
+            for (i, (key, value)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(value, out);
+            }
+            out.push('}');
+        }
+        Value::Array(values) => {
+            out.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(value, out);
+            }
+            out.push(']');
+        }
+        scalar => out.push_str(&scalar.to_string()),
+    }
+}
+
+/// Local stand-ins for Blockfrost, Shippo and TRP that either record what the
+/// real services answer or replay a recording, until dropped
+///
+/// A recording directory holds one subdirectory per `SERVICES` entry with one
+/// `Exchange` per file. Replays match requests on method, path and
+/// `body_sha256`; a request nothing matches is answered `501 Not Implemented`
+/// and listed by `misses`. Must be started within a Tokio runtime.
+pub struct ReplaySession {
+    tasks: Vec<JoinHandle<()>>,
+    misses: Arc<Mutex<Vec<String>>>,
+}
+
+impl ReplaySession {
+    /// Forward `config`'s Blockfrost, Shippo and TRP requests to them, recording
+    /// every exchange into `dir`; returns the config pointing at the recorders
+    pub async fn record(config: &Config, dir: &Path) -> Result<(Self, Config)> {
+        let http_client = reqwest::Client::new();
+        Self::start(config, |service, upstream| {
+            let dir = dir.join(service);
+            std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+            Ok(Mode::Record { upstream: upstream.to_string(), http_client: http_client.clone(), dir, next: AtomicUsize::new(0) })
+        })
+    }
+
+    /// Answer `config`'s Blockfrost, Shippo and TRP requests from the recording
+    /// in `dir`; returns the config pointing at the replays
+    pub async fn replay(config: &Config, dir: &Path) -> Result<(Self, Config)> {
+        Self::start(config, |service, _upstream| {
+            let mut exchanges: HashMap<_, Vec<Exchange>> = HashMap::new();
+            for exchange in load_exchanges(&dir.join(service))? {
+                exchanges.entry(exchange.key()).or_default().push(exchange);
+            }
+            Ok(Mode::Replay { exchanges, served: Mutex::new(HashMap::new()) })
+        })
+    }
+
+    /// Requests no recorded exchange matched, as `service METHOD path (body sha256 ...)`
+    pub fn misses(&self) -> Vec<String> {
+        self.misses.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    fn start(config: &Config, mut mode: impl FnMut(&str, &str) -> Result<Mode>) -> Result<(Self, Config)> {
+        let misses = Arc::new(Mutex::new(Vec::new()));
+        let mut session = Self { tasks: Vec::new(), misses: misses.clone() };
+        let mut config = config.clone();
+
+        for service in SERVICES {
+            let upstream = match service {
+                "blockfrost" => &mut config.blockfrost_url,
+                "shippo" => &mut config.shippo_url,
+                _ => &mut config.trp_url,
+            };
+            let mode = mode(service, upstream.as_str())?;
+
+            let listener = TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0)))
+                .with_context(|| format!("Failed to bind the {} replay server", service))?;
+            listener.set_nonblocking(true).context("Failed to configure the replay listener")?;
+            *upstream = format!("http://{}", listener.local_addr().context("Failed to read the replay server address")?);
+
+            let server = Server::from_tcp(listener).context("Failed to start the replay server")?;
+            session.tasks.push(tokio::spawn(serve(server, service, Arc::new(mode), misses.clone())));
+        }
+
+        Ok((session, config))
+    }
+}
+
+impl Drop for ReplaySession {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+enum Mode {
+    Record { upstream: String, http_client: reqwest::Client, dir: PathBuf, next: AtomicUsize },
+    Replay { exchanges: HashMap<(String, String, String), Vec<Exchange>>, served: Mutex<HashMap<(String, String, String), usize>> },
+}
+
+async fn serve(
+    server: hyper::server::Builder<hyper::server::conn::AddrIncoming>,
+    service: &'static str,
+    mode: Arc<Mode>,
+    misses: Arc<Mutex<Vec<String>>>,
+) {
+    let make_service = make_service_fn(move |_connection| {
+        let (mode, misses) = (mode.clone(), misses.clone());
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                let (mode, misses) = (mode.clone(), misses.clone());
+                async move { Ok::<_, Infallible>(respond(service, &mode, &misses, request).await) }
+            }))
+        }
+    });
+
+    if let Err(e) = server.serve(make_service).await {
+        tracing::warn!(service, error = %e, "Replay server failed");
+    }
+}
+
+async fn respond(service: &str, mode: &Mode, misses: &Mutex<Vec<String>>, request: Request<Body>) -> Response<Body> {
+    let method = request.method().clone();
+    let path = request.uri().path_and_query().map_or("/", |path| path.as_str()).to_string();
+    let mut headers = request.headers().clone();
+    let body = match hyper::body::to_bytes(request.into_body()).await {
+        Ok(body) => body,
+        Err(e) => return status_response(StatusCode::BAD_REQUEST, &format!("Failed to read the request body: {}", e)),
+    };
+
+    match mode {
+        Mode::Record { upstream, http_client, dir, next } => {
+            // The client's own Host and length do not apply to the upstream request
+            headers.remove(HOST);
+            headers.remove(CONTENT_LENGTH);
+            let url = if path == "/" { upstream.clone() } else { format!("{}{}", upstream.trim_end_matches('/'), path) };
+
+            let forwarded = async {
+                let response = http_client.request(method.clone(), &url).headers(headers).body(body.to_vec()).send().await?;
+                let status = response.status();
+                let content_type = response.headers().get(CONTENT_TYPE).and_then(|value| value.to_str().ok()).map(str::to_string);
+                let response_bytes = response.bytes().await?;
+                Ok::<_, reqwest::Error>((status, content_type, response_bytes))
+            };
+            let (status, content_type, response_bytes) = match forwarded.await {
+                Ok(forwarded) => forwarded,
+                Err(e) => return status_response(StatusCode::BAD_GATEWAY, &format!("{} is unreachable: {}", service, e)),
+            };
+
+            let exchange = Exchange {
+                method: method.to_string(),
+                path,
+                body_sha256: body_sha256(&body),
+                request_body: normalized_body(&body),
+                status: status.as_u16(),
+                content_type: content_type.clone(),
+                response_body: response_value(content_type.as_deref(), &response_bytes),
+            };
+            let file = dir.join(format!("{:03}-{}.json", next.fetch_add(1, Ordering::SeqCst), method.as_str().to_lowercase()));
+            if let Err(e) = write_exchange(&file, &exchange) {
+                tracing::warn!(file = %file.display(), error = %format!("{:#}", e), "Failed to record an exchange");
+            }
+
+            let mut response = Response::builder().status(status);
+            if let Some(content_type) = content_type {
+                response = response.header(CONTENT_TYPE, content_type);
+            }
+            response
+                .body(Body::from(response_bytes))
+                .unwrap_or_else(|_| status_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to build the response"))
+        }
+        Mode::Replay { exchanges, served } => {
+            let key = (method.to_string(), path, body_sha256(&body));
+            let Some(recorded) = exchanges.get(&key) else {
+                let miss = format!("{} {} {} (body sha256 {})", service, key.0, key.1, key.2);
+                misses.lock().unwrap_or_else(|e| e.into_inner()).push(miss.clone());
+                return status_response(StatusCode::NOT_IMPLEMENTED, &format!("No recorded exchange matches {}", miss));
+            };
+
+            // Repeated requests get the recorded answers in order, then the last one again
+            let exchange = {
+                let mut served = served.lock().unwrap_or_else(|e| e.into_inner());
+                let count = served.entry(key).or_insert(0);
+                *count += 1;
+                &recorded[(*count - 1).min(recorded.len() - 1)]
+            };
+            replayed_response(exchange, &body)
+        }
+    }
+}
+
+/// `exchange`'s response, carrying the JSON-RPC `id` of `request_body` when it has one
+fn replayed_response(exchange: &Exchange, request_body: &[u8]) -> Response<Body> {
+    let body = match &exchange.response_body {
+        Value::String(text) => text.clone().into_bytes(),
+        value => {
+            let mut value = value.clone();
+            let id = serde_json::from_slice::<Value>(request_body).ok().and_then(|request| request.get("id").cloned());
+            if let Some(id) = id.filter(|_| value.get("jsonrpc").is_some()) {
+                value["id"] = id;
+            }
+            value.to_string().into_bytes()
+        }
+    };
+
+    let mut response = Response::builder().status(exchange.status);
+    if let Some(content_type) = &exchange.content_type {
+        response = response.header(CONTENT_TYPE, content_type);
+    }
+    response
+        .body(Body::from(body))
+        .unwrap_or_else(|_| status_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to build the response"))
+}
+
+fn response_value(content_type: Option<&str>, body: &[u8]) -> Value {
+    content_type
+        .filter(|content_type| content_type.contains("json"))
+        .and_then(|_| serde_json::from_slice(body).ok())
+        .unwrap_or_else(|| Value::String(String::from_utf8_lossy(body).into_owned()))
+}
+
+fn write_exchange(file: &Path, exchange: &Exchange) -> Result<()> {
+    let json = serde_json::to_string_pretty(exchange).context("Failed to serialize the exchange")?;
+    std::fs::write(file, json + "\n").with_context(|| format!("Failed to write {}", file.display()))
+}
+
+/// Exchanges recorded in `dir`, in file name order; none when it does not exist
+fn load_exchanges(dir: &Path) -> Result<Vec<Exchange>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut files = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<Vec<_>>>()
+        .with_context(|| format!("Failed to list {}", dir.display()))?;
+    files.retain(|file| file.extension().is_some_and(|extension| extension == "json"));
+    files.sort();
+
+    files
+        .iter()
+        .map(|file| {
+            let json = std::fs::read_to_string(file).with_context(|| format!("Failed to read {}", file.display()))?;
+            let exchange: Exchange = serde_json::from_str(&json).with_context(|| format!("Invalid exchange in {}", file.display()))?;
+            if exchange.method.is_empty() || !exchange.path.starts_with('/') {
+                bail!("{} does not record a request", file.display());
+            }
+            Ok(exchange)
+        })
+        .collect()
+}
+
+fn status_response(status: StatusCode, message: &str) -> Response<Body> {
+    let mut response = Response::new(Body::from(message.to_string()));
+    *response.status_mut() = status;
+    response
+}
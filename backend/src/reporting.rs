@@ -0,0 +1,297 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+
+use crate::config::Network;
+use crate::run_report::ShipmentReport;
+
+/// Outcome of a single shipment case in a report
+///
+/// What the oracle observed and did is a `ShipmentReport`, as in a run report;
+/// the remaining fields are the expectations the case checks it against.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CaseReport {
+    pub name: String,
+    pub shipment: ShipmentReport,
+    pub provisioned: bool,
+    pub tracking_tx_hash: String,
+    pub tracking_tx_index: u32,
+    pub tracking_outbox: String,
+    pub expected_status: String,
+    pub expected_timestamp: Option<u64>,
+    pub expected_tx_hash: Option<String>,
+    pub expected_outbox: Option<String>,
+    pub actual_outbox: Option<String>,
+    pub expected_p_status: Option<String>,
+    pub actual_p_status: Option<String>,
+    pub expected_p_utxo_ref: Option<String>,
+    pub actual_p_utxo_ref: Option<String>,
+    pub expected_oracle: Option<String>,
+    pub actual_oracle: Option<String>,
+    pub expected_oracle_pkh: Option<String>,
+    pub actual_oracle_pkh: Option<String>,
+    pub expected_payment: Option<String>,
+    pub actual_payment: Option<String>,
+    pub expected_validator_script_ref: Option<String>,
+    pub actual_validator_script_ref: Option<String>,
+    /// Key hash of the oracle key that signed the close transaction
+    #[serde(default)]
+    pub signer_pkh: Option<String>,
+    pub passed: bool,
+    pub errors: Vec<String>,
+}
+
+/// A set of shipment cases with pass/fail totals
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Report {
+    pub cases: Vec<CaseReport>,
+    pub passed: usize,
+    pub failed: usize,
+}
+
+impl Report {
+    pub fn new(cases: Vec<CaseReport>) -> Self {
+        let passed = cases.iter().filter(|case| case.passed).count();
+        let failed = cases.len() - passed;
+
+        Self { cases, passed, failed }
+    }
+}
+
+/// Renders reports as markdown or self-contained HTML
+///
+/// When a network is set, transaction hashes link to its Cardanoscan explorer.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ReportRenderer {
+    network: Option<Network>,
+}
+
+impl ReportRenderer {
+    pub fn new(network: Option<Network>) -> Self {
+        Self { network }
+    }
+
+    pub fn markdown(&self, report: &Report) -> String {
+        let mut out = String::new();
+        out.push_str("# Integration Test Report\n\n");
+        out.push_str(&format!("- Passed: {}\n", report.passed));
+        out.push_str(&format!("- Failed: {}\n\n", report.failed));
+
+        for case in &report.cases {
+            out.push_str(&format!("## {}\n", case_title(case)));
+            out.push_str(&format!("### {}\n", tracking_heading(case)));
+            out.push_str(&markdown_json(&tracking_json(case)));
+            if let Some(link) = self.markdown_tx_link(&case.tracking_tx_hash) {
+                out.push_str(&format!("- Explorer: {}\n", link));
+            }
+
+            out.push_str("### Shipment\n");
+            for (label, value) in shipment_lines(case) {
+                out.push_str(&format!("- {}: {}\n", label, value));
+            }
+
+            out.push_str("### Transition\n");
+            out.push_str("```\n");
+            out.push_str(&transition(case));
+            out.push_str("\n```\n");
+
+            if let Some(shipment) = shipment_json(case) {
+                out.push_str("### Shipment UTxO\n");
+                out.push_str(&markdown_json(&shipment));
+                if let Some(link) = case.expected_tx_hash.as_deref().and_then(|tx| self.markdown_tx_link(tx)) {
+                    out.push_str(&format!("- Explorer: {}\n", link));
+                }
+            }
+
+            if let Some(signer_pkh) = &case.signer_pkh {
+                out.push_str(&format!("- Signed by: `{}`\n", signer_pkh));
+            }
+
+            out.push_str(&format!("### Result -> `{}`\n", result_label(case)));
+            if !case.errors.is_empty() {
+                out.push_str(&format!("- Errors: {}\n", case.errors.join("; ")));
+            }
+            out.push('\n');
+        }
+
+        out
+    }
+
+    pub fn html(&self, report: &Report) -> String {
+        let mut body = String::new();
+        body.push_str("<h1>Integration Test Report</h1>\n");
+        body.push_str(&format!(
+            "<ul class=\"totals\"><li>Passed: {}</li><li>Failed: {}</li></ul>\n",
+            report.passed, report.failed
+        ));
+
+        for case in &report.cases {
+            let class = if case.passed { "case pass" } else { "case fail" };
+            body.push_str(&format!("<section class=\"{}\">\n", class));
+            body.push_str(&format!("<h2>{}</h2>\n", escape_html(case_title(case))));
+
+            body.push_str(&format!("<h3>{}</h3>\n", escape_html(tracking_heading(case))));
+            body.push_str(&html_json(&tracking_json(case)));
+            if let Some(link) = self.html_tx_link(&case.tracking_tx_hash) {
+                body.push_str(&format!("<p>Explorer: {}</p>\n", link));
+            }
+
+            body.push_str("<h3>Shipment</h3>\n<ul>\n");
+            for (label, value) in shipment_lines(case) {
+                body.push_str(&format!("<li>{}: {}</li>\n", label, escape_html(&value)));
+            }
+            body.push_str("</ul>\n");
+
+            body.push_str("<h3>Transition</h3>\n");
+            body.push_str(&format!("<pre>{}</pre>\n", escape_html(&transition(case))));
+
+            if let Some(shipment) = shipment_json(case) {
+                body.push_str("<h3>Shipment UTxO</h3>\n");
+                body.push_str(&html_json(&shipment));
+                if let Some(link) = case.expected_tx_hash.as_deref().and_then(|tx| self.html_tx_link(tx)) {
+                    body.push_str(&format!("<p>Explorer: {}</p>\n", link));
+                }
+            }
+
+            if let Some(signer_pkh) = &case.signer_pkh {
+                body.push_str(&format!("<p>Signed by: <code>{}</code></p>\n", escape_html(signer_pkh)));
+            }
+
+            body.push_str(&format!("<h3>Result <code>{}</code></h3>\n", result_label(case)));
+            if !case.errors.is_empty() {
+                body.push_str("<ul class=\"errors\">\n");
+                for error in &case.errors {
+                    body.push_str(&format!("<li>{}</li>\n", escape_html(error)));
+                }
+                body.push_str("</ul>\n");
+            }
+            body.push_str("</section>\n");
+        }
+
+        html_document("Integration Test Report", &body)
+    }
+
+    fn tx_url(&self, tx_hash: &str) -> Option<String> {
+        self.network.map(|network| network.transaction_url(tx_hash))
+    }
+
+    fn markdown_tx_link(&self, tx_hash: &str) -> Option<String> {
+        self.tx_url(tx_hash).map(|url| format!("[{}]({})", tx_hash, url))
+    }
+
+    fn html_tx_link(&self, tx_hash: &str) -> Option<String> {
+        self.tx_url(tx_hash)
+            .map(|url| format!("<a href=\"{}\">{}</a>", escape_html(&url), escape_html(tx_hash)))
+    }
+}
+
+fn case_title(case: &CaseReport) -> &str {
+    match case.name.as_str() {
+        "transit_skip" => "(transit_skip) No transition test",
+        "delivered" => "(delivered) Delivered transition test",
+        "failure" => "(failure) Not delivered transition test",
+        _ => case.name.as_str(),
+    }
+}
+
+fn tracking_heading(case: &CaseReport) -> &'static str {
+    if case.provisioned {
+        "Tracking UTxO (provisioned)"
+    } else {
+        "Tracking UTxO"
+    }
+}
+
+fn tracking_json(case: &CaseReport) -> Value {
+    json!({
+        "tx_hash": case.tracking_tx_hash,
+        "tx_index": case.tracking_tx_index,
+        "datum": {
+            "carrier": case.shipment.carrier,
+            "tracking_number": case.shipment.tracking_number,
+            "outbox_address": case.tracking_outbox,
+        }
+    })
+}
+
+fn shipment_json(case: &CaseReport) -> Option<Value> {
+    let tx_hash = case.expected_tx_hash.as_ref()?;
+
+    Some(json!({
+        "tx_hash": tx_hash,
+        "tx_index": 0,
+        "datum": {
+            "carrier": case.shipment.carrier,
+            "tracking_number": case.shipment.tracking_number,
+            "status": case.shipment.derived_status.clone().unwrap_or_else(|| "UNKNOWN".to_string()),
+            "timestamp": case.expected_timestamp,
+            "oracle_pkh": case.expected_oracle_pkh,
+        }
+    }))
+}
+
+fn shipment_lines(case: &CaseReport) -> Vec<(&'static str, String)> {
+    let mut lines = vec![
+        ("Carrier", case.shipment.carrier.clone()),
+        ("Tracking", case.shipment.tracking_number.clone()),
+        ("Status", case.shipment.fetched_status.clone().unwrap_or_else(|| case.expected_status.clone())),
+    ];
+
+    if let Some(ref status_details) = case.shipment.status_details {
+        lines.push(("Details", status_details.clone()));
+    }
+
+    lines
+}
+
+fn transition(case: &CaseReport) -> String {
+    let transition_to = case.shipment.derived_status.as_deref().unwrap_or("NO TRANSITION");
+    format!("{} -> {}", case.expected_status, transition_to)
+}
+
+fn result_label(case: &CaseReport) -> &'static str {
+    if case.passed { "PASS" } else { "FAIL" }
+}
+
+fn pretty_json(value: &Value) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_else(|_| "{}".to_string())
+}
+
+fn markdown_json(value: &Value) -> String {
+    format!("```\n{}\n```\n", pretty_json(value))
+}
+
+fn html_json(value: &Value) -> String {
+    format!("<pre>{}</pre>\n", escape_html(&pretty_json(value)))
+}
+
+fn html_document(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        escape_html(title),
+        HTML_STYLE,
+        body
+    )
+}
+
+const HTML_STYLE: &str = "body { font-family: system-ui, sans-serif; max-width: 960px; margin: 2rem auto; padding: 0 1rem; color: #1d1d1f; }
+pre { background: #f5f5f7; padding: 0.75rem; overflow-x: auto; }
+.case { border-left: 4px solid #d2d2d7; padding-left: 1rem; margin-bottom: 2rem; }
+.case.pass { border-color: #2eb67d; }
+.case.fail { border-color: #e01e5a; }
+.errors { color: #e01e5a; }
+";
+
+pub fn escape_html(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,66 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Crockford's base32 alphabet, as used by ULIDs
+const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Characters in an encoded run ID
+pub const RUN_ID_LEN: usize = 26;
+
+tokio::task_local! {
+    static CURRENT: String;
+}
+
+/// A new run ID: a ULID of the current time
+pub fn generate() -> String {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis() as u64)
+        .unwrap_or_default();
+
+    generate_at(millis)
+}
+
+/// A ULID of `millis` (Unix time in milliseconds)
+///
+/// The 48-bit timestamp comes first, so IDs sort by the time their run started;
+/// the remaining 80 bits are random.
+pub fn generate_at(millis: u64) -> String {
+    let random = (random_u64() as u128) << 16 | (random_u64() & 0xffff) as u128;
+    let value = ((millis & 0xffff_ffff_ffff) as u128) << 80 | random;
+
+    (0..RUN_ID_LEN)
+        .map(|i| ALPHABET[((value >> (125 - 5 * i)) & 0x1f) as usize] as char)
+        .collect()
+}
+
+/// Unix time in milliseconds encoded in a run ID; `None` if it is not one
+pub fn timestamp(run_id: &str) -> Option<u64> {
+    if run_id.len() != RUN_ID_LEN {
+        return None;
+    }
+
+    let mut value: u128 = 0;
+    for c in run_id.bytes() {
+        let digit = ALPHABET.iter().position(|&a| a == c.to_ascii_uppercase())?;
+        value = value << 5 | digit as u128;
+    }
+
+    Some((value >> 80) as u64)
+}
+
+/// Run `task` with `run_id` as the ID of the current run
+pub async fn scope<F: Future>(run_id: String, task: F) -> F::Output {
+    CURRENT.scope(run_id, task).await
+}
+
+/// ID of the run the calling task belongs to; `None` outside `DataFetcher::run`
+pub fn current() -> Option<String> {
+    CURRENT.try_with(String::clone).ok()
+}
+
+/// SipHash of nothing under fresh random keys, unpredictable enough for an ID
+fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
@@ -0,0 +1,134 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::fetcher::RunStats;
+use crate::tx3::CloseShipmentParams;
+
+/// What a run did with a shipment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ShipmentAction {
+    /// A close transaction was submitted
+    Submitted { tx_hash: String },
+    /// A close transaction was signed but, under `DRY_RUN`, not submitted
+    DryRun {
+        tx_hash: String,
+        /// Parameters the transaction was resolved with
+        params: Box<CloseShipmentParams>,
+        /// The signed transaction, hex-encoded
+        cbor_hex: String,
+    },
+    /// Nothing to close now, e.g. the status is not final or the shipment is left to a later run
+    Skipped { reason: String },
+    /// The status could not be fetched or the close could not be made
+    Failed { error: String },
+}
+
+impl ShipmentAction {
+    /// Hash of the submitted or, under `DRY_RUN`, signed close transaction
+    pub fn tx_hash(&self) -> Option<&str> {
+        match self {
+            ShipmentAction::Submitted { tx_hash } | ShipmentAction::DryRun { tx_hash, .. } => Some(tx_hash),
+            _ => None,
+        }
+    }
+}
+
+/// Why a shipment without a final carrier status is closed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CloseReason {
+    /// The datum's deadline passed
+    Deadline,
+    /// The tracking UTxO outlived `STALE_STATUS_TIMEOUT_DAYS` in a `STALE_STATUSES` carrier status
+    StaleTimeout,
+}
+
+/// How a run handled one tracking UTxO
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShipmentReport {
+    pub utxo_ref: String,
+    pub carrier: String,
+    /// Tracking number as written in the datum (a hash in privacy mode)
+    pub tracking_number: String,
+    /// Outbox address of the merchant the shipment belongs to
+    #[serde(default)]
+    pub outbox_address: String,
+    /// Carrier status as fetched; `None` when the carrier was not asked
+    pub fetched_status: Option<String>,
+    pub status_details: Option<String>,
+    /// Final status the shipment is closed with; `None` while it is not final
+    pub derived_status: Option<String>,
+    /// Why the shipment is closed without a final carrier status; `None` when the carrier status is final
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub close_reason: Option<CloseReason>,
+    pub action: ShipmentAction,
+    pub started_at: DateTime<Utc>,
+    pub elapsed_ms: u64,
+}
+
+impl ShipmentReport {
+    pub fn failed(&self) -> bool {
+        matches!(self.action, ShipmentAction::Failed { .. })
+    }
+}
+
+/// Outcome of a `DataFetcher::run`, one entry per tracking UTxO it looked at
+///
+/// UTxOs with an undecodable datum have no entry; they are counted in `stats.undecodable`.
+#[derive(Debug, Clone, Serialize)]
+pub struct RunReport {
+    /// ID carried by the run's logs and notifications
+    pub run_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tenant: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub stats: RunStats,
+    pub shipments: Vec<ShipmentReport>,
+    /// Subtotals of `shipments` per merchant outbox address
+    pub outboxes: BTreeMap<String, OutboxSubtotal>,
+}
+
+/// How a run handled the shipments of one merchant outbox address
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct OutboxSubtotal {
+    pub shipments: usize,
+    /// Closes submitted or, under `DRY_RUN`, signed
+    pub closed: usize,
+    pub failed: usize,
+    pub skipped: usize,
+}
+
+/// Subtotals of `shipments` per outbox address
+pub fn outbox_subtotals(shipments: &[ShipmentReport]) -> BTreeMap<String, OutboxSubtotal> {
+    let mut subtotals: BTreeMap<String, OutboxSubtotal> = BTreeMap::new();
+    for shipment in shipments {
+        let subtotal = subtotals.entry(shipment.outbox_address.clone()).or_default();
+        subtotal.shipments += 1;
+        match shipment.action {
+            ShipmentAction::Submitted { .. } | ShipmentAction::DryRun { .. } => subtotal.closed += 1,
+            ShipmentAction::Failed { .. } => subtotal.failed += 1,
+            ShipmentAction::Skipped { .. } => subtotal.skipped += 1,
+        }
+    }
+
+    subtotals
+}
+
+impl RunReport {
+    pub fn failures(&self) -> impl Iterator<Item = &ShipmentReport> {
+        self.shipments.iter().filter(|shipment| shipment.failed())
+    }
+
+    /// Whether no shipment failed
+    pub fn succeeded(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(self).context("Failed to serialize the run report")
+    }
+}
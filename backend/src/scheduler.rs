@@ -1,46 +1,433 @@
-use anyhow::Result;
+use anyhow::{Context, Result, anyhow};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+use tokio::sync::{Mutex, RwLock};
 use tokio_cron_scheduler::{Job, JobScheduler};
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info, warn};
 use crate::{
     config::Config,
     fetcher::DataFetcher,
+    heartbeat::{Heartbeat, PingStatus},
+    oracle::Oracle,
+    run_report::{RunReport, ShipmentAction},
 };
 
-pub async fn create_and_run_scheduler(config: Config, data_fetcher: Arc<DataFetcher>) -> Result<()> {
-    let scheduler = JobScheduler::new().await?;
+/// Whether the process schedules runs or makes one and exits (`RUN_MODE`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RunMode {
+    /// Run on `CRON_SCHEDULE` until shut down
+    #[default]
+    Scheduled,
+    /// Run every pipeline once, for an external scheduler, see `run_once`
+    Once,
+}
+
+impl FromStr for RunMode {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "scheduled" => Ok(RunMode::Scheduled),
+            "once" => Ok(RunMode::Once),
+            other => Err(anyhow!("expected once or scheduled, got '{}'", other)),
+        }
+    }
+}
 
-    let job_data_fetcher = data_fetcher.clone();
-    let job = Job::new_async(config.cron_schedule.as_str(), move |_uuid, _l| {
-        let data_fetcher = job_data_fetcher.clone();
-        Box::pin(async move {
-            execute_fetch_job(data_fetcher).await;
+impl fmt::Display for RunMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RunMode::Scheduled => "scheduled",
+            RunMode::Once => "once",
         })
-    })?;
+    }
+}
+
+/// What a pipeline does when its run is triggered while the previous one is still going (`OVERLAP_POLICY`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OverlapPolicy {
+    /// Drop the trigger; the error downcasts to `PreviousRunActive`
+    #[default]
+    Skip,
+    /// Start the run as soon as the previous one finishes
+    Queue,
+}
+
+impl FromStr for OverlapPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "skip" => Ok(OverlapPolicy::Skip),
+            "queue" => Ok(OverlapPolicy::Queue),
+            other => Err(anyhow!("expected skip or queue, got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for OverlapPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OverlapPolicy::Skip => "skip",
+            OverlapPolicy::Queue => "queue",
+        })
+    }
+}
+
+/// A run was triggered while the previous run of the pipeline was still going, and skipped
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Previous run is still active, skipping this one")]
+pub struct PreviousRunActive;
+
+/// A fetcher with its own cron schedule and heartbeat
+///
+/// Multi-tenant deployments register one pipeline per tenant in the shared scheduler.
+pub struct Pipeline {
+    cron_schedule: String,
+    cron_timezone: Tz,
+    data_fetcher: Arc<DataFetcher>,
+    heartbeat: Option<Arc<Heartbeat>>,
+    /// Read-held by every run in flight, so shutdown can wait for them by taking it for writing
+    running: RwLock<()>,
+    overlap_policy: OverlapPolicy,
+    /// Held by the run in flight, so two runs never close the same UTxOs concurrently
+    exclusive: Mutex<()>,
+}
+
+impl Pipeline {
+    pub fn new(config: &Config, data_fetcher: Arc<DataFetcher>) -> Result<Self> {
+        let heartbeat = match &config.heartbeat_url {
+            Some(url) => Some(Arc::new(Heartbeat::new(url.clone())?)),
+            None => None,
+        };
+
+        Ok(Self {
+            cron_schedule: config.cron_schedule.clone(),
+            cron_timezone: config.cron_timezone,
+            data_fetcher,
+            heartbeat,
+            running: RwLock::new(()),
+            overlap_policy: config.overlap_policy,
+            exclusive: Mutex::new(()),
+        })
+    }
+
+    pub fn for_oracle(oracle: &Oracle) -> Result<Self> {
+        Self::new(oracle.config(), oracle.data_fetcher().clone())
+    }
+
+    pub fn data_fetcher(&self) -> &Arc<DataFetcher> {
+        &self.data_fetcher
+    }
+
+    pub fn cron_schedule(&self) -> &str {
+        &self.cron_schedule
+    }
+
+    /// Next time `CRON_SCHEDULE` fires; `None` when it never fires again or cannot be read
+    pub fn next_run_at(&self) -> Option<DateTime<Utc>> {
+        let schedule = cron::Schedule::from_str(&self.cron_schedule).ok()?;
+        schedule.upcoming(self.cron_timezone).next().map(|at| at.with_timezone(&Utc))
+    }
+
+    /// Whether a run of the pipeline is in flight
+    pub fn is_running(&self) -> bool {
+        self.exclusive.try_lock().is_err()
+    }
+
+    /// Outcome of the latest heartbeat ping; `None` without `HEARTBEAT_URL` or before the first run
+    pub fn heartbeat_status(&self) -> Option<PingStatus> {
+        self.heartbeat.as_ref()?.last_status()
+    }
+
+    /// `[tenant] ` log prefix, empty outside multi-tenant deployments
+    fn label(&self) -> String {
+        match self.data_fetcher.tenant() {
+            Some(tenant) => format!("[{}] ", tenant),
+            None => String::new(),
+        }
+    }
+}
+
+/// Run `pipelines` on their schedules until SIGTERM or Ctrl-C, see `run_scheduler_until`
+///
+/// The pipelines are shared, e.g. with the `AdminServer` triggering runs of its own.
+pub async fn create_and_run_scheduler(pipelines: Vec<Arc<Pipeline>>, shutdown_grace: Duration) -> Result<()> {
+    run_shared_scheduler_until(pipelines, shutdown_grace, shutdown_signal()).await
+}
+
+/// Run every pipeline once now and then on its cron schedule, until `shutdown` resolves
+///
+/// On shutdown the scheduler stops starting runs, and runs in flight get up to
+/// `grace` to finish so no close is cut off between signing and submission.
+/// Runs still going after that are abandoned with a warning.
+pub async fn run_scheduler_until(
+    pipelines: Vec<Pipeline>,
+    grace: Duration,
+    shutdown: impl Future<Output = ()>,
+) -> Result<()> {
+    run_shared_scheduler_until(pipelines.into_iter().map(Arc::new).collect(), grace, shutdown).await
+}
+
+/// `run_scheduler_until` for pipelines shared with other triggers
+pub async fn run_shared_scheduler_until(
+    pipelines: Vec<Arc<Pipeline>>,
+    grace: Duration,
+    shutdown: impl Future<Output = ()>,
+) -> Result<()> {
+    let mut scheduler = JobScheduler::new().await?;
+
+    for pipeline in &pipelines {
+        let job_pipeline = pipeline.clone();
+        let job = Job::new_async_tz(pipeline.cron_schedule.as_str(), pipeline.cron_timezone, move |_uuid, _l| {
+            let pipeline = job_pipeline.clone();
+            Box::pin(async move {
+                let _ = execute_fetch_job(&pipeline).await;
+            })
+        })
+        .with_context(|| format!("{}Invalid cron schedule {}", pipeline.label(), pipeline.cron_schedule))?;
+
+        scheduler.add(job).await?;
+    }
 
-    scheduler.add(job).await?;
     scheduler.start().await?;
 
-    execute_fetch_job(data_fetcher.clone()).await;
+    let startup = pipelines.clone();
+    tokio::spawn(async move { run_all(&startup).await });
+
+    shutdown.await;
+    info!(grace_seconds = grace.as_secs(), "🛑 Shutting down, waiting for runs in flight");
+    scheduler.shutdown().await?;
+
+    let idle = futures::future::join_all(pipelines.iter().map(|pipeline| pipeline.running.write()));
+    match tokio::time::timeout(grace, idle).await {
+        Ok(_) => info!("👋 No run in flight, exiting"),
+        Err(_) => warn!(grace_seconds = grace.as_secs(), "⚠️  Runs still in flight after the grace period, exiting anyway"),
+    }
+
+    Ok(())
+}
+
+/// Resolves on Ctrl-C or, on unix, SIGTERM
+pub async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut sigterm) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = sigterm.recv() => {}
+                }
+                return;
+            }
+            Err(e) => warn!(error = %e, "⚠️  Failed to listen for SIGTERM, only Ctrl-C shuts down gracefully"),
+        }
+    }
+
+    if let Err(e) = tokio::signal::ctrl_c().await {
+        warn!(error = %e, "⚠️  Failed to listen for Ctrl-C");
+        std::future::pending::<()>().await;
+    }
+}
+
+/// How one pipeline's run went in `run_once`
+#[derive(Debug, Clone, Serialize)]
+pub struct PipelineRun {
+    pub tenant: Option<String>,
+    /// `None` when the run aborted
+    pub report: Option<RunReport>,
+    /// Why the run aborted
+    pub error: Option<String>,
+}
+
+/// Outcome of `run_once`, one entry per pipeline in pipeline order
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSummary {
+    pub runs: Vec<PipelineRun>,
+}
+
+impl RunSummary {
+    /// Whether every run completed without a failed shipment
+    pub fn succeeded(&self) -> bool {
+        self.runs
+            .iter()
+            .all(|run| run.error.is_none() && run.report.as_ref().is_some_and(RunReport::succeeded))
+    }
+
+    /// Process exit status for the summary: 1 when a run aborted or a shipment failed
+    pub fn exit_code(&self) -> i32 {
+        if self.succeeded() { 0 } else { 1 }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize the run summary")
+    }
+}
+
+impl fmt::Display for RunSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for run in &self.runs {
+            let label = run.tenant.as_ref().map(|tenant| format!("[{}] ", tenant)).unwrap_or_default();
+            match (&run.report, &run.error) {
+                (Some(report), _) => {
+                    let stats = &report.stats;
+                    writeln!(
+                        f,
+                        "{}{} shipments, {} submitted, {} failed, {} skipped",
+                        label,
+                        stats.shipments,
+                        stats.submitted,
+                        stats.failed,
+                        stats.skipped
+                            + stats.skipped_policy
+                            + stats.skipped_budget
+                            + stats.skipped_unregistered
+                            + stats.skipped_carrier,
+                    )?;
+                    for (outbox, subtotal) in &report.outboxes {
+                        writeln!(
+                            f,
+                            "{}  {}: {} shipments, {} closed, {} failed, {} skipped",
+                            label, outbox, subtotal.shipments, subtotal.closed, subtotal.failed, subtotal.skipped,
+                        )?;
+                    }
+                    for shipment in report.failures() {
+                        if let ShipmentAction::Failed { error } = &shipment.action {
+                            writeln!(f, "{}  ❌ {}: {}", label, shipment.utxo_ref, error)?;
+                        }
+                    }
+                }
+                (None, error) => writeln!(f, "{}Run aborted: {}", label, error.as_deref().unwrap_or("unknown error"))?,
+            }
+        }
 
-    loop {
-        tokio::time::sleep(tokio::time::Duration::from_secs(60)).await;
+        write!(f, "{}", if self.succeeded() { "✅ Run succeeded" } else { "❌ Run failed" })
     }
 }
 
-async fn execute_fetch_job(data_fetcher: Arc<DataFetcher>) {
-    println!(
-        "[{}] Executing scheduled fetch...",
-        chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
-    );
-    println!("================================");
+/// Run every pipeline once and summarize the outcome, for `RUN_MODE=once`
+///
+/// The process is expected to exit with `RunSummary::exit_code` afterwards.
+pub async fn run_once(pipelines: Vec<Pipeline>) -> RunSummary {
+    let pipelines: Vec<Arc<Pipeline>> = pipelines.into_iter().map(Arc::new).collect();
+    let results = run_all(&pipelines).await;
+
+    let runs = pipelines
+        .iter()
+        .zip(results)
+        .map(|(pipeline, result)| PipelineRun {
+            tenant: pipeline.data_fetcher.tenant().map(str::to_string),
+            error: result.as_ref().err().map(|e| format!("{:#}", e)),
+            report: result.ok(),
+        })
+        .collect();
+
+    RunSummary { runs }
+}
+
+/// Run every pipeline once, concurrently
+///
+/// Each pipeline runs in its own task, so an error or panic in one never
+/// affects the others. Results are returned in pipeline order.
+pub async fn run_all(pipelines: &[Arc<Pipeline>]) -> Vec<Result<RunReport>> {
+    let handles = pipelines.iter().map(|pipeline| {
+        let pipeline = pipeline.clone();
+        tokio::spawn(async move { execute_fetch_job(&pipeline).await })
+    });
+
+    futures::future::join_all(handles)
+        .await
+        .into_iter()
+        .zip(pipelines)
+        .map(|(result, pipeline)| {
+            result
+                .map_err(|e| anyhow!("{}Fetch job panicked: {}", pipeline.label(), e))
+                .and_then(|report| report)
+        })
+        .collect()
+}
+
+/// Run the pipeline's fetcher once, logging its report and pinging its heartbeat with the outcome
+///
+/// A run never overlaps the previous one of the same pipeline: under
+/// `OverlapPolicy::Skip` the trigger fails with `PreviousRunActive` if a run is
+/// in flight, under `OverlapPolicy::Queue` it waits for that run to finish.
+pub async fn execute_fetch_job(pipeline: &Pipeline) -> Result<RunReport> {
+    execute_fetch_job_with(pipeline, pipeline.overlap_policy).await
+}
+
+/// `execute_fetch_job` for an on-demand trigger, which never waits for a run in flight
+///
+/// Whatever the `OVERLAP_POLICY`, a trigger while the pipeline is running
+/// fails with `PreviousRunActive`.
+pub async fn trigger_fetch_job(pipeline: &Pipeline) -> Result<RunReport> {
+    execute_fetch_job_with(pipeline, OverlapPolicy::Skip).await
+}
+
+async fn execute_fetch_job_with(pipeline: &Pipeline, overlap_policy: OverlapPolicy) -> Result<RunReport> {
+    let tenant = pipeline.data_fetcher.tenant();
+    let _exclusive = match overlap_policy {
+        OverlapPolicy::Skip => match pipeline.exclusive.try_lock() {
+            Ok(guard) => guard,
+            Err(_) => {
+                warn!(tenant, "⏭️  {}", PreviousRunActive);
+                return Err(PreviousRunActive.into());
+            }
+        },
+        OverlapPolicy::Queue => match pipeline.exclusive.try_lock() {
+            Ok(guard) => guard,
+            Err(_) => {
+                warn!(tenant, "⏳ Previous run is still active, queueing this one");
+                pipeline.exclusive.lock().await
+            }
+        },
+    };
+    let _running = pipeline.running.read().await;
 
-    if let Err(e) = data_fetcher.run().await {
-        eprintln!("Error during fetch job: {:?}", e);
-    } else {
-        println!(
-            "[{}] Fetch job completed successfully",
-            chrono::Utc::now().format("%Y-%m-%d %H:%M:%S UTC")
-        );
+    info!(tenant, "Executing scheduled fetch...");
+
+    let result = pipeline.data_fetcher.run().await;
+
+    match &result {
+        Ok(report) => {
+            let stats = &report.stats;
+            info!(
+                tenant,
+                run_id = %report.run_id,
+                shipments = stats.shipments,
+                submitted = stats.submitted,
+                failed = stats.failed,
+                "Fetch job completed successfully",
+            );
+            match report.to_json() {
+                Ok(json) => info!(tenant, run_id = %report.run_id, report = %json, "📋 Run report"),
+                Err(e) => warn!(tenant, error = %format!("{:#}", e), "⚠️  Failed to log the run report"),
+            }
+
+            if let Some(heartbeat) = &pipeline.heartbeat {
+                if stats.failed == 0 {
+                    heartbeat.ping_success().await;
+                } else {
+                    heartbeat.ping_failure(Some(stats.failed)).await;
+                }
+            }
+        }
+        Err(e) => {
+            error!(tenant, error = %format!("{:#}", e), "Error during fetch job");
+
+            if let Some(heartbeat) = &pipeline.heartbeat {
+                heartbeat.ping_failure(None).await;
+            }
+        }
     }
-    println!("================================");
+
+    result
 }
@@ -0,0 +1,309 @@
+use anyhow::{Context, Result, anyhow, bail};
+use pallas::crypto::hash::Hasher;
+use pallas::ledger::addresses::{Address, Network};
+use std::fmt;
+use std::time::Duration;
+
+use crate::blockchain::{CardanoClient, blockfrost_http_client};
+use crate::config::Config;
+use crate::models::{TrackingDatum, TrackingNumber, TrackingUTxO};
+use crate::oracle::Oracle;
+use crate::run_id;
+use crate::signing::{SigningKeyMaterial, sign_envelope};
+use crate::submitter::{BlockfrostSubmitter, TxSubmitter};
+
+/// Carrier of self-test shipments; scheduled runs never ask a carrier about them
+pub const SELF_TEST_CARRIER: &str = "selftest";
+
+const SELF_TEST_STATUS: &str = "DELIVERED";
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(300);
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Stages of a self-test, in the order they run
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Lock a `selftest` tracking UTxO at the oracle address
+    Provision,
+    /// Find it in a Blockfrost scan
+    Discover,
+    /// Resolve, validate, sign and submit its close through the oracle
+    Close,
+    /// Wait for the close transaction to be on-chain
+    Confirm,
+    /// Decode the shipment datum the close paid to the outbox
+    VerifyDatum,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [Stage::Provision, Stage::Discover, Stage::Close, Stage::Confirm, Stage::VerifyDatum];
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Stage::Provision => "provision",
+            Stage::Discover => "discover",
+            Stage::Close => "close",
+            Stage::Confirm => "confirm",
+            Stage::VerifyDatum => "verify-datum",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    Passed(String),
+    Failed(String),
+    /// Not run because an earlier stage failed
+    Skipped,
+}
+
+/// Outcome of every stage of a self-test
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestReport {
+    pub stages: Vec<(Stage, Outcome)>,
+}
+
+impl SelfTestReport {
+    pub fn passed(&self) -> bool {
+        self.stages.iter().all(|(_, outcome)| matches!(outcome, Outcome::Passed(_)))
+    }
+
+    /// The stage that stopped the self-test
+    pub fn failed_stage(&self) -> Option<Stage> {
+        self.stages
+            .iter()
+            .find(|(_, outcome)| matches!(outcome, Outcome::Failed(_)))
+            .map(|(stage, _)| *stage)
+    }
+
+    fn pass(&mut self, stage: Stage, detail: String) {
+        self.stages.push((stage, Outcome::Passed(detail)));
+    }
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (stage, outcome) in &self.stages {
+            match outcome {
+                Outcome::Passed(detail) => writeln!(f, "✅ {}: {}", stage, detail)?,
+                Outcome::Failed(error) => writeln!(f, "❌ {}: {}", stage, error)?,
+                Outcome::Skipped => writeln!(f, "⏭️  {}: skipped", stage)?,
+            }
+        }
+
+        match self.failed_stage() {
+            Some(stage) => write!(f, "Self-test FAILED at {}", stage),
+            None => write!(f, "Self-test passed"),
+        }
+    }
+}
+
+/// End-to-end check of the key, validator, TRP and submitter with a synthetic shipment
+///
+/// Provisioning spends from the `TEST_FUNDING_SK` address (see
+/// `provision_tracking_utxo`); the shipment is closed into
+/// `ORACLE_PAYMENT_ADDRESS`, so the locked ADA comes back to the oracle.
+pub struct SelfTest<'a> {
+    oracle: &'a Oracle,
+    tracking_utxo: Option<String>,
+    confirmation_timeout: Duration,
+}
+
+impl<'a> SelfTest<'a> {
+    pub fn new(oracle: &'a Oracle) -> Self {
+        Self {
+            oracle,
+            tracking_utxo: None,
+            confirmation_timeout: CONFIRMATION_TIMEOUT,
+        }
+    }
+
+    /// Close an already provisioned `selftest` UTxO (`TxHash#TxIx`) instead of provisioning one
+    pub fn with_tracking_utxo(mut self, utxo_ref: &str) -> Self {
+        self.tracking_utxo = Some(utxo_ref.to_string());
+        self
+    }
+
+    pub fn with_confirmation_timeout(mut self, timeout: Duration) -> Self {
+        self.confirmation_timeout = timeout;
+        self
+    }
+
+    /// Run every stage, stopping at the first failure
+    pub async fn run(&self) -> SelfTestReport {
+        let mut report = SelfTestReport { stages: Vec::new() };
+        if let Err((stage, e)) = self.run_stages(&mut report).await {
+            report.stages.push((stage, Outcome::Failed(format!("{:#}", e))));
+        }
+        for stage in Stage::ALL.into_iter().skip(report.stages.len()) {
+            report.stages.push((stage, Outcome::Skipped));
+        }
+
+        report
+    }
+
+    async fn run_stages(&self, report: &mut SelfTestReport) -> Result<(), (Stage, anyhow::Error)> {
+        let at = |stage: Stage| move |e: anyhow::Error| (stage, e);
+
+        let utxo_ref = self.provision().await.map_err(at(Stage::Provision))?;
+        report.pass(Stage::Provision, format!("tracking UTxO {}", utxo_ref));
+
+        let tracking = self.discover(&utxo_ref).await.map_err(at(Stage::Discover))?;
+        report.pass(Stage::Discover, format!("{} {} at the oracle address", tracking.datum.carrier, tracking.datum.tracking_number));
+
+        let closed = self.oracle.close(&utxo_ref, SELF_TEST_STATUS).await.map_err(at(Stage::Close))?;
+        if closed.is_dry_run() {
+            return Err((Stage::Close, anyhow!("DRY_RUN is set, {} was signed but not submitted", closed.tx_hash())));
+        }
+        report.pass(Stage::Close, format!("submitted {} (fee {} lovelace)", closed.tx_hash(), closed.fee));
+
+        let height = self.confirm(closed.tx_hash()).await.map_err(at(Stage::Confirm))?;
+        report.pass(Stage::Confirm, format!("in block {}", height));
+
+        self.verify_datum(&tracking, closed.tx_hash()).await.map_err(at(Stage::VerifyDatum))?;
+        report.pass(Stage::VerifyDatum, format!("{} recorded by {}", SELF_TEST_STATUS, self.oracle.chain().signer_pkh()));
+
+        Ok(())
+    }
+
+    async fn provision(&self) -> Result<String> {
+        if let Some(utxo_ref) = &self.tracking_utxo {
+            return Ok(utxo_ref.clone());
+        }
+
+        let config = self.oracle.config();
+        let datum = TrackingDatum {
+            carrier: SELF_TEST_CARRIER.to_string(),
+            tracking_number: TrackingNumber::Plain(format!("SELFTEST-{}", run_id::generate())),
+            outbox_address: Address::from_bech32(&config.oracle_payment_address)
+                .map_err(|e| anyhow!("ORACLE_PAYMENT_ADDRESS must be a bech32 address: {}", e))?,
+            deadline: None,
+        };
+
+        provision_tracking_utxo(config, &datum).await
+    }
+
+    async fn discover(&self, utxo_ref: &str) -> Result<TrackingUTxO> {
+        let tracking = self
+            .oracle
+            .scan()
+            .await?
+            .into_iter()
+            .find(|shipment| shipment.utxo_ref.to_string() == utxo_ref)
+            .with_context(|| format!("No tracking UTxO {} at the oracle address", utxo_ref))?;
+
+        // Never close a merchant's shipment as delivered
+        if tracking.datum.carrier != SELF_TEST_CARRIER {
+            bail!("{} is not a self-test shipment (carrier {})", utxo_ref, tracking.datum.carrier);
+        }
+
+        Ok(tracking)
+    }
+
+    async fn confirm(&self, tx_hash: &str) -> Result<u64> {
+        let deadline = tokio::time::Instant::now() + self.confirmation_timeout;
+        loop {
+            if let Some(height) = self.oracle.chain().tx_block_height(tx_hash).await? {
+                return Ok(height);
+            }
+            if tokio::time::Instant::now() >= deadline {
+                bail!("Transaction {} not on-chain after {:?}", tx_hash, self.confirmation_timeout);
+            }
+
+            tokio::time::sleep(CONFIRMATION_POLL_INTERVAL.min(self.confirmation_timeout)).await;
+        }
+    }
+
+    async fn verify_datum(&self, tracking: &TrackingUTxO, tx_hash: &str) -> Result<()> {
+        let outbox = tracking.datum.outbox_address.to_string();
+        let datum = self
+            .oracle
+            .chain()
+            .shipment_datum_in(tx_hash, std::slice::from_ref(&outbox))
+            .await?
+            .with_context(|| format!("No shipment datum paid to {} in {}", outbox, tx_hash))?;
+
+        let signer = self.oracle.chain().signer_pkh();
+        let mismatches: Vec<String> = [
+            ("carrier", datum.carrier.as_str(), tracking.datum.carrier.as_str()),
+            ("tracking number", &datum.tracking_number.to_string(), &tracking.datum.tracking_number.to_string()),
+            ("status", datum.status.as_str(), SELF_TEST_STATUS),
+            ("oracle key hash", datum.oracle_pkh.as_str(), signer.as_str()),
+        ]
+        .into_iter()
+        .filter(|(_, actual, expected)| actual != expected)
+        .map(|(field, actual, expected)| format!("{} is {}, expected {}", field, actual, expected))
+        .collect();
+
+        if !mismatches.is_empty() {
+            bail!("Shipment datum mismatch: {}", mismatches.join("; "));
+        }
+
+        Ok(())
+    }
+}
+
+/// Lock a fresh tracking UTxO at the oracle address and wait until it is on-chain
+///
+/// The transaction is funded and signed with the key in `TEST_FUNDING_SK`
+/// (its enterprise address must hold enough ADA). Returns the `TxHash#TxIx`
+/// of the new tracking output.
+pub async fn provision_tracking_utxo(config: &Config, datum: &TrackingDatum) -> Result<String> {
+    let funding_sk = std::env::var("TEST_FUNDING_SK").context("TEST_FUNDING_SK not set")?;
+    let funding_key = SigningKeyMaterial::from_hex(&funding_sk).context("Invalid TEST_FUNDING_SK")?;
+    let funding_address = enterprise_address(&funding_key, &config.oracle_address)?;
+
+    let client = CardanoClient::new(config.clone())?;
+    let (_params, envelope) = client.prepare_track_shipment(&funding_address, datum).await?;
+    let signed = sign_envelope(&envelope, &funding_key)?;
+
+    let http_client = blockfrost_http_client(config)?;
+    let submitter = BlockfrostSubmitter::new(config.blockfrost_url.clone(), http_client.clone())
+        .with_project_id_configured(config.blockfrost_project_id.is_some());
+    let tx_hash = submitter.submit(signed.cbor).await?;
+
+    wait_for_confirmation(&http_client, &config.blockfrost_url, &tx_hash).await?;
+
+    // The tracking output is always the first output of the track-shipment tx
+    Ok(format!("{}#0", tx_hash))
+}
+
+/// Enterprise address for `key` on the same network as `reference_address`
+fn enterprise_address(key: &SigningKeyMaterial, reference_address: &str) -> Result<String> {
+    let reference = Address::from_bech32(reference_address)
+        .map_err(|e| anyhow!("Invalid address {}: {}", reference_address, e))?;
+    let network_id = match reference.network() {
+        Some(Network::Mainnet) => 0x01,
+        _ => 0x00,
+    };
+
+    let mut bytes = vec![0x60 | network_id];
+    bytes.extend_from_slice(Hasher::<224>::hash(&key.public_key()).as_ref());
+
+    Address::from_bytes(&bytes)
+        .and_then(|address| address.to_bech32())
+        .map_err(|e| anyhow!("Failed to build funding address: {}", e))
+}
+
+async fn wait_for_confirmation(http_client: &reqwest::Client, blockfrost_url: &str, tx_hash: &str) -> Result<()> {
+    let url = format!("{}/txs/{}", blockfrost_url, tx_hash);
+    let deadline = tokio::time::Instant::now() + CONFIRMATION_TIMEOUT;
+
+    loop {
+        let response = http_client.get(&url).send().await?;
+        if response.status().is_success() {
+            return Ok(());
+        }
+
+        if response.status() != reqwest::StatusCode::NOT_FOUND {
+            bail!("Blockfrost tx lookup failed (status {})", response.status());
+        }
+
+        if tokio::time::Instant::now() >= deadline {
+            bail!("Transaction {} not confirmed after {:?}", tx_hash, CONFIRMATION_TIMEOUT);
+        }
+
+        tokio::time::sleep(CONFIRMATION_POLL_INTERVAL).await;
+    }
+}
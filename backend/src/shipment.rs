@@ -1,62 +1,244 @@
-use anyhow::{Context, Result};
-use reqwest::Client;
+use anyhow::{Context, Result, anyhow, bail};
+use reqwest::header::HeaderMap;
+use reqwest::{Client, RequestBuilder, Response, StatusCode, Url};
+use std::time::Duration;
+use tracing::{Instrument, debug, info, warn};
+
+use crate::backoff;
 
 use crate::config::Config;
-use crate::models::{TrackingResponse, TrackingStatus};
+use crate::logging;
+use crate::metrics::Metrics;
+use crate::proxy;
+use crate::rate_limit::RateLimiter;
+use crate::models::{TrackRegistration, TrackingResponse, TrackingStatus};
+use crate::redact::{redact, register_config_secrets};
+use crate::tracking_provider::TrackingProvider;
+
+/// Wait before retrying a 429 without `Retry-After`, doubled on each retry after
+const RETRY_BASE_BACKOFF: Duration = Duration::from_secs(1);
+/// Longest wait before retrying a 429; a longer `Retry-After` is cut short
+const MAX_RETRY_WAIT: Duration = Duration::from_secs(60);
+
+/// Why Shippo gave no tracking status
+///
+/// Variants carrying a message display the full message, Shippo's answer included.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ShipmentError {
+    /// The API key is missing, invalid or revoked (401, 403), so no shipment can be fetched
+    #[error("{0}")]
+    Unauthorized(String),
+    /// Shippo does not know the carrier/tracking pair (404) and `SHIPPO_AUTO_REGISTER` is off
+    #[error("{carrier} {tracking_number} is not registered with Shippo")]
+    NotFound { carrier: String, tracking_number: String },
+    /// Shippo asks to slow down (429), for `retry_after` if it says how long
+    #[error("{message}")]
+    RateLimited { retry_after: Option<Duration>, message: String },
+    /// Shippo refused the request or answered with something that is not a tracking status
+    #[error("{0}")]
+    InvalidResponse(String),
+    /// Shippo could not be reached or failed (5xx)
+    #[error("{0}")]
+    Network(String),
+}
+
+impl ShipmentError {
+    /// Classify a non-success Shippo answer about `tracking_number`; `message` is what to display
+    pub fn from_response(
+        status: StatusCode,
+        headers: &HeaderMap,
+        message: String,
+        carrier: &str,
+        tracking_number: &str,
+    ) -> Self {
+        match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => ShipmentError::Unauthorized(message),
+            StatusCode::NOT_FOUND => ShipmentError::NotFound {
+                carrier: carrier.to_string(),
+                tracking_number: tracking_number.to_string(),
+            },
+            StatusCode::TOO_MANY_REQUESTS => {
+                ShipmentError::RateLimited { retry_after: backoff::retry_after(headers), message }
+            }
+            status if status.is_server_error() => ShipmentError::Network(message),
+            _ => ShipmentError::InvalidResponse(message),
+        }
+    }
+}
 
 pub struct ShipmentClient {
     config: Config,
     http_client: Client,
+    metrics: Metrics,
+    limiter: RateLimiter,
 }
 
 impl ShipmentClient {
     pub fn new(config: Config) -> Result<Self> {
-        let http_client = Client::builder()
-            .timeout(std::time::Duration::from_secs(30))
+        register_config_secrets(&config);
+
+        let builder = Client::builder().timeout(std::time::Duration::from_secs(30));
+        let shippo_proxy = config.http_proxy_shippo.as_ref().map(|proxy| proxy.expose().as_str());
+        let http_client = proxy::apply(builder, shippo_proxy, config.no_proxy.as_deref())?
             .build()
             .context("Failed to create HTTP client")?;
         
-        Ok(Self { config, http_client })
+        let limiter = RateLimiter::per_second_and_minute(config.shippo_max_rps, config.shippo_max_per_minute);
+        Ok(Self { config, http_client, metrics: Metrics::new(), limiter })
     }
 
+    /// Count fetched statuses and Shippo errors in `metrics`
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Latest status of `tracking_number`
+    ///
+    /// Every answer Shippo gives other than a status downcasts to `ShipmentError`. A pair
+    /// Shippo does not know is `NotFound`, or is registered with `POST /tracks/` first when
+    /// `SHIPPO_AUTO_REGISTER` is set.
     pub async fn fetch_shipment_status(&self, carrier: &str, tracking_number: &str) -> Result<TrackingStatus> {
-        let url = format!(
-            "https://api.goshippo.com/tracks/{}/{}",
-            carrier,
-            tracking_number
-        );
-
-        let response = self.http_client
-            .get(&url)
-            .header("Authorization", format!("ShippoToken {}", self.config.shippo_api_key))
-            .send()
+        let url = tracking_url(&self.config.shippo_url, carrier, tracking_number)?;
+        debug!(carrier, tracking_number, "Fetching tracking status from Shippo");
+
+        let mut result = self.request_status(url, carrier, tracking_number).await;
+        if self.config.shippo_auto_register && matches!(result, Err(ShipmentError::NotFound { .. })) {
+            info!(carrier, tracking_number, "📝 Registering the tracking number with Shippo");
+            result = self.register(carrier, tracking_number).await;
+        }
+
+        match &result {
+            Ok(_) => self.metrics.status_fetched(carrier),
+            Err(ShipmentError::NotFound { .. }) => {}
+            Err(_) => self.metrics.request_error("shippo"),
+        }
+
+        result.map_err(Into::into)
+    }
+
+    async fn request_status(&self, url: Url, carrier: &str, tracking_number: &str) -> Result<TrackingStatus, ShipmentError> {
+        let request = self.http_client.get(url);
+        let response = self
+            .send(request)
             .await
-            .context("Failed to send request to Shipment API")?;
+            .map_err(|e| ShipmentError::Network(format!("Failed to send request to Shipment API: {}", redact(&e.to_string()))))?;
 
         if !response.status().is_success() {
             let status = response.status();
+            let headers = response.headers().clone();
             let body = response.text().await.unwrap_or_default();
-            anyhow::bail!(
-                "Shipment API query failed (status {}): {}",
-                status,
-                body
-            );
+            let message = format!("Shipment API query failed (status {}): {}", status, redact(&body));
+            return Err(ShipmentError::from_response(status, &headers, message, carrier, tracking_number));
         }
 
         let tracking: TrackingResponse = response
             .json()
             .await
-            .context("Failed to parse Shipment API response")?;
+            .map_err(|e| ShipmentError::InvalidResponse(format!("Failed to parse Shipment API response: {}", e)))?;
 
+        debug!(status = %tracking.tracking_status.status, "Shippo answered");
         Ok(tracking.tracking_status)
     }
+
+    /// Send a Shippo request once `SHIPPO_MAX_RPS` and `SHIPPO_MAX_PER_MINUTE` allow it
+    ///
+    /// A 429 is retried up to `SHIPPO_MAX_RETRIES` times, after `Retry-After` or an
+    /// exponential backoff, each retry waiting for the rate limiter again. The last
+    /// answer is returned whatever its status, for the caller to classify.
+    async fn send(&self, request: RequestBuilder) -> reqwest::Result<Response> {
+        let request = request.header("Authorization", format!("ShippoToken {}", self.config.shippo_api_key.expose()));
+        let mut attempt = 0;
+        loop {
+            self.limiter.acquire().await;
+            let (client, attempt_request) = request.try_clone().expect("Shippo requests have in-memory bodies").build_split();
+            let attempt_request = attempt_request?;
+            let span = logging::http_span("shippo", attempt_request.method(), attempt_request.url().as_str());
+            let response = client.execute(attempt_request).instrument(span).await?;
+            if response.status() != StatusCode::TOO_MANY_REQUESTS || attempt >= self.config.shippo_max_retries {
+                return Ok(response);
+            }
+
+            let wait = backoff::retry_after(response.headers())
+                .unwrap_or_else(|| backoff::exponential(RETRY_BASE_BACKOFF, attempt))
+                .min(MAX_RETRY_WAIT);
+            attempt += 1;
+            warn!(
+                attempt,
+                max_retries = self.config.shippo_max_retries,
+                wait_ms = wait.as_millis() as u64,
+                "⚠️  Shippo is rate limiting, retrying",
+            );
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Register a webhook-less tracker and read the status Shippo answers with
+    async fn register(&self, carrier: &str, tracking_number: &str) -> Result<TrackingStatus, ShipmentError> {
+        // SHIPPO_URL already made a valid tracking URL, so it can take a path
+        let mut url = Url::parse(&self.config.shippo_url)
+            .map_err(|e| ShipmentError::InvalidResponse(format!("Invalid SHIPPO_URL '{}': {}", self.config.shippo_url, e)))?;
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.pop_if_empty().extend(["tracks", ""]);
+        }
+
+        let request = self.http_client
+            .post(url)
+            .json(&serde_json::json!({ "carrier": carrier, "tracking_number": tracking_number }));
+        let response = self
+            .send(request)
+            .await
+            .map_err(|e| ShipmentError::Network(format!("Failed to send registration to Shipment API: {}", redact(&e.to_string()))))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = response.text().await.unwrap_or_default();
+            let message = format!("Shipment API registration failed (status {}): {}", status, redact(&body));
+            return Err(ShipmentError::from_response(status, &headers, message, carrier, tracking_number));
+        }
+
+        let registration: TrackRegistration = response
+            .json()
+            .await
+            .map_err(|e| ShipmentError::InvalidResponse(format!("Failed to parse Shipment API registration response: {}", e)))?;
+
+        let tracking_status = registration.tracking_status.unwrap_or_else(|| TrackingStatus {
+            status: "UNKNOWN".into(),
+            status_details: "Registered with Shippo, no status yet".to_string(),
+            status_date: None,
+        });
+        debug!(status = %tracking_status.status, "Shippo registered the tracking number");
+        Ok(tracking_status)
+    }
 }
 
-pub fn get_status(tracking_status: &TrackingStatus) -> Option<String> {
-    match tracking_status.status.as_str() {
-        "DELIVERED" => Some("DELIVERED".to_string()),
-        "RETURNED" => Some("NOT_DELIVERED".to_string()),
-        "FAILURE" => Some("NOT_DELIVERED".to_string()),
-        _ => None,
+#[async_trait::async_trait]
+impl TrackingProvider for ShipmentClient {
+    async fn fetch_status(&self, carrier: &str, tracking_number: &str) -> Result<TrackingStatus> {
+        self.fetch_shipment_status(carrier, tracking_number).await
     }
 }
+
+/// Shippo tracking URL of `tracking_number`, each path segment percent-encoded
+///
+/// Carrier and tracking number come from on-chain datums anyone can write, so
+/// control characters are refused and `/`, `#`, `?` or spaces cannot leave their segment.
+pub fn tracking_url(shippo_url: &str, carrier: &str, tracking_number: &str) -> Result<Url> {
+    for (name, value) in [("carrier", carrier), ("tracking number", tracking_number)] {
+        if value.is_empty() {
+            bail!("{} is empty", name);
+        }
+        if value.chars().any(char::is_control) {
+            bail!("{} {:?} contains control characters", name, value);
+        }
+    }
+
+    let mut url = Url::parse(shippo_url).with_context(|| format!("Invalid SHIPPO_URL '{}'", shippo_url))?;
+    url.path_segments_mut()
+        .map_err(|_| anyhow!("SHIPPO_URL '{}' cannot have a path", shippo_url))?
+        .pop_if_empty()
+        .extend(["tracks", carrier, tracking_number]);
+
+    Ok(url)
+}
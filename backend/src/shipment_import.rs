@@ -0,0 +1,241 @@
+use anyhow::{Context, Result, bail};
+use pallas::ledger::addresses::Address;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::lifecycle::{LifecycleTransition, ShipmentLifecycle};
+use crate::models::{TrackingDatum, TrackingUTxO, UtxoRef};
+use crate::state::StateStore;
+
+/// Columns of a shipment import CSV, in order; a first line naming them is a header
+pub const COLUMNS: [&str; 4] = ["utxo_ref", "carrier", "tracking_number", "outbox"];
+
+/// One open shipment listed in an import CSV, validated but not yet checked against the chain
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportRow {
+    /// 1-based line number in the CSV
+    pub line: usize,
+    pub utxo_ref: String,
+    /// Lowercase Shippo carrier token
+    pub carrier: String,
+    pub tracking_number: String,
+    /// Bech32 outbox address
+    pub outbox: String,
+}
+
+/// `TxHash#TxIx` with a 64 hex character hash, lowercased
+pub fn parse_utxo_ref(value: &str) -> Result<String> {
+    let utxo_ref: UtxoRef = value.parse().context("Invalid UTxO ref")?;
+    Ok(utxo_ref.to_string())
+}
+
+/// Shippo carrier token: trimmed, lowercase, letters, digits and underscores only
+pub fn normalize_carrier(value: &str) -> Result<String> {
+    let carrier = value.trim().to_ascii_lowercase();
+    if carrier.is_empty() || !carrier.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        bail!("carrier must be a Shippo carrier token such as usps, got '{}'", value.trim());
+    }
+
+    Ok(carrier)
+}
+
+impl ImportRow {
+    /// Parse and validate the CSV `line` numbered `number`
+    pub fn parse(number: usize, line: &str) -> Result<Self> {
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let [utxo_ref, carrier, tracking_number, outbox] = fields.as_slice() else {
+            bail!("expected {} columns ({}), got {}", COLUMNS.len(), COLUMNS.join(","), fields.len());
+        };
+
+        if tracking_number.is_empty() {
+            bail!("tracking number is empty");
+        }
+        Address::from_bech32(outbox).with_context(|| format!("outbox '{}' is not a bech32 address", outbox))?;
+
+        Ok(ImportRow {
+            line: number,
+            utxo_ref: parse_utxo_ref(utxo_ref)?,
+            carrier: normalize_carrier(carrier)?,
+            tracking_number: tracking_number.to_string(),
+            outbox: outbox.to_string(),
+        })
+    }
+
+    /// How the on-chain tracking datum disagrees with this row, if it does
+    ///
+    /// Privacy-mode datums hold a tracking hash, which the row must then give as hex.
+    pub fn mismatches(&self, datum: &TrackingDatum) -> Vec<String> {
+        let mut mismatches = Vec::new();
+
+        if datum.carrier.to_ascii_lowercase() != self.carrier {
+            mismatches.push(format!("carrier is {} on-chain", datum.carrier));
+        }
+        let tracking_number = datum.tracking_number.to_string();
+        if tracking_number != self.tracking_number {
+            mismatches.push(format!("tracking number is {} on-chain", tracking_number));
+        }
+        let outbox = Address::from_bech32(&self.outbox).map(|address| address.to_vec()).ok();
+        if outbox != Some(datum.outbox_address.to_vec()) {
+            let onchain = datum.outbox_address.to_bech32().unwrap_or_else(|_| datum.outbox_address.to_hex());
+            mismatches.push(format!("outbox is {} on-chain", onchain));
+        }
+
+        mismatches
+    }
+}
+
+/// What happened to one CSV row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportOutcome {
+    /// Lifecycle entries were written to the state store
+    Imported,
+    /// Already tracked, or its datum does not match and `--force` was not given
+    Skipped,
+    /// Malformed, or not an open tracking UTxO at the oracle address
+    Errored,
+}
+
+impl ImportOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportOutcome::Imported => "imported",
+            ImportOutcome::Skipped => "skipped",
+            ImportOutcome::Errored => "errored",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ImportEntry {
+    pub line: usize,
+    /// The UTxO ref as written in the CSV
+    pub utxo_ref: String,
+    pub outcome: ImportOutcome,
+    /// Why the row was skipped or errored, or the mismatch accepted with `--force`
+    pub reason: Option<String>,
+}
+
+/// Result of seeding the state store from an import CSV
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub skipped: usize,
+    pub errored: usize,
+    pub entries: Vec<ImportEntry>,
+}
+
+/// Seed `state` with the open shipments listed in `csv`
+///
+/// Each row must name one of the tracking UTxOs in `open` (as scanned at the
+/// oracle address) and match its datum. Rows whose datum does not match are
+/// skipped unless `force` is set. Imported shipments are recorded as
+/// discovered and awaiting the carrier at `now`, so the next run polls them
+/// without discovering them again. Shipments the state store already tracks
+/// are left alone, so an import can be repeated.
+pub async fn import_shipments(
+    csv: &str,
+    open: &[TrackingUTxO],
+    state: &dyn StateStore,
+    force: bool,
+    now: u64,
+) -> Result<ImportReport> {
+    let open: HashMap<String, &TrackingDatum> = open
+        .iter()
+        .map(|utxo| (utxo.utxo_ref.to_string(), &utxo.datum))
+        .collect();
+
+    let mut report = ImportReport::default();
+    for (index, line) in csv.lines().enumerate() {
+        let number = index + 1;
+        if line.trim().is_empty() || (index == 0 && line.trim().starts_with(COLUMNS[0])) {
+            continue;
+        }
+        let raw_ref = line.split(',').next().unwrap_or_default().trim().to_string();
+
+        let (outcome, reason) = match ImportRow::parse(number, line) {
+            Err(e) => (ImportOutcome::Errored, Some(format!("{:#}", e))),
+            Ok(row) => import_row(&row, &open, state, force, now).await?,
+        };
+        report.push(ImportEntry { line: number, utxo_ref: raw_ref, outcome, reason });
+    }
+
+    Ok(report)
+}
+
+async fn import_row(
+    row: &ImportRow,
+    open: &HashMap<String, &TrackingDatum>,
+    state: &dyn StateStore,
+    force: bool,
+    now: u64,
+) -> Result<(ImportOutcome, Option<String>)> {
+    let Some(datum) = open.get(&row.utxo_ref) else {
+        return Ok((ImportOutcome::Errored, Some("not an open tracking UTxO at the oracle address".to_string())));
+    };
+
+    let mismatches = datum_mismatch(row, datum);
+    if mismatches.is_some() && !force {
+        return Ok((ImportOutcome::Skipped, mismatches));
+    }
+
+    if let Some(current) = state.lifecycle(&row.utxo_ref).await?.pop() {
+        return Ok((ImportOutcome::Skipped, Some(format!("already tracked as {}", current.state))));
+    }
+
+    for lifecycle in [ShipmentLifecycle::Discovered, ShipmentLifecycle::AwaitingCarrier] {
+        state
+            .record_transition(&LifecycleTransition { utxo_ref: row.utxo_ref.clone(), state: lifecycle, at: now })
+            .await?;
+    }
+
+    Ok((ImportOutcome::Imported, mismatches.map(|mismatches| format!("forced: {}", mismatches))))
+}
+
+fn datum_mismatch(row: &ImportRow, datum: &TrackingDatum) -> Option<String> {
+    let mismatches = row.mismatches(datum);
+    (!mismatches.is_empty()).then(|| format!("datum mismatch: {}", mismatches.join(", ")))
+}
+
+impl ImportReport {
+    fn push(&mut self, entry: ImportEntry) {
+        match entry.outcome {
+            ImportOutcome::Imported => self.imported += 1,
+            ImportOutcome::Skipped => self.skipped += 1,
+            ImportOutcome::Errored => self.errored += 1,
+        }
+        self.entries.push(entry);
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize import report")
+    }
+
+    /// Markdown summary listing every row that was not imported cleanly
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# Shipment Import\n\n");
+        out.push_str(&format!("- Imported: {}\n", self.imported));
+        out.push_str(&format!("- Skipped: {}\n", self.skipped));
+        out.push_str(&format!("- Errored: {}\n", self.errored));
+
+        let noted: Vec<&ImportEntry> = self.entries.iter().filter(|entry| entry.reason.is_some()).collect();
+        if noted.is_empty() {
+            return out;
+        }
+
+        out.push_str("\n| Line | UTxO | Outcome | Reason |\n");
+        out.push_str("|---|---|---|---|\n");
+        for entry in noted {
+            out.push_str(&format!(
+                "| {} | `{}` | {} | {} |\n",
+                entry.line,
+                entry.utxo_ref,
+                entry.outcome.as_str(),
+                entry.reason.as_deref().unwrap_or_default()
+            ));
+        }
+
+        out
+    }
+}
@@ -0,0 +1,167 @@
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+use crate::config::Config;
+use crate::state::{ShipmentState, StateStore};
+
+/// Default `SHIPPO_DEGRADED_TRANSIT_HOURS`
+pub const DEFAULT_DEGRADED_TRANSIT_HOURS: u64 = 72;
+
+/// Carrier status of shipments still polled once the budget is spent
+const NEAR_FINAL_STATUS: &str = "TRANSIT";
+
+/// Cursor holding the last billing period whose spent budget was notified
+const ALERT_CURSOR: &str = "shippo_budget_alerted";
+
+/// Billing period containing `now`: the calendar month in `timezone`, as `YYYY-MM`
+pub fn period(now: DateTime<Utc>, timezone: Tz) -> String {
+    now.with_timezone(&timezone).format("%Y-%m").to_string()
+}
+
+/// Monthly cap on Shippo tracking API calls (`SHIPPO_MONTHLY_BUDGET`)
+///
+/// Once the calls of a billing period reach the budget, runs only poll
+/// shipments the carrier has reported in transit for longer than
+/// `transit_age`, or with a final status not closed yet, until the period
+/// rolls over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShippoBudget {
+    pub monthly_calls: u64,
+    pub transit_age: chrono::Duration,
+}
+
+/// Builds the budget configured by `SHIPPO_MONTHLY_BUDGET`, if any
+pub fn from_config(config: &Config) -> Option<ShippoBudget> {
+    config.shippo_monthly_budget.map(|monthly_calls| {
+        ShippoBudget::new(monthly_calls)
+            .with_transit_age(chrono::Duration::hours(config.shippo_degraded_transit_hours as i64))
+    })
+}
+
+impl ShippoBudget {
+    pub fn new(monthly_calls: u64) -> Self {
+        Self {
+            monthly_calls,
+            transit_age: chrono::Duration::hours(DEFAULT_DEGRADED_TRANSIT_HOURS as i64),
+        }
+    }
+
+    /// Keep polling shipments in transit for longer than `age` once the budget is spent
+    pub fn with_transit_age(mut self, age: chrono::Duration) -> Self {
+        self.transit_age = age;
+        self
+    }
+
+    /// Whether `calls` made this period leave nothing for ordinary polling
+    pub fn is_spent(&self, calls: u64) -> bool {
+        calls >= self.monthly_calls
+    }
+
+    /// Whether a shipment is worth polling on a spent budget
+    ///
+    /// It is when the carrier has reported it in transit for longer than
+    /// `transit_age`, or when its final status is known but not closed yet.
+    pub fn is_near_final(&self, shipment: Option<&ShipmentState>, now: DateTime<Utc>) -> bool {
+        let Some(shipment) = shipment else {
+            return false;
+        };
+        if shipment.status.is_some() && shipment.closed_tx_hash.is_none() {
+            return true;
+        }
+
+        shipment.carrier_status.as_deref() == Some(NEAR_FINAL_STATUS)
+            && shipment
+                .carrier_status_since
+                .is_some_and(|since| now.timestamp() - since as i64 > self.transit_age.num_seconds())
+    }
+}
+
+/// Record that the budget of `period` was notified as spent; `false` if it already was
+///
+/// The period is kept in the state store, so a restart does not notify again.
+pub async fn mark_alerted(state: &dyn StateStore, period: &str) -> Result<bool> {
+    if state.cursor(ALERT_CURSOR).await?.as_deref() == Some(period) {
+        return Ok(false);
+    }
+
+    state.set_cursor(ALERT_CURSOR, period).await?;
+    Ok(true)
+}
+
+/// Shippo tracking API calls of one billing period
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct UsageReport {
+    /// Billing month, `YYYY-MM`
+    pub period: String,
+    pub calls: u64,
+    /// `SHIPPO_MONTHLY_BUDGET`, if set
+    pub budget: Option<u64>,
+    pub by_carrier: BTreeMap<String, u64>,
+    pub by_outbox: BTreeMap<String, u64>,
+}
+
+impl UsageReport {
+    pub async fn from_state(state: &dyn StateStore, period: &str, budget: Option<u64>) -> Result<Self> {
+        let mut report = UsageReport {
+            period: period.to_string(),
+            budget,
+            ..Default::default()
+        };
+
+        for calls in state.tracking_calls(period).await? {
+            report.calls += calls.calls;
+            *report.by_carrier.entry(calls.carrier).or_default() += calls.calls;
+            *report.by_outbox.entry(calls.outbox_address).or_default() += calls.calls;
+        }
+
+        Ok(report)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize usage report")
+    }
+
+    /// Markdown summary with one table per carrier and per outbox address
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# Shippo Usage\n\n");
+        out.push_str(&format!("- Period: {}\n", self.period));
+        match self.budget {
+            Some(budget) => out.push_str(&format!("- Calls: {} of {}\n", self.calls, budget)),
+            None => out.push_str(&format!("- Calls: {}\n", self.calls)),
+        }
+        out.push('\n');
+
+        if self.calls == 0 {
+            out.push_str("No tracking calls in this period.\n");
+            return out;
+        }
+
+        out.push_str("| Carrier | Calls |\n");
+        out.push_str("|---|---|\n");
+        for (carrier, calls) in &self.by_carrier {
+            out.push_str(&format!("| {} | {} |\n", carrier, calls));
+        }
+
+        out.push_str("\n| Outbox | Calls |\n");
+        out.push_str("|---|---|\n");
+        for (outbox, calls) in &self.by_outbox {
+            out.push_str(&format!("| `{}` | {} |\n", outbox, calls));
+        }
+
+        out
+    }
+}
+
+/// Parse a `YYYY-MM` billing period
+pub fn parse_period(value: &str) -> Result<String> {
+    let value = value.trim();
+    if value.len() != 7 || chrono::NaiveDate::parse_from_str(&format!("{}-01", value), "%Y-%m-%d").is_err() {
+        bail!("expected a YYYY-MM month, got '{}'", value);
+    }
+
+    Ok(value.to_string())
+}
@@ -0,0 +1,403 @@
+use anyhow::{Context, Result, anyhow, bail};
+use ed25519_dalek::hazmat::{ExpandedSecretKey, raw_sign};
+use ed25519_dalek::{SecretKey, Signature, VerifyingKey};
+use pallas::codec::utils::{Bytes, KeepRaw, NonEmptySet};
+use pallas::crypto::hash::Hasher;
+use pallas::ledger::{primitives::conway::VKeyWitness, traverse::MultiEraTx};
+use serde::Deserialize;
+use sha2::Sha512;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use tx3_sdk::trp::TxEnvelope;
+use zeroize::Zeroizing;
+
+use crate::config::Config;
+use crate::mnemonic;
+use crate::redact::register_secret;
+
+/// cardano-cli text envelope type of a (non-extended) payment signing key
+pub const PAYMENT_SKEY_TYPE: &str = "PaymentSigningKeyShelley_ed25519";
+
+/// Ed25519 key used to witness close-shipment transactions
+///
+/// Held expanded, so keys given as a 32-byte seed and BIP32-Ed25519 keys
+/// derived from a mnemonic sign the same way.
+pub struct SigningKeyMaterial {
+    key: ExpandedSecretKey,
+    verifying_key: VerifyingKey,
+}
+
+impl SigningKeyMaterial {
+    pub fn from_bytes(bytes: &SecretKey) -> Self {
+        Self::from_expanded(ExpandedSecretKey::from(bytes))
+    }
+
+    pub fn from_expanded(key: ExpandedSecretKey) -> Self {
+        let verifying_key = VerifyingKey::from(&key);
+        Self { key, verifying_key }
+    }
+
+    /// Read a cardano-cli `PaymentSigningKeyShelley_ed25519` text envelope (`payment.skey`)
+    pub fn from_text_envelope(json: &str) -> Result<Self> {
+        Self::from_hex(&text_envelope_key(json)?)
+    }
+
+    /// The first payment key (`1852'/1815'/0'/0/0`) of a BIP-39 mnemonic
+    pub fn from_mnemonic(phrase: &str) -> Result<Self> {
+        mnemonic::payment_key(phrase)
+    }
+
+    /// Parse a hex-encoded 32-byte Ed25519 secret key
+    pub fn from_hex(hex_key: &str) -> Result<Self> {
+        let bytes = Zeroizing::new(hex::decode(hex_key.trim()).context("Signing key must be hex-encoded")?);
+        let bytes: Zeroizing<[u8; 32]> = Zeroizing::new(
+            bytes
+                .as_slice()
+                .try_into()
+                .map_err(|_| anyhow!("Signing key must be 32 bytes, got {}", bytes.len()))?,
+        );
+
+        Ok(Self::from_bytes(&bytes))
+    }
+
+    pub fn public_key(&self) -> [u8; 32] {
+        self.verifying_key.to_bytes()
+    }
+
+    fn sign(&self, message: &[u8]) -> Signature {
+        raw_sign::<Sha512>(&self.key, message, &self.verifying_key)
+    }
+
+    /// Hex-encoded blake2b-224 hash of the public key, as checked by the validator
+    pub fn pkh(&self) -> String {
+        hex::encode(Hasher::<224>::hash(&self.public_key()))
+    }
+}
+
+#[derive(Deserialize)]
+struct TextEnvelope {
+    #[serde(rename = "type")]
+    key_type: String,
+    #[serde(rename = "cborHex")]
+    cbor_hex: String,
+}
+
+/// Hex key carried by a `PaymentSigningKeyShelley_ed25519` text envelope
+fn text_envelope_key(json: &str) -> Result<Zeroizing<String>> {
+    let envelope: TextEnvelope = serde_json::from_str(json).context("Signing key file is not a cardano-cli text envelope")?;
+    let cbor_hex = Zeroizing::new(envelope.cbor_hex);
+    if envelope.key_type != PAYMENT_SKEY_TYPE {
+        bail!("Signing key file has type {}, expected {}", envelope.key_type, PAYMENT_SKEY_TYPE);
+    }
+
+    // CBOR byte string header (0x58 0x20) followed by the 32-byte key
+    let hex_key = cbor_hex
+        .strip_prefix("5820")
+        .context("Signing key file cborHex must be a 32-byte CBOR byte string")?;
+
+    Ok(Zeroizing::new(hex_key.to_string()))
+}
+
+impl fmt::Debug for SigningKeyMaterial {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigningKeyMaterial")
+            .field("public_key", &hex::encode(self.public_key()))
+            .finish_non_exhaustive()
+    }
+}
+
+/// Oracle signing keys, with the key each validator expects
+///
+/// Several keys can be active at once so `ORACLE_SK` can be rotated without
+/// losing the ability to close shipments created under an older key.
+#[derive(Debug)]
+pub struct OracleKeyring {
+    keys: Vec<SigningKeyMaterial>,
+    validator_keys: HashMap<String, String>,
+}
+
+impl OracleKeyring {
+    /// Build a keyring; the first key is used for validators without an explicit selection
+    pub fn new(keys: Vec<SigningKeyMaterial>) -> Result<Self> {
+        if keys.is_empty() {
+            bail!("At least one oracle signing key is required");
+        }
+
+        for (index, key) in keys.iter().enumerate() {
+            if keys[..index].iter().any(|other| other.pkh() == key.pkh()) {
+                bail!("Duplicate oracle signing key (pkh {})", key.pkh());
+            }
+        }
+
+        Ok(Self {
+            keys,
+            validator_keys: HashMap::new(),
+        })
+    }
+
+    /// Load the configured key source and apply `ORACLE_VALIDATOR_KEYS` and `ORACLE_PKH`
+    ///
+    /// Keys come from `ORACLE_SK_FILE`, `ORACLE_MNEMONIC` or else `ORACLE_SKS`/`ORACLE_SK`.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let keys = if let Some(path) = &config.oracle_sk_file {
+            vec![load_skey_file(Path::new(path)).context("Invalid ORACLE_SK_FILE")?]
+        } else if let Some(phrase) = &config.oracle_mnemonic {
+            vec![SigningKeyMaterial::from_mnemonic(phrase.expose()).context("Invalid ORACLE_MNEMONIC")?]
+        } else {
+            config
+                .oracle_sks
+                .iter()
+                .enumerate()
+                .map(|(index, entry)| {
+                    load_key(entry.expose()).with_context(|| format!("Invalid oracle signing key #{}", index + 1))
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        let mut keyring = Self::new(keys)?;
+        for (validator, pkh) in &config.oracle_validator_keys {
+            keyring = keyring.with_validator_key(validator, pkh)?;
+        }
+
+        if let Some(pkh) = &config.oracle_pkh
+            && !keyring.validator_keys.contains_key(&config.validator_script_ref.to_string())
+        {
+            keyring = keyring
+                .with_validator_key(&config.validator_script_ref.to_string(), pkh)
+                .context("ORACLE_PKH does not match any oracle signing key")?;
+        }
+
+        Ok(keyring)
+    }
+
+    /// Sign for `validator_script_ref` with the key whose hash is `pkh`
+    pub fn with_validator_key(mut self, validator_script_ref: &str, pkh: &str) -> Result<Self> {
+        let pkh = pkh.trim().to_lowercase();
+        if !self.keys.iter().any(|key| key.pkh() == pkh) {
+            bail!("No oracle signing key with pkh {} (validator {})", pkh, validator_script_ref);
+        }
+
+        self.validator_keys.insert(validator_script_ref.to_string(), pkh);
+        Ok(self)
+    }
+
+    /// The key the given validator expects, defaulting to the first key
+    pub fn select(&self, validator_script_ref: &str) -> &SigningKeyMaterial {
+        self.validator_keys
+            .get(validator_script_ref)
+            .and_then(|pkh| self.keys.iter().find(|key| &key.pkh() == pkh))
+            .unwrap_or(&self.keys[0])
+    }
+
+    pub fn pkhs(&self) -> Vec<String> {
+        self.keys.iter().map(SigningKeyMaterial::pkh).collect()
+    }
+
+    /// Take the key `select` returns for `validator_script_ref`
+    pub fn into_selected(mut self, validator_script_ref: &str) -> SigningKeyMaterial {
+        let pkh = self.select(validator_script_ref).pkh();
+        let index = self.keys.iter().position(|key| key.pkh() == pkh).unwrap_or(0);
+        self.keys.swap_remove(index)
+    }
+}
+
+/// Read a key given inline as hex or as a path to a file holding the hex key
+fn load_key(entry: &str) -> Result<SigningKeyMaterial> {
+    let path = Path::new(entry);
+    if !path.is_file() {
+        return SigningKeyMaterial::from_hex(entry);
+    }
+
+    let contents = Zeroizing::new(
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read key file {}", path.display()))?,
+    );
+    register_secret(contents.trim());
+
+    SigningKeyMaterial::from_hex(&contents)
+}
+
+/// Read a cardano-cli `payment.skey` file
+fn load_skey_file(path: &Path) -> Result<SigningKeyMaterial> {
+    let contents = Zeroizing::new(
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read key file {}", path.display()))?,
+    );
+    let hex_key = text_envelope_key(&contents)?;
+    register_secret(&hex_key);
+
+    SigningKeyMaterial::from_hex(&hex_key)
+}
+
+/// Raw Ed25519 verifying key
+pub type VerifyingKeyBytes = [u8; 32];
+
+/// Raw Ed25519 signature
+pub type SignatureBytes = [u8; 64];
+
+/// Signs the hashes of close-shipment transactions with the oracle key
+#[async_trait::async_trait]
+pub trait TxSigner: Send + Sync {
+    /// Hex-encoded blake2b-224 hash of the verifying key, passed to the validator as `oracle_pkh`
+    fn pkh(&self) -> String;
+
+    /// Sign a transaction hash, returning the verifying key and signature of the vkey witness
+    async fn sign(&self, tx_hash: &[u8]) -> Result<(VerifyingKeyBytes, SignatureBytes)>;
+}
+
+/// Where close transactions are signed (`ORACLE_SIGNER`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SignerKind {
+    /// In-memory keys from `ORACLE_SKS`
+    #[default]
+    Local,
+    /// The AWS KMS key `KMS_KEY_ID` (needs the `kms` feature)
+    Kms,
+}
+
+impl FromStr for SignerKind {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "local" => Ok(SignerKind::Local),
+            "kms" => Ok(SignerKind::Kms),
+            other => Err(anyhow!("expected local or kms, got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for SignerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SignerKind::Local => "local",
+            SignerKind::Kms => "kms",
+        })
+    }
+}
+
+/// Builds the signer selected by `ORACLE_SIGNER` for the configured validator
+pub fn from_config(config: &Config) -> Result<Box<dyn TxSigner>> {
+    match config.oracle_signer {
+        SignerKind::Local => {
+            let key = OracleKeyring::from_config(config)?.into_selected(&config.validator_script_ref.to_string());
+            Ok(Box::new(LocalSigner::new(key)))
+        }
+        #[cfg(feature = "kms")]
+        SignerKind::Kms => Ok(Box::new(crate::kms::KmsSigner::from_config(config)?)),
+        #[cfg(not(feature = "kms"))]
+        SignerKind::Kms => bail!("ORACLE_SIGNER=kms needs the oracle built with the `kms` feature"),
+    }
+}
+
+/// Signs with an in-memory Ed25519 key
+#[derive(Debug)]
+pub struct LocalSigner {
+    key: SigningKeyMaterial,
+}
+
+impl LocalSigner {
+    pub fn new(key: SigningKeyMaterial) -> Self {
+        Self { key }
+    }
+}
+
+#[async_trait::async_trait]
+impl TxSigner for LocalSigner {
+    fn pkh(&self) -> String {
+        self.key.pkh()
+    }
+
+    async fn sign(&self, tx_hash: &[u8]) -> Result<(VerifyingKeyBytes, SignatureBytes)> {
+        Ok((self.key.public_key(), self.key.sign(tx_hash).to_bytes()))
+    }
+}
+
+/// A witnessed transaction ready for submission
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedTx {
+    pub hash: String,
+    pub cbor: Vec<u8>,
+}
+
+impl SignedTx {
+    pub fn to_hex(&self) -> String {
+        hex::encode(&self.cbor)
+    }
+}
+
+/// The hash a TRP envelope asks to sign is not the hash of its transaction body
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Envelope hash {envelope_hash} does not match the transaction body hash {body_hash}, refusing to sign")]
+pub struct EnvelopeHashMismatch {
+    pub envelope_hash: String,
+    pub body_hash: String,
+}
+
+/// Fee declared by the body of a resolved TRP envelope, in lovelace
+pub fn envelope_fee(envelope: &TxEnvelope) -> Result<u64> {
+    let bytes = hex::decode(&envelope.tx).context("Envelope tx must be hex-encoded")?;
+    let tx = MultiEraTx::decode(&bytes).context("Failed to decode envelope tx")?;
+
+    tx.fee().ok_or_else(|| anyhow!("Envelope tx declares no fee"))
+}
+
+/// Sign a resolved TRP envelope and inject the vkey witness into its witness set
+///
+/// The transaction body and auxiliary data are re-emitted from their original
+/// bytes, so the transaction hash is unchanged by signing. The envelope hash is
+/// only signed if it is the blake2b-256 of those body bytes; otherwise the TRP
+/// server would choose what the oracle key witnesses.
+pub fn sign_envelope(envelope: &TxEnvelope, key: &SigningKeyMaterial) -> Result<SignedTx> {
+    let tx_hash = checked_tx_hash(envelope)?;
+    let signature = key.sign(&tx_hash);
+
+    witness_envelope(envelope, key.public_key(), signature.to_bytes())
+}
+
+/// `sign_envelope` with the witness produced by `signer`
+pub async fn sign_envelope_with(envelope: &TxEnvelope, signer: &dyn TxSigner) -> Result<SignedTx> {
+    let tx_hash = checked_tx_hash(envelope)?;
+    let (vkey, signature) = signer.sign(&tx_hash).await?;
+
+    witness_envelope(envelope, vkey, signature)
+}
+
+/// The envelope hash as bytes, once checked against the transaction body
+fn checked_tx_hash(envelope: &TxEnvelope) -> Result<Vec<u8>> {
+    let tx_hash_bytes = hex::decode(&envelope.hash).context("Envelope hash must be hex-encoded")?;
+    if tx_hash_bytes.len() != 32 {
+        bail!("Envelope hash must be 32 bytes, got {}", tx_hash_bytes.len());
+    }
+
+    let bytes = hex::decode(&envelope.tx).context("Envelope tx must be hex-encoded")?;
+    let tx = MultiEraTx::decode(&bytes).context("Failed to decode envelope tx")?;
+
+    let body_hash = tx.hash().to_string();
+    if !body_hash.eq_ignore_ascii_case(&envelope.hash) {
+        return Err(EnvelopeHashMismatch { envelope_hash: envelope.hash.clone(), body_hash }.into());
+    }
+
+    Ok(tx_hash_bytes)
+}
+
+fn witness_envelope(envelope: &TxEnvelope, vkey: VerifyingKeyBytes, signature: SignatureBytes) -> Result<SignedTx> {
+    let bytes = hex::decode(&envelope.tx).context("Envelope tx must be hex-encoded")?;
+    let tx = MultiEraTx::decode(&bytes).context("Failed to decode envelope tx")?;
+
+    let witness = VKeyWitness {
+        vkey: Bytes::from(vkey.to_vec()),
+        signature: Bytes::from(signature.to_vec()),
+    };
+
+    let mut tx = tx.as_conway().ok_or(anyhow!("Unsupported tx era"))?.to_owned();
+
+    let mut witness_set = tx.transaction_witness_set.unwrap();
+    witness_set.vkeywitness = NonEmptySet::from_vec(vec![witness]);
+    tx.transaction_witness_set = KeepRaw::from(witness_set);
+
+    Ok(SignedTx {
+        hash: envelope.hash.clone(),
+        cbor: pallas::codec::minicbor::to_vec(&tx)?,
+    })
+}
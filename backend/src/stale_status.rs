@@ -0,0 +1,45 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::BTreeSet;
+
+use crate::config::Config;
+use crate::models::CarrierStatus;
+
+/// Carrier statuses that go stale by default: labels created but never scanned, or unknown to the carrier
+pub const DEFAULT_STALE_STATUSES: [&str; 2] = ["PRE_TRANSIT", "UNKNOWN"];
+
+/// Closes shipments stuck in a non-final carrier status (`STALE_STATUS_TIMEOUT_DAYS`)
+///
+/// A tracking UTxO's age is the block time of the transaction that created it.
+/// `TRANSIT` only goes stale when `STALE_STATUSES` lists it.
+#[derive(Debug, Clone)]
+pub struct StaleStatusPolicy {
+    timeout: Duration,
+    statuses: BTreeSet<String>,
+}
+
+/// Parse a comma-separated carrier status list, trimmed and uppercase
+pub fn parse_statuses(value: &str) -> BTreeSet<String> {
+    value.split(',').map(|status| status.trim().to_uppercase()).filter(|status| !status.is_empty()).collect()
+}
+
+/// Policy for the `STALE_STATUS_TIMEOUT_DAYS` of `config`; `None` when it is not set
+pub fn from_config(config: &Config) -> Option<StaleStatusPolicy> {
+    let days = config.stale_status_timeout_days?;
+    Some(StaleStatusPolicy::new(Duration::days(i64::from(days)), config.stale_statuses.clone()))
+}
+
+impl StaleStatusPolicy {
+    pub fn new(timeout: Duration, statuses: BTreeSet<String>) -> Self {
+        Self { timeout, statuses }
+    }
+
+    /// Whether shipments in carrier status `status` can go stale at all
+    pub fn applies_to(&self, status: &CarrierStatus) -> bool {
+        self.statuses.contains(status.as_str())
+    }
+
+    /// Whether a tracking UTxO created at `created_at` is older than the timeout by `now`
+    pub fn expired(&self, created_at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        now - created_at > self.timeout
+    }
+}
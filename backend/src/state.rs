@@ -0,0 +1,809 @@
+use anyhow::{Context, Result, bail};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+
+use crate::config::Config;
+use crate::lifecycle::{LifecycleTransition, ShipmentLifecycle};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+/// Ordered schema migrations; the database's `user_version` records how many have run
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE cursor (
+        name TEXT PRIMARY KEY,
+        value TEXT NOT NULL
+    );
+    CREATE TABLE shipment_state (
+        utxo_ref TEXT PRIMARY KEY,
+        status TEXT,
+        failure_count INTEGER NOT NULL DEFAULT 0,
+        next_attempt_at INTEGER,
+        dead INTEGER NOT NULL DEFAULT 0,
+        closed_tx_hash TEXT
+    );
+    CREATE TABLE submissions (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        utxo_ref TEXT NOT NULL,
+        tx_hash TEXT NOT NULL,
+        status TEXT NOT NULL,
+        submitted_at INTEGER NOT NULL
+    );
+    CREATE INDEX submissions_utxo_ref ON submissions (utxo_ref);",
+    "ALTER TABLE submissions ADD COLUMN signer_pkh TEXT;",
+    "CREATE TABLE tracking_lookup (
+        hash TEXT PRIMARY KEY,
+        carrier TEXT NOT NULL,
+        tracking_number TEXT NOT NULL
+    );",
+    "ALTER TABLE submissions ADD COLUMN fee INTEGER;
+    ALTER TABLE submissions ADD COLUMN outbox_address TEXT;",
+    "CREATE TABLE shipment_lifecycle (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        utxo_ref TEXT NOT NULL,
+        state TEXT NOT NULL,
+        detail TEXT,
+        at INTEGER NOT NULL
+    );
+    CREATE INDEX shipment_lifecycle_utxo_ref ON shipment_lifecycle (utxo_ref);",
+    "ALTER TABLE submissions ADD COLUMN carrier TEXT;
+    ALTER TABLE submissions ADD COLUMN tracking_number TEXT;",
+    "ALTER TABLE shipment_state ADD COLUMN carrier_status TEXT;
+    ALTER TABLE shipment_state ADD COLUMN carrier_status_since INTEGER;
+    CREATE TABLE tracking_calls (
+        period TEXT NOT NULL,
+        carrier TEXT NOT NULL,
+        outbox_address TEXT NOT NULL,
+        calls INTEGER NOT NULL,
+        PRIMARY KEY (period, carrier, outbox_address)
+    );",
+    "CREATE TABLE submission_attempts (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        utxo_ref TEXT NOT NULL,
+        status TEXT NOT NULL,
+        attempted_at INTEGER NOT NULL,
+        tx_hash TEXT,
+        response TEXT NOT NULL
+    );
+    CREATE INDEX submission_attempts_utxo_ref ON submission_attempts (utxo_ref);",
+];
+
+/// Schema version of a fully migrated database
+pub const SCHEMA_VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Path of the SQLite file named by a `sqlite://<path>` (or `sqlite:<path>`) database URL
+pub fn sqlite_path(database_url: &str) -> Result<String> {
+    let url = database_url.trim();
+    let Some(path) = url.strip_prefix("sqlite://").or_else(|| url.strip_prefix("sqlite:")) else {
+        bail!("expected a sqlite://<path> URL, got '{}'", url);
+    };
+    if path.is_empty() {
+        bail!("sqlite URL '{}' has no path", url);
+    }
+
+    Ok(path.to_string())
+}
+
+/// Opens the configured state database, namespaced to the tenant if there is one
+pub fn from_config(config: &Config) -> Result<Option<Arc<dyn StateStore>>> {
+    let Some(path) = &config.state_db_path else {
+        return Ok(None);
+    };
+
+    let store: Arc<dyn StateStore> = Arc::new(SqliteStore::open(path)?);
+    Ok(Some(match &config.tenant {
+        Some(tenant) => Arc::new(NamespacedStore::new(store, tenant)),
+        None => store,
+    }))
+}
+
+/// What the oracle knows about a tracking UTxO across runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShipmentState {
+    pub utxo_ref: String,
+    pub status: Option<String>,
+    pub failure_count: u32,
+    /// Unix timestamp before which the shipment should not be retried
+    pub next_attempt_at: Option<u64>,
+    pub dead: bool,
+    pub closed_tx_hash: Option<String>,
+    /// Latest non-final status reported by the carrier (e.g. `TRANSIT`)
+    pub carrier_status: Option<String>,
+    /// Unix timestamp of the first poll that saw `carrier_status`
+    pub carrier_status_since: Option<u64>,
+}
+
+impl ShipmentState {
+    pub fn new(utxo_ref: &str) -> Self {
+        Self {
+            utxo_ref: utxo_ref.to_string(),
+            status: None,
+            failure_count: 0,
+            next_attempt_at: None,
+            dead: false,
+            closed_tx_hash: None,
+            carrier_status: None,
+            carrier_status_since: None,
+        }
+    }
+}
+
+/// A close-shipment transaction accepted by the submit API
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Submission {
+    pub utxo_ref: String,
+    pub tx_hash: String,
+    pub status: String,
+    pub submitted_at: u64,
+    /// Key hash of the oracle key that signed the transaction
+    pub signer_pkh: Option<String>,
+    /// Fee paid by the transaction, in lovelace
+    pub fee: Option<u64>,
+    /// Address receiving the shipment datum
+    pub outbox_address: Option<String>,
+    pub carrier: Option<String>,
+    /// Tracking number as written in the datum (the hash hex for privacy-mode shipments)
+    pub tracking_number: Option<String>,
+}
+
+/// One attempt at closing a shipment, accepted or not
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SubmissionAttempt {
+    pub utxo_ref: String,
+    pub status: String,
+    pub attempted_at: u64,
+    /// Hash of the accepted transaction; `None` when the attempt failed
+    pub tx_hash: Option<String>,
+    /// What the submitter answered: the transaction hash, or the error
+    pub response: String,
+}
+
+/// Tracking API calls made in a billing period for shipments of one carrier and outbox address
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrackingCalls {
+    /// Billing month, `YYYY-MM`
+    pub period: String,
+    pub carrier: String,
+    pub outbox_address: String,
+    pub calls: u64,
+}
+
+/// Durable oracle state shared by every run
+#[async_trait::async_trait]
+pub trait StateStore: Send + Sync {
+    async fn cursor(&self, name: &str) -> Result<Option<String>>;
+
+    async fn set_cursor(&self, name: &str, value: &str) -> Result<()>;
+
+    async fn shipment(&self, utxo_ref: &str) -> Result<Option<ShipmentState>>;
+
+    async fn save_shipment(&self, state: &ShipmentState) -> Result<()>;
+
+    async fn record_submission(&self, submission: &Submission) -> Result<()>;
+
+    /// Submissions for a UTxO, oldest first
+    async fn submissions(&self, utxo_ref: &str) -> Result<Vec<Submission>>;
+
+    /// Every submission, oldest first
+    async fn journal(&self) -> Result<Vec<Submission>>;
+
+    /// Record a close attempt, whether or not the submit API accepted it
+    async fn record_attempt(&self, attempt: &SubmissionAttempt) -> Result<()>;
+
+    /// Close attempts for a UTxO, oldest first
+    async fn attempts(&self, utxo_ref: &str) -> Result<Vec<SubmissionAttempt>>;
+
+    async fn record_transition(&self, transition: &LifecycleTransition) -> Result<()>;
+
+    /// Lifecycle transitions of a UTxO, oldest first; the last one is its current state
+    async fn lifecycle(&self, utxo_ref: &str) -> Result<Vec<LifecycleTransition>>;
+
+    /// The latest transition of every shipment with a lifecycle
+    async fn lifecycles(&self) -> Result<Vec<LifecycleTransition>>;
+
+    /// Remember the tracking number behind a privacy-mode datum hash
+    async fn register_tracking(&self, hash: &[u8; 32], carrier: &str, tracking_number: &str) -> Result<()>;
+
+    async fn tracking_number(&self, hash: &[u8; 32]) -> Result<Option<String>>;
+
+    /// Count one tracking API call in `period` for a shipment of `carrier` paying `outbox_address`
+    async fn record_tracking_call(&self, period: &str, carrier: &str, outbox_address: &str) -> Result<()>;
+
+    /// Tracking API calls counted in `period`, by carrier and outbox address
+    async fn tracking_calls(&self, period: &str) -> Result<Vec<TrackingCalls>>;
+}
+
+/// SQLite-backed store; all access is serialized through one connection
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    /// Open (or create) the database at `path` and bring its schema up to date
+    ///
+    /// The database runs in WAL mode so a crash mid-write never leaves a
+    /// partially applied transaction behind.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let conn = Connection::open(path)
+            .with_context(|| format!("Failed to open state database {}", path.display()))?;
+
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+
+        Self::from_connection(conn)
+    }
+
+    pub fn open_in_memory() -> Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(mut conn: Connection) -> Result<Self> {
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        migrate(&mut conn)?;
+
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    pub async fn schema_version(&self) -> Result<u32> {
+        let conn = self.conn.lock().await;
+        Ok(conn.pragma_query_value(None, "user_version", |row| row.get(0))?)
+    }
+}
+
+/// Apply every migration newer than the database's `user_version`, each in its own transaction
+fn migrate(conn: &mut Connection) -> Result<()> {
+    let current: u32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+    if current > SCHEMA_VERSION {
+        bail!(
+            "State database schema version {} is newer than this binary supports ({})",
+            current,
+            SCHEMA_VERSION
+        );
+    }
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(current as usize) {
+        let version = index as u32 + 1;
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)
+            .with_context(|| format!("State database migration {} failed", version))?;
+        tx.pragma_update(None, "user_version", version)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl StateStore for SqliteStore {
+    async fn cursor(&self, name: &str) -> Result<Option<String>> {
+        let conn = self.conn.lock().await;
+        let value = conn
+            .query_row("SELECT value FROM cursor WHERE name = ?1", params![name], |row| row.get(0))
+            .optional()?;
+
+        Ok(value)
+    }
+
+    async fn set_cursor(&self, name: &str, value: &str) -> Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO cursor (name, value) VALUES (?1, ?2)
+             ON CONFLICT (name) DO UPDATE SET value = excluded.value",
+            params![name, value],
+        )?;
+
+        Ok(())
+    }
+
+    async fn shipment(&self, utxo_ref: &str) -> Result<Option<ShipmentState>> {
+        let conn = self.conn.lock().await;
+        let state = conn
+            .query_row(
+                "SELECT utxo_ref, status, failure_count, next_attempt_at, dead, closed_tx_hash,
+                    carrier_status, carrier_status_since
+                 FROM shipment_state WHERE utxo_ref = ?1",
+                params![utxo_ref],
+                |row| {
+                    Ok(ShipmentState {
+                        utxo_ref: row.get(0)?,
+                        status: row.get(1)?,
+                        failure_count: row.get(2)?,
+                        next_attempt_at: row.get(3)?,
+                        dead: row.get(4)?,
+                        closed_tx_hash: row.get(5)?,
+                        carrier_status: row.get(6)?,
+                        carrier_status_since: row.get(7)?,
+                    })
+                },
+            )
+            .optional()?;
+
+        Ok(state)
+    }
+
+    async fn save_shipment(&self, state: &ShipmentState) -> Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO shipment_state
+                (utxo_ref, status, failure_count, next_attempt_at, dead, closed_tx_hash, carrier_status, carrier_status_since)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+             ON CONFLICT (utxo_ref) DO UPDATE SET
+                status = excluded.status,
+                failure_count = excluded.failure_count,
+                next_attempt_at = excluded.next_attempt_at,
+                dead = excluded.dead,
+                closed_tx_hash = excluded.closed_tx_hash,
+                carrier_status = excluded.carrier_status,
+                carrier_status_since = excluded.carrier_status_since",
+            params![
+                state.utxo_ref,
+                state.status,
+                state.failure_count,
+                state.next_attempt_at,
+                state.dead,
+                state.closed_tx_hash,
+                state.carrier_status,
+                state.carrier_status_since,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    async fn record_submission(&self, submission: &Submission) -> Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO submissions
+                (utxo_ref, tx_hash, status, submitted_at, signer_pkh, fee, outbox_address, carrier, tracking_number)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                submission.utxo_ref,
+                submission.tx_hash,
+                submission.status,
+                submission.submitted_at,
+                submission.signer_pkh,
+                submission.fee,
+                submission.outbox_address,
+                submission.carrier,
+                submission.tracking_number,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    async fn submissions(&self, utxo_ref: &str) -> Result<Vec<Submission>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT utxo_ref, tx_hash, status, submitted_at, signer_pkh, fee, outbox_address, carrier, tracking_number
+             FROM submissions WHERE utxo_ref = ?1 ORDER BY id",
+        )?;
+        let submissions = stmt
+            .query_map(params![utxo_ref], submission_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(submissions)
+    }
+
+    async fn journal(&self) -> Result<Vec<Submission>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT utxo_ref, tx_hash, status, submitted_at, signer_pkh, fee, outbox_address, carrier, tracking_number
+             FROM submissions ORDER BY id",
+        )?;
+        let submissions = stmt
+            .query_map([], submission_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(submissions)
+    }
+
+    async fn record_attempt(&self, attempt: &SubmissionAttempt) -> Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO submission_attempts (utxo_ref, status, attempted_at, tx_hash, response)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![attempt.utxo_ref, attempt.status, attempt.attempted_at, attempt.tx_hash, attempt.response],
+        )?;
+
+        Ok(())
+    }
+
+    async fn attempts(&self, utxo_ref: &str) -> Result<Vec<SubmissionAttempt>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT utxo_ref, status, attempted_at, tx_hash, response
+             FROM submission_attempts WHERE utxo_ref = ?1 ORDER BY id",
+        )?;
+        let attempts = stmt
+            .query_map(params![utxo_ref], |row| {
+                Ok(SubmissionAttempt {
+                    utxo_ref: row.get(0)?,
+                    status: row.get(1)?,
+                    attempted_at: row.get(2)?,
+                    tx_hash: row.get(3)?,
+                    response: row.get(4)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(attempts)
+    }
+
+    async fn record_transition(&self, transition: &LifecycleTransition) -> Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO shipment_lifecycle (utxo_ref, state, detail, at) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                transition.utxo_ref,
+                transition.state.name(),
+                transition.state.detail(),
+                transition.at,
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    async fn lifecycle(&self, utxo_ref: &str) -> Result<Vec<LifecycleTransition>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT utxo_ref, state, detail, at FROM shipment_lifecycle WHERE utxo_ref = ?1 ORDER BY id",
+        )?;
+        let rows = stmt
+            .query_map(params![utxo_ref], transition_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        rows.into_iter().map(transition_from_parts).collect()
+    }
+
+    async fn lifecycles(&self) -> Result<Vec<LifecycleTransition>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT utxo_ref, state, detail, at FROM shipment_lifecycle
+             WHERE id IN (SELECT MAX(id) FROM shipment_lifecycle GROUP BY utxo_ref) ORDER BY id",
+        )?;
+        let rows = stmt
+            .query_map([], transition_from_row)?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        rows.into_iter().map(transition_from_parts).collect()
+    }
+
+    async fn register_tracking(&self, hash: &[u8; 32], carrier: &str, tracking_number: &str) -> Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO tracking_lookup (hash, carrier, tracking_number) VALUES (?1, ?2, ?3)
+             ON CONFLICT (hash) DO UPDATE SET carrier = excluded.carrier, tracking_number = excluded.tracking_number",
+            params![hex::encode(hash), carrier, tracking_number],
+        )?;
+
+        Ok(())
+    }
+
+    async fn tracking_number(&self, hash: &[u8; 32]) -> Result<Option<String>> {
+        let conn = self.conn.lock().await;
+        let tracking_number = conn
+            .query_row(
+                "SELECT tracking_number FROM tracking_lookup WHERE hash = ?1",
+                params![hex::encode(hash)],
+                |row| row.get(0),
+            )
+            .optional()?;
+
+        Ok(tracking_number)
+    }
+
+    async fn record_tracking_call(&self, period: &str, carrier: &str, outbox_address: &str) -> Result<()> {
+        let conn = self.conn.lock().await;
+        conn.execute(
+            "INSERT INTO tracking_calls (period, carrier, outbox_address, calls) VALUES (?1, ?2, ?3, 1)
+             ON CONFLICT (period, carrier, outbox_address) DO UPDATE SET calls = calls + 1",
+            params![period, carrier, outbox_address],
+        )?;
+
+        Ok(())
+    }
+
+    async fn tracking_calls(&self, period: &str) -> Result<Vec<TrackingCalls>> {
+        let conn = self.conn.lock().await;
+        let mut stmt = conn.prepare(
+            "SELECT period, carrier, outbox_address, calls FROM tracking_calls
+             WHERE period = ?1 ORDER BY carrier, outbox_address",
+        )?;
+        let calls = stmt
+            .query_map(params![period], |row| {
+                Ok(TrackingCalls {
+                    period: row.get(0)?,
+                    carrier: row.get(1)?,
+                    outbox_address: row.get(2)?,
+                    calls: row.get(3)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        Ok(calls)
+    }
+}
+
+fn submission_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Submission> {
+    Ok(Submission {
+        utxo_ref: row.get(0)?,
+        tx_hash: row.get(1)?,
+        status: row.get(2)?,
+        submitted_at: row.get(3)?,
+        signer_pkh: row.get(4)?,
+        fee: row.get(5)?,
+        outbox_address: row.get(6)?,
+        carrier: row.get(7)?,
+        tracking_number: row.get(8)?,
+    })
+}
+
+/// A `shipment_lifecycle` row: utxo_ref, state name, detail and timestamp
+type TransitionRow = (String, String, Option<String>, u64);
+
+fn transition_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<TransitionRow> {
+    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+}
+
+fn transition_from_parts((utxo_ref, state, detail, at): TransitionRow) -> Result<LifecycleTransition> {
+    let state = ShipmentLifecycle::from_parts(&state, detail.as_deref())
+        .with_context(|| format!("Invalid lifecycle row for {}", utxo_ref))?;
+
+    Ok(LifecycleTransition { utxo_ref, state, at })
+}
+
+/// Scopes another store to one tenant by prefixing every key with `<namespace>/`
+///
+/// Tenants sharing a database never see each other's cursors, shipments,
+/// submissions or tracking call counts. Tracking registrations are shared: their hashes are salted per
+/// merchant, not per tenant.
+pub struct NamespacedStore {
+    inner: Arc<dyn StateStore>,
+    namespace: String,
+}
+
+impl NamespacedStore {
+    pub fn new(inner: Arc<dyn StateStore>, namespace: &str) -> Self {
+        Self { inner, namespace: namespace.to_string() }
+    }
+
+    fn key(&self, key: &str) -> String {
+        format!("{}/{}", self.namespace, key)
+    }
+
+    fn strip<'a>(&self, key: &'a str) -> &'a str {
+        key.strip_prefix(&self.namespace)
+            .and_then(|key| key.strip_prefix('/'))
+            .unwrap_or(key)
+    }
+}
+
+#[async_trait::async_trait]
+impl StateStore for NamespacedStore {
+    async fn cursor(&self, name: &str) -> Result<Option<String>> {
+        self.inner.cursor(&self.key(name)).await
+    }
+
+    async fn set_cursor(&self, name: &str, value: &str) -> Result<()> {
+        self.inner.set_cursor(&self.key(name), value).await
+    }
+
+    async fn shipment(&self, utxo_ref: &str) -> Result<Option<ShipmentState>> {
+        let state = self.inner.shipment(&self.key(utxo_ref)).await?;
+        Ok(state.map(|state| ShipmentState { utxo_ref: self.strip(&state.utxo_ref).to_string(), ..state }))
+    }
+
+    async fn save_shipment(&self, state: &ShipmentState) -> Result<()> {
+        self.inner
+            .save_shipment(&ShipmentState { utxo_ref: self.key(&state.utxo_ref), ..state.clone() })
+            .await
+    }
+
+    async fn record_submission(&self, submission: &Submission) -> Result<()> {
+        self.inner
+            .record_submission(&Submission { utxo_ref: self.key(&submission.utxo_ref), ..submission.clone() })
+            .await
+    }
+
+    async fn submissions(&self, utxo_ref: &str) -> Result<Vec<Submission>> {
+        let submissions = self.inner.submissions(&self.key(utxo_ref)).await?;
+        Ok(submissions
+            .into_iter()
+            .map(|submission| Submission { utxo_ref: self.strip(&submission.utxo_ref).to_string(), ..submission })
+            .collect())
+    }
+
+    async fn journal(&self) -> Result<Vec<Submission>> {
+        let prefix = self.key("");
+        let submissions = self.inner.journal().await?;
+        Ok(submissions
+            .into_iter()
+            .filter(|submission| submission.utxo_ref.starts_with(&prefix))
+            .map(|submission| Submission { utxo_ref: self.strip(&submission.utxo_ref).to_string(), ..submission })
+            .collect())
+    }
+
+    async fn record_attempt(&self, attempt: &SubmissionAttempt) -> Result<()> {
+        self.inner
+            .record_attempt(&SubmissionAttempt { utxo_ref: self.key(&attempt.utxo_ref), ..attempt.clone() })
+            .await
+    }
+
+    async fn attempts(&self, utxo_ref: &str) -> Result<Vec<SubmissionAttempt>> {
+        let attempts = self.inner.attempts(&self.key(utxo_ref)).await?;
+        Ok(attempts
+            .into_iter()
+            .map(|attempt| SubmissionAttempt { utxo_ref: self.strip(&attempt.utxo_ref).to_string(), ..attempt })
+            .collect())
+    }
+
+    async fn record_transition(&self, transition: &LifecycleTransition) -> Result<()> {
+        self.inner
+            .record_transition(&LifecycleTransition { utxo_ref: self.key(&transition.utxo_ref), ..transition.clone() })
+            .await
+    }
+
+    async fn lifecycle(&self, utxo_ref: &str) -> Result<Vec<LifecycleTransition>> {
+        let transitions = self.inner.lifecycle(&self.key(utxo_ref)).await?;
+        Ok(transitions
+            .into_iter()
+            .map(|transition| LifecycleTransition { utxo_ref: self.strip(&transition.utxo_ref).to_string(), ..transition })
+            .collect())
+    }
+
+    async fn lifecycles(&self) -> Result<Vec<LifecycleTransition>> {
+        let prefix = self.key("");
+        let transitions = self.inner.lifecycles().await?;
+        Ok(transitions
+            .into_iter()
+            .filter(|transition| transition.utxo_ref.starts_with(&prefix))
+            .map(|transition| LifecycleTransition { utxo_ref: self.strip(&transition.utxo_ref).to_string(), ..transition })
+            .collect())
+    }
+
+    async fn register_tracking(&self, hash: &[u8; 32], carrier: &str, tracking_number: &str) -> Result<()> {
+        self.inner.register_tracking(hash, carrier, tracking_number).await
+    }
+
+    async fn tracking_number(&self, hash: &[u8; 32]) -> Result<Option<String>> {
+        self.inner.tracking_number(hash).await
+    }
+
+    async fn record_tracking_call(&self, period: &str, carrier: &str, outbox_address: &str) -> Result<()> {
+        self.inner.record_tracking_call(&self.key(period), carrier, outbox_address).await
+    }
+
+    async fn tracking_calls(&self, period: &str) -> Result<Vec<TrackingCalls>> {
+        let calls = self.inner.tracking_calls(&self.key(period)).await?;
+        Ok(calls
+            .into_iter()
+            .map(|calls| TrackingCalls { period: self.strip(&calls.period).to_string(), ..calls })
+            .collect())
+    }
+}
+
+/// Non-persistent store for tests and dry runs
+#[derive(Default)]
+pub struct MemoryStore {
+    inner: Mutex<MemoryState>,
+}
+
+#[derive(Default)]
+struct MemoryState {
+    cursors: HashMap<String, String>,
+    shipments: HashMap<String, ShipmentState>,
+    submissions: Vec<Submission>,
+    attempts: Vec<SubmissionAttempt>,
+    transitions: Vec<LifecycleTransition>,
+    tracking_numbers: HashMap<[u8; 32], String>,
+    tracking_calls: BTreeMap<(String, String, String), u64>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait::async_trait]
+impl StateStore for MemoryStore {
+    async fn cursor(&self, name: &str) -> Result<Option<String>> {
+        Ok(self.inner.lock().await.cursors.get(name).cloned())
+    }
+
+    async fn set_cursor(&self, name: &str, value: &str) -> Result<()> {
+        self.inner.lock().await.cursors.insert(name.to_string(), value.to_string());
+        Ok(())
+    }
+
+    async fn shipment(&self, utxo_ref: &str) -> Result<Option<ShipmentState>> {
+        Ok(self.inner.lock().await.shipments.get(utxo_ref).cloned())
+    }
+
+    async fn save_shipment(&self, state: &ShipmentState) -> Result<()> {
+        self.inner.lock().await.shipments.insert(state.utxo_ref.clone(), state.clone());
+        Ok(())
+    }
+
+    async fn record_submission(&self, submission: &Submission) -> Result<()> {
+        self.inner.lock().await.submissions.push(submission.clone());
+        Ok(())
+    }
+
+    async fn submissions(&self, utxo_ref: &str) -> Result<Vec<Submission>> {
+        let inner = self.inner.lock().await;
+        Ok(inner.submissions.iter().filter(|s| s.utxo_ref == utxo_ref).cloned().collect())
+    }
+
+    async fn journal(&self) -> Result<Vec<Submission>> {
+        Ok(self.inner.lock().await.submissions.clone())
+    }
+
+    async fn record_attempt(&self, attempt: &SubmissionAttempt) -> Result<()> {
+        self.inner.lock().await.attempts.push(attempt.clone());
+        Ok(())
+    }
+
+    async fn attempts(&self, utxo_ref: &str) -> Result<Vec<SubmissionAttempt>> {
+        let inner = self.inner.lock().await;
+        Ok(inner.attempts.iter().filter(|a| a.utxo_ref == utxo_ref).cloned().collect())
+    }
+
+    async fn record_transition(&self, transition: &LifecycleTransition) -> Result<()> {
+        self.inner.lock().await.transitions.push(transition.clone());
+        Ok(())
+    }
+
+    async fn lifecycle(&self, utxo_ref: &str) -> Result<Vec<LifecycleTransition>> {
+        let inner = self.inner.lock().await;
+        Ok(inner.transitions.iter().filter(|t| t.utxo_ref == utxo_ref).cloned().collect())
+    }
+
+    async fn lifecycles(&self) -> Result<Vec<LifecycleTransition>> {
+        let inner = self.inner.lock().await;
+        let mut latest: Vec<LifecycleTransition> = Vec::new();
+        for transition in inner.transitions.iter().rev() {
+            if !latest.iter().any(|t| t.utxo_ref == transition.utxo_ref) {
+                latest.push(transition.clone());
+            }
+        }
+        latest.reverse();
+
+        Ok(latest)
+    }
+
+    async fn register_tracking(&self, hash: &[u8; 32], _carrier: &str, tracking_number: &str) -> Result<()> {
+        self.inner.lock().await.tracking_numbers.insert(*hash, tracking_number.to_string());
+        Ok(())
+    }
+
+    async fn tracking_number(&self, hash: &[u8; 32]) -> Result<Option<String>> {
+        Ok(self.inner.lock().await.tracking_numbers.get(hash).cloned())
+    }
+
+    async fn record_tracking_call(&self, period: &str, carrier: &str, outbox_address: &str) -> Result<()> {
+        let key = (period.to_string(), carrier.to_string(), outbox_address.to_string());
+        *self.inner.lock().await.tracking_calls.entry(key).or_default() += 1;
+        Ok(())
+    }
+
+    async fn tracking_calls(&self, period: &str) -> Result<Vec<TrackingCalls>> {
+        let inner = self.inner.lock().await;
+        Ok(inner
+            .tracking_calls
+            .iter()
+            .filter(|((candidate, _, _), _)| candidate == period)
+            .map(|((period, carrier, outbox_address), calls)| TrackingCalls {
+                period: period.clone(),
+                carrier: carrier.clone(),
+                outbox_address: outbox_address.clone(),
+                calls: *calls,
+            })
+            .collect())
+    }
+}
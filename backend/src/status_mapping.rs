@@ -0,0 +1,85 @@
+use anyhow::{Context, Result, bail};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::models::{DerivedStatus, TrackingStatus};
+
+/// On-chain status of shipments closed without a final carrier status, past their deadline or stale
+pub const TIMEOUT_STATUS: DerivedStatus = DerivedStatus::NotDelivered;
+
+/// Carrier statuses that close a shipment, and the on-chain status each one closes it with (`STATUS_MAP`)
+///
+/// Carrier statuses are in Shippo's vocabulary, which the other providers are
+/// translated into. Any status not in the mapping leaves the shipment open.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatusMapping {
+    statuses: BTreeMap<String, DerivedStatus>,
+}
+
+impl Default for StatusMapping {
+    /// `DELIVERED:DELIVERED,RETURNED:NOT_DELIVERED,FAILURE:NOT_DELIVERED`
+    fn default() -> Self {
+        let statuses = [
+            ("DELIVERED", DerivedStatus::Delivered),
+            ("RETURNED", DerivedStatus::NotDelivered),
+            ("FAILURE", DerivedStatus::NotDelivered),
+        ]
+        .into_iter()
+        .map(|(carrier, on_chain)| (carrier.to_string(), on_chain))
+        .collect();
+        Self { statuses }
+    }
+}
+
+impl StatusMapping {
+    /// On-chain status to close a shipment with, `None` while its carrier status is not final
+    pub fn map(&self, status: &TrackingStatus) -> Option<DerivedStatus> {
+        self.statuses.get(status.status.as_str()).copied()
+    }
+
+    /// On-chain statuses the oracle closes shipments with, the deadline's included
+    pub fn on_chain_statuses(&self) -> Vec<DerivedStatus> {
+        let mut statuses: Vec<DerivedStatus> = self.statuses.values().copied().collect();
+        statuses.push(TIMEOUT_STATUS);
+        statuses.sort_unstable();
+        statuses.dedup();
+        statuses
+    }
+}
+
+impl FromStr for StatusMapping {
+    type Err = anyhow::Error;
+
+    /// Comma-separated `carrier_status:on_chain_status` pairs, e.g. `DELIVERED:DELIVERED,FAILURE:NOT_DELIVERED`
+    ///
+    /// On-chain statuses must be ones the tracking validator accepts (`DerivedStatus`),
+    /// so a typo fails the config load instead of every close it would make.
+    fn from_str(value: &str) -> Result<Self> {
+        let mut statuses = BTreeMap::new();
+        for pair in value.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let Some((carrier, on_chain)) = pair.split_once(':') else {
+                bail!("expected carrier_status:on_chain_status, got '{}'", pair);
+            };
+            let (carrier, on_chain) = (carrier.trim().to_uppercase(), on_chain.trim());
+            if carrier.is_empty() || on_chain.is_empty() {
+                bail!("expected carrier_status:on_chain_status, got '{}'", pair);
+            }
+            let on_chain = on_chain.parse().with_context(|| format!("Invalid on-chain status of {}", carrier))?;
+            statuses.insert(carrier, on_chain);
+        }
+
+        if statuses.is_empty() {
+            bail!("no carrier status closes a shipment");
+        }
+
+        Ok(Self { statuses })
+    }
+}
+
+impl fmt::Display for StatusMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let pairs: Vec<String> = self.statuses.iter().map(|(carrier, on_chain)| format!("{}:{}", carrier, on_chain)).collect();
+        f.write_str(&pairs.join(","))
+    }
+}
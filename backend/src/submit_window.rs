@@ -0,0 +1,71 @@
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Days, Duration, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::fmt;
+
+/// Local-time window in which close transactions may be submitted
+///
+/// Polling carriers is not restricted; final statuses found outside the window
+/// wait for the next run inside it. A window whose end is before its start
+/// spans midnight (`22:00-06:00`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubmitWindow {
+    pub start: NaiveTime,
+    /// First minute outside the window
+    pub end: NaiveTime,
+    pub timezone: Tz,
+}
+
+impl SubmitWindow {
+    /// Parse a `HH:MM-HH:MM` window in `timezone`
+    pub fn parse(value: &str, timezone: Tz) -> Result<Self> {
+        let Some((start, end)) = value.trim().split_once('-') else {
+            bail!("expected HH:MM-HH:MM, got '{}'", value.trim());
+        };
+
+        let parse_time = |time: &str| {
+            NaiveTime::parse_from_str(time.trim(), "%H:%M")
+                .with_context(|| format!("expected a HH:MM time, got '{}'", time.trim()))
+        };
+        let (start, end) = (parse_time(start)?, parse_time(end)?);
+        if start == end {
+            bail!("window {} is empty", value.trim());
+        }
+
+        Ok(Self { start, end, timezone })
+    }
+
+    /// Whether submissions are allowed at `at`
+    pub fn is_open(&self, at: DateTime<Utc>) -> bool {
+        let time = at.with_timezone(&self.timezone).time();
+        if self.start < self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+
+    /// When the window next opens after `at`
+    pub fn next_open(&self, at: DateTime<Utc>) -> DateTime<Utc> {
+        let local = at.with_timezone(&self.timezone);
+        let mut date = local.date_naive();
+        if local.time() >= self.start {
+            date = date + Days::new(1);
+        }
+
+        // A start skipped by a DST change opens the window an hour later that day
+        let start = date.and_time(self.start);
+        self.timezone
+            .from_local_datetime(&start)
+            .earliest()
+            .or_else(|| self.timezone.from_local_datetime(&(start + Duration::hours(1))).earliest())
+            .map(|open| open.with_timezone(&Utc))
+            .unwrap_or(at)
+    }
+}
+
+impl fmt::Display for SubmitWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{} {}", self.start.format("%H:%M"), self.end.format("%H:%M"), self.timezone)
+    }
+}
@@ -1,15 +1,207 @@
 use anyhow::{Context, Result, anyhow};
 use reqwest::Client as HttpClient;
-use serde_json::Value;
+use reqwest::StatusCode;
+use reqwest::header::HeaderMap;
+use serde_json::{Value, json};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use tracing::{Instrument, warn};
+
+use crate::backoff;
+use crate::blockchain::blockfrost_http_client;
+use crate::config::Config;
+use crate::logging;
+use crate::redact::redact;
+
+/// Default `SUBMIT_MAX_RETRIES`
+pub const DEFAULT_SUBMIT_MAX_RETRIES: u32 = 3;
+
+/// Default `SUBMIT_BASE_BACKOFF_MS`
+pub const DEFAULT_SUBMIT_BASE_BACKOFF_MS: u64 = 500;
+
+/// Rejections meaning an input is spent already, possibly by this very transaction
+const ALREADY_SPENT_REASONS: [&str; 3] = ["BadInputsUTxO", "already been included", "unknownOutputReferences"];
+
+/// Rejections meaning the fee is below the ledger minimum
+const FEE_TOO_SMALL_REASONS: [&str; 2] = ["FeeTooSmallUTxO", "minimumRequiredFee"];
+
+/// Ledger rejections that no retry can fix, even when they come with a 5xx status
+const PERMANENT_REASONS: [&str; 3] = ["ValueNotConservedUTxO", "ScriptFailure", "PlutusFailure"];
 
 #[async_trait::async_trait]
 pub trait TxSubmitter: Send + Sync {
-    async fn submit(&self, signed_tx: Vec<u8>) -> Result<String>;
+    async fn submit(&self, signed_tx: Vec<u8>) -> Result<String, SubmitError>;
+}
+
+/// Why a submit API did not accept a transaction
+///
+/// Every variant displays the full message of the submit API.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum SubmitError {
+    /// An input is spent already, or the transaction is already on-chain
+    #[error("{0}")]
+    AlreadySpent(String),
+    /// The fee is below the ledger minimum
+    #[error("{0}")]
+    FeeTooSmall(String),
+    /// The submit API asks to slow down (429), for `retry_after` if it says how long
+    #[error("{message}")]
+    RateLimited { retry_after: Option<Duration>, message: String },
+    /// No verdict on the transaction: a network error, 5xx or full mempool (425)
+    #[error("{0}")]
+    Network(String),
+    /// Rejected for any other reason, e.g. a bad witness or a failing script
+    #[error("{0}")]
+    Other(String),
+}
+
+impl SubmitError {
+    /// Classify the `status` and `body` a submit API answered with; `message` is what to display
+    pub fn from_response(status: StatusCode, headers: &HeaderMap, body: &str, message: String) -> Self {
+        if ALREADY_SPENT_REASONS.iter().any(|reason| body.contains(reason)) {
+            return SubmitError::AlreadySpent(message);
+        }
+        if FEE_TOO_SMALL_REASONS.iter().any(|reason| body.contains(reason)) {
+            return SubmitError::FeeTooSmall(message);
+        }
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            return SubmitError::RateLimited { retry_after: backoff::retry_after(headers), message };
+        }
+        if (status.is_server_error() || status.as_u16() == 425)
+            && !PERMANENT_REASONS.iter().any(|reason| body.contains(reason))
+        {
+            return SubmitError::Network(message);
+        }
+
+        SubmitError::Other(message)
+    }
+
+    /// Whether the transaction may be accepted when submitted again
+    pub fn is_transient(&self) -> bool {
+        matches!(self, SubmitError::RateLimited { .. } | SubmitError::Network(_))
+    }
+}
+
+/// Where signed close transactions are submitted (`SUBMITTER`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SubmitterKind {
+    /// Blockfrost's `/tx/submit`, with the `BLOCKFROST_URL` credentials
+    #[default]
+    Blockfrost,
+    /// An Ogmios node at `OGMIOS_URL`
+    Ogmios,
+}
+
+impl FromStr for SubmitterKind {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "blockfrost" => Ok(SubmitterKind::Blockfrost),
+            "ogmios" => Ok(SubmitterKind::Ogmios),
+            other => Err(anyhow!("expected blockfrost or ogmios, got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for SubmitterKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SubmitterKind::Blockfrost => "blockfrost",
+            SubmitterKind::Ogmios => "ogmios",
+        })
+    }
+}
+
+/// Builds the submitter selected by `SUBMITTER`
+pub fn from_config(config: &Config) -> Result<Box<dyn TxSubmitter>> {
+    match config.submitter {
+        SubmitterKind::Blockfrost => Ok(retrying(
+            config,
+            BlockfrostSubmitter::new(config.blockfrost_url.clone(), blockfrost_http_client(config)?)
+                .with_project_id_configured(config.blockfrost_project_id.is_some()),
+        )),
+        SubmitterKind::Ogmios => {
+            let ogmios_url = config.ogmios_url.clone().context("SUBMITTER=ogmios needs OGMIOS_URL")?;
+            Ok(retrying(config, OgmiosSubmitter::new(ogmios_url, HttpClient::new())))
+        }
+    }
+}
+
+fn retrying<S: TxSubmitter + 'static>(config: &Config, inner: S) -> Box<dyn TxSubmitter> {
+    Box::new(
+        RetryingSubmitter::new(inner)
+            .with_max_retries(config.submit_max_retries)
+            .with_base_backoff(Duration::from_millis(config.submit_base_backoff_ms)),
+    )
+}
+
+/// Retries the transient failures of `inner` with exponential backoff and jitter
+///
+/// Attempt `n` waits `base_backoff * 2^n` plus up to half as much again, or
+/// longer if a rate limit says so. Any other error is returned at once, as is
+/// the last one once `max_retries` is reached.
+pub struct RetryingSubmitter<S: TxSubmitter> {
+    inner: S,
+    max_retries: u32,
+    base_backoff: Duration,
+}
+
+impl<S: TxSubmitter> RetryingSubmitter<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            max_retries: DEFAULT_SUBMIT_MAX_RETRIES,
+            base_backoff: Duration::from_millis(DEFAULT_SUBMIT_BASE_BACKOFF_MS),
+        }
+    }
+
+    /// Retry a transient failure at most `max_retries` times; 0 submits once
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Wait `base_backoff` before the first retry, doubling it on each one after
+    pub fn with_base_backoff(mut self, base_backoff: Duration) -> Self {
+        self.base_backoff = base_backoff;
+        self
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: TxSubmitter> TxSubmitter for RetryingSubmitter<S> {
+    async fn submit(&self, signed_tx: Vec<u8>) -> Result<String, SubmitError> {
+        let mut attempt = 0;
+        loop {
+            match self.inner.submit(signed_tx.clone()).await {
+                Err(e) if attempt < self.max_retries && e.is_transient() => {
+                    let mut backoff = backoff::exponential(self.base_backoff, attempt);
+                    if let SubmitError::RateLimited { retry_after: Some(retry_after), .. } = &e {
+                        backoff = backoff.max(*retry_after);
+                    }
+                    attempt += 1;
+                    warn!(
+                        attempt,
+                        max_retries = self.max_retries,
+                        backoff_ms = backoff.as_millis() as u64,
+                        error = %e,
+                        "⚠️  Retrying transaction submission",
+                    );
+                    tokio::time::sleep(backoff).await;
+                }
+                result => return result,
+            }
+        }
+    }
 }
 
 pub struct BlockfrostSubmitter {
     blockfrost_url: String,
     http_client: HttpClient,
+    /// Whether `http_client` sends a `project_id` header, for the hint on 403 responses
+    project_id_configured: bool,
 }
 
 impl BlockfrostSubmitter {
@@ -17,13 +209,31 @@ impl BlockfrostSubmitter {
         Self {
             blockfrost_url,
             http_client,
+            project_id_configured: false,
         }
     }
+
+    /// Note whether `BLOCKFROST_PROJECT_ID` is set, so a 403 says which credential to check
+    pub fn with_project_id_configured(mut self, configured: bool) -> Self {
+        self.project_id_configured = configured;
+        self
+    }
+}
+
+/// What to check when Blockfrost answers 403 Forbidden; empty for any other status
+pub fn forbidden_hint(status: StatusCode, project_id_configured: bool) -> &'static str {
+    match (status, project_id_configured) {
+        (StatusCode::FORBIDDEN, true) => " (BLOCKFROST_PROJECT_ID is set; check it belongs to this network)",
+        (StatusCode::FORBIDDEN, false) => {
+            " (BLOCKFROST_PROJECT_ID is not set; set it unless a proxy adds the project_id header)"
+        }
+        _ => "",
+    }
 }
 
 #[async_trait::async_trait]
 impl TxSubmitter for BlockfrostSubmitter {
-    async fn submit(&self, signed_tx: Vec<u8>) -> Result<String> {
+    async fn submit(&self, signed_tx: Vec<u8>) -> Result<String, SubmitError> {
         let url = format!("{}/tx/submit", self.blockfrost_url);
 
         let response = self
@@ -32,29 +242,117 @@ impl TxSubmitter for BlockfrostSubmitter {
             .header("Content-Type", "application/cbor")
             .body(signed_tx)
             .send()
+            .instrument(logging::http_span("blockfrost", &reqwest::Method::POST, &url))
             .await
-            .context("Failed to submit transaction to Blockfrost")?;
+            .map_err(|e| SubmitError::Network(format!("Failed to submit transaction to Blockfrost: {}", redact(&e.to_string()))))?;
 
         if !response.status().is_success() {
             let status = response.status();
+            let headers = response.headers().clone();
             let body = response.text().await.unwrap_or_default();
-            return Err(anyhow!(
-                "Blockfrost transaction submission failed (status {}): {}",
+            let message = format!(
+                "Blockfrost transaction submission failed (status {}): {}{}",
                 status,
-                body
-            ));
+                redact(&body),
+                forbidden_hint(status, self.project_id_configured)
+            );
+            return Err(SubmitError::from_response(status, &headers, &body, message));
         }
 
         let response_json: Value = response
             .json()
             .await
-            .context("Failed to parse Blockfrost submission response")?;
+            .map_err(|e| SubmitError::Other(format!("Failed to parse Blockfrost submission response: {}", e)))?;
 
         let tx_hash = response_json
             .as_str()
-            .ok_or_else(|| anyhow!("Expected tx hash string in response"))?
+            .ok_or_else(|| SubmitError::Other("Expected tx hash string in response".to_string()))?
             .to_string();
 
         Ok(tx_hash)
     }
 }
+
+/// Submits through the `submitTransaction` method of Ogmios' JSON-RPC over HTTP
+pub struct OgmiosSubmitter {
+    ogmios_url: String,
+    http_client: HttpClient,
+}
+
+impl OgmiosSubmitter {
+    pub fn new(ogmios_url: String, http_client: HttpClient) -> Self {
+        Self { ogmios_url, http_client }
+    }
+}
+
+/// Readable reason of an Ogmios JSON-RPC `error`: its message and code, then the ledger's details
+///
+/// Script evaluation failures list the error of each failing validator.
+pub fn ogmios_error_reason(error: &Value) -> String {
+    let message = error.get("message").and_then(Value::as_str).unwrap_or("unknown error");
+    let mut reason = match error.get("code").and_then(Value::as_i64) {
+        Some(code) => format!("{} (code {})", message, code),
+        None => message.to_string(),
+    };
+
+    match error.get("data") {
+        None | Some(Value::Null) => {}
+        Some(Value::Array(failures)) if failures.iter().all(|failure| failure.get("validator").is_some()) => {
+            for failure in failures {
+                let validator = &failure["validator"];
+                reason.push_str(&format!(
+                    "; {} #{}: {}",
+                    validator.get("purpose").and_then(Value::as_str).unwrap_or("validator"),
+                    validator.get("index").and_then(Value::as_u64).unwrap_or_default(),
+                    ogmios_error_reason(&failure["error"])
+                ));
+            }
+        }
+        Some(data) => reason.push_str(&format!(": {}", data)),
+    }
+
+    reason
+}
+
+#[async_trait::async_trait]
+impl TxSubmitter for OgmiosSubmitter {
+    async fn submit(&self, signed_tx: Vec<u8>) -> Result<String, SubmitError> {
+        let request = json!({
+            "jsonrpc": "2.0",
+            "method": "submitTransaction",
+            "params": { "transaction": { "cbor": hex::encode(&signed_tx) } },
+            "id": null,
+        });
+
+        let response = self
+            .http_client
+            .post(&self.ogmios_url)
+            .json(&request)
+            .send()
+            .instrument(logging::http_span("ogmios", &reqwest::Method::POST, &self.ogmios_url))
+            .await
+            .map_err(|e| SubmitError::Network(format!("Failed to submit transaction to Ogmios: {}", redact(&e.to_string()))))?;
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = response.text().await.unwrap_or_default();
+        let Ok(reply) = serde_json::from_str::<Value>(&body) else {
+            let message = format!("Ogmios transaction submission failed (status {}): {}", status, redact(&body));
+            return Err(SubmitError::from_response(status, &headers, &body, message));
+        };
+
+        if let Some(error) = reply.get("error") {
+            let reason = ogmios_error_reason(error);
+            let message = format!("Ogmios rejected the transaction: {}", redact(&reason));
+            return Err(SubmitError::from_response(status, &headers, &reason, message));
+        }
+
+        let tx_id = reply
+            .pointer("/result/transaction/id")
+            .and_then(Value::as_str)
+            .ok_or_else(|| SubmitError::Other(format!("Expected a transaction id in the Ogmios response: {}", redact(&body))))?
+            .to_string();
+
+        Ok(tx_id)
+    }
+}
@@ -0,0 +1,81 @@
+use anyhow::{Context, Result};
+use once_cell::sync::OnceCell;
+use opentelemetry::KeyValue;
+use opentelemetry::trace::TracerProvider as _;
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+/// `service.name` of the exported spans
+pub const SERVICE_NAME: &str = "shipping-oracle";
+
+/// Variables that turn the OTLP export on; the exporter reads the other `OTEL_EXPORTER_OTLP_*` ones itself
+const ENDPOINT_VARS: &[&str] = &["OTEL_EXPORTER_OTLP_ENDPOINT", "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT"];
+
+/// Provider installed by `layer_from_env`, kept to flush its spans on `shutdown`
+static PROVIDER: OnceCell<TracerProvider> = OnceCell::new();
+
+/// Resource the spans are exported with: the service name and version, and the Cardano network if known
+pub fn resource(network: Option<&str>) -> Resource {
+    let mut attributes = vec![
+        KeyValue::new("service.name", SERVICE_NAME),
+        KeyValue::new("service.version", env!("CARGO_PKG_VERSION")),
+    ];
+    if let Some(network) = network {
+        attributes.push(KeyValue::new("cardano.network", network.to_string()));
+    }
+
+    Resource::new(attributes)
+}
+
+/// Layer exporting the `run`, `shipment` and `http.request` spans through `provider`
+pub fn layer<S>(provider: &TracerProvider) -> OpenTelemetryLayer<S, Tracer>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(SERVICE_NAME))
+}
+
+/// OTLP export layer when `OTEL_EXPORTER_OTLP_ENDPOINT` (or its `_TRACES_` variant) is set
+///
+/// Spans are batched and sent over gRPC. The resource is labelled with
+/// `CARDANO_NETWORK` from the process environment, so not under `TENANTS`.
+pub fn layer_from_env<S>() -> Result<Option<OpenTelemetryLayer<S, Tracer>>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let configured = ENDPOINT_VARS
+        .iter()
+        .any(|key| std::env::var(key).is_ok_and(|value| !value.trim().is_empty()));
+    if !configured {
+        return Ok(None);
+    }
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .build()
+        .context("Failed to build the OTLP span exporter")?;
+    let network = std::env::var("CARDANO_NETWORK")
+        .ok()
+        .map(|network| network.trim().to_lowercase())
+        .filter(|network| !network.is_empty());
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_resource(resource(network.as_deref()))
+        .build();
+
+    let layer = layer(&provider);
+    let _ = PROVIDER.set(provider);
+    Ok(Some(layer))
+}
+
+/// Export the spans still batched; a no-op without OTLP export
+pub fn shutdown() {
+    if let Some(provider) = PROVIDER.get()
+        && let Err(e) = provider.shutdown()
+    {
+        eprintln!("⚠️  Failed to flush OpenTelemetry spans: {}", e);
+    }
+}
@@ -0,0 +1,81 @@
+use anyhow::{Context, Result, bail};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::config::Config;
+
+/// One isolated oracle pipeline in a multi-tenant deployment
+#[derive(Debug, Clone)]
+pub struct Tenant {
+    pub name: String,
+    pub config: Config,
+}
+
+/// Load every `[tenant.<name>]` section of the `TENANTS` file
+pub fn load(path: impl AsRef<Path>) -> Result<Vec<Tenant>> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read tenants file {}", path.display()))?;
+
+    parse(&contents).with_context(|| format!("Invalid tenants file {}", path.display()))
+}
+
+/// Parse tenants from TOML
+///
+/// Each `[tenant.<name>]` section holds a full configuration keyed by the
+/// environment variable names (`ORACLE_ADDRESS = "addr..."`). Sections do not
+/// inherit from the process environment or from each other.
+pub fn parse(contents: &str) -> Result<Vec<Tenant>> {
+    let document: toml::Table = toml::from_str(contents)?;
+
+    for key in document.keys() {
+        if key != "tenant" {
+            bail!("unexpected top-level key '{}' (expected [tenant.<name>] sections)", key);
+        }
+    }
+
+    let Some(sections) = document.get("tenant").and_then(toml::Value::as_table) else {
+        bail!("no [tenant.<name>] sections");
+    };
+
+    if sections.is_empty() {
+        bail!("no [tenant.<name>] sections");
+    }
+
+    let mut tenants = Vec::with_capacity(sections.len());
+    for (name, section) in sections {
+        validate_name(name)?;
+
+        let Some(section) = section.as_table() else {
+            bail!("tenant.{} must be a table", name);
+        };
+
+        let mut vars = HashMap::new();
+        for (key, value) in section {
+            let Some(value) = value.as_str() else {
+                bail!("tenant.{}.{} must be a string", name, key);
+            };
+            vars.insert(key.as_str(), value.to_string());
+        }
+
+        let mut config = Config::from_vars(|key| vars.get(key).cloned())
+            .with_context(|| format!("Invalid configuration for tenant {}", name))?;
+        config.tenant = Some(name.clone());
+
+        tenants.push(Tenant { name: name.clone(), config });
+    }
+
+    Ok(tenants)
+}
+
+/// Tenant names label logs and namespace state keys, so keep them plain
+fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if !valid {
+        bail!("tenant name '{}' may only contain letters, digits, '-' and '_'", name);
+    }
+
+    Ok(())
+}
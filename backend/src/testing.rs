@@ -0,0 +1,634 @@
+use anyhow::{Context, Result};
+use pallas::ledger::addresses::Address;
+use pallas::ledger::traverse::MultiEraTx;
+use serde_json::{Value, json};
+use chrono::{DateTime, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tx3_sdk::trp::TxEnvelope;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+use crate::blockchain::CardanoClient;
+use crate::clock::Clock;
+use crate::config::{
+    Config, DEFAULT_BLOCKFROST_BASE_BACKOFF_MS, DEFAULT_BLOCKFROST_MAX_BACKOFF_MS, DEFAULT_BLOCKFROST_MAX_PAGES,
+    DEFAULT_CLOSE_CONCURRENCY, DEFAULT_CLOSE_QUEUE_DEPTH,
+    DEFAULT_FETCH_CONCURRENCY, DEFAULT_MAX_FEE_LOVELACE, DEFAULT_METRICS_MAX_OUTBOXES, DEFAULT_PENDING_TX_TTL_MINUTES,
+    DEFAULT_ROLLBACK_DEPTH, DEFAULT_SHUTDOWN_GRACE_SECONDS,
+};
+use crate::indexer::{IndexerKind, ScanMode};
+use crate::models::{DerivedStatus, TrackingDatum, TrackingNumber, TrackingStatus, TrackingUTxO};
+use crate::notifier::NotifyMode;
+use crate::scheduler::{OverlapPolicy, RunMode};
+use crate::shipment::ShipmentError;
+use crate::shippo_budget::DEFAULT_DEGRADED_TRANSIT_HOURS;
+use crate::signing::SignerKind;
+use crate::stale_status::DEFAULT_STALE_STATUSES;
+use crate::status_mapping::StatusMapping;
+use crate::submitter::{DEFAULT_SUBMIT_BASE_BACKOFF_MS, SubmitError, SubmitterKind, TxSubmitter};
+use crate::timestamp_source::TimestampSource;
+use crate::tracking_provider::{ProviderKind, TrackingProvider};
+
+pub const ORACLE_ADDRESS: &str = "addr_test1vqpp4rqsgkhyaz5ejjtwzane9wnkggfrn9pptgmtwq7fqws6t8yck";
+/// `ORACLE_ADDRESS`'s key as a mainnet enterprise address
+pub const MAINNET_ORACLE_ADDRESS: &str = "addr1vypp4rqsgkhyaz5ejjtwzane9wnkggfrn9pptgmtwq7fqwsprnchn";
+pub const ORACLE_PKH: &str = "021a8c1045ae4e8a999496e176792ba7642123994215a36b703c903a";
+pub const OUTBOX_ADDRESS: &str = "addr_test1qqcytargera54zzzgk9ajg2y2xlhrx4efgvjfe970vr57cxkxjyj4nx7n47t6s9saftdn3dypt4573lawvqutsh2ydrs3hxqj3";
+/// Shippo API key of `test_config`
+pub const SHIPPO_API_KEY: &str = "shippo_test_0123456789abcdef";
+pub const VALIDATOR_SCRIPT_REF: &str = "a6a57fe7cfcd69537dc88bfe4321cd7f164f26afd21c91c78cced224e6496f41#1";
+
+/// Raw bytes of `OUTBOX_ADDRESS` as stored in the datum
+const OUTBOX_ADDRESS_BYTES: &str = "003045f468c8fb4a8842458bd9214451bf719ab94a1924e4be7b074f60d634892accde9d7cbd40b0ea56d9c5a40aeb4f47fd7301c5c2ea2347";
+
+/// Close of one tracking UTxO as delivered at `CLOSABLE_CLOSED_AT`, resolved by TRP and valid to sign
+const CLOSE_ENVELOPE_FIXTURE: &str = include_str!("../tests/fixtures/close_shipment_envelope_valid.json");
+/// Tracking UTxO and tracking number the fixture close spends
+const FIXTURE_TRACKING_TX_HASH: &str = "a7a264ac1bef1f0da6312d16fb8f68b18d1a0d5010d8cb6a61790bf02a759301";
+const FIXTURE_TRACKING_NUMBER: &str = "9400111899223456789012";
+/// Timestamp of the shipment datum every `close_envelope` writes; the oracle clock must read it
+pub const CLOSABLE_CLOSED_AT: i64 = 1771090081;
+
+/// Configuration pointing every upstream at `base_url`
+pub fn test_config(base_url: &str) -> Config {
+    Config {
+        cron_schedule: "0 */5 * * * *".to_string(),
+        cron_timezone: chrono_tz::Tz::UTC,
+        submit_window: None,
+        shippo_api_key: SHIPPO_API_KEY.into(),
+        shippo_url: base_url.to_string(),
+        shippo_auto_register: false,
+        shippo_max_rps: None,
+        shippo_max_per_minute: None,
+        shippo_max_retries: 0,
+        easypost_api_key: None,
+        easypost_url: base_url.to_string(),
+        aftership_api_key: None,
+        aftership_url: base_url.to_string(),
+        aftership_slugs: BTreeMap::new(),
+        carrier_providers: BTreeMap::new(),
+        carrier_limits: BTreeMap::new(),
+        carrier_allowlist: None,
+        carrier_denylist: None,
+        tracking_provider: ProviderKind::Shippo,
+        mock_tracking_fixtures: None,
+        tracking_cache_ttl_secs: 0,
+        status_mapping: StatusMapping::default(),
+        stale_status_timeout_days: None,
+        stale_statuses: DEFAULT_STALE_STATUSES.iter().map(|status| status.to_string()).collect(),
+        validator_script_ref: VALIDATOR_SCRIPT_REF.parse().unwrap(),
+        oracle_signer: SignerKind::Local,
+        oracle_sks: vec!["00".repeat(32).into()],
+        oracle_sk_file: None,
+        oracle_mnemonic: None,
+        oracle_pkh: None,
+        oracle_validator_keys: HashMap::new(),
+        kms_key_id: None,
+        oracle_address: ORACLE_ADDRESS.to_string(),
+        oracle_payment_address: ORACLE_ADDRESS.to_string(),
+        blockfrost_url: base_url.to_string(),
+        trp_url: base_url.to_string(),
+        blockfrost_project_id: None,
+        blockfrost_max_pages: DEFAULT_BLOCKFROST_MAX_PAGES,
+        blockfrost_max_retries: 0,
+        blockfrost_base_backoff_ms: DEFAULT_BLOCKFROST_BASE_BACKOFF_MS,
+        blockfrost_max_backoff_ms: DEFAULT_BLOCKFROST_MAX_BACKOFF_MS,
+        chain_indexer: IndexerKind::Blockfrost,
+        kupo_url: None,
+        scan_mode: ScanMode::Full,
+        rollback_depth: DEFAULT_ROLLBACK_DEPTH,
+        submitter: SubmitterKind::Blockfrost,
+        ogmios_url: None,
+        submit_max_retries: 0,
+        submit_base_backoff_ms: DEFAULT_SUBMIT_BASE_BACKOFF_MS,
+        trp_api_key: None,
+        notify_webhook_url: None,
+        notify_webhook_secret: None,
+        notify_slack_webhook: None,
+        notify_discord_webhook: None,
+        notify_webhook_template: None,
+        notify_mode: NotifyMode::PerShipment,
+        cardano_network: None,
+        heartbeat_url: None,
+        metrics_addr: None,
+        outbox_aliases: BTreeMap::new(),
+        metrics_max_outboxes: DEFAULT_METRICS_MAX_OUTBOXES,
+        admin_addr: None,
+        admin_token: None,
+        sentry_dsn: None,
+        state_db_path: None,
+        tracking_lookup_path: None,
+        outbox_allowlist_file: None,
+        outbox_denylist_file: None,
+        max_fee_lovelace: DEFAULT_MAX_FEE_LOVELACE,
+        shippo_monthly_budget: None,
+        shippo_degraded_transit_hours: DEFAULT_DEGRADED_TRANSIT_HOURS,
+        validator_script_hash: None,
+        strict_startup: false,
+        recheck_before_sign: false,
+        pending_tx_ttl_minutes: DEFAULT_PENDING_TX_TTL_MINUTES,
+        fetch_concurrency: DEFAULT_FETCH_CONCURRENCY,
+        close_concurrency: DEFAULT_CLOSE_CONCURRENCY,
+        close_queue_depth: DEFAULT_CLOSE_QUEUE_DEPTH,
+        self_test_on_start: false,
+        dry_run: false,
+        shutdown_grace_seconds: DEFAULT_SHUTDOWN_GRACE_SECONDS,
+        overlap_policy: OverlapPolicy::Skip,
+        run_mode: RunMode::Scheduled,
+        timestamp_source: TimestampSource::Oracle,
+        datum_codecs: vec!["positional".to_string()],
+        http_proxy_shippo: None,
+        http_proxy_blockfrost: None,
+        no_proxy: None,
+        tenant: None,
+    }
+}
+
+/// Clock stopped at a given time, moving `tick` forward each time it is read
+pub struct FrozenClock {
+    now: Mutex<DateTime<Utc>>,
+    tick: chrono::Duration,
+}
+
+impl FrozenClock {
+    pub fn at(now: DateTime<Utc>) -> Self {
+        Self::ticking(now, chrono::Duration::zero())
+    }
+
+    pub fn ticking(now: DateTime<Utc>, tick: chrono::Duration) -> Self {
+        Self { now: Mutex::new(now), tick }
+    }
+}
+
+impl Clock for FrozenClock {
+    fn now(&self) -> DateTime<Utc> {
+        let mut now = self.now.lock().unwrap_or_else(|e| e.into_inner());
+        let current = *now;
+        *now = current + self.tick;
+        current
+    }
+}
+
+/// Hex-encoded inline datum `Constr0 [carrier, tracking_number, outbox]`
+pub fn tracking_datum_cbor(carrier: &str, tracking_number: &str) -> String {
+    datum_cbor(carrier, TrackingNumber::Plain(tracking_number.to_string()), None)
+}
+
+/// Privacy-mode inline datum carrying `hash` in place of the tracking number
+pub fn hashed_tracking_datum_cbor(carrier: &str, hash: &[u8; 32]) -> String {
+    datum_cbor(carrier, TrackingNumber::Hashed(*hash), None)
+}
+
+/// Inline datum `Constr0 [carrier, tracking_number, outbox, deadline]`
+pub fn deadline_tracking_datum_cbor(carrier: &str, tracking_number: &str, deadline: u64) -> String {
+    datum_cbor(carrier, TrackingNumber::Plain(tracking_number.to_string()), Some(deadline))
+}
+
+fn datum_cbor(carrier: &str, tracking_number: TrackingNumber, deadline: Option<u64>) -> String {
+    let outbox = hex::decode(OUTBOX_ADDRESS_BYTES).expect("outbox bytes are valid hex");
+
+    TrackingDatum {
+        carrier: carrier.to_string(),
+        tracking_number,
+        outbox_address: Address::from_bytes(&outbox).expect("outbox bytes are an address"),
+        deadline,
+    }
+    .to_cbor_hex()
+}
+
+/// Hex-encoded inline datum `{carrier, tracking_number, outbox_address}` for the `map` codec
+pub fn map_tracking_datum_cbor(carrier: &str, tracking_number: &str) -> String {
+    let outbox = hex::decode(OUTBOX_ADDRESS_BYTES).expect("outbox bytes are valid hex");
+
+    // Definite 3-entry map keyed by field name
+    let mut cbor = vec![0xa3];
+    for (key, value) in [
+        ("carrier", carrier.as_bytes()),
+        ("tracking_number", tracking_number.as_bytes()),
+        ("outbox_address", outbox.as_slice()),
+    ] {
+        push_cbor_bytes(&mut cbor, key.as_bytes());
+        push_cbor_bytes(&mut cbor, value);
+    }
+
+    hex::encode(cbor)
+}
+
+/// Hex-encoded inline datum `Constr0 [carrier, tracking_number, status, timestamp, oracle_pkh]`
+pub fn shipment_datum_cbor(carrier: &str, tracking_number: &str, status: &str, timestamp: u64, oracle_pkh: &str) -> String {
+    let oracle_pkh = hex::decode(oracle_pkh).expect("oracle pkh is valid hex");
+
+    // Tag 121 (constructor 0) wrapping a definite 5-element array
+    let mut cbor = vec![0xd8, 0x79, 0x85];
+    push_cbor_bytes(&mut cbor, carrier.as_bytes());
+    push_cbor_bytes(&mut cbor, tracking_number.as_bytes());
+    push_cbor_bytes(&mut cbor, status.as_bytes());
+    cbor.push(0x1b);
+    cbor.extend(timestamp.to_be_bytes());
+    push_cbor_bytes(&mut cbor, &oracle_pkh);
+
+    hex::encode(cbor)
+}
+
+fn push_cbor_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    match bytes.len() {
+        len @ 0..=23 => out.push(0x40 | len as u8),
+        len @ 24..=0xff => out.extend([0x58, len as u8]),
+        len => {
+            let len = u16::try_from(len).expect("datum field longer than 65535 bytes");
+            out.push(0x59);
+            out.extend(len.to_be_bytes());
+        }
+    }
+    out.extend_from_slice(bytes);
+}
+
+/// Deterministic tracking number for the `index`-th generated shipment
+pub fn tracking_number(index: usize) -> String {
+    format!("TRK{:010}", index)
+}
+
+/// `count` distinct inline datums
+pub fn datum_corpus(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|i| tracking_datum_cbor("usps", &tracking_number(i)))
+        .collect()
+}
+
+/// Blockfrost `/addresses/{address}/utxos` response holding `count` tracking UTxOs
+pub fn blockfrost_utxos(count: usize) -> Value {
+    let utxos: Vec<Value> = (0..count)
+        .map(|i| blockfrost_utxo(i, &tracking_datum_cbor("usps", &tracking_number(i))))
+        .collect();
+
+    Value::Array(utxos)
+}
+
+/// The `index`-th generated UTxO at the oracle address, `{index:064x}#0`, holding `inline_datum`
+fn blockfrost_utxo(index: usize, inline_datum: &str) -> Value {
+    json!({
+        "address": ORACLE_ADDRESS,
+        "tx_hash": format!("{:064x}", index),
+        "tx_index": 0,
+        "output_index": 0,
+        "amount": [{ "unit": "lovelace", "quantity": "2000000" }],
+        "block": format!("{:064x}", index / 20),
+        "data_hash": null,
+        "inline_datum": inline_datum,
+        "reference_script_hash": null,
+    })
+}
+
+/// Tracking number of the `index`-th closable shipment, as long as the fixture close's
+pub fn closable_tracking_number(index: usize) -> String {
+    format!("94001118992{:011}", index)
+}
+
+/// Blockfrost UTxOs of `count` USPS shipments whose closes `close_envelope` builds
+pub fn closable_utxos(count: usize) -> Vec<Value> {
+    (0..count)
+        .map(|i| blockfrost_utxo(i, &tracking_datum_cbor("usps", &closable_tracking_number(i))))
+        .collect()
+}
+
+/// Close of the `index`-th closable shipment as delivered at `CLOSABLE_CLOSED_AT`, as TRP would resolve it
+///
+/// The fixture close is rewritten to spend that UTxO into a datum with its
+/// tracking number, and hashed again, so it passes validation and signing.
+pub fn close_envelope(index: usize) -> Result<TxEnvelope> {
+    let fixture: Value = serde_json::from_str(CLOSE_ENVELOPE_FIXTURE).context("Invalid close envelope fixture")?;
+    let tx = fixture["tx"]
+        .as_str()
+        .context("Close envelope fixture has no tx")?
+        .replace(FIXTURE_TRACKING_TX_HASH, &format!("{:064x}", index))
+        .replace(&hex::encode(FIXTURE_TRACKING_NUMBER), &hex::encode(closable_tracking_number(index)));
+
+    let bytes = hex::decode(&tx)?;
+    let hash = MultiEraTx::decode(&bytes).context("Failed to decode the rewritten close")?.hash().to_string();
+    Ok(TxEnvelope { tx, hash })
+}
+
+/// Shippo `/tracks/{carrier}/{tracking_number}` response with the given status
+pub fn shippo_track(carrier: &str, tracking_number: &str, status: &str) -> Value {
+    json!({
+        "carrier": carrier,
+        "tracking_number": tracking_number,
+        "tracking_status": {
+            "status": status,
+            "status_details": format!("Shipment is {}", status.to_lowercase()),
+            "status_date": "2026-02-14T17:28:01.000Z",
+        },
+        "tracking_history": [],
+    })
+}
+
+/// Blockfrost list responder serving `utxos` one page at a time, following the
+/// `page` and `count` query parameters like Blockfrost does
+pub struct PagedUtxos(pub Vec<Value>);
+
+impl Respond for PagedUtxos {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let param = |name: &str, default: usize| {
+            request
+                .url
+                .query_pairs()
+                .find(|(key, _)| key == name)
+                .and_then(|(_, value)| value.parse().ok())
+                .unwrap_or(default)
+        };
+        let (page, count) = (param("page", 1).max(1), param("count", 100));
+
+        let page: Vec<&Value> = self.0.iter().skip((page - 1) * count).take(count).collect();
+        ResponseTemplate::new(200).set_body_json(page)
+    }
+}
+
+/// In-process Blockfrost and Shippo serving a fixed set of shipments
+pub struct MockProviders {
+    server: MockServer,
+}
+
+impl MockProviders {
+    /// Serve `shipments` tracking UTxOs, all reporting `status` on Shippo
+    pub async fn start(shipments: usize, status: &str) -> Self {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+            .respond_with(PagedUtxos(blockfrost_utxos(shipments).as_array().cloned().unwrap_or_default()))
+            .mount(&server)
+            .await;
+
+        Mock::given(method("GET"))
+            .and(path_regex("^/tracks/"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", status)))
+            .mount(&server)
+            .await;
+
+        Self { server }
+    }
+
+    pub fn uri(&self) -> String {
+        self.server.uri()
+    }
+
+    pub fn config(&self) -> Config {
+        test_config(&self.uri())
+    }
+}
+
+/// In-process Blockfrost and TRP for `count` closable shipments
+///
+/// Blockfrost lists the `closable_utxos`; TRP resolves the close of each to its
+/// `close_envelope`. No tracking API is served: pair it with a fake provider.
+pub struct ClosableShipments {
+    server: MockServer,
+}
+
+impl ClosableShipments {
+    pub async fn start(count: usize) -> Self {
+        let server = MockServer::start().await;
+
+        Mock::given(method("GET"))
+            .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+            .respond_with(PagedUtxos(closable_utxos(count)))
+            .mount(&server)
+            .await;
+
+        let envelopes = (0..count)
+            .map(|i| Ok((format!("{:064x}#0", i), close_envelope(i)?)))
+            .collect::<Result<HashMap<_, _>>>()
+            .expect("closable envelopes");
+        Mock::given(method("POST"))
+            .and(path("/"))
+            .respond_with(TrpCloses(envelopes))
+            .mount(&server)
+            .await;
+
+        Self { server }
+    }
+
+    pub fn uri(&self) -> String {
+        self.server.uri()
+    }
+
+    pub fn config(&self) -> Config {
+        test_config(&self.uri())
+    }
+
+    /// Closes TRP was asked to resolve so far
+    pub async fn resolves(&self) -> usize {
+        let requests = self.server.received_requests().await.unwrap_or_default();
+        requests.iter().filter(|request| request.method.as_str() == "POST").count()
+    }
+}
+
+/// TRP resolving each close to the envelope of its `p_utxo_ref`, echoing the JSON-RPC id
+struct TrpCloses(HashMap<String, TxEnvelope>);
+
+impl Respond for TrpCloses {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let body = serde_json::from_slice::<Value>(&request.body).unwrap_or(Value::Null);
+        let id = body.get("id").cloned().unwrap_or(Value::Null);
+
+        match find_key(&body, "p_utxo_ref").and_then(Value::as_str).and_then(|utxo_ref| self.0.get(utxo_ref)) {
+            Some(envelope) => ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "result": { "tx": envelope.tx, "hash": envelope.hash },
+            })),
+            None => ResponseTemplate::new(200).set_body_json(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": -32602, "message": "unknown p_utxo_ref" },
+            })),
+        }
+    }
+}
+
+/// First value under `key` anywhere in `value`
+fn find_key<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    match value {
+        Value::Object(map) => map.get(key).or_else(|| map.values().find_map(|value| find_key(value, key))),
+        Value::Array(values) => values.iter().find_map(|value| find_key(value, key)),
+        _ => None,
+    }
+}
+
+/// Deterministic draw for `key` under `seed`: FNV-1a, then the splitmix64 finalizer
+fn seeded_draw(seed: u64, key: &str) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325 ^ seed;
+    for byte in key.bytes() {
+        hash = (hash ^ byte as u64).wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    let mut z = hash.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// What `FlakyProvider` answered to one call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderOutcome {
+    /// The configured status
+    Status,
+    /// `ShipmentError::RateLimited`, to retry shortly
+    RateLimited,
+    /// `ShipmentError::Network`, to retry next run
+    Network,
+    /// `ShipmentError::InvalidResponse`, which no retry fixes
+    Invalid,
+}
+
+impl ProviderOutcome {
+    pub fn is_failure(&self) -> bool {
+        *self != ProviderOutcome::Status
+    }
+}
+
+/// One `FlakyProvider::fetch_status` call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderCall {
+    pub carrier: String,
+    pub tracking_number: String,
+    pub outcome: ProviderOutcome,
+    pub latency: Duration,
+}
+
+/// Tracking provider answering one status for every shipment, except for a seeded share of calls
+///
+/// Failing calls are split between transient (`RateLimited`, `Network`) and
+/// permanent (`InvalidResponse`) errors. Each answer comes after a latency drawn
+/// up to `max_latency`. Outcome and latency only depend on the seed, the
+/// shipment and how many times it was asked before, not on the call order.
+pub struct FlakyProvider {
+    seed: u64,
+    failure_percent: u64,
+    max_latency: Duration,
+    status: String,
+    calls: Mutex<Vec<ProviderCall>>,
+}
+
+impl FlakyProvider {
+    /// Answer `status`, or fail `failure_percent` percent of calls
+    pub fn new(seed: u64, failure_percent: u64, status: &str) -> Self {
+        Self { seed, failure_percent, max_latency: Duration::ZERO, status: status.to_string(), calls: Mutex::new(Vec::new()) }
+    }
+
+    pub fn with_max_latency(mut self, max_latency: Duration) -> Self {
+        self.max_latency = max_latency;
+        self
+    }
+
+    /// Every call so far, in the order they were made
+    pub fn calls(&self) -> Vec<ProviderCall> {
+        self.calls.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl TrackingProvider for FlakyProvider {
+    async fn fetch_status(&self, carrier: &str, tracking_number: &str) -> Result<TrackingStatus> {
+        let call = {
+            let mut calls = self.calls.lock().unwrap_or_else(|e| e.into_inner());
+            let attempt = calls.iter().filter(|call| call.carrier == carrier && call.tracking_number == tracking_number).count();
+            let draw = seeded_draw(self.seed, &format!("{}/{}#{}", carrier, tracking_number, attempt));
+            let outcome = match (draw % 100 < self.failure_percent, (draw >> 32) % 3) {
+                (false, _) => ProviderOutcome::Status,
+                (true, 0) => ProviderOutcome::RateLimited,
+                (true, 1) => ProviderOutcome::Network,
+                (true, _) => ProviderOutcome::Invalid,
+            };
+            let latency = self.max_latency.mul_f64(((draw >> 8) % 1000) as f64 / 1000.0);
+            let call = ProviderCall { carrier: carrier.to_string(), tracking_number: tracking_number.to_string(), outcome, latency };
+            calls.push(call.clone());
+            call
+        };
+
+        tokio::time::sleep(call.latency).await;
+        let message = format!("{} {} failed on purpose", carrier, tracking_number);
+        match call.outcome {
+            ProviderOutcome::Status => Ok(TrackingStatus {
+                status: self.status.as_str().into(),
+                status_details: format!("Shipment is {}", self.status.to_lowercase()),
+                status_date: None,
+            }),
+            ProviderOutcome::RateLimited => {
+                Err(ShipmentError::RateLimited { retry_after: Some(Duration::from_millis(10)), message }.into())
+            }
+            ProviderOutcome::Network => Err(ShipmentError::Network(message).into()),
+            ProviderOutcome::Invalid => Err(ShipmentError::InvalidResponse(message).into()),
+        }
+    }
+}
+
+/// One transaction handed to `RecordingSubmitter`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Submission {
+    pub tx_hash: String,
+    /// UTxOs (`TxHash#TxIx`) the transaction spends
+    pub inputs: Vec<String>,
+    /// Accepted, rather than answered as already on-chain
+    pub accepted: bool,
+    /// The signed transaction as submitted
+    pub cbor: Vec<u8>,
+}
+
+/// Submitter accepting every transaction, except for a seeded share it answers as already on-chain
+///
+/// Clones share their log of submissions.
+#[derive(Clone)]
+pub struct RecordingSubmitter {
+    seed: u64,
+    duplicate_percent: u64,
+    latency: Duration,
+    submissions: Arc<Mutex<Vec<Submission>>>,
+}
+
+impl RecordingSubmitter {
+    /// Answer `duplicate_percent` percent of submissions as duplicates
+    pub fn new(seed: u64, duplicate_percent: u64) -> Self {
+        Self { seed, duplicate_percent, latency: Duration::ZERO, submissions: Arc::new(Mutex::new(Vec::new())) }
+    }
+
+    /// Take `latency` to answer every submission
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Every submission so far, in the order they were made
+    pub fn submissions(&self) -> Vec<Submission> {
+        self.submissions.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+}
+
+#[async_trait::async_trait]
+impl TxSubmitter for RecordingSubmitter {
+    async fn submit(&self, signed_tx: Vec<u8>) -> Result<String, SubmitError> {
+        tokio::time::sleep(self.latency).await;
+        let tx = MultiEraTx::decode(&signed_tx).map_err(|e| SubmitError::Other(format!("Undecodable transaction: {}", e)))?;
+        let tx_hash = tx.hash().to_string();
+        let inputs = tx.inputs().iter().map(|input| format!("{}#{}", input.hash(), input.index())).collect();
+
+        let mut submissions = self.submissions.lock().unwrap_or_else(|e| e.into_inner());
+        let attempt = submissions.iter().filter(|submission| submission.tx_hash == tx_hash).count();
+        let accepted = seeded_draw(self.seed, &format!("{}#{}", tx_hash, attempt)) % 100 >= self.duplicate_percent;
+        submissions.push(Submission { tx_hash: tx_hash.clone(), inputs, accepted, cbor: signed_tx.clone() });
+
+        if accepted {
+            Ok(tx_hash)
+        } else {
+            Err(SubmitError::AlreadySpent(format!("BadInputsUTxO: {} is already on-chain", tx_hash)))
+        }
+    }
+}
+
+/// Teardown: close a provisioned tracking UTxO for real so it does not
+/// accumulate at the oracle address, returning the close's transaction hash
+pub async fn close_provisioned(config: &Config, tracking: &TrackingUTxO, status: DerivedStatus) -> Result<String> {
+    CardanoClient::new(config.clone())?.submit_shipment(tracking, status).await
+}
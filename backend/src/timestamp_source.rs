@@ -0,0 +1,103 @@
+use anyhow::{Result, anyhow};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::fmt;
+use std::str::FromStr;
+
+/// Carrier dates at most this far ahead of the oracle clock are accepted as clock skew
+const MAX_CARRIER_CLOCK_SKEW_SECS: i64 = 5 * 60;
+
+/// Carrier dates older than this are treated as bogus
+const MAX_CARRIER_DATE_AGE_DAYS: i64 = 90;
+
+/// Where the `p_timestamp` of a close comes from (`TIMESTAMP_SOURCE`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimestampSource {
+    /// When the oracle closes the shipment
+    Oracle,
+    /// The carrier's `status_date`, when present and plausible, else the oracle clock
+    #[default]
+    Carrier,
+    /// Chosen by the operator closing the shipment by hand; not a `TIMESTAMP_SOURCE`
+    Operator,
+}
+
+impl FromStr for TimestampSource {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "oracle" => Ok(TimestampSource::Oracle),
+            "carrier" => Ok(TimestampSource::Carrier),
+            other => Err(anyhow!("expected oracle or carrier, got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for TimestampSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TimestampSource::Oracle => "oracle",
+            TimestampSource::Carrier => "carrier",
+            TimestampSource::Operator => "operator",
+        })
+    }
+}
+
+/// The `p_timestamp` of a close and where it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CloseTimestamp {
+    /// Unix timestamp written in the shipment datum
+    pub timestamp: u64,
+    pub source: TimestampSource,
+    /// Oracle clock when the close was prepared
+    pub oracle_timestamp: u64,
+    /// Why the carrier date was not used when the carrier source fell back to the oracle clock
+    pub fallback: Option<String>,
+}
+
+impl CloseTimestamp {
+    pub fn oracle(now: DateTime<Utc>) -> Self {
+        let timestamp = now.timestamp() as u64;
+        Self {
+            timestamp,
+            source: TimestampSource::Oracle,
+            oracle_timestamp: timestamp,
+            fallback: None,
+        }
+    }
+}
+
+impl TimestampSource {
+    /// Timestamp for a close prepared at `now` of a shipment the carrier last updated at `status_date`
+    pub fn resolve(self, status_date: Option<&str>, now: DateTime<Utc>) -> CloseTimestamp {
+        let oracle = CloseTimestamp::oracle(now);
+        if self == TimestampSource::Oracle {
+            return oracle;
+        }
+
+        match carrier_timestamp(status_date, now) {
+            Ok(timestamp) => CloseTimestamp { timestamp, source: TimestampSource::Carrier, ..oracle },
+            Err(reason) => CloseTimestamp { fallback: Some(reason), ..oracle },
+        }
+    }
+}
+
+fn carrier_timestamp(status_date: Option<&str>, now: DateTime<Utc>) -> Result<u64, String> {
+    let Some(status_date) = status_date.map(str::trim).filter(|date| !date.is_empty()) else {
+        return Err("the carrier reported no status date".to_string());
+    };
+
+    let date = DateTime::parse_from_rfc3339(status_date)
+        .map_err(|_| format!("carrier status date {} is not an RFC 3339 date", status_date))?
+        .with_timezone(&Utc);
+    if date > now + Duration::seconds(MAX_CARRIER_CLOCK_SKEW_SECS) {
+        return Err(format!("carrier status date {} is in the future", status_date));
+    }
+    if date < now - Duration::days(MAX_CARRIER_DATE_AGE_DAYS) {
+        return Err(format!("carrier status date {} is more than {} days old", status_date, MAX_CARRIER_DATE_AGE_DAYS));
+    }
+
+    Ok(date.timestamp() as u64)
+}
@@ -0,0 +1,57 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::models::TrackingStatus;
+use crate::status_mapping::StatusMapping;
+
+/// A status and when the carrier was asked for it
+#[derive(Debug)]
+struct Entry {
+    status: TrackingStatus,
+    fetched_at: DateTime<Utc>,
+}
+
+/// Carrier statuses reused for `ttl` after they were fetched (`TRACKING_CACHE_TTL_SECS`)
+///
+/// Only statuses the `StatusMapping` leaves open are kept: a final one leads to a
+/// close attempt, and if that fails the next run must ask the carrier again.
+#[derive(Debug)]
+pub struct TrackingCache {
+    ttl: Duration,
+    mapping: StatusMapping,
+    entries: Mutex<HashMap<(String, String), Entry>>,
+}
+
+impl TrackingCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self { ttl, mapping: StatusMapping::default(), entries: Mutex::new(HashMap::new()) }
+    }
+
+    /// Tell final statuses apart with `mapping` rather than the default one
+    pub fn with_mapping(mut self, mapping: StatusMapping) -> Self {
+        self.mapping = mapping;
+        self
+    }
+
+    /// Status of `tracking_number` fetched less than `ttl` before `now`
+    pub fn get(&self, carrier: &str, tracking_number: &str, now: DateTime<Utc>) -> Option<TrackingStatus> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = entries.get(&(carrier.to_string(), tracking_number.to_string()))?;
+        (now - entry.fetched_at < self.ttl).then(|| entry.status.clone())
+    }
+
+    /// Remember `status` as fetched at `now`, forgetting the pair instead when it is final
+    pub fn insert(&self, carrier: &str, tracking_number: &str, status: &TrackingStatus, now: DateTime<Utc>) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        // Expired entries of shipments no longer polled would otherwise stay forever
+        entries.retain(|_, entry| now - entry.fetched_at < self.ttl);
+
+        let key = (carrier.to_string(), tracking_number.to_string());
+        if self.mapping.map(status).is_some() {
+            entries.remove(&key);
+        } else {
+            entries.insert(key, Entry { status: status.clone(), fetched_at: now });
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use anyhow::{Context, Result, anyhow, bail};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::aftership::AfterShipProvider;
+use crate::carrier_limits::CarrierLimits;
+use crate::config::Config;
+use crate::easypost::EasyPostProvider;
+use crate::metrics::Metrics;
+use crate::mock_provider::MockProvider;
+use crate::models::TrackingStatus;
+use crate::shipment::ShipmentClient;
+
+/// Source of carrier tracking statuses
+#[async_trait::async_trait]
+pub trait TrackingProvider: Send + Sync {
+    /// Latest status of `tracking_number` in Shippo's vocabulary (`TRANSIT`, `DELIVERED`, `RETURNED`, `FAILURE`, ...)
+    async fn fetch_status(&self, carrier: &str, tracking_number: &str) -> Result<TrackingStatus>;
+
+    /// Provider answering for `carrier`; only Shippo calls count against `SHIPPO_MONTHLY_BUDGET`
+    fn provider(&self, _carrier: &str) -> ProviderKind {
+        ProviderKind::Shippo
+    }
+}
+
+/// Tracking APIs a shipment can be routed to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ProviderKind {
+    #[default]
+    Shippo,
+    EasyPost,
+    AfterShip,
+    /// Statuses derived from the tracking number, for local runs and demos
+    Mock,
+}
+
+impl FromStr for ProviderKind {
+    type Err = anyhow::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "shippo" => Ok(ProviderKind::Shippo),
+            "easypost" => Ok(ProviderKind::EasyPost),
+            "aftership" => Ok(ProviderKind::AfterShip),
+            "mock" => Ok(ProviderKind::Mock),
+            other => Err(anyhow!("expected shippo, easypost, aftership or mock, got '{}'", other)),
+        }
+    }
+}
+
+impl fmt::Display for ProviderKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ProviderKind::Shippo => "shippo",
+            ProviderKind::EasyPost => "easypost",
+            ProviderKind::AfterShip => "aftership",
+            ProviderKind::Mock => "mock",
+        })
+    }
+}
+
+/// Parse `CARRIER_PROVIDERS`: comma-separated `carrier=provider` pairs, e.g. `fedex=easypost,ups=easypost`
+pub fn parse_carrier_providers(value: &str) -> Result<BTreeMap<String, ProviderKind>> {
+    let mut carriers = BTreeMap::new();
+    for pair in value.split(',').map(str::trim).filter(|pair| !pair.is_empty()) {
+        let Some((carrier, provider)) = pair.split_once('=') else {
+            bail!("expected carrier=provider, got '{}'", pair);
+        };
+        let carrier = carrier.trim().to_lowercase();
+        if carrier.is_empty() {
+            bail!("expected carrier=provider, got '{}'", pair);
+        }
+        carriers.insert(carrier, provider.parse()?);
+    }
+
+    Ok(carriers)
+}
+
+/// Routes every shipment to the tracking provider of its carrier
+///
+/// A datum carrier `<provider>:<carrier>` (e.g. `easypost:fedex`) names its provider and is
+/// queried as `<carrier>`. Other carriers go where `CARRIER_PROVIDERS` sends them, else to
+/// the default provider (`TRACKING_PROVIDER`, Shippo unless set). Requests are
+/// limited per queried carrier by `CARRIER_LIMITS`, and timed per carrier.
+pub struct TrackingProviders {
+    providers: HashMap<ProviderKind, Arc<dyn TrackingProvider>>,
+    carriers: BTreeMap<String, ProviderKind>,
+    default: ProviderKind,
+    limits: CarrierLimits,
+    metrics: Metrics,
+}
+
+impl TrackingProviders {
+    /// Every carrier tracked through `shippo`
+    pub fn new(shippo: Arc<dyn TrackingProvider>) -> Self {
+        Self {
+            providers: HashMap::from([(ProviderKind::Shippo, shippo)]),
+            carriers: BTreeMap::new(),
+            default: ProviderKind::Shippo,
+            limits: CarrierLimits::default(),
+            metrics: Metrics::new(),
+        }
+    }
+
+    pub fn with_provider(mut self, kind: ProviderKind, provider: Arc<dyn TrackingProvider>) -> Self {
+        self.providers.insert(kind, provider);
+        self
+    }
+
+    /// Send unprefixed carriers (lowercase) to the given providers
+    pub fn with_carriers(mut self, carriers: BTreeMap<String, ProviderKind>) -> Self {
+        self.carriers = carriers;
+        self
+    }
+
+    /// Send carriers neither prefixed nor mapped to `kind` instead of Shippo
+    pub fn with_default(mut self, kind: ProviderKind) -> Self {
+        self.default = kind;
+        self
+    }
+
+    /// Pace and retry each carrier's requests within `limits`
+    pub fn with_carrier_limits(mut self, limits: CarrierLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Record the latency and errors of every request, per carrier, in `metrics`
+    pub fn with_metrics(mut self, metrics: Metrics) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Provider of a datum `carrier`, and the carrier name to query it with
+    pub fn route<'a>(&self, carrier: &'a str) -> (ProviderKind, &'a str) {
+        if let Some((prefix, name)) = carrier.split_once(':')
+            && let Ok(kind) = prefix.parse()
+        {
+            return (kind, name);
+        }
+
+        let kind = self.carriers.get(&carrier.to_lowercase()).copied().unwrap_or(self.default);
+        (kind, carrier)
+    }
+}
+
+#[async_trait::async_trait]
+impl TrackingProvider for TrackingProviders {
+    async fn fetch_status(&self, carrier: &str, tracking_number: &str) -> Result<TrackingStatus> {
+        let (kind, carrier) = self.route(carrier);
+        let provider = self
+            .providers
+            .get(&kind)
+            .with_context(|| format!("No {} tracking provider is configured", kind))?;
+
+        self.limits
+            .run(carrier, || async move {
+                let started = Instant::now();
+                let result = provider.fetch_status(carrier, tracking_number).await;
+                self.metrics.tracking_request(carrier, started.elapsed(), result.is_err());
+                result
+            })
+            .await
+    }
+
+    fn provider(&self, carrier: &str) -> ProviderKind {
+        self.route(carrier).0
+    }
+}
+
+/// Shippo through `shippo`, plus every provider `config` holds credentials for
+///
+/// The mock provider is only added when `TRACKING_PROVIDER` or `CARRIER_PROVIDERS` selects
+/// it, so a `mock:` datum carrier cannot close shipments on a real deployment.
+pub fn from_config(config: &Config, shippo: ShipmentClient, metrics: &Metrics) -> Result<TrackingProviders> {
+    let mut providers = TrackingProviders::new(Arc::new(shippo))
+        .with_carriers(config.carrier_providers.clone())
+        .with_default(config.tracking_provider)
+        .with_carrier_limits(CarrierLimits::from_config(config))
+        .with_metrics(metrics.clone());
+
+    if config.easypost_api_key.is_some() {
+        let easypost = EasyPostProvider::new(config.clone())?.with_metrics(metrics.clone());
+        providers = providers.with_provider(ProviderKind::EasyPost, Arc::new(easypost));
+    }
+
+    if config.aftership_api_key.is_some() {
+        let aftership = AfterShipProvider::new(config.clone())?.with_metrics(metrics.clone());
+        providers = providers.with_provider(ProviderKind::AfterShip, Arc::new(aftership));
+    }
+
+    let mock_selected = config.tracking_provider == ProviderKind::Mock
+        || config.carrier_providers.values().any(|kind| *kind == ProviderKind::Mock);
+    if mock_selected {
+        providers = providers.with_provider(ProviderKind::Mock, Arc::new(MockProvider::from_config(config)?));
+    }
+
+    Ok(providers)
+}
@@ -0,0 +1,117 @@
+use anyhow::{Context, Result};
+use pallas::ledger::{
+    addresses::Address,
+    primitives::conway::DatumOption,
+    traverse::MultiEraTx,
+};
+use tx3_sdk::trp::TxEnvelope;
+
+use crate::models::ShipmentDatum;
+
+/// What a resolved close-shipment transaction must do before the oracle signs it
+#[derive(Debug, Clone)]
+pub struct CloseExpectation {
+    /// Tracking UTxO (`TxHash#TxIx`), the only input the transaction may spend
+    pub utxo_ref: String,
+    /// Address that must receive `datum`
+    pub outbox_address: Address,
+    /// Only other address the transaction may pay, as change
+    pub payment_address: Address,
+    pub datum: ShipmentDatum,
+}
+
+/// A resolved transaction does not do what the oracle asked TRP to build
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Resolved transaction failed validation: {}", .violations.join("; "))]
+pub struct TxValidationFailed {
+    /// One line per difference between the transaction and the expectation
+    pub violations: Vec<String>,
+}
+
+/// Check a resolved close-shipment envelope against what was requested
+///
+/// Violations are collected rather than reported one at a time, so the error
+/// carries a full diff; it downcasts to `TxValidationFailed`.
+pub fn validate_close_tx(envelope: &TxEnvelope, expected: &CloseExpectation) -> Result<()> {
+    let bytes = hex::decode(&envelope.tx).context("Envelope tx must be hex-encoded")?;
+    let tx = MultiEraTx::decode(&bytes).context("Failed to decode envelope tx")?;
+
+    let mut violations = Vec::new();
+
+    let inputs: Vec<String> = tx
+        .inputs()
+        .iter()
+        .map(|input| format!("{}#{}", input.hash(), input.index()))
+        .collect();
+    if inputs != [expected.utxo_ref.as_str()] {
+        violations.push(format!("inputs: expected [{}], got [{}]", expected.utxo_ref, inputs.join(", ")));
+    }
+
+    let mut outbox_outputs = 0;
+    for (index, output) in tx.outputs().iter().enumerate() {
+        let address = match output.address() {
+            Ok(address) => address,
+            Err(e) => {
+                violations.push(format!("output #{}: undecodable address ({})", index, e));
+                continue;
+            }
+        };
+
+        if same_address(&address, &expected.outbox_address) {
+            outbox_outputs += 1;
+
+            let datum = match output.datum() {
+                Some(DatumOption::Data(data)) => ShipmentDatum::from_cbor(&hex::encode(data.raw_cbor())),
+                _ => None,
+            };
+            match datum {
+                Some(datum) => violations.extend(
+                    datum_diff(&expected.datum, &datum)
+                        .into_iter()
+                        .map(|diff| format!("output #{} datum {}", index, diff)),
+                ),
+                None => violations.push(format!("output #{}: outbox output has no inline shipment datum", index)),
+            }
+        } else if !same_address(&address, &expected.payment_address) {
+            violations.push(format!("output #{}: pays unknown address {}", index, address));
+        }
+    }
+
+    if outbox_outputs == 0 {
+        violations.push(format!("outputs: nothing paid to outbox address {}", expected.outbox_address));
+    } else if outbox_outputs > 1 {
+        violations.push(format!(
+            "outputs: {} outputs pay outbox address {}, expected exactly one",
+            outbox_outputs, expected.outbox_address
+        ));
+    }
+
+    if !violations.is_empty() {
+        return Err(TxValidationFailed { violations }.into());
+    }
+
+    Ok(())
+}
+
+/// Addresses are equal when their raw header and credential bytes are
+pub fn same_address(a: &Address, b: &Address) -> bool {
+    a.to_vec() == b.to_vec()
+}
+
+/// `field: expected X, got Y` for every field that differs
+fn datum_diff(expected: &ShipmentDatum, actual: &ShipmentDatum) -> Vec<String> {
+    let mut diff = Vec::new();
+    let mut compare = |field: &str, expected: String, actual: String| {
+        if expected != actual {
+            diff.push(format!("{}: expected {}, got {}", field, expected, actual));
+        }
+    };
+
+    compare("carrier", expected.carrier.clone(), actual.carrier.clone());
+    compare("tracking_number", expected.tracking_number.to_string(), actual.tracking_number.to_string());
+    compare("status", expected.status.clone(), actual.status.clone());
+    compare("timestamp", expected.timestamp.to_string(), actual.timestamp.to_string());
+    compare("oracle_pkh", expected.oracle_pkh.clone(), actual.oracle_pkh.clone());
+
+    diff
+}
@@ -0,0 +1,496 @@
+use pallas::ledger::addresses::Address;
+use serde_json::{Value, json};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing_subscriber::layer::SubscriberExt;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::admin::AdminServer;
+use shipping_oracle::config::Config;
+use shipping_oracle::debug_events::EventBuffer;
+use shipping_oracle::models::{TrackingDatum, TrackingNumber, TrackingUTxO};
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::redact::Secret;
+use shipping_oracle::scheduler::{OverlapPolicy, Pipeline};
+use shipping_oracle::state::{MemoryStore, ShipmentState, StateStore, Submission};
+use shipping_oracle::testing::{ORACLE_ADDRESS, OUTBOX_ADDRESS, blockfrost_utxos, shippo_track, test_config, tracking_number};
+
+const TOKEN: &str = "admin-token-0123456789";
+
+/// How long a run takes: the oracle address scan answers this late
+const RUN_TIME: Duration = Duration::from_millis(500);
+
+/// Blockfrost serving `shipments` tracking UTxOs after `delay`, all in transit on Shippo
+async fn serve_shipments(shipments: usize, delay: Duration) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(shipments)).set_delay(delay))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", "TRANSIT")))
+        .mount(&server)
+        .await;
+
+    server
+}
+
+/// Admin API over one pipeline built from `server`, stopped through the returned sender
+fn start_admin(server: &MockServer, overlap_policy: OverlapPolicy) -> (SocketAddr, oneshot::Sender<()>, JoinHandle<anyhow::Result<()>>) {
+    let mut config = test_config(&server.uri());
+    config.overlap_policy = overlap_policy;
+    let oracle = Oracle::from_config(config).unwrap();
+    let pipeline = Arc::new(Pipeline::for_oracle(&oracle).unwrap());
+
+    serve(vec![(pipeline, Secret::new(TOKEN.to_string()))])
+}
+
+/// Admin API over `pipelines`, each opened by its token
+fn serve(pipelines: Vec<(Arc<Pipeline>, Secret<String>)>) -> (SocketAddr, oneshot::Sender<()>, JoinHandle<anyhow::Result<()>>) {
+    let admin = AdminServer::bind("127.0.0.1:0".parse().unwrap(), pipelines).unwrap();
+    let addr = admin.local_addr().unwrap();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let serving = tokio::spawn(admin.serve_until(async {
+        let _ = stopped.await;
+    }));
+
+    (addr, stop, serving)
+}
+
+/// Pipeline of `tenant` over `server`
+fn tenant_pipeline(server: &MockServer, tenant: &str) -> Arc<Pipeline> {
+    let config = Config { tenant: Some(tenant.to_string()), ..test_config(&server.uri()) };
+    Arc::new(Pipeline::for_oracle(&Oracle::from_config(config).unwrap()).unwrap())
+}
+
+/// Send `method path` with `token` as the bearer token, returning the status and body
+async fn call(addr: SocketAddr, method: reqwest::Method, path: &str, token: Option<&str>) -> (u16, String) {
+    let mut request = reqwest::Client::new().request(method, format!("http://{}{}", addr, path));
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+
+    let response = request.send().await.unwrap();
+    (response.status().as_u16(), response.text().await.unwrap())
+}
+
+#[tokio::test]
+async fn requests_without_the_token_are_refused() {
+    let server = serve_shipments(1, Duration::ZERO).await;
+    let (addr, stop, serving) = start_admin(&server, OverlapPolicy::Skip);
+
+    let response = reqwest::get(format!("http://{}/shipments", addr)).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(response.headers()["www-authenticate"], "Bearer");
+
+    assert_eq!(call(addr, reqwest::Method::POST, "/run", Some("admin-token-wrong")).await.0, 401);
+    assert_eq!(call(addr, reqwest::Method::GET, "/runs/latest", Some("")).await.0, 401);
+    assert!(server.received_requests().await.unwrap().is_empty(), "no upstream is queried without the token");
+
+    assert_eq!(call(addr, reqwest::Method::GET, "/runs", Some("admin-token-wrong")).await.0, 401);
+    assert_eq!(call(addr, reqwest::Method::GET, "/reports", Some(TOKEN)).await.0, 404);
+    assert_eq!(call(addr, reqwest::Method::GET, "/shipments?tenant=acme", Some(TOKEN)).await.0, 404);
+
+    stop.send(()).unwrap();
+    serving.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn runs_are_triggered_and_reported() {
+    let server = serve_shipments(2, Duration::ZERO).await;
+    let (addr, stop, serving) = start_admin(&server, OverlapPolicy::Skip);
+
+    let (status, body) = call(addr, reqwest::Method::GET, "/runs/latest", Some(TOKEN)).await;
+    assert_eq!((status, body.as_str()), (404, "No run has completed yet"));
+
+    // Nothing is listed before a run scanned the address, and no upstream is asked
+    let (status, body) = call(addr, reqwest::Method::GET, "/shipments", Some(TOKEN)).await;
+    assert_eq!(status, 200);
+    let page: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(page, json!({ "scanned_at": null, "total": 0, "offset": 0, "limit": 50, "shipments": [] }));
+    let (_, body) = call(addr, reqwest::Method::GET, "/runs", Some(TOKEN)).await;
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap(), json!({ "total": 0, "offset": 0, "limit": 20, "runs": [] }));
+    assert!(server.received_requests().await.unwrap().is_empty());
+
+    let (status, body) = call(addr, reqwest::Method::POST, "/run", Some(TOKEN)).await;
+    assert_eq!(status, 200, "{}", body);
+    let report: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(report["stats"]["shipments"], json!(2));
+
+    let (status, body) = call(addr, reqwest::Method::GET, "/runs/latest", Some(TOKEN)).await;
+    assert_eq!(status, 200);
+    let latest: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(latest["run_id"], report["run_id"]);
+
+    let scans_before = server.received_requests().await.unwrap().len();
+    let (status, body) = call(addr, reqwest::Method::GET, "/shipments?format=json", Some(TOKEN)).await;
+    assert_eq!(status, 200);
+    let page: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(page["total"], json!(2));
+    assert!(page["scanned_at"].is_string());
+    let shipments = page["shipments"].as_array().unwrap();
+    assert_eq!(shipments.len(), 2);
+    assert_eq!(shipments[0]["tx_hash"], json!(format!("{:064x}", 0)));
+    assert_eq!(shipments[0]["tx_index"], json!(0));
+    assert_eq!(shipments[0]["datum"]["carrier"], json!("usps"));
+    assert_eq!((&shipments[0]["failure_count"], &shipments[0]["next_attempt_at"], &shipments[0]["dead"]), (&json!(0), &Value::Null, &json!(false)));
+    // The same shape as `list --json`, plus what the oracle knows of the shipment
+    let shipment: TrackingUTxO = serde_json::from_value(shipments[0].clone()).unwrap();
+    assert_eq!(shipment.utxo_ref.to_string(), format!("{:064x}#0", 0));
+    let last_status = &shipments[1]["last_status"];
+    assert_eq!(last_status["run_id"], report["run_id"]);
+    assert_eq!(last_status["fetched_status"], json!("TRANSIT"));
+    assert_eq!(last_status["derived_status"], Value::Null);
+
+    let (_, body) = call(addr, reqwest::Method::GET, "/shipments?offset=1&limit=5", Some(TOKEN)).await;
+    let page: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!((&page["total"], &page["offset"], &page["limit"]), (&json!(2), &json!(1), &json!(5)));
+    assert_eq!(page["shipments"][0]["tx_hash"], json!(format!("{:064x}", 1)));
+    assert_eq!(server.received_requests().await.unwrap().len(), scans_before, "listing reads the latest scan");
+
+    stop.send(()).unwrap();
+    serving.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn recent_runs_are_paged_newest_first() {
+    let server = serve_shipments(1, Duration::ZERO).await;
+    let (addr, stop, serving) = start_admin(&server, OverlapPolicy::Skip);
+
+    let mut run_ids = Vec::new();
+    for _ in 0..3 {
+        let (status, body) = call(addr, reqwest::Method::POST, "/run", Some(TOKEN)).await;
+        assert_eq!(status, 200, "{}", body);
+        run_ids.push(serde_json::from_str::<Value>(&body).unwrap()["run_id"].clone());
+    }
+
+    let (status, body) = call(addr, reqwest::Method::GET, "/runs", Some(TOKEN)).await;
+    assert_eq!(status, 200);
+    let page: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(page["total"], json!(3));
+    let listed: Vec<&Value> = page["runs"].as_array().unwrap().iter().map(|run| &run["run_id"]).collect();
+    assert_eq!(listed, run_ids.iter().rev().collect::<Vec<_>>());
+    assert_eq!(page["runs"][0]["stats"]["shipments"], json!(1));
+    assert_eq!(page["runs"][0]["error"], Value::Null);
+    assert!(page["runs"][0]["started_at"].as_str().unwrap() <= page["runs"][0]["finished_at"].as_str().unwrap());
+
+    let (_, body) = call(addr, reqwest::Method::GET, "/runs?offset=1&limit=1", Some(TOKEN)).await;
+    let page: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!((&page["total"], &page["offset"], &page["limit"]), (&json!(3), &json!(1), &json!(1)));
+    assert_eq!(page["runs"].as_array().unwrap().len(), 1);
+    assert_eq!(page["runs"][0]["run_id"], run_ids[1]);
+
+    assert_eq!(call(addr, reqwest::Method::GET, "/runs?limit=-1", Some(TOKEN)).await.0, 400);
+    let (status, body) = call(addr, reqwest::Method::GET, "/runs?format=csv", Some(TOKEN)).await;
+    assert_eq!((status, body.as_str()), (400, "Unsupported format \"csv\", only json is served"));
+
+    stop.send(()).unwrap();
+    serving.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn listed_shipments_carry_their_backoff_state() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(1)))
+        .mount(&server)
+        .await;
+    // Delivered, but the close cannot be built against the mock
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", "DELIVERED")))
+        .mount(&server)
+        .await;
+
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config { state_db_path: Some(dir.path().join("state.db").display().to_string()), ..test_config(&server.uri()) };
+    let pipeline = Arc::new(Pipeline::for_oracle(&Oracle::from_config(config).unwrap()).unwrap());
+    let (addr, stop, serving) = serve(vec![(pipeline, Secret::new(TOKEN.to_string()))]);
+
+    let (status, body) = call(addr, reqwest::Method::POST, "/run", Some(TOKEN)).await;
+    assert_eq!(status, 200, "{}", body);
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["stats"]["failed"], json!(1));
+
+    let (_, body) = call(addr, reqwest::Method::GET, "/shipments", Some(TOKEN)).await;
+    let shipment = &serde_json::from_str::<Value>(&body).unwrap()["shipments"][0];
+    assert_eq!(shipment["failure_count"], json!(1));
+    assert_eq!(shipment["dead"], json!(false));
+    assert_eq!(shipment["last_status"]["derived_status"], json!("DELIVERED"));
+
+    stop.send(()).unwrap();
+    serving.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn trigger_during_a_run_is_a_conflict() {
+    // Even a queueing pipeline refuses an on-demand run while one is in flight
+    let server = serve_shipments(1, RUN_TIME).await;
+    let (addr, stop, serving) = start_admin(&server, OverlapPolicy::Queue);
+
+    let first = tokio::spawn(call(addr, reqwest::Method::POST, "/run", Some(TOKEN)));
+    tokio::time::sleep(RUN_TIME / 5).await;
+
+    let (status, body) = call(addr, reqwest::Method::POST, "/run", Some(TOKEN)).await;
+    assert_eq!((status, body.as_str()), (409, "Previous run is still active, skipping this one"));
+
+    assert_eq!(first.await.unwrap().0, 200);
+    let scans = server.received_requests().await.unwrap();
+    assert_eq!(scans.iter().filter(|request| request.url.path().starts_with("/addresses/")).count(), 1);
+
+    stop.send(()).unwrap();
+    serving.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn each_tenant_answers_to_its_own_token() {
+    let server = serve_shipments(1, Duration::ZERO).await;
+    let (acme_token, globex_token) = ("acme-token-0123456789", "globex-token-0123456789");
+    let (addr, stop, serving) = serve(vec![
+        (tenant_pipeline(&server, "acme"), Secret::new(acme_token.to_string())),
+        (tenant_pipeline(&server, "globex"), Secret::new(globex_token.to_string())),
+        (tenant_pipeline(&server, "initech_eu"), Secret::new(acme_token.to_string())),
+    ]);
+
+    assert_eq!(call(addr, reqwest::Method::GET, "/shipments", Some(globex_token)).await.0, 200, "the only tenant the token opens");
+    assert_eq!(call(addr, reqwest::Method::GET, "/shipments?tenant=acme", Some(globex_token)).await.0, 404);
+    assert_eq!(call(addr, reqwest::Method::GET, "/shipments", Some(acme_token)).await.0, 400);
+    assert_eq!(call(addr, reqwest::Method::GET, "/shipments?tenant=acme", Some(acme_token)).await.0, 200);
+    assert_eq!(call(addr, reqwest::Method::GET, "/shipments?tenant=initech%5Feu", Some(acme_token)).await.0, 200);
+    assert_eq!(call(addr, reqwest::Method::GET, "/shipments?tenant=globex", Some(TOKEN)).await.0, 401);
+
+    stop.send(()).unwrap();
+    serving.await.unwrap().unwrap();
+}
+
+/// `value` with every leaf replaced by the name of its JSON type
+fn schema(value: &Value) -> Value {
+    match value {
+        Value::Null => json!("null"),
+        Value::Bool(_) => json!("bool"),
+        Value::Number(_) => json!("number"),
+        Value::String(_) => json!("string"),
+        Value::Array(items) => Value::Array(items.iter().map(schema).collect()),
+        Value::Object(fields) => Value::Object(fields.iter().map(|(key, value)| (key.clone(), schema(value))).collect()),
+    }
+}
+
+#[tokio::test]
+async fn status_has_a_stable_schema_and_calls_no_upstream() {
+    let server = serve_shipments(1, Duration::ZERO).await;
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config {
+        state_db_path: Some(dir.path().join("state.db").display().to_string()),
+        shippo_monthly_budget: Some(1000),
+        ..test_config(&server.uri())
+    };
+    let pipeline = Arc::new(Pipeline::for_oracle(&Oracle::from_config(config).unwrap()).unwrap());
+    let (addr, stop, serving) = serve(vec![(pipeline, Secret::new(TOKEN.to_string()))]);
+
+    let (status, body) = call(addr, reqwest::Method::GET, "/status", Some(TOKEN)).await;
+    assert_eq!(status, 200, "{}", body);
+    let before: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!((&before["last_run"], &before["last_completed_run"]), (&Value::Null, &Value::Null));
+    assert_eq!(before["scheduler"]["running"], json!(false));
+
+    assert_eq!(call(addr, reqwest::Method::POST, "/run", Some(TOKEN)).await.0, 200);
+    let requests = server.received_requests().await.unwrap().len();
+
+    let (_, body) = call(addr, reqwest::Method::GET, "/status", Some(TOKEN)).await;
+    let status: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(server.received_requests().await.unwrap().len(), requests, "status only reads cached state");
+
+    let run_schema = json!({
+        "run_id": "string",
+        "started_at": "string",
+        "finished_at": "string",
+        "succeeded": "bool",
+        "stats": schema(&serde_json::to_value(shipping_oracle::fetcher::RunStats::default()).unwrap()),
+        "error": "null",
+    });
+    assert_eq!(
+        schema(&status),
+        json!({
+            "tenant": "null",
+            "scheduler": {
+                "cron_schedule": "string",
+                "running": "bool",
+                "next_run_at": "string",
+                "submit_window_open": "null",
+            },
+            "last_run": run_schema,
+            "last_completed_run": run_schema,
+            "pending_submissions": "number",
+            "heartbeat": "null",
+            "shippo_quota": {
+                "period": "string",
+                "calls": "number",
+                "budget": "number",
+                "spent": "bool",
+            },
+            "fees": {
+                "closures": "number",
+                "total_lovelace": "number",
+                "unknown_fee": "number",
+            },
+        })
+    );
+    assert_eq!(status["last_run"]["run_id"], status["last_completed_run"]["run_id"]);
+    assert_eq!(status["shippo_quota"]["calls"], json!(1));
+    assert_eq!(status["shippo_quota"]["spent"], json!(false));
+    assert!(status["scheduler"]["next_run_at"].as_str().unwrap() > status["last_run"]["finished_at"].as_str().unwrap());
+
+    stop.send(()).unwrap();
+    serving.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn status_sums_the_fees_of_journalled_closures() {
+    let server = serve_shipments(0, Duration::ZERO).await;
+    let state = Arc::new(MemoryStore::new());
+    // `bb#0` was closed again after `b1` never landed; only `b2` closes it
+    for (utxo_ref, tx_hash, fee) in [("aa#0", "a1", 170_000), ("bb#0", "b1", 180_000), ("bb#0", "b2", 190_000)] {
+        state
+            .record_submission(&Submission {
+                utxo_ref: utxo_ref.to_string(),
+                tx_hash: tx_hash.to_string(),
+                status: "DELIVERED".to_string(),
+                submitted_at: 1_771_090_081,
+                signer_pkh: None,
+                fee: Some(fee),
+                outbox_address: Some(OUTBOX_ADDRESS.to_string()),
+                carrier: None,
+                tracking_number: None,
+            })
+            .await
+            .unwrap();
+        state
+            .save_shipment(&ShipmentState { closed_tx_hash: Some(tx_hash.to_string()), ..ShipmentState::new(utxo_ref) })
+            .await
+            .unwrap();
+    }
+
+    let oracle = Oracle::builder().config(test_config(&server.uri())).state(state).build().unwrap();
+    let (addr, stop, serving) = serve(vec![(Arc::new(Pipeline::for_oracle(&oracle).unwrap()), Secret::new(TOKEN.to_string()))]);
+
+    let (status, body) = call(addr, reqwest::Method::GET, "/status", Some(TOKEN)).await;
+    assert_eq!(status, 200, "{}", body);
+    let status: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(status["fees"], json!({ "closures": 2, "total_lovelace": 360_000, "unknown_fee": 0 }));
+
+    stop.send(()).unwrap();
+    serving.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn merchant_shipments_are_filtered_by_outbox() {
+    // The second shipment goes to another merchant
+    let other_outbox = ORACLE_ADDRESS;
+    let mut utxos = blockfrost_utxos(2);
+    utxos[1]["inline_datum"] = json!(
+        TrackingDatum {
+            carrier: "usps".to_string(),
+            tracking_number: TrackingNumber::Plain(tracking_number(1)),
+            outbox_address: Address::from_bech32(other_outbox).unwrap(),
+            deadline: None,
+        }
+        .to_cbor_hex()
+    );
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(utxos))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", "TRANSIT")))
+        .mount(&server)
+        .await;
+    let (addr, stop, serving) = start_admin(&server, OverlapPolicy::Skip);
+    assert_eq!(call(addr, reqwest::Method::POST, "/run", Some(TOKEN)).await.0, 200);
+
+    for (outbox, tx_hash) in [(OUTBOX_ADDRESS, format!("{:064x}", 0)), (other_outbox, format!("{:064x}", 1))] {
+        let (status, body) = call(addr, reqwest::Method::GET, &format!("/merchants/{}/shipments", outbox), Some(TOKEN)).await;
+        assert_eq!(status, 200, "{}", body);
+        let page: Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(page["total"], json!(1));
+        assert_eq!(page["shipments"][0]["tx_hash"], json!(tx_hash));
+        assert_eq!(page["shipments"][0]["datum"]["outbox_address"], json!(outbox));
+        assert_eq!(page["shipments"][0]["last_status"]["fetched_status"], json!("TRANSIT"));
+    }
+
+    let (_, body) = call(addr, reqwest::Method::GET, "/merchants/addr_test1unknown/shipments", Some(TOKEN)).await;
+    assert_eq!(serde_json::from_str::<Value>(&body).unwrap()["total"], json!(0));
+    assert_eq!(call(addr, reqwest::Method::GET, "/merchants//shipments", Some(TOKEN)).await.0, 404);
+    assert_eq!(call(addr, reqwest::Method::GET, &format!("/merchants/{}/shipments", OUTBOX_ADDRESS), None).await.0, 401);
+
+    stop.send(()).unwrap();
+    serving.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn debug_events_are_served_per_tenant() {
+    let server = serve_shipments(1, Duration::ZERO).await;
+    let (acme_token, globex_token) = ("acme-token-0123456789", "globex-token-0123456789");
+    let events = EventBuffer::new(10);
+    for (tenant, message) in [(Some("acme"), "acme one"), (Some("globex"), "globex"), (None, "process"), (Some("acme"), "acme two")] {
+        let subscriber = tracing_subscriber::registry().with(events.layer());
+        tracing::subscriber::with_default(subscriber, || match tenant {
+            Some(tenant) => tracing::warn!(tenant, "{}", message),
+            None => tracing::warn!("{}", message),
+        });
+    }
+
+    let admin = AdminServer::bind(
+        "127.0.0.1:0".parse().unwrap(),
+        vec![
+            (tenant_pipeline(&server, "acme"), Secret::new(acme_token.to_string())),
+            (tenant_pipeline(&server, "globex"), Secret::new(globex_token.to_string())),
+        ],
+    )
+    .unwrap()
+    .with_debug_events(events);
+    let addr = admin.local_addr().unwrap();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let serving = tokio::spawn(admin.serve_until(async {
+        let _ = stopped.await;
+    }));
+
+    let messages = |body: &str| -> Vec<Value> {
+        let page: Value = serde_json::from_str(body).unwrap();
+        page["events"].as_array().unwrap().iter().map(|event| event["message"].clone()).collect()
+    };
+
+    let (status, body) = call(addr, reqwest::Method::GET, "/debug/events", Some(acme_token)).await;
+    assert_eq!(status, 200);
+    assert_eq!(messages(&body), vec![json!("acme two"), json!("acme one")], "newest first, other tenants and process-wide events hidden");
+
+    let (status, body) = call(addr, reqwest::Method::GET, "/debug/events?limit=1", Some(acme_token)).await;
+    assert_eq!(status, 200);
+    let page: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!((page["total"].clone(), page["limit"].clone()), (json!(2), json!(1)));
+    assert_eq!(messages(&body), vec![json!("acme two")]);
+
+    let (_, body) = call(addr, reqwest::Method::GET, "/debug/events", Some(globex_token)).await;
+    assert_eq!(messages(&body), vec![json!("globex")]);
+    assert_eq!(call(addr, reqwest::Method::GET, "/debug/events?tenant=acme", Some(globex_token)).await.0, 404);
+    assert_eq!(call(addr, reqwest::Method::GET, "/debug/events", None).await.0, 401);
+    assert_eq!(call(addr, reqwest::Method::GET, "/debug/events?limit=many", Some(acme_token)).await.0, 400);
+    assert!(server.received_requests().await.unwrap().is_empty());
+
+    stop.send(()).unwrap();
+    serving.await.unwrap().unwrap();
+
+    // A server without an event buffer has no such route
+    let (addr, stop, serving) = start_admin(&server, OverlapPolicy::Skip);
+    assert_eq!(call(addr, reqwest::Method::GET, "/debug/events", Some(TOKEN)).await.0, 404);
+    stop.send(()).unwrap();
+    serving.await.unwrap().unwrap();
+}
@@ -0,0 +1,133 @@
+use std::collections::BTreeMap;
+use wiremock::matchers::{header, method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::aftership::{AfterShipProvider, parse_slugs, shippo_status, slug};
+use shipping_oracle::config::Config;
+use shipping_oracle::metrics::Metrics;
+use shipping_oracle::models::{CarrierStatus, DerivedStatus};
+use shipping_oracle::shipment::ShipmentClient;
+use shipping_oracle::status_mapping::StatusMapping;
+use shipping_oracle::testing::test_config;
+use shipping_oracle::tracking_provider::{self, ProviderKind, TrackingProvider};
+
+const AFTERSHIP_KEY: &str = "asat_test_0123456789";
+
+fn fixture(name: &str) -> String {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("missing fixture {}: {}", path, e))
+}
+
+fn aftership_config(server: &MockServer) -> Config {
+    let mut config = test_config(&server.uri());
+    config.aftership_api_key = Some(AFTERSHIP_KEY.into());
+    config
+}
+
+/// Serve the recorded `fixture` for `slug`/`tracking_number` to requests carrying the API key
+async fn serve(server: &MockServer, slug: &str, tracking_number: &str, fixture_name: &str) {
+    Mock::given(method("GET"))
+        .and(path(format!("/v4/trackings/{}/{}", slug, tracking_number)))
+        .and(header("aftership-api-key", AFTERSHIP_KEY))
+        .respond_with(ResponseTemplate::new(200).set_body_raw(fixture(fixture_name), "application/json"))
+        .mount(server)
+        .await;
+}
+
+#[test]
+fn aftership_tags_map_to_shippo_vocabulary() {
+    for (tag, shippo) in [
+        ("Pending", "PRE_TRANSIT"),
+        ("InfoReceived", "PRE_TRANSIT"),
+        ("InTransit", "TRANSIT"),
+        ("OutForDelivery", "TRANSIT"),
+        ("AvailableForPickup", "TRANSIT"),
+        ("AttemptFail", "TRANSIT"),
+        ("Delivered", "DELIVERED"),
+        ("Exception", "FAILURE"),
+        ("Expired", "UNKNOWN"),
+    ] {
+        assert_eq!(shippo_status(tag), shippo, "{}", tag);
+    }
+}
+
+#[test]
+fn carrier_slugs_follow_the_table_and_overrides() {
+    let none = BTreeMap::new();
+    assert_eq!(slug("usps", &none), "usps");
+    assert_eq!(slug("dhl_express", &none), "dhl");
+    assert_eq!(slug("Canada_Post", &none), "canada-post");
+
+    let overrides = parse_slugs("LaserShip = lasership-api, dhl_express=dhl-express-custom").unwrap();
+    assert_eq!(slug("lasership", &overrides), "lasership-api");
+    assert_eq!(slug("dhl_express", &overrides), "dhl-express-custom");
+
+    assert!(parse_slugs("").unwrap().is_empty());
+    assert!(parse_slugs("lasership").unwrap_err().to_string().contains("expected carrier=slug, got 'lasership'"));
+    assert!(parse_slugs("lasership=").is_err());
+}
+
+#[tokio::test]
+async fn recorded_trackings_are_read_into_statuses() {
+    let server = MockServer::start().await;
+    serve(&server, "fedex", "AS1000000001", "aftership_tracking_delivered.json").await;
+    serve(&server, "ups", "AS1000000002", "aftership_tracking_exception.json").await;
+    serve(&server, "dhl", "AS1000000003", "aftership_tracking_in_transit.json").await;
+    let aftership = AfterShipProvider::new(aftership_config(&server)).unwrap();
+
+    let delivered = aftership.fetch_status("fedex", "AS1000000001").await.unwrap();
+    assert_eq!(delivered.status, CarrierStatus::Delivered);
+    assert_eq!(delivered.status_details, "Delivered");
+    assert_eq!(delivered.status_date.as_deref(), Some("2026-02-14T09:28:01-08:00"));
+    assert_eq!(StatusMapping::default().map(&delivered), Some(DerivedStatus::Delivered));
+
+    let exception = aftership.fetch_status("ups", "AS1000000002").await.unwrap();
+    assert_eq!(exception.status, CarrierStatus::Failure);
+    assert_eq!(exception.status_details, "Recipient refused delivery");
+    assert_eq!(StatusMapping::default().map(&exception), Some(DerivedStatus::NotDelivered));
+
+    let in_transit = aftership.fetch_status("dhl_express", "AS1000000003").await.unwrap();
+    assert_eq!(in_transit.status, CarrierStatus::Transit);
+    assert_eq!(in_transit.status_details, "Departure Scan");
+    assert_eq!(StatusMapping::default().map(&in_transit), None);
+    assert_eq!(aftership.provider("dhl_express"), ProviderKind::AfterShip);
+}
+
+#[tokio::test]
+async fn unknown_trackings_fail_with_the_slug() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+            "meta": { "code": 4004, "message": "Tracking does not exist.", "type": "NotFound" },
+            "data": {}
+        })))
+        .mount(&server)
+        .await;
+
+    let aftership = AfterShipProvider::new(aftership_config(&server)).unwrap();
+    let err = aftership.fetch_status("dhl_express", "AS404").await.unwrap_err();
+    assert!(err.to_string().contains("AfterShip query failed for dhl AS404 (status 404"));
+    assert!(err.to_string().contains("Tracking does not exist."));
+
+    let err = AfterShipProvider::new(test_config(&server.uri())).err().unwrap();
+    assert!(err.to_string().contains("AFTERSHIP_API_KEY"));
+}
+
+#[tokio::test]
+async fn carriers_are_routed_to_aftership_with_configured_slugs() {
+    let server = MockServer::start().await;
+    serve(&server, "dhl", "AS1000000003", "aftership_tracking_in_transit.json").await;
+    serve(&server, "lasership-api", "AS1000000001", "aftership_tracking_delivered.json").await;
+
+    let mut config = aftership_config(&server);
+    config.carrier_providers = BTreeMap::from([("lasership".to_string(), ProviderKind::AfterShip)]);
+    config.aftership_slugs = parse_slugs("lasership=lasership-api").unwrap();
+    let shippo = ShipmentClient::new(config.clone()).unwrap();
+    let providers = tracking_provider::from_config(&config, shippo, &Metrics::new()).unwrap();
+
+    let status = providers.fetch_status("aftership:dhl_express", "AS1000000003").await.unwrap();
+    assert_eq!(status.status, CarrierStatus::Transit);
+    let status = providers.fetch_status("lasership", "AS1000000001").await.unwrap();
+    assert_eq!(status.status, CarrierStatus::Delivered);
+    assert_eq!(providers.provider("lasership"), ProviderKind::AfterShip);
+}
@@ -0,0 +1,155 @@
+use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use shipping_oracle::carrier_limits::{CarrierLimit, CarrierLimits, parse_carrier_limits};
+use shipping_oracle::metrics::Metrics;
+use shipping_oracle::models::TrackingStatus;
+use shipping_oracle::shipment::ShipmentError;
+use shipping_oracle::tracking_provider::{TrackingProvider, TrackingProviders};
+
+const LATENCY: Duration = Duration::from_millis(50);
+const UPS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// One tracking request seen by `RecordingProvider`
+#[derive(Debug, Clone)]
+struct Call {
+    carrier: String,
+    started: Instant,
+    finished: Instant,
+}
+
+/// Answers every request after `LATENCY`, first with the `failures` queued for its tracking number
+#[derive(Default)]
+struct RecordingProvider {
+    calls: Mutex<Vec<Call>>,
+    failures: Mutex<HashMap<String, Vec<ShipmentError>>>,
+}
+
+impl RecordingProvider {
+    fn failing(tracking_number: &str, failures: Vec<ShipmentError>) -> Self {
+        Self { failures: Mutex::new(HashMap::from([(tracking_number.to_string(), failures)])), ..Self::default() }
+    }
+
+    fn calls(&self, carrier: &str) -> Vec<Call> {
+        self.calls.lock().unwrap().iter().filter(|call| call.carrier == carrier).cloned().collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl TrackingProvider for RecordingProvider {
+    async fn fetch_status(&self, carrier: &str, tracking_number: &str) -> Result<TrackingStatus> {
+        let started = Instant::now();
+        tokio::time::sleep(LATENCY).await;
+        let call = Call { carrier: carrier.to_string(), started, finished: Instant::now() };
+        self.calls.lock().unwrap().push(call);
+
+        let failure = self.failures.lock().unwrap().get_mut(tracking_number).and_then(|failures| {
+            (!failures.is_empty()).then(|| failures.remove(0))
+        });
+        match failure {
+            Some(error) => Err(error.into()),
+            None => Ok(TrackingStatus { status: "TRANSIT".into(), status_details: "In transit".to_string(), status_date: None }),
+        }
+    }
+}
+
+fn ups_limits(max_retries: Option<u32>) -> CarrierLimits {
+    let limit = CarrierLimit { max_concurrency: Some(1), min_interval_ms: Some(UPS_INTERVAL.as_millis() as u64), max_retries };
+    CarrierLimits::new(&BTreeMap::from([("ups".to_string(), limit)]), 8)
+}
+
+#[test]
+fn carrier_limits_parse() {
+    let limits = parse_carrier_limits(r#"{"UPS": {"max_concurrency": 2, "min_interval_ms": 500}, "fedex": {"max_retries": 3}}"#).unwrap();
+    assert_eq!(
+        limits,
+        BTreeMap::from([
+            ("ups".to_string(), CarrierLimit { max_concurrency: Some(2), min_interval_ms: Some(500), max_retries: None }),
+            ("fedex".to_string(), CarrierLimit { max_concurrency: None, min_interval_ms: None, max_retries: Some(3) }),
+        ])
+    );
+    assert!(parse_carrier_limits("{}").unwrap().is_empty());
+    assert!(parse_carrier_limits("ups=2").unwrap_err().to_string().contains("expected a JSON object of carrier limits"));
+    assert!(parse_carrier_limits(r#"{"ups": {"max_concurrency": 0}}"#).unwrap_err().to_string().contains("max_concurrency of ups must be at least 1"));
+    assert!(parse_carrier_limits(r#"{"ups": {"concurrency": 2}}"#).is_err());
+    assert!(parse_carrier_limits(r#"{" ": {}}"#).is_err());
+}
+
+#[tokio::test]
+async fn a_paced_carrier_does_not_hold_back_the_others() {
+    let provider = Arc::new(RecordingProvider::default());
+    let providers = TrackingProviders::new(provider.clone()).with_carrier_limits(ups_limits(None));
+
+    let batch = (0..4).flat_map(|i| [("UPS", format!("1Z{}", i)), ("usps", format!("94{}", i))]);
+    let started = Instant::now();
+    let results = futures::future::join_all(batch.map(|(carrier, tracking_number)| {
+        let providers = &providers;
+        async move { providers.fetch_status(carrier, &tracking_number).await }
+    }))
+    .await;
+    assert!(results.iter().all(Result::is_ok));
+
+    // One UPS request at a time, each starting UPS_INTERVAL after the previous one
+    let mut ups = provider.calls("UPS");
+    ups.sort_by_key(|call| call.started);
+    assert_eq!(ups.len(), 4);
+    for pair in ups.windows(2) {
+        assert!(pair[1].started >= pair[0].finished, "UPS requests overlapped");
+        assert!(pair[1].started - pair[0].started >= UPS_INTERVAL, "UPS requests {:?} apart", pair[1].started - pair[0].started);
+    }
+
+    // USPS requests all went out at once, while UPS waited its turns
+    let usps = provider.calls("usps");
+    assert_eq!(usps.len(), 4);
+    for call in &usps {
+        assert!(call.started - started < LATENCY, "a USPS request waited {:?}", call.started - started);
+        assert!(call.finished - started < UPS_INTERVAL, "a USPS request finished after {:?}", call.finished - started);
+    }
+}
+
+#[tokio::test]
+async fn transient_failures_are_retried_up_to_the_carrier_max_retries() {
+    let rate_limited = || ShipmentError::RateLimited { retry_after: Some(Duration::from_millis(10)), message: "slow down".to_string() };
+
+    let provider = Arc::new(RecordingProvider::failing("1Z0", vec![rate_limited(), ShipmentError::Network("reset".to_string())]));
+    let providers = TrackingProviders::new(provider.clone()).with_carrier_limits(ups_limits(Some(2)));
+    assert!(providers.fetch_status("ups", "1Z0").await.is_ok());
+    assert_eq!(provider.calls("ups").len(), 3);
+
+    let provider = Arc::new(RecordingProvider::failing("1Z0", vec![rate_limited(), rate_limited()]));
+    let providers = TrackingProviders::new(provider.clone()).with_carrier_limits(ups_limits(Some(1)));
+    let err = providers.fetch_status("ups", "1Z0").await.unwrap_err();
+    assert!(matches!(err.downcast_ref::<ShipmentError>(), Some(ShipmentError::RateLimited { .. })));
+    assert_eq!(provider.calls("ups").len(), 2);
+
+    // Refused requests are not retried, nor are carriers without limits
+    let provider = Arc::new(RecordingProvider::failing("1Z0", vec![ShipmentError::InvalidResponse("bad".to_string())]));
+    let providers = TrackingProviders::new(provider.clone()).with_carrier_limits(ups_limits(Some(2)));
+    assert!(providers.fetch_status("ups", "1Z0").await.is_err());
+    assert_eq!(provider.calls("ups").len(), 1);
+
+    let provider = Arc::new(RecordingProvider::failing("94", vec![rate_limited()]));
+    let providers = TrackingProviders::new(provider.clone()).with_carrier_limits(ups_limits(Some(2)));
+    assert!(providers.fetch_status("usps", "94").await.is_err());
+    assert_eq!(provider.calls("usps").len(), 1);
+}
+
+#[tokio::test]
+async fn tracking_latency_and_errors_are_labelled_by_carrier() {
+    let provider = Arc::new(RecordingProvider::failing("1Z0", vec![ShipmentError::Network("reset".to_string())]));
+    let metrics = Metrics::new().for_tenant(Some("acme"));
+    let providers = TrackingProviders::new(provider).with_carrier_limits(ups_limits(Some(1))).with_metrics(metrics.clone());
+
+    providers.fetch_status("UPS", "1Z0").await.unwrap();
+    providers.fetch_status("usps", "94").await.unwrap();
+
+    let body = metrics.render().unwrap();
+    let sample = |line: &str| body.lines().any(|sample| sample == line);
+    assert!(sample(r#"oracle_tracking_request_seconds_count{carrier="ups",tenant="acme"} 2"#), "{}", body);
+    assert!(sample(r#"oracle_tracking_request_seconds_count{carrier="usps",tenant="acme"} 1"#), "{}", body);
+    assert!(sample(r#"oracle_tracking_errors_total{carrier="ups",tenant="acme"} 1"#), "{}", body);
+    assert!(!body.contains(r#"oracle_tracking_errors_total{carrier="usps""#), "{}", body);
+}
@@ -0,0 +1,86 @@
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::carrier_policy::{CarrierPolicy, CarrierViolation, parse_carriers};
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::run_report::ShipmentAction;
+use shipping_oracle::testing::{ORACLE_ADDRESS, blockfrost_utxos, shippo_track, test_config, tracking_datum_cbor};
+
+#[test]
+fn carrier_lists_are_trimmed_and_lowercase() {
+    let carriers = parse_carriers(" USPS, fedex ,,DHL_Express ");
+    assert_eq!(carriers.into_iter().collect::<Vec<_>>(), vec!["dhl_express", "fedex", "usps"]);
+    assert!(parse_carriers(" , ").is_empty());
+}
+
+#[test]
+fn allowlist_only_refuses_other_carriers() {
+    let policy = CarrierPolicy::new(Some(parse_carriers("usps,fedex")), None);
+
+    assert_eq!(policy.check("usps"), Ok(()));
+    assert_eq!(policy.check(" FedEx "), Ok(()));
+    assert_eq!(policy.check("easypost:fedex"), Ok(()));
+    assert_eq!(policy.check("lol"), Err(CarrierViolation::NotAllowlisted));
+    assert_eq!(policy.check("easypost:lol"), Err(CarrierViolation::NotAllowlisted));
+    assert_eq!(policy.check("usps:fedex"), Err(CarrierViolation::NotAllowlisted));
+}
+
+#[test]
+fn denylist_only_refuses_listed_carriers() {
+    let policy = CarrierPolicy::new(None, Some(parse_carriers("lol")));
+
+    assert_eq!(policy.check("usps"), Ok(()));
+    assert_eq!(policy.check("LOL"), Err(CarrierViolation::Denylisted));
+    assert_eq!(policy.check("mock:lol"), Err(CarrierViolation::Denylisted));
+}
+
+#[test]
+fn denylist_wins_over_allowlist() {
+    let policy = CarrierPolicy::new(Some(parse_carriers("usps,fedex")), Some(parse_carriers("fedex")));
+
+    assert_eq!(policy.check("usps"), Ok(()));
+    assert_eq!(policy.check("fedex"), Err(CarrierViolation::Denylisted));
+    assert_eq!(policy.check("ups"), Err(CarrierViolation::NotAllowlisted));
+    assert_eq!(CarrierViolation::Denylisted.to_string(), "carrier is denylisted");
+    assert_eq!(CarrierViolation::NotAllowlisted.to_string(), "carrier is not allowlisted");
+}
+
+#[tokio::test]
+async fn refused_carriers_are_skipped_before_any_tracking_call() {
+    let server = MockServer::start().await;
+    let mut utxos = blockfrost_utxos(3);
+    for (i, carrier) in ["usps", "lol", "fedex"].iter().enumerate() {
+        utxos[i]["inline_datum"] = tracking_datum_cbor(carrier, &format!("TRK{:010}", i)).into();
+    }
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(utxos))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", "TRANSIT")))
+        .mount(&server)
+        .await;
+
+    let mut config = test_config(&server.uri());
+    config.carrier_allowlist = Some(parse_carriers("USPS, lol"));
+    config.carrier_denylist = Some(parse_carriers("lol"));
+    let oracle = Oracle::builder().config(config).build().unwrap();
+    let report = oracle.run_once().await.unwrap();
+
+    assert_eq!((report.stats.shipments, report.stats.skipped_carrier, report.stats.failed), (3, 2, 0));
+    let reasons: Vec<Option<&str>> = report
+        .shipments
+        .iter()
+        .map(|shipment| match &shipment.action {
+            ShipmentAction::Skipped { reason } => Some(reason.as_str()),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(reasons, vec![Some("status is not final"), Some("carrier is denylisted"), Some("carrier is not allowlisted")]);
+
+    let requests = server.received_requests().await.unwrap();
+    let tracked: Vec<&str> = requests.iter().map(|request| request.url.path()).filter(|path| path.starts_with("/tracks/")).collect();
+    assert_eq!(tracked, vec!["/tracks/usps/TRK0000000000"]);
+}
@@ -0,0 +1,329 @@
+use clap::Parser;
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+use shipping_oracle::cli::{self, Cli, CloseArgs, Command};
+use shipping_oracle::models::{DerivedStatus, TrackingUTxO};
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::scheduler::RunMode;
+use shipping_oracle::submitter::{SubmitError, TxSubmitter};
+use shipping_oracle::testing::{
+    ORACLE_ADDRESS, OUTBOX_ADDRESS, VALIDATOR_SCRIPT_REF, blockfrost_utxos, shippo_track, test_config,
+    tracking_datum_cbor,
+};
+
+/// The tracking UTxO `close_shipment_envelope_valid.json` spends, closed at `CLOSED_AT`
+const TRACKING_TX_HASH: &str = "a7a264ac1bef1f0da6312d16fb8f68b18d1a0d5010d8cb6a61790bf02a759301";
+const TRACKING_NUMBER: &str = "9400111899223456789012";
+const CLOSED_AT: u64 = 1771090081;
+const SUBMITTED_TX_HASH: &str = "c1";
+
+#[derive(Deserialize)]
+struct EnvelopeFixture {
+    tx: String,
+    hash: String,
+}
+
+/// TRP answering every resolve with the fixture envelope, echoing the JSON-RPC id
+struct TrpResolve(EnvelopeFixture);
+
+impl Respond for TrpResolve {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let id = serde_json::from_slice::<Value>(&request.body)
+            .ok()
+            .and_then(|body| body.get("id").cloned())
+            .unwrap_or(Value::Null);
+
+        ResponseTemplate::new(200).set_body_json(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": { "tx": self.0.tx, "hash": self.0.hash },
+        }))
+    }
+}
+
+struct CountingSubmitter(Arc<AtomicUsize>);
+
+#[async_trait::async_trait]
+impl TxSubmitter for CountingSubmitter {
+    async fn submit(&self, _signed_tx: Vec<u8>) -> Result<String, SubmitError> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Ok(SUBMITTED_TX_HASH.to_string())
+    }
+}
+
+/// Blockfrost holding one unspent tracking UTxO for `TRACKING_NUMBER`, Shippo reporting
+/// `status`, and TRP resolving its close to the fixture envelope
+async fn serve(status: &str) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "address": ORACLE_ADDRESS,
+            "tx_hash": TRACKING_TX_HASH,
+            "tx_index": 0,
+            "output_index": 0,
+            "amount": [{ "unit": "lovelace", "quantity": "2000000" }],
+            "block": format!("{:064x}", 0),
+            "data_hash": null,
+            "inline_datum": tracking_datum_cbor("usps", TRACKING_NUMBER),
+            "reference_script_hash": null,
+        }])))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", TRACKING_NUMBER, status)))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path(format!("/txs/{}/utxos", TRACKING_TX_HASH)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "hash": TRACKING_TX_HASH,
+            "inputs": [],
+            "outputs": [{
+                "address": ORACLE_ADDRESS,
+                "output_index": 0,
+                "inline_datum": tracking_datum_cbor("usps", TRACKING_NUMBER),
+                "reference_script_hash": null,
+                "consumed_by_tx": null,
+            }],
+        })))
+        .mount(&server)
+        .await;
+
+    let fixture = format!("{}/tests/fixtures/close_shipment_envelope_valid.json", env!("CARGO_MANIFEST_DIR"));
+    let envelope = serde_json::from_str(&std::fs::read_to_string(fixture).unwrap()).unwrap();
+    Mock::given(method("POST"))
+        .and(path("/"))
+        .respond_with(TrpResolve(envelope))
+        .mount(&server)
+        .await;
+
+    server
+}
+
+fn oracle(server: &MockServer, submissions: &Arc<AtomicUsize>) -> Oracle {
+    Oracle::builder()
+        .config(test_config(&server.uri()))
+        .submitter(CountingSubmitter(submissions.clone()))
+        .build()
+        .unwrap()
+}
+
+/// `close` arguments for the served tracking UTxO, closed as delivered at `CLOSED_AT`
+fn close_args(extra: &[&str]) -> CloseArgs {
+    let utxo_ref = utxo_ref();
+    let closed_at = CLOSED_AT.to_string();
+    let mut args = vec!["shipping-oracle", "close", &utxo_ref, "DELIVERED", "--timestamp", &closed_at];
+    args.extend_from_slice(extra);
+    let Some(Command::Close(close)) = Cli::try_parse_from(args).unwrap().command else {
+        panic!("expected close");
+    };
+    close
+}
+
+fn utxo_ref() -> String {
+    format!("{}#0", TRACKING_TX_HASH)
+}
+
+#[tokio::test]
+async fn list_prints_the_tracking_utxos() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(2)))
+        .mount(&server)
+        .await;
+    let oracle = Oracle::from_config(test_config(&server.uri())).unwrap();
+
+    let mut table = Vec::new();
+    cli::list(&oracle, false, &mut table).await.unwrap();
+    let table = String::from_utf8(table).unwrap();
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines.len(), 4, "{}", table);
+    assert!(lines[0].starts_with("UTXO"), "{}", table);
+    assert!(lines[1].starts_with(&format!("{:064x}#0", 0)), "{}", table);
+    assert!(lines[1].contains("TRK0000000000") && lines[1].ends_with(OUTBOX_ADDRESS), "{}", table);
+    assert_eq!(lines[3], "2 tracking UTxO(s)");
+
+    let mut json = Vec::new();
+    cli::list(&oracle, true, &mut json).await.unwrap();
+    let shipments: Vec<TrackingUTxO> = serde_json::from_slice(&json).unwrap();
+    assert_eq!(shipments, oracle.scan().await.unwrap());
+}
+
+#[tokio::test]
+async fn status_shows_what_the_shipment_would_close_as() {
+    let server = serve("DELIVERED").await;
+    let mut out = Vec::new();
+    cli::status(&test_config(&server.uri()), "usps", TRACKING_NUMBER, &mut out).await.unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with(&format!("usps {}: DELIVERED\n", TRACKING_NUMBER)), "{}", out);
+    assert!(out.ends_with("Closes as DELIVERED\n"), "{}", out);
+
+    let server = serve("TRANSIT").await;
+    let mut out = Vec::new();
+    cli::status(&test_config(&server.uri()), "usps", TRACKING_NUMBER, &mut out).await.unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.ends_with("Not final; the shipment stays open\n"), "{}", out);
+}
+
+#[tokio::test]
+async fn close_shows_the_datum_and_waits_for_confirmation() {
+    let server = serve("TRANSIT").await;
+    let submissions = Arc::new(AtomicUsize::new(0));
+    let oracle = oracle(&server, &submissions);
+
+    let mut out = Vec::new();
+    cli::close(&oracle, &close_args(&[]), &mut "n\n".as_bytes(), &mut out).await.unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with(&format!("Tracking UTxO {}\n", utxo_ref())), "{}", out);
+    assert!(out.contains(&format!("\"tracking_number\": \"{}\"", TRACKING_NUMBER)), "{}", out);
+    assert!(out.ends_with("Close as DELIVERED? [y/N] Not closed\n"), "{}", out);
+    assert_eq!(submissions.load(Ordering::SeqCst), 0);
+
+    let mut out = Vec::new();
+    cli::close(&oracle, &close_args(&[]), &mut "y\n".as_bytes(), &mut out).await.unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.ends_with(&format!("Closed as DELIVERED in {}\n", SUBMITTED_TX_HASH)), "{}", out);
+    assert_eq!(submissions.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn close_with_yes_skips_the_prompt() {
+    let server = serve("TRANSIT").await;
+    let submissions = Arc::new(AtomicUsize::new(0));
+    let oracle = oracle(&server, &submissions);
+
+    let mut out = Vec::new();
+    cli::close(&oracle, &close_args(&["--yes"]), &mut "".as_bytes(), &mut out).await.unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(!out.contains("[y/N]"), "{}", out);
+    assert_eq!(submissions.load(Ordering::SeqCst), 1);
+
+    let mut unknown = close_args(&["--yes"]);
+    unknown.utxo_ref = format!("{}#3", TRACKING_TX_HASH).parse().unwrap();
+    let error = cli::close(&oracle, &unknown, &mut "".as_bytes(), &mut Vec::new()).await.unwrap_err();
+    assert!(error.to_string().contains("not found on-chain"), "{}", error);
+}
+
+#[test]
+fn subcommands_parse_their_arguments() {
+    let cli = Cli::try_parse_from(["shipping-oracle", "close", &utxo_ref(), "NOT_DELIVERED", "--yes"]).unwrap();
+    let Some(Command::Close(close)) = cli.command else {
+        panic!("expected close, got {:?}", cli.command);
+    };
+    assert_eq!(close.utxo_ref.to_string(), utxo_ref());
+    assert_eq!((close.status, close.timestamp, close.force, close.yes), (DerivedStatus::NotDelivered, None, false, true));
+
+    assert!(Cli::try_parse_from(["shipping-oracle", "close", "not-a-ref", "DELIVERED"]).is_err());
+    assert!(Cli::try_parse_from(["shipping-oracle", "close", &utxo_ref(), "TRANSIT"]).is_err());
+    assert!(Cli::try_parse_from(["shipping-oracle", "close", &utxo_ref(), "RETURNED"]).is_err());
+    assert!(Cli::try_parse_from(["shipping-oracle", "status", "usps"]).is_err());
+
+    let cli = Cli::try_parse_from(["shipping-oracle", "list", "--json"]).unwrap();
+    assert!(matches!(cli.command, Some(Command::List { json: true, tenant: None })));
+}
+
+#[test]
+fn once_overrides_the_configured_run_mode() {
+    let run_mode = |args: &[&str]| Cli::try_parse_from(args).unwrap().run_mode(RunMode::Scheduled);
+
+    assert_eq!(run_mode(&["shipping-oracle"]), RunMode::Scheduled);
+    assert_eq!(run_mode(&["shipping-oracle", "run"]), RunMode::Scheduled);
+    assert_eq!(run_mode(&["shipping-oracle", "once"]), RunMode::Once);
+    assert_eq!(run_mode(&["shipping-oracle", "--once"]), RunMode::Once);
+}
+
+/// Environment of a local deployment, signing with `test_config`'s key
+fn env() -> HashMap<&'static str, String> {
+    [
+        ("SHIPPO_API_KEY", "shippo_test_key".to_string()),
+        ("VALIDATOR_SCRIPT_REF", VALIDATOR_SCRIPT_REF.to_string()),
+        ("ORACLE_SKS", "00".repeat(32)),
+        ("ORACLE_ADDRESS", ORACLE_ADDRESS.to_string()),
+        ("ORACLE_PAYMENT_ADDRESS", ORACLE_ADDRESS.to_string()),
+        ("BLOCKFROST_URL", "http://localhost:3000".to_string()),
+        ("TRP_URL", "http://localhost:8164".to_string()),
+    ]
+    .into_iter()
+    .collect()
+}
+
+#[test]
+fn flags_take_precedence_over_the_environment() {
+    let mut env = env();
+    env.insert("DATABASE_URL", "sqlite://env.db".to_string());
+    let var = |key: &str| env.get(key).cloned();
+
+    let cli = Cli::try_parse_from(["shipping-oracle", "list"]).unwrap();
+    assert!(cli.overrides.is_empty());
+    let config = cli.overrides.load(var).unwrap();
+    assert_eq!(config.blockfrost_url, "http://localhost:3000");
+    assert_eq!(config.state_db_path.as_deref(), Some("env.db"));
+    assert!(!config.dry_run);
+
+    let cli = Cli::try_parse_from([
+        "shipping-oracle",
+        "--blockfrost-url",
+        "http://blockfrost.local",
+        "list",
+        "--state-db-path",
+        "flag.db",
+        "--dry-run",
+    ])
+    .unwrap();
+    let config = cli.overrides.load(var).unwrap();
+    assert_eq!(config.blockfrost_url, "http://blockfrost.local");
+    assert_eq!(config.trp_url, "http://localhost:8164");
+    assert_eq!(config.state_db_path.as_deref(), Some("flag.db"));
+    assert!(config.dry_run);
+}
+
+#[tokio::test]
+async fn dry_run_flag_keeps_close_from_submitting() {
+    let server = serve("TRANSIT").await;
+    let submissions = Arc::new(AtomicUsize::new(0));
+    let (uri, closed_at) = (server.uri(), CLOSED_AT.to_string());
+    let args = Cli::try_parse_from([
+        "shipping-oracle",
+        "--dry-run",
+        "--blockfrost-url",
+        &uri,
+        "--trp-url",
+        &uri,
+        "close",
+        &utxo_ref(),
+        "DELIVERED",
+        "--timestamp",
+        &closed_at,
+        "--yes",
+    ])
+    .unwrap();
+    let Some(Command::Close(close)) = &args.command else {
+        panic!("expected close, got {:?}", args.command);
+    };
+
+    let env = env();
+    let config = args.overrides.load(|key| env.get(key).cloned()).unwrap();
+    let oracle = Oracle::builder()
+        .config(config)
+        .submitter(CountingSubmitter(submissions.clone()))
+        .build()
+        .unwrap();
+
+    let mut out = Vec::new();
+    cli::close(&oracle, close, &mut "".as_bytes(), &mut out).await.unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.starts_with(&format!("Tracking UTxO {}\n", utxo_ref())), "{}", out);
+    assert!(out.ends_with(", not submitted\n"), "{}", out);
+    assert!(out.contains("Dry run: signed the close as DELIVERED in "), "{}", out);
+    assert_eq!(submissions.load(Ordering::SeqCst), 0);
+}
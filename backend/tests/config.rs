@@ -0,0 +1,180 @@
+use std::collections::HashMap;
+
+use shipping_oracle::config::{Config, Network};
+use shipping_oracle::testing::{MAINNET_ORACLE_ADDRESS, ORACLE_ADDRESS, ORACLE_PKH, OUTBOX_ADDRESS, VALIDATOR_SCRIPT_REF};
+use shipping_oracle::timestamp_source::TimestampSource;
+
+const ORACLE_SK: &str = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+
+/// Load a config from a valid base with `overrides` applied
+fn config(overrides: &[(&'static str, &str)]) -> anyhow::Result<Config> {
+    let mut vars: HashMap<&str, String> = [
+        ("SHIPPO_API_KEY", "shippo_test_key"),
+        ("VALIDATOR_SCRIPT_REF", VALIDATOR_SCRIPT_REF),
+        ("ORACLE_SKS", ORACLE_SK),
+        ("ORACLE_ADDRESS", ORACLE_ADDRESS),
+        ("ORACLE_PAYMENT_ADDRESS", ORACLE_ADDRESS),
+        ("BLOCKFROST_URL", "http://localhost:3000"),
+        ("TRP_URL", "http://localhost:8164"),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key, value.to_string()))
+    .collect();
+    vars.extend(overrides.iter().map(|(key, value)| (*key, value.to_string())));
+
+    Config::from_vars(|key| vars.get(key).cloned())
+}
+
+fn error(overrides: &[(&'static str, &str)]) -> String {
+    format!("{:#}", config(overrides).unwrap_err())
+}
+
+#[test]
+fn valid_settings_pass_validation() {
+    let config = config(&[
+        ("ORACLE_PKH", ORACLE_PKH),
+        ("VALIDATOR_SCRIPT_HASH", &"AB".repeat(28)),
+        ("ORACLE_VALIDATOR_KEYS", &format!("{}={}", VALIDATOR_SCRIPT_REF, ORACLE_PKH)),
+    ])
+    .unwrap();
+
+    assert_eq!(config.validator_script_hash, Some("ab".repeat(28)));
+    config.validate().unwrap();
+}
+
+#[test]
+fn addresses_must_be_bech32() {
+    let err = error(&[("ORACLE_ADDRESS", "addr_test1notanaddress")]);
+    assert!(err.starts_with("ORACLE_ADDRESS is not a bech32 address (addr_test1notanaddress)"), "{}", err);
+
+    let err = error(&[("ORACLE_PAYMENT_ADDRESS", "stake1")]);
+    assert!(err.starts_with("ORACLE_PAYMENT_ADDRESS is not a bech32 address (stake1)"), "{}", err);
+}
+
+#[test]
+fn addresses_must_be_on_the_declared_network() {
+    let err = error(&[
+        ("CARDANO_NETWORK", "preprod"),
+        ("ORACLE_ADDRESS", MAINNET_ORACLE_ADDRESS),
+        ("ORACLE_PAYMENT_ADDRESS", MAINNET_ORACLE_ADDRESS),
+    ]);
+    assert_eq!(err, format!("ORACLE_ADDRESS is a mainnet address ({}) but CARDANO_NETWORK is preprod", MAINNET_ORACLE_ADDRESS));
+
+    let err = error(&[("CARDANO_NETWORK", "mainnet"), ("ORACLE_ADDRESS", MAINNET_ORACLE_ADDRESS)]);
+    assert_eq!(err, format!("ORACLE_PAYMENT_ADDRESS is a testnet address ({}) but CARDANO_NETWORK is mainnet", ORACLE_ADDRESS));
+
+    let mainnet = config(&[
+        ("CARDANO_NETWORK", "mainnet"),
+        ("ORACLE_ADDRESS", MAINNET_ORACLE_ADDRESS),
+        ("ORACLE_PAYMENT_ADDRESS", MAINNET_ORACLE_ADDRESS),
+    ])
+    .unwrap();
+    assert_eq!(mainnet.cardano_network, Some(Network::Mainnet));
+    assert!(config(&[("CARDANO_NETWORK", "preview")]).is_ok());
+}
+
+#[test]
+fn network_magics_name_their_network() {
+    assert_eq!(Network::from_magic(764_824_073), Some(Network::Mainnet));
+    assert_eq!(Network::from_magic(Network::Preview.network_magic()), Some(Network::Preview));
+    assert_eq!(Network::from_magic(42), None);
+}
+
+#[test]
+fn hashes_must_be_28_bytes_of_hex() {
+    assert_eq!(
+        error(&[("VALIDATOR_SCRIPT_HASH", &"ab".repeat(32))]),
+        "VALIDATOR_SCRIPT_HASH must be a hex-encoded 28-byte script hash"
+    );
+    assert_eq!(
+        error(&[("ORACLE_PKH", "not-a-hash")]),
+        "ORACLE_PKH must be a hex-encoded 28-byte key hash, got 'not-a-hash'"
+    );
+
+    let keys = format!("{}=abcd", VALIDATOR_SCRIPT_REF);
+    assert_eq!(
+        error(&[("ORACLE_VALIDATOR_KEYS", &keys)]),
+        format!("ORACLE_VALIDATOR_KEYS pkh of {} must be a hex-encoded 28-byte key hash, got 'abcd'", VALIDATOR_SCRIPT_REF)
+    );
+}
+
+#[test]
+fn signing_keys_must_be_32_bytes_of_hex_and_are_not_echoed() {
+    let short = &ORACLE_SK[..62];
+    let err = error(&[("ORACLE_SKS", &format!("{},{}", ORACLE_SK, short))]);
+    assert_eq!(err, "ORACLE_SKS key 2 must be a hex-encoded 32-byte signing key or a key file");
+    assert!(!err.contains(short));
+
+    let err = error(&[("ORACLE_SKS", "/no/such/payment.skey")]);
+    assert_eq!(err, "ORACLE_SKS key 1 must be a hex-encoded 32-byte signing key or a key file");
+}
+
+#[test]
+fn script_ref_must_be_a_utxo_ref() {
+    let err = error(&[("VALIDATOR_SCRIPT_REF", VALIDATOR_SCRIPT_REF.split_once('#').unwrap().0)]);
+    assert!(err.starts_with("Invalid VALIDATOR_SCRIPT_REF: expected TxHash#TxIx"), "{}", err);
+}
+
+#[test]
+fn endpoints_must_be_urls() {
+    let err = error(&[("BLOCKFROST_URL", "blockfrost.local/api/v0")]);
+    assert!(err.starts_with("BLOCKFROST_URL is not a URL (blockfrost.local/api/v0)"), "{}", err);
+
+    let err = error(&[("TRP_URL", "trp")]);
+    assert!(err.starts_with("TRP_URL is not a URL (trp)"), "{}", err);
+}
+
+#[test]
+fn trp_api_key_is_optional() {
+    assert!(config(&[]).unwrap().trp_api_key.is_none());
+    assert!(config(&[("TRP_API_KEY", "  ")]).unwrap().trp_api_key.is_none());
+
+    let config = config(&[("TRP_API_KEY", " dmtr_trp_key ")]).unwrap();
+    assert_eq!(config.trp_api_key.unwrap().expose(), "dmtr_trp_key");
+}
+
+#[test]
+fn admin_api_requires_a_token() {
+    assert!(config(&[]).unwrap().admin_addr.is_none());
+    assert_eq!(error(&[("ADMIN_ADDR", "127.0.0.1:9091")]), "ADMIN_TOKEN is required when ADMIN_ADDR is set");
+    assert_eq!(error(&[("ADMIN_ADDR", "127.0.0.1:9091"), ("ADMIN_TOKEN", " ")]), "ADMIN_TOKEN is required when ADMIN_ADDR is set");
+    assert!(error(&[("ADMIN_ADDR", "localhost"), ("ADMIN_TOKEN", "t")]).starts_with("Invalid ADMIN_ADDR"));
+
+    let config = config(&[("ADMIN_ADDR", "127.0.0.1:9091"), ("ADMIN_TOKEN", " admin-token ")]).unwrap();
+    assert_eq!(config.admin_addr, Some("127.0.0.1:9091".parse().unwrap()));
+    assert_eq!(config.admin_token.unwrap().expose(), "admin-token");
+}
+
+#[test]
+fn status_map_only_takes_on_chain_statuses_the_validator_accepts() {
+    assert_eq!(config(&[]).unwrap().status_mapping.to_string(), "DELIVERED:DELIVERED,FAILURE:NOT_DELIVERED,RETURNED:NOT_DELIVERED");
+    assert_eq!(
+        error(&[("STATUS_MAP", "DELIVERED:DELIVERED,RETURNED:RETURNED")]),
+        "Invalid STATUS_MAP: Invalid on-chain status of RETURNED: expected DELIVERED or NOT_DELIVERED, got 'RETURNED'"
+    );
+    assert!(error(&[("STATUS_MAP", "DELIVERED:delivered")]).ends_with("got 'delivered'"));
+
+    let config = config(&[("STATUS_MAP", "DELIVERED:DELIVERED,HELD:NOT_DELIVERED")]).unwrap();
+    assert_eq!(config.status_mapping.to_string(), "DELIVERED:DELIVERED,HELD:NOT_DELIVERED");
+}
+
+#[test]
+fn outbox_aliases_name_bech32_addresses() {
+    let config = config(&[("OUTBOX_ALIASES", &format!(" {} = acme-store ,", OUTBOX_ADDRESS)), ("METRICS_MAX_OUTBOXES", "10")]).unwrap();
+    assert_eq!(config.outbox_aliases.get(OUTBOX_ADDRESS).map(String::as_str), Some("acme-store"));
+    assert_eq!(config.metrics_max_outboxes, 10);
+
+    let err = error(&[("OUTBOX_ALIASES", "acme-store")]);
+    assert_eq!(err, "Invalid OUTBOX_ALIASES: expected address=alias, got 'acme-store'");
+    let err = error(&[("OUTBOX_ALIASES", "addr_test1notanaddress=acme")]);
+    assert!(err.starts_with("Invalid OUTBOX_ALIASES: addr_test1notanaddress is not a bech32 address"), "{}", err);
+    let err = error(&[("OUTBOX_ALIASES", &format!("{}=other", OUTBOX_ADDRESS))]);
+    assert!(err.contains("cannot be aliased 'other'"), "{}", err);
+}
+
+#[test]
+fn close_timestamps_come_from_the_carrier_unless_the_oracle_clock_is_asked_for() {
+    assert_eq!(config(&[]).unwrap().timestamp_source, TimestampSource::Carrier);
+    assert_eq!(config(&[("TIMESTAMP_SOURCE", "oracle")]).unwrap().timestamp_source, TimestampSource::Oracle);
+    assert_eq!(error(&[("TIMESTAMP_SOURCE", "shippo")]), "Invalid TIMESTAMP_SOURCE: expected oracle or carrier, got 'shippo'");
+}
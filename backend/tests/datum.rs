@@ -0,0 +1,253 @@
+use pallas::ledger::addresses::Address;
+use proptest::prelude::*;
+use serde_json::json;
+
+use shipping_oracle::datum_codec::DecodeError;
+use shipping_oracle::models::{ShipmentDatum, TrackingDatum, TrackingNumber, TrackingUTxO, UtxoRef};
+use shipping_oracle::testing::{
+    ORACLE_PKH, OUTBOX_ADDRESS, deadline_tracking_datum_cbor, hashed_tracking_datum_cbor, shipment_datum_cbor,
+    tracking_datum_cbor,
+};
+
+const OUTBOX_ADDRESS_BYTES: &str = "003045f468c8fb4a8842458bd9214451bf719ab94a1924e4be7b074f60d634892accde9d7cbd40b0ea56d9c5a40aeb4f47fd7301c5c2ea2347";
+
+/// Constr0 datum with raw byte-string fields
+fn datum_with_fields(fields: &[&[u8]]) -> String {
+    let mut cbor = vec![0xd8, 0x79, 0x80 | fields.len() as u8];
+    for field in fields {
+        cbor.push(0x58);
+        cbor.push(field.len() as u8);
+        cbor.extend_from_slice(field);
+    }
+    hex::encode(cbor)
+}
+
+#[test]
+fn from_cbor_decodes_definite_and_indefinite_datums() {
+    let datum = TrackingDatum::from_cbor(&tracking_datum_cbor("usps", "9400111899223197428490")).unwrap();
+    assert_eq!(datum.carrier, "usps");
+    assert_eq!(datum.tracking_number.as_plain(), Some("9400111899223197428490"));
+    assert_eq!(datum.outbox_address.to_bech32().unwrap(), OUTBOX_ADDRESS);
+
+    let indefinite = format!(
+        "d8799f4673686970706f4e53484950504f5f5452414e534954583900{}ff",
+        &OUTBOX_ADDRESS_BYTES[2..]
+    );
+    let datum = TrackingDatum::from_cbor(&indefinite).unwrap();
+    assert_eq!(datum.tracking_number.as_plain(), Some("SHIPPO_TRANSIT"));
+}
+
+#[test]
+fn from_cbor_distinguishes_hashed_tracking_numbers() {
+    let hash: [u8; 32] = std::array::from_fn(|i| 0x80 + i as u8);
+    let datum = TrackingDatum::from_cbor(&hashed_tracking_datum_cbor("usps", &hash)).unwrap();
+    assert_eq!(datum.tracking_number, TrackingNumber::Hashed(hash));
+    assert_eq!(datum.tracking_number.as_bytes(), hash.as_slice());
+
+    // 32 printable characters is still a plain tracking number
+    let long_plain = "1Z999AA10123456784ABCDEFGHIJKLMN";
+    let datum = TrackingDatum::from_cbor(&tracking_datum_cbor("ups", long_plain)).unwrap();
+    assert_eq!(datum.tracking_number.as_plain(), Some(long_plain));
+}
+
+#[test]
+fn shipment_datum_from_cbor_decodes_close_output() {
+    let cbor = shipment_datum_cbor("usps", "9400111899223197428490", "DELIVERED", 1771090081, ORACLE_PKH);
+    let datum = ShipmentDatum::from_cbor(&cbor).unwrap();
+
+    assert_eq!(datum.carrier, "usps");
+    assert_eq!(datum.tracking_number.as_plain(), Some("9400111899223197428490"));
+    assert_eq!(datum.status, "DELIVERED");
+    assert_eq!(datum.timestamp, 1771090081);
+    assert_eq!(datum.oracle_pkh, ORACLE_PKH);
+
+    // A tracking datum is not a shipment datum
+    assert!(ShipmentDatum::from_cbor(&tracking_datum_cbor("usps", "9400111899223197428490")).is_none());
+}
+
+fn field(field: &'static str, reason: &'static str) -> DecodeError {
+    DecodeError::Field { field, reason }
+}
+
+#[test]
+fn from_cbor_rejects_non_hex_input() {
+    for input in ["zz", "d87", "é"] {
+        assert_eq!(TrackingDatum::from_cbor(input).unwrap_err(), DecodeError::InvalidHex, "{}", input);
+    }
+    assert!(matches!(TrackingDatum::from_cbor(""), Err(DecodeError::NotPlutusData(_))));
+}
+
+#[test]
+fn from_cbor_rejects_invalid_utf8_instead_of_defaulting() {
+    let outbox = hex::decode(OUTBOX_ADDRESS_BYTES).unwrap();
+
+    let bad_carrier = datum_with_fields(&[&[0xff, 0xfe], b"9400111899223197428490", &outbox]);
+    assert_eq!(
+        TrackingDatum::from_cbor(&bad_carrier).unwrap_err(),
+        field("carrier", "not a non-empty UTF-8 string")
+    );
+
+    let bad_tracking = datum_with_fields(&[b"usps", &[0xc3, 0x28], &outbox]);
+    assert_eq!(
+        TrackingDatum::from_cbor(&bad_tracking).unwrap_err(),
+        field("tracking_number", "neither a non-empty UTF-8 string nor a 32-byte hash")
+    );
+}
+
+#[test]
+fn from_cbor_rejects_empty_fields() {
+    let outbox = hex::decode(OUTBOX_ADDRESS_BYTES).unwrap();
+    assert_eq!(
+        TrackingDatum::from_cbor(&datum_with_fields(&[b"", b"9400111899223197428490", &outbox])).unwrap_err(),
+        field("carrier", "not a non-empty UTF-8 string")
+    );
+    assert!(TrackingDatum::from_cbor(&datum_with_fields(&[b"usps", b"", &outbox])).is_err());
+}
+
+#[test]
+fn from_cbor_rejects_missing_or_mistyped_fields() {
+    assert_eq!(
+        TrackingDatum::from_cbor(&datum_with_fields(&[b"usps", b"9400111899223197428490"])).unwrap_err(),
+        field("outbox_address", "missing")
+    );
+    assert_eq!(
+        TrackingDatum::from_cbor(&datum_with_fields(&[b"usps", b"9400111899223197428490", &[0x01, 0x02]])).unwrap_err(),
+        field("outbox_address", "not a valid address")
+    );
+    // Integer instead of bytes in the carrier position
+    assert_eq!(TrackingDatum::from_cbor("d879830141614161").unwrap_err(), field("carrier", "not a byte string"));
+    // Not a constructor at all
+    assert_eq!(TrackingDatum::from_cbor("00").unwrap_err(), DecodeError::UnexpectedShape("a constructor"));
+}
+
+#[test]
+fn from_cbor_rejects_oversized_and_deeply_nested_input() {
+    let oversized = format!("{}{}", tracking_datum_cbor("usps", "9400111899223197428490"), "00".repeat(2048));
+    assert_eq!(TrackingDatum::from_cbor(&oversized).unwrap_err(), DecodeError::TooLarge);
+
+    let deeply_nested = format!("{}00", "81".repeat(100_000));
+    assert_eq!(TrackingDatum::from_cbor(&deeply_nested).unwrap_err(), DecodeError::TooLarge);
+
+    let nested_within_cap = format!("{}00", "81".repeat(1000));
+    assert!(TrackingDatum::from_cbor(&nested_within_cap).is_err());
+}
+
+#[test]
+fn from_cbor_reads_the_optional_deadline() {
+    let datum = TrackingDatum::from_cbor(&tracking_datum_cbor("usps", "9400111899223197428490")).unwrap();
+    assert_eq!(datum.deadline, None);
+
+    let datum = TrackingDatum::from_cbor(&deadline_tracking_datum_cbor("usps", "9400111899223197428490", 1_773_144_000)).unwrap();
+    assert_eq!(datum.deadline, Some(1_773_144_000));
+    assert_eq!(datum.outbox_address.to_bech32().unwrap(), OUTBOX_ADDRESS);
+
+    let outbox = hex::decode(OUTBOX_ADDRESS_BYTES).unwrap();
+    let bytes_deadline = datum_with_fields(&[b"usps", b"9400111899223197428490", &outbox, b"tomorrow"]);
+    assert_eq!(TrackingDatum::from_cbor(&bytes_deadline).unwrap_err(), field("deadline", "not an integer"));
+
+    // Four fields, the last being -1
+    let three_fields = datum_with_fields(&[b"usps", b"9400111899223197428490", &outbox]);
+    let negative_deadline = format!("d87984{}20", &three_fields[6..]);
+    assert_eq!(TrackingDatum::from_cbor(&negative_deadline).unwrap_err(), field("deadline", "not a Unix timestamp"));
+}
+
+/// Shelley base, enterprise and script addresses on both networks, round-tripped through bech32
+fn address() -> impl Strategy<Value = Address> {
+    let headers = prop::sample::select(vec![0x00u8, 0x01, 0x10, 0x60, 0x61, 0x70]);
+    (headers, any::<[u8; 28]>(), any::<[u8; 28]>()).prop_map(|(header, payment, delegation)| {
+        let mut bytes = vec![header];
+        bytes.extend(payment);
+        if header >> 4 < 4 {
+            bytes.extend(delegation);
+        }
+        let bech32 = Address::from_bytes(&bytes).unwrap().to_bech32().unwrap();
+        Address::from_bech32(&bech32).unwrap()
+    })
+}
+
+#[test]
+fn to_cbor_matches_the_hand_written_layout() {
+    let datum = TrackingDatum::from_cbor(&tracking_datum_cbor("usps", "9400111899223197428490")).unwrap();
+    assert_eq!(datum.to_cbor_hex(), tracking_datum_cbor("usps", "9400111899223197428490"));
+
+    let expected = format!("d87983447573707356393430303131313839393232333139373432383439305839{}", OUTBOX_ADDRESS_BYTES);
+    assert_eq!(datum.to_cbor_hex(), expected);
+}
+
+#[test]
+fn tracking_utxos_serialize_in_the_report_shape() {
+    let utxo = TrackingUTxO {
+        utxo_ref: UtxoRef::from_hex(&"ab".repeat(32), 1).unwrap(),
+        datum: TrackingDatum::from_cbor(&tracking_datum_cbor("usps", "9400111899223197428490")).unwrap(),
+    };
+
+    let json = serde_json::to_value(&utxo).unwrap();
+    assert_eq!(
+        json,
+        json!({
+            "tx_hash": "ab".repeat(32),
+            "tx_index": 1,
+            "datum": {
+                "carrier": "usps",
+                "tracking_number": "9400111899223197428490",
+                "outbox_address": OUTBOX_ADDRESS,
+            }
+        })
+    );
+    assert_eq!(serde_json::from_value::<TrackingUTxO>(json).unwrap(), utxo);
+}
+
+#[test]
+fn tracking_datums_deserialize_deadlines_and_hashed_tracking_numbers() {
+    let hash = [0xa5; 32];
+    let datum: TrackingDatum = serde_json::from_value(json!({
+        "carrier": "usps",
+        "tracking_number": hex::encode(hash),
+        "outbox_address": OUTBOX_ADDRESS,
+        "deadline": 1_773_144_000,
+    }))
+    .unwrap();
+
+    assert_eq!(datum.tracking_number, TrackingNumber::Hashed(hash));
+    assert_eq!(datum.deadline, Some(1_773_144_000));
+    assert_eq!(serde_json::to_value(&datum).unwrap()["deadline"], 1_773_144_000);
+}
+
+#[test]
+fn tracking_datums_reject_invalid_outbox_addresses() {
+    let err = serde_json::from_value::<TrackingDatum>(json!({
+        "carrier": "usps",
+        "tracking_number": "9400111899223197428490",
+        "outbox_address": "addr_test1notanaddress",
+    }))
+    .unwrap_err();
+    assert!(err.to_string().starts_with("outbox_address 'addr_test1notanaddress' is not a bech32 address"));
+
+    let err = serde_json::from_value::<TrackingUTxO>(json!({
+        "tx_hash": "abcd",
+        "tx_index": 0,
+        "datum": { "carrier": "usps", "tracking_number": "9400111899223197428490", "outbox_address": OUTBOX_ADDRESS },
+    }))
+    .unwrap_err();
+    assert_eq!(err.to_string(), "tx hash must be 64 hex characters, got 'abcd'");
+}
+
+proptest! {
+    #[test]
+    fn to_cbor_round_trips(
+        carrier in "[ -~]{1,24}",
+        tracking_number in "[ -~]{1,40}",
+        outbox_address in address(),
+        deadline in prop::option::of(any::<u64>()),
+    ) {
+        let datum = TrackingDatum {
+            carrier,
+            tracking_number: TrackingNumber::Plain(tracking_number),
+            outbox_address,
+            deadline,
+        };
+
+        prop_assert_eq!(TrackingDatum::from_cbor(&datum.to_cbor_hex()).unwrap(), datum.clone());
+        prop_assert_eq!(serde_json::from_value::<TrackingDatum>(serde_json::to_value(&datum).unwrap()).unwrap(), datum);
+    }
+}
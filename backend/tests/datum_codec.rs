@@ -0,0 +1,111 @@
+use std::sync::Arc;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::blockchain::CardanoClient;
+use shipping_oracle::datum_codec::{CodecRegistry, DatumCodec, DecodeError, MapCodec, PositionalCodec};
+use shipping_oracle::fetcher::DataFetcher;
+use shipping_oracle::models::{TrackingDatum, TrackingNumber};
+use shipping_oracle::shipment::ShipmentClient;
+use shipping_oracle::testing::{ORACLE_ADDRESS, OUTBOX_ADDRESS, map_tracking_datum_cbor, test_config, tracking_datum_cbor};
+
+fn fixture(name: &str) -> String {
+    std::fs::read_to_string(format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap()
+}
+
+fn bytes(datum: &str) -> Vec<u8> {
+    hex::decode(datum).unwrap()
+}
+
+/// Reads the positional layout with the carrier and tracking number swapped
+struct SwappedCodec;
+
+impl DatumCodec for SwappedCodec {
+    fn name(&self) -> &str {
+        "swapped"
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Result<TrackingDatum, DecodeError> {
+        let datum = PositionalCodec.decode(bytes)?;
+        Ok(TrackingDatum {
+            carrier: datum.tracking_number.to_string(),
+            tracking_number: TrackingNumber::Plain(datum.carrier),
+            outbox_address: datum.outbox_address,
+            deadline: datum.deadline,
+        })
+    }
+}
+
+#[test]
+fn each_codec_decodes_its_own_encoding() {
+    let positional = bytes(&tracking_datum_cbor("usps", "TRK1"));
+    let map = bytes(&map_tracking_datum_cbor("usps", "TRK1"));
+
+    for (codec, datum) in [(&PositionalCodec as &dyn DatumCodec, &positional), (&MapCodec, &map)] {
+        let decoded = codec.decode(datum).unwrap();
+        assert_eq!(decoded.carrier, "usps");
+        assert_eq!(decoded.tracking_number.as_plain(), Some("TRK1"));
+        assert_eq!(decoded.outbox_address.to_bech32().unwrap(), OUTBOX_ADDRESS);
+    }
+
+    assert_eq!(PositionalCodec.decode(&map).err(), Some(DecodeError::UnexpectedShape("a constructor")));
+    assert_eq!(MapCodec.decode(&positional).err(), Some(DecodeError::UnexpectedShape("a map")));
+}
+
+#[test]
+fn rejections_list_every_codec_in_order() {
+    let registry = CodecRegistry::from_names(&["map", "positional"]).unwrap();
+    // Map datum without an outbox address
+    let rejection = registry
+        .decode_hex("a2476361727269657244757370734f747261636b696e675f6e756d6265724454524b31")
+        .unwrap_err();
+
+    assert_eq!(
+        rejection.to_string(),
+        "map: field outbox_address: missing; positional: expected a constructor"
+    );
+    assert_eq!(registry.decode_hex("zz").unwrap_err().attempts.len(), 2);
+    assert!(CodecRegistry::from_names(&["positional", "json"]).is_err());
+    assert!(CodecRegistry::from_names::<&str>(&[]).is_err());
+}
+
+#[test]
+fn registry_order_decides_which_codec_wins() {
+    let datum = tracking_datum_cbor("usps", "TRK1");
+
+    let positional_first = CodecRegistry::new(vec![Box::new(PositionalCodec), Box::new(SwappedCodec)]);
+    assert_eq!(positional_first.decode_hex(&datum).unwrap().carrier, "usps");
+
+    let swapped_first = CodecRegistry::new(vec![Box::new(SwappedCodec), Box::new(PositionalCodec)]);
+    assert_eq!(swapped_first.decode_hex(&datum).unwrap().carrier, "TRK1");
+}
+
+#[tokio::test]
+async fn scans_decode_with_the_configured_codecs() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_string(fixture("blockfrost_mixed_datums.json")))
+        .mount(&server)
+        .await;
+
+    // The fixture holds a positional datum, a map datum and a map datum missing its outbox
+    let mut config = test_config(&server.uri());
+    let scan = CardanoClient::new(config.clone()).unwrap().scan_shipments().await.unwrap();
+    assert_eq!((scan.shipments.len(), scan.undecodable.len()), (1, 2));
+
+    config.datum_codecs = vec!["positional".to_string(), "map".to_string()];
+    let client = CardanoClient::new(config.clone()).unwrap();
+    let scan = client.scan_shipments().await.unwrap();
+    assert_eq!(scan.shipments.len(), 2);
+    assert_eq!(scan.shipments[1].datum.tracking_number.as_plain(), Some("SHIPPO_TRANSIT"));
+    assert_eq!(scan.undecodable[0].utxo_ref, format!("{}#1", "8185f0c4c844e28214c52c6871753304f273d0fea872b95fe6e32974d16ef520"));
+    assert_eq!(
+        scan.undecodable[0].rejection.to_string(),
+        "positional: expected a constructor; map: field outbox_address: missing"
+    );
+
+    let fetcher = DataFetcher::new(Arc::new(client), Arc::new(ShipmentClient::new(config).unwrap()));
+    let stats = fetcher.run().await.unwrap().stats;
+    assert_eq!((stats.shipments, stats.undecodable), (2, 1));
+}
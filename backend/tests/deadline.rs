@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::notifier::OracleEvent;
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::state::MemoryStore;
+use shipping_oracle::testing::{
+    FrozenClock, ORACLE_ADDRESS, blockfrost_utxos, deadline_tracking_datum_cbor, shippo_track, test_config,
+    tracking_number,
+};
+
+fn at(rfc3339: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+}
+
+/// One tracking UTxO due by `deadline` that the carrier reports as `status`
+async fn serve_shipment(server: &MockServer, deadline: DateTime<Utc>, status: &str) {
+    let mut utxos = blockfrost_utxos(1);
+    utxos[0]["inline_datum"] = deadline_tracking_datum_cbor("usps", &tracking_number(0), deadline.timestamp() as u64).into();
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(utxos))
+        .mount(server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", status)))
+        .mount(server)
+        .await;
+}
+
+fn oracle(server: &MockServer, now: DateTime<Utc>) -> Oracle {
+    Oracle::builder()
+        .config(test_config(&server.uri()))
+        .state(Arc::new(MemoryStore::new()))
+        .clock(FrozenClock::at(now))
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn expired_shipment_in_transit_is_closed_as_not_delivered() {
+    let server = MockServer::start().await;
+    serve_shipment(&server, at("2026-03-01T00:00:00Z"), "TRANSIT").await;
+    let oracle = oracle(&server, at("2026-03-10T12:00:00Z"));
+    let mut events = oracle.subscribe();
+
+    // TRP is not mocked, so the close fails after the status is chosen
+    let stats = oracle.run_once().await.unwrap().stats;
+    assert_eq!(stats.failed, 1);
+
+    let OracleEvent::ShipmentFailed { status, .. } = events.try_recv().unwrap() else {
+        panic!("expected a failed shipment");
+    };
+    assert_eq!(status, "NOT_DELIVERED");
+}
+
+#[tokio::test]
+async fn shipment_in_transit_before_its_deadline_is_left_open() {
+    let server = MockServer::start().await;
+    serve_shipment(&server, at("2026-03-20T00:00:00Z"), "TRANSIT").await;
+    let oracle = oracle(&server, at("2026-03-10T12:00:00Z"));
+    let mut events = oracle.subscribe();
+
+    let stats = oracle.run_once().await.unwrap().stats;
+    assert_eq!((stats.shipments, stats.failed, stats.submitted), (1, 0, 0));
+    assert!(events.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn final_carrier_status_wins_over_the_deadline() {
+    let server = MockServer::start().await;
+    serve_shipment(&server, at("2026-03-01T00:00:00Z"), "DELIVERED").await;
+    let oracle = oracle(&server, at("2026-03-10T12:00:00Z"));
+    let mut events = oracle.subscribe();
+
+    oracle.run_once().await.unwrap();
+
+    let OracleEvent::ShipmentFailed { status, .. } = events.try_recv().unwrap() else {
+        panic!("expected a failed shipment");
+    };
+    assert_eq!(status, "DELIVERED");
+}
@@ -0,0 +1,102 @@
+use serde_json::{Value, json};
+use tokio::sync::broadcast;
+use tracing_subscriber::layer::SubscriberExt;
+
+use shipping_oracle::debug_events::{self, EventBuffer};
+use shipping_oracle::notifier::OracleEvent;
+use shipping_oracle::redact;
+
+/// The events `buffer` keeps, newest first, as `GET /debug/events` serializes them
+fn serialized(buffer: &EventBuffer) -> Vec<Value> {
+    buffer.recent().iter().map(|event| serde_json::to_value(event).unwrap()).collect()
+}
+
+fn failed(tenant: Option<&str>) -> OracleEvent {
+    OracleEvent::ShipmentFailed {
+        utxo_ref: format!("{:064x}#0", 0),
+        carrier: "usps".to_string(),
+        tracking_number: "TRK0".to_string(),
+        status: "DELIVERED".to_string(),
+        timestamp: 1_700_000_000,
+        error: "submit refused with key sk_debug_events_secret".to_string(),
+        tenant: tenant.map(str::to_string),
+        run_id: Some("run-1".to_string()),
+    }
+}
+
+#[test]
+fn the_oldest_events_are_evicted_past_the_capacity() {
+    let buffer = EventBuffer::new(3);
+    let subscriber = tracing_subscriber::registry().with(buffer.layer());
+    tracing::subscriber::with_default(subscriber, || {
+        for n in 0..5 {
+            tracing::warn!(n, "warning {}", n);
+            tracing::info!("not kept");
+        }
+    });
+
+    let messages: Vec<Value> = serialized(&buffer).into_iter().map(|event| event["message"].clone()).collect();
+    assert_eq!(messages, vec![json!("warning 4"), json!("warning 3"), json!("warning 2")]);
+    assert_eq!(buffer.capacity(), 3);
+}
+
+#[test]
+fn log_records_carry_their_span_context_redacted() {
+    redact::register_secret("sk_debug_events_secret");
+    let buffer = EventBuffer::new(10);
+    let subscriber = tracing_subscriber::registry().with(buffer.layer());
+    tracing::subscriber::with_default(subscriber, || {
+        let run = tracing::info_span!("run", run_id = "run-1", tenant = "acme");
+        let _run = run.enter();
+        let shipment = tracing::info_span!("shipment", utxo_ref = "abc#0", carrier = "usps");
+        let _shipment = shipment.enter();
+        tracing::error!(error = "rejected sk_debug_events_secret", "❌ Failed to close shipment");
+    });
+
+    let events = serialized(&buffer);
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert!(event["at"].is_string());
+    assert_eq!(event["source"], json!("log"));
+    assert_eq!(event["tenant"], json!("acme"));
+    assert_eq!(event["level"], json!("ERROR"));
+    assert_eq!(event["target"], json!("debug_events"));
+    assert_eq!(event["message"], json!("❌ Failed to close shipment"));
+    assert_eq!(event["fields"], json!({ "error": "rejected [REDACTED]" }));
+    assert_eq!(
+        event["context"],
+        json!({ "run_id": "run-1", "tenant": "acme", "utxo_ref": "abc#0", "carrier": "usps" })
+    );
+}
+
+#[test]
+fn oracle_events_are_kept_as_the_notifiers_see_them() {
+    redact::register_secret("sk_debug_events_secret");
+    let buffer = EventBuffer::new(10);
+    buffer.record_oracle_event(&failed(Some("acme")));
+
+    let events = serialized(&buffer);
+    let event = &events[0];
+    assert_eq!(event["source"], json!("oracle"));
+    assert_eq!(event["tenant"], json!("acme"));
+    assert_eq!(event["event"]["event"], json!("shipment_failed"));
+    assert_eq!(event["event"]["utxo_ref"], json!(format!("{:064x}#0", 0)));
+    assert_eq!(event["event"]["tracking_number"], json!("TRK0"));
+    assert_eq!(event["event"]["error"], json!("submit refused with key [REDACTED]"));
+}
+
+#[tokio::test]
+async fn subscribed_events_are_recorded_until_the_channel_closes() {
+    let buffer = EventBuffer::new(10);
+    let (sender, receiver) = broadcast::channel(4);
+    let recording = tokio::spawn(debug_events::record_oracle_events(buffer.clone(), receiver));
+
+    sender.send(failed(None)).unwrap();
+    sender.send(failed(None)).unwrap();
+    drop(sender);
+    recording.await.unwrap();
+
+    let events = serialized(&buffer);
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0]["tenant"], Value::Null);
+}
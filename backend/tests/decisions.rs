@@ -0,0 +1,141 @@
+use serde_json::json;
+use std::sync::Arc;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::decisions::{Confirmation, DecisionQuery, DecisionSource, find_decisions};
+use shipping_oracle::lifecycle::{LifecycleTransition, ShipmentLifecycle};
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::state::{MemoryStore, ShipmentState, StateStore, Submission};
+use shipping_oracle::testing::{ORACLE_PKH, OUTBOX_ADDRESS, shipment_datum_cbor, test_config};
+
+const TRACKING_NUMBER: &str = "9400111899223456789012";
+const OTHER_OUTBOX: &str = "addr_test1vqpp4rqsgkhyaz5ejjtwzane9wnkggfrn9pptgmtwq7fqws6t8yck";
+
+/// Journal a closure of `utxo_ref` by `tx_hash` the way a run does
+async fn close(state: &dyn StateStore, utxo_ref: &str, tx_hash: &str, tracking_number: &str, outbox: &str, at: u64) {
+    state
+        .record_submission(&Submission {
+            utxo_ref: utxo_ref.to_string(),
+            tx_hash: tx_hash.to_string(),
+            status: "DELIVERED".to_string(),
+            submitted_at: at,
+            signer_pkh: None,
+            fee: Some(174_257),
+            outbox_address: Some(outbox.to_string()),
+            carrier: Some("usps".to_string()),
+            tracking_number: Some(tracking_number.to_string()),
+        })
+        .await
+        .unwrap();
+    state
+        .save_shipment(&ShipmentState { closed_tx_hash: Some(tx_hash.to_string()), ..ShipmentState::new(utxo_ref) })
+        .await
+        .unwrap();
+}
+
+fn by_tracking_number() -> DecisionQuery {
+    DecisionQuery {
+        tracking_number: Some(TRACKING_NUMBER.to_string()),
+        ..DecisionQuery::default()
+    }
+}
+
+#[tokio::test]
+async fn journalled_decisions_are_found_locally() {
+    let state = MemoryStore::new();
+    // aa#0 was resubmitted; only a2 closed it
+    close(&state, "aa#0", "a1", TRACKING_NUMBER, OUTBOX_ADDRESS, 100).await;
+    close(&state, "aa#0", "a2", TRACKING_NUMBER, OUTBOX_ADDRESS, 200).await;
+    close(&state, "bb#0", "b1", "1Z999AA10123456784", OUTBOX_ADDRESS, 300).await;
+    state
+        .record_transition(&LifecycleTransition {
+            utxo_ref: "aa#0".to_string(),
+            state: ShipmentLifecycle::Confirmed { block: 4_213_337 },
+            at: 400,
+        })
+        .await
+        .unwrap();
+
+    let page = find_decisions(&state, &by_tracking_number()).await.unwrap();
+    assert_eq!(page.total, 1);
+    let decision = &page.decisions[0];
+    assert_eq!((decision.utxo_ref.as_deref(), decision.tx_hash.as_str()), (Some("aa#0"), "a2"));
+    assert_eq!((decision.status.as_str(), decision.timestamp), ("DELIVERED", 200));
+    assert_eq!(decision.confirmation, Confirmation::Confirmed { block: Some(4_213_337) });
+    assert_eq!(decision.source, DecisionSource::State);
+
+    let json: serde_json::Value = serde_json::from_str(&page.to_json().unwrap()).unwrap();
+    assert_eq!(json["decisions"][0]["confirmation"], json!({ "state": "confirmed", "block": 4_213_337 }));
+    assert_eq!(json["decisions"][0]["source"], "state");
+
+    let by_outbox = DecisionQuery {
+        outbox: Some(OUTBOX_ADDRESS.to_string()),
+        offset: 1,
+        limit: 1,
+        ..DecisionQuery::default()
+    };
+    let page = find_decisions(&state, &by_outbox).await.unwrap();
+    assert_eq!((page.total, page.offset, page.limit), (2, 1, 1));
+    assert_eq!(page.decisions[0].tx_hash, "b1");
+    assert_eq!(page.decisions[0].confirmation, Confirmation::Pending);
+
+    assert!(find_decisions(&state, &DecisionQuery::default()).await.is_err());
+}
+
+#[tokio::test]
+async fn missing_local_state_falls_back_to_the_outbox() {
+    let server = MockServer::start().await;
+    let datum = |tracking_number: &str, status: &str, timestamp: u64| {
+        shipment_datum_cbor("usps", tracking_number, status, timestamp, ORACLE_PKH)
+    };
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", OUTBOX_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([
+            { "tx_hash": "c1", "output_index": 0, "inline_datum": datum(TRACKING_NUMBER, "NOT_DELIVERED", 100) },
+            { "tx_hash": "c2", "output_index": 0, "inline_datum": datum(TRACKING_NUMBER, "DELIVERED", 200) },
+            { "tx_hash": "c3", "output_index": 0, "inline_datum": datum("1Z999AA10123456784", "DELIVERED", 300) },
+            { "tx_hash": "c4", "output_index": 1, "inline_datum": null },
+        ])))
+        .mount(&server)
+        .await;
+
+    let oracle = Oracle::builder()
+        .config(test_config(&server.uri()))
+        .state(Arc::new(MemoryStore::new()))
+        .build()
+        .unwrap();
+    let query = DecisionQuery {
+        outbox: Some(OUTBOX_ADDRESS.to_string()),
+        ..by_tracking_number()
+    };
+
+    let page = oracle.decisions(&query).await.unwrap();
+    assert_eq!(page.total, 1);
+    let decision = &page.decisions[0];
+    assert_eq!((decision.tx_hash.as_str(), decision.status.as_str(), decision.timestamp), ("c2", "DELIVERED", 200));
+    assert_eq!(decision.utxo_ref, None);
+    assert_eq!(decision.confirmation, Confirmation::Confirmed { block: None });
+    assert_eq!(decision.source, DecisionSource::Chain);
+}
+
+#[tokio::test]
+async fn unknown_shipments_are_not_found() {
+    let server = MockServer::start().await;
+    let oracle = Oracle::builder()
+        .config(test_config(&server.uri()))
+        .state(Arc::new(MemoryStore::new()))
+        .build()
+        .unwrap();
+
+    // Blockfrost answers 404 for an address that never held anything
+    let query = DecisionQuery {
+        outbox: Some(OTHER_OUTBOX.to_string()),
+        ..by_tracking_number()
+    };
+    let page = oracle.decisions(&query).await.unwrap();
+    assert_eq!((page.total, page.decisions.len()), (0, 0));
+
+    let page = oracle.decisions(&by_tracking_number()).await.unwrap();
+    assert_eq!(page.total, 0);
+}
@@ -0,0 +1,148 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::run_report::ShipmentAction;
+use shipping_oracle::submitter::{SubmitError, TxSubmitter};
+use shipping_oracle::testing::{FrozenClock, ORACLE_ADDRESS, shippo_track, test_config, tracking_datum_cbor};
+
+/// The tracking UTxO `close_shipment_envelope_valid.json` spends, closed as delivered at `CLOSED_AT`
+const TRACKING_TX_HASH: &str = "a7a264ac1bef1f0da6312d16fb8f68b18d1a0d5010d8cb6a61790bf02a759301";
+const TRACKING_NUMBER: &str = "9400111899223456789012";
+const CLOSED_AT: i64 = 1771090081;
+const SUBMITTED_TX_HASH: &str = "c1";
+
+#[derive(Clone, Deserialize)]
+struct EnvelopeFixture {
+    tx: String,
+    hash: String,
+}
+
+fn envelope() -> EnvelopeFixture {
+    let path = format!("{}/tests/fixtures/close_shipment_envelope_valid.json", env!("CARGO_MANIFEST_DIR"));
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+/// TRP answering every resolve with the fixture envelope, echoing the JSON-RPC id
+struct TrpResolve(EnvelopeFixture);
+
+impl Respond for TrpResolve {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let id = serde_json::from_slice::<Value>(&request.body)
+            .ok()
+            .and_then(|body| body.get("id").cloned())
+            .unwrap_or(Value::Null);
+
+        ResponseTemplate::new(200).set_body_json(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": { "tx": self.0.tx, "hash": self.0.hash },
+        }))
+    }
+}
+
+struct CountingSubmitter(Arc<AtomicUsize>);
+
+#[async_trait::async_trait]
+impl TxSubmitter for CountingSubmitter {
+    async fn submit(&self, _signed_tx: Vec<u8>) -> Result<String, SubmitError> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Ok(SUBMITTED_TX_HASH.to_string())
+    }
+}
+
+/// One delivered shipment whose close TRP resolves to the fixture envelope
+async fn serve_delivered() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "address": ORACLE_ADDRESS,
+            "tx_hash": TRACKING_TX_HASH,
+            "tx_index": 0,
+            "output_index": 0,
+            "amount": [{ "unit": "lovelace", "quantity": "2000000" }],
+            "block": format!("{:064x}", 0),
+            "data_hash": null,
+            "inline_datum": tracking_datum_cbor("usps", TRACKING_NUMBER),
+            "reference_script_hash": null,
+        }])))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", TRACKING_NUMBER, "DELIVERED")))
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/"))
+        .respond_with(TrpResolve(envelope()))
+        .mount(&server)
+        .await;
+
+    server
+}
+
+fn oracle(server: &MockServer, dry_run: bool, submissions: &Arc<AtomicUsize>) -> Oracle {
+    let mut config = test_config(&server.uri());
+    config.dry_run = dry_run;
+    let closed_at: DateTime<Utc> = DateTime::from_timestamp(CLOSED_AT, 0).unwrap();
+
+    Oracle::builder()
+        .config(config)
+        .clock(FrozenClock::at(closed_at))
+        .submitter(CountingSubmitter(submissions.clone()))
+        .build()
+        .unwrap()
+}
+
+#[tokio::test]
+async fn dry_run_signs_without_submitting() {
+    let server = serve_delivered().await;
+    let submissions = Arc::new(AtomicUsize::new(0));
+    let oracle = oracle(&server, true, &submissions);
+
+    let report = oracle.run_once().await.unwrap();
+    let stats = report.stats;
+    assert_eq!((stats.shipments, stats.dry_run, stats.submitted, stats.failed), (1, 1, 0, 0));
+    assert_eq!(submissions.load(Ordering::SeqCst), 0);
+    assert!(oracle.data_fetcher().pending_submissions().is_empty());
+
+    assert_eq!(report.shipments.len(), 1);
+    let shipment = &report.shipments[0];
+    assert_eq!(shipment.utxo_ref, format!("{}#0", TRACKING_TX_HASH));
+    assert_eq!(shipment.derived_status.as_deref(), Some("DELIVERED"));
+    let ShipmentAction::DryRun { tx_hash, params, cbor_hex } = &shipment.action else {
+        panic!("expected a dry run, got {:?}", shipment.action);
+    };
+    assert_eq!(tx_hash, &envelope().hash);
+    assert_eq!(params.p_timestamp, CLOSED_AT.to_string());
+    assert!(cbor_hex.len() > envelope().tx.len());
+
+    let entry = serde_json::to_value(shipment).unwrap();
+    assert_eq!(entry["action"]["type"], json!("dry_run"));
+    assert_eq!(entry["action"]["tx_hash"], json!(envelope().hash));
+}
+
+#[tokio::test]
+async fn same_close_is_submitted_without_dry_run() {
+    let server = serve_delivered().await;
+    let submissions = Arc::new(AtomicUsize::new(0));
+    let oracle = oracle(&server, false, &submissions);
+
+    let report = oracle.run_once().await.unwrap();
+    assert_eq!((report.stats.dry_run, report.stats.submitted, report.stats.failed), (0, 1, 0));
+    assert_eq!(submissions.load(Ordering::SeqCst), 1);
+
+    assert_eq!(report.shipments.len(), 1);
+    assert!(matches!(
+        &report.shipments[0].action,
+        ShipmentAction::Submitted { tx_hash } if tx_hash == SUBMITTED_TX_HASH
+    ));
+    assert_eq!(oracle.data_fetcher().last_run().unwrap().shipments.len(), 1);
+}
@@ -0,0 +1,81 @@
+use std::sync::Arc;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::state::{MemoryStore, StateStore};
+use shipping_oracle::testing::{ORACLE_ADDRESS, blockfrost_utxos, shippo_track, test_config, tracking_datum_cbor};
+
+fn utxo_ref(index: usize) -> String {
+    format!("{:064x}#0", index)
+}
+
+/// Serve a tracking UTxO per carrier/tracking number pair, all of them with carrier status `status`
+async fn serve(server: &MockServer, pairs: &[(&str, &str)], status: &str) {
+    let mut utxos = blockfrost_utxos(pairs.len());
+    for (i, (carrier, tracking_number)) in pairs.iter().enumerate() {
+        utxos[i]["inline_datum"] = tracking_datum_cbor(carrier, tracking_number).into();
+    }
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(utxos))
+        .mount(server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", status)))
+        .mount(server)
+        .await;
+}
+
+async fn tracks_requested(server: &MockServer) -> Vec<String> {
+    let requests = server.received_requests().await.unwrap();
+    requests
+        .iter()
+        .map(|request| request.url.path().to_string())
+        .filter(|path| path.starts_with("/tracks/"))
+        .collect()
+}
+
+#[tokio::test]
+async fn duplicate_tracking_numbers_are_fetched_once() {
+    let server = MockServer::start().await;
+    serve(&server, &[("usps", "DUP1"), ("usps", "OTHER"), ("usps", "DUP1")], "TRANSIT").await;
+    let oracle = Oracle::builder().config(test_config(&server.uri())).build().unwrap();
+
+    let report = oracle.run_once().await.unwrap();
+    assert_eq!((report.stats.shipments, report.stats.deduplicated, report.stats.failed), (3, 1, 0));
+    assert!(report.shipments.iter().all(|shipment| shipment.fetched_status.as_deref() == Some("TRANSIT")));
+
+    let mut requested = tracks_requested(&server).await;
+    requested.sort();
+    assert_eq!(requested, vec!["/tracks/usps/DUP1", "/tracks/usps/OTHER"]);
+}
+
+#[tokio::test]
+async fn every_duplicate_is_closed_with_the_shared_status() {
+    let server = MockServer::start().await;
+    serve(&server, &[("usps", "DUP1"), ("usps", "DUP1")], "DELIVERED").await;
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    let oracle = Oracle::builder().config(test_config(&server.uri())).state(state.clone()).build().unwrap();
+
+    // TRP is not mocked, so both closes are attempted and fail
+    let report = oracle.run_once().await.unwrap();
+    assert_eq!((report.stats.deduplicated, report.stats.failed), (1, 2));
+    assert!(report.shipments.iter().all(|shipment| shipment.derived_status.as_deref() == Some("DELIVERED")));
+    for index in 0..2 {
+        assert!(!state.attempts(&utxo_ref(index)).await.unwrap().is_empty(), "no close attempted for {}", index);
+    }
+    assert_eq!(tracks_requested(&server).await.len(), 1);
+}
+
+#[tokio::test]
+async fn other_carriers_are_not_duplicates() {
+    let server = MockServer::start().await;
+    serve(&server, &[("usps", "DUP1"), ("fedex", "DUP1")], "TRANSIT").await;
+    let oracle = Oracle::builder().config(test_config(&server.uri())).build().unwrap();
+
+    let report = oracle.run_once().await.unwrap();
+    assert_eq!(report.stats.deduplicated, 0);
+    assert_eq!(tracks_requested(&server).await.len(), 2);
+}
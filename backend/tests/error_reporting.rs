@@ -0,0 +1,84 @@
+#![cfg(feature = "sentry")]
+
+use sentry::protocol::Level;
+use sentry::test::with_captured_events;
+
+use shipping_oracle::error_reporting::{SentryNotifier, error_class};
+use shipping_oracle::notifier::{Notifier, OracleEvent};
+use shipping_oracle::timestamp_source::TimestampSource;
+
+const RUN_ID: &str = "01K7NVX3C5RZ1E4GQ8M2WJ6T9B";
+
+fn failed_event(utxo_ref: &str, error: &str) -> OracleEvent {
+    OracleEvent::ShipmentFailed {
+        utxo_ref: utxo_ref.to_string(),
+        carrier: "usps".to_string(),
+        tracking_number: "9400111899223197428490".to_string(),
+        status: "DELIVERED".to_string(),
+        timestamp: 1771090081,
+        error: error.to_string(),
+        tenant: Some("preprod".to_string()),
+        run_id: Some(RUN_ID.to_string()),
+    }
+}
+
+#[test]
+fn failed_run_captures_grouped_sentry_events() {
+    let events = with_captured_events(|| {
+        futures::executor::block_on(async {
+            let notifier = SentryNotifier;
+            notifier.notify(&failed_event(
+                "a7a2#0",
+                "Blockfrost transaction submission failed (status 400): {\"error\":\"BadInputs a7a2#0\"}",
+            )).await.unwrap();
+            notifier.notify(&failed_event(
+                "8185#0",
+                "Blockfrost transaction submission failed (status 400): {\"error\":\"BadInputs 8185#0\"}",
+            )).await.unwrap();
+        });
+    });
+
+    assert_eq!(events.len(), 2);
+    for event in &events {
+        assert_eq!(event.level, Level::Error);
+        assert_eq!(event.tags.get("stage").map(String::as_str), Some("submit"));
+        assert_eq!(event.tags.get("carrier").map(String::as_str), Some("usps"));
+        assert_eq!(event.tags.get("tenant").map(String::as_str), Some("preprod"));
+        assert_eq!(event.tags.get("run_id").map(String::as_str), Some(RUN_ID));
+        assert_eq!(
+            event.fingerprint.iter().map(|f| f.as_ref()).collect::<Vec<_>>(),
+            vec!["shipment-failed", "Blockfrost transaction submission failed"],
+        );
+    }
+    assert_eq!(events[0].tags.get("utxo_ref").map(String::as_str), Some("a7a2#0"));
+    assert_eq!(events[1].tags.get("utxo_ref").map(String::as_str), Some("8185#0"));
+}
+
+#[test]
+fn closed_shipments_are_not_captured() {
+    let events = with_captured_events(|| {
+        futures::executor::block_on(async {
+            SentryNotifier.notify(&OracleEvent::ShipmentClosed {
+                utxo_ref: "a7a2#0".to_string(),
+                carrier: "usps".to_string(),
+                tracking_number: "9400111899223197428490".to_string(),
+                status: "DELIVERED".to_string(),
+                timestamp: 1771090081,
+                timestamp_source: TimestampSource::Oracle,
+                oracle_timestamp: 1771090081,
+                tx_hash: "584cbabb".to_string(),
+                tenant: None,
+                run_id: None,
+            }).await.unwrap();
+        });
+    });
+
+    assert!(events.is_empty());
+}
+
+#[test]
+fn error_class_strips_details() {
+    assert_eq!(error_class("Shipment API query failed (status 500): boom"), "Shipment API query failed");
+    assert_eq!(error_class("Failed to resolve transaction: timeout"), "Failed to resolve transaction");
+    assert_eq!(error_class(""), "unknown");
+}
@@ -0,0 +1,146 @@
+use serde_json::json;
+use std::sync::Arc;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::blockchain::CardanoClient;
+use shipping_oracle::fees::{FeeReport, OutboxFees};
+use shipping_oracle::fetcher::DataFetcher;
+use shipping_oracle::lifecycle::{LifecycleTransition, ShipmentLifecycle};
+use shipping_oracle::metrics::Metrics;
+use shipping_oracle::shipment::ShipmentClient;
+use shipping_oracle::state::{MemoryStore, ShipmentState, SqliteStore, StateStore, Submission};
+use shipping_oracle::testing::{ORACLE_ADDRESS, test_config};
+
+const OUTBOX_A: &str = "addr_test1vqpp4rqsgkhyaz5ejjtwzane9wnkggfrn9pptgmtwq7fqws6t8yck";
+const OUTBOX_B: &str = "addr_test1qqcytargera54zzzgk9ajg2y2xlhrx4efgvjfe970vr57cxkxjyj4nx7n47t6s9saftdn3dypt4573lawvqutsh2ydrs3hxqj3";
+
+const RUN_1: u64 = 1_771_000_000;
+const RUN_2: u64 = 1_771_090_081;
+
+/// Journal a closure the way a run does: record the submission, then mark the shipment closed by it
+async fn close(state: &dyn StateStore, utxo_ref: &str, tx_hash: &str, fee: u64, outbox: &str, at: u64) {
+    state
+        .record_submission(&Submission {
+            utxo_ref: utxo_ref.to_string(),
+            tx_hash: tx_hash.to_string(),
+            status: "DELIVERED".to_string(),
+            submitted_at: at,
+            signer_pkh: None,
+            fee: Some(fee),
+            outbox_address: Some(outbox.to_string()),
+            carrier: None,
+            tracking_number: None,
+        })
+        .await
+        .unwrap();
+
+    let mut shipment = state.shipment(utxo_ref).await.unwrap().unwrap_or_else(|| ShipmentState::new(utxo_ref));
+    shipment.closed_tx_hash = Some(tx_hash.to_string());
+    state.save_shipment(&shipment).await.unwrap();
+}
+
+/// Two runs: the second resubmits `bb#0`, whose first close never landed, and closes `cc#0`
+async fn two_runs(state: &dyn StateStore) {
+    close(state, "aa#0", "a1", 170_000, OUTBOX_A, RUN_1).await;
+    close(state, "bb#0", "b1", 180_000, OUTBOX_B, RUN_1).await;
+
+    close(state, "bb#0", "b2", 190_000, OUTBOX_B, RUN_2).await;
+    close(state, "cc#0", "c1", 200_000, OUTBOX_A, RUN_2).await;
+}
+
+#[tokio::test]
+async fn fees_accumulate_across_runs_without_double_counting() {
+    for state in [
+        Box::new(MemoryStore::new()) as Box<dyn StateStore>,
+        Box::new(SqliteStore::open_in_memory().unwrap()),
+    ] {
+        two_runs(state.as_ref()).await;
+
+        let report = FeeReport::from_state(state.as_ref(), None).await.unwrap();
+        assert_eq!(report.closures, 3);
+        assert_eq!(report.total_lovelace, 170_000 + 190_000 + 200_000);
+        assert_eq!(report.by_outbox[OUTBOX_A], OutboxFees { closures: 2, lovelace: 370_000 });
+        assert_eq!(report.by_outbox[OUTBOX_B], OutboxFees { closures: 1, lovelace: 190_000 });
+
+        let report = FeeReport::from_state(state.as_ref(), Some(RUN_2)).await.unwrap();
+        assert_eq!((report.closures, report.total_lovelace), (2, 390_000));
+    }
+}
+
+#[tokio::test]
+async fn closures_without_a_recorded_fee_are_reported_separately() {
+    let state = MemoryStore::new();
+    close(&state, "aa#0", "a1", 170_000, OUTBOX_A, RUN_1).await;
+    state
+        .record_submission(&Submission {
+            utxo_ref: "dd#0".to_string(),
+            tx_hash: "d1".to_string(),
+            status: "DELIVERED".to_string(),
+            submitted_at: RUN_1,
+            signer_pkh: None,
+            fee: None,
+            outbox_address: None,
+            carrier: None,
+            tracking_number: None,
+        })
+        .await
+        .unwrap();
+    state
+        .save_shipment(&ShipmentState { closed_tx_hash: Some("d1".to_string()), ..ShipmentState::new("dd#0") })
+        .await
+        .unwrap();
+
+    let report = FeeReport::from_state(&state, None).await.unwrap();
+    assert_eq!((report.closures, report.total_lovelace, report.unknown_fee), (1, 170_000, 1));
+
+    let markdown = report.to_markdown();
+    assert!(markdown.contains("- Total: 170000 lovelace (0.170000 ADA)"), "{}", markdown);
+    assert!(markdown.contains(&format!("| `{}` | 1 | 170000 | 0.170000 |", OUTBOX_A)), "{}", markdown);
+    assert!(markdown.contains("- Closures without a recorded fee: 1"), "{}", markdown);
+}
+
+#[tokio::test]
+async fn confirmed_closes_add_their_fee_to_the_metrics_once() {
+    let server = MockServer::start().await;
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    let metrics = Metrics::new().for_tenant(Some("acme"));
+    let config = test_config(&server.uri());
+    let fetcher = DataFetcher::new(
+        Arc::new(CardanoClient::new(config.clone()).unwrap()),
+        Arc::new(ShipmentClient::new(config).unwrap()),
+    )
+    .with_state(state.clone())
+    .with_metrics(metrics.clone());
+
+    // `b1` landed; `b2`, submitted again in the next run, never did
+    close(state.as_ref(), "bb#0", "b1", 180_000, OUTBOX_B, RUN_1).await;
+    close(state.as_ref(), "bb#0", "b2", 190_000, OUTBOX_B, RUN_2).await;
+    state
+        .record_transition(&LifecycleTransition {
+            utxo_ref: "bb#0".to_string(),
+            state: ShipmentLifecycle::Submitted { tx_hash: "b2".to_string() },
+            at: RUN_2,
+        })
+        .await
+        .unwrap();
+
+    // The tracking UTxO is gone and only `b1` is on-chain
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/txs/b1"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "hash": "b1", "block_height": 4_213_337 })))
+        .mount(&server)
+        .await;
+
+    let sample = r#"oracle_fees_paid_lovelace_total{tenant="acme"} 180000"#;
+    for _ in 0..2 {
+        fetcher.run().await.unwrap();
+        let body = metrics.render().unwrap();
+        assert!(body.lines().any(|line| line == sample), "{}", body);
+    }
+}
@@ -0,0 +1,69 @@
+use std::time::{Duration, Instant};
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::run_report::{RunReport, ShipmentAction};
+use shipping_oracle::testing::{ORACLE_ADDRESS, blockfrost_utxos, shippo_track, test_config};
+
+const SHIPPO_DELAY: Duration = Duration::from_millis(200);
+
+/// Serve `shipments` tracking UTxOs whose carrier status takes `SHIPPO_DELAY` to come
+async fn serve_slow(server: &MockServer, shipments: usize) {
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(shipments)))
+        .mount(server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/tracks/usps/TRK0000000002"))
+        .respond_with(ResponseTemplate::new(500).set_delay(SHIPPO_DELAY))
+        .with_priority(1)
+        .mount(server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", "TRANSIT")).set_delay(SHIPPO_DELAY))
+        .mount(server)
+        .await;
+}
+
+async fn timed_run(concurrency: usize) -> (RunReport, Duration) {
+    let server = MockServer::start().await;
+    serve_slow(&server, 8).await;
+    let mut config = test_config(&server.uri());
+    config.fetch_concurrency = concurrency;
+    let oracle = Oracle::builder().config(config).build().unwrap();
+
+    let started = Instant::now();
+    let report = oracle.run_once().await.unwrap();
+    (report, started.elapsed())
+}
+
+#[tokio::test]
+async fn run_time_scales_with_the_concurrency_limit() {
+    let (_, sequential) = timed_run(1).await;
+    assert!(sequential >= SHIPPO_DELAY * 8, "sequential run took {:?}", sequential);
+
+    let (_, concurrent) = timed_run(4).await;
+    assert!(concurrent >= SHIPPO_DELAY * 2, "concurrent run took {:?}", concurrent);
+    assert!(concurrent < SHIPPO_DELAY * 6, "concurrent run took {:?}", concurrent);
+}
+
+#[tokio::test]
+async fn a_failing_shipment_does_not_stop_the_others() {
+    let (report, _) = timed_run(4).await;
+
+    assert_eq!((report.stats.shipments, report.stats.failed), (8, 1));
+    let tracking_numbers: Vec<&str> = report.shipments.iter().map(|shipment| shipment.tracking_number.as_str()).collect();
+    let scan_order: Vec<String> = (0..8).map(|i| format!("TRK{:010}", i)).collect();
+    assert_eq!(tracking_numbers, scan_order);
+
+    for shipment in &report.shipments {
+        if shipment.tracking_number == "TRK0000000002" {
+            assert!(matches!(&shipment.action, ShipmentAction::Failed { error } if error.contains("status 500")));
+        } else {
+            assert_eq!(shipment.fetched_status.as_deref(), Some("TRANSIT"));
+        }
+    }
+}
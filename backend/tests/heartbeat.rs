@@ -0,0 +1,57 @@
+use wiremock::matchers::{method, path, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::heartbeat::Heartbeat;
+
+#[tokio::test]
+async fn heartbeat_pings_base_url_after_successful_run() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/ping/oracle"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let heartbeat = Heartbeat::new(format!("{}/ping/oracle/", server.uri())).unwrap();
+    heartbeat.ping_success().await;
+
+    let status = heartbeat.last_status().unwrap();
+    assert!(status.run_succeeded);
+    assert!(status.delivered);
+}
+
+#[tokio::test]
+async fn heartbeat_pings_fail_url_with_failure_count() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/ping/oracle/fail"))
+        .and(query_param("failures", "3"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let heartbeat = Heartbeat::new(format!("{}/ping/oracle", server.uri())).unwrap();
+    heartbeat.ping_failure(Some(3)).await;
+
+    let status = heartbeat.last_status().unwrap();
+    assert!(!status.run_succeeded);
+    assert!(status.delivered);
+}
+
+#[tokio::test]
+async fn heartbeat_ping_errors_are_recorded_not_raised() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .respond_with(ResponseTemplate::new(503))
+        .mount(&server)
+        .await;
+
+    let heartbeat = Heartbeat::new(server.uri()).unwrap();
+    heartbeat.ping_success().await;
+
+    let status = heartbeat.last_status().unwrap();
+    assert!(!status.delivered);
+    assert!(status.error.is_some());
+}
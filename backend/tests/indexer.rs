@@ -0,0 +1,289 @@
+use serde_json::{Value, json};
+use std::sync::Arc;
+use wiremock::matchers::{method, path, path_regex, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::blockchain::CardanoClient;
+use shipping_oracle::config::Config;
+use shipping_oracle::indexer::{ChainIndexer, IncrementalIndexer, IndexedUTxO, IndexerKind, KupoIndexer, ScanMode};
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::state::MemoryStore;
+use shipping_oracle::testing::{
+    ORACLE_ADDRESS, OUTBOX_ADDRESS, blockfrost_utxos, shippo_track, test_config, tracking_datum_cbor, tracking_number,
+};
+
+const DATUM_HASH: &str = "923918e403bf43c34b4ef6b48eb2ee04babed17320d8d1b9ff9ad086e86f44ec";
+
+/// Kupo `/matches` entry for output `index` of a transaction, with a datum of `datum_type`
+fn kupo_match(index: usize, datum_type: Option<&str>) -> Value {
+    json!({
+        "transaction_index": 0,
+        "transaction_id": format!("{:064x}", index),
+        "output_index": 0,
+        "address": ORACLE_ADDRESS,
+        "value": { "coins": 2000000, "assets": {} },
+        "datum_hash": datum_type.map(|_| format!("{:064x}", index)),
+        "datum_type": datum_type,
+        "script_hash": null,
+        "created_at": { "slot_no": 51000000 + index, "header_hash": format!("{:064x}", index) },
+        "spent_at": null,
+    })
+}
+
+/// A Kupo instance indexing `matches` at the oracle address, knowing the datum of each inline one
+async fn serve_kupo(server: &MockServer, matches: Vec<Value>) {
+    for (index, output) in matches.iter().enumerate() {
+        if output["datum_type"] == "inline" {
+            Mock::given(method("GET"))
+                .and(path(format!("/datums/{}", output["datum_hash"].as_str().unwrap())))
+                .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                    "datum": tracking_datum_cbor("usps", &tracking_number(index)),
+                })))
+                .mount(server)
+                .await;
+        }
+    }
+    Mock::given(method("GET"))
+        .and(path(format!("/matches/{}", ORACLE_ADDRESS)))
+        .and(query_param("unspent", ""))
+        .respond_with(ResponseTemplate::new(200).set_body_json(Value::Array(matches)))
+        .mount(server)
+        .await;
+}
+
+fn kupo_config(server: &MockServer) -> Config {
+    Config {
+        chain_indexer: IndexerKind::Kupo,
+        kupo_url: Some(server.uri()),
+        ..test_config(&server.uri())
+    }
+}
+
+#[test]
+fn indexer_kinds_parse_case_insensitively() {
+    assert_eq!("kupo".parse::<IndexerKind>().unwrap(), IndexerKind::Kupo);
+    assert_eq!(" Blockfrost ".parse::<IndexerKind>().unwrap(), IndexerKind::Blockfrost);
+    assert_eq!(
+        "ogmios".parse::<IndexerKind>().unwrap_err().to_string(),
+        "expected blockfrost or kupo, got 'ogmios'"
+    );
+    assert_eq!(IndexerKind::default(), IndexerKind::Blockfrost);
+}
+
+#[tokio::test]
+async fn kupo_lists_unspent_matches_with_their_inline_datums() {
+    let server = MockServer::start().await;
+    serve_kupo(&server, vec![kupo_match(0, Some("inline")), kupo_match(1, Some("hash")), kupo_match(2, None)]).await;
+
+    let indexer = KupoIndexer::new(server.uri(), reqwest::Client::new());
+    let listed = indexer.unspent_at(ORACLE_ADDRESS).await.unwrap();
+
+    assert!(!listed.truncated);
+    assert_eq!(
+        listed.utxos[0],
+        IndexedUTxO {
+            tx_hash: format!("{:064x}", 0),
+            output_index: 0,
+            inline_datum: Some(tracking_datum_cbor("usps", &tracking_number(0))),
+        }
+    );
+    // Only inline datums are fetched
+    assert_eq!((listed.utxos[1].inline_datum.as_ref(), listed.utxos[2].inline_datum.as_ref()), (None, None));
+    let datum_requests = server.received_requests().await.unwrap();
+    assert_eq!(datum_requests.iter().filter(|request| request.url.path().starts_with("/datums/")).count(), 1);
+}
+
+#[tokio::test]
+async fn unknown_kupo_datums_are_listed_without_one() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/datums/{}", DATUM_HASH)))
+        .respond_with(ResponseTemplate::new(200).set_body_string("null"))
+        .mount(&server)
+        .await;
+    let mut output = kupo_match(0, Some("inline"));
+    output["datum_hash"] = DATUM_HASH.into();
+    Mock::given(method("GET"))
+        .and(path(format!("/matches/{}", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([output])))
+        .mount(&server)
+        .await;
+
+    let listed = KupoIndexer::new(server.uri(), reqwest::Client::new()).unspent_at(ORACLE_ADDRESS).await.unwrap();
+    assert_eq!(listed.utxos.len(), 1);
+    assert_eq!(listed.utxos[0].inline_datum, None);
+}
+
+#[tokio::test]
+async fn kupo_errors_fail_the_scan() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/matches/"))
+        .respond_with(ResponseTemplate::new(400).set_body_string(r#"{"hint":"Invalid pattern!"}"#))
+        .mount(&server)
+        .await;
+
+    let err = CardanoClient::new(kupo_config(&server)).unwrap().scan_shipments().await.unwrap_err();
+    assert!(err.to_string().contains("Kupo query failed (status 400 Bad Request)"), "{}", err);
+    assert!(err.to_string().contains("Invalid pattern!"), "{}", err);
+}
+
+#[tokio::test]
+async fn runs_discover_shipments_through_kupo() {
+    let server = MockServer::start().await;
+    serve_kupo(&server, vec![kupo_match(0, Some("inline")), kupo_match(1, Some("inline"))]).await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", "TRANSIT")))
+        .mount(&server)
+        .await;
+
+    let oracle = Oracle::builder()
+        .config(kupo_config(&server))
+        .state(Arc::new(MemoryStore::new()))
+        .build()
+        .unwrap();
+    let stats = oracle.run_once().await.unwrap().stats;
+
+    assert_eq!((stats.shipments, stats.undecodable, stats.failed), (2, 0, 0));
+    let requests = server.received_requests().await.unwrap();
+    assert!(requests.iter().all(|request| !request.url.path().starts_with("/addresses/")));
+    assert_eq!(requests.iter().filter(|request| request.url.path().starts_with("/tracks/")).count(), 2);
+}
+
+fn tx_hash(index: usize) -> String {
+    format!("{:064x}", index)
+}
+
+/// Blockfrost `/txs/{hash}/utxos` entry at `address`
+fn tx_io(address: &str, tx_hash: &str, output_index: u32, reference: bool) -> Value {
+    json!({
+        "address": address,
+        "amount": [{ "unit": "lovelace", "quantity": "2000000" }],
+        "tx_hash": tx_hash,
+        "output_index": output_index,
+        "inline_datum": null,
+        "collateral": false,
+        "reference": reference,
+    })
+}
+
+/// Blockfrost at chain tip `tip`, listing `txs` (hash and block height) from block `from` onwards
+async fn serve_blockfrost_chain(server: &MockServer, tip: u64, from: u64, txs: &[(String, u64)]) {
+    server.reset().await;
+    Mock::given(method("GET"))
+        .and(path("/blocks/latest"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "height": tip })))
+        .mount(server)
+        .await;
+    let listed: Vec<Value> = txs
+        .iter()
+        .map(|(tx_hash, height)| json!({ "tx_hash": tx_hash, "tx_index": 0, "block_height": height, "block_time": 1771090081 }))
+        .collect();
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/transactions", ORACLE_ADDRESS)))
+        .and(query_param("order", "asc"))
+        .and(query_param("from", from.to_string()))
+        .respond_with(ResponseTemplate::new(200).set_body_json(listed))
+        .mount(server)
+        .await;
+}
+
+/// Serve the inputs and outputs of `tx_hash`, expecting exactly one lookup
+async fn serve_tx(server: &MockServer, tx_hash: &str, inputs: Vec<Value>, outputs: Vec<Value>) {
+    Mock::given(method("GET"))
+        .and(path(format!("/txs/{}/utxos", tx_hash)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "hash": tx_hash,
+            "inputs": inputs,
+            "outputs": outputs,
+        })))
+        .expect(1)
+        .mount(server)
+        .await;
+}
+
+/// The incremental indexer's first scan: one tracking UTxO listed at chain tip 1000
+async fn bootstrapped_indexer(server: &MockServer) -> IncrementalIndexer {
+    serve_blockfrost_chain(server, 1000, 0, &[]).await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(1)))
+        .mount(server)
+        .await;
+
+    let indexer = IncrementalIndexer::new(test_config(&server.uri()), reqwest::Client::new(), Arc::new(MemoryStore::new()));
+    let listed = indexer.unspent_at(ORACLE_ADDRESS).await.unwrap();
+    assert_eq!(utxo_refs(&listed.utxos), vec![format!("{}#0", tx_hash(0))]);
+
+    indexer
+}
+
+fn utxo_refs(utxos: &[IndexedUTxO]) -> Vec<String> {
+    utxos.iter().map(|utxo| format!("{}#{}", utxo.tx_hash, utxo.output_index)).collect()
+}
+
+#[test]
+fn scan_modes_parse_case_insensitively() {
+    assert_eq!(" Incremental ".parse::<ScanMode>().unwrap(), ScanMode::Incremental);
+    assert_eq!("full".parse::<ScanMode>().unwrap(), ScanMode::Full);
+    assert_eq!("delta".parse::<ScanMode>().unwrap_err().to_string(), "expected full or incremental, got 'delta'");
+}
+
+#[tokio::test]
+async fn incremental_scan_reads_only_new_transactions() {
+    let server = MockServer::start().await;
+    let indexer = bootstrapped_indexer(&server).await;
+
+    // One transaction landed since; the scan starts ROLLBACK_DEPTH (20) blocks below the last tip
+    let mut output = tx_io(ORACLE_ADDRESS, &tx_hash(1), 0, false);
+    output["inline_datum"] = json!(tracking_datum_cbor("usps", &tracking_number(1)));
+    serve_blockfrost_chain(&server, 1010, 981, &[(tx_hash(1), 1005)]).await;
+    serve_tx(&server, &tx_hash(1), vec![], vec![output, tx_io(OUTBOX_ADDRESS, &tx_hash(1), 1, false)]).await;
+
+    let listed = indexer.unspent_at(ORACLE_ADDRESS).await.unwrap();
+
+    assert!(!listed.truncated);
+    assert_eq!(utxo_refs(&listed.utxos), vec![format!("{}#0", tx_hash(0)), format!("{}#0", tx_hash(1))]);
+    assert_eq!(listed.utxos[1].inline_datum, Some(tracking_datum_cbor("usps", &tracking_number(1))));
+    // Requests since the chain was last served: no address listing, one transaction lookup
+    let requests = server.received_requests().await.unwrap();
+    let listing = format!("/addresses/{}/utxos", ORACLE_ADDRESS);
+    assert!(requests.iter().all(|request| request.url.path() != listing));
+    assert_eq!(requests.iter().filter(|request| request.url.path().starts_with("/txs/")).count(), 1);
+}
+
+#[tokio::test]
+async fn incremental_scan_drops_spent_outputs_and_undoes_rollbacks() {
+    let server = MockServer::start().await;
+    let indexer = bootstrapped_indexer(&server).await;
+
+    // Transaction 1 creates a tracking UTxO, transaction 2 spends the first one
+    // and only references the new one
+    serve_blockfrost_chain(&server, 1010, 981, &[(tx_hash(1), 1005), (tx_hash(2), 1008)]).await;
+    serve_tx(&server, &tx_hash(1), vec![], vec![tx_io(ORACLE_ADDRESS, &tx_hash(1), 0, false)]).await;
+    serve_tx(
+        &server,
+        &tx_hash(2),
+        vec![tx_io(ORACLE_ADDRESS, &tx_hash(0), 0, false), tx_io(ORACLE_ADDRESS, &tx_hash(1), 0, true)],
+        vec![tx_io(OUTBOX_ADDRESS, &tx_hash(2), 0, false)],
+    )
+    .await;
+
+    let listed = indexer.unspent_at(ORACLE_ADDRESS).await.unwrap();
+    assert_eq!(utxo_refs(&listed.utxos), vec![format!("{}#0", tx_hash(1))]);
+
+    // Both were rolled back: the blocks since 991 are read again and hold neither
+    serve_blockfrost_chain(&server, 1012, 991, &[]).await;
+
+    let listed = indexer.unspent_at(ORACLE_ADDRESS).await.unwrap();
+    assert_eq!(utxo_refs(&listed.utxos), vec![format!("{}#0", tx_hash(0))]);
+}
+
+#[tokio::test]
+async fn incremental_scan_needs_a_state_database() {
+    let config = Config { scan_mode: ScanMode::Incremental, ..test_config("http://localhost") };
+
+    let err = Oracle::from_config(config).err().expect("no state database");
+    assert_eq!(err.to_string(), "SCAN_MODE=incremental needs a state database");
+}
@@ -1,14 +1,20 @@
 use anyhow::{Result, anyhow};
+use chrono::Utc;
 use pallas::ledger::addresses::Address;
-use serde::Serialize;
 use std::fs;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use shipping_oracle::blockchain::CardanoClient;
 use shipping_oracle::config::Config;
-use shipping_oracle::models::{TrackingDatum, TrackingUTxO};
-use shipping_oracle::shipment::{ShipmentClient, get_status};
-use shipping_oracle::submitter::TxSubmitter;
+use shipping_oracle::models::{CarrierStatus, DerivedStatus, TrackingDatum, TrackingNumber, TrackingUTxO, UtxoRef};
+use shipping_oracle::reporting::{CaseReport, Report, ReportRenderer};
+use shipping_oracle::run_report::{ShipmentAction, ShipmentReport};
+use shipping_oracle::self_test;
+use shipping_oracle::shipment::ShipmentClient;
+use shipping_oracle::status_mapping::StatusMapping;
+use shipping_oracle::submitter::{SubmitError, TxSubmitter};
+use shipping_oracle::testing;
 
 const OUTBOX_ADDRESS: &str = "addr_test1qqcytargera54zzzgk9ajg2y2xlhrx4efgvjfe970vr57cxkxjyj4nx7n47t6s9saftdn3dypt4573lawvqutsh2ydrs3hxqj3";
 const ORACLE_ADDRESS: &str = "addr_test1vqpp4rqsgkhyaz5ejjtwzane9wnkggfrn9pptgmtwq7fqws6t8yck";
@@ -49,53 +55,12 @@ impl MockSubmitter {
 
 #[async_trait::async_trait]
 impl TxSubmitter for MockSubmitter {
-    async fn submit(&self, signed_tx: Vec<u8>) -> Result<String> {
-        self.calls.lock().map_err(|_| anyhow!("submit lock poisoned"))?.push(signed_tx);
+    async fn submit(&self, signed_tx: Vec<u8>) -> Result<String, SubmitError> {
+        self.calls.lock().map_err(|_| SubmitError::Other("submit lock poisoned".to_string()))?.push(signed_tx);
         Ok(self.expected_hash.clone())
     }
 }
 
-#[derive(Serialize, Clone)]
-struct CaseReport {
-    name: String,
-    tracking_utxo: String,
-    tracking_tx_hash: String,
-    tracking_tx_index: u32,
-    tracking_outbox: String,
-    carrier: String,
-    tracking_number: String,
-    expected_status: String,
-    actual_status: Option<String>,
-    status_details: Option<String>,
-    derived_status: Option<String>,
-    expected_timestamp: Option<u64>,
-    expected_tx_hash: Option<String>,
-    actual_tx_hash: Option<String>,
-    expected_outbox: Option<String>,
-    actual_outbox: Option<String>,
-    expected_p_status: Option<String>,
-    actual_p_status: Option<String>,
-    expected_p_utxo_ref: Option<String>,
-    actual_p_utxo_ref: Option<String>,
-    expected_oracle: Option<String>,
-    actual_oracle: Option<String>,
-    expected_oracle_pkh: Option<String>,
-    actual_oracle_pkh: Option<String>,
-    expected_payment: Option<String>,
-    actual_payment: Option<String>,
-    expected_validator_script_ref: Option<String>,
-    actual_validator_script_ref: Option<String>,
-    passed: bool,
-    errors: Vec<String>,
-}
-
-#[derive(Serialize)]
-struct Report {
-    cases: Vec<CaseReport>,
-    passed: usize,
-    failed: usize,
-}
-
 #[tokio::test]
 async fn integration_tracking_to_shipment() -> Result<()> {
     dotenvy::dotenv().ok();
@@ -103,6 +68,16 @@ async fn integration_tracking_to_shipment() -> Result<()> {
     let config = Config::from_env()?;
     let shipment_client = ShipmentClient::new(config.clone())?;
 
+    let live_utxos: Vec<String> = CardanoClient::new(config.clone())?
+        .fetch_shipments()
+        .await?
+        .iter()
+        .map(|utxo| utxo.utxo_ref.to_string())
+        .collect();
+
+    let delivered = resolve_tracking_utxo(&config, &live_utxos, DELIVERED_UTXO, DELIVERED_TRACKING).await?;
+    let failure = resolve_tracking_utxo(&config, &live_utxos, FAILURE_UTXO, FAILURE_TRACKING).await?;
+
     let mut cases = Vec::new();
 
     cases.push(run_transit_case(&shipment_client).await?);
@@ -110,7 +85,7 @@ async fn integration_tracking_to_shipment() -> Result<()> {
         &config,
         &shipment_client,
         "delivered",
-        DELIVERED_UTXO,
+        &delivered,
         DELIVERED_TRACKING,
         "DELIVERED",
         "DELIVERED",
@@ -122,7 +97,7 @@ async fn integration_tracking_to_shipment() -> Result<()> {
         &config,
         &shipment_client,
         "failure",
-        FAILURE_UTXO,
+        &failure,
         FAILURE_TRACKING,
         "FAILURE",
         "NOT_DELIVERED",
@@ -131,7 +106,10 @@ async fn integration_tracking_to_shipment() -> Result<()> {
         FAILURE_HASH,
     ).await?);
 
-    write_reports(&cases)?;
+    write_reports(&cases, &config)?;
+
+    teardown(&config, &delivered, DELIVERED_TRACKING, DerivedStatus::Delivered).await;
+    teardown(&config, &failure, FAILURE_TRACKING, DerivedStatus::NotDelivered).await;
 
     let failed = cases.iter().filter(|case| !case.passed).count();
     if failed > 0 {
@@ -141,24 +119,85 @@ async fn integration_tracking_to_shipment() -> Result<()> {
     Ok(())
 }
 
+/// A tracking UTxO used by a close case, either the hardcoded one or a
+/// freshly provisioned replacement
+struct ResolvedUtxo {
+    utxo_ref: String,
+    provisioned: bool,
+}
+
+async fn resolve_tracking_utxo(
+    config: &Config,
+    live_utxos: &[String],
+    hardcoded: &str,
+    tracking_number: &str,
+) -> Result<ResolvedUtxo> {
+    if live_utxos.iter().any(|utxo| utxo == hardcoded) {
+        return Ok(ResolvedUtxo { utxo_ref: hardcoded.to_string(), provisioned: false });
+    }
+
+    if std::env::var("TEST_FUNDING_SK").is_err() {
+        println!("⚠️  {} is no longer on-chain and TEST_FUNDING_SK is not set", hardcoded);
+        return Ok(ResolvedUtxo { utxo_ref: hardcoded.to_string(), provisioned: false });
+    }
+
+    let datum = tracking_datum(tracking_number)?;
+    let utxo_ref = self_test::provision_tracking_utxo(config, &datum).await?;
+    println!("🆕 Provisioned {} for {} (replacing {})", utxo_ref, tracking_number, hardcoded);
+
+    Ok(ResolvedUtxo { utxo_ref, provisioned: true })
+}
+
+async fn teardown(config: &Config, resolved: &ResolvedUtxo, tracking_number: &str, status: DerivedStatus) {
+    if !resolved.provisioned {
+        return;
+    }
+
+    let Ok(utxo_ref) = resolved.utxo_ref.parse::<UtxoRef>() else {
+        return;
+    };
+    let Ok(datum) = tracking_datum(tracking_number) else {
+        return;
+    };
+
+    let tracking = TrackingUTxO { utxo_ref, datum };
+    match testing::close_provisioned(config, &tracking, status).await {
+        Ok(tx_hash) => println!("🧹 Closed provisioned UTxO {} in {}", tracking.utxo_ref, tx_hash),
+        Err(e) => println!("⚠️  Failed to close provisioned UTxO {}: {}", tracking.utxo_ref, e),
+    }
+}
+
+fn tracking_datum(tracking_number: &str) -> Result<TrackingDatum> {
+    let outbox_address = Address::from_bech32(OUTBOX_ADDRESS)
+        .map_err(|err| anyhow!("invalid outbox address: {}", err))?;
+
+    Ok(TrackingDatum {
+        carrier: SHIPPO_CARRIER.to_string(),
+        tracking_number: TrackingNumber::Plain(tracking_number.to_string()),
+        outbox_address,
+        deadline: None,
+    })
+}
+
 async fn run_transit_case(shipment_client: &ShipmentClient) -> Result<CaseReport> {
     let mut errors = Vec::new();
+    let (started_at, started) = (Utc::now(), Instant::now());
     let status = shipment_client
         .fetch_shipment_status(SHIPPO_CARRIER, TRANSIT_TRACKING)
         .await;
 
     let (actual_status, status_details, derived_status) = match status {
         Ok(status) => {
-            if status.status != "TRANSIT" {
+            if status.status != CarrierStatus::Transit {
                 errors.push(format!("expected status TRANSIT, got {}", status.status));
             }
 
-            let derived = get_status(&status);
+            let derived = StatusMapping::default().map(&status);
             if derived.is_some() {
                 errors.push("expected non-final status to be skipped".to_string());
             }
 
-            (Some(status.status), Some(status.status_details), derived)
+            (Some(status.status.to_string()), Some(status.status_details), derived)
         }
         Err(err) => {
             errors.push(format!("failed to fetch status: {}", err));
@@ -166,23 +205,34 @@ async fn run_transit_case(shipment_client: &ShipmentClient) -> Result<CaseReport
         }
     };
 
+    let action = match &actual_status {
+        Some(_) => ShipmentAction::Skipped { reason: "status is not final".to_string() },
+        None => ShipmentAction::Failed { error: errors.join("; ") },
+    };
     let (tracking_tx_hash, tracking_tx_index) = split_utxo(TRANSIT_UTXO)?;
 
     Ok(CaseReport {
         name: "transit_skip".to_string(),
-        tracking_utxo: TRANSIT_UTXO.to_string(),
+        shipment: ShipmentReport {
+            utxo_ref: TRANSIT_UTXO.to_string(),
+            carrier: SHIPPO_CARRIER.to_string(),
+            tracking_number: TRANSIT_TRACKING.to_string(),
+            outbox_address: OUTBOX_ADDRESS.to_string(),
+            fetched_status: actual_status,
+            status_details,
+            derived_status: derived_status.map(|status| status.to_string()),
+            close_reason: None,
+            action,
+            started_at,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        },
+        provisioned: false,
         tracking_tx_hash,
         tracking_tx_index,
         tracking_outbox: OUTBOX_ADDRESS.to_string(),
-        carrier: SHIPPO_CARRIER.to_string(),
-        tracking_number: TRANSIT_TRACKING.to_string(),
         expected_status: "TRANSIT".to_string(),
-        actual_status,
-        status_details,
-        derived_status,
         expected_timestamp: None,
         expected_tx_hash: None,
-        actual_tx_hash: None,
         expected_outbox: None,
         actual_outbox: None,
         expected_p_status: None,
@@ -197,6 +247,7 @@ async fn run_transit_case(shipment_client: &ShipmentClient) -> Result<CaseReport
         actual_payment: None,
         expected_validator_script_ref: None,
         actual_validator_script_ref: None,
+        signer_pkh: None,
         passed: errors.is_empty(),
         errors,
     })
@@ -206,7 +257,7 @@ async fn run_close_case(
     config: &Config,
     shipment_client: &ShipmentClient,
     name: &str,
-    utxo_ref: &str,
+    resolved: &ResolvedUtxo,
     tracking_number: &str,
     expected_status: &str,
     expected_derived_status: &str,
@@ -215,6 +266,11 @@ async fn run_close_case(
     expected_hash: &str,
 ) -> Result<CaseReport> {
     let mut errors = Vec::new();
+    let (started_at, started) = (Utc::now(), Instant::now());
+    let utxo_ref = resolved.utxo_ref.as_str();
+
+    // A provisioned UTxO yields a different close transaction than the pinned one
+    let expected_hash = (!resolved.provisioned).then_some(expected_hash);
 
     let status = shipment_client
         .fetch_shipment_status(SHIPPO_CARRIER, tracking_number)
@@ -222,12 +278,12 @@ async fn run_close_case(
 
     let (actual_status, status_details, derived_status) = match status {
         Ok(status) => {
-            if status.status != expected_status {
+            if status.status.as_str() != expected_status {
                 errors.push(format!("expected status {}, got {}", expected_status, status.status));
             }
-            let derived = get_status(&status);
-            if let Some(ref derived_status) = derived {
-                if derived_status != expected_derived_status {
+            let derived = StatusMapping::default().map(&status);
+            if let Some(derived_status) = derived {
+                if derived_status.as_str() != expected_derived_status {
                     errors.push(format!(
                         "expected derived status {}, got {}",
                         expected_derived_status,
@@ -235,7 +291,7 @@ async fn run_close_case(
                     ));
                 }
             }
-            (Some(status.status), Some(status.status_details), derived)
+            (Some(status.status.to_string()), Some(status.status_details), derived)
         }
         Err(err) => {
             errors.push(format!("failed to fetch status: {}", err));
@@ -243,50 +299,42 @@ async fn run_close_case(
         }
     };
 
-    let (tx_hash, params, envelope_hash, submit_calls) = if errors.is_empty() {
-        let outbox_address = Address::from_bech32(OUTBOX_ADDRESS)
-            .map_err(|err| anyhow!("invalid outbox address: {}", err))?;
-        let (tx_hash, tx_index) = split_utxo(utxo_ref)?;
+    let (submitted, params, envelope_hash, submit_calls, signer_pkh) = if errors.is_empty() {
         let tracking = TrackingUTxO {
-            tx_hash,
-            tx_index,
-            datum: TrackingDatum {
-                carrier: SHIPPO_CARRIER.to_string(),
-                tracking_number: tracking_number.to_string(),
-                outbox_address,
-            },
+            utxo_ref: utxo_ref.parse()?,
+            datum: tracking_datum(tracking_number)?,
         };
 
-        let calls = Arc::new(Mutex::new(Vec::new()));
-        let submitter = MockSubmitter::new(expected_hash, calls.clone());
-        let client = CardanoClient::with_submitter(config.clone(), Box::new(submitter))?;
-
-        let derived_status_value = derived_status.clone().unwrap_or_default();
-        if derived_status_value.is_empty() {
-            errors.push("expected a final status to submit".to_string());
-            (None, None, None, 0)
-        } else {
-            let (params, envelope) = client
-                .prepare_close_shipment_at(&tracking, &derived_status_value, timestamp)
+        if let Some(derived_status) = derived_status {
+            let (params, envelope) = CardanoClient::new(config.clone())?
+                .prepare_close_shipment_at(&tracking, derived_status, timestamp)
                 .await?;
 
+            let calls = Arc::new(Mutex::new(Vec::new()));
+            let submitter = MockSubmitter::new(expected_hash.unwrap_or(&envelope.hash), calls.clone());
+            let client = CardanoClient::with_submitter(config.clone(), Box::new(submitter))?;
+
             let submit_result = client
-                .submit_shipment_at(&tracking, &derived_status_value, timestamp)
+                .submit_shipment_at(&tracking, derived_status, timestamp)
                 .await;
 
             let submit_calls = calls.lock().map_err(|_| anyhow!("submit lock poisoned"))?.len();
-            let tx_hash = submit_result.ok();
+            let submitted = submit_result.map_err(|err| format!("{:#}", err));
 
-            (tx_hash, Some(params), Some(envelope.hash), submit_calls)
+            (submitted, Some(params), Some(envelope.hash), submit_calls, Some(client.signer_pkh()))
+        } else {
+            errors.push("expected a final status to submit".to_string());
+            (Err(errors.join("; ")), None, None, 0, None)
         }
     } else {
-        (None, None, None, 0)
+        (Err(errors.join("; ")), None, None, 0, None)
     };
+    let tx_hash = submitted.as_ref().ok().cloned();
 
-    if let Some(ref envelope_hash) = envelope_hash {
-        if envelope_hash != expected_hash {
-            errors.push(format!("expected envelope hash {}, got {}", expected_hash, envelope_hash));
-        }
+    if let (Some(envelope_hash), Some(expected_hash)) = (&envelope_hash, expected_hash)
+        && envelope_hash != expected_hash
+    {
+        errors.push(format!("expected envelope hash {}, got {}", expected_hash, envelope_hash));
     }
 
     if submit_calls != 1 {
@@ -294,7 +342,9 @@ async fn run_close_case(
     }
 
     if let Some(ref tx_hash) = tx_hash {
-        if tx_hash != expected_hash {
+        if let Some(expected_hash) = expected_hash.or(envelope_hash.as_deref())
+            && tx_hash != expected_hash
+        {
             errors.push(format!("expected tx hash {}, got {}", expected_hash, tx_hash));
         }
     } else {
@@ -351,23 +401,34 @@ async fn run_close_case(
         (None, None, None, None, None, None, None)
     };
 
+    let action = match submitted {
+        Ok(tx_hash) => ShipmentAction::Submitted { tx_hash },
+        Err(error) => ShipmentAction::Failed { error },
+    };
     let (tracking_tx_hash, tracking_tx_index) = split_utxo(utxo_ref)?;
 
     Ok(CaseReport {
         name: name.to_string(),
-        tracking_utxo: utxo_ref.to_string(),
+        shipment: ShipmentReport {
+            utxo_ref: utxo_ref.to_string(),
+            carrier: SHIPPO_CARRIER.to_string(),
+            tracking_number: tracking_number.to_string(),
+            outbox_address: OUTBOX_ADDRESS.to_string(),
+            fetched_status: actual_status,
+            status_details,
+            derived_status: derived_status.map(|status| status.to_string()),
+            close_reason: None,
+            action,
+            started_at,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+        },
+        provisioned: resolved.provisioned,
         tracking_tx_hash,
         tracking_tx_index,
         tracking_outbox: OUTBOX_ADDRESS.to_string(),
-        carrier: SHIPPO_CARRIER.to_string(),
-        tracking_number: tracking_number.to_string(),
         expected_status: expected_status.to_string(),
-        actual_status,
-        status_details,
-        derived_status,
         expected_timestamp: Some(timestamp),
-        expected_tx_hash: Some(expected_hash.to_string()),
-        actual_tx_hash: tx_hash,
+        expected_tx_hash: expected_hash.map(str::to_string).or(envelope_hash),
         expected_outbox: Some(OUTBOX_ADDRESS.to_string()),
         actual_outbox,
         expected_p_status: Some(expected_p_status.to_string()),
@@ -382,125 +443,37 @@ async fn run_close_case(
         actual_payment,
         expected_validator_script_ref: Some(VALIDATOR_SCRIPT_REF.to_string()),
         actual_validator_script_ref,
+        signer_pkh,
         passed: errors.is_empty(),
         errors,
     })
 }
 
 fn split_utxo(utxo_ref: &str) -> Result<(String, u32)> {
-    let mut parts = utxo_ref.split('#');
-    let tx_hash = parts.next().ok_or_else(|| anyhow!("missing tx hash"))?;
-    let index = parts.next().ok_or_else(|| anyhow!("missing tx index"))?;
-    if parts.next().is_some() {
-        return Err(anyhow!("invalid utxo ref"));
-    }
-
-    let tx_index = index.parse::<u32>()
-        .map_err(|_| anyhow!("invalid tx index"))?;
-    Ok((tx_hash.to_string(), tx_index))
+    let utxo_ref: UtxoRef = utxo_ref.parse()?;
+    Ok((utxo_ref.tx_hash_hex(), utxo_ref.index))
 }
 
 fn is_numeric(value: &str) -> bool {
     !value.is_empty() && value.chars().all(|ch| ch.is_ascii_digit())
 }
 
-fn write_reports(cases: &[CaseReport]) -> Result<()> {
+fn write_reports(cases: &[CaseReport], config: &Config) -> Result<()> {
     let reports_dir = std::path::Path::new("reports");
     fs::create_dir_all(reports_dir)?;
 
-    let passed = cases.iter().filter(|case| case.passed).count();
-    let failed = cases.len() - passed;
-    let report = Report {
-        cases: cases.to_vec(),
-        passed,
-        failed,
-    };
+    let report = Report::new(cases.to_vec());
+    let renderer = ReportRenderer::new(config.cardano_network);
 
     let json_path = reports_dir.join("integration.json");
     let json = serde_json::to_string_pretty(&report)?;
     fs::write(&json_path, json)?;
 
     let md_path = reports_dir.join("integration.md");
-    let md = render_markdown(&report);
-    fs::write(&md_path, md)?;
+    fs::write(&md_path, renderer.markdown(&report))?;
 
-    Ok(())
-}
+    let html_path = reports_dir.join("integration.html");
+    fs::write(&html_path, renderer.html(&report))?;
 
-fn render_markdown(report: &Report) -> String {
-    let mut out = String::new();
-    out.push_str("# Integration Test Report\n\n");
-    out.push_str(&format!("- Passed: {}\n", report.passed));
-    out.push_str(&format!("- Failed: {}\n\n", report.failed));
-
-    for case in &report.cases {
-        let title = match case.name.as_str() {
-            "transit_skip" => "(transit_skip) No transition test",
-            "delivered" => "(delivered) Delivered transition test",
-            "failure" => "(failure) Not delivered transition test",
-            _ => case.name.as_str(),
-        };
-
-        out.push_str(&format!("## {}\n", title));
-        out.push_str("### Tracking UTxO\n");
-        let tracking_json = serde_json::json!({
-            "tx_hash": case.tracking_tx_hash,
-            "tx_index": case.tracking_tx_index,
-            "datum": {
-                "carrier": case.carrier,
-                "tracking_number": case.tracking_number,
-                "outbox_address": case.tracking_outbox,
-            }
-        });
-        out.push_str("```\n");
-        out.push_str(&serde_json::to_string_pretty(&tracking_json).unwrap_or_else(|_| "{}".to_string()));
-        out.push_str("\n```\n");
-
-        out.push_str("### Shipment\n");
-        out.push_str(&format!("- Carrier: {}\n", case.carrier));
-        out.push_str(&format!("- Tracking: {}\n", case.tracking_number));
-        if let Some(ref actual_status) = case.actual_status {
-            out.push_str(&format!("- Status: {}\n", actual_status));
-        } else {
-            out.push_str(&format!("- Status: {}\n", case.expected_status));
-        }
-        if let Some(ref status_details) = case.status_details {
-            out.push_str(&format!("- Details: {}\n", status_details));
-        }
-
-        out.push_str("### Transition\n");
-        let transition_to = match case.derived_status.as_deref() {
-            Some(status) => status,
-            None => "NO TRANSITION",
-        };
-        out.push_str("```\n");
-        out.push_str(&format!("{} -> {}\n", case.expected_status, transition_to));
-        out.push_str("```\n");
-
-        if let Some(ref tx_hash) = case.expected_tx_hash {
-            out.push_str("### Shipment UTxO\n");
-            let shipment_json = serde_json::json!({
-                "tx_hash": tx_hash,
-                "tx_index": 0,
-                "datum": {
-                    "carrier": case.carrier,
-                    "tracking_number": case.tracking_number,
-                    "status": case.derived_status.clone().unwrap_or_else(|| "UNKNOWN".to_string()),
-                    "timestamp": case.expected_timestamp,
-                    "oracle_pkh": case.expected_oracle_pkh,
-                }
-            });
-            out.push_str("```\n");
-            out.push_str(&serde_json::to_string_pretty(&shipment_json).unwrap_or_else(|_| "{}".to_string()));
-            out.push_str("\n```\n");
-        }
-
-        out.push_str(&format!("### Result -> `{}`\n", if case.passed { "PASS" } else { "FAIL" }));
-        if !case.errors.is_empty() {
-            out.push_str(&format!("- Errors: {}\n", case.errors.join("; ")));
-        }
-        out.push('\n');
-    }
-
-    out
+    Ok(())
 }
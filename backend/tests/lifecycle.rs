@@ -0,0 +1,268 @@
+use serde_json::json;
+use std::sync::Arc;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::blockchain::CardanoClient;
+use shipping_oracle::config::Config;
+use shipping_oracle::fetcher::DataFetcher;
+use shipping_oracle::lifecycle::{LifecycleTransition, ShipmentLifecycle, check_transition};
+use shipping_oracle::shipment::ShipmentClient;
+use shipping_oracle::state::{MemoryStore, NamespacedStore, SqliteStore, StateStore, Submission};
+use shipping_oracle::testing::{ORACLE_ADDRESS, blockfrost_utxos, shippo_track, test_config};
+
+const CLOSE_TX: &str = "c10fe3a1b2c3d4e5f60718293a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d";
+const BLOCK: u64 = 4_213_337;
+
+fn utxo_ref(index: usize) -> String {
+    format!("{:064x}#0", index)
+}
+
+/// Serve `shipments` tracking UTxOs reporting `status`, replacing whatever was mounted before
+async fn serve(server: &MockServer, shipments: usize, status: &str) {
+    server.reset().await;
+
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(shipments)))
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", status)))
+        .mount(server)
+        .await;
+}
+
+fn fetcher(server: &MockServer, state: Arc<dyn StateStore>) -> DataFetcher {
+    let config = test_config(&server.uri());
+    DataFetcher::new(
+        Arc::new(CardanoClient::new(config.clone()).unwrap()),
+        Arc::new(ShipmentClient::new(config).unwrap()),
+    )
+    .with_state(state)
+}
+
+async fn history(state: &dyn StateStore, utxo_ref: &str) -> Vec<ShipmentLifecycle> {
+    state
+        .lifecycle(utxo_ref)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|transition| transition.state)
+        .collect()
+}
+
+#[tokio::test]
+async fn shipment_walks_from_discovery_to_confirmation() {
+    let server = MockServer::start().await;
+    let state: Arc<dyn StateStore> = Arc::new(SqliteStore::open_in_memory().unwrap());
+    let fetcher = fetcher(&server, state.clone());
+    let shipment = utxo_ref(0);
+
+    serve(&server, 1, "TRANSIT").await;
+    fetcher.run().await.unwrap();
+    fetcher.run().await.unwrap();
+    assert_eq!(
+        history(state.as_ref(), &shipment).await,
+        vec![ShipmentLifecycle::Discovered, ShipmentLifecycle::AwaitingCarrier]
+    );
+
+    // TRP is not mocked, so the close is attempted and fails
+    serve(&server, 1, "DELIVERED").await;
+    let stats = fetcher.run().await.unwrap().stats;
+    assert_eq!(stats.failed, 1);
+    assert_eq!(history(state.as_ref(), &shipment).await.last(), Some(&ShipmentLifecycle::FinalStatusKnown));
+
+    // What a successful close journals
+    state
+        .record_submission(&Submission {
+            utxo_ref: shipment.clone(),
+            tx_hash: CLOSE_TX.to_string(),
+            status: "DELIVERED".to_string(),
+            submitted_at: 1_771_090_081,
+            signer_pkh: None,
+            fee: Some(174_257),
+            outbox_address: None,
+            carrier: Some("usps".to_string()),
+            tracking_number: Some("9400111899223456789012".to_string()),
+        })
+        .await
+        .unwrap();
+    state
+        .record_transition(&LifecycleTransition {
+            utxo_ref: shipment.clone(),
+            state: ShipmentLifecycle::Submitted { tx_hash: CLOSE_TX.to_string() },
+            at: 1_771_090_081,
+        })
+        .await
+        .unwrap();
+
+    // The tracking UTxO is gone and the close is on-chain
+    serve(&server, 0, "DELIVERED").await;
+    Mock::given(method("GET"))
+        .and(path(format!("/txs/{}", CLOSE_TX)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "hash": CLOSE_TX, "block_height": BLOCK })))
+        .mount(&server)
+        .await;
+    let stats = fetcher.run().await.unwrap().stats;
+    assert_eq!(stats.illegal_transitions, 0);
+
+    let transitions = state.lifecycle(&shipment).await.unwrap();
+    assert_eq!(
+        transitions.iter().map(|transition| transition.state.clone()).collect::<Vec<_>>(),
+        vec![
+            ShipmentLifecycle::Discovered,
+            ShipmentLifecycle::AwaitingCarrier,
+            ShipmentLifecycle::FinalStatusKnown,
+            ShipmentLifecycle::Submitted { tx_hash: CLOSE_TX.to_string() },
+            ShipmentLifecycle::Confirmed { block: BLOCK },
+        ]
+    );
+    assert!(transitions.iter().all(|transition| transition.at > 0));
+    assert_eq!(state.lifecycles().await.unwrap(), vec![transitions[4].clone()]);
+}
+
+#[tokio::test]
+async fn shipment_spent_elsewhere_expires() {
+    let server = MockServer::start().await;
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    let fetcher = fetcher(&server, state.clone());
+
+    serve(&server, 2, "TRANSIT").await;
+    fetcher.run().await.unwrap();
+
+    serve(&server, 1, "TRANSIT").await;
+    fetcher.run().await.unwrap();
+    fetcher.run().await.unwrap();
+
+    assert_eq!(
+        history(state.as_ref(), &utxo_ref(0)).await,
+        vec![ShipmentLifecycle::Discovered, ShipmentLifecycle::AwaitingCarrier]
+    );
+    assert_eq!(
+        history(state.as_ref(), &utxo_ref(1)).await,
+        vec![ShipmentLifecycle::Discovered, ShipmentLifecycle::AwaitingCarrier, ShipmentLifecycle::Expired]
+    );
+}
+
+#[tokio::test]
+async fn truncated_scan_expires_nothing() {
+    let server = MockServer::start().await;
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    let beyond_the_limit = utxo_ref(150);
+    state
+        .record_transition(&LifecycleTransition { utxo_ref: beyond_the_limit.clone(), state: ShipmentLifecycle::Discovered, at: 1 })
+        .await
+        .unwrap();
+
+    // One full page and BLOCKFROST_MAX_PAGES=1: the UTxO may sit on a page that was not read
+    serve(&server, 100, "TRANSIT").await;
+    let config = Config { blockfrost_max_pages: 1, ..test_config(&server.uri()) };
+    let fetcher = DataFetcher::new(
+        Arc::new(CardanoClient::new(config.clone()).unwrap()),
+        Arc::new(ShipmentClient::new(config).unwrap()),
+    )
+    .with_state(state.clone());
+    fetcher.run().await.unwrap();
+
+    assert_eq!(history(state.as_ref(), &beyond_the_limit).await, vec![ShipmentLifecycle::Discovered]);
+}
+
+#[tokio::test]
+async fn illegal_transitions_are_counted_not_recorded() {
+    let server = MockServer::start().await;
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    let fetcher = fetcher(&server, state.clone());
+    let shipment = utxo_ref(0);
+
+    for lifecycle in [ShipmentLifecycle::Discovered, ShipmentLifecycle::Expired] {
+        state
+            .record_transition(&LifecycleTransition { utxo_ref: shipment.clone(), state: lifecycle, at: 1 })
+            .await
+            .unwrap();
+    }
+
+    serve(&server, 1, "TRANSIT").await;
+    let stats = fetcher.run().await.unwrap().stats;
+
+    assert_eq!((stats.shipments, stats.illegal_transitions), (1, 1));
+    assert_eq!(fetcher.illegal_transitions(), 1);
+    assert_eq!(
+        history(state.as_ref(), &shipment).await,
+        vec![ShipmentLifecycle::Discovered, ShipmentLifecycle::Expired]
+    );
+}
+
+#[test]
+fn transitions_follow_the_lifecycle() {
+    use ShipmentLifecycle::*;
+
+    let submitted = Submitted { tx_hash: CLOSE_TX.to_string() };
+    let legal = [
+        (None, Discovered),
+        (Some(Discovered), AwaitingCarrier),
+        (Some(Discovered), FinalStatusKnown),
+        (Some(AwaitingCarrier), FinalStatusKnown),
+        (Some(FinalStatusKnown), submitted.clone()),
+        (Some(submitted.clone()), Submitted { tx_hash: "resubmitted".to_string() }),
+        (Some(submitted.clone()), FinalStatusKnown),
+        (Some(submitted.clone()), Confirmed { block: BLOCK }),
+        (Some(AwaitingCarrier), Expired),
+        (Some(FinalStatusKnown), DeadLettered { reason: "fee ceiling".to_string() }),
+    ];
+    for (from, to) in legal {
+        assert!(check_transition("aa#0", from.as_ref(), &to).is_ok(), "{:?} -> {:?}", from, to);
+    }
+
+    let illegal = [
+        (None, AwaitingCarrier),
+        (Some(Discovered), Discovered),
+        (Some(AwaitingCarrier), submitted.clone()),
+        (Some(submitted.clone()), Expired),
+        (Some(Confirmed { block: BLOCK }), FinalStatusKnown),
+        (Some(Expired), DeadLettered { reason: "late".to_string() }),
+    ];
+    for (from, to) in illegal {
+        assert!(check_transition("aa#0", from.as_ref(), &to).is_err(), "{:?} -> {:?}", from, to);
+    }
+
+    let error = check_transition("aa#0", Some(&Confirmed { block: BLOCK }), &Expired).unwrap_err();
+    assert_eq!(error.to_string(), format!("Illegal lifecycle transition for aa#0: confirmed ({}) -> expired", BLOCK));
+}
+
+#[tokio::test]
+async fn lifecycles_persist_per_tenant() {
+    let shared: Arc<dyn StateStore> = Arc::new(SqliteStore::open_in_memory().unwrap());
+    let preprod = NamespacedStore::new(shared.clone(), "preprod");
+    let mainnet = NamespacedStore::new(shared.clone(), "mainnet");
+
+    let states = [
+        ShipmentLifecycle::Discovered,
+        ShipmentLifecycle::FinalStatusKnown,
+        ShipmentLifecycle::DeadLettered { reason: "validator changed".to_string() },
+    ];
+    for (at, state) in states.iter().enumerate() {
+        preprod
+            .record_transition(&LifecycleTransition { utxo_ref: "aa#0".to_string(), state: state.clone(), at: at as u64 })
+            .await
+            .unwrap();
+    }
+    mainnet
+        .record_transition(&LifecycleTransition {
+            utxo_ref: "aa#0".to_string(),
+            state: ShipmentLifecycle::Discovered,
+            at: 7,
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(history(&preprod, "aa#0").await, states.to_vec());
+    assert_eq!(
+        preprod.lifecycles().await.unwrap(),
+        vec![LifecycleTransition { utxo_ref: "aa#0".to_string(), state: states[2].clone(), at: 2 }]
+    );
+    assert_eq!(history(&mainnet, "aa#0").await, vec![ShipmentLifecycle::Discovered]);
+    assert_eq!(shared.lifecycles().await.unwrap().len(), 2);
+}
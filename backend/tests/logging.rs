@@ -0,0 +1,97 @@
+use serde_json::{Value, json};
+use std::io;
+use std::sync::{Arc, Mutex};
+use tracing_subscriber::fmt::MakeWriter;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::logging::LogFormat;
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::testing::{ORACLE_ADDRESS, blockfrost_utxos, shippo_track, test_config, tracking_number};
+
+/// In-memory log sink shared with the subscriber
+#[derive(Clone, Default)]
+struct Logs(Arc<Mutex<Vec<u8>>>);
+
+impl Logs {
+    fn events(&self) -> Vec<Value> {
+        let bytes = self.0.lock().unwrap().clone();
+        String::from_utf8(bytes)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+}
+
+impl io::Write for Logs {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Logs {
+    type Writer = Logs;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[tokio::test]
+async fn shipment_events_carry_the_run_and_shipment_spans() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(1)))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(format!("/tracks/usps/{}", tracking_number(0))))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", &tracking_number(0), "TRANSIT")))
+        .mount(&server)
+        .await;
+
+    let logs = Logs::default();
+    let subscriber = tracing_subscriber::fmt()
+        .json()
+        .with_current_span(true)
+        .with_span_list(true)
+        .with_writer(logs.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let mut config = test_config(&server.uri());
+    config.tenant = Some("acme".to_string());
+    let report = Oracle::from_config(config).unwrap().run_once().await.unwrap();
+
+    let events = logs.events();
+    let status = events
+        .iter()
+        .find(|event| event["fields"]["message"] == json!("📍 Carrier status"))
+        .expect("a carrier status event");
+    assert_eq!(status["level"], json!("INFO"));
+    assert_eq!(status["fields"]["status"], json!("TRANSIT"));
+    assert_eq!(status["span"]["name"], json!("shipment"));
+    assert_eq!(status["span"]["carrier"], json!("usps"));
+    assert_eq!(status["span"]["tracking_number"], json!(tracking_number(0)));
+    assert_eq!(status["span"]["utxo_ref"], json!(format!("{:064x}#0", 0)));
+    assert_eq!(status["spans"][0]["name"], json!("run"));
+    assert_eq!(status["spans"][0]["run_id"], json!(report.run_id));
+    assert_eq!(status["spans"][0]["tenant"], json!("acme"));
+}
+
+#[test]
+fn log_formats_parse_case_insensitively() {
+    assert_eq!(" JSON ".parse::<LogFormat>().unwrap(), LogFormat::Json);
+    assert_eq!("text".parse::<LogFormat>().unwrap(), LogFormat::Text);
+    assert_eq!(
+        "logfmt".parse::<LogFormat>().unwrap_err().to_string(),
+        "expected text or json, got 'logfmt'"
+    );
+}
@@ -0,0 +1,245 @@
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+use shipping_oracle::blockchain::{CardanoClient, Raced};
+use shipping_oracle::config::Config;
+use shipping_oracle::models::{DerivedStatus, UtxoRef};
+use shipping_oracle::notifier::OracleEvent;
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::run_report::ShipmentAction;
+use shipping_oracle::state::{MemoryStore, StateStore};
+use shipping_oracle::submitter::{SubmitError, TxSubmitter};
+use shipping_oracle::testing::{ORACLE_ADDRESS, OUTBOX_ADDRESS, map_tracking_datum_cbor, test_config, tracking_datum_cbor};
+use shipping_oracle::timestamp_source::TimestampSource;
+
+/// The tracking UTxO `close_shipment_envelope_valid.json` spends, closed as delivered at `CLOSED_AT`
+const TRACKING_TX_HASH: &str = "a7a264ac1bef1f0da6312d16fb8f68b18d1a0d5010d8cb6a61790bf02a759301";
+const TRACKING_NUMBER: &str = "9400111899223456789012";
+const CLOSED_AT: u64 = 1771090081;
+const SUBMITTED_TX_HASH: &str = "c1";
+
+#[derive(Deserialize)]
+struct EnvelopeFixture {
+    tx: String,
+    hash: String,
+}
+
+/// TRP answering every resolve with the fixture envelope, echoing the JSON-RPC id
+struct TrpResolve(EnvelopeFixture);
+
+impl Respond for TrpResolve {
+    fn respond(&self, request: &Request) -> ResponseTemplate {
+        let id = serde_json::from_slice::<Value>(&request.body)
+            .ok()
+            .and_then(|body| body.get("id").cloned())
+            .unwrap_or(Value::Null);
+
+        ResponseTemplate::new(200).set_body_json(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": { "tx": self.0.tx, "hash": self.0.hash },
+        }))
+    }
+}
+
+struct CountingSubmitter(Arc<AtomicUsize>);
+
+#[async_trait::async_trait]
+impl TxSubmitter for CountingSubmitter {
+    async fn submit(&self, _signed_tx: Vec<u8>) -> Result<String, SubmitError> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Ok(SUBMITTED_TX_HASH.to_string())
+    }
+}
+
+/// Blockfrost holding output 0 of `TRACKING_TX_HASH` at `address`, spent by `consumed_by_tx`,
+/// and TRP resolving its close to the fixture envelope
+async fn serve(address: &str, inline_datum: &str, consumed_by_tx: Option<&str>) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/txs/{}/utxos", TRACKING_TX_HASH)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "hash": TRACKING_TX_HASH,
+            "inputs": [],
+            "outputs": [{
+                "address": address,
+                "output_index": 0,
+                "inline_datum": inline_datum,
+                "reference_script_hash": null,
+                "consumed_by_tx": consumed_by_tx,
+            }],
+        })))
+        .mount(&server)
+        .await;
+
+    let fixture = format!("{}/tests/fixtures/close_shipment_envelope_valid.json", env!("CARGO_MANIFEST_DIR"));
+    let envelope = serde_json::from_str(&std::fs::read_to_string(fixture).unwrap()).unwrap();
+    Mock::given(method("POST"))
+        .and(path("/"))
+        .respond_with(TrpResolve(envelope))
+        .mount(&server)
+        .await;
+
+    server
+}
+
+fn client(server: &MockServer, submissions: &Arc<AtomicUsize>) -> CardanoClient {
+    CardanoClient::with_submitter(test_config(&server.uri()), Box::new(CountingSubmitter(submissions.clone()))).unwrap()
+}
+
+/// Oracle over `server` journalling into `state`, with `configure` applied to the test config
+fn oracle(
+    server: &MockServer,
+    submissions: &Arc<AtomicUsize>,
+    state: &Arc<dyn StateStore>,
+    configure: impl FnOnce(&mut Config),
+) -> Oracle {
+    let mut config = test_config(&server.uri());
+    configure(&mut config);
+    Oracle::builder()
+        .config(config)
+        .submitter(CountingSubmitter(submissions.clone()))
+        .state(state.clone())
+        .build()
+        .unwrap()
+}
+
+fn utxo_ref(index: u32) -> UtxoRef {
+    format!("{}#{}", TRACKING_TX_HASH, index).parse().unwrap()
+}
+
+#[tokio::test]
+async fn close_utxo_submits_a_close_for_the_onchain_datum() {
+    let server = serve(ORACLE_ADDRESS, &tracking_datum_cbor("usps", TRACKING_NUMBER), None).await;
+    let submissions = Arc::new(AtomicUsize::new(0));
+    let chain = client(&server, &submissions);
+
+    let tracking = chain.tracking_utxo(&utxo_ref(0), false).await.unwrap();
+    assert_eq!(tracking.datum.carrier, "usps");
+    assert_eq!(tracking.datum.tracking_number.to_string(), TRACKING_NUMBER);
+    assert_eq!(tracking.datum.outbox_address.to_bech32().unwrap(), OUTBOX_ADDRESS);
+
+    let tx_hash = chain.close_utxo(&utxo_ref(0), DerivedStatus::Delivered, Some(CLOSED_AT)).await.unwrap();
+    assert_eq!(tx_hash, SUBMITTED_TX_HASH);
+    assert_eq!(submissions.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn close_utxo_refuses_spent_missing_and_foreign_utxos() {
+    let datum = tracking_datum_cbor("usps", TRACKING_NUMBER);
+    let submissions = Arc::new(AtomicUsize::new(0));
+
+    let server = serve(ORACLE_ADDRESS, &datum, Some("ab")).await;
+    let error = client(&server, &submissions)
+        .close_utxo(&utxo_ref(0), DerivedStatus::Delivered, Some(CLOSED_AT))
+        .await
+        .unwrap_err();
+    assert_eq!(error.downcast_ref::<Raced>().unwrap().spent_by, "ab");
+
+    let error = client(&server, &submissions)
+        .close_utxo(&utxo_ref(1), DerivedStatus::Delivered, Some(CLOSED_AT))
+        .await
+        .unwrap_err();
+    assert_eq!(error.to_string(), format!("Tracking UTxO {}#1 not found on-chain", TRACKING_TX_HASH));
+
+    let server = serve(OUTBOX_ADDRESS, &datum, None).await;
+    let error = client(&server, &submissions)
+        .close_utxo(&utxo_ref(0), DerivedStatus::Delivered, Some(CLOSED_AT))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("not at the oracle address"), "{}", error);
+
+    assert_eq!(submissions.load(Ordering::SeqCst), 0);
+}
+
+#[tokio::test]
+async fn undecodable_datums_are_only_closed_when_forced() {
+    // DATUM_CODECS only has the positional codec, so the map datum is refused unless forced
+    let server = serve(ORACLE_ADDRESS, &map_tracking_datum_cbor("usps", TRACKING_NUMBER), None).await;
+    let submissions = Arc::new(AtomicUsize::new(0));
+    let chain = client(&server, &submissions);
+
+    let error = chain.close_utxo(&utxo_ref(0), DerivedStatus::Delivered, Some(CLOSED_AT)).await.unwrap_err();
+    assert!(error.to_string().starts_with("DATUM_CODECS cannot decode the datum of"), "{}", error);
+    assert_eq!(submissions.load(Ordering::SeqCst), 0);
+
+    let tx_hash = chain.force_close_utxo(&utxo_ref(0), DerivedStatus::Delivered, Some(CLOSED_AT)).await.unwrap();
+    assert_eq!(tx_hash, SUBMITTED_TX_HASH);
+    assert_eq!(submissions.load(Ordering::SeqCst), 1);
+
+    let server = serve(ORACLE_ADDRESS, "d87980", None).await;
+    let error = client(&server, &submissions)
+        .force_close_utxo(&utxo_ref(0), DerivedStatus::Delivered, Some(CLOSED_AT))
+        .await
+        .unwrap_err();
+    assert!(error.to_string().starts_with("No datum codec decodes the datum of"), "{}", error);
+}
+
+#[tokio::test]
+async fn operator_closes_are_journalled_and_not_repeated() {
+    let server = serve(ORACLE_ADDRESS, &tracking_datum_cbor("usps", TRACKING_NUMBER), None).await;
+    let submissions = Arc::new(AtomicUsize::new(0));
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    let oracle = oracle(&server, &submissions, &state, |_| {});
+    let mut events = oracle.subscribe();
+
+    let tracking = oracle.chain().tracking_utxo(&utxo_ref(0), false).await.unwrap();
+    let closed = oracle.close_shipment(&tracking, DerivedStatus::Delivered, Some(CLOSED_AT)).await.unwrap();
+    assert!(matches!(&closed.action, ShipmentAction::Submitted { tx_hash } if tx_hash == SUBMITTED_TX_HASH));
+    assert_eq!(submissions.load(Ordering::SeqCst), 1);
+
+    let utxo_ref = utxo_ref(0).to_string();
+    let journalled = state.submissions(&utxo_ref).await.unwrap();
+    assert_eq!(journalled.len(), 1);
+    assert_eq!((journalled[0].status.as_str(), journalled[0].submitted_at), ("DELIVERED", CLOSED_AT));
+    assert_eq!(state.attempts(&utxo_ref).await.unwrap().len(), 1);
+    assert!(matches!(
+        events.try_recv().unwrap(),
+        OracleEvent::ShipmentClosed { timestamp: CLOSED_AT, timestamp_source: TimestampSource::Operator, .. }
+    ));
+
+    // The close is pending until the tracking UTxO is seen spent, so it is not submitted twice
+    let error = oracle.close_shipment(&tracking, DerivedStatus::Delivered, Some(CLOSED_AT)).await.unwrap_err();
+    assert_eq!(error.to_string(), format!("A close of {} is already pending in {}", utxo_ref, SUBMITTED_TX_HASH));
+    assert_eq!(submissions.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn operator_closes_follow_the_outbox_policy() {
+    let server = serve(ORACLE_ADDRESS, &tracking_datum_cbor("usps", TRACKING_NUMBER), None).await;
+    let submissions = Arc::new(AtomicUsize::new(0));
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    let denylist = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(denylist.path(), format!("{}\n", OUTBOX_ADDRESS)).unwrap();
+    let oracle = oracle(&server, &submissions, &state, |config| {
+        config.outbox_denylist_file = Some(denylist.path().display().to_string());
+    });
+
+    let tracking = oracle.chain().tracking_utxo(&utxo_ref(0), false).await.unwrap();
+    let error = oracle.close_shipment(&tracking, DerivedStatus::Delivered, Some(CLOSED_AT)).await.unwrap_err();
+    assert!(error.to_string().starts_with(&format!("Refusing to close {}", utxo_ref(0))), "{}", error);
+    assert_eq!(submissions.load(Ordering::SeqCst), 0);
+    assert!(state.attempts(&utxo_ref(0).to_string()).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn dry_run_closes_are_signed_not_submitted() {
+    let server = serve(ORACLE_ADDRESS, &tracking_datum_cbor("usps", TRACKING_NUMBER), None).await;
+    let submissions = Arc::new(AtomicUsize::new(0));
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    let oracle = oracle(&server, &submissions, &state, |config| config.dry_run = true);
+
+    let tracking = oracle.chain().tracking_utxo(&utxo_ref(0), false).await.unwrap();
+    let closed = oracle.close_shipment(&tracking, DerivedStatus::Delivered, Some(CLOSED_AT)).await.unwrap();
+    assert!(closed.is_dry_run());
+    assert!(!closed.tx_hash().is_empty());
+    assert!(state.attempts(&utxo_ref(0).to_string()).await.unwrap().is_empty());
+
+    let error = oracle.chain().close_utxo(&utxo_ref(0), DerivedStatus::Delivered, Some(CLOSED_AT)).await.unwrap_err();
+    assert_eq!(error.to_string(), format!("DRY_RUN is set, not submitting a close of {}", utxo_ref(0)));
+    assert_eq!(submissions.load(Ordering::SeqCst), 0);
+}
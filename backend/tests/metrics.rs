@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+use tokio::sync::oneshot;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::metrics::{Metrics, MetricsServer, OTHER_OUTBOX};
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::testing::{ORACLE_ADDRESS, OUTBOX_ADDRESS, blockfrost_utxos, shippo_track, test_config, tracking_number};
+
+/// Shipments reporting `statuses` in order; `None` answers 500. TRP is not mocked, so every close fails
+async fn serve_shipments(statuses: &[Option<&str>]) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(statuses.len())))
+        .mount(&server)
+        .await;
+    for (index, status) in statuses.iter().enumerate() {
+        let response = match status {
+            Some(status) => {
+                ResponseTemplate::new(200).set_body_json(shippo_track("usps", &tracking_number(index), status))
+            }
+            None => ResponseTemplate::new(500),
+        };
+        Mock::given(method("GET"))
+            .and(path(format!("/tracks/usps/{}", tracking_number(index))))
+            .respond_with(response)
+            .mount(&server)
+            .await;
+    }
+
+    server
+}
+
+async fn run_tenant(server: &MockServer, metrics: &Metrics) {
+    let mut config = test_config(&server.uri());
+    config.tenant = Some("acme".to_string());
+    let oracle = Oracle::builder().config(config).metrics(metrics.clone()).build().unwrap();
+
+    oracle.run_once().await.unwrap();
+}
+
+/// Serve `metrics`, scrape `path` once and shut the server down
+async fn scrape(metrics: &Metrics, path: &str) -> (u16, String) {
+    let server = MetricsServer::bind("127.0.0.1:0".parse().unwrap(), metrics.clone()).unwrap();
+    let addr = server.local_addr().unwrap();
+    let (stop, stopped) = oneshot::channel::<()>();
+    let serving = tokio::spawn(server.serve_until(async {
+        let _ = stopped.await;
+    }));
+
+    let response = reqwest::get(format!("http://{}{}", addr, path)).await.unwrap();
+    let scraped = (response.status().as_u16(), response.text().await.unwrap());
+
+    stop.send(()).unwrap();
+    serving.await.unwrap().unwrap();
+    scraped
+}
+
+/// Value of the `metric` sample carrying every label of `labels`
+fn sample(body: &str, metric: &str, labels: &[&str]) -> Option<f64> {
+    body.lines()
+        .filter(|line| line.starts_with(&format!("{}{{", metric)))
+        .find(|line| labels.iter().all(|label| line.contains(label)))
+        .and_then(|line| line.rsplit(' ').next())
+        .map(|value| value.parse().unwrap())
+}
+
+#[tokio::test]
+async fn scrape_counts_the_run() {
+    let server = serve_shipments(&[Some("TRANSIT"), Some("DELIVERED"), None]).await;
+    let metrics = Metrics::new();
+    run_tenant(&server, &metrics).await;
+
+    let (status, body) = scrape(&metrics, "/metrics").await;
+
+    assert_eq!(status, 200);
+    let tenant = r#"tenant="acme""#;
+    assert_eq!(sample(&body, "oracle_shipments_discovered_total", &[tenant]), Some(3.0));
+    assert_eq!(sample(&body, "oracle_statuses_fetched_total", &[tenant, r#"carrier="usps""#]), Some(2.0));
+    assert_eq!(sample(&body, "oracle_closes_failed_total", &[tenant]), Some(2.0));
+    let outbox = format!(r#"outbox="{}""#, OUTBOX_ADDRESS);
+    assert_eq!(sample(&body, "oracle_closes_failed_total", &[tenant, &outbox]), Some(2.0));
+    assert_eq!(sample(&body, "oracle_closes_submitted_total", &[tenant]), None);
+    assert_eq!(sample(&body, "oracle_request_errors_total", &[tenant, r#"service="shippo""#]), Some(1.0));
+    assert_eq!(sample(&body, "oracle_request_errors_total", &[tenant, r#"service="trp""#]), Some(1.0));
+    assert_eq!(sample(&body, "oracle_pending_closes", &[tenant]), Some(0.0));
+    // A shipment failed, so the run does not count as successful
+    assert_eq!(sample(&body, "oracle_last_successful_run_timestamp_seconds", &[tenant]), None);
+}
+
+#[tokio::test]
+async fn scrape_reports_the_last_successful_run() {
+    let server = serve_shipments(&[Some("TRANSIT")]).await;
+    let metrics = Metrics::new();
+    let before = chrono::Utc::now().timestamp() as f64;
+    run_tenant(&server, &metrics).await;
+
+    let (_, body) = scrape(&metrics, "/metrics").await;
+
+    let last_run = sample(&body, "oracle_last_successful_run_timestamp_seconds", &[r#"tenant="acme""#]).unwrap();
+    assert!(last_run >= before, "last successful run at {}", last_run);
+    assert_eq!(sample(&body, "oracle_closes_failed_total", &[]), None);
+}
+
+#[tokio::test]
+async fn other_paths_are_not_found() {
+    let (status, _) = scrape(&Metrics::new(), "/health").await;
+
+    assert_eq!(status, 404);
+}
+
+#[tokio::test]
+async fn merchants_are_labelled_with_their_alias() {
+    let server = serve_shipments(&[Some("DELIVERED")]).await;
+    let metrics = Metrics::new();
+    let mut config = test_config(&server.uri());
+    config.outbox_aliases = BTreeMap::from([(OUTBOX_ADDRESS.to_string(), "acme-store".to_string())]);
+    Oracle::builder().config(config).metrics(metrics.clone()).build().unwrap().run_once().await.unwrap();
+
+    let body = metrics.render().unwrap();
+
+    assert_eq!(sample(&body, "oracle_closes_failed_total", &[r#"outbox="acme-store""#]), Some(1.0));
+    assert!(!body.contains(OUTBOX_ADDRESS), "{}", body);
+}
+
+#[test]
+fn outboxes_over_the_cap_share_the_other_label() {
+    let aliases = BTreeMap::from([("addr_test1merchant".to_string(), "acme-store".to_string())]);
+    let metrics = Metrics::new().with_outbox_labels(aliases, 2);
+
+    for outbox in ["addr_test1a", "addr_test1b", "addr_test1c", "addr_test1merchant", "addr_test1d", "addr_test1a"] {
+        metrics.close_submitted(outbox);
+    }
+    let body = metrics.render().unwrap();
+
+    let submitted = |outbox: &str| sample(&body, "oracle_closes_submitted_total", &[&format!(r#"outbox="{}""#, outbox)]);
+    assert_eq!(submitted("addr_test1a"), Some(2.0));
+    assert_eq!(submitted("addr_test1b"), Some(1.0));
+    // Aliased merchants are always labelled, whatever the cap
+    assert_eq!(submitted("acme-store"), Some(1.0));
+    assert_eq!(submitted("addr_test1c"), None);
+    assert_eq!(submitted("addr_test1d"), None);
+    assert_eq!(submitted(OTHER_OUTBOX), Some(2.0));
+}
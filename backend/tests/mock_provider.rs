@@ -0,0 +1,112 @@
+use std::io::Write;
+use std::sync::Arc;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::metrics::Metrics;
+use shipping_oracle::models::CarrierStatus;
+use shipping_oracle::mock_provider::{MockProvider, suffix_status};
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::shipment::ShipmentClient;
+use shipping_oracle::state::{MemoryStore, StateStore};
+use shipping_oracle::testing::{ORACLE_ADDRESS, blockfrost_utxos, test_config, tracking_datum_cbor};
+use shipping_oracle::tracking_provider::{self, ProviderKind, TrackingProvider};
+
+fn utxo_ref(index: usize) -> String {
+    format!("{:064x}#0", index)
+}
+
+fn fixtures(json: &str) -> tempfile::NamedTempFile {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(json.as_bytes()).unwrap();
+    file
+}
+
+#[test]
+fn suffixes_pick_the_status() {
+    assert_eq!(suffix_status("DEMO1-DELIVERED"), "DELIVERED");
+    assert_eq!(suffix_status("demo-2-failure"), "FAILURE");
+    assert_eq!(suffix_status("DEMO3-RETURNED"), "RETURNED");
+    assert_eq!(suffix_status("DEMO4-PRE_TRANSIT"), "PRE_TRANSIT");
+    assert_eq!(suffix_status("DEMO5"), "TRANSIT");
+    assert_eq!(suffix_status("DEMO6-LOST"), "TRANSIT");
+}
+
+#[tokio::test]
+async fn fixtures_win_over_suffixes() {
+    let file = fixtures(
+        r#"{
+            "DEMO1": "DELIVERED",
+            "DEMO2-DELIVERED": { "status": "TRANSIT", "status_details": "Held at customs", "status_date": "2026-02-14T17:28:01Z" }
+        }"#,
+    );
+    let mock = MockProvider::from_fixtures(file.path().to_str().unwrap()).unwrap();
+
+    let delivered = mock.fetch_status("usps", "DEMO1").await.unwrap();
+    assert_eq!((delivered.status.as_str(), delivered.status_details.as_str()), ("DELIVERED", "Mock DELIVERED status"));
+    assert_eq!(delivered.status_date, None);
+
+    let held = mock.fetch_status("usps", "DEMO2-DELIVERED").await.unwrap();
+    assert_eq!((held.status.as_str(), held.status_details.as_str()), ("TRANSIT", "Held at customs"));
+    assert_eq!(held.status_date.as_deref(), Some("2026-02-14T17:28:01Z"));
+
+    let failure = mock.fetch_status("usps", "DEMO3-FAILURE").await.unwrap();
+    assert_eq!(failure.status, CarrierStatus::Failure);
+    assert_eq!(mock.provider("usps"), ProviderKind::Mock);
+
+    let err = MockProvider::from_fixtures(fixtures("[]").path().to_str().unwrap()).unwrap_err();
+    assert!(err.to_string().contains("Invalid MOCK_TRACKING_FIXTURES"));
+    assert!(MockProvider::from_fixtures("/nonexistent/fixtures.json").is_err());
+}
+
+#[tokio::test]
+async fn mock_statuses_close_only_final_shipments() {
+    let server = MockServer::start().await;
+    let mut utxos = blockfrost_utxos(4);
+    for (i, tracking_number) in ["DEMO0-DELIVERED", "DEMO1-TRANSIT", "DEMO2-FAILURE", "DEMO3"].iter().enumerate() {
+        utxos[i]["inline_datum"] = tracking_datum_cbor("usps", tracking_number).into();
+    }
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(utxos))
+        .mount(&server)
+        .await;
+
+    let mut config = test_config(&server.uri());
+    config.tracking_provider = ProviderKind::Mock;
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    let oracle = Oracle::builder().config(config).state(state.clone()).build().unwrap();
+
+    // TRP is not mocked, so every close is attempted and fails
+    let stats = oracle.run_once().await.unwrap().stats;
+    assert_eq!((stats.shipments, stats.failed, stats.submitted), (4, 2, 0));
+
+    let mut attempted = Vec::new();
+    for i in 0..4 {
+        if !state.attempts(&utxo_ref(i)).await.unwrap().is_empty() {
+            attempted.push(i);
+        }
+    }
+    assert_eq!(attempted, vec![0, 2]);
+
+    let requests = server.received_requests().await.unwrap();
+    assert!(!requests.iter().any(|request| request.url.path().starts_with("/tracks/")));
+}
+
+#[tokio::test]
+async fn mock_carriers_need_the_mock_provider_selected() {
+    let config = test_config("http://localhost");
+    let shippo = ShipmentClient::new(config.clone()).unwrap();
+    let providers = tracking_provider::from_config(&config, shippo, &Metrics::new()).unwrap();
+
+    let err = providers.fetch_status("mock:usps", "DEMO0-DELIVERED").await.unwrap_err();
+    assert!(err.to_string().contains("No mock tracking provider is configured"));
+
+    let mut config = test_config("http://localhost");
+    config.carrier_providers = [("demo".to_string(), ProviderKind::Mock)].into();
+    let shippo = ShipmentClient::new(config.clone()).unwrap();
+    let providers = tracking_provider::from_config(&config, shippo, &Metrics::new()).unwrap();
+    assert_eq!(providers.fetch_status("mock:usps", "DEMO0-DELIVERED").await.unwrap().status, CarrierStatus::Delivered);
+    assert_eq!(providers.fetch_status("demo", "DEMO0-RETURNED").await.unwrap().status, CarrierStatus::Returned);
+    assert_eq!(providers.provider("usps"), ProviderKind::Shippo);
+}
@@ -0,0 +1,438 @@
+use anyhow::Result;
+use std::time::Duration;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::config::Network;
+use shipping_oracle::metrics::Metrics;
+use shipping_oracle::notifier::{
+    CompositeNotifier, DiscordNotifier, Notifier, NotifyMode, OracleEvent, SIGNATURE_HEADER, SlackNotifier,
+    WebhookNotifier, WebhookTemplate, discord_payload, sign_payload, slack_payload, slack_summary_payload,
+};
+use shipping_oracle::timestamp_source::TimestampSource;
+use std::sync::Arc;
+
+const WEBHOOK_SECRET: &str = "merchant-shared-secret";
+
+fn closed_event() -> OracleEvent {
+    OracleEvent::ShipmentClosed {
+        utxo_ref: "a7a264ac1bef1f0da6312d16fb8f68b18d1a0d5010d8cb6a61790bf02a759301#0".to_string(),
+        carrier: "shippo".to_string(),
+        tracking_number: "SHIPPO_DELIVERED".to_string(),
+        status: "DELIVERED".to_string(),
+        timestamp: 1771090081,
+        timestamp_source: TimestampSource::Oracle,
+        oracle_timestamp: 1771090081,
+        tx_hash: "584cbabb4a075d96d065b6e158d737f98c961dc5802e4b3f905f1f533d28f68f".to_string(),
+        tenant: None,
+        run_id: None,
+    }
+}
+
+fn failed_event(index: usize) -> OracleEvent {
+    OracleEvent::ShipmentFailed {
+        utxo_ref: format!("8185f0c4c844e28214c52c6871753304f273d0fea872b95fe6e32974d16ef520#{}", index),
+        carrier: "shippo".to_string(),
+        tracking_number: format!("SHIPPO_FAILURE_{}", index),
+        status: "NOT_DELIVERED".to_string(),
+        timestamp: 1771090081,
+        error: "Blockfrost transaction submission failed (status 400 Bad Request)".to_string(),
+        tenant: None,
+        run_id: None,
+    }
+}
+
+#[tokio::test]
+async fn webhook_payload_is_signed() -> Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/hook"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let notifier = WebhookNotifier::new(format!("{}/hook", server.uri()), Some(WEBHOOK_SECRET.to_string()))?;
+    notifier.notify(&closed_event()).await?;
+
+    let requests = server.received_requests().await.unwrap_or_default();
+    let request = &requests[0];
+
+    let signature = request.headers
+        .get(SIGNATURE_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    assert_eq!(signature, sign_payload(WEBHOOK_SECRET, &request.body)?);
+
+    let payload: serde_json::Value = request.body_json()?;
+    assert_eq!(payload["event"], "shipment_closed");
+    assert_eq!(payload["utxo_ref"], "a7a264ac1bef1f0da6312d16fb8f68b18d1a0d5010d8cb6a61790bf02a759301#0");
+    assert_eq!(payload["carrier"], "shippo");
+    assert_eq!(payload["tracking_number"], "SHIPPO_DELIVERED");
+    assert_eq!(payload["status"], "DELIVERED");
+    assert_eq!(payload["timestamp"], 1771090081);
+    assert_eq!(payload["tx_hash"], "584cbabb4a075d96d065b6e158d737f98c961dc5802e4b3f905f1f533d28f68f");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn webhook_retries_on_server_error() -> Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .up_to_n_times(2)
+        .expect(2)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let metrics = Metrics::new();
+    let notifier = WebhookNotifier::new(server.uri(), None)?
+        .with_retry_backoff(Duration::from_millis(10))
+        .with_metrics(metrics.clone());
+    notifier.notify(&closed_event()).await?;
+
+    assert!(!metrics.render()?.contains("oracle_notification_delivery_failures_total{"));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn webhook_counts_failed_deliveries() -> Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(4)
+        .mount(&server)
+        .await;
+
+    let metrics = Metrics::new().for_tenant(Some("acme"));
+    let notifier = WebhookNotifier::new(server.uri(), None)?
+        .with_retry_backoff(Duration::from_millis(10))
+        .with_metrics(metrics.clone());
+
+    assert!(notifier.notify(&closed_event()).await.is_err());
+    let body = metrics.render()?;
+    assert!(
+        body.lines().any(|line| line == r#"oracle_notification_delivery_failures_total{notifier="webhook",tenant="acme"} 1"#),
+        "{}",
+        body
+    );
+
+    Ok(())
+}
+
+#[test]
+fn slack_payload_for_closed_shipment() {
+    let payload = slack_payload(&closed_event(), Some(Network::Preprod));
+
+    assert_eq!(payload, serde_json::json!({
+        "text": "shippo SHIPPO_DELIVERED closed as DELIVERED",
+        "blocks": [
+            {
+                "type": "header",
+                "text": { "type": "plain_text", "text": "✅ Shipment closed" },
+            },
+            {
+                "type": "section",
+                "fields": [
+                    { "type": "mrkdwn", "text": "*Carrier*\nshippo" },
+                    { "type": "mrkdwn", "text": "*Tracking number*\nSHIPPO_DELIVERED" },
+                    { "type": "mrkdwn", "text": "*Status*\nDELIVERED" },
+                    { "type": "mrkdwn", "text": "*UTxO*\n`a7a264ac1bef1f0da6312d16fb8f68b18d1a0d5010d8cb6a61790bf02a759301#0`" },
+                ],
+            },
+            {
+                "type": "section",
+                "text": {
+                    "type": "mrkdwn",
+                    "text": "<https://preprod.cardanoscan.io/transaction/584cbabb4a075d96d065b6e158d737f98c961dc5802e4b3f905f1f533d28f68f|View transaction>",
+                },
+            },
+        ],
+    }));
+}
+
+#[test]
+fn tenant_events_are_labelled() {
+    let OracleEvent::ShipmentClosed { utxo_ref, carrier, tracking_number, status, timestamp, timestamp_source, oracle_timestamp, tx_hash, .. } = closed_event() else {
+        unreachable!();
+    };
+    let event = OracleEvent::ShipmentClosed {
+        utxo_ref, carrier, tracking_number, status, timestamp, timestamp_source, oracle_timestamp, tx_hash,
+        tenant: Some("mainnet".to_string()),
+        run_id: None,
+    };
+
+    assert_eq!(serde_json::to_value(&event).unwrap()["tenant"], "mainnet");
+    assert!(serde_json::to_value(closed_event()).unwrap().get("tenant").is_none());
+
+    let fields = &discord_payload(&event, None)["embeds"][0]["fields"];
+    assert_eq!(fields[0], serde_json::json!({ "name": "Tenant", "value": "mainnet", "inline": true }));
+}
+
+#[test]
+fn run_events_carry_their_run_id() {
+    let run_id = "01K7NVX3C5RZ1E4GQ8M2WJ6T9B";
+    let OracleEvent::ShipmentFailed { utxo_ref, carrier, tracking_number, status, timestamp, error, .. } = failed_event(0) else {
+        unreachable!();
+    };
+    let event = OracleEvent::ShipmentFailed {
+        utxo_ref, carrier, tracking_number, status, timestamp, error,
+        tenant: Some("mainnet".to_string()),
+        run_id: Some(run_id.to_string()),
+    };
+
+    assert_eq!(serde_json::to_value(&event).unwrap()["run_id"], run_id);
+    assert!(serde_json::to_value(failed_event(0)).unwrap().get("run_id").is_none());
+
+    let fields = slack_payload(&event, None)["blocks"][1]["fields"].clone();
+    let fields = fields.as_array().unwrap();
+    assert_eq!(fields.last().unwrap()["text"], format!("*Run*\n`{}`", run_id));
+
+    let summary = slack_summary_payload(&[event], None);
+    assert_eq!(summary["blocks"][0]["text"]["text"], format!("Oracle run summary (mainnet, {})", run_id));
+}
+
+#[test]
+fn skipped_shipments_show_the_refused_outbox() {
+    let event = OracleEvent::ShipmentSkipped {
+        utxo_ref: "a7a264ac1bef1f0da6312d16fb8f68b18d1a0d5010d8cb6a61790bf02a759301#0".to_string(),
+        carrier: "usps".to_string(),
+        tracking_number: "9400111899223456789012".to_string(),
+        outbox_address: "addr_test1vqpp4rqsgkhyaz5ejjtwzane9wnkggfrn9pptgmtwq7fqws6t8yck".to_string(),
+        reason: "outbox address is not allowlisted".to_string(),
+        tenant: None,
+        run_id: None,
+    };
+
+    assert_eq!(serde_json::to_value(&event).unwrap()["event"], "shipment_skipped");
+    assert!(!event.is_failure());
+
+    let embed = &discord_payload(&event, None)["embeds"][0];
+    assert_eq!(embed["title"], "🚫 Shipment skipped");
+    assert_eq!(embed["color"], 0xecb22e);
+    assert_eq!(embed["fields"][2]["name"], "Outbox");
+    assert_eq!(embed["fields"][4], serde_json::json!({ "name": "Reason", "value": "outbox address is not allowlisted", "inline": false }));
+
+    let summary = slack_summary_payload(&[event, closed_event()], None);
+    assert_eq!(summary["text"], "Oracle run: 1 closed, 0 failed");
+}
+
+#[test]
+fn discord_payload_for_closed_shipment() {
+    let payload = discord_payload(&closed_event(), Some(Network::Mainnet));
+
+    assert_eq!(payload, serde_json::json!({
+        "embeds": [{
+            "title": "✅ Shipment closed",
+            "color": 0x2eb67d,
+            "url": "https://cardanoscan.io/transaction/584cbabb4a075d96d065b6e158d737f98c961dc5802e4b3f905f1f533d28f68f",
+            "fields": [
+                { "name": "Carrier", "value": "shippo", "inline": true },
+                { "name": "Tracking number", "value": "SHIPPO_DELIVERED", "inline": true },
+                { "name": "Status", "value": "DELIVERED", "inline": true },
+                { "name": "UTxO", "value": "`a7a264ac1bef1f0da6312d16fb8f68b18d1a0d5010d8cb6a61790bf02a759301#0`", "inline": true },
+            ],
+        }],
+    }));
+}
+
+#[tokio::test]
+async fn chat_notifiers_summarize_large_runs() -> Result<()> {
+    let slack = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&slack)
+        .await;
+
+    let discord = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(204))
+        .expect(1)
+        .mount(&discord)
+        .await;
+
+    let notifier = CompositeNotifier::new(vec![
+        Arc::new(SlackNotifier::new(slack.uri(), Some(Network::Preprod))?),
+        Arc::new(DiscordNotifier::new(discord.uri(), Some(Network::Preprod))?),
+    ]);
+
+    for index in 0..50 {
+        notifier.notify(&failed_event(index)).await?;
+    }
+    notifier.flush().await?;
+
+    let requests = slack.received_requests().await.unwrap_or_default();
+    let payload: serde_json::Value = requests[0].body_json()?;
+    assert_eq!(payload["text"], "Oracle run: 0 closed, 50 failed");
+
+    let requests = discord.received_requests().await.unwrap_or_default();
+    let payload: serde_json::Value = requests[0].body_json()?;
+    assert_eq!(payload["embeds"][0]["fields"][1]["value"], "50");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn chat_notifiers_send_small_runs_individually() -> Result<()> {
+    let slack = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&slack)
+        .await;
+
+    let notifier = SlackNotifier::new(slack.uri(), None)?;
+    notifier.notify(&closed_event()).await?;
+    notifier.notify(&failed_event(0)).await?;
+    notifier.flush().await?;
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn webhook_per_run_posts_the_run_once() -> Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let notifier = WebhookNotifier::new(server.uri(), Some(WEBHOOK_SECRET.to_string()))?.with_mode(NotifyMode::PerRun);
+    notifier.notify(&closed_event()).await?;
+    notifier.notify(&failed_event(0)).await?;
+    assert!(server.received_requests().await.unwrap_or_default().is_empty());
+    notifier.flush().await?;
+    // Nothing left to send
+    notifier.flush().await?;
+
+    let requests = server.received_requests().await.unwrap_or_default();
+    let request = &requests[0];
+    let signature = request.headers.get(SIGNATURE_HEADER).and_then(|value| value.to_str().ok());
+    assert_eq!(signature, Some(sign_payload(WEBHOOK_SECRET, &request.body)?.as_str()));
+
+    let payload: serde_json::Value = request.body_json()?;
+    assert_eq!(payload["event"], "run_summary");
+    assert_eq!((payload["closed"].as_u64(), payload["failed"].as_u64()), (Some(1), Some(1)));
+    assert_eq!(payload["events"][0]["event"], "shipment_closed");
+    assert_eq!(payload["events"][0]["tx_hash"], "584cbabb4a075d96d065b6e158d737f98c961dc5802e4b3f905f1f533d28f68f");
+    assert_eq!(payload["events"][1]["event"], "shipment_failed");
+    assert_eq!(payload["events"][1]["error"], "Blockfrost transaction submission failed (status 400 Bad Request)");
+    assert!(payload.get("run_id").is_none());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn webhook_template_renders_each_event() -> Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&server)
+        .await;
+
+    let template = WebhookTemplate::parse(r#"{"text": "{{title}}: {{summary}}", "tx": "{{ tx_hash }}", "error": "{{error}}"}"#)?;
+    let notifier = WebhookNotifier::new(server.uri(), None)?.with_template(template);
+    notifier.notify(&closed_event()).await?;
+    let OracleEvent::ShipmentFailed { utxo_ref, carrier, tracking_number, status, timestamp, .. } = failed_event(0) else {
+        unreachable!();
+    };
+    notifier
+        .notify(&OracleEvent::ShipmentFailed {
+            utxo_ref, carrier, tracking_number, status, timestamp,
+            error: "TRP answered \"bad request\"".to_string(),
+            tenant: None,
+            run_id: None,
+        })
+        .await?;
+
+    let requests = server.received_requests().await.unwrap_or_default();
+    let closed: serde_json::Value = requests[0].body_json()?;
+    assert_eq!(closed, serde_json::json!({
+        "text": "✅ Shipment closed: shippo SHIPPO_DELIVERED closed as DELIVERED",
+        "tx": "584cbabb4a075d96d065b6e158d737f98c961dc5802e4b3f905f1f533d28f68f",
+        "error": "",
+    }));
+    let failed: serde_json::Value = requests[1].body_json()?;
+    assert_eq!(failed["error"], "TRP answered \"bad request\"");
+    assert_eq!(failed["text"], "❌ Shipment close failed: shippo SHIPPO_FAILURE_0 failed: TRP answered \"bad request\"");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn webhook_template_renders_the_run() -> Result<()> {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let template = WebhookTemplate::parse(r#"{"text": "{{summary}}", "failed": "{{failed}}"}"#)?;
+    let notifier = WebhookNotifier::new(server.uri(), None)?.with_mode(NotifyMode::PerRun).with_template(template);
+    notifier.notify(&closed_event()).await?;
+    notifier.notify(&failed_event(0)).await?;
+    notifier.flush().await?;
+
+    let requests = server.received_requests().await.unwrap_or_default();
+    let payload: serde_json::Value = requests[0].body_json()?;
+    assert_eq!(payload["failed"], "1");
+    assert_eq!(
+        payload["text"],
+        "Oracle run: 1 closed, 1 failed\n\
+         • shippo SHIPPO_DELIVERED closed as DELIVERED\n\
+         • shippo SHIPPO_FAILURE_0 failed: Blockfrost transaction submission failed (status 400 Bad Request)"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn webhook_templates_are_checked() {
+    let unknown = WebhookTemplate::parse(r#"{"text": "{{tracking}}"}"#).unwrap_err();
+    assert!(unknown.to_string().starts_with("unknown placeholder {{tracking}}"), "{}", unknown);
+
+    let invalid = WebhookTemplate::parse(r#"{"text": {{summary}}}"#).unwrap_err();
+    assert_eq!(invalid.to_string(), "template does not render valid JSON");
+}
+
+#[tokio::test]
+async fn chat_notifiers_summarize_every_run_per_run() -> Result<()> {
+    let slack = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&slack)
+        .await;
+
+    let notifier = SlackNotifier::new(slack.uri(), None)?.with_mode(NotifyMode::PerRun);
+    notifier.notify(&closed_event()).await?;
+    notifier.notify(&failed_event(0)).await?;
+    notifier.flush().await?;
+
+    let requests = slack.received_requests().await.unwrap_or_default();
+    let payload: serde_json::Value = requests[0].body_json()?;
+    assert_eq!(payload["text"], "Oracle run: 1 closed, 1 failed");
+
+    Ok(())
+}
+
+#[test]
+fn notify_modes_parse_case_insensitively() {
+    assert_eq!(" PER_RUN ".parse::<NotifyMode>().unwrap(), NotifyMode::PerRun);
+    assert_eq!("per_shipment".parse::<NotifyMode>().unwrap(), NotifyMode::PerShipment);
+    assert_eq!(
+        "daily".parse::<NotifyMode>().unwrap_err().to_string(),
+        "expected per_shipment or per_run, got 'daily'"
+    );
+}
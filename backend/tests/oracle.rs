@@ -0,0 +1,150 @@
+use serde_json::json;
+use std::sync::Arc;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::blockchain::{CardanoClient, ValidatorScriptCheck};
+use shipping_oracle::notifier::OracleEvent;
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::state::{MemoryStore, StateStore};
+use shipping_oracle::submitter::{SubmitError, TxSubmitter};
+use shipping_oracle::testing::{
+    ORACLE_ADDRESS, VALIDATOR_SCRIPT_REF, blockfrost_utxos, shippo_track, test_config,
+};
+
+const SCRIPT_HASH: &str = "5f1a5c2bbf2e0d3b1d6a6d0b1a1a8b5e2c6d9e7f0a1b2c3d4e5f6071";
+
+/// Blockfrost and Shippo serving `shipments` tracking UTxOs with `status`, plus the validator script ref
+async fn start_providers(shipments: usize, status: &str) -> MockServer {
+    let server = MockServer::start().await;
+
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(shipments)))
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", status)))
+        .mount(&server)
+        .await;
+
+    let (validator_tx, index) = VALIDATOR_SCRIPT_REF.split_once('#').unwrap();
+    Mock::given(method("GET"))
+        .and(path(format!("/txs/{}/utxos", validator_tx)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "hash": validator_tx,
+            "inputs": [],
+            "outputs": [{
+                "address": ORACLE_ADDRESS,
+                "output_index": index.parse::<u32>().unwrap(),
+                "reference_script_hash": SCRIPT_HASH,
+                "consumed_by_tx": null,
+            }],
+        })))
+        .mount(&server)
+        .await;
+
+    server
+}
+
+struct UnusedSubmitter;
+
+#[async_trait::async_trait]
+impl TxSubmitter for UnusedSubmitter {
+    async fn submit(&self, _signed_tx: Vec<u8>) -> Result<String, SubmitError> {
+        Err(SubmitError::Other("not expected to submit".to_string()))
+    }
+}
+
+#[tokio::test]
+async fn run_once_is_reflected_in_health() {
+    let server = start_providers(3, "TRANSIT").await;
+    let oracle = Oracle::from_config(test_config(&server.uri())).unwrap();
+
+    let health = oracle.health().await;
+    assert!(health.last_run.is_none());
+    assert!(health.is_healthy());
+
+    let stats = oracle.run_once().await.unwrap().stats;
+    assert_eq!((stats.shipments, stats.submitted, stats.failed), (3, 0, 0));
+
+    let health = oracle.health().await;
+    assert_eq!(health.last_run.unwrap().result, Ok(stats));
+    assert_eq!(
+        health.validator_script,
+        Some(ValidatorScriptCheck::Present { script_hash: SCRIPT_HASH.to_string() })
+    );
+}
+
+#[tokio::test]
+async fn aborted_run_is_unhealthy() {
+    let server = MockServer::start().await;
+    let oracle = Oracle::from_config(test_config(&server.uri())).unwrap();
+
+    assert!(oracle.run_once().await.is_err());
+
+    let health = oracle.health().await;
+    assert!(!health.is_healthy());
+    assert!(health.last_run.unwrap().result.is_err());
+}
+
+#[tokio::test]
+async fn scan_and_close_use_the_tracking_utxos() {
+    let server = start_providers(2, "TRANSIT").await;
+    let oracle = Oracle::builder()
+        .config(test_config(&server.uri()))
+        .submitter(UnusedSubmitter)
+        .build()
+        .unwrap();
+
+    let shipments = oracle.scan().await.unwrap();
+    assert_eq!(shipments.len(), 2);
+
+    let error = oracle.close(&format!("{:064x}#5", 0), "DELIVERED").await.unwrap_err();
+    assert!(error.to_string().contains("No tracking UTxO"), "{}", error);
+
+    let utxo_ref = shipments[0].utxo_ref.to_string();
+    let error = oracle.close(&utxo_ref, "TRANSIT").await.unwrap_err();
+    assert!(error.to_string().contains("Status must be one of"), "{}", error);
+}
+
+#[tokio::test]
+async fn subscribers_and_state_see_close_attempts() {
+    // TRP is not mocked, so every close fails to resolve and is reported as failed
+    let server = start_providers(1, "DELIVERED").await;
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    let oracle = Oracle::builder()
+        .config(test_config(&server.uri()))
+        .state(state.clone())
+        .build()
+        .unwrap();
+
+    let mut events = oracle.subscribe();
+    let stats = oracle.run_once().await.unwrap().stats;
+    assert_eq!((stats.submitted, stats.failed), (0, 1));
+
+    let OracleEvent::ShipmentFailed { utxo_ref, status, run_id, .. } = events.try_recv().unwrap() else {
+        panic!("expected a failed close");
+    };
+    assert_eq!(status, "DELIVERED");
+    let last_run = oracle.health().await.last_run.unwrap();
+    assert_eq!(run_id, Some(last_run.run_id));
+    assert_eq!(state.shipment(&utxo_ref).await.unwrap().unwrap().failure_count, 1);
+}
+
+#[test]
+fn builder_rejects_incomplete_or_conflicting_parts() {
+    assert!(Oracle::builder().build().is_err());
+
+    let config = test_config("http://localhost");
+    let error = Oracle::builder()
+        .config(config.clone())
+        .chain(CardanoClient::new(config).unwrap())
+        .submitter(UnusedSubmitter)
+        .build()
+        .err()
+        .unwrap();
+    assert!(error.to_string().contains("either a chain client or a submitter"), "{}", error);
+}
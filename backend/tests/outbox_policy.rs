@@ -0,0 +1,112 @@
+use std::path::Path;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::notifier::OracleEvent;
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::outbox_policy::OutboxPolicy;
+use shipping_oracle::testing::{ORACLE_ADDRESS, OUTBOX_ADDRESS, blockfrost_utxos, shippo_track, test_config};
+
+/// One tracking UTxO into `OUTBOX_ADDRESS`; the carrier must be asked `carrier_requests` times
+async fn serve(carrier_requests: u64) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(1)))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", "TRANSIT")))
+        .expect(carrier_requests)
+        .mount(&server)
+        .await;
+
+    server
+}
+
+fn oracle(server: &MockServer, allowlist: Option<&Path>, denylist: Option<&Path>) -> Oracle {
+    let mut config = test_config(&server.uri());
+    config.outbox_allowlist_file = allowlist.map(|path| path.display().to_string());
+    config.outbox_denylist_file = denylist.map(|path| path.display().to_string());
+    Oracle::builder().config(config).build().unwrap()
+}
+
+fn list(addresses: &[&str]) -> tempfile::NamedTempFile {
+    let file = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(file.path(), format!("# onboarded outboxes\n{}\n", addresses.join("\n"))).unwrap();
+    file
+}
+
+#[tokio::test]
+async fn allowlisted_outboxes_are_tracked() {
+    let server = serve(1).await;
+    let allowlist = list(&[OUTBOX_ADDRESS]);
+    let oracle = oracle(&server, Some(allowlist.path()), None);
+
+    let stats = oracle.run_once().await.unwrap().stats;
+    assert_eq!((stats.shipments, stats.skipped_policy), (1, 0));
+}
+
+#[tokio::test]
+async fn outboxes_missing_from_the_allowlist_are_skipped() {
+    let server = serve(0).await;
+    let allowlist = list(&[ORACLE_ADDRESS]);
+    let oracle = oracle(&server, Some(allowlist.path()), None);
+    let mut events = oracle.subscribe();
+
+    for _ in 0..2 {
+        let stats = oracle.run_once().await.unwrap().stats;
+        assert_eq!((stats.shipments, stats.skipped_policy, stats.failed), (1, 1, 0));
+    }
+
+    // Notified once, not on every run
+    let OracleEvent::ShipmentSkipped { utxo_ref, outbox_address, reason, .. } = events.try_recv().unwrap() else {
+        panic!("expected a skipped shipment");
+    };
+    assert_eq!(utxo_ref, format!("{:064x}#0", 0));
+    assert_eq!(outbox_address, OUTBOX_ADDRESS);
+    assert_eq!(reason, "outbox address is not allowlisted");
+    assert!(events.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn denylisted_outboxes_are_skipped_even_when_allowlisted() {
+    let server = serve(0).await;
+    let allowlist = list(&[OUTBOX_ADDRESS]);
+    let denylist = list(&[OUTBOX_ADDRESS]);
+    let oracle = oracle(&server, Some(allowlist.path()), Some(denylist.path()));
+    let mut events = oracle.subscribe();
+
+    let stats = oracle.run_once().await.unwrap().stats;
+    assert_eq!(stats.skipped_policy, 1);
+    let OracleEvent::ShipmentSkipped { reason, .. } = events.try_recv().unwrap() else {
+        panic!("expected a skipped shipment");
+    };
+    assert_eq!(reason, "outbox address is denylisted");
+}
+
+#[tokio::test]
+async fn edited_lists_apply_from_the_next_run() {
+    let server = serve(1).await;
+    let denylist = list(&[OUTBOX_ADDRESS]);
+    let oracle = oracle(&server, None, Some(denylist.path()));
+    assert_eq!(oracle.run_once().await.unwrap().stats.skipped_policy, 1);
+
+    // An invalid edit keeps the previous list
+    std::fs::write(denylist.path(), "not-an-address\n").unwrap();
+    assert_eq!(oracle.run_once().await.unwrap().stats.skipped_policy, 1);
+
+    std::fs::write(denylist.path(), format!("{}\n", ORACLE_ADDRESS)).unwrap();
+    assert_eq!(oracle.run_once().await.unwrap().stats.skipped_policy, 0);
+}
+
+#[test]
+fn invalid_lists_are_rejected_at_startup() {
+    let file = list(&[OUTBOX_ADDRESS, "addr_test1notanaddress"]);
+    let path = file.path().display().to_string();
+
+    let error = OutboxPolicy::new(Some(&path), None).err().unwrap();
+    assert!(format!("{:#}", error).contains(&format!("{}:3: invalid address", path)), "{:#}", error);
+    assert!(OutboxPolicy::new(None, Some("/nonexistent/denylist.txt")).is_err());
+}
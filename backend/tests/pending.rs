@@ -0,0 +1,155 @@
+use chrono::{DateTime, Duration, Utc};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::lifecycle::{LifecycleTransition, ShipmentLifecycle};
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::state::{MemoryStore, ShipmentState, StateStore};
+use shipping_oracle::submitter::{SubmitError, TxSubmitter};
+use shipping_oracle::testing::{FrozenClock, ORACLE_ADDRESS, blockfrost_utxos, shippo_track, test_config};
+
+const TX_HASH: &str = "c1";
+
+fn utxo_ref(index: usize) -> String {
+    format!("{:064x}#0", index)
+}
+
+fn submitted_at() -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339("2026-03-10T12:00:00Z").unwrap().with_timezone(&Utc)
+}
+
+struct CountingSubmitter(Arc<AtomicUsize>);
+
+#[async_trait::async_trait]
+impl TxSubmitter for CountingSubmitter {
+    async fn submit(&self, _signed_tx: Vec<u8>) -> Result<String, SubmitError> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Ok(TX_HASH.to_string())
+    }
+}
+
+/// `shipments` tracking UTxOs the carrier reports delivered
+async fn serve_delivered(server: &MockServer, shipments: usize) {
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(shipments)))
+        .mount(server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", "DELIVERED")))
+        .mount(server)
+        .await;
+}
+
+/// What a successful close of the first shipment at `submitted_at` leaves in the state store
+async fn seed_submitted(state: &dyn StateStore) {
+    let utxo_ref = utxo_ref(0);
+    let at = submitted_at().timestamp() as u64;
+    for lifecycle in [
+        ShipmentLifecycle::Discovered,
+        ShipmentLifecycle::FinalStatusKnown,
+        ShipmentLifecycle::Submitted { tx_hash: TX_HASH.to_string() },
+    ] {
+        state
+            .record_transition(&LifecycleTransition { utxo_ref: utxo_ref.clone(), state: lifecycle, at })
+            .await
+            .unwrap();
+    }
+    state
+        .save_shipment(&ShipmentState {
+            status: Some("DELIVERED".to_string()),
+            closed_tx_hash: Some(TX_HASH.to_string()),
+            ..ShipmentState::new(&utxo_ref)
+        })
+        .await
+        .unwrap();
+}
+
+fn oracle(server: &MockServer, state: Arc<dyn StateStore>, now: DateTime<Utc>, submissions: &Arc<AtomicUsize>) -> Oracle {
+    Oracle::builder()
+        .config(test_config(&server.uri()))
+        .state(state)
+        .clock(FrozenClock::at(now))
+        .submitter(CountingSubmitter(submissions.clone()))
+        .build()
+        .unwrap()
+}
+
+async fn tracks_requested(server: &MockServer) -> usize {
+    let requests = server.received_requests().await.unwrap();
+    requests.iter().filter(|request| request.url.path().starts_with("/tracks/")).count()
+}
+
+#[tokio::test]
+async fn pending_close_is_not_submitted_again() {
+    let server = MockServer::start().await;
+    serve_delivered(&server, 1).await;
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    seed_submitted(state.as_ref()).await;
+    let submissions = Arc::new(AtomicUsize::new(0));
+    let oracle = oracle(&server, state.clone(), submitted_at() + Duration::minutes(5), &submissions);
+
+    for _ in 0..2 {
+        let stats = oracle.run_once().await.unwrap().stats;
+        assert_eq!((stats.shipments, stats.pending, stats.submitted, stats.failed), (1, 1, 0, 0));
+    }
+    assert_eq!(tracks_requested(&server).await, 0);
+    assert_eq!(submissions.load(Ordering::SeqCst), 0);
+
+    let pending = oracle.data_fetcher().pending_submissions();
+    assert_eq!(pending.len(), 1);
+    assert_eq!((pending[0].utxo_ref.as_str(), pending[0].tx_hash.as_str()), (utxo_ref(0).as_str(), TX_HASH));
+    assert_eq!(pending[0].submitted_at, submitted_at());
+}
+
+#[tokio::test]
+async fn unconfirmed_close_is_polled_again_after_the_ttl() {
+    let server = MockServer::start().await;
+    serve_delivered(&server, 1).await;
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    seed_submitted(state.as_ref()).await;
+    let submissions = Arc::new(AtomicUsize::new(0));
+    let oracle = oracle(&server, state.clone(), submitted_at() + Duration::minutes(31), &submissions);
+
+    let stats = oracle.run_once().await.unwrap().stats;
+    assert_eq!((stats.pending, stats.illegal_transitions), (0, 0));
+    assert_eq!(tracks_requested(&server).await, 1);
+    assert!(oracle.data_fetcher().pending_submissions().is_empty());
+}
+
+#[tokio::test]
+async fn requeued_close_is_not_pending() {
+    let server = MockServer::start().await;
+    serve_delivered(&server, 1).await;
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    seed_submitted(state.as_ref()).await;
+    // As left by `reconcile --requeue`
+    state.save_shipment(&ShipmentState::new(&utxo_ref(0))).await.unwrap();
+    let submissions = Arc::new(AtomicUsize::new(0));
+    let oracle = oracle(&server, state.clone(), submitted_at() + Duration::minutes(5), &submissions);
+
+    assert_eq!(oracle.run_once().await.unwrap().stats.pending, 0);
+    assert_eq!(tracks_requested(&server).await, 1);
+}
+
+#[tokio::test]
+async fn spent_utxo_leaves_the_pending_set() {
+    let server = MockServer::start().await;
+    serve_delivered(&server, 1).await;
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    seed_submitted(state.as_ref()).await;
+    let submissions = Arc::new(AtomicUsize::new(0));
+    let oracle = oracle(&server, state.clone(), submitted_at() + Duration::minutes(5), &submissions);
+
+    oracle.run_once().await.unwrap();
+    assert_eq!(oracle.data_fetcher().pending_submissions().len(), 1);
+
+    // The close confirmed: the tracking UTxO is gone from the oracle address
+    server.reset().await;
+    serve_delivered(&server, 0).await;
+    assert_eq!(oracle.run_once().await.unwrap().stats.shipments, 0);
+    assert!(oracle.data_fetcher().pending_submissions().is_empty());
+}
@@ -0,0 +1,107 @@
+use serde_json::json;
+use std::sync::Arc;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::blockchain::CardanoClient;
+use shipping_oracle::fetcher::DataFetcher;
+use shipping_oracle::privacy::{TrackingLookup, tracking_hash};
+use shipping_oracle::shipment::ShipmentClient;
+use shipping_oracle::state::{MemoryStore, StateStore};
+use shipping_oracle::testing::{ORACLE_ADDRESS, hashed_tracking_datum_cbor, shippo_track, test_config};
+
+const TRACKING_NUMBER: &str = "9400111899223197428490";
+const SALT: &str = "merchant-salt";
+const TRACKING_HASH: &str = "4a922a7abe2a54f23cf43f4e0ec80bfa1d3b9c7094c36119af63d4708451d77c";
+
+fn registered_hash() -> [u8; 32] {
+    tracking_hash("usps", TRACKING_NUMBER, SALT)
+}
+
+#[test]
+fn tracking_hash_is_blake2b_256_of_carrier_number_and_salt() {
+    assert_eq!(hex::encode(registered_hash()), TRACKING_HASH);
+    assert_ne!(tracking_hash("usps", TRACKING_NUMBER, "other-salt"), registered_hash());
+    assert_ne!(tracking_hash("fedex", TRACKING_NUMBER, SALT), registered_hash());
+}
+
+#[tokio::test]
+async fn lookup_file_resolves_registered_hashes() {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    std::io::Write::write_all(
+        &mut file,
+        json!({ TRACKING_HASH: TRACKING_NUMBER }).to_string().as_bytes(),
+    )
+    .unwrap();
+
+    let lookup = TrackingLookup::from_file(file.path()).unwrap();
+    assert_eq!(lookup.resolve(&registered_hash()).await.unwrap().as_deref(), Some(TRACKING_NUMBER));
+    assert_eq!(lookup.resolve(&[0u8; 32]).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn lookup_falls_back_to_state_store() {
+    let state = Arc::new(MemoryStore::new());
+    state.register_tracking(&registered_hash(), "usps", TRACKING_NUMBER).await.unwrap();
+
+    let lookup = TrackingLookup::new().with_state(state);
+    assert_eq!(lookup.resolve(&registered_hash()).await.unwrap().as_deref(), Some(TRACKING_NUMBER));
+    assert_eq!(lookup.resolve(&[0u8; 32]).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn fetcher_resolves_hashes_and_skips_unregistered_ones() {
+    let server = MockServer::start().await;
+    let unregistered = tracking_hash("usps", "9400111899223197428506", SALT);
+
+    let utxos: Vec<_> = [registered_hash(), unregistered]
+        .iter()
+        .enumerate()
+        .map(|(i, hash)| {
+            json!({
+                "address": ORACLE_ADDRESS,
+                "tx_hash": format!("{:064x}", i),
+                "tx_index": 0,
+                "output_index": 0,
+                "amount": [{ "unit": "lovelace", "quantity": "2000000" }],
+                "inline_datum": hashed_tracking_datum_cbor("usps", hash),
+            })
+        })
+        .collect();
+
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(utxos))
+        .mount(&server)
+        .await;
+
+    // Only the plaintext of the registered hash reaches the provider
+    Mock::given(method("GET"))
+        .and(path(format!("/tracks/usps/{}", TRACKING_NUMBER)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", TRACKING_NUMBER, "TRANSIT")))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(404))
+        .expect(0)
+        .mount(&server)
+        .await;
+
+    let config = test_config(&server.uri());
+    let mut lookup = TrackingLookup::new();
+    lookup.insert(registered_hash(), TRACKING_NUMBER.to_string());
+
+    let fetcher = DataFetcher::new(
+        Arc::new(CardanoClient::new(config.clone()).unwrap()),
+        Arc::new(ShipmentClient::new(config).unwrap()),
+    )
+    .with_tracking_lookup(Arc::new(lookup));
+
+    let stats = fetcher.run().await.unwrap().stats;
+    assert_eq!(stats.shipments, 2);
+    assert_eq!(stats.skipped, 1);
+    assert_eq!(stats.failed, 0);
+}
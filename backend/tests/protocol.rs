@@ -0,0 +1,71 @@
+use shipping_oracle::protocol::{binding_params, check_bindings, parse};
+use shipping_oracle::tx3::CloseShipmentParams;
+
+/// Arguments of `close_shipment`; changing them changes what the oracle must send to TRP
+const CLOSE_SHIPMENT_ARGS: [&str; 8] = [
+    "oracle",
+    "oracle_pkh",
+    "outbox",
+    "p_status",
+    "p_timestamp",
+    "p_utxo_ref",
+    "payment",
+    "validator_script_ref",
+];
+
+fn protocol() -> String {
+    std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/../tx3/main.tx3")).unwrap()
+}
+
+fn bindings() -> String {
+    std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/src/tx3.rs")).unwrap()
+}
+
+#[test]
+fn close_shipment_params_match_the_golden_list() {
+    let close = parse(&protocol()).unwrap().into_iter().find(|tx| tx.name == "close_shipment").unwrap();
+    assert_eq!(close.params_struct(), "CloseShipmentParams");
+    assert_eq!(close.args, CLOSE_SHIPMENT_ARGS);
+
+    let params = CloseShipmentParams {
+        oracle: String::new(),
+        oracle_pkh: String::new(),
+        outbox: String::new(),
+        p_status: String::new(),
+        p_timestamp: String::new(),
+        p_utxo_ref: String::new(),
+        payment: String::new(),
+        validator_script_ref: String::new(),
+    };
+    let serialized = serde_json::to_value(params).unwrap();
+    let mut fields: Vec<&str> = serialized.as_object().unwrap().keys().map(String::as_str).collect();
+    fields.sort();
+    assert_eq!(fields, CLOSE_SHIPMENT_ARGS);
+}
+
+#[test]
+fn committed_bindings_match_the_protocol() {
+    check_bindings(&protocol(), &bindings()).unwrap();
+    assert_eq!(binding_params(&bindings()).len(), parse(&protocol()).unwrap().len());
+}
+
+#[test]
+fn renamed_and_new_parameters_are_reported() {
+    let protocol = protocol()
+        .replace("p_status: Bytes,", "p_state: Bytes,")
+        .replace("status: p_status,", "status: p_state,")
+        .replace("p_tracking_number: Bytes,", "p_tracking_number: Bytes,\n    p_reference: Bytes,");
+
+    let error = check_bindings(&protocol, &bindings()).unwrap_err().to_string();
+    assert!(error.contains("tx close_shipment: CloseShipmentParams is missing p_state"), "{}", error);
+    assert!(error.contains("CloseShipmentParams has fields the protocol no longer takes: p_status"), "{}", error);
+    assert!(error.contains("tx track_shipment: TrackShipmentParams is missing p_reference"), "{}", error);
+}
+
+#[test]
+fn new_transactions_need_bindings() {
+    let protocol = format!("{}\ntx cancel_shipment(p_utxo_ref: UtxoRef) {{ input tracking {{ ref: p_utxo_ref, }} }}", protocol());
+
+    let error = check_bindings(&protocol, &bindings()).unwrap_err().to_string();
+    assert!(error.contains("tx cancel_shipment: no CancelShipmentParams in the bindings"), "{}", error);
+}
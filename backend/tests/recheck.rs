@@ -0,0 +1,88 @@
+use serde_json::json;
+use std::sync::Arc;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::notifier::OracleEvent;
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::state::{MemoryStore, StateStore};
+use shipping_oracle::testing::{ORACLE_ADDRESS, blockfrost_utxos, shippo_track, test_config};
+
+const RIVAL_TX: &str = "5e1f0a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d5e6f708192a3b4c5d6e7";
+
+/// One delivered tracking UTxO; by sign time it was spent by `spent_by`
+async fn serve(spent_by: Option<&str>, rechecks: u64) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(1)))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", "DELIVERED")))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path(format!("/txs/{:064x}/utxos", 0)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "outputs": [{
+                "address": ORACLE_ADDRESS,
+                "output_index": 0,
+                "inline_datum": null,
+                "reference_script_hash": null,
+                "consumed_by_tx": spent_by,
+            }],
+        })))
+        .expect(rechecks)
+        .mount(&server)
+        .await;
+
+    server
+}
+
+fn oracle(server: &MockServer, recheck: bool, state: Arc<dyn StateStore>) -> Oracle {
+    let mut config = test_config(&server.uri());
+    config.recheck_before_sign = recheck;
+    Oracle::builder().config(config).state(state).build().unwrap()
+}
+
+#[tokio::test]
+async fn utxo_spent_since_the_scan_is_not_signed() {
+    let server = serve(Some(RIVAL_TX), 1).await;
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    let oracle = oracle(&server, true, state.clone());
+    let mut events = oracle.subscribe();
+
+    let stats = oracle.run_once().await.unwrap().stats;
+    assert_eq!((stats.raced, stats.failed, stats.submitted), (1, 0, 0));
+
+    let OracleEvent::ShipmentSkipped { reason, .. } = events.try_recv().unwrap() else {
+        panic!("expected a skipped shipment");
+    };
+    assert!(reason.contains(RIVAL_TX), "{}", reason);
+    assert_eq!(state.shipment(&format!("{:064x}#0", 0)).await.unwrap().map(|s| s.failure_count), None);
+
+    // Nothing was resolved through TRP
+    let requests = server.received_requests().await.unwrap();
+    assert!(requests.iter().all(|request| request.method.as_str() == "GET"));
+}
+
+#[tokio::test]
+async fn unspent_utxo_goes_on_to_be_closed() {
+    let server = serve(None, 1).await;
+    let oracle = oracle(&server, true, Arc::new(MemoryStore::new()));
+
+    // TRP is not mocked, so the close goes ahead and fails to resolve
+    let stats = oracle.run_once().await.unwrap().stats;
+    assert_eq!((stats.raced, stats.failed), (0, 1));
+}
+
+#[tokio::test]
+async fn recheck_is_off_by_default() {
+    let server = serve(Some(RIVAL_TX), 0).await;
+    let oracle = oracle(&server, false, Arc::new(MemoryStore::new()));
+
+    let stats = oracle.run_once().await.unwrap().stats;
+    assert_eq!((stats.raced, stats.failed), (0, 1));
+}
@@ -0,0 +1,146 @@
+use serde_json::json;
+use std::sync::Arc;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::blockchain::CardanoClient;
+use shipping_oracle::reconcile::{ReconcileReport, ReconcileStatus};
+use shipping_oracle::state::{MemoryStore, ShipmentState, StateStore, Submission};
+use shipping_oracle::testing::{
+    ORACLE_ADDRESS, ORACLE_PKH, OUTBOX_ADDRESS, shipment_datum_cbor, test_config, tracking_datum_cbor,
+};
+
+fn journal() -> Vec<Submission> {
+    serde_json::from_str(include_str!("fixtures/journal.json")).unwrap()
+}
+
+/// Mount a confirmed close tx paying a shipment datum with `status`, signed by `oracle_pkh`, to the outbox
+async fn mount_close_tx(server: &MockServer, tx_hash: &str, status: &str, oracle_pkh: &str) {
+    Mock::given(method("GET"))
+        .and(path(format!("/txs/{}", tx_hash)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "hash": tx_hash, "block_height": 4213337 })))
+        .mount(server)
+        .await;
+
+    Mock::given(method("GET"))
+        .and(path(format!("/txs/{}/utxos", tx_hash)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "hash": tx_hash,
+            "inputs": [],
+            "outputs": [
+                {
+                    "address": OUTBOX_ADDRESS,
+                    "output_index": 0,
+                    "inline_datum": shipment_datum_cbor("usps", "9400111899223197428490", status, 1771090081, oracle_pkh),
+                },
+                { "address": ORACLE_ADDRESS, "output_index": 1, "inline_datum": null },
+            ],
+        })))
+        .mount(server)
+        .await;
+}
+
+/// Chain matching `fixtures/journal.json`: tx 11 confirmed, 22 and 33 never landed,
+/// 44 closed with another status, 55 signed by another key; only tracking UTxO bb is unspent
+async fn start_chain() -> MockServer {
+    let server = MockServer::start().await;
+
+    mount_close_tx(&server, &format!("11{}", "0".repeat(62)), "DELIVERED", ORACLE_PKH).await;
+    mount_close_tx(&server, &format!("44{}", "0".repeat(62)), "DELIVERED", ORACLE_PKH).await;
+    mount_close_tx(&server, &format!("55{}", "0".repeat(62)), "DELIVERED", ORACLE_PKH).await;
+
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "tx_hash": format!("bb{}", "0".repeat(62)),
+            "output_index": 0,
+            "inline_datum": tracking_datum_cbor("usps", "9400111899223197428506"),
+        }])))
+        .mount(&server)
+        .await;
+
+    server
+}
+
+async fn reconcile() -> ReconcileReport {
+    let server = start_chain().await;
+    let client = CardanoClient::new(test_config(&server.uri())).unwrap();
+
+    client.reconcile(&[OUTBOX_ADDRESS.to_string()], &journal()).await.unwrap()
+}
+
+#[tokio::test]
+async fn reconcile_classifies_every_journal_entry() {
+    let report = reconcile().await;
+
+    let statuses: Vec<_> = report.entries.iter().map(|entry| entry.status).collect();
+    assert_eq!(
+        statuses,
+        vec![
+            ReconcileStatus::Confirmed,
+            ReconcileStatus::MissingOnChain,
+            ReconcileStatus::MissingOnChain,
+            ReconcileStatus::DatumMismatch,
+            ReconcileStatus::DatumMismatch,
+        ],
+    );
+    assert_eq!((report.confirmed, report.missing_on_chain, report.datum_mismatch), (1, 2, 2));
+
+    assert_eq!(
+        report.entries[3].detail.as_deref(),
+        Some("status DELIVERED on-chain, NOT_DELIVERED in journal"),
+    );
+    assert!(report.entries[4].detail.as_deref().unwrap().starts_with("signed by 021a8c10"));
+
+    let unspent: Vec<_> = report.entries.iter().map(|entry| entry.tracking_unspent).collect();
+    assert_eq!(unspent, vec![false, true, false, false, false]);
+}
+
+#[tokio::test]
+async fn shipment_datum_must_be_at_an_outbox() {
+    let server = start_chain().await;
+    let client = CardanoClient::new(test_config(&server.uri())).unwrap();
+
+    let report = client.reconcile(&[ORACLE_ADDRESS.to_string()], &journal()[..1]).await.unwrap();
+    assert_eq!(report.entries[0].status, ReconcileStatus::DatumMismatch);
+    assert_eq!(report.entries[0].detail.as_deref(), Some("no shipment datum at an outbox address"));
+}
+
+#[tokio::test]
+async fn requeue_only_reopens_missing_closures_with_unspent_tracking() {
+    let report = reconcile().await;
+    let state = Arc::new(MemoryStore::new());
+
+    for submission in journal() {
+        let mut shipment = ShipmentState::new(&submission.utxo_ref);
+        shipment.status = Some(submission.status.clone());
+        shipment.closed_tx_hash = Some(submission.tx_hash.clone());
+        state.save_shipment(&shipment).await.unwrap();
+    }
+
+    let queued = report.requeue(state.as_ref()).await.unwrap();
+    let resubmittable = format!("bb{}#0", "0".repeat(62));
+    assert_eq!(queued, vec![resubmittable.clone()]);
+
+    let reopened = state.shipment(&resubmittable).await.unwrap().unwrap();
+    assert_eq!(reopened.closed_tx_hash, None);
+    assert_eq!(reopened.status.as_deref(), Some("DELIVERED"));
+
+    let spent = state.shipment(&format!("cc{}#0", "0".repeat(62))).await.unwrap().unwrap();
+    assert!(spent.closed_tx_hash.is_some());
+}
+
+#[tokio::test]
+async fn report_renders_as_json_and_markdown() {
+    let report = reconcile().await;
+
+    let value: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+    assert_eq!(value["missing_on_chain"], 2);
+    assert_eq!(value["entries"][1]["reconcile_status"], "missing_on_chain");
+    assert_eq!(value["entries"][1]["status"], "DELIVERED");
+    assert_eq!(value["entries"][1]["tracking_unspent"], true);
+
+    let markdown = report.to_markdown(None);
+    assert!(markdown.contains("- Missing on-chain: 2\n"), "{}", markdown);
+    assert!(markdown.contains(&format!("| missing_on_chain | `bb{}#0` |", "0".repeat(62))), "{}", markdown);
+}
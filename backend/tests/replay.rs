@@ -0,0 +1,130 @@
+// End-to-end runs against recorded Blockfrost, Shippo and TRP exchanges
+//
+// `tests/fixtures/replay/two_shipments` holds a run over two shipments, one delivered and one
+// in transit. After a change to the requests the oracle makes, record it again with
+// `cargo test --test replay -- --ignored`.
+
+use chrono::DateTime;
+use std::path::Path;
+use std::sync::Arc;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::blockchain::CardanoClient;
+use shipping_oracle::config::Config;
+use shipping_oracle::fetcher::DataFetcher;
+use shipping_oracle::replay::{ReplaySession, body_sha256};
+use shipping_oracle::run_report::{RunReport, ShipmentAction};
+use shipping_oracle::shipment::ShipmentClient;
+use shipping_oracle::signing::{self, sign_envelope_with};
+use shipping_oracle::testing::{
+    CLOSABLE_CLOSED_AT, ClosableShipments, FrozenClock, RecordingSubmitter, close_envelope, closable_tracking_number, shippo_track,
+    test_config,
+};
+
+const RECORDING: &str = "tests/fixtures/replay/two_shipments";
+const SEED: u64 = 0x5eed_0703;
+
+/// One run at `CLOSABLE_CLOSED_AT` with `config`, submitting through `submitter`
+async fn run(config: Config, submitter: RecordingSubmitter) -> RunReport {
+    let blockchain = Arc::new(CardanoClient::with_submitter(config.clone(), Box::new(submitter)).unwrap());
+    DataFetcher::new(blockchain, Arc::new(ShipmentClient::new(config).unwrap()))
+        .with_clock(Arc::new(FrozenClock::at(DateTime::from_timestamp(CLOSABLE_CLOSED_AT, 0).unwrap())))
+        .run()
+        .await
+        .unwrap()
+}
+
+/// Record the two-shipment run into `dir`, against fakes of Blockfrost, Shippo and TRP
+async fn record(dir: &Path) -> RunReport {
+    let chain = ClosableShipments::start(2).await;
+    let shippo = MockServer::start().await;
+    for (i, status) in ["DELIVERED", "TRANSIT"].into_iter().enumerate() {
+        Mock::given(method("GET"))
+            .and(path(format!("/tracks/usps/{}", closable_tracking_number(i))))
+            .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", &closable_tracking_number(i), status)))
+            .mount(&shippo)
+            .await;
+    }
+
+    let config = Config { shippo_url: shippo.uri(), ..chain.config() };
+    let (_session, recording) = ReplaySession::record(&config, dir).await.unwrap();
+    run(recording, RecordingSubmitter::new(SEED, 0)).await
+}
+
+/// Check `report` and the close `submitter` got against the two-shipment scenario
+async fn assert_two_shipment_run(config: &Config, report: &RunReport, submitter: &RecordingSubmitter) {
+    let envelope = close_envelope(0).unwrap();
+    assert_eq!((report.stats.shipments, report.stats.submitted, report.stats.failed), (2, 1, 0));
+    assert_eq!(report.stats.fees_lovelace, 174_257);
+
+    let action = |i: usize| {
+        let tracking_number = closable_tracking_number(i);
+        &report.shipments.iter().find(|shipment| shipment.tracking_number == tracking_number).unwrap().action
+    };
+    assert!(matches!(action(0), ShipmentAction::Submitted { tx_hash } if *tx_hash == envelope.hash), "{:?}", action(0));
+    assert!(matches!(action(1), ShipmentAction::Skipped { .. }), "{:?}", action(1));
+
+    // The close submitted is the recorded TRP envelope, witnessed by the oracle key
+    let signed = sign_envelope_with(&envelope, signing::from_config(config).unwrap().as_ref()).await.unwrap();
+    let submissions = submitter.submissions();
+    assert_eq!(submissions.len(), 1);
+    assert_eq!(submissions[0].inputs, vec![format!("{:064x}#0", 0)]);
+    assert_eq!(submissions[0].cbor, signed.cbor);
+}
+
+#[tokio::test]
+async fn recorded_run_closes_the_delivered_shipment_only() {
+    let config = test_config("http://upstream.invalid");
+    let (session, replay) = ReplaySession::replay(&config, Path::new(RECORDING)).await.unwrap();
+    let submitter = RecordingSubmitter::new(SEED, 0);
+
+    let report = run(replay, submitter.clone()).await;
+
+    assert_eq!(session.misses(), Vec::<String>::new());
+    assert_two_shipment_run(&config, &report, &submitter).await;
+}
+
+#[tokio::test]
+async fn a_recorded_run_replays_to_the_same_report() {
+    let dir = tempfile::tempdir().unwrap();
+    let recorded = record(dir.path()).await;
+
+    let config = test_config("http://upstream.invalid");
+    let (session, replay) = ReplaySession::replay(&config, dir.path()).await.unwrap();
+    let submitter = RecordingSubmitter::new(SEED, 0);
+    let report = run(replay, submitter.clone()).await;
+
+    assert_eq!(session.misses(), Vec::<String>::new());
+    assert_eq!(report.stats, recorded.stats);
+    assert_two_shipment_run(&config, &report, &submitter).await;
+}
+
+#[tokio::test]
+async fn unrecorded_requests_are_refused_and_listed() {
+    let (session, replay) = ReplaySession::replay(&test_config("http://upstream.invalid"), Path::new(RECORDING)).await.unwrap();
+
+    let response = reqwest::get(format!("{}/tracks/usps/9400UNKNOWN", replay.shippo_url)).await.unwrap();
+
+    assert_eq!(response.status(), 501);
+    assert_eq!(session.misses(), vec![format!("shippo GET /tracks/usps/9400UNKNOWN (body sha256 {})", body_sha256(b""))]);
+}
+
+#[test]
+fn request_bodies_match_whatever_their_json_rpc_id_and_key_order() {
+    assert_eq!(
+        body_sha256(br#"{"jsonrpc":"2.0","id":"a","method":"trp.resolve","params":{"b":1,"a":[true,null]}}"#),
+        body_sha256(br#"{ "params": { "a": [true, null], "b": 1 }, "method": "trp.resolve", "id": 7, "jsonrpc": "2.0" }"#),
+    );
+    assert_ne!(body_sha256(br#"{"jsonrpc":"2.0","method":"trp.resolve"}"#), body_sha256(br#"{"jsonrpc":"2.0","method":"trp.submit"}"#));
+}
+
+#[tokio::test]
+#[ignore = "rewrites the recording, run with --ignored"]
+async fn record_the_two_shipment_run() {
+    let dir = Path::new(RECORDING);
+    if dir.exists() {
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    record(dir).await;
+}
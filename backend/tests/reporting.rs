@@ -0,0 +1,68 @@
+use shipping_oracle::config::Network;
+use shipping_oracle::reporting::{Report, ReportRenderer, escape_html};
+
+fn fixture_path(name: &str) -> String {
+    format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+}
+
+fn fixture(name: &str) -> String {
+    let path = fixture_path(name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("missing fixture {}: {}", path, e))
+}
+
+fn report() -> Report {
+    serde_json::from_str(&fixture("report.json")).unwrap()
+}
+
+/// Compare against a golden file, rewriting it instead when `UPDATE_GOLDEN` is set
+fn assert_golden(name: &str, actual: &str) {
+    if std::env::var("UPDATE_GOLDEN").is_ok() {
+        std::fs::write(fixture_path(name), actual).unwrap();
+        return;
+    }
+
+    assert_eq!(actual, fixture(name), "{} is out of date (rerun with UPDATE_GOLDEN=1)", name);
+}
+
+#[test]
+fn markdown_matches_golden() {
+    let rendered = ReportRenderer::new(Some(Network::Preprod)).markdown(&report());
+    assert_golden("report.md", &rendered);
+}
+
+#[test]
+fn html_matches_golden() {
+    let rendered = ReportRenderer::new(Some(Network::Preprod)).html(&report());
+    assert_golden("report.html", &rendered);
+}
+
+#[test]
+fn html_is_self_contained_and_escaped() {
+    let rendered = ReportRenderer::new(None).html(&report());
+
+    assert!(!rendered.contains("<script"));
+    assert!(!rendered.contains("<link"));
+    assert!(!rendered.contains("src="));
+    assert!(rendered.contains("&lt;address unknown&gt; &amp; undeliverable"));
+    assert!(!rendered.contains("<address unknown>"));
+}
+
+#[test]
+fn explorer_links_follow_network() {
+    let report = report();
+    let tx_hash = &report.cases[1].expected_tx_hash.clone().unwrap();
+
+    let mainnet = ReportRenderer::new(Some(Network::Mainnet)).markdown(&report);
+    assert!(mainnet.contains(&format!("(https://cardanoscan.io/transaction/{})", tx_hash)));
+
+    let preview = ReportRenderer::new(Some(Network::Preview)).html(&report);
+    assert!(preview.contains(&format!("href=\"https://preview.cardanoscan.io/transaction/{}\"", tx_hash)));
+
+    let unlinked = ReportRenderer::new(None).markdown(&report);
+    assert!(!unlinked.contains("cardanoscan"));
+}
+
+#[test]
+fn escape_html_covers_markup_characters() {
+    assert_eq!(escape_html("<a href=\"x\">Tom & Jerry's</a>"), "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;");
+}
@@ -0,0 +1,30 @@
+use shipping_oracle::run_id::{self, RUN_ID_LEN};
+
+const CROCKFORD: &str = "0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+#[test]
+fn run_ids_are_ulids_sorted_by_start_time() {
+    let millis = 1_771_090_081_123;
+    let first = run_id::generate_at(millis);
+    let later = run_id::generate_at(millis + 1);
+
+    assert_eq!(first.len(), RUN_ID_LEN);
+    assert!(first.chars().all(|c| CROCKFORD.contains(c)), "{}", first);
+    assert!(first < later, "{} >= {}", first, later);
+    assert_ne!(run_id::generate_at(millis), first);
+
+    assert_eq!(run_id::timestamp(&first), Some(millis));
+    assert_eq!(run_id::timestamp(&first.to_lowercase()), Some(millis));
+    assert_eq!(run_id::timestamp("not-a-run-id"), None);
+}
+
+#[tokio::test]
+async fn current_run_id_is_scoped_to_the_run() {
+    assert_eq!(run_id::current(), None);
+
+    let id = run_id::generate();
+    let seen = run_id::scope(id.clone(), async { run_id::current() }).await;
+    assert_eq!(seen, Some(id));
+
+    assert_eq!(run_id::current(), None);
+}
@@ -0,0 +1,78 @@
+use serde_json::json;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::run_report::ShipmentAction;
+use shipping_oracle::testing::{ORACLE_ADDRESS, blockfrost_utxos, shippo_track, test_config, tracking_number};
+
+/// Three shipments: one in transit, one delivered (closing it fails, TRP is not mocked)
+/// and one whose status cannot be fetched
+async fn serve_shipments() -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(3)))
+        .mount(&server)
+        .await;
+    for (index, status) in [(0, "TRANSIT"), (1, "DELIVERED")] {
+        Mock::given(method("GET"))
+            .and(path(format!("/tracks/usps/{}", tracking_number(index))))
+            .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", &tracking_number(index), status)))
+            .mount(&server)
+            .await;
+    }
+    Mock::given(method("GET"))
+        .and(path(format!("/tracks/usps/{}", tracking_number(2))))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&server)
+        .await;
+
+    server
+}
+
+#[tokio::test]
+async fn report_has_an_entry_per_shipment() {
+    let server = serve_shipments().await;
+    let oracle = Oracle::from_config(test_config(&server.uri())).unwrap();
+
+    let report = oracle.run_once().await.unwrap();
+
+    assert_eq!((report.stats.shipments, report.stats.failed), (3, 2));
+    assert!(!report.succeeded());
+    assert_eq!(report.failures().count(), 2);
+    let entries: Vec<_> = report.shipments.iter().map(|shipment| shipment.tracking_number.as_str()).collect();
+    assert_eq!(entries, [tracking_number(0), tracking_number(1), tracking_number(2)]);
+
+    let transit = &report.shipments[0];
+    assert_eq!(transit.utxo_ref, format!("{:064x}#0", 0));
+    assert_eq!(transit.carrier, "usps");
+    assert_eq!(transit.fetched_status.as_deref(), Some("TRANSIT"));
+    assert_eq!(transit.derived_status, None);
+    assert!(matches!(&transit.action, ShipmentAction::Skipped { reason } if reason == "status is not final"));
+
+    let delivered = &report.shipments[1];
+    assert_eq!(delivered.fetched_status.as_deref(), Some("DELIVERED"));
+    assert_eq!(delivered.derived_status.as_deref(), Some("DELIVERED"));
+    assert!(delivered.failed());
+
+    let unfetched = &report.shipments[2];
+    assert_eq!(unfetched.fetched_status, None);
+    assert!(matches!(&unfetched.action, ShipmentAction::Failed { error } if error.contains("Failed to fetch shipment status")));
+}
+
+#[tokio::test]
+async fn report_serializes_actions_by_type() {
+    let server = serve_shipments().await;
+    let oracle = Oracle::from_config(test_config(&server.uri())).unwrap();
+
+    let report = oracle.run_once().await.unwrap();
+    let value: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+
+    assert_eq!(value["run_id"], json!(oracle.data_fetcher().last_run().unwrap().run_id));
+    assert_eq!(value["stats"]["shipments"], json!(3));
+    assert_eq!(value["shipments"][0]["action"], json!({ "type": "skipped", "reason": "status is not final" }));
+    assert_eq!(value["shipments"][1]["action"]["type"], json!("failed"));
+    assert!(value["shipments"][0]["elapsed_ms"].is_u64());
+    assert!(value.get("tenant").is_none());
+}
@@ -0,0 +1,192 @@
+use serde_json::json;
+use std::time::Duration;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::config::Config;
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::scheduler::{
+    OverlapPolicy, Pipeline, PreviousRunActive, RunMode, execute_fetch_job, run_once, run_scheduler_until,
+};
+use shipping_oracle::testing::{ORACLE_ADDRESS, OUTBOX_ADDRESS, blockfrost_utxos, shippo_track, test_config};
+
+/// How long the startup run takes: the oracle address scan answers this late
+const RUN_TIME: Duration = Duration::from_millis(500);
+
+/// Shutdown requested while the startup run is still scanning
+const SHUTDOWN_AFTER: Duration = Duration::from_millis(100);
+
+async fn slow_oracle(server: &MockServer) -> Oracle {
+    slow_oracle_with(server, test_config(&server.uri())).await
+}
+
+async fn slow_oracle_with(server: &MockServer, config: Config) -> Oracle {
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([])).set_delay(RUN_TIME))
+        .mount(server)
+        .await;
+
+    Oracle::from_config(config).unwrap()
+}
+
+/// Runs started, one oracle address scan each
+async fn scans(server: &MockServer) -> usize {
+    let requests = server.received_requests().await.unwrap();
+    requests.iter().filter(|request| request.url.path().starts_with("/addresses/")).count()
+}
+
+async fn overlapping_pipeline(server: &MockServer, overlap_policy: OverlapPolicy) -> Pipeline {
+    let mut config = test_config(&server.uri());
+    config.overlap_policy = overlap_policy;
+    let oracle = slow_oracle_with(server, config).await;
+
+    Pipeline::for_oracle(&oracle).unwrap()
+}
+
+/// Pipeline for a tenant whose `shipments` tracking UTxOs all report `status`;
+/// TRP is not mocked, so every close fails
+async fn tenant_pipeline(server: &MockServer, tenant: &str, shipments: usize, status: &str) -> Pipeline {
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(shipments)))
+        .mount(server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", status)))
+        .mount(server)
+        .await;
+
+    let mut config = test_config(&server.uri());
+    config.tenant = Some(tenant.to_string());
+    Pipeline::for_oracle(&Oracle::from_config(config).unwrap()).unwrap()
+}
+
+#[tokio::test]
+async fn shutdown_waits_for_the_run_in_flight() {
+    let server = MockServer::start().await;
+    let oracle = slow_oracle(&server).await;
+    let pipelines = vec![Pipeline::for_oracle(&oracle).unwrap()];
+
+    let started = tokio::time::Instant::now();
+    run_scheduler_until(pipelines, Duration::from_secs(10), tokio::time::sleep(SHUTDOWN_AFTER))
+        .await
+        .unwrap();
+
+    assert!(started.elapsed() >= RUN_TIME, "returned after {:?}", started.elapsed());
+    let last_run = oracle.data_fetcher().last_run().expect("the run in flight finished");
+    assert_eq!(last_run.result.unwrap().shipments, 0);
+}
+
+#[tokio::test]
+async fn shutdown_gives_up_after_the_grace_period() {
+    let server = MockServer::start().await;
+    let oracle = slow_oracle(&server).await;
+    let pipelines = vec![Pipeline::for_oracle(&oracle).unwrap()];
+
+    let started = tokio::time::Instant::now();
+    run_scheduler_until(pipelines, Duration::from_millis(50), tokio::time::sleep(SHUTDOWN_AFTER))
+        .await
+        .unwrap();
+
+    assert!(started.elapsed() < RUN_TIME, "returned after {:?}", started.elapsed());
+    assert!(oracle.data_fetcher().last_run().is_none());
+}
+
+#[tokio::test]
+async fn overlapping_trigger_is_skipped() {
+    let server = MockServer::start().await;
+    let pipeline = overlapping_pipeline(&server, OverlapPolicy::Skip).await;
+
+    let (first, second) = tokio::join!(execute_fetch_job(&pipeline), async {
+        tokio::time::sleep(SHUTDOWN_AFTER).await;
+        execute_fetch_job(&pipeline).await
+    });
+
+    assert_eq!(first.unwrap().stats.shipments, 0);
+    assert!(second.unwrap_err().downcast_ref::<PreviousRunActive>().is_some());
+    assert_eq!(scans(&server).await, 1);
+
+    // Once the run is over, the next trigger runs again
+    execute_fetch_job(&pipeline).await.unwrap();
+    assert_eq!(scans(&server).await, 2);
+}
+
+#[tokio::test]
+async fn overlapping_trigger_is_queued() {
+    let server = MockServer::start().await;
+    let pipeline = overlapping_pipeline(&server, OverlapPolicy::Queue).await;
+
+    let started = tokio::time::Instant::now();
+    let (first, second) = tokio::join!(execute_fetch_job(&pipeline), async {
+        tokio::time::sleep(SHUTDOWN_AFTER).await;
+        execute_fetch_job(&pipeline).await
+    });
+
+    first.unwrap();
+    second.unwrap();
+    assert_eq!(scans(&server).await, 2);
+    // The queued run only started once the first had finished
+    assert!(started.elapsed() >= RUN_TIME * 2, "both runs took {:?}", started.elapsed());
+}
+
+#[tokio::test]
+async fn run_once_succeeds_when_nothing_failed() {
+    let server = MockServer::start().await;
+    let pipeline = tenant_pipeline(&server, "acme", 2, "TRANSIT").await;
+
+    let summary = run_once(vec![pipeline]).await;
+
+    assert!(summary.succeeded());
+    assert_eq!(summary.exit_code(), 0);
+    assert_eq!(summary.runs[0].report.as_ref().unwrap().stats.shipments, 2);
+    assert!(summary.to_string().ends_with("✅ Run succeeded"), "{}", summary);
+}
+
+#[tokio::test]
+async fn run_once_fails_when_a_submission_failed() {
+    let (healthy, failing) = (MockServer::start().await, MockServer::start().await);
+    let pipelines = vec![
+        tenant_pipeline(&healthy, "acme", 1, "TRANSIT").await,
+        tenant_pipeline(&failing, "globex", 1, "DELIVERED").await,
+    ];
+
+    let summary = run_once(pipelines).await;
+
+    assert!(!summary.succeeded());
+    assert_eq!(summary.exit_code(), 1);
+    let runs: serde_json::Value = serde_json::from_str(&summary.to_json().unwrap()).unwrap();
+    assert_eq!(runs["runs"][0]["tenant"], json!("acme"));
+    assert_eq!(runs["runs"][0]["report"]["stats"]["failed"], json!(0));
+    assert_eq!(runs["runs"][1]["tenant"], json!("globex"));
+    assert_eq!(runs["runs"][1]["report"]["stats"]["failed"], json!(1));
+    assert_eq!(runs["runs"][1]["report"]["shipments"][0]["action"]["type"], json!("failed"));
+    assert!(summary.to_string().contains("[globex] 1 shipments, 0 submitted, 1 failed"), "{}", summary);
+    assert_eq!(
+        runs["runs"][1]["report"]["outboxes"][OUTBOX_ADDRESS],
+        json!({ "shipments": 1, "closed": 0, "failed": 1, "skipped": 0 })
+    );
+    let subtotal = format!("[globex]   {}: 1 shipments, 0 closed, 1 failed, 0 skipped", OUTBOX_ADDRESS);
+    assert!(summary.to_string().contains(&subtotal), "{}", summary);
+}
+
+#[test]
+fn run_modes_parse_case_insensitively() {
+    assert_eq!("ONCE".parse::<RunMode>().unwrap(), RunMode::Once);
+    assert_eq!(" scheduled ".parse::<RunMode>().unwrap(), RunMode::Scheduled);
+    assert_eq!(
+        "forever".parse::<RunMode>().unwrap_err().to_string(),
+        "expected once or scheduled, got 'forever'"
+    );
+}
+
+#[test]
+fn overlap_policies_parse_case_insensitively() {
+    assert_eq!(" Queue ".parse::<OverlapPolicy>().unwrap(), OverlapPolicy::Queue);
+    assert_eq!("SKIP".parse::<OverlapPolicy>().unwrap(), OverlapPolicy::Skip);
+    assert_eq!(
+        "wait".parse::<OverlapPolicy>().unwrap_err().to_string(),
+        "expected skip or queue, got 'wait'"
+    );
+}
@@ -0,0 +1,113 @@
+use serde_json::json;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::config::Config;
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::self_test::{Outcome, SELF_TEST_CARRIER, SelfTest, Stage};
+use shipping_oracle::testing::{ORACLE_ADDRESS, test_config, tracking_datum_cbor};
+
+const UTXO_REF: &str = "5c1f0a2b3c4d5e6f708192a3b4c5d6e7f8091a2b3c4d5e6f708192a3b4c5d6e7#0";
+
+/// Blockfrost holding one tracking UTxO for `carrier`; Shippo must be asked `carrier_requests` times
+async fn serve(carrier: &str, carrier_requests: u64) -> MockServer {
+    let server = MockServer::start().await;
+    let (tx_hash, _) = UTXO_REF.split_once('#').unwrap();
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!([{
+            "address": ORACLE_ADDRESS,
+            "tx_hash": tx_hash,
+            "tx_index": 0,
+            "output_index": 0,
+            "amount": [{ "unit": "lovelace", "quantity": "12000000" }],
+            "block": tx_hash,
+            "data_hash": null,
+            "inline_datum": tracking_datum_cbor(carrier, "SELFTEST-01"),
+            "reference_script_hash": null,
+        }])))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(404))
+        .expect(carrier_requests)
+        .mount(&server)
+        .await;
+
+    server
+}
+
+fn oracle(server: &MockServer) -> Oracle {
+    Oracle::builder().config(test_config(&server.uri())).build().unwrap()
+}
+
+fn outcome(outcomes: &[(Stage, Outcome)], stage: Stage) -> &Outcome {
+    &outcomes.iter().find(|(candidate, _)| *candidate == stage).unwrap().1
+}
+
+#[tokio::test]
+async fn report_pinpoints_the_failing_stage() {
+    let server = serve(SELF_TEST_CARRIER, 0).await;
+    let oracle = oracle(&server);
+
+    // TRP is not mocked, so resolving the close fails
+    let report = SelfTest::new(&oracle).with_tracking_utxo(UTXO_REF).run().await;
+    assert!(!report.passed());
+    assert_eq!(report.failed_stage(), Some(Stage::Close));
+    assert_eq!(report.stages.iter().map(|(stage, _)| *stage).collect::<Vec<_>>(), Stage::ALL);
+    assert_eq!(outcome(&report.stages, Stage::Provision), &Outcome::Passed(format!("tracking UTxO {}", UTXO_REF)));
+    assert_eq!(outcome(&report.stages, Stage::Discover), &Outcome::Passed("selftest SELFTEST-01 at the oracle address".to_string()));
+    assert_eq!(outcome(&report.stages, Stage::VerifyDatum), &Outcome::Skipped);
+
+    let rendered = report.to_string();
+    assert!(rendered.contains("❌ close: "), "{}", rendered);
+    assert!(rendered.ends_with("Self-test FAILED at close"), "{}", rendered);
+}
+
+#[tokio::test]
+async fn merchant_shipments_are_never_closed() {
+    let server = serve("usps", 0).await;
+    let oracle = oracle(&server);
+
+    let report = SelfTest::new(&oracle).with_tracking_utxo(UTXO_REF).run().await;
+    assert_eq!(report.failed_stage(), Some(Stage::Discover));
+    let Outcome::Failed(error) = outcome(&report.stages, Stage::Discover) else {
+        panic!("expected discovery to fail");
+    };
+    assert_eq!(error, &format!("{} is not a self-test shipment (carrier usps)", UTXO_REF));
+    assert_eq!(outcome(&report.stages, Stage::Close), &Outcome::Skipped);
+}
+
+#[tokio::test]
+async fn missing_tracking_utxo_fails_discovery() {
+    let server = serve(SELF_TEST_CARRIER, 0).await;
+    let oracle = oracle(&server);
+    let other = format!("{:064x}#3", 7);
+
+    let report = SelfTest::new(&oracle).with_tracking_utxo(&other).run().await;
+    assert_eq!(report.failed_stage(), Some(Stage::Discover));
+    assert_eq!(
+        outcome(&report.stages, Stage::Discover),
+        &Outcome::Failed(format!("No tracking UTxO {} at the oracle address", other))
+    );
+}
+
+#[tokio::test]
+async fn scheduled_runs_leave_self_test_shipments_alone() {
+    let server = serve(SELF_TEST_CARRIER, 0).await;
+
+    let stats = oracle(&server).run_once().await.unwrap().stats;
+    assert_eq!((stats.shipments, stats.failed, stats.submitted), (1, 0, 0));
+}
+
+/// Needs a funded `TEST_FUNDING_SK` and the preprod settings in `.env`
+#[tokio::test]
+#[ignore = "submits transactions on preprod"]
+async fn self_test_passes_on_preprod() {
+    dotenvy::dotenv().ok();
+    let oracle = Oracle::from_config(Config::from_env().unwrap()).unwrap();
+
+    let report = SelfTest::new(&oracle).run().await;
+    assert!(report.passed(), "{}", report);
+}
@@ -0,0 +1,134 @@
+use std::sync::Arc;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::lifecycle::ShipmentLifecycle;
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::shipment_import::{ImportOutcome, ImportRow, import_shipments, normalize_carrier, parse_utxo_ref};
+use shipping_oracle::state::{MemoryStore, StateStore};
+use shipping_oracle::testing::{ORACLE_ADDRESS, OUTBOX_ADDRESS, blockfrost_utxos, shippo_track, test_config};
+
+const CSV: &str = include_str!("fixtures/import_shipments.csv");
+const NOW: u64 = 1771090081;
+
+fn utxo_ref(index: usize) -> String {
+    format!("{:064x}#0", index)
+}
+
+/// An oracle whose address holds the first four tracking UTxOs of the fixture, all in transit
+async fn start_oracle(state: Arc<dyn StateStore>) -> (MockServer, Oracle) {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(4)))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", "TRANSIT")))
+        .mount(&server)
+        .await;
+
+    let oracle = Oracle::builder().config(test_config(&server.uri())).state(state).build().unwrap();
+    (server, oracle)
+}
+
+#[test]
+fn rows_are_validated() {
+    assert_eq!(parse_utxo_ref(&format!("{}#3", "AB".repeat(32))).unwrap(), format!("{}#3", "ab".repeat(32)));
+    assert!(parse_utxo_ref("abcd#0").is_err());
+    assert!(parse_utxo_ref(&"ab".repeat(32)).is_err());
+    assert!(parse_utxo_ref(&format!("{}#x", "ab".repeat(32))).is_err());
+
+    assert_eq!(normalize_carrier(" DHL_Express ").unwrap(), "dhl_express");
+    assert!(normalize_carrier("").is_err());
+    assert!(normalize_carrier("u.s.p.s").is_err());
+
+    let row = ImportRow::parse(2, &format!("{}, USPS ,TRK0000000001,{}", utxo_ref(1), OUTBOX_ADDRESS)).unwrap();
+    assert_eq!((row.line, row.carrier.as_str(), row.tracking_number.as_str()), (2, "usps", "TRK0000000001"));
+
+    let error = ImportRow::parse(3, &format!("{},usps,TRK1", utxo_ref(1))).unwrap_err();
+    assert!(error.to_string().contains("expected 4 columns"), "{}", error);
+    let error = ImportRow::parse(3, &format!("{},usps,,{}", utxo_ref(1), OUTBOX_ADDRESS)).unwrap_err();
+    assert!(error.to_string().contains("tracking number is empty"), "{}", error);
+    let error = ImportRow::parse(3, &format!("{},usps,TRK1,addr_test1nope", utxo_ref(1))).unwrap_err();
+    assert!(error.to_string().contains("is not a bech32 address"), "{}", error);
+}
+
+#[tokio::test]
+async fn fixture_rows_are_imported_skipped_or_errored() {
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    let (_server, oracle) = start_oracle(state.clone()).await;
+    let open = oracle.scan().await.unwrap();
+
+    let report = import_shipments(CSV, &open, state.as_ref(), false, NOW).await.unwrap();
+    assert_eq!((report.imported, report.skipped, report.errored), (2, 1, 4));
+
+    let outcomes: Vec<(usize, ImportOutcome)> = report.entries.iter().map(|entry| (entry.line, entry.outcome)).collect();
+    assert_eq!(
+        outcomes,
+        vec![
+            (2, ImportOutcome::Imported),
+            (3, ImportOutcome::Imported),
+            (4, ImportOutcome::Skipped),
+            (5, ImportOutcome::Errored),
+            (6, ImportOutcome::Errored),
+            (7, ImportOutcome::Errored),
+            (8, ImportOutcome::Errored),
+        ]
+    );
+    assert_eq!(
+        report.entries[2].reason.as_deref(),
+        Some("datum mismatch: tracking number is TRK0000000002 on-chain")
+    );
+    assert_eq!(report.entries[6].reason.as_deref(), Some("not an open tracking UTxO at the oracle address"));
+
+    let markdown = report.to_markdown();
+    assert!(markdown.contains("- Imported: 2\n- Skipped: 1\n- Errored: 4\n"), "{}", markdown);
+    assert!(markdown.contains("| 6 | `not-a-utxo-ref` | errored |"), "{}", markdown);
+
+    let lifecycle: Vec<ShipmentLifecycle> =
+        state.lifecycle(&utxo_ref(0)).await.unwrap().into_iter().map(|transition| transition.state).collect();
+    assert_eq!(lifecycle, vec![ShipmentLifecycle::Discovered, ShipmentLifecycle::AwaitingCarrier]);
+    assert!(state.lifecycle(&utxo_ref(2)).await.unwrap().is_empty());
+
+    // Importing again leaves the imported shipments alone
+    let again = import_shipments(CSV, &open, state.as_ref(), false, NOW + 60).await.unwrap();
+    assert_eq!((again.imported, again.skipped, again.errored), (0, 3, 4));
+    assert_eq!(again.entries[0].reason.as_deref(), Some("already tracked as awaiting_carrier"));
+    assert_eq!(state.lifecycle(&utxo_ref(0)).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn force_accepts_mismatched_rows() {
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    let (_server, oracle) = start_oracle(state.clone()).await;
+    let open = oracle.scan().await.unwrap();
+
+    let report = import_shipments(CSV, &open, state.as_ref(), true, NOW).await.unwrap();
+    assert_eq!((report.imported, report.skipped, report.errored), (3, 0, 4));
+    assert_eq!(
+        report.entries[2].reason.as_deref(),
+        Some("forced: datum mismatch: tracking number is TRK0000000002 on-chain")
+    );
+    assert_eq!(state.lifecycle(&utxo_ref(2)).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn imported_shipments_are_not_discovered_again() {
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    let (_server, oracle) = start_oracle(state.clone()).await;
+    let open = oracle.scan().await.unwrap();
+    import_shipments(CSV, &open, state.as_ref(), false, NOW).await.unwrap();
+
+    let stats = oracle.run_once().await.unwrap().stats;
+    assert_eq!((stats.shipments, stats.illegal_transitions), (4, 0));
+
+    // Still in transit: the imported lifecycle carries on, other shipments start from discovery
+    let imported = state.lifecycle(&utxo_ref(0)).await.unwrap();
+    assert_eq!(imported.len(), 2);
+    assert_eq!(imported[0].at, NOW);
+    let discovered = state.lifecycle(&utxo_ref(2)).await.unwrap();
+    assert_eq!(discovered[0].state, ShipmentLifecycle::Discovered);
+    assert!(discovered[0].at > NOW);
+}
@@ -0,0 +1,178 @@
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::America::New_York;
+use chrono_tz::Tz;
+use std::sync::Arc;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::notifier::OracleEvent;
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::shippo_budget::{ShippoBudget, UsageReport, period};
+use shipping_oracle::state::{MemoryStore, NamespacedStore, ShipmentState, SqliteStore, StateStore};
+use shipping_oracle::testing::{FrozenClock, ORACLE_ADDRESS, OUTBOX_ADDRESS, blockfrost_utxos, shippo_track, test_config};
+
+fn at(rfc3339: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+}
+
+fn utxo_ref(index: usize) -> String {
+    format!("{:064x}#0", index)
+}
+
+/// `shipments` tracking UTxOs the carrier reports in transit
+async fn serve_in_transit(shipments: usize) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(shipments)))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", "TRANSIT")))
+        .mount(&server)
+        .await;
+
+    server
+}
+
+fn oracle(server: &MockServer, state: Arc<dyn StateStore>, budget: Option<u64>, now: DateTime<Utc>) -> Oracle {
+    let mut config = test_config(&server.uri());
+    config.cron_timezone = New_York;
+    config.shippo_monthly_budget = budget;
+    Oracle::builder().config(config).state(state).clock(FrozenClock::at(now)).build().unwrap()
+}
+
+async fn tracks_requested(server: &MockServer) -> usize {
+    let requests = server.received_requests().await.unwrap();
+    requests.iter().filter(|request| request.url.path().starts_with("/tracks/")).count()
+}
+
+fn in_transit_since(utxo_ref: &str, since: DateTime<Utc>) -> ShipmentState {
+    ShipmentState {
+        carrier_status: Some("TRANSIT".to_string()),
+        carrier_status_since: Some(since.timestamp() as u64),
+        ..ShipmentState::new(utxo_ref)
+    }
+}
+
+#[test]
+fn periods_roll_over_in_the_configured_timezone() {
+    // 03:00 UTC on March 1st is still February in New York
+    assert_eq!(period(at("2026-03-01T03:00:00Z"), New_York), "2026-02");
+    assert_eq!(period(at("2026-03-01T05:00:00Z"), New_York), "2026-03");
+    assert_eq!(period(at("2026-03-01T03:00:00Z"), Tz::UTC), "2026-03");
+}
+
+#[test]
+fn long_transits_and_unclosed_final_statuses_are_near_final() {
+    let now = at("2026-03-10T12:00:00Z");
+    let budget = ShippoBudget::new(100);
+
+    assert!(budget.is_near_final(Some(&in_transit_since("a#0", now - Duration::days(4))), now));
+    assert!(!budget.is_near_final(Some(&in_transit_since("a#0", now - Duration::hours(1))), now));
+    assert!(!budget.is_near_final(None, now));
+
+    let pre_transit = ShipmentState {
+        carrier_status: Some("PRE_TRANSIT".to_string()),
+        ..in_transit_since("a#0", now - Duration::days(4))
+    };
+    assert!(!budget.is_near_final(Some(&pre_transit), now));
+
+    // A final status whose close failed or was deferred is worth polling again, a closed one is not
+    let unclosed = ShipmentState { status: Some("DELIVERED".to_string()), ..ShipmentState::new("a#0") };
+    assert!(budget.is_near_final(Some(&unclosed), now));
+    let closed = ShipmentState { closed_tx_hash: Some("aa".to_string()), ..unclosed };
+    assert!(!budget.is_near_final(Some(&closed), now));
+
+    let impatient = budget.with_transit_age(Duration::minutes(30));
+    assert!(impatient.is_near_final(Some(&in_transit_since("a#0", now - Duration::hours(1))), now));
+    assert!(budget.is_spent(100) && !budget.is_spent(99));
+}
+
+#[tokio::test]
+async fn tracking_calls_are_counted_per_month_carrier_and_outbox() {
+    let server = serve_in_transit(3).await;
+    let state: Arc<dyn StateStore> = Arc::new(SqliteStore::open_in_memory().unwrap());
+
+    oracle(&server, state.clone(), None, at("2026-02-27T12:00:00Z")).run_once().await.unwrap();
+    oracle(&server, state.clone(), None, at("2026-03-01T03:00:00Z")).run_once().await.unwrap();
+    oracle(&server, state.clone(), None, at("2026-03-01T05:00:00Z")).run_once().await.unwrap();
+
+    let february = UsageReport::from_state(state.as_ref(), "2026-02", Some(10)).await.unwrap();
+    assert_eq!((february.calls, february.budget), (6, Some(10)));
+    assert_eq!(february.by_carrier.get("usps"), Some(&6));
+    assert_eq!(february.by_outbox.get(OUTBOX_ADDRESS), Some(&6));
+    assert!(february.to_markdown().contains("- Calls: 6 of 10"));
+
+    let march = UsageReport::from_state(state.as_ref(), "2026-03", None).await.unwrap();
+    assert_eq!(march.calls, 3);
+
+    // The carrier status and when it was first seen are remembered
+    let shipment = state.shipment(&utxo_ref(0)).await.unwrap().unwrap();
+    assert_eq!(shipment.carrier_status.as_deref(), Some("TRANSIT"));
+    assert_eq!(shipment.carrier_status_since, Some(at("2026-02-27T12:00:00Z").timestamp() as u64));
+}
+
+#[tokio::test]
+async fn spent_budget_polls_only_long_running_transits() {
+    let server = serve_in_transit(3).await;
+    let now = at("2026-03-10T12:00:00Z");
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    for _ in 0..5 {
+        state.record_tracking_call("2026-03", "usps", OUTBOX_ADDRESS).await.unwrap();
+    }
+    state.save_shipment(&in_transit_since(&utxo_ref(0), now - Duration::days(4))).await.unwrap();
+    state.save_shipment(&in_transit_since(&utxo_ref(1), now - Duration::hours(1))).await.unwrap();
+
+    let oracle = oracle(&server, state.clone(), Some(5), now);
+    let mut events = oracle.subscribe();
+    let stats = oracle.run_once().await.unwrap().stats;
+    assert_eq!((stats.shipments, stats.skipped_budget), (3, 2));
+    assert_eq!(tracks_requested(&server).await, 1);
+
+    let OracleEvent::BudgetExceeded { period, calls, budget, .. } = events.try_recv().unwrap() else {
+        panic!("expected a budget exceeded event");
+    };
+    assert_eq!((period.as_str(), calls, budget), ("2026-03", 5, 5));
+    assert!(events.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn budget_alert_is_sent_once_per_period_across_restarts() {
+    let server = serve_in_transit(1).await;
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    state.record_tracking_call("2026-03", "usps", OUTBOX_ADDRESS).await.unwrap();
+
+    let mut alerts = 0;
+    for now in ["2026-03-10T12:00:00Z", "2026-03-11T12:00:00Z"] {
+        // A fresh oracle per run, as after a restart
+        let oracle = oracle(&server, state.clone(), Some(1), at(now));
+        let mut events = oracle.subscribe();
+        assert_eq!(oracle.run_once().await.unwrap().stats.skipped_budget, 1);
+        alerts += std::iter::from_fn(|| events.try_recv().ok()).count();
+    }
+    assert_eq!(alerts, 1);
+    assert_eq!(tracks_requested(&server).await, 0);
+
+    // A new month starts with a fresh budget
+    let oracle = oracle(&server, state.clone(), Some(1), at("2026-04-01T12:00:00Z"));
+    assert_eq!(oracle.run_once().await.unwrap().stats.skipped_budget, 0);
+    assert_eq!(tracks_requested(&server).await, 1);
+}
+
+#[tokio::test]
+async fn tenants_count_their_own_calls() {
+    let inner: Arc<dyn StateStore> = Arc::new(SqliteStore::open_in_memory().unwrap());
+    let acme = NamespacedStore::new(inner.clone(), "acme");
+    let globex = NamespacedStore::new(inner.clone(), "globex");
+
+    acme.record_tracking_call("2026-03", "usps", OUTBOX_ADDRESS).await.unwrap();
+    acme.record_tracking_call("2026-03", "usps", OUTBOX_ADDRESS).await.unwrap();
+    globex.record_tracking_call("2026-03", "ups", OUTBOX_ADDRESS).await.unwrap();
+
+    let calls = acme.tracking_calls("2026-03").await.unwrap();
+    assert_eq!(calls.len(), 1);
+    assert_eq!((calls[0].period.as_str(), calls[0].carrier.as_str(), calls[0].calls), ("2026-03", "usps", 2));
+    assert!(inner.tracking_calls("2026-03").await.unwrap().is_empty());
+}
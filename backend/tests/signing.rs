@@ -0,0 +1,449 @@
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use pallas::ledger::{addresses::Address, traverse::MultiEraTx};
+use proptest::prelude::*;
+use serde::Deserialize;
+use tx3_sdk::trp::TxEnvelope;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use shipping_oracle::blockchain::{CardanoClient, ClosedShipment, FeeExceeded};
+use shipping_oracle::config::Config;
+use shipping_oracle::signing::{
+    EnvelopeHashMismatch, LocalSigner, OracleKeyring, SignatureBytes, SignerKind, SigningKeyMaterial, TxSigner,
+    VerifyingKeyBytes, envelope_fee, sign_envelope,
+};
+use shipping_oracle::submitter::{SubmitError, TxSubmitter};
+use shipping_oracle::testing::{ORACLE_ADDRESS, VALIDATOR_SCRIPT_REF, test_config};
+
+/// RFC 8032 test vector 1 secret key, never used on any network
+const THROWAWAY_SK: &str = "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60";
+const THROWAWAY_VK: &str = "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a";
+const THROWAWAY_PKH: &str = "35dedd2982a03cf39e7dce03c839994ffdec2ec6b04f1cf2d40e61a3";
+
+/// RFC 8032 test vector 2, standing in for a rotated-in key
+const ROTATED_SK: &str = "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb";
+const ROTATED_PKH: &str = "977efb35ab621d39dbeb7274ec7795a34708ff4d25a01a1df04c1f27";
+
+/// CIP-19 test vector mnemonic, whose `1852'/1815'/0'/0/0` key pays `addr_test1qz2fxv2umyhttkxyxp8x0dlpdt3k6cwng5pxj3jhsydzer3jcu5d8ps7zex2k2xt3uqxgjqnnj83ws8lhrn648jjxtwq2ytjqp`
+const CIP19_MNEMONIC: &str = "test walk nut penalty hip pave soap entry language right filter choice";
+const CIP19_VK: &str = "73fea80d424276ad0978d4fe5310e8bc2d485f5f6bb3bf87612989f112ad5a7d";
+const CIP19_PKH: &str = "9493315cd92eb5d8c4304e67b7e16ae36d61d34502694657811a2c8e";
+
+const OTHER_VALIDATOR_REF: &str = "1f0c8d7e6b5a49382716f5e4d3c2b1a09f8e7d6c5b4a39281706f5e4d3c2b1a0#0";
+
+#[derive(Deserialize)]
+struct EnvelopeFixture {
+    tx: String,
+    hash: String,
+}
+
+fn fixture(name: &str) -> String {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("missing fixture {}: {}", path, e))
+}
+
+fn envelope() -> TxEnvelope {
+    envelope_fixture("close_shipment_envelope.json")
+}
+
+fn envelope_fixture(name: &str) -> TxEnvelope {
+    let fixture: EnvelopeFixture = serde_json::from_str(&fixture(name)).unwrap();
+    TxEnvelope {
+        tx: fixture.tx,
+        hash: fixture.hash,
+    }
+}
+
+/// Counts submissions and accepts every transaction
+struct CountingSubmitter {
+    calls: Arc<AtomicUsize>,
+}
+
+#[async_trait::async_trait]
+impl TxSubmitter for CountingSubmitter {
+    async fn submit(&self, _signed_tx: Vec<u8>) -> Result<String, SubmitError> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Ok(envelope().hash)
+    }
+}
+
+/// Keeps every transaction it is handed
+struct RecordingSubmitter {
+    submitted: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+#[async_trait::async_trait]
+impl TxSubmitter for RecordingSubmitter {
+    async fn submit(&self, signed_tx: Vec<u8>) -> Result<String, SubmitError> {
+        self.submitted.lock().unwrap().push(signed_tx);
+        Ok(envelope().hash)
+    }
+}
+
+/// Answers every request with the same made-up witness, like an external signer would
+struct FixedSigner;
+
+#[async_trait::async_trait]
+impl TxSigner for FixedSigner {
+    fn pkh(&self) -> String {
+        ROTATED_PKH.to_string()
+    }
+
+    async fn sign(&self, _tx_hash: &[u8]) -> anyhow::Result<(VerifyingKeyBytes, SignatureBytes)> {
+        Ok(([7; 32], [9; 64]))
+    }
+}
+
+fn counting_client() -> (CardanoClient, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let submitter = Box::new(CountingSubmitter { calls: calls.clone() });
+    let client = CardanoClient::with_submitter(test_config("http://localhost"), submitter).unwrap();
+
+    (client, calls)
+}
+
+fn witnesses(cbor: &[u8]) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let tx = MultiEraTx::decode(cbor).unwrap();
+    let tx = tx.as_conway().unwrap();
+
+    tx.transaction_witness_set
+        .vkeywitness
+        .iter()
+        .flat_map(|set| set.iter())
+        .map(|w| (w.vkey.to_vec(), w.signature.to_vec()))
+        .collect()
+}
+
+#[test]
+fn sign_envelope_matches_golden_vector() {
+    let key = SigningKeyMaterial::from_hex(THROWAWAY_SK).unwrap();
+    assert_eq!(hex::encode(key.public_key()), THROWAWAY_VK);
+
+    let envelope = envelope();
+    let signed = sign_envelope(&envelope, &key).unwrap();
+
+    assert_eq!(signed.hash, envelope.hash);
+    assert_eq!(signed.to_hex(), fixture("close_shipment_signed.hex").trim());
+}
+
+#[test]
+fn sign_envelope_rejects_malformed_input() {
+    let key = SigningKeyMaterial::from_hex(THROWAWAY_SK).unwrap();
+
+    let bad_hash = TxEnvelope { hash: "not-hex".to_string(), ..envelope() };
+    assert!(sign_envelope(&bad_hash, &key).is_err());
+
+    let short_hash = TxEnvelope { hash: "abcd".to_string(), ..envelope() };
+    assert!(sign_envelope(&short_hash, &key).is_err());
+
+    let bad_tx = TxEnvelope { tx: "84a0".to_string(), ..envelope() };
+    assert!(sign_envelope(&bad_tx, &key).is_err());
+}
+
+#[test]
+fn tampered_envelope_hash_is_not_signed() {
+    let key = SigningKeyMaterial::from_hex(THROWAWAY_SK).unwrap();
+    assert!(sign_envelope(&envelope(), &key).is_ok());
+
+    // The hash of another transaction, as a compromised TRP server would send it
+    let other_hash = envelope_fixture("close_shipment_envelope_valid.json").hash;
+    let tampered = TxEnvelope { hash: other_hash.clone(), ..envelope() };
+    let error = sign_envelope(&tampered, &key).unwrap_err();
+
+    assert_eq!(
+        error.downcast_ref::<EnvelopeHashMismatch>(),
+        Some(&EnvelopeHashMismatch { envelope_hash: other_hash, body_hash: envelope().hash }),
+    );
+}
+
+#[test]
+fn signing_key_material_rejects_bad_keys() {
+    assert!(SigningKeyMaterial::from_hex("zz").is_err());
+    assert!(SigningKeyMaterial::from_hex(&"00".repeat(31)).is_err());
+    assert!(!format!("{:?}", SigningKeyMaterial::from_hex(THROWAWAY_SK).unwrap()).contains(THROWAWAY_SK));
+}
+
+#[test]
+fn malformed_keys_fail_at_startup() {
+    let mut config = test_config("http://localhost");
+    config.oracle_sks = vec![THROWAWAY_SK.into(), "00".repeat(31).into()];
+
+    let Err(err) = CardanoClient::new(config) else { panic!("client built with a 31-byte key") };
+    assert_eq!(format!("{:#}", err), "Invalid oracle signing key #2: Signing key must be 32 bytes, got 31");
+}
+
+#[test]
+fn pkh_is_blake2b_224_of_public_key() {
+    assert_eq!(SigningKeyMaterial::from_hex(THROWAWAY_SK).unwrap().pkh(), THROWAWAY_PKH);
+    assert_eq!(SigningKeyMaterial::from_hex(ROTATED_SK).unwrap().pkh(), ROTATED_PKH);
+}
+
+#[test]
+fn keyring_rejects_duplicate_keys() {
+    let keys = vec![
+        SigningKeyMaterial::from_hex(THROWAWAY_SK).unwrap(),
+        SigningKeyMaterial::from_hex(&THROWAWAY_SK.to_uppercase()).unwrap(),
+    ];
+
+    let err = OracleKeyring::new(keys).unwrap_err();
+    assert!(err.to_string().contains(THROWAWAY_PKH), "{}", err);
+    assert!(OracleKeyring::new(Vec::new()).is_err());
+}
+
+#[test]
+fn single_key_config_keeps_working() {
+    let mut config = test_config("http://localhost");
+    config.oracle_sks = vec![THROWAWAY_SK.into()];
+    config.oracle_pkh = Some(THROWAWAY_PKH.to_string());
+
+    let keyring = OracleKeyring::from_config(&config).unwrap();
+    assert_eq!(keyring.select(VALIDATOR_SCRIPT_REF).pkh(), THROWAWAY_PKH);
+
+    config.oracle_pkh = Some(ROTATED_PKH.to_string());
+    assert!(OracleKeyring::from_config(&config).is_err());
+}
+
+#[test]
+fn oracle_pkh_must_match_a_signing_key_at_startup() {
+    let mut config = test_config("http://localhost");
+    config.oracle_sks = vec![THROWAWAY_SK.into()];
+
+    config.oracle_pkh = Some(THROWAWAY_PKH.to_string());
+    assert_eq!(CardanoClient::new(config.clone()).unwrap().signer_pkh(), THROWAWAY_PKH);
+
+    config.oracle_pkh = Some(ROTATED_PKH.to_string());
+    let Err(err) = CardanoClient::new(config) else { panic!("client built with a foreign ORACLE_PKH") };
+    assert!(format!("{:#}", err).starts_with("ORACLE_PKH does not match any oracle signing key"), "{:#}", err);
+}
+
+#[test]
+fn oracle_addresses_must_share_a_network() {
+    let mut mainnet = Address::from_bech32(ORACLE_ADDRESS).unwrap().to_vec();
+    mainnet[0] |= 0x01;
+    let mainnet = Address::from_bytes(&mainnet).unwrap().to_bech32().unwrap();
+
+    let mut config = test_config("http://localhost");
+    assert!(CardanoClient::new(config.clone()).is_ok());
+
+    config.oracle_payment_address = mainnet;
+    let Err(err) = CardanoClient::new(config.clone()) else { panic!("client built with mixed networks") };
+    assert_eq!(
+        err.to_string(),
+        "ORACLE_ADDRESS is a testnet address but ORACLE_PAYMENT_ADDRESS is a mainnet address"
+    );
+
+    config.oracle_payment_address = "not-an-address".to_string();
+    let Err(err) = CardanoClient::new(config) else { panic!("client built with an invalid address") };
+    assert!(err.to_string().starts_with("ORACLE_PAYMENT_ADDRESS is not a bech32 address"), "{}", err);
+}
+
+#[test]
+fn keyring_selects_key_per_validator() {
+    let mut config = test_config("http://localhost");
+    config.oracle_sks = vec![THROWAWAY_SK.into(), ROTATED_SK.into()];
+    config.oracle_validator_keys.insert(OTHER_VALIDATOR_REF.to_string(), ROTATED_PKH.to_string());
+
+    let keyring = OracleKeyring::from_config(&config).unwrap();
+    assert_eq!(keyring.pkhs(), vec![THROWAWAY_PKH, ROTATED_PKH]);
+    assert_eq!(keyring.select(VALIDATOR_SCRIPT_REF).pkh(), THROWAWAY_PKH);
+    assert_eq!(keyring.select(OTHER_VALIDATOR_REF).pkh(), ROTATED_PKH);
+
+    config.validator_script_ref = OTHER_VALIDATOR_REF.parse().unwrap();
+    assert_eq!(CardanoClient::new(config).unwrap().signer_pkh(), ROTATED_PKH);
+}
+
+#[test]
+fn keyring_loads_keys_from_files() {
+    let mut key_file = tempfile::NamedTempFile::new().unwrap();
+    std::io::Write::write_all(&mut key_file, format!("{}\n", ROTATED_SK).as_bytes()).unwrap();
+
+    let mut config = test_config("http://localhost");
+    config.oracle_sks = vec![THROWAWAY_SK.into(), key_file.path().display().to_string().into()];
+    config.oracle_pkh = Some(ROTATED_PKH.to_string());
+
+    let keyring = OracleKeyring::from_config(&config).unwrap();
+    assert_eq!(keyring.select(VALIDATOR_SCRIPT_REF).pkh(), ROTATED_PKH);
+}
+
+/// A cardano-cli `payment.skey` holding `hex_key`
+fn skey_file(key_type: &str, hex_key: &str) -> tempfile::NamedTempFile {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    let envelope = serde_json::json!({
+        "type": key_type,
+        "description": "Payment Signing Key",
+        "cborHex": format!("5820{}", hex_key),
+    });
+    std::io::Write::write_all(&mut file, envelope.to_string().as_bytes()).unwrap();
+    file
+}
+
+#[tokio::test]
+async fn mnemonic_derives_the_cip1852_payment_key() {
+    let key = SigningKeyMaterial::from_mnemonic(CIP19_MNEMONIC).unwrap();
+    assert_eq!((hex::encode(key.public_key()), key.pkh()), (CIP19_VK.to_string(), CIP19_PKH.to_string()));
+
+    let hash = hex::decode(envelope().hash).unwrap();
+    let (vkey, signature) = LocalSigner::new(key).sign(&hash).await.unwrap();
+    let verifying_key = VerifyingKey::from_bytes(&vkey).unwrap();
+    assert!(verifying_key.verify(&hash, &Signature::from_bytes(&signature)).is_ok());
+
+    let err = SigningKeyMaterial::from_mnemonic("test walk nut penalty hip pave soap entry language right filter filter")
+        .unwrap_err();
+    assert!(err.to_string().starts_with("Invalid mnemonic"), "{}", err);
+}
+
+#[test]
+fn mnemonic_keys_sign_envelopes() {
+    let mut config = test_config("http://localhost");
+    config.oracle_sks = Vec::new();
+    config.oracle_mnemonic = Some(CIP19_MNEMONIC.into());
+
+    let keyring = OracleKeyring::from_config(&config).unwrap();
+    let key = keyring.select(VALIDATOR_SCRIPT_REF);
+    let signed = sign_envelope(&envelope(), key).unwrap();
+
+    let (vkey, signature) = witnesses(&signed.cbor).remove(0);
+    assert_eq!(hex::encode(&vkey), CIP19_VK);
+    let verifying_key = VerifyingKey::from_bytes(vkey.as_slice().try_into().unwrap()).unwrap();
+    let hash = hex::decode(envelope().hash).unwrap();
+    assert!(verifying_key.verify(&hash, &Signature::from_slice(&signature).unwrap()).is_ok());
+}
+
+#[test]
+fn keyring_loads_cardano_cli_skey_files() {
+    let file = skey_file("PaymentSigningKeyShelley_ed25519", THROWAWAY_SK);
+    let mut config = test_config("http://localhost");
+    config.oracle_sks = Vec::new();
+    config.oracle_sk_file = Some(file.path().display().to_string());
+
+    assert_eq!(CardanoClient::new(config.clone()).unwrap().signer_pkh(), THROWAWAY_PKH);
+
+    let extended = skey_file("PaymentExtendedSigningKeyShelley_ed25519_bip32", THROWAWAY_SK);
+    config.oracle_sk_file = Some(extended.path().display().to_string());
+    let err = OracleKeyring::from_config(&config).unwrap_err();
+    assert_eq!(
+        format!("{:#}", err),
+        "Invalid ORACLE_SK_FILE: Signing key file has type PaymentExtendedSigningKeyShelley_ed25519_bip32, \
+         expected PaymentSigningKeyShelley_ed25519"
+    );
+}
+
+#[test]
+fn exactly_one_key_source_is_configured() {
+    let base = [
+        ("SHIPPO_API_KEY", "shippo_test_key"),
+        ("VALIDATOR_SCRIPT_REF", VALIDATOR_SCRIPT_REF),
+        ("ORACLE_ADDRESS", ORACLE_ADDRESS),
+        ("ORACLE_PAYMENT_ADDRESS", ORACLE_ADDRESS),
+        ("BLOCKFROST_URL", "http://localhost"),
+        ("TRP_URL", "http://localhost"),
+    ];
+    let config = |sources: &[(&'static str, &'static str)]| {
+        let vars: HashMap<&str, &str> = base.iter().chain(sources).copied().collect();
+        Config::from_vars(|key| vars.get(key).map(|value| value.to_string()))
+    };
+
+    let mnemonic = config(&[("ORACLE_MNEMONIC", "  test walk nut penalty hip pave soap entry language right filter choice\n")]).unwrap();
+    assert_eq!(mnemonic.oracle_mnemonic.unwrap().expose(), CIP19_MNEMONIC);
+    assert!(config(&[("ORACLE_SK", THROWAWAY_SK)]).is_ok());
+
+    let err = config(&[]).unwrap_err();
+    assert_eq!(err.to_string(), "No oracle signing key: set ORACLE_SKS (or ORACLE_SK), ORACLE_SK_FILE or ORACLE_MNEMONIC");
+    let err = config(&[("ORACLE_SK", THROWAWAY_SK), ("ORACLE_MNEMONIC", CIP19_MNEMONIC)]).unwrap_err();
+    assert_eq!(err.to_string(), "Set only one of ORACLE_SKS (or ORACLE_SK), ORACLE_SK_FILE and ORACLE_MNEMONIC");
+}
+
+#[test]
+fn envelope_fee_reads_the_body_fee() {
+    assert_eq!(envelope_fee(&envelope()).unwrap(), 174_257);
+    assert_eq!(envelope_fee(&envelope_fixture("close_shipment_envelope_inflated_fee.json")).unwrap(), 100_000_000);
+}
+
+#[tokio::test]
+async fn normal_fee_is_signed_and_submitted() {
+    let (client, calls) = counting_client();
+
+    let closed = client.submit_envelope(&envelope()).await.unwrap();
+    assert_eq!(closed, ClosedShipment { tx_hash: envelope().hash, fee: 174_257 });
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn injected_submitter_receives_the_signed_cbor() {
+    let config = test_config("http://localhost");
+    let submitted = Arc::new(Mutex::new(Vec::new()));
+    let submitter = Box::new(RecordingSubmitter { submitted: submitted.clone() });
+    let client = CardanoClient::with_submitter(config.clone(), submitter).unwrap();
+
+    client.submit_envelope(&envelope()).await.unwrap();
+
+    let keyring = OracleKeyring::from_config(&config).unwrap();
+    let signed = sign_envelope(&envelope(), keyring.select(VALIDATOR_SCRIPT_REF)).unwrap();
+    assert_eq!(*submitted.lock().unwrap(), vec![signed.cbor]);
+}
+
+#[tokio::test]
+async fn inflated_fee_never_reaches_the_submitter() {
+    let (client, calls) = counting_client();
+
+    let error = client
+        .submit_envelope(&envelope_fixture("close_shipment_envelope_inflated_fee.json"))
+        .await
+        .unwrap_err();
+
+    assert_eq!(
+        error.downcast_ref::<FeeExceeded>(),
+        Some(&FeeExceeded { fee: 100_000_000, max_fee: 2_000_000 }),
+    );
+    assert_eq!(calls.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn signer_kinds_parse_case_insensitively() {
+    assert_eq!("KMS".parse::<SignerKind>().unwrap(), SignerKind::Kms);
+    assert_eq!(" local ".parse::<SignerKind>().unwrap(), SignerKind::Local);
+    assert_eq!("hsm".parse::<SignerKind>().unwrap_err().to_string(), "expected local or kms, got 'hsm'");
+    assert_eq!(SignerKind::default(), SignerKind::Local);
+}
+
+#[tokio::test]
+async fn injected_signer_provides_the_witness() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/tx/submit"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(envelope().hash))
+        .expect(1)
+        .mount(&server)
+        .await;
+
+    let client = CardanoClient::with_signer(test_config(&server.uri()), Box::new(FixedSigner)).unwrap();
+    assert_eq!(client.signer_pkh(), ROTATED_PKH);
+    client.submit_envelope(&envelope()).await.unwrap();
+
+    let requests = server.received_requests().await.unwrap();
+    assert_eq!(witnesses(&requests[0].body), vec![(vec![7; 32], vec![9; 64])]);
+}
+
+proptest! {
+    #[test]
+    fn signed_witness_verifies_against_envelope_hash(sk in any::<[u8; 32]>()) {
+        let key = SigningKeyMaterial::from_bytes(&sk);
+        let envelope = envelope();
+        let signed = sign_envelope(&envelope, &key).unwrap();
+
+        let witnesses = witnesses(&signed.cbor);
+        prop_assert_eq!(witnesses.len(), 1);
+
+        let (vkey, signature) = &witnesses[0];
+        prop_assert_eq!(vkey.clone(), key.public_key().to_vec());
+
+        let verifying_key = VerifyingKey::from_bytes(vkey.as_slice().try_into().unwrap()).unwrap();
+        let signature = Signature::from_slice(signature).unwrap();
+        let hash = hex::decode(&envelope.hash).unwrap();
+        prop_assert!(verifying_key.verify(&hash, &signature).is_ok());
+    }
+}
@@ -0,0 +1,171 @@
+// Soak tests of `DataFetcher::run` over hundreds of shipments and flaky fakes
+//
+// Ignored by default; run with `cargo test --test soak -- --ignored`.
+
+use chrono::DateTime;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use shipping_oracle::blockchain::CardanoClient;
+use shipping_oracle::fetcher::{DataFetcher, RECENT_RUNS};
+use shipping_oracle::testing::{
+    CLOSABLE_CLOSED_AT, ClosableShipments, FlakyProvider, FrozenClock, ProviderOutcome, RecordingSubmitter,
+};
+
+const SHIPMENTS: usize = 500;
+const RUNS: usize = 5;
+const SEED: u64 = 0x5eed_0704;
+/// Share of tracking calls failing, transiently or not
+const FAILURE_PERCENT: u64 = 20;
+/// Share of submissions answered as already on-chain
+const DUPLICATE_PERCENT: u64 = 5;
+const MAX_LATENCY: Duration = Duration::from_millis(20);
+const CONCURRENCY: usize = 16;
+/// Wall-clock budget of one run
+const RUN_BUDGET: Duration = Duration::from_secs(30);
+const SUBMIT_LATENCY: Duration = Duration::from_millis(5);
+
+/// Fetcher over fresh fakes of `shipments` closable shipments, checking `concurrency` and closing `close_concurrency` at once
+async fn soak_fetcher(
+    shipments: usize,
+    provider: Arc<FlakyProvider>,
+    submitter: RecordingSubmitter,
+    concurrency: usize,
+    close_concurrency: usize,
+) -> (ClosableShipments, DataFetcher) {
+    let chain = ClosableShipments::start(shipments).await;
+    let blockchain = Arc::new(CardanoClient::with_submitter(chain.config(), Box::new(submitter)).unwrap());
+    let fetcher = DataFetcher::new(blockchain, provider)
+        .with_clock(Arc::new(FrozenClock::at(DateTime::from_timestamp(CLOSABLE_CLOSED_AT, 0).unwrap())))
+        .with_concurrency(concurrency)
+        .with_close_concurrency(close_concurrency);
+    (chain, fetcher)
+}
+
+/// Wall-clock time of one run over the soak fakes, and every transaction it submitted with its answer
+async fn timed_run(concurrency: usize, close_concurrency: usize) -> (Duration, BTreeSet<(String, bool)>) {
+    let provider = Arc::new(FlakyProvider::new(SEED, FAILURE_PERCENT, "DELIVERED").with_max_latency(MAX_LATENCY));
+    let submitter = RecordingSubmitter::new(SEED, DUPLICATE_PERCENT).with_latency(SUBMIT_LATENCY);
+    let (_chain, fetcher) = soak_fetcher(SHIPMENTS, provider, submitter.clone(), concurrency, close_concurrency).await;
+
+    let started = Instant::now();
+    fetcher.run().await.unwrap();
+    let elapsed = started.elapsed();
+    let submitted = submitter.submissions().into_iter().map(|submission| (submission.tx_hash, submission.accepted)).collect();
+    (elapsed, submitted)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "soak test, run with --ignored"]
+async fn hundreds_of_shipments_survive_flaky_providers() {
+    let provider = Arc::new(FlakyProvider::new(SEED, FAILURE_PERCENT, "DELIVERED").with_max_latency(MAX_LATENCY));
+    let submitter = RecordingSubmitter::new(SEED, DUPLICATE_PERCENT);
+    let (chain, fetcher) = soak_fetcher(SHIPMENTS, provider.clone(), submitter.clone(), CONCURRENCY, CONCURRENCY).await;
+
+    let mut accepted_before = 0;
+    for run in 0..RUNS {
+        let (calls_before, submissions_before) = (provider.calls().len(), submitter.submissions().len());
+        let started = Instant::now();
+        let report = fetcher.run().await.unwrap();
+        let elapsed = started.elapsed();
+        assert!(elapsed < RUN_BUDGET, "run {} took {:?}", run, elapsed);
+
+        let calls = provider.calls().split_off(calls_before);
+        let submissions = submitter.submissions().split_off(submissions_before);
+        let stats = report.stats;
+
+        // A shipment is asked twice in a run only when the first call was rate limited
+        let mut per_shipment: HashMap<&str, Vec<ProviderOutcome>> = HashMap::new();
+        for call in &calls {
+            per_shipment.entry(call.tracking_number.as_str()).or_default().push(call.outcome);
+        }
+        for (tracking_number, outcomes) in &per_shipment {
+            assert!(
+                outcomes.len() == 1 || (outcomes.len() == 2 && outcomes[0] == ProviderOutcome::RateLimited),
+                "run {}: {} was asked {:?}",
+                run,
+                tracking_number,
+                outcomes,
+            );
+        }
+        let last: Vec<ProviderOutcome> = per_shipment.values().map(|outcomes| outcomes[outcomes.len() - 1]).collect();
+        let delivered = last.iter().filter(|outcome| !outcome.is_failure()).count();
+        let accepted = submissions.iter().filter(|submission| submission.accepted).count();
+
+        assert_eq!(stats.shipments, SHIPMENTS);
+        assert_eq!(stats.pending, accepted_before, "run {}: closes accepted by earlier runs are pending", run);
+        assert_eq!(per_shipment.len(), SHIPMENTS - accepted_before, "run {}: every other shipment is polled", run);
+        assert_eq!(submissions.len(), delivered, "run {}: every delivered shipment is submitted once", run);
+        assert_eq!(stats.submitted, accepted);
+        assert_eq!(stats.raced, submissions.len() - accepted);
+        assert_eq!(stats.failed, last.len() - delivered);
+        assert_eq!(stats.pending + stats.submitted + stats.raced + stats.failed, SHIPMENTS);
+        assert_eq!(report.shipments.len(), SHIPMENTS);
+
+        accepted_before += accepted;
+        assert_eq!(fetcher.pending_submissions().len(), accepted_before);
+    }
+
+    // No close is accepted twice, whatever the duplicate answers and retries
+    let mut closed = HashSet::new();
+    for submission in submitter.submissions().into_iter().filter(|submission| submission.accepted) {
+        assert_eq!(submission.inputs.len(), 1);
+        assert!(closed.insert(submission.inputs[0].clone()), "{} closed twice", submission.inputs[0]);
+    }
+    assert_eq!(chain.resolves().await, submitter.submissions().len(), "every resolved close is submitted");
+
+    // What the fetcher keeps between runs is bounded by the shipments and RECENT_RUNS, not by the runs
+    assert!(fetcher.pending_submissions().len() <= SHIPMENTS);
+    assert_eq!(fetcher.recent_runs().len(), RUNS.min(RECENT_RUNS));
+    assert_eq!(fetcher.last_scan().unwrap().shipments.len(), SHIPMENTS);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "soak test, run with --ignored"]
+async fn the_two_stages_outpace_one_shipment_at_a_time_with_the_same_submissions() {
+    let (sequential, sequential_submitted) = timed_run(1, 1).await;
+    let (pipelined, pipelined_submitted) = timed_run(CONCURRENCY, CONCURRENCY / 4).await;
+
+    assert!(pipelined * 2 < sequential, "two stages took {:?}, one at a time {:?}", pipelined, sequential);
+    assert!(!sequential_submitted.is_empty());
+    assert_eq!(pipelined_submitted, sequential_submitted, "both runs submit the same closes with the same answers");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn a_full_close_queue_holds_checking_back() {
+    const SHIPMENTS: usize = 40;
+    const CONCURRENCY: usize = 4;
+    const QUEUE_DEPTH: usize = 2;
+
+    // Statuses come at once and every close is slow, so only the queue keeps checking from running ahead
+    let provider = Arc::new(FlakyProvider::new(SEED, 0, "DELIVERED"));
+    let submitter = RecordingSubmitter::new(SEED, 0).with_latency(Duration::from_millis(20));
+    let (_chain, fetcher) = soak_fetcher(SHIPMENTS, provider.clone(), submitter.clone(), CONCURRENCY, 1).await;
+    let fetcher = fetcher.with_close_queue_depth(QUEUE_DEPTH);
+
+    let done = Arc::new(AtomicBool::new(false));
+    let most_ahead = Arc::new(AtomicUsize::new(0));
+    let sampling = tokio::spawn({
+        let (provider, submitter, done, most_ahead) = (provider.clone(), submitter.clone(), done.clone(), most_ahead.clone());
+        async move {
+            while !done.load(Ordering::Relaxed) {
+                // Checked first, so a submission made in between only lowers the count
+                let checked = provider.calls().len();
+                let ahead = checked.saturating_sub(submitter.submissions().len());
+                most_ahead.fetch_max(ahead, Ordering::Relaxed);
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        }
+    });
+    let report = fetcher.run().await.unwrap();
+    done.store(true, Ordering::Relaxed);
+    sampling.await.unwrap();
+
+    assert_eq!(report.stats.submitted, SHIPMENTS);
+    // Shipments checked but not submitted are being checked, queued or closed
+    let most_ahead = most_ahead.load(Ordering::Relaxed);
+    assert!(most_ahead <= CONCURRENCY + QUEUE_DEPTH + 1, "checking ran {} shipments ahead of the closes", most_ahead);
+    assert!(most_ahead > 0);
+}
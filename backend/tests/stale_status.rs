@@ -0,0 +1,115 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use serde_json::json;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::config::Config;
+use shipping_oracle::models::CarrierStatus;
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::run_report::{CloseReason, ShipmentAction};
+use shipping_oracle::stale_status::{StaleStatusPolicy, parse_statuses};
+use shipping_oracle::testing::{FrozenClock, ORACLE_ADDRESS, blockfrost_utxos, shippo_track, test_config};
+
+fn now() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2026, 3, 1, 12, 0, 0).unwrap()
+}
+
+/// Serve one tracking UTxO in carrier status `status` per entry of `ages`, created that long before `now()`
+async fn serve(server: &MockServer, status: &str, ages: &[Duration]) {
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(ages.len())))
+        .mount(server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", status)))
+        .mount(server)
+        .await;
+    for (i, age) in ages.iter().enumerate() {
+        let tx_hash = format!("{:064x}", i);
+        let block_time = (now() - *age).timestamp();
+        Mock::given(method("GET"))
+            .and(path(format!("/txs/{}", tx_hash)))
+            .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+                "hash": tx_hash,
+                "block_height": 4213337,
+                "block_time": block_time,
+            })))
+            .mount(server)
+            .await;
+    }
+}
+
+async fn txs_requested(server: &MockServer) -> usize {
+    let requests = server.received_requests().await.unwrap();
+    requests.iter().filter(|request| request.url.path().starts_with("/txs/")).count()
+}
+
+fn stale_config(server: &MockServer, days: u32) -> Config {
+    let mut config = test_config(&server.uri());
+    config.stale_status_timeout_days = Some(days);
+    config
+}
+
+#[test]
+fn only_listed_statuses_go_stale_after_the_timeout() {
+    let policy = StaleStatusPolicy::new(Duration::days(30), parse_statuses(" pre_transit, UNKNOWN ,"));
+
+    assert!(policy.applies_to(&CarrierStatus::PreTransit));
+    assert!(policy.applies_to(&CarrierStatus::from("UNKNOWN")));
+    assert!(!policy.applies_to(&CarrierStatus::Transit));
+    assert!(policy.expired(now() - Duration::days(31), now()));
+    assert!(!policy.expired(now() - Duration::days(30), now()));
+}
+
+#[tokio::test]
+async fn old_pre_transit_shipments_are_closed_as_not_delivered() {
+    let server = MockServer::start().await;
+    serve(&server, "PRE_TRANSIT", &[Duration::days(45), Duration::days(2)]).await;
+    let oracle = Oracle::builder().config(stale_config(&server, 30)).clock(FrozenClock::at(now())).build().unwrap();
+
+    // TRP is not mocked, so the close is attempted and fails
+    let report = oracle.run_once().await.unwrap();
+    assert_eq!((report.stats.shipments, report.stats.failed), (2, 1));
+
+    let stale = &report.shipments[0];
+    assert_eq!(stale.derived_status.as_deref(), Some("NOT_DELIVERED"));
+    assert_eq!(stale.close_reason, Some(CloseReason::StaleTimeout));
+    assert!(matches!(stale.action, ShipmentAction::Failed { .. }));
+    assert_eq!(serde_json::to_value(stale).unwrap()["close_reason"], "stale_timeout");
+
+    let young = &report.shipments[1];
+    assert_eq!(young.derived_status, None);
+    assert_eq!(young.close_reason, None);
+    assert!(matches!(&young.action, ShipmentAction::Skipped { reason } if reason == "status is not final"));
+}
+
+#[tokio::test]
+async fn block_times_are_fetched_once_per_tracking_utxo() {
+    let server = MockServer::start().await;
+    serve(&server, "UNKNOWN", &[Duration::days(2)]).await;
+    let oracle = Oracle::builder().config(stale_config(&server, 30)).clock(FrozenClock::at(now())).build().unwrap();
+
+    oracle.run_once().await.unwrap();
+    oracle.run_once().await.unwrap();
+    assert_eq!(txs_requested(&server).await, 1);
+}
+
+#[tokio::test]
+async fn transit_only_goes_stale_when_listed() {
+    let server = MockServer::start().await;
+    serve(&server, "TRANSIT", &[Duration::days(90)]).await;
+
+    let oracle = Oracle::builder().config(stale_config(&server, 30)).clock(FrozenClock::at(now())).build().unwrap();
+    let report = oracle.run_once().await.unwrap();
+    assert_eq!(report.shipments[0].derived_status, None);
+    assert_eq!(txs_requested(&server).await, 0);
+
+    let mut config = stale_config(&server, 30);
+    config.stale_statuses = parse_statuses("PRE_TRANSIT,UNKNOWN,TRANSIT");
+    let oracle = Oracle::builder().config(config).clock(FrozenClock::at(now())).build().unwrap();
+    let report = oracle.run_once().await.unwrap();
+    assert_eq!(report.shipments[0].derived_status.as_deref(), Some("NOT_DELIVERED"));
+    assert_eq!(report.shipments[0].close_reason, Some(CloseReason::StaleTimeout));
+}
@@ -0,0 +1,253 @@
+use rusqlite::Connection;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::lifecycle::ShipmentLifecycle;
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::state::{
+    MemoryStore, SCHEMA_VERSION, ShipmentState, SqliteStore, StateStore, Submission, SubmissionAttempt, sqlite_path,
+};
+use shipping_oracle::testing::{ORACLE_ADDRESS, blockfrost_utxos, shippo_track, test_config, tracking_number};
+
+const UTXO_REF: &str = "a6a57fe7cfcd69537dc88bfe4321cd7f164f26afd21c91c78cced224e6496f41#0";
+
+fn closed_state() -> ShipmentState {
+    ShipmentState {
+        status: Some("DELIVERED".to_string()),
+        failure_count: 2,
+        next_attempt_at: Some(1771090081),
+        closed_tx_hash: Some("9e8d7c6b5a4938271605f4e3d2c1b0a99e8d7c6b5a4938271605f4e3d2c1b0a9".to_string()),
+        carrier_status: Some("DELIVERED".to_string()),
+        carrier_status_since: Some(1771090081),
+        ..ShipmentState::new(UTXO_REF)
+    }
+}
+
+fn submission(tx_hash: &str, submitted_at: u64) -> Submission {
+    Submission {
+        utxo_ref: UTXO_REF.to_string(),
+        tx_hash: tx_hash.to_string(),
+        status: "DELIVERED".to_string(),
+        submitted_at,
+        signer_pkh: Some("021a8c1045ae4e8a999496e176792ba7642123994215a36b703c903a".to_string()),
+        fee: Some(174_257),
+        outbox_address: Some("addr_test1vqpp4rqsgkhyaz5ejjtwzane9wnkggfrn9pptgmtwq7fqws6t8yck".to_string()),
+        carrier: Some("usps".to_string()),
+        tracking_number: Some("9400111899223456789012".to_string()),
+    }
+}
+
+async fn exercise(store: &dyn StateStore) {
+    assert_eq!(store.cursor("blockfrost").await.unwrap(), None);
+    store.set_cursor("blockfrost", "page-1").await.unwrap();
+    store.set_cursor("blockfrost", "page-2").await.unwrap();
+    assert_eq!(store.cursor("blockfrost").await.unwrap().as_deref(), Some("page-2"));
+
+    assert_eq!(store.shipment(UTXO_REF).await.unwrap(), None);
+    store.save_shipment(&ShipmentState::new(UTXO_REF)).await.unwrap();
+    store.save_shipment(&closed_state()).await.unwrap();
+    assert_eq!(store.shipment(UTXO_REF).await.unwrap(), Some(closed_state()));
+
+    store.record_submission(&submission("aa", 1)).await.unwrap();
+    store.record_submission(&submission("bb", 2)).await.unwrap();
+    let submissions = store.submissions(UTXO_REF).await.unwrap();
+    assert_eq!(submissions, vec![submission("aa", 1), submission("bb", 2)]);
+    assert!(store.submissions("other#0").await.unwrap().is_empty());
+
+    let other = Submission { utxo_ref: "other#0".to_string(), ..submission("cc", 3) };
+    store.record_submission(&other).await.unwrap();
+    assert_eq!(store.journal().await.unwrap(), vec![submission("aa", 1), submission("bb", 2), other]);
+
+    let failed = SubmissionAttempt {
+        utxo_ref: UTXO_REF.to_string(),
+        status: "DELIVERED".to_string(),
+        attempted_at: 1,
+        tx_hash: None,
+        response: "TRP resolve failed (status 503)".to_string(),
+    };
+    let accepted = SubmissionAttempt { attempted_at: 2, tx_hash: Some("aa".to_string()), response: "aa".to_string(), ..failed.clone() };
+    store.record_attempt(&failed).await.unwrap();
+    store.record_attempt(&accepted).await.unwrap();
+    assert_eq!(store.attempts(UTXO_REF).await.unwrap(), vec![failed, accepted]);
+    assert!(store.attempts("other#0").await.unwrap().is_empty());
+
+    assert!(store.tracking_calls("2026-03").await.unwrap().is_empty());
+    store.record_tracking_call("2026-03", "usps", "addr_a").await.unwrap();
+    store.record_tracking_call("2026-03", "usps", "addr_a").await.unwrap();
+    store.record_tracking_call("2026-03", "ups", "addr_a").await.unwrap();
+    store.record_tracking_call("2026-04", "usps", "addr_a").await.unwrap();
+    let calls: Vec<(String, u64)> = store
+        .tracking_calls("2026-03")
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|calls| (calls.carrier, calls.calls))
+        .collect();
+    assert_eq!(calls, vec![("ups".to_string(), 1), ("usps".to_string(), 2)]);
+}
+
+#[tokio::test]
+async fn sqlite_and_memory_stores_behave_alike() {
+    exercise(&SqliteStore::open_in_memory().unwrap()).await;
+    exercise(&MemoryStore::new()).await;
+}
+
+#[tokio::test]
+async fn migrations_run_once_and_are_idempotent() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.db");
+
+    let store = SqliteStore::open(&path).unwrap();
+    assert_eq!(store.schema_version().await.unwrap(), SCHEMA_VERSION);
+    store.set_cursor("blockfrost", "page-3").await.unwrap();
+    drop(store);
+
+    let reopened = SqliteStore::open(&path).unwrap();
+    assert_eq!(reopened.schema_version().await.unwrap(), SCHEMA_VERSION);
+    assert_eq!(reopened.cursor("blockfrost").await.unwrap().as_deref(), Some("page-3"));
+}
+
+#[tokio::test]
+async fn migration_adds_signer_to_existing_submissions() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.db");
+
+    // Schema version 1, before submissions recorded the signing key
+    let conn = Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE submissions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            utxo_ref TEXT NOT NULL,
+            tx_hash TEXT NOT NULL,
+            status TEXT NOT NULL,
+            submitted_at INTEGER NOT NULL
+        );
+        CREATE TABLE shipment_state (
+            utxo_ref TEXT PRIMARY KEY,
+            status TEXT,
+            failure_count INTEGER NOT NULL DEFAULT 0,
+            next_attempt_at INTEGER,
+            dead INTEGER NOT NULL DEFAULT 0,
+            closed_tx_hash TEXT
+        );",
+    )
+    .unwrap();
+    conn.execute(
+        "INSERT INTO submissions (utxo_ref, tx_hash, status, submitted_at) VALUES (?1, 'aa', 'DELIVERED', 1)",
+        [UTXO_REF],
+    )
+    .unwrap();
+    conn.pragma_update(None, "user_version", 1).unwrap();
+    drop(conn);
+
+    let store = SqliteStore::open(&path).unwrap();
+    assert_eq!(store.schema_version().await.unwrap(), SCHEMA_VERSION);
+    assert_eq!(
+        store.submissions(UTXO_REF).await.unwrap(),
+        vec![Submission {
+            signer_pkh: None,
+            fee: None,
+            outbox_address: None,
+            carrier: None,
+            tracking_number: None,
+            ..submission("aa", 1)
+        }]
+    );
+}
+
+#[tokio::test]
+async fn newer_schema_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.db");
+
+    let conn = Connection::open(&path).unwrap();
+    conn.pragma_update(None, "user_version", SCHEMA_VERSION + 1).unwrap();
+    drop(conn);
+
+    let err = SqliteStore::open(&path).err().expect("newer schema must be rejected");
+    assert!(err.to_string().contains("newer than this binary supports"), "{}", err);
+}
+
+#[tokio::test]
+async fn committed_writes_survive_without_clean_shutdown() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("state.db");
+
+    let store = SqliteStore::open(&path).unwrap();
+    store.save_shipment(&closed_state()).await.unwrap();
+    store.record_submission(&submission("aa", 1)).await.unwrap();
+
+    // A second connection sees committed data while the writer is still open,
+    // i.e. before any checkpoint, as it would after a crash
+    let reader = Connection::open(&path).unwrap();
+    let mode: String = reader.pragma_query_value(None, "journal_mode", |row| row.get(0)).unwrap();
+    assert_eq!(mode, "wal");
+    let count: u32 = reader
+        .query_row("SELECT COUNT(*) FROM submissions", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(count, 1);
+
+    // Skip the destructor so the WAL is never checkpointed on close
+    std::mem::forget(store);
+
+    let recovered = SqliteStore::open(&path).unwrap();
+    assert_eq!(recovered.shipment(UTXO_REF).await.unwrap(), Some(closed_state()));
+    assert_eq!(recovered.submissions(UTXO_REF).await.unwrap(), vec![submission("aa", 1)]);
+}
+
+#[test]
+fn database_urls_name_a_sqlite_file() {
+    assert_eq!(sqlite_path("sqlite:///var/lib/oracle/state.db").unwrap(), "/var/lib/oracle/state.db");
+    assert_eq!(sqlite_path("sqlite://oracle.db").unwrap(), "oracle.db");
+    assert_eq!(sqlite_path("sqlite:oracle.db").unwrap(), "oracle.db");
+    assert_eq!(
+        sqlite_path("postgres://localhost/oracle").unwrap_err().to_string(),
+        "expected a sqlite://<path> URL, got 'postgres://localhost/oracle'"
+    );
+    assert!(sqlite_path("sqlite://").is_err());
+}
+
+#[tokio::test]
+async fn restarts_resume_from_the_database() {
+    // Shipment 0 is in transit; shipment 1 is delivered but TRP is not mocked, so its close fails
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(2)))
+        .mount(&server)
+        .await;
+    for (index, status) in ["TRANSIT", "DELIVERED"].into_iter().enumerate() {
+        Mock::given(method("GET"))
+            .and(path(format!("/tracks/usps/{}", tracking_number(index))))
+            .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", &tracking_number(index), status)))
+            .mount(&server)
+            .await;
+    }
+
+    let dir = tempfile::tempdir().unwrap();
+    let mut config = test_config(&server.uri());
+    config.state_db_path = Some(dir.path().join("state.db").display().to_string());
+
+    // Each run is a fresh process as far as the oracle is concerned
+    for _ in 0..2 {
+        let oracle = Oracle::from_config(config.clone()).unwrap();
+        let stats = oracle.run_once().await.unwrap().stats;
+        assert_eq!((stats.shipments, stats.failed, stats.illegal_transitions), (2, 1, 0));
+    }
+
+    let state = SqliteStore::open(config.state_db_path.as_ref().unwrap()).unwrap();
+    let (in_transit, delivered) = (format!("{:064x}#0", 0), format!("{:064x}#0", 1));
+
+    let states: Vec<ShipmentLifecycle> = state.lifecycle(&in_transit).await.unwrap().into_iter().map(|t| t.state).collect();
+    assert_eq!(states, vec![ShipmentLifecycle::Discovered, ShipmentLifecycle::AwaitingCarrier]);
+    let shipment = state.shipment(&in_transit).await.unwrap().unwrap();
+    assert_eq!(shipment.carrier_status.as_deref(), Some("TRANSIT"));
+
+    let attempts = state.attempts(&delivered).await.unwrap();
+    assert_eq!(attempts.len(), 2);
+    assert!(attempts.iter().all(|attempt| attempt.status == "DELIVERED" && attempt.tx_hash.is_none()));
+    assert!(!attempts[0].response.is_empty());
+    assert_eq!(state.shipment(&delivered).await.unwrap().unwrap().failure_count, 2);
+    assert!(state.journal().await.unwrap().is_empty());
+    assert!(state.attempts(&in_transit).await.unwrap().is_empty());
+}
@@ -0,0 +1,107 @@
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::models::{CarrierStatus, DerivedStatus, TrackingStatus};
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::status_mapping::StatusMapping;
+use shipping_oracle::testing::{ORACLE_ADDRESS, blockfrost_utxos, shippo_track, test_config};
+
+fn status(status: &str) -> TrackingStatus {
+    TrackingStatus { status: status.into(), status_details: String::new(), status_date: None }
+}
+
+#[test]
+fn default_mapping_closes_returned_and_failed_shipments_as_not_delivered() {
+    let mapping = StatusMapping::default();
+
+    assert_eq!(mapping.map(&status("DELIVERED")), Some(DerivedStatus::Delivered));
+    assert_eq!(mapping.map(&status("RETURNED")), Some(DerivedStatus::NotDelivered));
+    assert_eq!(mapping.map(&status("FAILURE")), Some(DerivedStatus::NotDelivered));
+    assert_eq!(mapping.map(&status("TRANSIT")), None);
+    assert_eq!(mapping.map(&status("UNKNOWN")), None);
+    assert_eq!(mapping.on_chain_statuses(), vec![DerivedStatus::Delivered, DerivedStatus::NotDelivered]);
+    assert_eq!(mapping.to_string(), "DELIVERED:DELIVERED,FAILURE:NOT_DELIVERED,RETURNED:NOT_DELIVERED");
+}
+
+#[test]
+fn custom_mapping_replaces_the_default() {
+    let mapping: StatusMapping = " delivered:DELIVERED, HELD : NOT_DELIVERED ,,FAILURE:NOT_DELIVERED".parse().unwrap();
+
+    assert_eq!(mapping.map(&status("DELIVERED")), Some(DerivedStatus::Delivered));
+    assert_eq!(mapping.map(&status("HELD")), Some(DerivedStatus::NotDelivered));
+    assert_eq!(mapping.map(&status("FAILURE")), Some(DerivedStatus::NotDelivered));
+    assert_eq!(mapping.map(&status("RETURNED")), None);
+    assert_eq!(mapping.map(&status("TRANSIT")), None);
+    assert_eq!(mapping.on_chain_statuses(), vec![DerivedStatus::Delivered, DerivedStatus::NotDelivered]);
+
+    let delivered_only: StatusMapping = "DELIVERED:DELIVERED".parse().unwrap();
+    assert_eq!(delivered_only.map(&status("RETURNED")), None);
+}
+
+#[test]
+fn malformed_mappings_are_rejected() {
+    let err = "DELIVERED:DELIVERED,RETURNED".parse::<StatusMapping>().unwrap_err();
+    assert_eq!(err.to_string(), "expected carrier_status:on_chain_status, got 'RETURNED'");
+    let err = "DELIVERED:".parse::<StatusMapping>().unwrap_err();
+    assert_eq!(err.to_string(), "expected carrier_status:on_chain_status, got 'DELIVERED:'");
+    let err = " , ".parse::<StatusMapping>().unwrap_err();
+    assert_eq!(err.to_string(), "no carrier status closes a shipment");
+    let err = "DELIVERED:DELIVERD".parse::<StatusMapping>().unwrap_err();
+    assert_eq!(format!("{:#}", err), "Invalid on-chain status of DELIVERED: expected DELIVERED or NOT_DELIVERED, got 'DELIVERD'");
+}
+
+#[test]
+fn carrier_statuses_deserialize_from_shippo_strings() {
+    let statuses: Vec<CarrierStatus> =
+        serde_json::from_str(r#"["DELIVERED", "RETURNED", "FAILURE", "TRANSIT", "PRE_TRANSIT", "UNKNOWN", "HELD"]"#).unwrap();
+
+    assert_eq!(
+        statuses,
+        vec![
+            CarrierStatus::Delivered,
+            CarrierStatus::Returned,
+            CarrierStatus::Failure,
+            CarrierStatus::Transit,
+            CarrierStatus::PreTransit,
+            CarrierStatus::Unknown("UNKNOWN".to_string()),
+            CarrierStatus::Unknown("HELD".to_string()),
+        ]
+    );
+    assert_eq!(CarrierStatus::PreTransit.to_string(), "PRE_TRANSIT");
+    assert_eq!(CarrierStatus::Unknown("HELD".to_string()).to_string(), "HELD");
+}
+
+#[test]
+fn derived_statuses_encode_to_the_datum_bytes() {
+    assert_eq!(DerivedStatus::Delivered.hex(), "44454c495645524544");
+    assert_eq!(DerivedStatus::NotDelivered.hex(), "4e4f545f44454c495645524544");
+    assert_eq!(DerivedStatus::NotDelivered.as_onchain_bytes(), b"NOT_DELIVERED");
+    assert_eq!("NOT_DELIVERED".parse::<DerivedStatus>().unwrap(), DerivedStatus::NotDelivered);
+    assert_eq!(DerivedStatus::NotDelivered.to_string(), "NOT_DELIVERED");
+    assert!("RETURNED".parse::<DerivedStatus>().is_err());
+}
+
+#[tokio::test]
+async fn run_closes_shipments_with_the_mapped_status() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(1)))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", "HELD")))
+        .mount(&server)
+        .await;
+
+    let mut config = test_config(&server.uri());
+    config.status_mapping = "DELIVERED:DELIVERED,HELD:NOT_DELIVERED".parse().unwrap();
+    let oracle = Oracle::builder().config(config).build().unwrap();
+
+    // TRP is not mocked, so the close is attempted and fails
+    let report = oracle.run_once().await.unwrap();
+    assert_eq!((report.stats.shipments, report.stats.failed), (1, 1));
+    assert_eq!(report.shipments[0].fetched_status.as_deref(), Some("HELD"));
+    assert_eq!(report.shipments[0].derived_status.as_deref(), Some("NOT_DELIVERED"));
+}
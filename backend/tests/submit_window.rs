@@ -0,0 +1,145 @@
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::America::New_York;
+use std::sync::Arc;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::blockchain::CardanoClient;
+use shipping_oracle::fetcher::DataFetcher;
+use shipping_oracle::lifecycle::ShipmentLifecycle;
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::shipment::ShipmentClient;
+use shipping_oracle::state::{MemoryStore, StateStore};
+use shipping_oracle::submit_window::SubmitWindow;
+use shipping_oracle::testing::{FrozenClock, ORACLE_ADDRESS, blockfrost_utxos, shippo_track, test_config};
+
+fn at(rfc3339: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+}
+
+fn business_hours() -> SubmitWindow {
+    SubmitWindow::parse("08:00-20:00", New_York).unwrap()
+}
+
+fn utxo_ref(index: usize) -> String {
+    format!("{:064x}#0", index)
+}
+
+/// Serve `shipments` tracking UTxOs whose carrier reports them delivered
+async fn serve_delivered(shipments: usize) -> MockServer {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(shipments)))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", "DELIVERED")))
+        .mount(&server)
+        .await;
+
+    server
+}
+
+fn fetcher(server: &MockServer, state: Arc<dyn StateStore>, clock: FrozenClock) -> DataFetcher {
+    let config = test_config(&server.uri());
+    DataFetcher::new(
+        Arc::new(CardanoClient::new(config.clone()).unwrap()),
+        Arc::new(ShipmentClient::new(config).unwrap()),
+    )
+    .with_state(state)
+    .with_submit_window(business_hours())
+    .with_clock(Arc::new(clock))
+}
+
+#[test]
+fn windows_follow_local_time() {
+    let window = business_hours();
+    // 08:00-20:00 EST is 13:00-01:00 UTC
+    assert!(window.is_open(at("2026-03-02T13:00:00Z")));
+    assert!(window.is_open(at("2026-03-03T00:59:59Z")));
+    assert!(!window.is_open(at("2026-03-03T01:00:00Z")));
+    assert!(!window.is_open(at("2026-03-02T12:59:59Z")));
+
+    assert_eq!(window.next_open(at("2026-03-02T12:00:00Z")), at("2026-03-02T13:00:00Z"));
+    assert_eq!(window.next_open(at("2026-03-03T02:00:00Z")), at("2026-03-03T13:00:00Z"));
+    // Clocks go forward on March 8th; the window opens at 08:00 EDT
+    assert_eq!(window.next_open(at("2026-03-08T02:00:00Z")), at("2026-03-08T12:00:00Z"));
+
+    let overnight = SubmitWindow::parse("22:00-06:00", New_York).unwrap();
+    assert!(overnight.is_open(at("2026-03-03T04:00:00Z")));
+    assert!(overnight.is_open(at("2026-03-03T10:59:00Z")));
+    assert!(!overnight.is_open(at("2026-03-03T17:00:00Z")));
+    assert_eq!(overnight.next_open(at("2026-03-03T17:00:00Z")), at("2026-03-04T03:00:00Z"));
+
+    for invalid in ["08:00", "8am-8pm", "08:00-25:00", "09:00-09:00"] {
+        assert!(SubmitWindow::parse(invalid, New_York).is_err(), "{}", invalid);
+    }
+}
+
+#[tokio::test]
+async fn in_window_final_statuses_are_submitted() {
+    let server = serve_delivered(1).await;
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    let fetcher = fetcher(&server, state.clone(), FrozenClock::at(at("2026-03-02T17:00:00Z")));
+
+    // TRP is not mocked, so the close is attempted and fails
+    let stats = fetcher.run().await.unwrap().stats;
+    assert_eq!((stats.failed, stats.deferred_window), (1, 0));
+    assert_eq!(state.shipment(&utxo_ref(0)).await.unwrap().unwrap().next_attempt_at, None);
+    assert_eq!(fetcher.submit_window_open(), Some(true));
+}
+
+#[tokio::test]
+async fn out_of_window_final_statuses_are_deferred() {
+    let server = serve_delivered(2).await;
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    let fetcher = fetcher(&server, state.clone(), FrozenClock::at(at("2026-03-03T02:00:00Z")));
+
+    let stats = fetcher.run().await.unwrap().stats;
+    assert_eq!((stats.shipments, stats.failed, stats.deferred_window), (2, 0, 2));
+
+    let shipment = state.shipment(&utxo_ref(0)).await.unwrap().unwrap();
+    assert_eq!(shipment.status.as_deref(), Some("DELIVERED"));
+    assert_eq!(shipment.failure_count, 0);
+    assert_eq!(shipment.next_attempt_at, Some(at("2026-03-03T13:00:00Z").timestamp() as u64));
+    assert_eq!(
+        state.lifecycle(&utxo_ref(0)).await.unwrap().pop().map(|transition| transition.state),
+        Some(ShipmentLifecycle::FinalStatusKnown)
+    );
+    assert_eq!(fetcher.submit_window_open(), Some(false));
+}
+
+#[tokio::test]
+async fn run_crossing_the_window_end_stops_submitting() {
+    let server = serve_delivered(3).await;
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    // 19:59 in New York, a minute later on every read
+    let clock = FrozenClock::ticking(at("2026-03-03T00:59:00Z"), Duration::minutes(1));
+    let fetcher = fetcher(&server, state.clone(), clock);
+
+    let stats = fetcher.run().await.unwrap().stats;
+    assert_eq!((stats.shipments, stats.failed, stats.deferred_window), (3, 1, 2));
+    assert_eq!(state.shipment(&utxo_ref(0)).await.unwrap().unwrap().failure_count, 1);
+    for index in 1..3 {
+        let shipment = state.shipment(&utxo_ref(index)).await.unwrap().unwrap();
+        assert_eq!(shipment.next_attempt_at, Some(at("2026-03-03T13:00:00Z").timestamp() as u64));
+    }
+}
+
+#[tokio::test]
+async fn health_shows_whether_the_window_is_open() {
+    let server = MockServer::start().await;
+    let mut config = test_config(&server.uri());
+    let oracle = Oracle::builder().config(config.clone()).build().unwrap();
+    assert_eq!(oracle.health().await.submit_window_open, None);
+
+    config.submit_window = Some(business_hours());
+    let oracle = Oracle::builder()
+        .config(config)
+        .clock(FrozenClock::at(at("2026-03-03T02:00:00Z")))
+        .build()
+        .unwrap();
+    assert_eq!(oracle.health().await.submit_window_open, Some(false));
+}
@@ -0,0 +1,169 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::submitter::{BlockfrostSubmitter, OgmiosSubmitter, RetryingSubmitter, SubmitError, TxSubmitter};
+
+const TX_HASH: &str = "584cbabb4a075d96d065b6e158d737f98c961dc5802e4b3f905f1f533d28f68f";
+
+fn fixture(name: &str) -> String {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("missing fixture {}: {}", path, e))
+}
+
+/// Fails with `failure` the first `failures` times, then accepts the transaction
+struct FlakySubmitter {
+    calls: Arc<AtomicUsize>,
+    failures: usize,
+    failure: fn() -> SubmitError,
+}
+
+#[async_trait::async_trait]
+impl TxSubmitter for FlakySubmitter {
+    async fn submit(&self, _signed_tx: Vec<u8>) -> Result<String, SubmitError> {
+        if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+            return Err((self.failure)());
+        }
+        Ok(TX_HASH.to_string())
+    }
+}
+
+fn flaky(failures: usize, failure: fn() -> SubmitError) -> (RetryingSubmitter<FlakySubmitter>, Arc<AtomicUsize>) {
+    let calls = Arc::new(AtomicUsize::new(0));
+    let inner = FlakySubmitter { calls: calls.clone(), failures, failure };
+    let submitter = RetryingSubmitter::new(inner).with_base_backoff(Duration::from_millis(1));
+
+    (submitter, calls)
+}
+
+fn congested() -> SubmitError {
+    SubmitError::Network("Blockfrost transaction submission failed (status 503 Service Unavailable)".to_string())
+}
+
+fn bad_inputs() -> SubmitError {
+    SubmitError::AlreadySpent("Blockfrost transaction submission failed (status 400 Bad Request): BadInputsUTxO".to_string())
+}
+
+/// How Blockfrost's `/tx/submit` refuses a transaction with `status`, `body` and `headers`
+async fn blockfrost_rejection(status: u16, body: &str, headers: &[(&str, &str)]) -> SubmitError {
+    let server = MockServer::start().await;
+    let mut response = ResponseTemplate::new(status).set_body_string(body);
+    for (name, value) in headers {
+        response = response.insert_header(*name, *value);
+    }
+    Mock::given(method("POST")).and(path("/tx/submit")).respond_with(response).mount(&server).await;
+
+    let submitter = BlockfrostSubmitter::new(server.uri(), reqwest::Client::new());
+    submitter.submit(vec![0x84, 0xa0, 0xa0, 0xf5, 0xf6]).await.unwrap_err()
+}
+
+#[tokio::test]
+async fn transient_failures_are_retried() {
+    let (submitter, calls) = flaky(2, congested);
+
+    assert_eq!(submitter.submit(vec![0x80]).await.unwrap(), TX_HASH);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn invalid_transactions_are_not_retried() {
+    let (submitter, calls) = flaky(2, bad_inputs);
+
+    let err = submitter.submit(vec![0x80]).await.unwrap_err();
+    assert!(matches!(err, SubmitError::AlreadySpent(_)), "{:?}", err);
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn retries_stop_at_the_limit() {
+    let (submitter, calls) = flaky(5, congested);
+    let submitter = submitter.with_max_retries(2);
+
+    let err = submitter.submit(vec![0x80]).await.unwrap_err();
+    assert!(err.is_transient(), "{:?}", err);
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn blockfrost_rejections_are_classified() {
+    let bad_inputs = r#"{"status_code":400,"error":"Bad Request","message":"\"transaction submit error ShelleyTxValidationError ShelleyBasedEraConway (ApplyTxError (ConwayUtxowFailure (UtxoFailure (BadInputsUTxO (fromList [TxIn (TxId {unTxId = SafeHash \\\"a7a264ac1bef1f0da6312d16fb8f68b18d1a0d5010d8cb6a61790bf02a759301\\\"}) (TxIx 0)])))) :| []))\""}"#;
+    let err = blockfrost_rejection(400, bad_inputs, &[]).await;
+    assert!(matches!(err, SubmitError::AlreadySpent(_)), "{:?}", err);
+    assert!(err.to_string().contains("status 400 Bad Request"), "{}", err);
+
+    let fee_too_small = r#"{"status_code":400,"error":"Bad Request","message":"\"transaction submit error ShelleyTxValidationError ShelleyBasedEraConway (ApplyTxError (ConwayUtxowFailure (UtxoFailure (FeeTooSmallUTxO (Coin 174257) (Coin 170000))) :| []))\""}"#;
+    let err = blockfrost_rejection(400, fee_too_small, &[]).await;
+    assert!(matches!(err, SubmitError::FeeTooSmall(_)), "{:?}", err);
+
+    let missing_witness = r#"{"status_code":400,"error":"Bad Request","message":"\"transaction submit error ShelleyTxValidationError ShelleyBasedEraConway (ApplyTxError (ConwayUtxowFailure (MissingVKeyWitnessesUTXOW (fromList [KeyHash {unKeyHash = \\\"35dedd2982a03cf39e7dce03c839994ffdec2ec6b04f1cf2d40e61a3\\\"}])) :| []))\""}"#;
+    let err = blockfrost_rejection(400, missing_witness, &[]).await;
+    assert!(matches!(err, SubmitError::Other(_)), "{:?}", err);
+
+    let err = blockfrost_rejection(400, &fixture("blockfrost_submit_script_failure.json"), &[]).await;
+    assert!(matches!(err, SubmitError::Other(_)), "{:?}", err);
+
+    let over_limit = r#"{"status_code":429,"error":"Project Over Limit","message":"Usage is over limit."}"#;
+    let err = blockfrost_rejection(429, over_limit, &[("Retry-After", "2")]).await;
+    assert_eq!(
+        err,
+        SubmitError::RateLimited {
+            retry_after: Some(Duration::from_secs(2)),
+            message: format!("Blockfrost transaction submission failed (status 429 Too Many Requests): {}", over_limit),
+        }
+    );
+
+    let mempool_full = r#"{"status_code":425,"error":"Mempool Full","message":"Mempool is full, please try resubmitting again later."}"#;
+    let err = blockfrost_rejection(425, mempool_full, &[]).await;
+    assert!(matches!(err, SubmitError::Network(_)), "{:?}", err);
+
+    let unavailable = r#"{"status_code":500,"error":"Internal Server Error","message":"An unexpected response was received from the backend."}"#;
+    let err = blockfrost_rejection(500, unavailable, &[]).await;
+    assert!(matches!(err, SubmitError::Network(_)), "{:?}", err);
+}
+
+#[tokio::test]
+async fn unreachable_submit_api_is_a_network_error() {
+    // Nothing listens on port 1
+    let submitter = BlockfrostSubmitter::new("http://127.0.0.1:1".to_string(), reqwest::Client::new());
+
+    let err = submitter.submit(vec![0x80]).await.unwrap_err();
+    assert!(matches!(err, SubmitError::Network(_)), "{:?}", err);
+}
+
+#[tokio::test]
+async fn ogmios_spent_inputs_are_already_spent() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .respond_with(ResponseTemplate::new(400).set_body_string(
+            r#"{"jsonrpc":"2.0","method":"submitTransaction","error":{"code":3117,"message":"The transaction contains unknown UTxO references as inputs.","data":{"unknownOutputReferences":[{"transaction":{"id":"a7a264ac1bef1f0da6312d16fb8f68b18d1a0d5010d8cb6a61790bf02a759301"},"index":0}]}},"id":null}"#,
+        ))
+        .mount(&server)
+        .await;
+
+    let err = OgmiosSubmitter::new(server.uri(), reqwest::Client::new()).submit(vec![0x80]).await.unwrap_err();
+    assert!(matches!(err, SubmitError::AlreadySpent(_)), "{:?}", err);
+}
+
+#[tokio::test]
+async fn blockfrost_outage_is_retried_until_it_recovers() {
+    let server = MockServer::start().await;
+    Mock::given(method("POST"))
+        .and(path("/tx/submit"))
+        .respond_with(ResponseTemplate::new(502))
+        .up_to_n_times(1)
+        .mount(&server)
+        .await;
+    Mock::given(method("POST"))
+        .and(path("/tx/submit"))
+        .respond_with(ResponseTemplate::new(200).set_body_string(format!("\"{}\"", TX_HASH)))
+        .mount(&server)
+        .await;
+
+    let submitter = RetryingSubmitter::new(BlockfrostSubmitter::new(server.uri(), reqwest::Client::new()))
+        .with_base_backoff(Duration::from_millis(1));
+
+    assert_eq!(submitter.submit(vec![0x80]).await.unwrap(), TX_HASH);
+    assert_eq!(server.received_requests().await.unwrap().len(), 2);
+}
@@ -0,0 +1,94 @@
+#![cfg(feature = "otel")]
+
+use opentelemetry::Value;
+use opentelemetry_sdk::export::trace::SpanData;
+use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+use opentelemetry_sdk::trace::TracerProvider;
+use tracing_subscriber::layer::SubscriberExt;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::telemetry;
+use shipping_oracle::testing::{ORACLE_ADDRESS, blockfrost_utxos, shippo_track, test_config};
+
+fn attribute<'a>(span: &'a SpanData, key: &str) -> Option<&'a Value> {
+    span.attributes.iter().find(|attribute| attribute.key.as_str() == key).map(|attribute| &attribute.value)
+}
+
+#[tokio::test]
+async fn runs_export_a_span_per_shipment_under_the_run() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(2)))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", "TRANSIT")))
+        .mount(&server)
+        .await;
+
+    let exporter = InMemorySpanExporter::default();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .with_resource(telemetry::resource(Some("preprod")))
+        .build();
+    let subscriber = tracing_subscriber::registry().with(telemetry::layer(&provider));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let report = Oracle::from_config(test_config(&server.uri())).unwrap().run_once().await.unwrap();
+    assert_eq!(report.stats.shipments, 2);
+    for result in provider.force_flush() {
+        result.unwrap();
+    }
+
+    let spans = exporter.get_finished_spans().unwrap();
+    let named = |name: &str| spans.iter().filter(move |span| span.name == name);
+
+    let run = named("run").next().expect("a run span");
+    assert_eq!(attribute(run, "run_id"), Some(&Value::from(report.run_id.clone())));
+    let run_id = run.span_context.span_id();
+
+    let shipments: Vec<&SpanData> = named("shipment").collect();
+    assert_eq!(shipments.len(), 2);
+    let mut utxo_refs: Vec<String> = shipments
+        .iter()
+        .map(|shipment| {
+            assert_eq!(shipment.parent_span_id, run_id, "shipment spans are children of the run span");
+            assert_eq!(attribute(shipment, "carrier"), Some(&Value::from("usps")));
+            attribute(shipment, "utxo_ref").expect("a utxo_ref attribute").to_string()
+        })
+        .collect();
+    utxo_refs.sort();
+    assert_eq!(utxo_refs, vec![format!("{:064x}#0", 0), format!("{:064x}#0", 1)]);
+
+    // The address scan belongs to the run, each Shippo call to its shipment
+    let requests: Vec<&SpanData> = named("http.request").collect();
+    let scan = requests
+        .iter()
+        .find(|request| attribute(request, "peer.service") == Some(&Value::from("blockfrost")))
+        .expect("a Blockfrost request span");
+    assert_eq!(scan.parent_span_id, run_id);
+    let tracking: Vec<&&SpanData> = requests
+        .iter()
+        .filter(|request| attribute(request, "peer.service") == Some(&Value::from("shippo")))
+        .collect();
+    assert_eq!(tracking.len(), 2);
+    for request in tracking {
+        assert!(shipments.iter().any(|shipment| shipment.span_context.span_id() == request.parent_span_id));
+        assert_eq!(attribute(request, "http.request.method"), Some(&Value::from("GET")));
+    }
+}
+
+#[test]
+fn the_resource_names_the_service_version_and_network() {
+    let resource = telemetry::resource(Some("mainnet"));
+    let get = |key: &'static str| resource.get(opentelemetry::Key::from_static_str(key)).map(|value| value.to_string());
+
+    assert_eq!(get("service.name").as_deref(), Some(telemetry::SERVICE_NAME));
+    assert_eq!(get("service.version").as_deref(), Some(env!("CARGO_PKG_VERSION")));
+    assert_eq!(get("cardano.network").as_deref(), Some("mainnet"));
+    assert_eq!(telemetry::resource(None).get(opentelemetry::Key::from_static_str("cardano.network")), None);
+}
@@ -0,0 +1,100 @@
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::notifier::OracleEvent;
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::state::MemoryStore;
+use shipping_oracle::testing::{FrozenClock, ORACLE_ADDRESS, blockfrost_utxos, shippo_track, test_config};
+use shipping_oracle::timestamp_source::TimestampSource;
+
+/// `status_date` of `testing::shippo_track`
+const STATUS_DATE: &str = "2026-02-14T17:28:01.000Z";
+
+fn at(rfc3339: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(rfc3339).unwrap().with_timezone(&Utc)
+}
+
+#[test]
+fn sources_parse_case_insensitively() {
+    assert_eq!("oracle".parse::<TimestampSource>().unwrap(), TimestampSource::Oracle);
+    assert_eq!(" Carrier ".parse::<TimestampSource>().unwrap(), TimestampSource::Carrier);
+    assert_eq!(
+        "shippo".parse::<TimestampSource>().unwrap_err().to_string(),
+        "expected oracle or carrier, got 'shippo'"
+    );
+    assert_eq!(TimestampSource::default(), TimestampSource::Carrier);
+}
+
+#[test]
+fn carrier_date_is_used_when_plausible() {
+    let now = at("2026-02-14T19:00:00Z");
+
+    let timestamp = TimestampSource::Carrier.resolve(Some(STATUS_DATE), now);
+    assert_eq!(timestamp.source, TimestampSource::Carrier);
+    assert_eq!(timestamp.timestamp, at(STATUS_DATE).timestamp() as u64);
+    assert_eq!(timestamp.oracle_timestamp, now.timestamp() as u64);
+    assert_eq!(timestamp.fallback, None);
+
+    // The oracle source ignores the carrier date
+    let timestamp = TimestampSource::Oracle.resolve(Some(STATUS_DATE), now);
+    assert_eq!((timestamp.source, timestamp.timestamp), (TimestampSource::Oracle, now.timestamp() as u64));
+}
+
+#[test]
+fn implausible_carrier_dates_fall_back_to_the_oracle_clock() {
+    let now = at("2026-02-14T19:00:00Z");
+    let cases = [
+        (None, "the carrier reported no status date".to_string()),
+        (Some("yesterday"), "carrier status date yesterday is not an RFC 3339 date".to_string()),
+        (Some("2027-01-01T00:00:00Z"), "carrier status date 2027-01-01T00:00:00Z is in the future".to_string()),
+        (Some("2025-06-01T00:00:00Z"), "carrier status date 2025-06-01T00:00:00Z is more than 90 days old".to_string()),
+    ];
+
+    for (status_date, reason) in cases {
+        let timestamp = TimestampSource::Carrier.resolve(status_date, now);
+        assert_eq!(timestamp.source, TimestampSource::Oracle);
+        assert_eq!(timestamp.timestamp, now.timestamp() as u64);
+        assert_eq!(timestamp.fallback, Some(reason));
+    }
+
+    // A carrier clock a little ahead of ours is accepted
+    let timestamp = TimestampSource::Carrier.resolve(Some("2026-02-14T19:03:00Z"), now);
+    assert_eq!(timestamp.source, TimestampSource::Carrier);
+}
+
+#[tokio::test]
+async fn runs_close_with_the_carrier_date_by_default() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(1)))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", "DELIVERED")))
+        .mount(&server)
+        .await;
+
+    // `test_config` pins the oracle clock, so restore the default
+    let mut config = test_config(&server.uri());
+    config.timestamp_source = TimestampSource::default();
+    let oracle = Oracle::builder()
+        .config(config)
+        .state(Arc::new(MemoryStore::new()))
+        .clock(FrozenClock::at(at("2026-02-15T09:00:00Z")))
+        .build()
+        .unwrap();
+    let mut events = oracle.subscribe();
+
+    // TRP is not mocked, so the close fails after the timestamp is chosen
+    let stats = oracle.run_once().await.unwrap().stats;
+    assert_eq!(stats.failed, 1);
+
+    let OracleEvent::ShipmentFailed { timestamp, .. } = events.try_recv().unwrap() else {
+        panic!("expected a failed shipment");
+    };
+    assert_eq!(timestamp, at(STATUS_DATE).timestamp() as u64);
+}
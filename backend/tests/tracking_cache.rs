@@ -0,0 +1,96 @@
+use chrono::{Duration, TimeZone, Utc};
+use wiremock::matchers::{method, path, path_regex};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::config::Config;
+use shipping_oracle::models::{CarrierStatus, TrackingStatus};
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::testing::{FrozenClock, ORACLE_ADDRESS, blockfrost_utxos, shippo_track, test_config};
+use shipping_oracle::tracking_cache::TrackingCache;
+
+/// Serve `shipments` tracking UTxOs whose carrier status is `status`
+async fn serve(server: &MockServer, shipments: usize, status: &str) {
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(shipments)))
+        .mount(server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", status)))
+        .mount(server)
+        .await;
+}
+
+async fn tracks_requested(server: &MockServer) -> usize {
+    let requests = server.received_requests().await.unwrap();
+    requests.iter().filter(|request| request.url.path().starts_with("/tracks/")).count()
+}
+
+fn cached_config(server: &MockServer, ttl_secs: u64) -> Config {
+    let mut config = test_config(&server.uri());
+    config.tracking_cache_ttl_secs = ttl_secs;
+    config
+}
+
+fn status(status: &str) -> TrackingStatus {
+    TrackingStatus { status: status.into(), status_details: String::new(), status_date: None }
+}
+
+#[test]
+fn only_fresh_non_final_statuses_are_served() {
+    let cache = TrackingCache::new(Duration::minutes(5));
+    let now = Utc.with_ymd_and_hms(2026, 2, 14, 12, 0, 0).unwrap();
+
+    cache.insert("usps", "TRK1", &status("TRANSIT"), now);
+    assert_eq!(cache.get("usps", "TRK1", now + Duration::minutes(4)).unwrap().status, CarrierStatus::Transit);
+    assert!(cache.get("usps", "TRK1", now + Duration::minutes(5)).is_none());
+    assert!(cache.get("fedex", "TRK1", now).is_none());
+
+    cache.insert("usps", "TRK1", &status("DELIVERED"), now);
+    assert!(cache.get("usps", "TRK1", now).is_none());
+}
+
+#[tokio::test]
+async fn second_run_within_the_ttl_does_not_ask_shippo() {
+    let server = MockServer::start().await;
+    serve(&server, 2, "TRANSIT").await;
+    let oracle = Oracle::builder().config(cached_config(&server, 3600)).build().unwrap();
+
+    let first = oracle.run_once().await.unwrap();
+    assert_eq!((first.stats.shipments, first.stats.cached), (2, 0));
+    assert_eq!(tracks_requested(&server).await, 2);
+
+    let second = oracle.run_once().await.unwrap();
+    assert_eq!((second.stats.shipments, second.stats.cached, second.stats.failed), (2, 2, 0));
+    assert!(second.shipments.iter().all(|shipment| shipment.fetched_status.as_deref() == Some("TRANSIT")));
+    assert_eq!(tracks_requested(&server).await, 2);
+}
+
+#[tokio::test]
+async fn expired_statuses_are_fetched_again() {
+    let server = MockServer::start().await;
+    serve(&server, 2, "TRANSIT").await;
+    // Every reading of the clock is ten minutes after the one before
+    let clock = FrozenClock::ticking(Utc.with_ymd_and_hms(2026, 2, 14, 12, 0, 0).unwrap(), Duration::minutes(10));
+    let oracle = Oracle::builder().config(cached_config(&server, 300)).clock(clock).build().unwrap();
+
+    oracle.run_once().await.unwrap();
+    let second = oracle.run_once().await.unwrap();
+    assert_eq!(second.stats.cached, 0);
+    assert_eq!(tracks_requested(&server).await, 4);
+}
+
+#[tokio::test]
+async fn final_statuses_are_fetched_again_after_a_failed_close() {
+    let server = MockServer::start().await;
+    serve(&server, 1, "DELIVERED").await;
+    let oracle = Oracle::builder().config(cached_config(&server, 3600)).build().unwrap();
+
+    // TRP is not mocked, so each close fails
+    for _ in 0..2 {
+        let report = oracle.run_once().await.unwrap();
+        assert_eq!((report.stats.failed, report.stats.cached), (1, 0));
+    }
+    assert_eq!(tracks_requested(&server).await, 2);
+}
@@ -0,0 +1,190 @@
+use chrono::{DateTime, Utc};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use wiremock::matchers::{header, method, path, path_regex, query_param};
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use shipping_oracle::config::Config;
+use shipping_oracle::easypost::{EasyPostProvider, shippo_status};
+use shipping_oracle::models::CarrierStatus;
+use shipping_oracle::oracle::Oracle;
+use shipping_oracle::shipment::ShipmentClient;
+use shipping_oracle::shippo_budget::UsageReport;
+use shipping_oracle::state::{MemoryStore, StateStore};
+use shipping_oracle::testing::{FrozenClock, ORACLE_ADDRESS, blockfrost_utxos, shippo_track, test_config, tracking_number};
+use shipping_oracle::tracking_provider::{ProviderKind, TrackingProvider, TrackingProviders, parse_carrier_providers};
+
+const EASYPOST_KEY: &str = "EZTK_test_123";
+
+/// `Basic` authorization for `EASYPOST_KEY` with an empty password
+const EASYPOST_AUTH: &str = "Basic RVpUS190ZXN0XzEyMzo=";
+
+fn easypost_config(server: &MockServer) -> Config {
+    let mut config = test_config(&server.uri());
+    config.easypost_api_key = Some(EASYPOST_KEY.into());
+    config
+}
+
+fn tracker(carrier: &str, status: &str, messages: &[&str]) -> Value {
+    let details: Vec<Value> = messages
+        .iter()
+        .enumerate()
+        .map(|(i, message)| json!({ "message": message, "datetime": format!("2026-02-1{}T09:00:00Z", i) }))
+        .collect();
+
+    json!({ "carrier": carrier, "status": status, "status_detail": "arrived_at_facility", "tracking_details": details })
+}
+
+#[test]
+fn provider_kinds_and_carrier_mappings_parse() {
+    assert_eq!(" EasyPost ".parse::<ProviderKind>().unwrap(), ProviderKind::EasyPost);
+    assert_eq!("shippo".parse::<ProviderKind>().unwrap(), ProviderKind::Shippo);
+    assert_eq!(ProviderKind::EasyPost.to_string(), "easypost");
+    assert_eq!("AfterShip".parse::<ProviderKind>().unwrap(), ProviderKind::AfterShip);
+    assert_eq!("mock".parse::<ProviderKind>().unwrap(), ProviderKind::Mock);
+    let err = "17track".parse::<ProviderKind>().unwrap_err();
+    assert!(err.to_string().contains("expected shippo, easypost, aftership or mock, got '17track'"));
+
+    let carriers = parse_carrier_providers("FedEx=easypost, ups = easypost,,usps=shippo").unwrap();
+    assert_eq!(
+        carriers,
+        BTreeMap::from([
+            ("fedex".to_string(), ProviderKind::EasyPost),
+            ("ups".to_string(), ProviderKind::EasyPost),
+            ("usps".to_string(), ProviderKind::Shippo),
+        ])
+    );
+    assert!(parse_carrier_providers("").unwrap().is_empty());
+    assert!(parse_carrier_providers("fedex").unwrap_err().to_string().contains("expected carrier=provider, got 'fedex'"));
+    assert!(parse_carrier_providers("=easypost").is_err());
+    assert!(parse_carrier_providers("fedex=17track").is_err());
+}
+
+#[test]
+fn prefixes_win_over_carrier_mappings() {
+    let shippo = ShipmentClient::new(test_config("http://localhost")).unwrap();
+    let providers = TrackingProviders::new(Arc::new(shippo))
+        .with_carriers(BTreeMap::from([("fedex".to_string(), ProviderKind::EasyPost)]));
+
+    assert_eq!(providers.route("easypost:usps"), (ProviderKind::EasyPost, "usps"));
+    assert_eq!(providers.route("shippo:fedex"), (ProviderKind::Shippo, "fedex"));
+    assert_eq!(providers.route("FedEx"), (ProviderKind::EasyPost, "FedEx"));
+    assert_eq!(providers.route("usps"), (ProviderKind::Shippo, "usps"));
+    // Unknown prefixes are part of the carrier name
+    assert_eq!(providers.route("dhl:express"), (ProviderKind::Shippo, "dhl:express"));
+    assert_eq!(providers.provider("easypost:usps"), ProviderKind::EasyPost);
+}
+
+#[test]
+fn easypost_statuses_map_to_shippo_vocabulary() {
+    for (easypost, shippo) in [
+        ("pre_transit", "PRE_TRANSIT"),
+        ("in_transit", "TRANSIT"),
+        ("out_for_delivery", "TRANSIT"),
+        ("available_for_pickup", "TRANSIT"),
+        ("delivered", "DELIVERED"),
+        ("return_to_sender", "RETURNED"),
+        ("failure", "FAILURE"),
+        ("cancelled", "UNKNOWN"),
+        ("unknown", "UNKNOWN"),
+    ] {
+        assert_eq!(shippo_status(easypost), shippo, "{}", easypost);
+    }
+}
+
+#[tokio::test]
+async fn easypost_reads_the_tracker_of_the_datum_carrier() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/trackers"))
+        .and(query_param("tracking_code", "EZ1000000001"))
+        .and(header("authorization", EASYPOST_AUTH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "trackers": [
+                tracker("UPS", "in_transit", &["Departed"]),
+                tracker("FedEx", "delivered", &["Picked up", "Delivered, front door"]),
+            ]
+        })))
+        .mount(&server)
+        .await;
+
+    let easypost = EasyPostProvider::new(easypost_config(&server)).unwrap();
+    let status = easypost.fetch_status("fedex", "EZ1000000001").await.unwrap();
+    assert_eq!(status.status, CarrierStatus::Delivered);
+    assert_eq!(status.status_details, "Delivered, front door");
+    assert_eq!(status.status_date.as_deref(), Some("2026-02-11T09:00:00Z"));
+    assert_eq!(easypost.provider("fedex"), ProviderKind::EasyPost);
+
+    // Without a tracker of the datum carrier the first one is read
+    let status = easypost.fetch_status("usps", "EZ1000000001").await.unwrap();
+    assert_eq!((status.status.as_str(), status.status_details.as_str()), ("TRANSIT", "Departed"));
+}
+
+#[tokio::test]
+async fn easypost_errors_name_the_missing_tracker() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path("/trackers"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({ "trackers": [] })))
+        .mount(&server)
+        .await;
+
+    let easypost = EasyPostProvider::new(easypost_config(&server)).unwrap();
+    let err = easypost.fetch_status("fedex", "EZ404").await.unwrap_err();
+    assert!(err.to_string().contains("EasyPost has no tracker for fedex EZ404"));
+
+    let err = EasyPostProvider::new(test_config(&server.uri())).err().unwrap();
+    assert!(err.to_string().contains("EASYPOST_API_KEY"));
+
+    // Routing to a provider without credentials fails the shipment, not the registry
+    let shippo = ShipmentClient::new(test_config(&server.uri())).unwrap();
+    let err = TrackingProviders::new(Arc::new(shippo)).fetch_status("easypost:fedex", "EZ404").await.unwrap_err();
+    assert!(err.to_string().contains("No easypost tracking provider is configured"));
+}
+
+#[tokio::test]
+async fn mapped_carriers_are_tracked_through_easypost_outside_the_shippo_budget() {
+    let server = MockServer::start().await;
+    Mock::given(method("GET"))
+        .and(path(format!("/addresses/{}/utxos", ORACLE_ADDRESS)))
+        .respond_with(ResponseTemplate::new(200).set_body_json(blockfrost_utxos(2)))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path_regex("^/tracks/"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(shippo_track("usps", "TRK", "TRANSIT")))
+        .mount(&server)
+        .await;
+    Mock::given(method("GET"))
+        .and(path("/trackers"))
+        .and(header("authorization", EASYPOST_AUTH))
+        .respond_with(ResponseTemplate::new(200).set_body_json(json!({
+            "trackers": [tracker("USPS", "in_transit", &["Arrived at facility"])]
+        })))
+        .mount(&server)
+        .await;
+
+    let mut config = easypost_config(&server);
+    config.carrier_providers = parse_carrier_providers("usps=easypost").unwrap();
+    config.shippo_monthly_budget = Some(1);
+    let now: DateTime<Utc> = "2026-03-10T12:00:00Z".parse().unwrap();
+    let state: Arc<dyn StateStore> = Arc::new(MemoryStore::new());
+    let oracle = Oracle::builder().config(config).state(state.clone()).clock(FrozenClock::at(now)).build().unwrap();
+
+    let stats = oracle.run_once().await.unwrap().stats;
+    assert_eq!((stats.shipments, stats.skipped_budget), (2, 0));
+
+    let requests = server.received_requests().await.unwrap();
+    assert!(!requests.iter().any(|request| request.url.path().starts_with("/tracks/")));
+    let tracked: Vec<String> = requests
+        .iter()
+        .filter(|request| request.url.path() == "/trackers")
+        .filter_map(|request| request.url.query_pairs().find(|(key, _)| key == "tracking_code").map(|(_, value)| value.into_owned()))
+        .collect();
+    assert_eq!(tracked.len(), 2);
+    assert!(tracked.contains(&tracking_number(1)));
+
+    let usage = UsageReport::from_state(state.as_ref(), "2026-03", Some(1)).await.unwrap();
+    assert_eq!(usage.calls, 0);
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+
+use shipping_oracle::config::Config;
+use shipping_oracle::models::UtxoRef;
+use shipping_oracle::testing::{ORACLE_ADDRESS, VALIDATOR_SCRIPT_REF};
+
+#[test]
+fn utxo_refs_round_trip_through_display() {
+    let utxo_ref: UtxoRef = VALIDATOR_SCRIPT_REF.parse().unwrap();
+
+    assert_eq!(utxo_ref.index, 1);
+    assert_eq!(utxo_ref.tx_hash_hex(), VALIDATOR_SCRIPT_REF.split_once('#').unwrap().0);
+    assert_eq!(utxo_ref.to_string(), VALIDATOR_SCRIPT_REF);
+    assert_eq!(UtxoRef::from_hex(&utxo_ref.tx_hash_hex(), 1).unwrap(), utxo_ref);
+
+    let upper: UtxoRef = format!("{}#7", "AB".repeat(32)).parse().unwrap();
+    assert_eq!(upper.to_string(), format!("{}#7", "ab".repeat(32)));
+}
+
+#[test]
+fn malformed_utxo_refs_are_rejected() {
+    let err = "abcd#0".parse::<UtxoRef>().unwrap_err();
+    assert_eq!(err.to_string(), "tx hash must be 64 hex characters, got 'abcd'");
+    let err = format!("{}#0", "zz".repeat(32)).parse::<UtxoRef>().unwrap_err();
+    assert!(err.to_string().starts_with("tx hash must be 64 hex characters"));
+
+    let err = "ab".repeat(32).parse::<UtxoRef>().unwrap_err();
+    assert!(err.to_string().starts_with("expected TxHash#TxIx, got 'abab"));
+    let err = format!("{}#0#1", "ab".repeat(32)).parse::<UtxoRef>().unwrap_err();
+    assert!(err.to_string().starts_with("expected TxHash#TxIx"));
+
+    let err = format!("{}#x", "ab".repeat(32)).parse::<UtxoRef>().unwrap_err();
+    assert_eq!(err.to_string(), "output index must be a number, got 'x'");
+}
+
+#[test]
+fn utxo_refs_serialize_as_strings() {
+    let utxo_ref: UtxoRef = VALIDATOR_SCRIPT_REF.parse().unwrap();
+
+    let json = serde_json::to_value(utxo_ref).unwrap();
+    assert_eq!(json, VALIDATOR_SCRIPT_REF);
+    assert_eq!(serde_json::from_value::<UtxoRef>(json).unwrap(), utxo_ref);
+    assert!(serde_json::from_str::<UtxoRef>(r#""abcd#0""#).is_err());
+}
+
+#[test]
+fn validator_script_ref_is_validated_at_startup() {
+    let config = |script_ref: &str| {
+        let mut vars: HashMap<&str, String> = [
+            ("SHIPPO_API_KEY", "shippo_test_key"),
+            ("ORACLE_ADDRESS", ORACLE_ADDRESS),
+            ("ORACLE_PAYMENT_ADDRESS", ORACLE_ADDRESS),
+            ("BLOCKFROST_URL", "http://localhost"),
+            ("TRP_URL", "http://localhost"),
+            ("ORACLE_SK", "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60"),
+        ]
+        .into_iter()
+        .map(|(key, value)| (key, value.to_string()))
+        .collect();
+        vars.insert("VALIDATOR_SCRIPT_REF", script_ref.to_string());
+        Config::from_vars(|key| vars.get(key).cloned())
+    };
+
+    let upper = VALIDATOR_SCRIPT_REF.to_uppercase();
+    assert_eq!(config(&format!(" {} ", upper)).unwrap().validator_script_ref.to_string(), VALIDATOR_SCRIPT_REF);
+
+    let err = config("a6a57fe7#1").unwrap_err();
+    assert_eq!(format!("{:#}", err), "Invalid VALIDATOR_SCRIPT_REF: tx hash must be 64 hex characters, got 'a6a57fe7'");
+}
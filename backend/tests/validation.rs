@@ -0,0 +1,118 @@
+use pallas::ledger::addresses::Address;
+use serde::Deserialize;
+use tx3_sdk::trp::TxEnvelope;
+
+use shipping_oracle::models::{ShipmentDatum, TrackingNumber};
+use shipping_oracle::testing::{ORACLE_ADDRESS, ORACLE_PKH, OUTBOX_ADDRESS};
+use shipping_oracle::validation::{CloseExpectation, TxValidationFailed, same_address, validate_close_tx};
+
+const TRACKING_UTXO: &str = "a7a264ac1bef1f0da6312d16fb8f68b18d1a0d5010d8cb6a61790bf02a759301#0";
+
+#[derive(Deserialize)]
+struct EnvelopeFixture {
+    tx: String,
+    hash: String,
+}
+
+fn envelope(name: &str) -> TxEnvelope {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name);
+    let contents = std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("missing fixture {}: {}", path, e));
+    let fixture: EnvelopeFixture = serde_json::from_str(&contents).unwrap();
+
+    TxEnvelope {
+        tx: fixture.tx,
+        hash: fixture.hash,
+    }
+}
+
+/// The close requested for the fixtures: usps 9400111899223456789012 delivered
+fn expectation() -> CloseExpectation {
+    CloseExpectation {
+        utxo_ref: TRACKING_UTXO.to_string(),
+        outbox_address: Address::from_bech32(OUTBOX_ADDRESS).unwrap(),
+        payment_address: Address::from_bech32(ORACLE_ADDRESS).unwrap(),
+        datum: ShipmentDatum {
+            carrier: "usps".to_string(),
+            tracking_number: TrackingNumber::Plain("9400111899223456789012".to_string()),
+            status: "DELIVERED".to_string(),
+            timestamp: 1771090081,
+            oracle_pkh: ORACLE_PKH.to_string(),
+        },
+    }
+}
+
+fn violations(name: &str, expected: &CloseExpectation) -> Vec<String> {
+    let error = validate_close_tx(&envelope(name), expected).unwrap_err();
+    error
+        .downcast_ref::<TxValidationFailed>()
+        .unwrap_or_else(|| panic!("expected TxValidationFailed, got {:#}", error))
+        .violations
+        .clone()
+}
+
+#[test]
+fn requested_close_is_accepted() {
+    validate_close_tx(&envelope("close_shipment_envelope_valid.json"), &expectation()).unwrap();
+}
+
+#[test]
+fn wrong_outbox_is_rejected() {
+    let violations = violations("close_shipment_envelope_wrong_outbox.json", &expectation());
+
+    assert_eq!(violations.len(), 2, "{:?}", violations);
+    assert!(violations[0].starts_with("output #0: pays unknown address"), "{:?}", violations);
+    assert_eq!(violations[1], format!("outputs: nothing paid to outbox address {}", OUTBOX_ADDRESS));
+}
+
+#[test]
+fn wrong_status_datum_is_rejected() {
+    let violations = violations("close_shipment_envelope_wrong_status.json", &expectation());
+
+    assert_eq!(violations, vec!["output #0 datum status: expected DELIVERED, got TRANSIT".to_string()]);
+}
+
+#[test]
+fn other_tracking_utxo_is_rejected() {
+    let mut expected = expectation();
+    expected.utxo_ref = format!("{:064x}#1", 7);
+    expected.datum.timestamp += 1;
+
+    let violations = violations("close_shipment_envelope_valid.json", &expected);
+
+    assert_eq!(
+        violations,
+        vec![
+            format!("inputs: expected [{}], got [{}]", expected.utxo_ref, TRACKING_UTXO),
+            "output #0 datum timestamp: expected 1771090082, got 1771090081".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn second_outbox_output_is_rejected() {
+    let violations = violations("close_shipment_envelope_two_outbox.json", &expectation());
+
+    assert_eq!(
+        violations,
+        vec![format!("outputs: 2 outputs pay outbox address {}, expected exactly one", OUTBOX_ADDRESS)]
+    );
+}
+
+#[test]
+fn missing_tracking_input_is_rejected() {
+    let violations = violations("close_shipment_envelope_other_input.json", &expectation());
+
+    assert_eq!(
+        violations,
+        vec![format!("inputs: expected [{}], got [{:064x}#1]", TRACKING_UTXO, 7)]
+    );
+}
+
+#[test]
+fn addresses_compare_by_bytes() {
+    let outbox = Address::from_bech32(OUTBOX_ADDRESS).unwrap();
+    let oracle = Address::from_bech32(ORACLE_ADDRESS).unwrap();
+
+    assert!(same_address(&outbox, &Address::from_bytes(&outbox.to_vec()).unwrap()));
+    assert!(!same_address(&outbox, &oracle));
+}